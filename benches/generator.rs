@@ -0,0 +1,104 @@
+//! Criterion benchmarks for the audio callback's hot path: the noise
+//! generators every source draws from, the EQ, and a whole callback's
+//! worth of rendering. `cargo bench` runs them; `whitenoise bench` gives a
+//! quicker table without a toolchain.
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use rand::SeedableRng;
+use rand::prelude::{RngExt, SmallRng};
+use whitenoise::audio::{Frame, Generator, GraphicEq, NoiseGenerator, StreamOptions};
+use whitenoise::settings::{AudioSettings, BandFilters, NoiseRng, SoundStyle, SourceMix, bands};
+
+const SAMPLE_RATE: f32 = 48_000.0;
+// One block at the engine's default size.
+const FRAMES: usize = 256;
+
+fn noise_generators(c: &mut Criterion) {
+    let mut group = c.benchmark_group("noise generator");
+    group.throughput(Throughput::Elements(FRAMES as u64));
+    for (name, kind) in [
+        ("small", NoiseRng::Small),
+        ("xoshiro", NoiseRng::Xoshiro),
+        ("chacha", NoiseRng::Chacha),
+    ] {
+        let mut rng = NoiseGenerator::seeded(kind, &mut SmallRng::seed_from_u64(1));
+        let mut samples = [0.0_f32; FRAMES];
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                for sample in &mut samples {
+                    *sample = rng.random::<f32>() * 2.0 - 1.0;
+                }
+                black_box(&samples);
+            });
+        });
+    }
+    group.finish();
+}
+
+fn eq(c: &mut Criterion) {
+    let mut rng = SmallRng::seed_from_u64(2);
+    let input: Vec<Frame> = (0..FRAMES)
+        .map(|_| {
+            [
+                rng.random::<f32>() * 0.5 - 0.25,
+                rng.random::<f32>() * 0.5 - 0.25,
+            ]
+        })
+        .collect();
+    let mut group = c.benchmark_group("eq");
+    group.throughput(Throughput::Elements(FRAMES as u64));
+    for (name, filters) in [
+        ("crossover", BandFilters::Crossover),
+        ("peaking", BandFilters::Peaking),
+    ] {
+        let mut eq = GraphicEq::with_bands(SAMPLE_RATE, AudioSettings::default(), bands(), filters);
+        let mut block = input.clone();
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                block.copy_from_slice(&input);
+                eq.process_block(&mut block);
+                black_box(&block);
+            });
+        });
+    }
+    group.finish();
+}
+
+fn callback(c: &mut Criterion) {
+    let all = SoundStyle::ALL
+        .into_iter()
+        .fold(SourceMix::default(), |mut mix, style| {
+            mix.set_level(style, 1.0);
+            mix
+        });
+    let mut group = c.benchmark_group("callback");
+    group.throughput(Throughput::Elements(FRAMES as u64));
+    for (name, mix) in [
+        ("white", SourceMix::solo(SoundStyle::White)),
+        ("all sources", all),
+    ] {
+        let mut settings = AudioSettings {
+            volume: 0.5,
+            ..AudioSettings::default()
+        };
+        settings.set_mix(mix);
+        let options = StreamOptions {
+            seed: Some(3),
+            ..StreamOptions::default()
+        };
+        let mut generator = Generator::new(SAMPLE_RATE, 2, settings, options).unwrap();
+        let mut buffer = [0.0_f32; 2 * FRAMES];
+        group.bench_function(BenchmarkId::new("stereo", name), |b| {
+            b.iter(|| {
+                generator.fill_buffer(&mut buffer);
+                black_box(&buffer);
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, noise_generators, eq, callback);
+criterion_main!(benches);
@@ -0,0 +1,348 @@
+//! Ambient auto-level: with `--ambient-level` or an `[ambient]` table, a
+//! microphone listens to the room and the sound follows its noise, rising
+//! as the ventilation or the chatter picks up and settling back as the
+//! room quiets, to stay `margin_db` above it. The microphone hears the
+//! player too, so how loud the player is at the microphone is learned from
+//! the quietest moments, when the room adds least, and taken off what it
+//! hears. The gain moves a fraction of a dB per second within its clamps,
+//! on top of the listener's volume, and holds while the sound is paused,
+//! muted, ducked, or fading.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::{Data, Device, FromSample, I24, Sample, SampleFormat, SizedSample, U24};
+use serde::{Deserialize, Serialize};
+
+use crate::audio::AudioMonitor;
+use crate::settings::SharedSettings;
+
+pub const DEFAULT_MARGIN_DB: f32 = 3.0;
+pub const DEFAULT_MAX_BOOST_DB: f32 = 10.0;
+pub const DEFAULT_MAX_CUT_DB: f32 = 20.0;
+pub const DEFAULT_WINDOW_SECONDS: u32 = 30;
+/// The furthest either clamp reaches.
+pub const MAX_AMBIENT_GAIN_DB: f32 = 40.0;
+const MAX_WINDOW_SECONDS: u32 = 600;
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+// How fast the gain may move, in dB per second: ten dB takes most of a
+// minute, so a door slamming or a cough does not pump the sound.
+const MAX_SLEW_DB: f32 = 0.2;
+// The player's level at the microphone is the quietest seen over the last
+// few seconds, and may creep up this far per second in case the system
+// volume went up or the microphone moved closer. A room that never quiets
+// can push it up no faster than this.
+const COUPLING_SECONDS: usize = 5;
+const COUPLING_RELAX_DB: f32 = 1.0 / 3600.0;
+// Output quieter than this teaches nothing about the coupling.
+const SILENT_DB: f32 = -80.0;
+// The room is taken as at least this far under what the microphone hears:
+// closer to the player's own level than that, the two cannot be told apart.
+const UNRESOLVED_DB: f32 = 13.0;
+
+/// The `[ambient]` table: the microphone to listen with, by name, how far
+/// above the room the sound stays, the most it may rise or fall from the
+/// listener's volume, and how many seconds of the room count.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AmbientLevel {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input: Option<String>,
+    pub margin_db: f32,
+    pub max_boost_db: f32,
+    pub max_cut_db: f32,
+    pub window_seconds: u32,
+}
+
+impl Default for AmbientLevel {
+    fn default() -> Self {
+        Self {
+            input: None,
+            margin_db: DEFAULT_MARGIN_DB,
+            max_boost_db: DEFAULT_MAX_BOOST_DB,
+            max_cut_db: DEFAULT_MAX_CUT_DB,
+            window_seconds: DEFAULT_WINDOW_SECONDS,
+        }
+    }
+}
+
+impl AmbientLevel {
+    fn sanitize(mut self) -> Self {
+        let clamp = |value: f32, low: f32, default: f32| {
+            if value.is_finite() {
+                value.clamp(low, MAX_AMBIENT_GAIN_DB)
+            } else {
+                default
+            }
+        };
+        self.margin_db = clamp(self.margin_db, -MAX_AMBIENT_GAIN_DB, DEFAULT_MARGIN_DB);
+        self.max_boost_db = clamp(self.max_boost_db, 0.0, DEFAULT_MAX_BOOST_DB);
+        self.max_cut_db = clamp(self.max_cut_db, 0.0, DEFAULT_MAX_CUT_DB);
+        self.window_seconds = self.window_seconds.clamp(1, MAX_WINDOW_SECONDS);
+        self
+    }
+}
+
+/// The gain's state between readings, one a second.
+#[derive(Debug)]
+struct AutoLevel {
+    level: AmbientLevel,
+    // Microphone power and output power, mean squares, newest last.
+    window: VecDeque<(f32, f32)>,
+    // How much quieter the player is at the microphone than at its output.
+    coupling_db: Option<f32>,
+    gain_db: f32,
+}
+
+impl AutoLevel {
+    fn new(level: AmbientLevel) -> Self {
+        Self {
+            level: level.sanitize(),
+            window: VecDeque::new(),
+            coupling_db: None,
+            gain_db: 0.0,
+        }
+    }
+
+    /// Takes a second's reading of the microphone's power and the output's
+    /// RMS, and returns the gain to play at. The output is as played, with
+    /// the gain already in it.
+    fn update(&mut self, microphone: f32, output_rms: f32, hold: bool, seconds: f32) -> f32 {
+        self.window.push_back((microphone, output_rms * output_rms));
+        while self.window.len() > self.level.window_seconds as usize {
+            self.window.pop_front();
+        }
+        let output_db = power_db(output_rms * output_rms);
+        if output_db <= SILENT_DB {
+            return self.gain_db;
+        }
+        let recent = self.window.len().min(COUPLING_SECONDS);
+        let (heard, played) = mean(self.window.iter().rev().take(recent));
+        let coupling = power_db(heard) - power_db(played);
+        let coupling = self.coupling_db.map_or(coupling, |learned| {
+            coupling.min(learned + COUPLING_RELAX_DB * seconds)
+        });
+        self.coupling_db = Some(coupling);
+        if hold {
+            return self.gain_db;
+        }
+
+        let (heard, played) = mean(self.window.iter());
+        let own = played * db_power(coupling);
+        let room = (heard - own).max(heard * db_power(-UNRESOLVED_DB));
+        let wanted_db = power_db(room) + self.level.margin_db - coupling;
+        let unboosted_db = output_db - self.gain_db;
+        let target =
+            (wanted_db - unboosted_db).clamp(-self.level.max_cut_db, self.level.max_boost_db);
+        let step = MAX_SLEW_DB * seconds;
+        self.gain_db += (target - self.gain_db).clamp(-step, step);
+        self.gain_db
+    }
+}
+
+fn mean<'a>(readings: impl Iterator<Item = &'a (f32, f32)>) -> (f32, f32) {
+    let (count, heard, played) = readings.fold((0, 0.0, 0.0), |(count, heard, played), reading| {
+        (count + 1, heard + reading.0, played + reading.1)
+    });
+    let count = f32::max(count as f32, 1.0);
+    (heard / count, played / count)
+}
+
+fn power_db(power: f32) -> f32 {
+    10.0 * power.max(1e-12).log10()
+}
+
+fn db_power(db: f32) -> f32 {
+    10_f32.powf(db / 10.0)
+}
+
+/// Sum of squares and sample count heard since the last reading.
+#[derive(Debug, Default)]
+struct Heard {
+    power: f64,
+    samples: u64,
+}
+
+/// Starts listening on `microphone` and following the room until `running`
+/// clears. Fails when the microphone's stream cannot be opened; a stream
+/// that breaks later drops the gain back to the listener's volume.
+pub fn watch(
+    level: AmbientLevel,
+    microphone: &Device,
+    settings: Arc<SharedSettings>,
+    running: Arc<AtomicBool>,
+    monitor: Arc<AudioMonitor>,
+) -> Result<()> {
+    let config = microphone
+        .default_input_config()
+        .context("failed to query the microphone's default format")?;
+    let input_config = config.config();
+    let channels = usize::from(input_config.channels).max(1);
+    let heard = Arc::new(Mutex::new(Heard::default()));
+    let failed = Arc::new(AtomicBool::new(false));
+    let stream = {
+        let heard = Arc::clone(&heard);
+        let failed = Arc::clone(&failed);
+        microphone
+            .build_input_stream_raw(
+                input_config,
+                config.sample_format(),
+                move |data, _| {
+                    // The watcher only holds the lock to swap the sums out.
+                    if let Ok(mut heard) = heard.try_lock() {
+                        listen(data, channels, &mut heard);
+                    }
+                },
+                move |_| failed.store(true, Ordering::Relaxed),
+                None,
+            )
+            .context("failed to open the microphone's stream")?
+    };
+    stream.play().context("failed to start the microphone")?;
+    std::thread::spawn(move || {
+        let _stream = stream;
+        let mut auto_level = AutoLevel::new(level);
+        let mut last = Instant::now();
+        while running.load(Ordering::Relaxed) {
+            std::thread::sleep(CHECK_INTERVAL);
+            if failed.load(Ordering::Relaxed) {
+                settings
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .ambient_gain_db = None;
+                return;
+            }
+            let now = Instant::now();
+            let seconds = now.saturating_duration_since(last).as_secs_f32();
+            last = now;
+            let Heard { power, samples } = std::mem::take(&mut *lock(&heard));
+            if samples == 0 {
+                continue;
+            }
+            let microphone = (power / samples as f64) as f32;
+            let mut settings = settings
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            let hold = settings.paused
+                || settings.muted
+                || settings.duck_db.is_some()
+                || settings.call_duck_db.is_some()
+                || settings.schedule_fade.is_some()
+                || settings.sleep_timer.is_some_and(|timer| timer.fading(now));
+            let gain = auto_level.update(microphone, monitor.rms(), hold, seconds);
+            settings.ambient_gain_db = Some(gain);
+        }
+    });
+    Ok(())
+}
+
+fn listen(data: &Data, channels: usize, heard: &mut Heard) {
+    match data.sample_format() {
+        SampleFormat::I8 => accumulate::<i8>(data, channels, heard),
+        SampleFormat::I16 => accumulate::<i16>(data, channels, heard),
+        SampleFormat::I24 => accumulate::<I24>(data, channels, heard),
+        SampleFormat::I32 => accumulate::<i32>(data, channels, heard),
+        SampleFormat::I64 => accumulate::<i64>(data, channels, heard),
+        SampleFormat::U8 => accumulate::<u8>(data, channels, heard),
+        SampleFormat::U16 => accumulate::<u16>(data, channels, heard),
+        SampleFormat::U24 => accumulate::<U24>(data, channels, heard),
+        SampleFormat::U32 => accumulate::<u32>(data, channels, heard),
+        SampleFormat::U64 => accumulate::<u64>(data, channels, heard),
+        SampleFormat::F32 => accumulate::<f32>(data, channels, heard),
+        SampleFormat::F64 => accumulate::<f64>(data, channels, heard),
+        // Anything else is heard as nothing, and the gain holds.
+        _ => {}
+    }
+}
+
+/// The first channel only, as `calibrate` records it.
+fn accumulate<T>(data: &Data, channels: usize, heard: &mut Heard)
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let Some(samples) = data.as_slice::<T>() else {
+        return;
+    };
+    for sample in samples.iter().step_by(channels) {
+        let sample = f64::from(f32::from_sample(*sample));
+        heard.power += sample * sample;
+        heard.samples += 1;
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A room whose microphone hears the player 20 dB down from its output,
+    /// which plays at -20 dB RMS before the auto-level's gain.
+    fn run(auto_level: &mut AutoLevel, room_db: f32, seconds: usize, hold: bool) -> f32 {
+        let mut gain = auto_level.gain_db;
+        for _ in 0..seconds {
+            let output_rms = 10_f32.powf((-20.0 + gain) / 20.0);
+            let heard = db_power(room_db) + output_rms * output_rms * db_power(-20.0);
+            gain = auto_level.update(heard, output_rms, hold, 1.0);
+        }
+        gain
+    }
+
+    #[test]
+    fn the_sound_follows_the_room_slowly_within_its_clamps() {
+        let mut auto_level = AutoLevel::new(AmbientLevel::default());
+        // A quiet room teaches the coupling and lets the sound fall.
+        let quiet = run(&mut auto_level, -70.0, 10, false);
+        assert!((auto_level.coupling_db.unwrap() + 20.0).abs() < 0.1);
+        assert!((-2.1..-1.9).contains(&quiet), "{quiet}");
+        let quiet = run(&mut auto_level, -70.0, 300, false);
+        assert!((quiet + DEFAULT_MAX_CUT_DB).abs() < 0.01, "{quiet}");
+
+        // The ventilation comes on at -40 dB at the microphone: the sound
+        // rises until it is 3 dB above it there, -37 + 20 = -17 dB out,
+        // less what the learned coupling has crept up since it was quiet.
+        let busy = run(&mut auto_level, -40.0, 30, false);
+        assert!(busy < -13.0, "{busy}");
+        let busy = run(&mut auto_level, -40.0, 300, false);
+        assert!((busy - 3.0).abs() < 1.0, "{busy}");
+
+        // Louder than the boost allows stops at the clamp.
+        let loud = run(&mut auto_level, -20.0, 300, false);
+        assert!((loud - DEFAULT_MAX_BOOST_DB).abs() < 0.01, "{loud}");
+    }
+
+    #[test]
+    fn the_gain_holds_while_the_sound_is_held_or_silent() {
+        let mut auto_level = AutoLevel::new(AmbientLevel {
+            margin_db: 6.0,
+            ..AmbientLevel::default()
+        });
+        run(&mut auto_level, -70.0, 10, false);
+        let held = auto_level.gain_db;
+        assert_eq!(run(&mut auto_level, -30.0, 60, true), held);
+        assert_eq!(auto_level.update(db_power(-30.0), 0.0, false, 1.0), held);
+        assert!(run(&mut auto_level, -30.0, 60, false) > held + 10.0);
+
+        let wild = AmbientLevel {
+            margin_db: f32::NAN,
+            max_boost_db: 99.0,
+            max_cut_db: -5.0,
+            window_seconds: 0,
+            ..AmbientLevel::default()
+        }
+        .sanitize();
+        assert_eq!(wild.margin_db, DEFAULT_MARGIN_DB);
+        assert_eq!(wild.max_boost_db, MAX_AMBIENT_GAIN_DB);
+        assert_eq!(wild.max_cut_db, 0.0);
+        assert_eq!(wild.window_seconds, 1);
+    }
+}
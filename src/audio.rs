@@ -1,421 +1,635 @@
-use std::f32::consts::{FRAC_PI_2, PI};
+use std::convert::Infallible;
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI, SQRT_2};
 use std::io::Cursor;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
 use anyhow::{Context, Result, bail, ensure};
 use cpal::traits::DeviceTrait;
-use cpal::{Device, FromSample, I24, Sample, SampleFormat, SizedSample, Stream, StreamConfig, U24};
-use rand::prelude::{RngExt, SmallRng};
-
-use crate::settings::{AudioSettings, FREQUENCY_BANDS, SoundStyle, slider_to_db};
-
-const RAIN_WAV_DATA: &[u8] = include_bytes!("../assets/rain_loop.wav");
-const WHITE_NOISE_GAIN: f32 = 0.28;
-// Matches the white source RMS (0.28 / sqrt(3)) so switching styles keeps a
-// comparable signal level.
-const COLORED_NOISE_TARGET_RMS: f32 = 0.16;
-// RMS of the uniform [-1, 1) white input that drives the colored sources.
-const UNIFORM_INPUT_RMS: f64 = 0.577_350_269_189_625_8;
-const PINK_LADDER_START_HZ: f64 = 8.0;
-const PINK_LADDER_RATIO: f64 = 4.0;
-const BROWN_LEAK_HZ: f64 = 8.0;
+use cpal::{
+    Device, ErrorKind, FromSample, I24, Sample, SampleFormat, SizedSample, Stream, StreamConfig,
+    U24,
+};
+use rand::prelude::{RngExt, SmallRng, StdRng};
+use rand::rngs::{SysRng, Xoshiro128PlusPlus};
+use rand::{Rng, SeedableRng, TryRng};
+
+use crate::clock;
+use crate::contour::contour_db;
+use crate::dsp::{
+    BandSplitter, BeatTone, Biquad, BlueNoise, BrownNoise, Coefficients, Crossfeed, Cutoff,
+    LevelCap, LinearRamp, LoudnessMeter, NotchFilter, PeakLimiter, PinkNoise, Shape, VioletNoise,
+};
+use crate::mirror::MirrorTap;
+use crate::record::Tap;
+use crate::scene::{LayerShape, Modulation};
+use crate::settings::{
+    AudioSettings, BandFilters, BandOverlay, DEFAULT_LOOP_CROSSFADE_SECONDS, DriftSettings,
+    FREQUENCY_BANDS, FrequencyBand, HighPass, LoopSettings, MAX_BANDS, MAX_LOOP_CROSSFADE_SECONDS,
+    NoiseRng, Notch, SHUFFLE_MAX_SECONDS, SharedSettings, SoundStyle, TILT_BANDS, ToneMode,
+    ToneSettings, band_filters, bands, noise_rng, slider_to_db,
+};
+use crate::soundscape::{Fan, Fire, Ocean, Wind};
+
+/// The rain loop built into the binary, a WAV file.
+pub const RAIN_WAV_DATA: &[u8] = include_bytes!("../assets/rain_loop.wav");
+/// White noise's peak amplitude at full level.
+pub const WHITE_NOISE_GAIN: f32 = 0.28;
+/// The colored noises' RMS at full level. It matches the white source's
+/// RMS (0.28 / sqrt(3)) so switching styles keeps a comparable level.
+pub const COLORED_NOISE_TARGET_RMS: f32 = 0.16;
 const RAIN_TARGET_RMS: f32 = 0.12;
+// The embedded rain's K-weighted loudness after its own normalization. User
+// samples are brought here, so a quiet field recording and a hot one both sit
+// level with the rain and the synthesized sources it was matched to.
+const SAMPLE_TARGET_LUFS: f32 = -15.5;
+// Beyond this a sample is more likely near-silent or broken than merely quiet.
+const MAX_SAMPLE_GAIN_DB: f32 = 30.0;
+// ReplayGain 2.0's reference loudness: a track gain is this minus the
+// track's loudness.
+pub const REPLAY_GAIN_REFERENCE_LUFS: f32 = -18.0;
+// A track gain tag past this is taken as damaged rather than believed.
+const MAX_TAG_GAIN_DB: f32 = 60.0;
 const RAIN_PEAK_THRESHOLD: f32 = 0.28;
 const RAIN_PEAK_RATIO: f32 = 4.0;
+// A loop's splice is looked for among this many rising zero crossings at
+// each end, within an eighth of the recording and at most this long.
+const LOOP_SEARCH_SECONDS: f32 = 1.0;
+const LOOP_CANDIDATES: usize = 64;
+// Each candidate splice is judged on the waveform this long either side of
+// it, and on the level over this long, where the fade joins.
+const SPLICE_WAVEFORM_SECONDS: f32 = 0.004;
+const SPLICE_LEVEL_SECONDS: f32 = 0.5;
+// A crossfade's two stretches are compared at most at this many points.
+const FADE_CORRELATION_POINTS: usize = 2_048;
 const PARAMETER_RAMP_SECONDS: f32 = 0.05;
 const STYLE_CROSSFADE_SECONDS: f32 = 0.20;
-const EQ_SMOOTHING_SECONDS: f32 = 0.03;
-const EQ_GAIN_SNAP_DB: f32 = 0.01;
-
-// A deliberately gentle convenience curve. Equal-loudness contours depend on
-// playback level, so presenting fixed gains as "Fletcher-Munson correction"
-// would be misleading.
-const LISTENING_CONTOUR_DB: [f32; FREQUENCY_BANDS.len()] =
-    [4.0, 2.5, 1.0, 0.0, -0.5, -1.0, 0.0, 1.0];
-
-#[derive(Clone, Copy, Debug)]
-struct Coefficients {
-    b0: f32,
-    b1: f32,
-    b2: f32,
-    a1: f32,
-    a2: f32,
-}
-
-impl Coefficients {
-    const IDENTITY: Self = Self {
-        b0: 1.0,
-        b1: 0.0,
-        b2: 0.0,
-        a1: 0.0,
-        a2: 0.0,
-    };
-
-    fn peaking(sample_rate: f32, frequency: f32, q: f32, gain_db: f32) -> Self {
-        if gain_db.abs() < f32::EPSILON || frequency >= sample_rate * 0.48 {
-            return Self::IDENTITY;
-        }
+// A tone at 100% has the RMS of a colored source at full level.
+const TONE_FULL_AMPLITUDE: f32 = COLORED_NOISE_TARGET_RMS * SQRT_2;
+// Room tone at 100% sits 20 dB under a colored source at full level; it is
+// there to keep the output from ever going fully silent, not to be heard.
+const ROOM_TONE_FULL_RMS: f32 = COLORED_NOISE_TARGET_RMS * 0.1;
+// Pure tones make onsets obvious, so they fade in and out more slowly than
+// the noise parameters.
+const TONE_FADE_SECONDS: f32 = 0.5;
+// The bypass match never moves the raw mix more than 24 dB, so a mix that
+// the EQ has nearly silenced does not turn the raw one into a blast.
+const BYPASS_MATCH_LIMIT: f32 = 16.0;
+// The output meter's RMS integrates over about the time a VU meter takes.
+const OUTPUT_RMS_SECONDS: f32 = 0.3;
+// A night ceiling eases in and out over this long, so the change at the
+// window's edge sounds like a slow volume move rather than a step.
+const NIGHT_CEILING_SECONDS: f32 = 3.0;
+// A duck for other playback glides down and back up over this long.
+const DUCK_SECONDS: f32 = 1.0;
+// A scene's timed layer comes in and goes out over this long, so it arrives
+// like weather rather than being switched on.
+const LAYER_GATE_SECONDS: f32 = 8.0;
+// The drift at full depth: how far each band's gain, the balance, and the
+// loops' speed may wander either way.
+const DRIFT_BAND_DB: f32 = 3.0;
+const DRIFT_BALANCE_DB: f32 = 1.5;
+const DRIFT_SPEED: f32 = 0.01;
+// The drift's walks step this often; the EQ's glide and the balance ramp
+// smooth between steps.
+const DRIFT_STEP_SECONDS: f32 = 0.25;
+// Turning the drift on or off, or changing its depth, eases over this long.
+const DRIFT_FADE_SECONDS: f32 = 5.0;
+/// Frames the engine renders at a time unless told otherwise.
+pub const DEFAULT_BLOCK_SIZE: usize = 256;
+// Frames the engine takes through each stage before moving on to the next,
+// so the EQ filters a run at a time; small enough to sit on the stack.
+const RENDER_RUN: usize = 64;
 
-        let omega = 2.0 * PI * frequency / sample_rate;
-        let (sin_omega, cos_omega) = omega.sin_cos();
-        let alpha = sin_omega / (2.0 * q.max(0.1));
-        let amplitude = 10.0_f32.powf(gain_db / 40.0);
+/// Left and right output samples.
+pub type Frame = [f32; 2];
 
-        let b0 = 1.0 + alpha * amplitude;
-        let b1 = -2.0 * cos_omega;
-        let b2 = 1.0 - alpha * amplitude;
-        let a0 = 1.0 + alpha / amplitude;
-        let a1 = -2.0 * cos_omega;
-        let a2 = 1.0 - alpha / amplitude;
+// The deepest cut band_gain_db allows, which a muted or solo-silenced band
+// plays at whatever its slider says.
+const SILENCED_BAND_DB: f32 = -18.0;
 
-        Self {
-            b0: b0 / a0,
-            b1: b1 / a0,
-            b2: b2 / a0,
-            a1: a1 / a0,
-            a2: a2 / a0,
-        }
-    }
+/// Band `index`'s gain in dB as the EQ plays it, after the band mutes and
+/// solos.
+pub fn band_gain_db(settings: AudioSettings, index: usize) -> f32 {
+    gain_for_band(settings, index, &bands()[index])
 }
 
-#[derive(Debug)]
-struct Biquad {
-    sample_rate: f32,
-    frequency: f32,
-    q: f32,
-    current_gain_db: f32,
-    target_gain_db: f32,
-    smoothing: f32,
-    coefficients: Coefficients,
-    x1: f32,
-    x2: f32,
-    y1: f32,
-    y2: f32,
-}
-
-impl Biquad {
-    fn new(sample_rate: f32, frequency: f32, q: f32, gain_db: f32) -> Self {
-        let smoothing = 1.0 - (-1.0 / (EQ_SMOOTHING_SECONDS * sample_rate)).exp();
-        Self {
-            sample_rate,
-            frequency,
-            q,
-            current_gain_db: gain_db,
-            target_gain_db: gain_db,
-            smoothing,
-            coefficients: Coefficients::peaking(sample_rate, frequency, q, gain_db),
-            x1: 0.0,
-            x2: 0.0,
-            y1: 0.0,
-            y2: 0.0,
-        }
+/// `band_gain_db` for slot `index` of a layout whose band there is `band`.
+fn gain_for_band(settings: AudioSettings, index: usize, band: &FrequencyBand) -> f32 {
+    if settings.band_overlay.silences(index) {
+        return SILENCED_BAND_DB;
     }
+    let contour = settings
+        .contour_phon()
+        .map_or(0.0, |phon| contour_db(band.center_frequency(), phon));
+    (slider_to_db(settings.frequency_bands[index]) + contour + settings.room_correction_db[index])
+        .clamp(SILENCED_BAND_DB, 12.0)
+}
 
-    fn set_target_gain(&mut self, gain_db: f32) {
-        self.target_gain_db = gain_db;
+/// `gain_for_band` as a linear gain for a crossover band, where a muted or
+/// solo-silenced band can go fully silent.
+fn crossover_gain(settings: AudioSettings, index: usize, band: &FrequencyBand) -> f32 {
+    if settings.band_overlay.silences(index) {
+        return 0.0;
     }
+    10.0_f32.powf(gain_for_band(settings, index, band) / 20.0)
+}
 
-    fn process(&mut self, input: f32) -> f32 {
-        // Smooth in the gain domain and rebuild the coefficients from the
-        // smoothed gain. Interpolating raw biquad coefficients is unstable for
-        // the near-unit-circle poles of the low bands; every filter produced
-        // this way is a genuine peaking filter and therefore stable.
-        if self.current_gain_db != self.target_gain_db {
-            self.current_gain_db += (self.target_gain_db - self.current_gain_db) * self.smoothing;
-            if (self.current_gain_db - self.target_gain_db).abs() < EQ_GAIN_SNAP_DB {
-                self.current_gain_db = self.target_gain_db;
-            }
-            self.coefficients = Coefficients::peaking(
-                self.sample_rate,
-                self.frequency,
-                self.q,
-                self.current_gain_db,
-            );
-        }
-
-        let c = self.coefficients;
-        let output =
-            c.b0 * input + c.b1 * self.x1 + c.b2 * self.x2 - c.a1 * self.y1 - c.a2 * self.y2;
-
-        self.x2 = self.x1;
-        self.x1 = input;
-        self.y2 = self.y1;
-        self.y1 = output;
-
-        if output.is_finite() {
-            output
-        } else {
-            // A non-finite value in the feedback state would poison the band
-            // forever; flush it so the filter recovers on the next sample.
-            self.x1 = 0.0;
-            self.x2 = 0.0;
-            self.y1 = 0.0;
-            self.y2 = 0.0;
-            0.0
+/// Where a crossover splits `bands`: between each pair of neighbors, at the
+/// geometric midpoint of the gap or overlap between them, kept between
+/// their centers and below 0.45 times the sample rate. None when the bands
+/// are not in rising order, which only serial peaking filters can follow.
+pub fn crossovers_hz(bands: &[FrequencyBand], sample_rate: f32) -> Option<Vec<f32>> {
+    let ceiling = sample_rate * 0.45;
+    let mut crossovers: Vec<f32> = Vec::with_capacity(bands.len().saturating_sub(1));
+    for pair in bands.windows(2) {
+        let (lower, upper) = (pair[0].center_frequency(), pair[1].center_frequency());
+        if lower >= upper {
+            return None;
         }
+        let floor = crossovers.last().copied().unwrap_or(0.0);
+        crossovers.push(
+            (pair[0].max_freq * pair[1].min_freq)
+                .sqrt()
+                .clamp(lower, upper)
+                .min(ceiling)
+                .max(floor),
+        );
     }
+    Some(crossovers)
 }
 
-fn band_gain_db(settings: AudioSettings, index: usize) -> f32 {
-    let contour = if settings.listening_contour {
-        LISTENING_CONTOUR_DB[index]
-    } else {
-        0.0
-    };
-    (slider_to_db(settings.frequency_bands[index]) + contour).clamp(-18.0, 12.0)
+/// A band's tilt as a low shelf and a high shelf on its center: half the
+/// tilt is cut below the center and half added above, so the center itself
+/// is untouched and the band's edges sit close to the full tilt apart. Past
+/// the edges the shelves level off at half the tilt.
+fn tilt_gains_db(tilt_db: f32) -> [f32; 2] {
+    [-tilt_db / 2.0, tilt_db / 2.0]
 }
 
-#[derive(Debug)]
-struct GraphicEq {
-    filters: [Biquad; FREQUENCY_BANDS.len()],
-    last_values: [f32; FREQUENCY_BANDS.len()],
-    last_contour: bool,
+/// How far a tilted band's upper edge sits above its lower edge, in dB, for
+/// `TILT_BANDS[tilt]`.
+pub fn tilt_edge_difference_db(tilt: usize, tilt_db: f32) -> f32 {
+    let band = &FREQUENCY_BANDS[TILT_BANDS[tilt]];
+    let [low, high] = tilt_gains_db(tilt_db);
+    let shelves = [
+        Coefficients::shelf(
+            Shape::LowShelf,
+            ESTIMATE_SAMPLE_RATE,
+            band.center_frequency(),
+            low,
+        ),
+        Coefficients::shelf(
+            Shape::HighShelf,
+            ESTIMATE_SAMPLE_RATE,
+            band.center_frequency(),
+            high,
+        ),
+    ];
+    let response = |frequency: f32| -> f64 {
+        shelves
+            .iter()
+            .map(|shelf| shelf.magnitude_db(ESTIMATE_SAMPLE_RATE, frequency))
+            .sum()
+    };
+    (response(band.max_freq) - response(band.min_freq)) as f32
 }
 
-impl GraphicEq {
-    fn new(sample_rate: f32, settings: AudioSettings) -> Self {
-        Self {
-            filters: std::array::from_fn(|index| {
-                let band = FREQUENCY_BANDS[index];
-                Biquad::new(
-                    sample_rate,
-                    band.center_frequency(),
-                    band.q(),
-                    band_gain_db(settings, index),
-                )
-            }),
-            last_values: settings.frequency_bands,
-            last_contour: settings.listening_contour,
-        }
-    }
-
-    fn update(&mut self, settings: AudioSettings) {
-        if self.last_values == settings.frequency_bands
-            && self.last_contour == settings.listening_contour
-        {
-            return;
-        }
-
-        for (index, filter) in self.filters.iter_mut().enumerate() {
-            filter.set_target_gain(band_gain_db(settings, index));
-        }
+// UI estimates use a nominal rate: below 20 kHz the band responses barely
+// depend on the device rate, and the UI never sees the real one.
+const ESTIMATE_SAMPLE_RATE: f32 = 48_000.0;
+const ESTIMATE_POINTS: usize = 256;
 
-        self.last_values = settings.frequency_bands;
-        self.last_contour = settings.listening_contour;
+/// Each band's amplitude through a crossover at `crossovers` at
+/// `frequency`. The bands leave it in phase, so the output amplitude is the
+/// gain-weighted sum of these.
+fn crossover_responses(crossovers: &[f32], frequency: f32) -> Vec<f64> {
+    let half_db = |shape, crossover| {
+        BandSplitter::half_response_db(shape, ESTIMATE_SAMPLE_RATE, crossover, frequency)
+    };
+    let mut responses = Vec::with_capacity(crossovers.len() + 1);
+    let mut above_db = 0.0;
+    for &crossover in crossovers {
+        let low_db = half_db(Shape::LowPass, crossover);
+        responses.push(10_f64.powf((above_db + low_db) / 20.0));
+        above_db += half_db(Shape::HighPass, crossover);
     }
+    responses.push(10_f64.powf(above_db / 20.0));
+    responses
+}
 
-    fn process(&mut self, mut sample: f32) -> f32 {
-        for filter in &mut self.filters {
-            sample = filter.process(sample);
-        }
-        sample
-    }
+/// Estimated change in output RMS, in dB, caused by EQ band `index` for the
+/// current mix. Every other band applies on both sides of the ratio, so the
+/// figure isolates this band. Sources are modeled by their spectra (white
+/// flat, pink 1/f, brown 1/f^2, blue f, violet f^2); rain and the user's
+/// sample have no closed form and are approximated as pink, as are wind and
+/// the fan. The ocean and the fire carry most of their power low, and are
+/// approximated as brown.
+pub fn band_rms_contribution_db(settings: AudioSettings, index: usize) -> f32 {
+    eq_rms_change_db(settings, |band| band == index)
 }
 
-// One matched-Z first-order stage: H(z) = (1 - zero*z^-1) / (1 - pole*z^-1).
-#[derive(Debug, Clone, Copy)]
-struct OnePoleZero {
-    zero: f32,
-    pole: f32,
-    x1: f32,
-    y1: f32,
+/// Estimated change in output RMS, in dB, caused by all the band sliders
+/// together for the current mix, modeled as `band_rms_contribution_db`
+/// models one band. The tilts, the ceiling, and the notch are left out.
+///
+/// In mid/side mode each channel is the mid at cos² of its power and the
+/// side at sin², for the stereo width's blend angle, so the two curves'
+/// estimates add in those shares.
+pub fn eq_rms_gain_db(settings: AudioSettings) -> f32 {
+    let mid = eq_rms_change_db(settings, |_| true);
+    if !settings.mid_side {
+        return mid;
+    }
+    let side = eq_rms_change_db(settings.side(), |_| true);
+    let angle = settings.stereo_width.clamp(0.0, 1.0) * FRAC_PI_4;
+    let power = angle.cos().powi(2) * 10.0_f32.powf(mid / 10.0)
+        + angle.sin().powi(2) * 10.0_f32.powf(side / 10.0);
+    10.0 * power.log10()
 }
 
-impl OnePoleZero {
-    fn process(&mut self, input: f32) -> f32 {
-        let output = input - self.zero * self.x1 + self.pole * self.y1;
-        self.x1 = input;
-        self.y1 = output;
-        output
+/// How far the volume comes down to make room for the band sliders, in dB:
+/// as much as they raise the estimated RMS while `[output]` headroom is on,
+/// and never below `SILENCED_BAND_DB`. Cuts are not made up.
+pub fn headroom_db(settings: AudioSettings) -> f32 {
+    if !settings.headroom {
+        return 0.0;
     }
+    -eq_rms_gain_db(settings).clamp(0.0, -SILENCED_BAND_DB)
 }
 
-fn stage_power(zero: f64, pole: f64, cos_omega: f64) -> f64 {
-    (1.0 - 2.0 * zero * cos_omega + zero * zero) / (1.0 - 2.0 * pole * cos_omega + pole * pole)
+/// How far above its RMS noise peaks often enough that a limiter at that
+/// level would be working all the time: Gaussian noise passes four times
+/// its RMS about once in 16,000 samples.
+const NOISE_CREST_DB: f32 = 12.0;
+/// A volume trim stops here rather than silencing the sound.
+const MIN_TRIMMED_VOLUME: f32 = 0.01;
+
+/// Estimated level, in dBFS, of the sound's frequent peaks before the
+/// limiter: every source's RMS at its mix level, the band sliders with the
+/// listening contour and room correction as `eq_rms_gain_db` models them,
+/// the volume less any headroom, and noise's crest. The tone layer and the
+/// SPL cap are left out.
+pub fn estimated_peak_db(settings: AudioSettings) -> f32 {
+    let settings = settings.sanitize();
+    let sources_db =
+        20.0 * COLORED_NOISE_TARGET_RMS.log10() + 10.0 * settings.mix().total().max(1e-6).log10();
+    let volume_db = 20.0 * settings.volume.max(1e-6).log10() + headroom_db(settings);
+    sources_db + eq_rms_gain_db(settings) + volume_db + NOISE_CREST_DB
 }
 
-fn ladder_power(stages: &[(f64, f64)], cos_omega: f64) -> f64 {
-    stages
-        .iter()
-        .map(|&(zero, pole)| stage_power(zero, pole, cos_omega))
-        .product()
+/// How far `estimated_peak_db` sits above the limiter's threshold, in dB,
+/// when it does by more than a rounding error: a sound that lives in the
+/// limiter and distorts.
+pub fn limiter_excess_db(settings: AudioSettings) -> Option<f32> {
+    let excess = estimated_peak_db(settings) - settings.limiter.threshold_db;
+    (excess > 0.05 && settings.volume > 0.0).then_some(excess)
 }
 
-// Mean of |H|^2 over the digital band, i.e. the white-to-output variance gain.
-fn ladder_variance_gain(stages: &[(f64, f64)]) -> f64 {
-    const STEPS: usize = 16_384;
-    (0..STEPS)
-        .map(|step| {
-            let omega = std::f64::consts::PI * (step as f64 + 0.5) / STEPS as f64;
-            ladder_power(stages, omega.cos())
-        })
-        .sum::<f64>()
-        / STEPS as f64
+/// The volume that brings `estimated_peak_db` down to the limiter's
+/// threshold, or None when it is already there.
+pub fn trimmed_volume(settings: AudioSettings) -> Option<f32> {
+    let excess = limiter_excess_db(settings)?;
+    Some((settings.volume * 10_f32.powf(-excess / 20.0)).max(MIN_TRIMMED_VOLUME))
 }
 
-/// Pink noise (-3 dB per octave) built for the actual output sample rate: a
-/// ladder of matched-Z pole/zero stages spaced two octaves apart approximates
-/// the slope, and one correction zero solved at startup flattens the response
-/// near Nyquist. The result stays within about 0.25 dB of ideal pink from
-/// 20 Hz to 20 kHz at any common sample rate.
-#[derive(Debug)]
-struct PinkNoise {
-    stages: Vec<OnePoleZero>,
-    gain: f32,
-}
-
-impl PinkNoise {
-    fn new(sample_rate: f32, target_rms: f32) -> Self {
-        let fs = f64::from(sample_rate);
-        let radius = |frequency: f64| (-2.0 * std::f64::consts::PI * frequency / fs).exp();
-
-        let mut stages: Vec<(f64, f64)> = Vec::new();
-        let mut pole_hz = PINK_LADDER_START_HZ;
-        while pole_hz < fs {
-            let zero_hz = pole_hz * PINK_LADDER_RATIO.sqrt();
-            stages.push((radius(zero_hz), radius(pole_hz)));
-            pole_hz *= PINK_LADDER_RATIO;
-        }
-
-        // The raw ladder runs slightly hot approaching Nyquist. Solve one
-        // correction zero (1 - a*z^-1, a <= 0) so the deviation from the ideal
-        // -3 dB/octave line (anchored at 1 kHz) is zero at the band top.
-        let deviation_db = |correction: f64, frequency: f64| {
-            let response = |f: f64| {
-                let cos_omega = (2.0 * std::f64::consts::PI * f / fs).cos();
-                let power =
-                    ladder_power(&stages, cos_omega) * stage_power(correction, 0.0, cos_omega);
-                10.0 * power.log10() + 10.0 * f.log10()
-            };
-            response(frequency) - response(1_000.0)
-        };
-        let solve_at = (0.40 * fs).min(18_000.0);
-        let mut low = -0.6_f64;
-        let mut high = 0.0_f64;
-        for _ in 0..60 {
-            let mid = 0.5 * (low + high);
-            if deviation_db(mid, solve_at) > 0.0 {
-                high = mid;
-            } else {
-                low = mid;
-            }
-        }
-        stages.push((0.5 * (low + high), 0.0));
+/// The estimated RMS with every band as it plays over the RMS with the
+/// bands `flattened` picks at 0 dB.
+fn eq_rms_change_db(settings: AudioSettings, flattened: impl Fn(usize) -> bool) -> f32 {
+    let settings = settings.sanitize();
+    let mix = settings.mix();
+    let crossovers = match band_filters() {
+        BandFilters::Crossover => crossovers_hz(bands(), ESTIMATE_SAMPLE_RATE),
+        BandFilters::Peaking => None,
+    };
+    let gains: Vec<f64> = bands()
+        .iter()
+        .enumerate()
+        .map(|(index, band)| f64::from(crossover_gain(settings, index, band)))
+        .collect();
+    let coefficients: Vec<Coefficients> = bands()
+        .iter()
+        .enumerate()
+        .map(|(index, band)| {
+            Coefficients::peaking(
+                ESTIMATE_SAMPLE_RATE,
+                band.center_frequency(),
+                band.q(),
+                gain_for_band(settings, index, band),
+            )
+        })
+        .collect();
 
-        let gain =
-            f64::from(target_rms) / (UNIFORM_INPUT_RMS * ladder_variance_gain(&stages).sqrt());
+    let (low, high) = (20.0_f64, 20_000.0_f64);
+    let frequencies: Vec<f64> = (0..ESTIMATE_POINTS)
+        .map(|point| low * (high / low).powf(point as f64 / (ESTIMATE_POINTS - 1) as f64))
+        .collect();
+    // On a log grid each point covers a bandwidth proportional to f, so the
+    // per-point power of a 1/f^k spectrum is proportional to f^(1-k).
+    let shape = |exponent: f64| -> Vec<f64> {
+        let weights: Vec<f64> = frequencies.iter().map(|f| f.powf(exponent)).collect();
+        let total: f64 = weights.iter().sum();
+        weights.into_iter().map(|weight| weight / total).collect()
+    };
+    let (white, pink, brown) = (shape(1.0), shape(0.0), shape(-1.0));
+    let (blue, violet) = (shape(2.0), shape(3.0));
 
-        Self {
-            stages: stages
-                .into_iter()
-                .map(|(zero, pole)| OnePoleZero {
-                    zero: zero as f32,
-                    pole: pole as f32,
-                    x1: 0.0,
-                    y1: 0.0,
-                })
-                .collect(),
-            gain: gain as f32,
-        }
+    let mut with_band = 0.0;
+    let mut without_band = 0.0;
+    for (point, frequency) in frequencies.iter().enumerate() {
+        let source_power = f64::from(mix.white) * white[point]
+            + f64::from(mix.pink + mix.rain + mix.wind + mix.fan + mix.sample) * pink[point]
+            + f64::from(mix.brown + mix.ocean + mix.fire) * brown[point]
+            + f64::from(mix.blue) * blue[point]
+            + f64::from(mix.violet) * violet[point];
+        let (with, without) = if let Some(crossovers) = &crossovers {
+            let responses = crossover_responses(crossovers, *frequency as f32);
+            let (with, without) = (0..responses.len()).fold((0.0, 0.0), |(with, without), band| {
+                let flat = if flattened(band) { 1.0 } else { gains[band] };
+                (
+                    with + gains[band] * responses[band],
+                    without + flat * responses[band],
+                )
+            });
+            (with.powi(2), without.powi(2))
+        } else {
+            let (with_db, without_db) =
+                (0..coefficients.len()).fold((0.0, 0.0), |(with, without), band| {
+                    let response =
+                        coefficients[band].magnitude_db(ESTIMATE_SAMPLE_RATE, *frequency as f32);
+                    let flat = if flattened(band) { 0.0 } else { response };
+                    (with + response, without + flat)
+                });
+            (10_f64.powf(with_db / 10.0), 10_f64.powf(without_db / 10.0))
+        };
+        without_band += source_power * without;
+        with_band += source_power * with;
     }
 
-    fn process(&mut self, white: f32) -> f32 {
-        let mut sample = white;
-        for stage in &mut self.stages {
-            sample = stage.process(sample);
-        }
-        sample * self.gain
+    if without_band <= 0.0 {
+        return 0.0;
     }
+    (10.0 * (with_band / without_band).log10()) as f32
 }
 
-/// Brown noise (-6 dB per octave): a leaky integrator with the leak below the
-/// audible band. The output gain is exact, from the closed-form variance of a
-/// one-pole filter driven by white noise.
+/// The part of the EQ the band sliders drive, with a gain for each chain:
+/// the mid's and the side's in mid/side mode.
 #[derive(Debug)]
-struct BrownNoise {
-    pole: f32,
-    gain: f32,
-    y1: f32,
-}
-
-impl BrownNoise {
-    fn new(sample_rate: f32, target_rms: f32) -> Self {
-        let fs = f64::from(sample_rate);
-        let pole = (-2.0 * std::f64::consts::PI * BROWN_LEAK_HZ / fs).exp();
-        let variance_gain = 1.0 / (1.0 - pole * pole);
-        let gain = f64::from(target_rms) / (UNIFORM_INPUT_RMS * variance_gain.sqrt());
-        Self {
-            pole: pole as f32,
-            gain: gain as f32,
-            y1: 0.0,
-        }
-    }
-
-    fn process(&mut self, white: f32) -> f32 {
-        self.y1 = white + self.pole * self.y1;
-        self.y1 * self.gain
-    }
+enum BandStage {
+    Peaking(Vec<[Biquad; 2]>),
+    Crossover(BandSplitter),
 }
 
+/// Both chains' EQ: the band sliders, the tilts, the notch, the ceiling,
+/// and the high-pass, following the settings they were last given. Chain A
+/// becomes the output's mid and chain B its side, so in mid/side mode the
+/// band sliders shape A and the side's sliders B; the chains share every
+/// other filter's coefficients and run through it side by side.
 #[derive(Debug)]
-struct LinearRamp {
-    current: f32,
-    target: f32,
-    step: f32,
-    remaining: u32,
-    ramp_samples: u32,
+pub struct GraphicEq {
+    bands: &'static [FrequencyBand],
+    stage: BandStage,
+    tilts: [[Biquad<2>; 2]; TILT_BANDS.len()],
+    notch: NotchFilter<2>,
+    ceiling: Cutoff<2>,
+    high_pass: Cutoff<2>,
+    last_values: [f32; MAX_BANDS],
+    last_side_values: [f32; MAX_BANDS],
+    last_tilts: [f32; TILT_BANDS.len()],
+    last_notch: Notch,
+    last_ceiling: Option<f32>,
+    last_high_pass: HighPass,
+    last_contour: Option<f32>,
+    last_correction: [f32; MAX_BANDS],
+    last_overlay: BandOverlay,
 }
 
-impl LinearRamp {
-    fn new(value: f32, sample_rate: f32, seconds: f32) -> Self {
+impl GraphicEq {
+    /// The EQ for the active band layout and filter choice.
+    pub fn new(sample_rate: f32, settings: AudioSettings) -> Self {
+        Self::with_bands(sample_rate, settings, bands(), band_filters())
+    }
+
+    /// The bands of `bands` split by `filters`, whatever the active layout
+    /// and choice. A crossover falls back to peaking filters for bands out
+    /// of order.
+    pub fn with_bands(
+        sample_rate: f32,
+        settings: AudioSettings,
+        bands: &'static [FrequencyBand],
+        filters: BandFilters,
+    ) -> Self {
+        let crossovers = match filters {
+            BandFilters::Crossover => crossovers_hz(bands, sample_rate),
+            BandFilters::Peaking => None,
+        };
+        let side = settings.side();
+        let stage = match crossovers {
+            Some(crossovers) => {
+                let gains: Vec<f32> = bands
+                    .iter()
+                    .enumerate()
+                    .map(|(index, band)| crossover_gain(settings, index, band))
+                    .collect();
+                let mut splitter = BandSplitter::new(sample_rate, &crossovers, &gains);
+                if settings.mid_side {
+                    for (index, band) in bands.iter().enumerate() {
+                        let side = crossover_gain(side, index, band);
+                        splitter.set_channel_gains(index, [gains[index], side]);
+                    }
+                }
+                BandStage::Crossover(splitter)
+            }
+            None => BandStage::Peaking(
+                bands
+                    .iter()
+                    .enumerate()
+                    .map(|(index, band)| {
+                        [settings, side].map(|settings| {
+                            Biquad::new(
+                                sample_rate,
+                                band.center_frequency(),
+                                band.q(),
+                                gain_for_band(settings, index, band),
+                            )
+                        })
+                    })
+                    .collect(),
+            ),
+        };
         Self {
-            current: value,
-            target: value,
-            step: 0.0,
-            remaining: 0,
-            ramp_samples: (sample_rate * seconds).round().max(1.0) as u32,
+            bands,
+            stage,
+            tilts: std::array::from_fn(|tilt| {
+                let center = FREQUENCY_BANDS[TILT_BANDS[tilt]].center_frequency();
+                let [low, high] = tilt_gains_db(settings.band_tilts[tilt]);
+                [
+                    Biquad::with_shape(Shape::LowShelf, sample_rate, center, 1.0, low),
+                    Biquad::with_shape(Shape::HighShelf, sample_rate, center, 1.0, high),
+                ]
+            }),
+            notch: NotchFilter::new(sample_rate, settings.notch.hz, settings.notch.q()),
+            ceiling: Cutoff::low_pass(sample_rate, settings.ceiling_hz),
+            high_pass: Cutoff::high_pass(
+                sample_rate,
+                settings.high_pass.hz,
+                settings.high_pass.slope_db,
+            ),
+            last_values: settings.frequency_bands,
+            last_side_values: side.frequency_bands,
+            last_tilts: settings.band_tilts,
+            last_notch: settings.notch,
+            last_ceiling: settings.ceiling_hz,
+            last_high_pass: settings.high_pass,
+            last_contour: settings.contour_phon(),
+            last_correction: settings.room_correction_db,
+            last_overlay: settings.band_overlay,
         }
     }
 
-    fn set_target(&mut self, target: f32) {
-        if (self.target - target).abs() < f32::EPSILON {
+    fn update(&mut self, settings: AudioSettings) {
+        let side = settings.side();
+        if self.last_values == settings.frequency_bands
+            && self.last_side_values == side.frequency_bands
+            && self.last_tilts == settings.band_tilts
+            && self.last_notch == settings.notch
+            && self.last_ceiling == settings.ceiling_hz
+            && self.last_high_pass == settings.high_pass
+            && self.last_contour == settings.contour_phon()
+            && self.last_correction == settings.room_correction_db
+            && self.last_overlay == settings.band_overlay
+        {
             return;
         }
-        self.target = target;
-        self.remaining = self.ramp_samples;
-        self.step = (self.target - self.current) / self.remaining as f32;
+
+        for (index, band) in self.bands.iter().enumerate() {
+            match &mut self.stage {
+                BandStage::Peaking(filters) => {
+                    for (filter, settings) in filters[index].iter_mut().zip([settings, side]) {
+                        filter.set_target_gain(gain_for_band(settings, index, band));
+                    }
+                }
+                BandStage::Crossover(splitter) => {
+                    let gains =
+                        [settings, side].map(|settings| crossover_gain(settings, index, band));
+                    splitter.set_channel_gains(index, gains);
+                }
+            }
+        }
+        for (pair, tilt) in self.tilts.iter_mut().zip(settings.band_tilts) {
+            for (filter, gain_db) in pair.iter_mut().zip(tilt_gains_db(tilt)) {
+                filter.set_target_gain(gain_db);
+            }
+        }
+
+        if self.last_notch != settings.notch {
+            self.notch.set(settings.notch.hz, settings.notch.q());
+        }
+        self.ceiling.set_corner(settings.ceiling_hz);
+        self.high_pass.set_corner(settings.high_pass.hz);
+        self.high_pass.set_slope(settings.high_pass.slope_db);
+
+        self.last_values = settings.frequency_bands;
+        self.last_side_values = side.frequency_bands;
+        self.last_tilts = settings.band_tilts;
+        self.last_notch = settings.notch;
+        self.last_ceiling = settings.ceiling_hz;
+        self.last_high_pass = settings.high_pass;
+        self.last_contour = settings.contour_phon();
+        self.last_correction = settings.room_correction_db;
+        self.last_overlay = settings.band_overlay;
     }
 
-    fn next(&mut self) -> f32 {
-        if self.remaining > 0 {
-            self.current += self.step;
-            self.remaining -= 1;
-            if self.remaining == 0 {
-                self.current = self.target;
+    pub fn process(&mut self, frame: Frame) -> Frame {
+        let mut block = [frame];
+        self.process_block(&mut block);
+        block[0]
+    }
+
+    /// Filters `block` in place: the crossover a run of frames at a time,
+    /// the gliding filters frame by frame, where a chain of them overlaps.
+    /// The stages do not depend on one another, so this comes out the same
+    /// as filtering frame by frame throughout.
+    pub fn process_block(&mut self, block: &mut [Frame]) {
+        match &mut self.stage {
+            BandStage::Peaking(filters) => {
+                for frame in block.iter_mut() {
+                    for [mid, side] in filters.iter_mut() {
+                        *frame = [mid.process(frame[0]), side.process(frame[1])];
+                    }
+                }
             }
+            BandStage::Crossover(splitter) => splitter.process_block(block),
         }
-        self.current
+        for frame in block {
+            for filter in self.tilts.iter_mut().flatten() {
+                *frame = filter.process_lanes(*frame);
+            }
+            let notched = self.notch.process_lanes(*frame);
+            *frame = self
+                .high_pass
+                .process_lanes(self.ceiling.process_lanes(notched));
+        }
+    }
+
+    fn resets(&self) -> u32 {
+        let bands = match &self.stage {
+            BandStage::Peaking(filters) => filters
+                .iter()
+                .flatten()
+                .fold(0, |total: u32, filter| total.wrapping_add(filter.resets())),
+            BandStage::Crossover(splitter) => splitter.resets(),
+        };
+        self.tilts.iter().flatten().fold(
+            bands
+                .wrapping_add(self.ceiling.resets())
+                .wrapping_add(self.high_pass.resets())
+                .wrapping_add(self.notch.resets()),
+            |total, filter| total.wrapping_add(filter.resets()),
+        )
     }
 }
 
-#[derive(Debug)]
-struct RainSamplePlayer {
+/// A mono recording played in a seamless loop at the output rate: the
+/// built-in rain or a user's sample.
+#[derive(Debug, Clone)]
+pub struct RainSamplePlayer {
     samples: Vec<f32>,
     source_sample_rate: u32,
     target_sample_rate: f32,
     position: f64,
     crossfade_samples: usize,
+    // A new crossfade length waits here until playback is outside both the
+    // old fade and the new one, so changing it never jumps mid-fade.
+    pending_crossfade: usize,
     normalization_gain: f32,
+    // Where plain looping splices, from `loop_points`.
+    loop_start: usize,
+    loop_end: usize,
+    // The stretch playing now ends at `segment_end`, fading into the
+    // recording at `jump_to`. Plain looping keeps them at the loop's end
+    // and start; shuffling draws both afresh after every jump, from the RNG
+    // passed to `next_sample`.
+    segment_end: f64,
+    jump_to: f64,
+    shuffle: bool,
+    // How alike the two stretches the next crossfade joins are, which the
+    // fade's gains make up for.
+    fade_correlation: f32,
+    // The drift's playback speed, 1 for the recording's own pitch.
+    speed: f64,
 }
 
 impl RainSamplePlayer {
-    fn embedded(target_sample_rate: f32) -> Result<Self> {
+    /// The built-in rain, normalized to the other sources' level.
+    pub fn embedded(target_sample_rate: f32) -> Result<Self> {
         Self::from_wav(RAIN_WAV_DATA, target_sample_rate)
             .context("failed to decode the embedded rain recording")
     }
 
-    fn from_wav(data: &[u8], target_sample_rate: f32) -> Result<Self> {
+    /// Decodes any PCM or float WAV, downmixing multichannel files to mono.
+    pub fn from_wav(data: &[u8], target_sample_rate: f32) -> Result<Self> {
         ensure!(
             target_sample_rate.is_finite() && target_sample_rate > 0.0,
             "invalid target sample rate"
@@ -423,24 +637,21 @@ impl RainSamplePlayer {
 
         let reader = hound::WavReader::new(Cursor::new(data))?;
         let spec = reader.spec();
-        ensure!(spec.channels > 0, "rain recording has no channels");
-        ensure!(
-            spec.sample_rate > 0,
-            "rain recording has an invalid sample rate"
-        );
+        ensure!(spec.channels > 0, "recording has no channels");
+        ensure!(spec.sample_rate > 0, "recording has an invalid sample rate");
 
         let interleaved = decode_wav_samples(reader, spec)?;
         let channels = usize::from(spec.channels);
         ensure!(
             interleaved.len() % channels == 0,
-            "rain recording ends with an incomplete audio frame"
+            "recording ends with an incomplete audio frame"
         );
 
         let samples: Vec<f32> = interleaved
             .chunks_exact(channels)
             .map(|frame| frame.iter().copied().sum::<f32>() / channels as f32)
             .collect();
-        ensure!(samples.len() >= 4, "rain recording is empty or too short");
+        ensure!(samples.len() >= 4, "recording is empty or too short");
 
         let rms = (samples
             .iter()
@@ -448,19 +659,109 @@ impl RainSamplePlayer {
             .sum::<f64>()
             / samples.len() as f64)
             .sqrt() as f32;
-        ensure!(rms.is_finite() && rms > 0.0, "rain recording is silent");
-
-        let requested_crossfade = spec.sample_rate as usize * 2;
-        let crossfade_samples = requested_crossfade.min(samples.len() / 3).max(1);
+        ensure!(rms.is_finite() && rms > 0.0, "recording is silent");
+        let (loop_start, loop_end) = loop_points(&samples, spec.sample_rate);
 
-        Ok(Self {
+        let mut player = Self {
             samples,
             source_sample_rate: spec.sample_rate,
             target_sample_rate,
             position: 0.0,
-            crossfade_samples,
+            crossfade_samples: 0,
+            pending_crossfade: 0,
             normalization_gain: (RAIN_TARGET_RMS / rms).clamp(0.25, 8.0),
-        })
+            loop_start,
+            loop_end,
+            segment_end: loop_end as f64,
+            jump_to: loop_start as f64,
+            shuffle: false,
+            fade_correlation: 0.0,
+            speed: 1.0,
+        };
+        player.set_crossfade(DEFAULT_LOOP_CROSSFADE_SECONDS);
+        player.crossfade_samples = player.pending_crossfade;
+        player.fade_correlation = player.fade_correlation();
+        Ok(player)
+    }
+
+    /// A user's sample, brought to `SAMPLE_TARGET_LUFS` instead of the
+    /// rain's RMS target so its recording level no longer decides how loud
+    /// it plays. The level comes with the sample, so it is not measured
+    /// again.
+    pub fn user_sample(sample: &UserSample, target_sample_rate: f32) -> Result<Self> {
+        let mut player = Self::from_wav(&sample.data, target_sample_rate)?;
+        player.normalization_gain = 10_f32.powf(sample.level.gain_db / 20.0);
+        Ok(player)
+    }
+
+    /// Asks for a crossfade of `seconds`, kept to a third of the loop so the
+    /// fade never reaches into the start it fades to.
+    fn set_crossfade(&mut self, seconds: f32) {
+        let requested = (seconds * self.source_sample_rate as f32) as usize;
+        self.pending_crossfade = requested.min(self.samples.len() / 3).max(1);
+    }
+
+    /// Plays the loop `speed` times faster, and so that much higher.
+    fn set_speed(&mut self, speed: f32) {
+        self.speed = f64::from(speed);
+    }
+
+    /// Shuffles a loop shorter than `SHUFFLE_MAX_SECONDS`: each stretch
+    /// ends at a random point and crossfades into a random part of the
+    /// recording, so there is no loop period to pick out. Turning it on or
+    /// off takes effect at the next jump.
+    fn set_shuffle(&mut self, shuffle: bool) {
+        let seconds = self.samples.len() as f32 / self.source_sample_rate as f32;
+        self.shuffle = shuffle && seconds < SHUFFLE_MAX_SECONDS;
+    }
+
+    /// Picks where the stretch that starts at `position` ends and where it
+    /// jumps to. A shuffled stretch plays at least one fade's length before
+    /// its own fade, and its jump leaves room for a fade and a stretch after.
+    fn next_segment(&mut self, rng: &mut impl Rng) {
+        let length = self.samples.len() as f64;
+        if !self.shuffle {
+            self.segment_end = self.loop_end as f64;
+            self.jump_to = self.loop_start as f64;
+            return;
+        }
+        let fade = self.crossfade_samples.max(self.pending_crossfade) as f64;
+        self.jump_to = rng.random_range(0.0..=length - 3.0 * fade);
+        let earliest = (self.position + 2.0 * fade).min(length);
+        self.segment_end = rng.random_range(earliest..=length);
+    }
+
+    /// The same loop started halfway through, so its output is uncorrelated
+    /// with this player's over any stretch shorter than half the loop. A
+    /// shuffled copy draws its own jumps from its own chain's RNG.
+    fn half_a_loop_later(&self) -> Self {
+        let start = self.loop_start as f64;
+        let span = (self.loop_end - self.crossfade_samples) as f64 - start;
+        Self {
+            position: start + (self.position - start + span / 2.0).rem_euclid(span),
+            ..self.clone()
+        }
+    }
+
+    /// The correlation of the stretch fading out with the one fading in,
+    /// from -0.5 to 1. An equal-power fade keeps the level of unrelated
+    /// stretches, but swells by up to 3 dB at its middle between alike
+    /// ones, as a loop's two ends may be.
+    fn fade_correlation(&self) -> f32 {
+        let fade = self.crossfade_samples as f64;
+        let fade_start = (self.segment_end - fade).max(0.0);
+        let step = (fade / FADE_CORRELATION_POINTS as f64).max(1.0);
+        let (mut product, mut out_power, mut in_power) = (0.0_f64, 0.0_f64, 0.0_f64);
+        let mut offset = 0.0;
+        while offset < fade {
+            let out = f64::from(self.interpolated(fade_start + offset));
+            let into = f64::from(self.interpolated(self.jump_to + offset));
+            product += out * into;
+            out_power += out * out;
+            in_power += into * into;
+            offset += step;
+        }
+        (product / (out_power * in_power).sqrt().max(1e-12)).clamp(-0.5, 1.0) as f32
     }
 
     fn interpolated(&self, position: f64) -> f32 {
@@ -471,27 +772,242 @@ impl RainSamplePlayer {
         first + (second - first) * fraction
     }
 
-    fn next_sample(&mut self) -> f32 {
-        let fade_start = self.samples.len() - self.crossfade_samples;
-        let sample = if self.position >= fade_start as f64 {
-            let fade_position = self.position - fade_start as f64;
+    /// `rng` picks a shuffled loop's jumps and is left alone otherwise.
+    pub fn next_sample(&mut self, rng: &mut impl Rng) -> f32 {
+        if self.pending_crossfade != self.crossfade_samples {
+            let longest = self.pending_crossfade.max(self.crossfade_samples) as f64;
+            if self.position < self.segment_end - longest
+                && self.jump_to + longest <= self.samples.len() as f64
+            {
+                self.crossfade_samples = self.pending_crossfade;
+                self.fade_correlation = self.fade_correlation();
+            }
+        }
+        let fade_start = self.segment_end - self.crossfade_samples as f64;
+        let sample = if self.position >= fade_start {
+            let fade_position = self.position - fade_start;
             let progress = (fade_position / self.crossfade_samples as f64).clamp(0.0, 1.0) as f32;
             let angle = progress * FRAC_PI_2;
-            self.interpolated(self.position) * angle.cos()
-                + self.interpolated(fade_position) * angle.sin()
+            // Equal power between unrelated stretches, equal gain between
+            // identical ones, and in between for the rest.
+            let power = (1.0 + self.fade_correlation * (2.0 * angle).sin()).sqrt();
+            (self.interpolated(self.position) * angle.cos()
+                + self.interpolated(self.jump_to + fade_position) * angle.sin())
+                / power
         } else {
             self.interpolated(self.position)
         };
 
-        self.position += self.source_sample_rate as f64 / self.target_sample_rate as f64;
-        while self.position >= self.samples.len() as f64 {
-            self.position -= fade_start as f64;
+        self.position +=
+            self.speed * self.source_sample_rate as f64 / self.target_sample_rate as f64;
+        while self.position >= self.segment_end {
+            self.position = self.jump_to + (self.position - fade_start);
+            self.next_segment(rng);
+            self.fade_correlation = self.fade_correlation();
         }
 
         condition_rain_sample(sample * self.normalization_gain)
     }
 }
 
+/// Where a recording loops from and to: of the rising zero crossings near
+/// its start and near its end, the pair whose surroundings differ least,
+/// in waveform right around the splice and in level over the half second
+/// the fade joins, so the seam puts like beside like. A recording too short
+/// to search, or without crossings, loops whole.
+fn loop_points(samples: &[f32], sample_rate: u32) -> (usize, usize) {
+    let length = samples.len();
+    let rate = sample_rate as f32;
+    let half_window = ((SPLICE_WAVEFORM_SECONDS * rate) as usize).max(4);
+    let level_window = ((SPLICE_LEVEL_SECONDS * rate) as usize).clamp(1, length / 4 + 1);
+    let search = ((LOOP_SEARCH_SECONDS * rate) as usize).min(length / 8);
+    if search <= 2 * half_window {
+        return (0, length);
+    }
+    let crossings = |from: usize, to: usize| -> Vec<usize> {
+        let all: Vec<usize> = (from..to)
+            .filter(|&index| samples[index - 1] < 0.0 && samples[index] >= 0.0)
+            .collect();
+        let step = all.len().div_ceil(LOOP_CANDIDATES).max(1);
+        all.into_iter().step_by(step).collect()
+    };
+    let starts = crossings(half_window, search);
+    let ends = crossings(length - search, length - half_window);
+
+    let mut energy = Vec::with_capacity(length + 1);
+    energy.push(0.0_f64);
+    for sample in samples {
+        energy.push(energy[energy.len() - 1] + f64::from(*sample) * f64::from(*sample));
+    }
+    let mean_square = |from: usize, to: usize| (energy[to] - energy[from]) / (to - from) as f64;
+
+    let mut best = (f64::INFINITY, 0, length);
+    for &start in &starts {
+        let after = mean_square(start, (start + level_window).min(length));
+        // Latest first, so a tie keeps the longer loop.
+        for &end in ends.iter().rev() {
+            let before = mean_square(end.saturating_sub(level_window), end);
+            let (mut difference, mut power) = (0.0_f64, 0.0_f64);
+            for offset in 0..2 * half_window {
+                let into = f64::from(samples[start - half_window + offset]);
+                let out = f64::from(samples[end - half_window + offset]);
+                difference += (into - out) * (into - out);
+                power += into * into + out * out;
+            }
+            // A 6 dB step in level counts as much as an unrelated waveform.
+            let level_db = (10.0 * ((after + 1e-12) / (before + 1e-12)).log10()).abs();
+            let score = difference / power.max(1e-12) + level_db / 6.0;
+            if score < best.0 {
+                best = (score, start, end);
+            }
+        }
+    }
+    (best.1, best.2)
+}
+
+/// A user sample's measured loudness and the gain that brings it to the
+/// level of the built-in sources.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampleLevel {
+    pub loudness_lufs: f32,
+    pub gain_db: f32,
+    /// The loudness came from the file's ReplayGain tag, not a measurement.
+    pub tagged: bool,
+}
+
+impl SampleLevel {
+    /// Measures a sample loop the way it will be imported for playback.
+    pub fn measure(data: &[u8]) -> Result<Self> {
+        let player = RainSamplePlayer::from_wav(data, 48_000.0)
+            .context("failed to decode the sample loop (only WAV is supported)")?;
+        Ok(Self::of(&player))
+    }
+
+    fn of(player: &RainSamplePlayer) -> Self {
+        Self::from_loudness(loop_loudness(
+            &player.samples,
+            player.source_sample_rate as f32,
+        ))
+    }
+
+    /// The level of a sample already measured at `loudness_lufs`.
+    pub fn from_loudness(loudness_lufs: f32) -> Self {
+        Self {
+            loudness_lufs,
+            gain_db: (SAMPLE_TARGET_LUFS - loudness_lufs)
+                .clamp(-MAX_SAMPLE_GAIN_DB, MAX_SAMPLE_GAIN_DB),
+            tagged: false,
+        }
+    }
+
+    /// The level a `REPLAYGAIN_TRACK_GAIN` tag in the WAV file gives, if it
+    /// has one that reads.
+    pub fn from_tag(data: &[u8]) -> Option<Self> {
+        let gain = track_gain_tag(data)?;
+        Some(Self {
+            tagged: true,
+            ..Self::from_loudness(REPLAY_GAIN_REFERENCE_LUFS - gain)
+        })
+    }
+
+    /// The ReplayGain 2.0 track gain for this loudness: what a player that
+    /// reads the tags would apply.
+    pub fn replay_gain_db(&self) -> f32 {
+        REPLAY_GAIN_REFERENCE_LUFS - self.loudness_lufs
+    }
+}
+
+/// The `REPLAYGAIN_TRACK_GAIN` value in a WAV file's chunks other than its
+/// audio, as taggers leave it in a `LIST` chunk or an ID3 frame in an
+/// `id3 ` chunk: the name, any separators, then a number of dB. Names
+/// match without regard to case; text in UTF-16 is not read.
+fn track_gain_tag(data: &[u8]) -> Option<f32> {
+    const NAME: &[u8] = b"REPLAYGAIN_TRACK_GAIN";
+    if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return None;
+    }
+    let mut offset = 12;
+    while offset + 8 <= data.len() {
+        let id = &data[offset..offset + 4];
+        let size = u32::from_le_bytes(data[offset + 4..offset + 8].try_into().ok()?) as usize;
+        let body = &data[offset + 8..(offset + 8).saturating_add(size).min(data.len())];
+        if id != b"data" {
+            let found = body
+                .windows(NAME.len())
+                .position(|window| window.eq_ignore_ascii_case(NAME));
+            if let Some(at) = found {
+                let value: String = body[at + NAME.len()..]
+                    .iter()
+                    .skip_while(|byte| matches!(byte, 0 | b'=' | b':' | b' ' | b'\t'))
+                    .take_while(|byte| matches!(byte, b'+' | b'-' | b'.' | b'0'..=b'9'))
+                    .map(|byte| char::from(*byte))
+                    .collect();
+                return value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|gain| gain.is_finite() && gain.abs() <= MAX_TAG_GAIN_DB);
+            }
+        }
+        offset = offset.saturating_add(8 + size + size % 2);
+    }
+    None
+}
+
+/// A user's sample loop: the WAV file as read and its measured level.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserSample {
+    pub data: Vec<u8>,
+    pub level: SampleLevel,
+}
+
+impl UserSample {
+    /// Decodes and measures `data`; `sample_cache::load` skips the
+    /// measuring for a file it has seen before.
+    pub fn measure(data: Vec<u8>) -> Result<Self> {
+        let level = SampleLevel::measure(&data)?;
+        Ok(Self { data, level })
+    }
+}
+
+/// Integrated K-weighted loudness of a loop, ungated: a first pass through
+/// the loop fills the meter's one-second average, so the seam is measured
+/// the way it plays, and the second pass is averaged.
+fn loop_loudness(samples: &[f32], sample_rate: f32) -> f32 {
+    let mut meter = LoudnessMeter::new(sample_rate);
+    for sample in samples {
+        meter.process(*sample);
+    }
+    let mut total = 0.0_f64;
+    for sample in samples {
+        meter.process(*sample);
+        total += f64::from(meter.mean_square());
+    }
+    let mean_square = (total / samples.len() as f64).max(1e-12);
+    -0.691 + 10.0 * mean_square.log10() as f32
+}
+
+/// The longest crossfade each loop allows: a third of its length, as the
+/// players keep it. Without a sample, its entry is the settings maximum.
+pub fn loop_crossfade_limits(sample: Option<&UserSample>) -> Result<LoopSettings> {
+    let third = |data: &[u8]| -> Result<f32> {
+        let reader = hound::WavReader::new(Cursor::new(data))?;
+        let rate = reader.spec().sample_rate;
+        ensure!(rate > 0, "recording has an invalid sample rate");
+        Ok((reader.duration() / 3) as f32 / rate as f32)
+    };
+    Ok(LoopSettings {
+        rain_crossfade_seconds: third(RAIN_WAV_DATA)
+            .context("failed to decode the embedded rain recording")?,
+        sample_crossfade_seconds: match sample {
+            Some(sample) => third(&sample.data)
+                .context("failed to decode the sample loop (only WAV is supported)")?,
+            None => MAX_LOOP_CROSSFADE_SECONDS,
+        },
+        rain_shuffle: false,
+        sample_shuffle: false,
+    })
+}
+
 fn decode_wav_samples<R: std::io::Read>(
     reader: hound::WavReader<R>,
     spec: hound::WavSpec,
@@ -528,7 +1044,7 @@ fn decode_wav_samples<R: std::io::Read>(
                 .map_err(Into::into)
         }
         _ => bail!(
-            "unsupported rain WAV encoding: {:?}, {} bits",
+            "unsupported WAV encoding: {:?}, {} bits",
             spec.sample_format,
             spec.bits_per_sample
         ),
@@ -546,113 +1062,1386 @@ fn condition_rain_sample(sample: f32) -> f32 {
     sample.signum() * compressed
 }
 
-#[derive(Debug)]
-struct AudioEngine {
-    rng: SmallRng,
-    pink: PinkNoise,
-    brown: BrownNoise,
-    rain_player: RainSamplePlayer,
-    eq: GraphicEq,
-    volume: LinearRamp,
-    // One gain ramp per SoundStyle::ALL entry. All ramps share one duration
-    // and retarget together, so the linear gains always sum to 1 and the
-    // sqrt-gain mix stays equal-power, even when the style changes mid-fade.
-    style_gains: [LinearRamp; SoundStyle::ALL.len()],
+/// Left/right correlation at a stereo width: 1 at 0 (mono), about 0.71 at
+/// the default 0.5, and 0 at 1 (fully decorrelated). It never goes
+/// negative, so a downmix to mono never cancels.
+pub fn stereo_correlation(width: f32) -> f32 {
+    (width.clamp(0.0, 1.0) * FRAC_PI_2).cos()
 }
 
-impl AudioEngine {
-    fn new(sample_rate: f32, settings: AudioSettings) -> Result<Self> {
-        ensure!(
-            sample_rate.is_finite() && sample_rate > 0.0,
-            "invalid output sample rate"
-        );
-        let settings = settings.sanitize();
+/// Peak amplitude of the tone layer; the level is a power fraction, as in
+/// the source mix.
+fn tone_amplitude(tone: ToneSettings) -> f32 {
+    match tone.mode {
+        ToneMode::Off => 0.0,
+        ToneMode::Binaural | ToneMode::Isochronic => tone.level.sqrt() * TONE_FULL_AMPLITUDE,
+    }
+}
 
-        let mut volume = LinearRamp::new(0.0, sample_rate, PARAMETER_RAMP_SECONDS);
-        volume.set_target(settings.volume);
+/// The generator a chain draws its noise from, as `NoiseRng` chooses. Every
+/// source asks for at most one value a sample, so the match costs one
+/// well-predicted branch, next to the generator's own few nanoseconds.
+#[derive(Debug)]
+pub enum NoiseGenerator {
+    Small(SmallRng),
+    Xoshiro(Xoshiro128PlusPlus),
+    // Boxed: its buffered block is ten times the others' whole state.
+    ChaCha(Box<StdRng>),
+}
 
-        Ok(Self {
-            rng: rand::make_rng(),
-            pink: PinkNoise::new(sample_rate, COLORED_NOISE_TARGET_RMS),
-            brown: BrownNoise::new(sample_rate, COLORED_NOISE_TARGET_RMS),
-            rain_player: RainSamplePlayer::embedded(sample_rate)?,
-            eq: GraphicEq::new(sample_rate, settings),
-            volume,
-            style_gains: SoundStyle::ALL.map(|style| {
-                LinearRamp::new(
-                    settings.mix().level(style),
-                    sample_rate,
-                    STYLE_CROSSFADE_SECONDS,
-                )
-            }),
-        })
+impl NoiseGenerator {
+    /// A `kind` generator seeded from `seeds`, except ChaCha, which is keyed
+    /// from the system's entropy source so that its stream owes nothing to
+    /// a lesser generator. Should the system refuse, it falls back to
+    /// `seeds` rather than leave the chain without noise.
+    pub fn new(kind: NoiseRng, seeds: &mut SmallRng) -> Self {
+        if kind == NoiseRng::Chacha {
+            if let Ok(rng) = StdRng::try_from_rng(&mut SysRng) {
+                return Self::ChaCha(Box::new(rng));
+            }
+        }
+        Self::seeded(kind, seeds)
     }
 
-    fn update_settings(&mut self, settings: AudioSettings) {
-        let settings = settings.sanitize();
-        self.eq.update(settings);
-        self.volume.set_target(settings.volume);
-        for (style, ramp) in SoundStyle::ALL.iter().zip(self.style_gains.iter_mut()) {
-            ramp.set_target(settings.mix().level(*style));
+    /// A `kind` generator seeded from `seeds` alone, ChaCha included, so a
+    /// seeded engine repeats itself whichever generator it uses.
+    pub fn seeded(kind: NoiseRng, seeds: &mut SmallRng) -> Self {
+        match kind {
+            NoiseRng::Small => Self::Small(SmallRng::from_rng(seeds)),
+            NoiseRng::Xoshiro => Self::Xoshiro(Xoshiro128PlusPlus::from_rng(seeds)),
+            NoiseRng::Chacha => Self::ChaCha(Box::new(StdRng::from_rng(seeds))),
         }
     }
+}
 
-    fn next_sample(&mut self) -> f32 {
-        let mut mixed = 0.0;
-        for (style, ramp) in SoundStyle::ALL.iter().zip(self.style_gains.iter_mut()) {
-            let gain = ramp.next().clamp(0.0, 1.0);
-            if gain <= 0.0 {
-                continue;
-            }
-            let source = match style {
-                SoundStyle::White => (self.rng.random::<f32>() * 2.0 - 1.0) * WHITE_NOISE_GAIN,
-                SoundStyle::Pink => self.pink.process(self.rng.random::<f32>() * 2.0 - 1.0),
-                SoundStyle::Brown => self.brown.process(self.rng.random::<f32>() * 2.0 - 1.0),
-                SoundStyle::Rain => self.rain_player.next_sample(),
-            };
-            mixed += source * gain.sqrt();
-        }
+impl TryRng for NoiseGenerator {
+    type Error = Infallible;
 
-        let shaped = self.eq.process(mixed);
-        soft_limit(shaped * self.volume.next())
+    fn try_next_u32(&mut self) -> Result<u32, Infallible> {
+        Ok(match self {
+            Self::Small(rng) => rng.next_u32(),
+            Self::Xoshiro(rng) => rng.next_u32(),
+            Self::ChaCha(rng) => rng.next_u32(),
+        })
     }
-}
 
-fn soft_limit(sample: f32) -> f32 {
-    if !sample.is_finite() {
-        return 0.0;
+    fn try_next_u64(&mut self) -> Result<u64, Infallible> {
+        Ok(match self {
+            Self::Small(rng) => rng.next_u64(),
+            Self::Xoshiro(rng) => rng.next_u64(),
+            Self::ChaCha(rng) => rng.next_u64(),
+        })
     }
 
-    const KNEE: f32 = 0.8;
-    let magnitude = sample.abs();
-    if magnitude <= KNEE {
-        sample
-    } else {
-        let limited = KNEE + (1.0 - KNEE) * (1.0 - (-(magnitude - KNEE) / (1.0 - KNEE)).exp());
-        sample.signum() * limited.min(1.0)
+    fn try_fill_bytes(&mut self, bytes: &mut [u8]) -> Result<(), Infallible> {
+        match self {
+            Self::Small(rng) => rng.fill_bytes(bytes),
+            Self::Xoshiro(rng) => rng.fill_bytes(bytes),
+            Self::ChaCha(rng) => rng.fill_bytes(bytes),
+        }
+        Ok(())
     }
 }
 
-pub fn build_output_stream(
-    device: &Device,
-    config: StreamConfig,
-    sample_format: SampleFormat,
-    settings: Arc<Mutex<AudioSettings>>,
-    running: Arc<AtomicBool>,
+/// A white sample from -1 to 1.
+fn white(rng: &mut impl Rng) -> f32 {
+    rng.random::<f32>() * 2.0 - 1.0
+}
+
+/// One complete set of sources with its own RNG and filter state. Two chains
+/// fed the same settings produce uncorrelated noise of identical color and
+/// level; the second chain plays each loop half a loop apart.
+#[derive(Debug)]
+struct SourceChain {
+    // The chain's one noise generator: every source in it, the loops'
+    // shuffled jumps, and this ear's room tone draw from it in turn.
+    rng: NoiseGenerator,
+    pink: PinkNoise,
+    brown: BrownNoise,
+    blue: BlueNoise,
+    violet: VioletNoise,
+    rain_player: RainSamplePlayer,
+    ocean: Ocean,
+    wind: Wind,
+    fire: Fire,
+    fan: Fan,
+    // None plays the Sample layer as silence.
+    sample_player: Option<RainSamplePlayer>,
+}
+
+impl SourceChain {
+    /// Both chains take a copy of one `movement` RNG, which times the
+    /// soundscapes' waves and gusts alike in each.
+    fn new(
+        sample_rate: f32,
+        rng: NoiseGenerator,
+        rain_player: RainSamplePlayer,
+        sample_player: Option<RainSamplePlayer>,
+        movement: &SmallRng,
+    ) -> Self {
+        Self {
+            rng,
+            pink: PinkNoise::new(sample_rate, COLORED_NOISE_TARGET_RMS),
+            brown: BrownNoise::new(sample_rate, COLORED_NOISE_TARGET_RMS),
+            blue: BlueNoise::new(sample_rate, COLORED_NOISE_TARGET_RMS),
+            violet: VioletNoise::new(COLORED_NOISE_TARGET_RMS),
+            rain_player,
+            ocean: Ocean::new(sample_rate, COLORED_NOISE_TARGET_RMS, movement.clone()),
+            wind: Wind::new(sample_rate, COLORED_NOISE_TARGET_RMS, movement.clone()),
+            fire: Fire::new(sample_rate, COLORED_NOISE_TARGET_RMS),
+            fan: Fan::new(sample_rate, COLORED_NOISE_TARGET_RMS),
+            sample_player,
+        }
+    }
+
+    fn next_sample(&mut self, style: SoundStyle) -> f32 {
+        match style {
+            SoundStyle::White => white(&mut self.rng) * WHITE_NOISE_GAIN,
+            SoundStyle::Pink => self.pink.process(white(&mut self.rng)),
+            SoundStyle::Brown => self.brown.process(white(&mut self.rng)),
+            SoundStyle::Blue => self.blue.process(white(&mut self.rng)),
+            SoundStyle::Violet => self.violet.process(white(&mut self.rng)),
+            SoundStyle::Rain => self.rain_player.next_sample(&mut self.rng),
+            SoundStyle::Ocean => self.ocean.next_sample(&mut self.rng),
+            SoundStyle::Wind => self.wind.next_sample(&mut self.rng),
+            SoundStyle::Fire => self.fire.next_sample(&mut self.rng),
+            SoundStyle::Fan => self.fan.next_sample(&mut self.rng),
+            SoundStyle::Sample => self
+                .sample_player
+                .as_mut()
+                .map_or(0.0, |player| player.next_sample(&mut self.rng)),
+        }
+    }
+
+    fn set_loops(&mut self, loops: LoopSettings) {
+        self.rain_player.set_crossfade(loops.rain_crossfade_seconds);
+        self.rain_player.set_shuffle(loops.rain_shuffle);
+        if let Some(player) = &mut self.sample_player {
+            player.set_crossfade(loops.sample_crossfade_seconds);
+            player.set_shuffle(loops.sample_shuffle);
+        }
+    }
+
+    fn set_speed(&mut self, speed: f32) {
+        self.rain_player.set_speed(speed);
+        if let Some(player) = &mut self.sample_player {
+            player.set_speed(speed);
+        }
+    }
+
+    fn resets(&self) -> u32 {
+        self.pink
+            .resets()
+            .wrapping_add(self.brown.resets())
+            .wrapping_add(self.blue.resets())
+            .wrapping_add(self.violet.resets())
+            .wrapping_add(self.ocean.resets())
+            .wrapping_add(self.wind.resets())
+            .wrapping_add(self.fire.resets())
+            .wrapping_add(self.fan.resets())
+    }
+}
+
+/// A scene's low- and high-pass on one source in one chain. Both stay out
+/// of the path until a scene first sets a corner, so an unshaped source
+/// costs nothing; after that they glide open like the global filters.
+#[derive(Debug)]
+struct LayerFilters {
+    low_pass: Cutoff,
+    high_pass: Cutoff,
+    engaged: bool,
+}
+
+impl LayerFilters {
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            low_pass: Cutoff::low_pass(sample_rate, None),
+            high_pass: Cutoff::high_pass(sample_rate, None, 12),
+            engaged: false,
+        }
+    }
+
+    fn set(&mut self, shape: LayerShape) {
+        self.engaged |= shape.low_pass_hz.is_some() || shape.high_pass_hz.is_some();
+        self.low_pass.set_corner(shape.low_pass_hz);
+        self.high_pass.set_corner(shape.high_pass_hz);
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        if !self.engaged {
+            return input;
+        }
+        self.high_pass.process(self.low_pass.process(input))
+    }
+
+    fn resets(&self) -> u32 {
+        self.low_pass.resets().wrapping_add(self.high_pass.resets())
+    }
+}
+
+/// A scene's swell of one source's level, shared by both chains. The depth
+/// glides, so starting or ending a swell never steps the level.
+#[derive(Debug)]
+struct Swell {
+    modulation: Modulation,
+    depth: LinearRamp,
+    phase: f32,
+    sample_rate: f32,
+}
+
+impl Swell {
+    fn new(sample_rate: f32, modulation: Modulation) -> Self {
+        Self {
+            modulation,
+            depth: LinearRamp::new(modulation.depth, sample_rate, PARAMETER_RAMP_SECONDS),
+            phase: 0.0,
+            sample_rate,
+        }
+    }
+
+    fn set(&mut self, modulation: Modulation) {
+        self.modulation = modulation;
+        self.depth.set_target(modulation.depth);
+    }
+
+    fn next(&mut self) -> f32 {
+        let depth = self.depth.next_value();
+        if depth <= 0.0 {
+            return 1.0;
+        }
+        self.phase =
+            (self.phase + 1.0 / (self.modulation.period_seconds * self.sample_rate)).fract();
+        Modulation {
+            depth,
+            ..self.modulation
+        }
+        .gain(self.phase)
+    }
+}
+
+/// One of the drift's random walks. It glides along a raised cosine from
+/// one random point in [-1, 1] to the next, each leg taking half to one and
+/// a half periods, so it never settles into a cycle an ear could learn.
+#[derive(Debug)]
+struct Wander {
+    from: f32,
+    to: f32,
+    progress: f32,
+    step: f32,
+}
+
+impl Wander {
+    fn new(rng: &mut SmallRng) -> Self {
+        Self {
+            from: 0.0,
+            to: rng.random_range(-1.0..=1.0),
+            progress: 0.0,
+            step: 0.0,
+        }
+    }
+
+    /// Moves `seconds` along, at legs of about `period` seconds, and
+    /// returns where the walk is.
+    fn advance(&mut self, seconds: f32, period: f32, rng: &mut SmallRng) -> f32 {
+        if self.step == 0.0 {
+            self.step = seconds / (period * rng.random_range(0.5..1.5));
+        }
+        self.progress += self.step;
+        if self.progress >= 1.0 {
+            self.progress = (self.progress - 1.0).min(1.0);
+            self.from = self.to;
+            self.to = rng.random_range(-1.0..=1.0);
+            self.step = seconds / (period * rng.random_range(0.5..1.5));
+        }
+        let eased = (1.0 - (PI * self.progress).cos()) / 2.0;
+        self.from + (self.to - self.from) * eased
+    }
+}
+
+/// Where the drift has taken the sound at one step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Drifted {
+    bands_db: [f32; MAX_BANDS],
+    balance_db: f32,
+    speed: f32,
+}
+
+impl Default for Drifted {
+    fn default() -> Self {
+        Self {
+            bands_db: [0.0; MAX_BANDS],
+            balance_db: 0.0,
+            speed: 1.0,
+        }
+    }
+}
+
+/// The slow drift: a walk for each band's gain, one for the balance, and
+/// one for the loops' speed, stepped every `DRIFT_STEP_SECONDS`. Both
+/// chains share it, so a band moves in both ears at once.
+#[derive(Debug)]
+struct Drift {
+    rng: SmallRng,
+    bands: [Wander; MAX_BANDS],
+    balance: Wander,
+    speed: Wander,
+    // Stepped once per drift step, so its glide is counted in steps.
+    depth: LinearRamp,
+    period_seconds: f32,
+    step_samples: usize,
+    countdown: usize,
+    // Nothing to step while the depth sits at 0 after a last step there.
+    idle: bool,
+}
+
+impl Drift {
+    fn new(sample_rate: f32, drift: DriftSettings, mut rng: SmallRng) -> Self {
+        Self {
+            bands: std::array::from_fn(|_| Wander::new(&mut rng)),
+            balance: Wander::new(&mut rng),
+            speed: Wander::new(&mut rng),
+            rng,
+            depth: LinearRamp::new(
+                drift.active_depth(),
+                1.0 / DRIFT_STEP_SECONDS,
+                DRIFT_FADE_SECONDS,
+            ),
+            period_seconds: drift.period_seconds,
+            step_samples: ((sample_rate * DRIFT_STEP_SECONDS) as usize).max(1),
+            countdown: 0,
+            idle: false,
+        }
+    }
+
+    fn set(&mut self, drift: DriftSettings) {
+        self.depth.set_target(drift.active_depth());
+        self.period_seconds = drift.period_seconds;
+        self.idle = false;
+    }
+
+    /// Counts one sample, and at each step returns where the walks are,
+    /// scaled by the depth.
+    fn next(&mut self) -> Option<Drifted> {
+        if self.countdown > 0 {
+            self.countdown -= 1;
+            return None;
+        }
+        self.countdown = self.step_samples - 1;
+        if self.idle {
+            return None;
+        }
+        let depth = self.depth.next_value();
+        if depth <= 0.0 {
+            self.idle = true;
+            return Some(Drifted::default());
+        }
+        let (rng, period) = (&mut self.rng, self.period_seconds);
+        let mut walk =
+            |wander: &mut Wander| depth * wander.advance(DRIFT_STEP_SECONDS, period, rng);
+        Some(Drifted {
+            bands_db: self.bands.each_mut().map(|band| walk(band) * DRIFT_BAND_DB),
+            balance_db: walk(&mut self.balance) * DRIFT_BALANCE_DB,
+            speed: 1.0 + walk(&mut self.speed) * DRIFT_SPEED,
+        })
+    }
+}
+
+/// Every source, the EQ, the stereo stage, and the output gain, rendering
+/// stereo frames one at a time. [`Generator`] wraps it for output buffers.
+#[derive(Debug)]
+pub struct AudioEngine {
+    // Two uncorrelated chains, side by side through the EQ, that the stereo
+    // width blends into left and right.
+    chains: [SourceChain; 2],
+    eq: GraphicEq,
+    width: LinearRamp,
+    // Blend weights for the current width, recomputed only when it moves.
+    blend_width: f32,
+    blend: (f32, f32),
+    // None when the band layout cannot be split at its crossovers.
+    band_widths: Option<BandWidths>,
+    // Applies to the noise only, like the width, so a binaural beat's
+    // carriers stay one per ear.
+    crossfeed: Crossfeed,
+    crossfeed_amount: LinearRamp,
+    // Reference listening: 1 replaces the EQ stage and crossfeed with the
+    // raw mix at the processed mix's loudness. Both meters always run, so
+    // the match is ready the moment the key goes down.
+    bypass: LinearRamp,
+    processed_loudness: LoudnessMeter,
+    raw_loudness: LoudnessMeter,
+    volume: LinearRamp,
+    user_volume: f32,
+    // Sleep-timer fade, applied on top of the user's volume.
+    fade_gain: f32,
+    // headroom_db as a gain, applied with the volume.
+    headroom: f32,
+    paused: bool,
+    muted: bool,
+    // One gain ramp per SoundStyle::ALL entry. All ramps share one duration
+    // and retarget together, so the linear gains always sum to 1 and the
+    // sqrt-gain mix stays equal-power, even when the style changes mid-fade.
+    style_gains: [LinearRamp; SoundStyle::ALL.len()],
+    // A scene's shaping of each source, indexed like the style gains.
+    layer_filters: [[LayerFilters; SoundStyle::ALL.len()]; 2],
+    swells: [Swell; SoundStyle::ALL.len()],
+    // 0 while a timed layer's window or spell keeps it out.
+    layer_gates: [LinearRamp; SoundStyle::ALL.len()],
+    // The tone layer skips the EQ and the width blend: binaural beats need
+    // each carrier in exactly one ear. A mode change fades the playing mode
+    // out before the requested one fades in.
+    tone: BeatTone,
+    tone_settings: ToneSettings,
+    tone_playing: ToneMode,
+    tone_gain: LinearRamp,
+    // Pink noise under everything else, one filter per ear fed from that
+    // ear's chain generator. It skips the EQ and the style gains and plays
+    // on through a pause, so a switch or a pause never drops to digital
+    // silence; only a mute or the volume silences it.
+    room: [PinkNoise; 2],
+    room_level: f32,
+    room_gain: LinearRamp,
+    // The master limiter, after the volume and the room tone, so nothing the
+    // mix or a preset does can push the output past it. It holds the true
+    // peak under the [output] threshold, or under the night limit's
+    // ceiling, a linear amplitude that is 1 outside the window, if lower.
+    limiter: PeakLimiter,
+    threshold: f32,
+    ceiling: LinearRamp,
+    // --duck-on-playback's gain, applied to everything but the ceiling.
+    duck: LinearRamp,
+    // The [output] SPL cap as an RMS amplitude, held just before the
+    // limiter, or None while there is no cap or no calibration to read it by.
+    level_cap: LevelCap,
+    spl_cap: Option<f32>,
+    // The drift's band offsets ride on the settings' own room correction,
+    // so the EQ is given `eq_settings` with `drifted` added.
+    drift: Drift,
+    drifted: Drifted,
+    eq_settings: AudioSettings,
+    balance: [LinearRamp; 2],
+    // Largest output magnitude since the last take_peak(), and the least
+    // gain the limiter applied since the last take_reduction().
+    peak: f32,
+    least_gain: f32,
+    // Post-limiter power of both channels, smoothed over OUTPUT_RMS_SECONDS.
+    output_mean_square: f32,
+    output_smoothing: f32,
+}
+
+impl AudioEngine {
+    /// `sample` is a WAV file to loop as the Sample layer; without one that
+    /// layer is silent.
+    pub fn new(
+        sample_rate: f32,
+        settings: AudioSettings,
+        sample: Option<&UserSample>,
+    ) -> Result<Self> {
+        Self::with_seed(sample_rate, settings, sample, None)
+    }
+
+    /// As `new`, but with `seed` every generator in the engine starts from
+    /// it, so two engines given the same seed and settings play the same
+    /// samples.
+    pub fn with_seed(
+        sample_rate: f32,
+        settings: AudioSettings,
+        sample: Option<&UserSample>,
+        seed: Option<u64>,
+    ) -> Result<Self> {
+        ensure!(
+            sample_rate.is_finite() && sample_rate > 0.0,
+            "invalid output sample rate"
+        );
+        let settings = settings.sanitize();
+
+        let headroom = 10_f32.powf(headroom_db(settings) / 20.0);
+        let mut volume = LinearRamp::new(0.0, sample_rate, PARAMETER_RAMP_SECONDS);
+        if !settings.paused && !settings.muted {
+            volume.set_target(settings.volume * headroom);
+        }
+        let rain_player = RainSamplePlayer::embedded(sample_rate)?;
+        let offset_rain = rain_player.half_a_loop_later();
+        let sample_player = sample
+            .map(|sample| RainSamplePlayer::user_sample(sample, sample_rate))
+            .transpose()
+            .context("failed to decode the sample loop (only WAV is supported)")?;
+        let offset_sample = sample_player
+            .as_ref()
+            .map(RainSamplePlayer::half_a_loop_later);
+        let mut tone_gain = LinearRamp::new(0.0, sample_rate, TONE_FADE_SECONDS);
+        tone_gain.set_target(tone_amplitude(settings.tone));
+        let mut room_gain = LinearRamp::new(0.0, sample_rate, PARAMETER_RAMP_SECONDS);
+        if !settings.muted {
+            room_gain.set_target(settings.room_tone.sqrt() * settings.volume);
+        }
+
+        // One draw from the system, or the seed, seeds every generator the
+        // engine keeps.
+        let mut seeds = seed.map_or_else(rand::make_rng, SmallRng::seed_from_u64);
+        let movement = SmallRng::from_rng(&mut seeds);
+        let mut chain = |rain, sample| {
+            let rng = match seed {
+                Some(_) => NoiseGenerator::seeded(noise_rng(), &mut seeds),
+                None => NoiseGenerator::new(noise_rng(), &mut seeds),
+            };
+            SourceChain::new(sample_rate, rng, rain, sample, &movement)
+        };
+        let chains = [
+            chain(rain_player, sample_player),
+            chain(offset_rain, offset_sample),
+        ];
+        let drift = Drift::new(sample_rate, settings.drift, SmallRng::from_rng(&mut seeds));
+        let mut engine = Self {
+            chains,
+            eq: GraphicEq::new(sample_rate, settings),
+            width: LinearRamp::new(settings.stereo_width, sample_rate, PARAMETER_RAMP_SECONDS),
+            blend_width: f32::NAN,
+            blend: (1.0, 0.0),
+            band_widths: BandWidths::new(sample_rate, &settings),
+            crossfeed: Crossfeed::new(sample_rate),
+            crossfeed_amount: LinearRamp::new(
+                crossfeed_amount(settings),
+                sample_rate,
+                STYLE_CROSSFADE_SECONDS,
+            ),
+            bypass: LinearRamp::new(bypass_amount(settings), sample_rate, PARAMETER_RAMP_SECONDS),
+            processed_loudness: LoudnessMeter::new(sample_rate),
+            raw_loudness: LoudnessMeter::new(sample_rate),
+            volume,
+            user_volume: settings.volume,
+            fade_gain: 1.0,
+            headroom,
+            paused: settings.paused,
+            muted: settings.muted,
+            style_gains: SoundStyle::ALL.map(|style| {
+                LinearRamp::new(
+                    settings.mix().level(style),
+                    sample_rate,
+                    STYLE_CROSSFADE_SECONDS,
+                )
+            }),
+            layer_filters: [(); 2].map(|()| {
+                std::array::from_fn(|index| {
+                    let mut filters = LayerFilters::new(sample_rate);
+                    filters.set(settings.layers[index]);
+                    filters
+                })
+            }),
+            swells: settings
+                .layers
+                .map(|shape| Swell::new(sample_rate, shape.modulation)),
+            layer_gates: settings
+                .layers
+                .map(|shape| LinearRamp::new(layer_gate(shape), sample_rate, LAYER_GATE_SECONDS)),
+            tone: BeatTone::new(sample_rate),
+            tone_settings: settings.tone,
+            tone_playing: settings.tone.mode,
+            tone_gain,
+            room: [(); 2].map(|()| PinkNoise::new(sample_rate, ROOM_TONE_FULL_RMS)),
+            room_level: settings.room_tone,
+            room_gain,
+            limiter: PeakLimiter::new(sample_rate, settings.limiter.release_seconds()),
+            threshold: settings.limiter.threshold(),
+            ceiling: LinearRamp::new(
+                ceiling_amplitude(settings),
+                sample_rate,
+                NIGHT_CEILING_SECONDS,
+            ),
+            duck: LinearRamp::new(duck_amplitude(settings), sample_rate, DUCK_SECONDS),
+            level_cap: LevelCap::new(sample_rate),
+            spl_cap: settings.spl.cap_rms(),
+            drift,
+            drifted: Drifted::default(),
+            eq_settings: settings,
+            balance: [(); 2].map(|()| LinearRamp::new(1.0, sample_rate, DRIFT_STEP_SECONDS)),
+            peak: 0.0,
+            least_gain: 1.0,
+            output_mean_square: 0.0,
+            output_smoothing: 1.0 - (-1.0 / (OUTPUT_RMS_SECONDS * sample_rate)).exp(),
+        };
+        for chain in &mut engine.chains {
+            chain.set_loops(settings.loops);
+        }
+        Ok(engine)
+    }
+
+    fn update_settings(&mut self, settings: AudioSettings) {
+        let settings = settings.sanitize();
+        self.eq_settings = settings;
+        self.update_eqs();
+        self.drift.set(settings.drift);
+        for chain in &mut self.chains {
+            chain.set_loops(settings.loops);
+        }
+        self.width.set_target(settings.stereo_width);
+        if let Some(widths) = &mut self.band_widths {
+            widths.set(&settings);
+        }
+        self.crossfeed_amount.set_target(crossfeed_amount(settings));
+        self.bypass.set_target(bypass_amount(settings));
+        self.user_volume = settings.volume;
+        self.headroom = 10_f32.powf(headroom_db(settings) / 20.0);
+        self.paused = settings.paused;
+        self.muted = settings.muted;
+        self.room_level = settings.room_tone;
+        self.volume.set_target(self.volume_target());
+        self.room_gain.set_target(self.room_target());
+        self.threshold = settings.limiter.threshold();
+        self.limiter.set_release(settings.limiter.release_seconds());
+        self.ceiling.set_target(ceiling_amplitude(settings));
+        self.duck.set_target(duck_amplitude(settings));
+        self.spl_cap = settings.spl.cap_rms();
+        for (style, ramp) in SoundStyle::ALL.iter().zip(self.style_gains.iter_mut()) {
+            ramp.set_target(settings.mix().level(*style));
+        }
+        for (index, shape) in settings.layers.into_iter().enumerate() {
+            for filters in &mut self.layer_filters {
+                filters[index].set(shape);
+            }
+            self.swells[index].set(shape.modulation);
+            self.layer_gates[index].set_target(layer_gate(shape));
+        }
+        self.tone_settings = settings.tone;
+        self.tone_gain
+            .set_target(if settings.tone.mode == self.tone_playing {
+                tone_amplitude(settings.tone)
+            } else {
+                0.0
+            });
+    }
+
+    /// Gives the EQ the settings with the drift's band offsets added.
+    fn update_eqs(&mut self) {
+        let mut settings = self.eq_settings;
+        for (correction, offset) in settings
+            .room_correction_db
+            .iter_mut()
+            .zip(self.drifted.bands_db)
+        {
+            *correction += offset;
+        }
+        self.eq.update(settings);
+    }
+
+    /// Takes the drift's next step, if one is due, to the EQ, the balance,
+    /// and the loops.
+    fn step_drift(&mut self) {
+        let Some(drifted) = self.drift.next() else {
+            return;
+        };
+        self.drifted = drifted;
+        self.update_eqs();
+        let half = drifted.balance_db / 2.0;
+        for (ramp, gain_db) in self.balance.iter_mut().zip([-half, half]) {
+            ramp.set_target(10_f32.powf(gain_db / 20.0));
+        }
+        for chain in &mut self.chains {
+            chain.set_speed(drifted.speed);
+        }
+    }
+
+    /// Scales the volume target; the volume ramp smooths each step.
+    fn set_fade_gain(&mut self, gain: f32) {
+        self.fade_gain = if gain.is_finite() {
+            gain.clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        self.volume.set_target(self.volume_target());
+        self.room_gain.set_target(self.room_target());
+    }
+
+    /// Pausing and muting ramp the output to silence like any volume
+    /// change, so neither clicks.
+    fn volume_target(&self) -> f32 {
+        if self.paused || self.muted {
+            0.0
+        } else {
+            self.user_volume * self.fade_gain * self.headroom
+        }
+    }
+
+    /// The room tone follows the volume and the sleep fade but not a pause.
+    fn room_target(&self) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            self.room_level.sqrt() * self.user_volume * self.fade_gain
+        }
+    }
+
+    /// The next frame, rendered as a run of one.
+    pub fn next_frame(&mut self) -> Frame {
+        let mut block = [[0.0; 2]];
+        self.render(&mut block);
+        block[0]
+    }
+
+    /// Renders `block` a run at a time: the sources frame by frame, the EQ
+    /// over the whole run, and then the rest frame by frame again.
+    fn render(&mut self, block: &mut [Frame]) {
+        for run in block.chunks_mut(RENDER_RUN) {
+            // Once a pause has faded out, the sources stop too and resume
+            // from the same place. A mute leaves them running.
+            if self.paused && self.volume.settled() == Some(0.0) {
+                for frame in run {
+                    self.step_drift();
+                    let duck = self.duck.next_value();
+                    let room = self.next_room_tone().map(|sample| sample * duck);
+                    *frame = self.finish(room);
+                }
+                continue;
+            }
+            let mut rooms = [[0.0; 2]; RENDER_RUN];
+            for (frame, room) in run.iter_mut().zip(&mut rooms) {
+                self.step_drift();
+                *frame = self.next_mix();
+                *room = self.next_room_tone();
+            }
+            let mut processed = [[0.0; 2]; RENDER_RUN];
+            let processed = &mut processed[..run.len()];
+            processed.copy_from_slice(run);
+            self.eq.process_block(processed);
+            let mut split = [[0.0; 2]; RENDER_RUN];
+            let split = &mut split[..run.len()];
+            split.copy_from_slice(processed);
+            let splitting = self
+                .band_widths
+                .as_mut()
+                .is_some_and(|widths| widths.process_block(split));
+            for (((frame, processed), split), room) in
+                run.iter_mut().zip(&*processed).zip(&*split).zip(rooms)
+            {
+                let split = splitting.then_some(*split);
+                *frame = self.next_output(*frame, *processed, split, room);
+            }
+        }
+    }
+
+    /// The next frame of the two chains' sources, before the EQ.
+    fn next_mix(&mut self) -> Frame {
+        let mut mixed = [0.0; 2];
+        for (index, (style, ramp)) in SoundStyle::ALL
+            .iter()
+            .zip(self.style_gains.iter_mut())
+            .enumerate()
+        {
+            let gain = ramp.next_value().clamp(0.0, 1.0);
+            let swell = self.swells[index].next();
+            let gate = self.layer_gates[index].next_value();
+            if gain <= 0.0 || gate <= 0.0 {
+                continue;
+            }
+            let amplitude = gain.sqrt() * swell * gate;
+            for ((chain, filters), sum) in self
+                .chains
+                .iter_mut()
+                .zip(&mut self.layer_filters)
+                .zip(&mut mixed)
+            {
+                *sum += filters[index].process(chain.next_sample(*style)) * amplitude;
+            }
+        }
+        mixed
+    }
+
+    /// Everything after the EQ, from the mix before and after it, the
+    /// band widths' mid and side while they play, and the room tone. Left
+    /// is `a·A + b·B` and right is `a·A - b·B` for chains A and B, with
+    /// `a² + b² = 1`: each channel keeps the full level at any width, and
+    /// only their correlation changes.
+    fn next_output(
+        &mut self,
+        mixed: Frame,
+        processed: Frame,
+        split: Option<Frame>,
+        room: Frame,
+    ) -> Frame {
+        let [first, second] = mixed;
+        self.raw_loudness.process(first);
+        self.processed_loudness.process(processed[0]);
+        let bypass = self.bypass.next_value();
+        let [first, second] = if bypass > 0.0 {
+            let raw_gain = self.bypass_match_gain() * bypass;
+            [
+                processed[0] * (1.0 - bypass) + first * raw_gain,
+                processed[1] * (1.0 - bypass) + second * raw_gain,
+            ]
+        } else {
+            processed
+        };
+
+        let width = self.width.next_value();
+        if width != self.blend_width {
+            let angle = width.clamp(0.0, 1.0) * FRAC_PI_4;
+            self.blend = (angle.cos(), angle.sin());
+            self.blend_width = width;
+        }
+        let (common, opposed) = self.blend;
+        let mut blended = [
+            first * common + second * opposed,
+            first * common - second * opposed,
+        ];
+        // The raw sources of a bypass have no bands to widen.
+        if let (Some([mid, side]), Some(widths)) = (split, &mut self.band_widths) {
+            let amount = widths.amount.next_value() * (1.0 - bypass);
+            for (channel, split) in blended.iter_mut().zip([mid + side, mid - side]) {
+                *channel += (split - *channel) * amount;
+            }
+        }
+        let [left, right] = self
+            .crossfeed
+            .process(blended, self.crossfeed_amount.next_value() * (1.0 - bypass));
+        let [left, right] = [
+            left * self.balance[0].next_value(),
+            right * self.balance[1].next_value(),
+        ];
+        let [tone_left, tone_right] = self.next_tone();
+        let volume = self.volume.next_value();
+        let duck = self.duck.next_value();
+        let [room_left, room_right] = room;
+        let frame = [
+            ((left + tone_left) * volume + room_left) * duck,
+            ((right + tone_right) * volume + room_right) * duck,
+        ];
+        self.finish(frame)
+    }
+
+    /// The SPL cap, then the limiter under the threshold or the night
+    /// ceiling, noting the peak and the gain reduction for the meter.
+    fn finish(&mut self, frame: Frame) -> Frame {
+        let frame = self.level_cap.process(frame, self.spl_cap);
+        let threshold = self.threshold.min(self.ceiling.next_value());
+        let frame = self.limiter.process(frame, threshold);
+        self.peak = self.peak.max(frame[0].abs()).max(frame[1].abs());
+        self.least_gain = self.least_gain.min(self.limiter.gain());
+        frame
+    }
+
+    /// Gain that brings the raw mix to the processed mix's K-weighted
+    /// loudness. A silent mix has nothing to match.
+    fn bypass_match_gain(&self) -> f32 {
+        let raw = self.raw_loudness.mean_square();
+        if raw <= f32::MIN_POSITIVE {
+            return 1.0;
+        }
+        (self.processed_loudness.mean_square() / raw)
+            .sqrt()
+            .clamp(1.0 / BYPASS_MATCH_LIMIT, BYPASS_MATCH_LIMIT)
+    }
+
+    fn next_tone(&mut self) -> Frame {
+        let gain = self.tone_gain.next_value();
+        if gain <= 0.0 {
+            // Silent, so a pending mode change can take over without a click.
+            if self.tone_playing != self.tone_settings.mode {
+                self.tone_playing = self.tone_settings.mode;
+                self.tone_gain
+                    .set_target(tone_amplitude(self.tone_settings));
+            }
+            return [0.0; 2];
+        }
+        let ToneSettings {
+            carrier_hz,
+            beat_hz,
+            ..
+        } = self.tone_settings;
+        let frame = match self.tone_playing {
+            ToneMode::Off => return [0.0; 2],
+            ToneMode::Binaural => self.tone.binaural(carrier_hz, beat_hz),
+            ToneMode::Isochronic => self.tone.isochronic(carrier_hz, beat_hz),
+        };
+        frame.map(|sample| sample * gain)
+    }
+
+    fn next_room_tone(&mut self) -> Frame {
+        let gain = self.room_gain.next_value();
+        if gain <= 0.0 {
+            return [0.0; 2];
+        }
+        [0, 1].map(|ear| self.room[ear].process(white(&mut self.chains[ear].rng)) * gain)
+    }
+
+    /// The left channel alone, for mono consumers such as `bench`. It
+    /// carries the full level at any width.
+    pub fn next_sample(&mut self) -> f32 {
+        self.next_frame()[0]
+    }
+
+    fn render_block(&mut self, block: &mut [Frame]) {
+        self.render(block);
+        for frame in block {
+            let [left, right] = *frame;
+            let power = (left * left + right * right) * 0.5;
+            self.output_mean_square += (power - self.output_mean_square) * self.output_smoothing;
+        }
+    }
+
+    fn take_peak(&mut self) -> f32 {
+        std::mem::take(&mut self.peak)
+    }
+
+    /// The limiter's deepest gain reduction since the previous call, in dB.
+    fn take_reduction(&mut self) -> f32 {
+        let gain = std::mem::replace(&mut self.least_gain, 1.0);
+        (-20.0 * gain.max(1e-6).log10()).max(0.0)
+    }
+
+    fn output_rms(&self) -> f32 {
+        self.output_mean_square.sqrt()
+    }
+
+    /// Total state resets forced by non-finite values in any filter stage.
+    fn dsp_resets(&self) -> u32 {
+        std::iter::once(self.eq.resets())
+            .chain(
+                self.band_widths
+                    .as_ref()
+                    .map(|widths| widths.splitter.resets()),
+            )
+            .chain(self.chains.iter().map(SourceChain::resets))
+            .chain(
+                self.layer_filters
+                    .iter()
+                    .flatten()
+                    .map(LayerFilters::resets),
+            )
+            .chain(self.room.iter().map(PinkNoise::resets))
+            .fold(0, u32::wrapping_add)
+    }
+}
+
+fn crossfeed_amount(settings: AudioSettings) -> f32 {
+    if settings.crossfeed { 1.0 } else { 0.0 }
+}
+
+/// Per-band stereo width, as mid/side per band: both chains split at the
+/// band layout's crossovers, each band weighed into the mid (chain A) and
+/// the side (chain B) at its own width's blend, and summed again. While
+/// every band follows the stereo width it fades out and stops, leaving the
+/// plain blend, which costs nothing.
+#[derive(Debug)]
+struct BandWidths {
+    splitter: BandSplitter,
+    // 0 plays the plain blend, 1 the split one.
+    amount: LinearRamp,
+}
+
+impl BandWidths {
+    fn new(sample_rate: f32, settings: &AudioSettings) -> Option<Self> {
+        let crossovers = crossovers_hz(bands(), sample_rate)?;
+        let gains = vec![1.0; crossovers.len() + 1];
+        let mut widths = Self {
+            splitter: BandSplitter::new(sample_rate, &crossovers, &gains),
+            amount: LinearRamp::new(
+                band_widths_amount(settings),
+                sample_rate,
+                PARAMETER_RAMP_SECONDS,
+            ),
+        };
+        widths.set(settings);
+        Some(widths)
+    }
+
+    fn set(&mut self, settings: &AudioSettings) {
+        for band in 0..bands().len() {
+            let angle = settings.band_width(band) * FRAC_PI_4;
+            self.splitter
+                .set_channel_gains(band, [angle.cos(), angle.sin()]);
+        }
+        self.amount.set_target(band_widths_amount(settings));
+    }
+
+    /// Turns a run of the two chains into their mid and side, unless the
+    /// plain blend plays alone; returns whether it did.
+    fn process_block(&mut self, run: &mut [Frame]) -> bool {
+        if self.amount.settled() == Some(0.0) {
+            return false;
+        }
+        self.splitter.process_block(run);
+        true
+    }
+}
+
+fn band_widths_amount(settings: &AudioSettings) -> f32 {
+    if settings.has_band_widths() { 1.0 } else { 0.0 }
+}
+
+fn bypass_amount(settings: AudioSettings) -> f32 {
+    if settings.bypass { 1.0 } else { 0.0 }
+}
+
+fn ceiling_amplitude(settings: AudioSettings) -> f32 {
+    settings
+        .night_ceiling_db
+        .map_or(1.0, |ceiling_db| 10_f32.powf(ceiling_db / 20.0))
+}
+
+/// The ducks for other playback and for calls, the deeper of which
+/// applies, and the ambient auto-level's gain, which all answer to what
+/// goes on around the player and share a ramp.
+fn duck_amplitude(settings: AudioSettings) -> f32 {
+    let duck_db = settings
+        .duck_db
+        .unwrap_or(0.0)
+        .max(settings.call_duck_db.unwrap_or(0.0));
+    let ambient_db = settings.ambient_gain_db.unwrap_or(0.0);
+    10_f32.powf((ambient_db - duck_db) / 20.0)
+}
+
+fn layer_gate(shape: LayerShape) -> f32 {
+    if shape.silenced { 0.0 } else { 1.0 }
+}
+
+/// Fixed-size blocks between the engine and the device. The engine always
+/// renders `block_size` frames at a time, and per-block work (settings
+/// snapshot, fade, metering) runs once per block, however the backend sizes
+/// its callback buffers. Output lags by at most one block.
+#[derive(Debug)]
+struct BlockFifo {
+    block: Vec<Frame>,
+    position: usize,
+}
+
+impl BlockFifo {
+    fn new(block_size: usize) -> Self {
+        let block_size = block_size.max(1);
+        Self {
+            block: vec![[0.0; 2]; block_size],
+            // Start drained so the first read renders a fresh block.
+            position: block_size,
+        }
+    }
+
+    fn next(&mut self, mut render: impl FnMut(&mut [Frame])) -> Frame {
+        if self.position == self.block.len() {
+            render(&mut self.block);
+            self.position = 0;
+        }
+        let frame = self.block[self.position];
+        self.position += 1;
+        frame
+    }
+}
+
+/// Startup choices that stay fixed for the life of an output stream or a
+/// [`Generator`].
+#[derive(Debug, Clone, Copy)]
+pub struct StreamOptions<'a> {
+    /// WAV file to loop as the Sample layer.
+    pub sample: Option<&'a UserSample>,
+    /// Frames the engine renders per internal block.
+    pub block_size: usize,
+    /// Seeds every generator in the engine, so the same seed and settings
+    /// play the same sound; without one each engine sounds different.
+    pub seed: Option<u64>,
+}
+
+impl Default for StreamOptions<'_> {
+    fn default() -> Self {
+        Self {
+            sample: None,
+            block_size: DEFAULT_BLOCK_SIZE,
+            seed: None,
+        }
+    }
+}
+
+/// The whole sound as interleaved samples on demand, for a host that runs
+/// its own output. The output stream plays one of these too.
+#[derive(Debug)]
+pub struct Generator {
+    engine: AudioEngine,
+    fifo: BlockFifo,
+    settings: AudioSettings,
+    channels: usize,
+}
+
+impl Generator {
+    /// A generator for `channels` interleaved channels at `sample_rate`.
+    /// One channel carries the left side at full level; beyond two, even
+    /// channels get the left side and odd ones the right.
+    pub fn new(
+        sample_rate: f32,
+        channels: usize,
+        settings: AudioSettings,
+        options: StreamOptions,
+    ) -> Result<Self> {
+        let settings = settings.sanitize();
+        Ok(Self {
+            engine: AudioEngine::with_seed(sample_rate, settings, options.sample, options.seed)?,
+            fifo: BlockFifo::new(options.block_size),
+            settings,
+            channels: channels.max(1),
+        })
+    }
+
+    pub fn settings(&self) -> AudioSettings {
+        self.settings
+    }
+
+    /// Moves the sound toward `settings`. Levels, EQ, and the other
+    /// controls ramp to their new values instead of jumping.
+    pub fn set_settings(&mut self, settings: AudioSettings) {
+        let settings = settings.sanitize();
+        if settings != self.settings {
+            self.settings = settings;
+            self.engine.update_settings(settings);
+        }
+    }
+
+    /// Fills `buffer` with interleaved samples in [-1, 1]. A sleep timer or
+    /// scheduled fade in the settings fades the sound by the wall clock.
+    pub fn fill_buffer(&mut self, buffer: &mut [f32]) {
+        self.fill_with(buffer, || None, |_, _| {});
+    }
+
+    /// Largest output magnitude since the previous call.
+    pub fn take_peak(&mut self) -> f32 {
+        self.engine.take_peak()
+    }
+
+    /// The limiter's deepest gain reduction in dB since the previous call.
+    pub fn take_reduction(&mut self) -> f32 {
+        self.engine.take_reduction()
+    }
+
+    /// Output RMS of both channels over about the last 300 ms.
+    pub fn output_rms(&self) -> f32 {
+        self.engine.output_rms()
+    }
+
+    /// The left channel of the next frame, for mono consumers such as
+    /// `render`. It comes through the same blocks as `fill_buffer`, so a
+    /// seeded generator's samples match an output stream's sample for
+    /// sample.
+    pub fn next_sample(&mut self) -> f32 {
+        self.next_frame(&mut || None, &mut |_, _| {})[0]
+    }
+
+    /// Fills `data` a block at a time. Before each block `refresh` may hand
+    /// over newer settings, and after it `rendered` sees the engine and the
+    /// block.
+    fn fill_with<T>(
+        &mut self,
+        data: &mut [T],
+        mut refresh: impl FnMut() -> Option<AudioSettings>,
+        mut rendered: impl FnMut(&mut AudioEngine, &[Frame]),
+    ) where
+        T: Sample + FromSample<f32>,
+    {
+        let channels = self.channels;
+        write_interleaved_frames(data, channels, || {
+            self.next_frame(&mut refresh, &mut rendered)
+        });
+    }
+
+    fn next_frame(
+        &mut self,
+        refresh: &mut impl FnMut() -> Option<AudioSettings>,
+        rendered: &mut impl FnMut(&mut AudioEngine, &[Frame]),
+    ) -> Frame {
+        let Self {
+            engine,
+            fifo,
+            settings,
+            ..
+        } = self;
+        fifo.next(|block| {
+            if let Some(current) = refresh().filter(|current| current != settings) {
+                *settings = current;
+                engine.update_settings(current);
+            }
+            let now = clock::now();
+            engine.set_fade_gain(
+                settings.sleep_timer.map_or(1.0, |timer| timer.gain(now))
+                    * settings.schedule_fade.map_or(1.0, |fade| fade.gain(now)),
+            );
+            engine.render_block(block);
+            rendered(engine, block);
+        })
+    }
+}
+
+/// Counters the audio callback publishes for the UI and main threads. Each
+/// field is a lock-free atomic written at most once per buffer.
+#[derive(Debug, Default)]
+pub struct AudioMonitor {
+    dsp_resets: AtomicU32,
+    // f32 bits of the largest output magnitude since the last take_peak(),
+    // and of the deepest limiter gain reduction in dB since the last
+    // take_reduction(). Non-negative floats order the same as their bit
+    // patterns, so fetch_max keeps the loudest buffer.
+    peak: AtomicU32,
+    reduction: AtomicU32,
+    // f32 bits of the engine's output RMS after the latest block.
+    rms: AtomicU32,
+    // Set by the error callback when the device went away or the stream
+    // broke, and cleared once a new stream plays.
+    output_lost: AtomicBool,
+    // Set by the callback when rendering panicked; the output keeper takes
+    // it, counts it, and opens a new stream with a fresh engine.
+    engine_failed: AtomicBool,
+    engine_restarts: AtomicU32,
+    // Set by the silence watchdog when the output stayed silent while it
+    // should have played; the keeper takes it with `engine_failed`.
+    engine_silent: AtomicBool,
+    silence_restarts: AtomicU32,
+    // The fixed buffer size the device refused when the stream last opened,
+    // so it plays with its own; 0 when nothing was refused.
+    refused_buffer: AtomicU32,
+    // Where each rendered block is copied while a recording runs.
+    tap: Tap,
+    // And for the devices that play a copy of the sound.
+    mirrors: MirrorTap,
+}
+
+impl AudioMonitor {
+    /// Largest output magnitude since the previous call, after the limiter.
+    pub fn take_peak(&self) -> f32 {
+        f32::from_bits(self.peak.swap(0, Ordering::Relaxed))
+    }
+
+    /// How far the limiter turned the output down since the previous call,
+    /// in dB at its deepest; 0 when it left the output alone.
+    pub fn take_reduction(&self) -> f32 {
+        f32::from_bits(self.reduction.swap(0, Ordering::Relaxed))
+    }
+
+    /// Post-limiter RMS of both channels over about the last 300 ms.
+    pub fn rms(&self) -> f32 {
+        f32::from_bits(self.rms.load(Ordering::Relaxed))
+    }
+
+    /// Whether the output is gone and being opened again.
+    pub fn output_lost(&self) -> bool {
+        self.output_lost.load(Ordering::Relaxed)
+    }
+
+    pub fn set_output_lost(&self, lost: bool) {
+        self.output_lost.store(lost, Ordering::Relaxed);
+    }
+
+    /// Whether the engine panicked, or the silence watchdog gave up on it,
+    /// since the last call, counting it as a restart of that kind when it
+    /// did. The caller is the one that restarts it.
+    pub fn take_engine_failure(&self) -> bool {
+        let failed = self.engine_failed.swap(false, Ordering::Relaxed);
+        let silent = self.engine_silent.swap(false, Ordering::Relaxed);
+        if failed {
+            self.engine_restarts.fetch_add(1, Ordering::Relaxed);
+        } else if silent {
+            self.silence_restarts.fetch_add(1, Ordering::Relaxed);
+        }
+        failed || silent
+    }
+
+    /// How many times a panicking engine was replaced with a fresh one.
+    pub fn engine_restarts(&self) -> u32 {
+        self.engine_restarts.load(Ordering::Relaxed)
+    }
+
+    /// Asks for a fresh engine because the output stayed silent while the
+    /// settings say it should play.
+    pub fn report_silence(&self) {
+        self.engine_silent.store(true, Ordering::Relaxed);
+    }
+
+    /// How many times a silent engine was replaced with a fresh one.
+    pub fn silence_restarts(&self) -> u32 {
+        self.silence_restarts.load(Ordering::Relaxed)
+    }
+
+    /// The buffer size in frames that the device refused when the playing
+    /// stream was opened, or 0 when it plays with the size asked for.
+    pub fn refused_buffer(&self) -> u32 {
+        self.refused_buffer.load(Ordering::Relaxed)
+    }
+
+    pub fn set_refused_buffer(&self, frames: u32) {
+        self.refused_buffer.store(frames, Ordering::Relaxed);
+    }
+
+    /// Where a `Recording` takes what the callback renders.
+    pub fn tap(&self) -> &Tap {
+        &self.tap
+    }
+
+    /// Where a mirrored device takes what the callback renders.
+    pub fn mirrors(&self) -> &MirrorTap {
+        &self.mirrors
+    }
+
+    /// How many times a filter stage produced NaN/Inf and had its state reset.
+    pub fn dsp_resets(&self) -> u32 {
+        self.dsp_resets.load(Ordering::Relaxed)
+    }
+
+    fn publish(&self, engine: &mut AudioEngine) {
+        self.dsp_resets
+            .store(engine.dsp_resets(), Ordering::Relaxed);
+        self.peak
+            .fetch_max(engine.take_peak().to_bits(), Ordering::Relaxed);
+        self.reduction
+            .fetch_max(engine.take_reduction().to_bits(), Ordering::Relaxed);
+        self.rms
+            .store(engine.output_rms().to_bits(), Ordering::Relaxed);
+    }
+}
+
+pub fn build_output_stream(
+    device: &Device,
+    config: StreamConfig,
+    sample_format: SampleFormat,
+    settings: Arc<SharedSettings>,
+    running: Arc<AtomicBool>,
+    monitor: Arc<AudioMonitor>,
+    options: StreamOptions,
+) -> Result<Stream> {
+    let callback = DeviceCallback::new(
+        config.sample_rate as f32,
+        usize::from(config.channels),
+        settings,
+        running,
+        Arc::clone(&monitor),
+        options,
+    )?;
+    open_stream(device, config, sample_format, callback, move || {
+        monitor.set_output_lost(true);
+    })
+}
+
+/// What a stream's callback does with each buffer the device hands it, in
+/// whichever sample format the device plays.
+pub(crate) trait FillOutput: Send + 'static {
+    fn fill<T>(&mut self, data: &mut [T])
+    where
+        T: Sample + FromSample<f32>;
+}
+
+/// Opens a stream on `device` that `callback` fills, calling `lost` from
+/// the error callback when the stream no longer plays.
+pub(crate) fn open_stream<C: FillOutput>(
+    device: &Device,
+    config: StreamConfig,
+    sample_format: SampleFormat,
+    callback: C,
+    lost: impl Fn() + Send + 'static,
 ) -> Result<Stream> {
     match sample_format {
-        SampleFormat::I8 => build_typed_stream::<i8>(device, config, settings, running),
-        SampleFormat::I16 => build_typed_stream::<i16>(device, config, settings, running),
-        SampleFormat::I24 => build_typed_stream::<I24>(device, config, settings, running),
-        SampleFormat::I32 => build_typed_stream::<i32>(device, config, settings, running),
-        SampleFormat::I64 => build_typed_stream::<i64>(device, config, settings, running),
-        SampleFormat::U8 => build_typed_stream::<u8>(device, config, settings, running),
-        SampleFormat::U16 => build_typed_stream::<u16>(device, config, settings, running),
-        SampleFormat::U24 => build_typed_stream::<U24>(device, config, settings, running),
-        SampleFormat::U32 => build_typed_stream::<u32>(device, config, settings, running),
-        SampleFormat::U64 => build_typed_stream::<u64>(device, config, settings, running),
-        SampleFormat::F32 => build_typed_stream::<f32>(device, config, settings, running),
-        SampleFormat::F64 => build_typed_stream::<f64>(device, config, settings, running),
+        SampleFormat::I8 => build_typed_stream::<i8, C>(device, config, callback, lost),
+        SampleFormat::I16 => build_typed_stream::<i16, C>(device, config, callback, lost),
+        SampleFormat::I24 => build_typed_stream::<I24, C>(device, config, callback, lost),
+        SampleFormat::I32 => build_typed_stream::<i32, C>(device, config, callback, lost),
+        SampleFormat::I64 => build_typed_stream::<i64, C>(device, config, callback, lost),
+        SampleFormat::U8 => build_typed_stream::<u8, C>(device, config, callback, lost),
+        SampleFormat::U16 => build_typed_stream::<u16, C>(device, config, callback, lost),
+        SampleFormat::U24 => build_typed_stream::<U24, C>(device, config, callback, lost),
+        SampleFormat::U32 => build_typed_stream::<u32, C>(device, config, callback, lost),
+        SampleFormat::U64 => build_typed_stream::<u64, C>(device, config, callback, lost),
+        SampleFormat::F32 => build_typed_stream::<f32, C>(device, config, callback, lost),
+        SampleFormat::F64 => build_typed_stream::<f64, C>(device, config, callback, lost),
         SampleFormat::DsdU8 | SampleFormat::DsdU16 | SampleFormat::DsdU32 => {
             bail!("DSD output formats are not supported")
         }
@@ -660,93 +2449,285 @@ pub fn build_output_stream(
     }
 }
 
-fn build_typed_stream<T>(
+fn build_typed_stream<T, C>(
     device: &Device,
     config: StreamConfig,
-    settings: Arc<Mutex<AudioSettings>>,
-    running: Arc<AtomicBool>,
+    mut callback: C,
+    lost: impl Fn() + Send + 'static,
 ) -> Result<Stream>
 where
     T: SizedSample + FromSample<f32>,
+    C: FillOutput,
 {
-    let channels = usize::from(config.channels).max(1);
-    let initial_settings = settings
-        .lock()
-        .unwrap_or_else(|poisoned| poisoned.into_inner())
-        .sanitize();
-    let mut latest_settings = initial_settings;
-    let mut engine = AudioEngine::new(config.sample_rate as f32, initial_settings)?;
-    let audio_running = Arc::clone(&running);
-    let error_running = Arc::clone(&running);
-
     device
         .build_output_stream::<T, _, _>(
             config,
-            move |data, _| {
-                if !audio_running.load(Ordering::Relaxed) {
-                    data.fill(T::from_sample(0.0));
-                    return;
+            move |data, _| callback.fill(data),
+            // The output keeper opens a new stream; printing here would
+            // scroll over the interface.
+            move |error| {
+                if loses_output(&error) {
+                    lost();
                 }
+            },
+            None,
+        )
+        .context("failed to open the output audio stream")
+}
 
-                // Never wait for the UI thread from the real-time callback. If it is
-                // updating a setting, use the previous snapshot for this buffer.
-                if let Ok(current) = settings.try_lock() {
-                    let current = current.sanitize();
-                    if current != latest_settings {
-                        latest_settings = current;
-                        engine.update_settings(current);
-                    }
-                }
+/// What an output's callback does with each buffer it is handed, on a
+/// device or a stand-in for one: the generator's next samples under the
+/// latest settings, published to the monitor, or silence once playback
+/// stops or the engine has panicked.
+pub(crate) struct DeviceCallback {
+    generator: Generator,
+    sample_rate: f32,
+    settings: Arc<SharedSettings>,
+    // The version of the settings the generator last took.
+    seen: u64,
+    running: Arc<AtomicBool>,
+    monitor: Arc<AudioMonitor>,
+    failed: bool,
+}
+
+impl DeviceCallback {
+    pub(crate) fn new(
+        sample_rate: f32,
+        channels: usize,
+        settings: Arc<SharedSettings>,
+        running: Arc<AtomicBool>,
+        monitor: Arc<AudioMonitor>,
+        options: StreamOptions,
+    ) -> Result<Self> {
+        let mut seen = 0;
+        let initial_settings = settings
+            .changed_since(&mut seen)
+            .unwrap_or_else(|| settings.snapshot());
+        Ok(Self {
+            generator: Generator::new(sample_rate, channels, initial_settings, options)?,
+            sample_rate,
+            settings,
+            seen,
+            running,
+            monitor,
+            failed: false,
+        })
+    }
+}
+
+impl FillOutput for DeviceCallback {
+    fn fill<T>(&mut self, data: &mut [T])
+    where
+        T: Sample + FromSample<f32>,
+    {
+        if self.failed || !self.running.load(Ordering::Relaxed) {
+            data.fill(T::from_sample(0.0));
+            return;
+        }
+        let Self {
+            generator,
+            sample_rate,
+            settings,
+            seen,
+            monitor,
+            failed,
+            ..
+        } = self;
+        *failed = !fill_guarded(data, monitor, |data| {
+            generator.fill_with(
+                data,
+                || snapshot(settings, seen),
+                |engine, block| {
+                    monitor.publish(engine);
+                    monitor.tap.push(block, *sample_rate);
+                    monitor.mirrors.push(block, *sample_rate);
+                },
+            );
+        });
+    }
+}
+
+/// Runs `fill`, catching a panic at the callback boundary so a DSP bug
+/// cannot take the process down with it. A panicked fill leaves `data`
+/// silent and tells the output keeper, which replaces the stream and its
+/// engine; the panic message itself goes to stderr through the panic hook.
+/// Returns false after a panic, when the engine must not be used again.
+fn fill_guarded<T>(data: &mut [T], monitor: &AudioMonitor, fill: impl FnOnce(&mut [T])) -> bool
+where
+    T: Sample + FromSample<f32>,
+{
+    // The engine is dropped, never reused, after a panic, so a half-done
+    // update in it cannot be seen.
+    if panic::catch_unwind(AssertUnwindSafe(|| fill(data))).is_ok() {
+        return true;
+    }
+    data.fill(T::from_sample(0.0));
+    monitor.engine_failed.store(true, Ordering::Relaxed);
+    false
+}
 
-                write_interleaved_frames(data, channels, || engine.next_sample());
-            },
-            move |error| {
-                eprintln!("audio stream error: {error}");
-                error_running.store(false, Ordering::Relaxed);
-            },
-            None,
-        )
-        .context("failed to open the output audio stream")
+/// Whether a stream error means the stream no longer plays. An xrun is a
+/// dropout the stream recovers from by itself, a changed device means the
+/// host already rerouted the stream, and a refused real-time priority
+/// leaves it playing at normal priority; opening a new stream for any of
+/// them would only add a gap.
+fn loses_output(error: &cpal::Error) -> bool {
+    !matches!(
+        error.kind(),
+        ErrorKind::Xrun | ErrorKind::DeviceChanged | ErrorKind::RealtimeDenied
+    )
+}
+
+/// The settings a writer published since `seen`, or `None` while none
+/// did, so the real-time callback keeps its previous snapshot. It never
+/// waits on the settings lock, however long a writer holds it; a writer that
+/// panicked under the lock has still published what it had set.
+fn snapshot(settings: &SharedSettings, seen: &mut u64) -> Option<AudioSettings> {
+    settings
+        .changed_since(seen)
+        .map(|current| current.sanitize())
 }
 
-fn write_interleaved_frames<T, F>(data: &mut [T], channels: usize, mut next_sample: F)
+/// Mono devices get the left channel, which carries the full level on its
+/// own. Beyond two channels, even slots get left and odd slots get right,
+/// which matches the front pair and alternates over the rest.
+pub(crate) fn write_interleaved_frames<T, F>(data: &mut [T], channels: usize, mut next_frame: F)
 where
     T: Sample + FromSample<f32>,
-    F: FnMut() -> f32,
+    F: FnMut() -> Frame,
 {
     for frame in data.chunks_mut(channels.max(1)) {
-        let sample = T::from_sample(next_sample());
-        frame.fill(sample);
+        let stereo = next_frame().map(T::from_sample);
+        for (index, slot) in frame.iter_mut().enumerate() {
+            *slot = stereo[index % 2];
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::settings::SourceMix;
+    use crate::settings::{LimiterSettings, SourceMix, SplCalibration};
     use rand::SeedableRng;
 
     #[test]
-    fn one_generator_sample_is_written_per_audio_frame() {
-        let mut output = [0.0_f32; 8];
+    fn one_engine_frame_is_written_per_audio_frame() {
+        let counter = || {
+            let mut next = 0.0;
+            move || {
+                next += 1.0;
+                [next, -next]
+            }
+        };
+        let mut stereo = [0.0_f32; 8];
+        write_interleaved_frames(&mut stereo, 2, counter());
+        assert_eq!(stereo, [1.0, -1.0, 2.0, -2.0, 3.0, -3.0, 4.0, -4.0]);
+
+        let mut mono = [0.0_f32; 3];
+        write_interleaved_frames(&mut mono, 1, counter());
+        assert_eq!(mono, [1.0, 2.0, 3.0]);
+
+        let mut quad = [0.0_f32; 8];
+        write_interleaved_frames(&mut quad, 4, counter());
+        assert_eq!(quad, [1.0, -1.0, 1.0, -1.0, 2.0, -2.0, 2.0, -2.0]);
+    }
+
+    #[test]
+    fn a_panicking_fill_is_silenced_and_reported_once() {
+        let monitor = AudioMonitor::default();
+        let mut data = [0.5_f32; 8];
+        assert!(fill_guarded(&mut data, &monitor, |data| data.fill(0.25)));
+        assert_eq!(data, [0.25; 8]);
+        assert!(!monitor.take_engine_failure());
+
+        assert!(!fill_guarded(&mut data, &monitor, |data| {
+            data[0] = 1.0;
+            panic!("a DSP bug");
+        }));
+        assert_eq!(data, [0.0; 8]);
+        assert_eq!(monitor.engine_restarts(), 0);
+        assert!(monitor.take_engine_failure());
+        assert!(!monitor.take_engine_failure());
+        monitor.report_silence();
+        assert!(monitor.take_engine_failure());
+        assert!(!monitor.take_engine_failure());
+        assert_eq!(monitor.silence_restarts(), 1);
+        assert_eq!(monitor.engine_restarts(), 1);
+    }
+
+    #[test]
+    fn settings_snapshots_never_wait_on_the_lock_and_survive_a_panicked_writer() {
+        let settings = Arc::new(SharedSettings::new(AudioSettings {
+            volume: 0.3,
+            ..AudioSettings::default()
+        }));
+        let mut seen = 0;
+        assert_eq!(snapshot(&settings, &mut seen), None);
+
+        // A writer halfway through a change holds the lock, and the callback
+        // reads the settings published before it without waiting.
+        let mut held = settings.lock().unwrap();
+        held.volume = 0.5;
+        assert_eq!(snapshot(&settings, &mut seen), None);
+        assert_eq!(settings.snapshot().volume, 0.3);
+        drop(held);
+        assert_eq!(snapshot(&settings, &mut seen).map(|s| s.volume), Some(0.5));
+        assert_eq!(snapshot(&settings, &mut seen), None);
+        // A guard that only read publishes nothing new.
+        assert_eq!(settings.lock().unwrap().volume, 0.5);
+        assert_eq!(snapshot(&settings, &mut seen), None);
+
+        let poisoner = Arc::clone(&settings);
+        let _ = std::thread::spawn(move || {
+            let mut locked = poisoner.lock().unwrap();
+            locked.volume = 0.6;
+            panic!("poison the settings lock");
+        })
+        .join();
+        assert!(settings.is_poisoned());
+        assert_eq!(snapshot(&settings, &mut seen).map(|s| s.volume), Some(0.6));
+    }
+
+    #[test]
+    fn fifo_renders_whole_blocks_whatever_the_callback_size() {
+        let mut fifo = BlockFifo::new(64);
+        let mut blocks = 0;
         let mut next = 0.0;
-        write_interleaved_frames(&mut output, 2, || {
-            next += 1.0;
-            next
-        });
+        let mut read = Vec::new();
+        for callback_frames in [100, 37, 1, 256, 30] {
+            let mut output = vec![0.0_f32; callback_frames * 2];
+            write_interleaved_frames(&mut output, 2, || {
+                fifo.next(|block| {
+                    assert_eq!(block.len(), 64);
+                    blocks += 1;
+                    for frame in block {
+                        next += 1.0;
+                        *frame = [next, -next];
+                    }
+                })
+            });
+            read.extend(output.chunks(2).map(|frame| frame[0]));
+        }
 
-        assert_eq!(output, [1.0, 1.0, 2.0, 2.0, 3.0, 3.0, 4.0, 4.0]);
+        // 424 frames need seven 64-frame blocks, and order is preserved.
+        assert_eq!(blocks, 7);
+        assert!(
+            read.iter()
+                .enumerate()
+                .all(|(index, sample)| *sample == (index + 1) as f32)
+        );
+        assert_eq!(BlockFifo::new(0).block.len(), 1);
     }
 
     #[test]
     fn output_is_converted_to_integer_pcm() {
         let mut signed = [0_i16; 4];
-        write_interleaved_frames(&mut signed, 2, || 0.5);
+        write_interleaved_frames(&mut signed, 2, || [0.5; 2]);
         assert!(signed.iter().all(|sample| *sample > 16_000));
         assert!(signed.windows(2).all(|pair| pair[0] == pair[1]));
 
         let mut unsigned = [0_u16; 4];
-        write_interleaved_frames(&mut unsigned, 2, || 0.0);
+        write_interleaved_frames(&mut unsigned, 2, || [0.0; 2]);
         assert_eq!(unsigned, [32_768; 4]);
     }
 
@@ -761,34 +2742,802 @@ mod tests {
         assert!(player.normalization_gain <= 8.0);
     }
 
+    #[test]
+    fn user_sample_is_downmixed_resampled_and_looped() {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 22_050,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut wav = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut wav, spec).unwrap();
+        for frame in 0..22_050 {
+            let tone = (frame as f32 * 0.05).sin() * 8_000.0;
+            writer.write_sample(tone as i16).unwrap();
+            writer.write_sample((tone * 0.5) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        let data = wav.into_inner();
+
+        let mut player = RainSamplePlayer::from_wav(&data, 48_000.0).unwrap();
+        let mut rng = SmallRng::seed_from_u64(1);
+        assert_eq!(player.source_sample_rate, 22_050);
+        assert_eq!(player.samples.len(), 22_050);
+        let expected = ((0.05f32).sin() * 8_000.0) as i16 as f32 * 0.75 / 32_768.0;
+        assert!((player.samples[1] - expected).abs() < 1e-4);
+
+        // Three passes through a one-second loop, crossfade included.
+        for _ in 0..3 * 48_000 {
+            assert!(player.next_sample(&mut rng).is_finite());
+        }
+        assert!(player.position < 22_050.0);
+
+        let sample = UserSample::measure(data).unwrap();
+        let mut engine =
+            AudioEngine::new(48_000.0, AudioSettings::default(), Some(&sample)).unwrap();
+        assert!(engine.next_sample().is_finite());
+        assert!(UserSample::measure(b"not a wav".to_vec()).is_err());
+        let unreadable = UserSample {
+            data: b"not a wav".to_vec(),
+            level: sample.level,
+        };
+        assert!(AudioEngine::new(48_000.0, AudioSettings::default(), Some(&unreadable)).is_err());
+    }
+
+    #[test]
+    fn user_samples_play_at_the_rains_loudness_whatever_their_level() {
+        let rain = RainSamplePlayer::embedded(48_000.0).unwrap();
+        let rain_lufs =
+            SampleLevel::of(&rain).loudness_lufs + 20.0 * rain.normalization_gain.log10();
+        assert!((rain_lufs - SAMPLE_TARGET_LUFS).abs() < 0.1, "{rain_lufs}");
+
+        // The same hiss recorded 30 dB apart, and a bass-heavy hum whose RMS
+        // overstates how loud it sounds.
+        let wav = |amplitude: f32, hz: f32| {
+            let spec = hound::WavSpec {
+                channels: 1,
+                sample_rate: 44_100,
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            };
+            let mut rng = SmallRng::seed_from_u64(7);
+            let mut wav = Cursor::new(Vec::new());
+            let mut writer = hound::WavWriter::new(&mut wav, spec).unwrap();
+            for frame in 0..44_100 * 4 {
+                let value = if hz > 0.0 {
+                    (frame as f32 * hz * std::f32::consts::TAU / 44_100.0).sin()
+                } else {
+                    rng.random_range(-1.0..1.0)
+                };
+                writer
+                    .write_sample((value * amplitude * 32_767.0) as i16)
+                    .unwrap();
+            }
+            writer.finalize().unwrap();
+            wav.into_inner()
+        };
+        let loud = SampleLevel::measure(&wav(0.5, 0.0)).unwrap();
+        let quiet = SampleLevel::measure(&wav(0.5 / 31.62, 0.0)).unwrap();
+        assert!((quiet.loudness_lufs - loud.loudness_lufs + 30.0).abs() < 0.2);
+        assert!((quiet.gain_db - loud.gain_db - 30.0).abs() < 0.2);
+        for data in [wav(0.5, 0.0), wav(0.5 / 31.62, 0.0), wav(0.2, 60.0)] {
+            let sample = UserSample::measure(data).unwrap();
+            let level = sample.level;
+            assert!((level.loudness_lufs + level.gain_db - SAMPLE_TARGET_LUFS).abs() < 0.01);
+            let player = RainSamplePlayer::user_sample(&sample, 48_000.0).unwrap();
+            assert!((20.0 * player.normalization_gain.log10() - level.gain_db).abs() < 0.01);
+        }
+        // A near-silent file is boosted no further than the cap.
+        assert_eq!(
+            SampleLevel::measure(&wav(0.0002, 0.0)).unwrap().gain_db,
+            MAX_SAMPLE_GAIN_DB
+        );
+    }
+
+    #[test]
+    fn a_new_loop_crossfade_waits_out_the_fade_and_fits_the_loop() {
+        let limits = loop_crossfade_limits(None).unwrap();
+        assert_eq!(limits.rain_crossfade_seconds, 5.0);
+        assert_eq!(limits.sample_crossfade_seconds, MAX_LOOP_CROSSFADE_SECONDS);
+
+        // At the recording's own rate each output sample is one frame.
+        let mut player = RainSamplePlayer::embedded(44_100.0).unwrap();
+        let mut rng = SmallRng::seed_from_u64(1);
+        let frames = player.samples.len();
+        for _ in 0..frames - 44_100 {
+            player.next_sample(&mut rng);
+        }
+        player.set_crossfade(4.0);
+        player.next_sample(&mut rng);
+        assert_eq!(player.crossfade_samples, 44_100 * 2, "changed mid-fade");
+        for _ in 0..44_100 {
+            player.next_sample(&mut rng);
+        }
+        assert_eq!(player.crossfade_samples, 44_100 * 4);
+        player.set_crossfade(MAX_LOOP_CROSSFADE_SECONDS);
+        player.next_sample(&mut rng);
+        assert_eq!(player.crossfade_samples, frames / 3);
+
+        let mut settings = AudioSettings::default();
+        settings.set_mix(SourceMix::solo(SoundStyle::Rain));
+        settings.loops.rain_crossfade_seconds = 3.0;
+        let mut engine = AudioEngine::new(44_100.0, settings, None).unwrap();
+        engine.next_sample();
+        for chain in &engine.chains {
+            assert_eq!(chain.rain_player.crossfade_samples, 44_100 * 3);
+        }
+    }
+
+    #[test]
+    fn a_shuffled_sample_jumps_around_the_loop_without_clicks() {
+        let sine = |rate: u32, seconds: u32| {
+            let spec = hound::WavSpec {
+                channels: 1,
+                sample_rate: rate,
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            };
+            let mut wav = Cursor::new(Vec::new());
+            let mut writer = hound::WavWriter::new(&mut wav, spec).unwrap();
+            for frame in 0..rate * seconds {
+                let phase = frame as f32 * 50.0 * std::f32::consts::TAU / rate as f32;
+                writer.write_sample((phase.sin() * 8_000.0) as i16).unwrap();
+            }
+            writer.finalize().unwrap();
+            UserSample::measure(wav.into_inner()).unwrap()
+        };
+        let sample = sine(8_000, 6);
+        let mut player = RainSamplePlayer::user_sample(&sample, 8_000.0).unwrap();
+        let mut rng = SmallRng::seed_from_u64(3);
+        player.set_crossfade(0.5);
+        player.set_shuffle(true);
+
+        let length = player.samples.len() as f64;
+        let mut jumps = Vec::new();
+        let mut previous = player.next_sample(&mut rng);
+        let (mut peak, mut largest_step) = (0.0_f32, 0.0_f32);
+        for _ in 0..8_000 * 120 {
+            let jump_to = player.jump_to;
+            let sample = player.next_sample(&mut rng);
+            peak = peak.max(sample.abs());
+            largest_step = largest_step.max((sample - previous).abs());
+            previous = sample;
+            if player.jump_to != jump_to {
+                jumps.push(player.jump_to);
+            }
+        }
+        // A hard cut between two phases of the sine could step by twice the
+        // peak; the crossfades keep every step near the sine's own.
+        assert!(largest_step < 0.1 * peak, "{largest_step} against {peak}");
+        assert!(jumps.len() > 20, "{}", jumps.len());
+        assert!(jumps.iter().any(|start| *start < length / 4.0));
+        assert!(jumps.iter().any(|start| *start > length / 2.0));
+        assert!(jumps.iter().all(|start| *start <= length - 3.0 * 4_000.0));
+
+        // Off again, the stretch playing finishes and the loop goes back to
+        // repeating from its start.
+        player.set_shuffle(false);
+        for _ in 0..8_000 * 12 {
+            player.next_sample(&mut rng);
+        }
+        assert_eq!(
+            (player.jump_to, player.segment_end),
+            (player.loop_start as f64, player.loop_end as f64)
+        );
+
+        // A loop long enough not to need it ignores the setting.
+        let mut long = RainSamplePlayer::user_sample(&sine(1_000, 31), 48_000.0).unwrap();
+        long.set_shuffle(true);
+        assert!(!long.shuffle);
+
+        let mut settings = AudioSettings::default();
+        settings.loops.sample_shuffle = true;
+        let engine = AudioEngine::new(8_000.0, settings, Some(&sample)).unwrap();
+        assert!(engine.chains.iter().all(
+            |chain| chain.sample_player.as_ref().unwrap().shuffle && !chain.rain_player.shuffle
+        ));
+        settings.loops.rain_shuffle = true;
+        let engine = AudioEngine::new(8_000.0, settings, None).unwrap();
+        assert!(engine.chains.iter().all(|chain| chain.rain_player.shuffle));
+    }
+
+    #[test]
+    fn loops_splice_where_their_ends_match() {
+        // 50.3 Hz never fits the file a whole number of times, so looping
+        // it whole would step at the seam.
+        let rate = 8_000;
+        let tone: Vec<f32> = (0..rate * 4)
+            .map(|frame| (frame as f32 * 50.3 * std::f32::consts::TAU / rate as f32).sin())
+            .collect();
+        let (start, end) = loop_points(&tone, rate as u32);
+        assert!(
+            start < tone.len() / 8 && end > tone.len() * 7 / 8,
+            "{start} {end}"
+        );
+        assert!(tone[start - 1] < 0.0 && tone[start] >= 0.0);
+        assert!(tone[end - 1] < 0.0 && tone[end] >= 0.0);
+        for offset in 0..40 {
+            assert!((tone[start + offset] - tone[end + offset]).abs() < 0.05);
+        }
+        // A quieter tail is left out of the loop rather than spliced onto
+        // the loud start.
+        let mut stepped = tone.clone();
+        let quiet_from = tone.len() - rate / 8;
+        for sample in &mut stepped[quiet_from..] {
+            *sample *= 0.25;
+        }
+        let (_, end) = loop_points(&stepped, rate as u32);
+        assert!(end < quiet_from, "{end}");
+        assert_eq!(loop_points(&tone[..64], rate as u32), (0, 64));
+
+        // The embedded rain loops inside its last second.
+        let rain = RainSamplePlayer::embedded(48_000.0).unwrap();
+        assert!(rain.loop_start < 44_100 && rain.loop_end > rain.samples.len() - 44_100);
+    }
+
+    #[test]
+    fn a_fade_between_alike_stretches_does_not_swell() {
+        // A whole number of cycles, so the stretches the fade joins are the
+        // same: an equal-power fade would peak 3 dB up at its middle.
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 8_000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut wav = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut wav, spec).unwrap();
+        for frame in 0..8_000 * 6 {
+            let phase = frame as f32 * 50.0 * std::f32::consts::TAU / 8_000.0;
+            writer.write_sample((phase.sin() * 8_000.0) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        let sample = UserSample::measure(wav.into_inner()).unwrap();
+        let mut player = RainSamplePlayer::user_sample(&sample, 8_000.0).unwrap();
+        assert!(
+            player.fade_correlation > 0.99,
+            "{}",
+            player.fade_correlation
+        );
+        let mut rng = SmallRng::seed_from_u64(1);
+        let peaks: Vec<f32> = (0..3 * 60)
+            .map(|_| {
+                (0..400)
+                    .map(|_| player.next_sample(&mut rng).abs())
+                    .fold(0.0, f32::max)
+            })
+            .collect();
+        let steady = peaks[0];
+        assert!(
+            peaks.iter().all(|peak| (peak / steady - 1.0).abs() < 0.05),
+            "{peaks:?}"
+        );
+    }
+
+    #[test]
+    fn sample_layer_plays_beside_rain_and_is_silent_without_a_loop() {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 48_000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut wav = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut wav, spec).unwrap();
+        for frame in 0..48_000 {
+            let phase = frame as f32 * 220.0 * std::f32::consts::TAU / 48_000.0;
+            writer.write_sample((phase.sin() * 8_000.0) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        let sample = UserSample::measure(wav.into_inner()).unwrap();
+
+        let render = |mix: SourceMix, sample: Option<&UserSample>| -> Vec<f32> {
+            let mut settings = AudioSettings {
+                volume: 0.5,
+                ..AudioSettings::default()
+            };
+            settings.set_mix(mix);
+            let mut engine = AudioEngine::new(48_000.0, settings, sample).unwrap();
+            (0..96_000).map(|_| engine.next_sample()).collect()
+        };
+
+        let unloaded = render(SourceMix::solo(SoundStyle::Sample), None);
+        assert!(unloaded.iter().all(|sample| *sample == 0.0));
+
+        // Rain and the loop are both deterministic, and at this volume the
+        // limiter stays linear, so the layered output is their sum to within
+        // the EQ's rounding.
+        let mut rain = silent_mix();
+        rain.set_level(SoundStyle::Rain, 0.5);
+        let mut looped = silent_mix();
+        looped.set_level(SoundStyle::Sample, 0.5);
+        let mut both = rain;
+        both.set_level(SoundStyle::Sample, 0.5);
+        let (rain, looped, both) = (
+            render(rain, Some(&sample)),
+            render(looped, Some(&sample)),
+            render(both, Some(&sample)),
+        );
+        let loop_rms =
+            (looped.iter().map(|sample| sample * sample).sum::<f32>() / looped.len() as f32).sqrt();
+        assert!(loop_rms > 0.01, "sample layer RMS was {loop_rms}");
+        for ((both, rain), looped) in both.iter().zip(&rain).zip(&looped) {
+            assert!((both - rain - looped).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn drift_wanders_within_its_depth_and_eases_back_when_turned_off() {
+        let on = DriftSettings {
+            enabled: true,
+            depth: 1.0,
+            period_seconds: 10.0,
+        };
+        let mut drift = Drift::new(1_000.0, on, SmallRng::seed_from_u64(4));
+        let steps: Vec<Drifted> = (0..1_000 * 120).filter_map(|_| drift.next()).collect();
+        assert_eq!(steps.len(), 4 * 120);
+        let bands: Vec<f32> = steps.iter().flat_map(|step| step.bands_db).collect();
+        assert!(bands.iter().all(|db| db.abs() <= DRIFT_BAND_DB));
+        assert!(bands.iter().any(|db| *db > 1.5) && bands.iter().any(|db| *db < -1.5));
+        assert!(
+            steps
+                .iter()
+                .all(|step| step.balance_db.abs() <= DRIFT_BALANCE_DB)
+        );
+        assert!(
+            steps
+                .iter()
+                .all(|step| (step.speed - 1.0).abs() <= DRIFT_SPEED)
+        );
+        // A quarter second never moves a band by more than a fraction of
+        // a dB, so the EQ's glide hides each step.
+        for pair in steps.windows(2) {
+            for (before, after) in pair[0].bands_db.iter().zip(pair[1].bands_db) {
+                assert!((after - before).abs() < 0.3, "{before} to {after}");
+            }
+        }
+
+        drift.set(DriftSettings {
+            enabled: false,
+            ..on
+        });
+        let easing: Vec<Drifted> = (0..1_000 * 10).filter_map(|_| drift.next()).collect();
+        assert!(easing.len() > 4 * 4, "eased over {} steps", easing.len());
+        assert_eq!(easing.last(), Some(&Drifted::default()));
+        assert!((0..1_000 * 2).all(|_| drift.next().is_none()));
+    }
+
+    #[test]
+    fn every_noise_generator_draws_the_same_white_noise() {
+        let mut seeds = SmallRng::seed_from_u64(8);
+        for kind in [NoiseRng::Small, NoiseRng::Xoshiro, NoiseRng::Chacha] {
+            let mut rng = NoiseGenerator::new(kind, &mut seeds);
+            let samples: Vec<f64> = (0..200_000).map(|_| f64::from(white(&mut rng))).collect();
+            let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+            let power =
+                samples.iter().map(|sample| sample * sample).sum::<f64>() / samples.len() as f64;
+            let lag_one = samples
+                .windows(2)
+                .map(|pair| pair[0] * pair[1])
+                .sum::<f64>()
+                / samples.len() as f64;
+            assert!(mean.abs() < 0.005, "{kind:?} mean {mean}");
+            assert!((power - 1.0 / 3.0).abs() < 0.005, "{kind:?} power {power}");
+            assert!(lag_one.abs() < 0.005, "{kind:?} correlation {lag_one}");
+        }
+    }
+
     #[test]
     fn rain_resampling_advances_once_per_target_frame() {
         let mut player = RainSamplePlayer::embedded(48_000.0).unwrap();
+        let mut rng = SmallRng::seed_from_u64(1);
         for _ in 0..48_000 {
-            player.next_sample();
+            player.next_sample(&mut rng);
         }
 
         assert!((player.position - 44_100.0).abs() < 0.01);
     }
 
+    #[test]
+    fn band_contribution_follows_the_source_spectrum() {
+        let neutral = AudioSettings::default();
+        for index in 0..FREQUENCY_BANDS.len() {
+            assert!(band_rms_contribution_db(neutral, index).abs() < 1e-6);
+        }
+
+        let boosted = |style, index| {
+            let mut settings = AudioSettings::default();
+            settings.set_mix(SourceMix::solo(style));
+            settings.frequency_bands[index] = 1.0;
+            band_rms_contribution_db(settings, index)
+        };
+        let sub_bass = 0;
+        let air = FREQUENCY_BANDS.len() - 1;
+        // White noise has almost no power below 60 Hz; brown has most of it.
+        assert!(boosted(SoundStyle::White, sub_bass) < 0.2);
+        assert!(boosted(SoundStyle::Brown, sub_bass) > 3.0);
+        assert!(boosted(SoundStyle::White, air) > 3.0);
+        assert!(boosted(SoundStyle::Brown, air) < 0.1);
+
+        let mut cut = AudioSettings::default();
+        cut.set_mix(SourceMix::solo(SoundStyle::Pink));
+        cut.frequency_bands[3] = 0.0;
+        let contribution = band_rms_contribution_db(cut, 3);
+        assert!(contribution < -0.5 && contribution > -12.0);
+    }
+
+    #[test]
+    fn headroom_takes_back_what_the_band_sliders_add() {
+        let mut settings = AudioSettings {
+            headroom: true,
+            ..AudioSettings::default()
+        };
+        assert!(eq_rms_gain_db(settings).abs() < 1e-6);
+        assert_eq!(headroom_db(settings), 0.0);
+
+        // Every band at +12 dB raises everything by 12 dB.
+        settings.frequency_bands = [1.0; MAX_BANDS];
+        assert!((eq_rms_gain_db(settings) - 12.0).abs() < 0.1);
+        assert!((headroom_db(settings) + 12.0).abs() < 0.1);
+        // One band's share depends on the source, as its readout does.
+        settings.frequency_bands = [0.5; MAX_BANDS];
+        settings.frequency_bands[0] = 1.0;
+        settings.set_mix(SourceMix::solo(SoundStyle::Brown));
+        let brown = headroom_db(settings);
+        settings.set_mix(SourceMix::solo(SoundStyle::White));
+        assert!(brown < -3.0 && headroom_db(settings) > -0.2, "{brown}");
+        // Cuts are not made up, and without the switch nothing moves.
+        settings.frequency_bands = [0.0; MAX_BANDS];
+        assert!(eq_rms_gain_db(settings) < -11.0);
+        assert_eq!(headroom_db(settings), 0.0);
+        settings.frequency_bands = [1.0; MAX_BANDS];
+        settings.headroom = false;
+        assert_eq!(headroom_db(settings), 0.0);
+    }
+
+    #[test]
+    fn headroom_keeps_a_full_boost_out_of_the_limiter() {
+        let settings = AudioSettings {
+            volume: 0.5,
+            frequency_bands: [1.0; MAX_BANDS],
+            headroom: true,
+            ..AudioSettings::default()
+        };
+        let rms_of = |settings| {
+            let mut engine = AudioEngine::with_seed(48_000.0, settings, None, Some(7)).unwrap();
+            let mut block = [[0.0; 2]; 4_800];
+            for _ in 0..10 {
+                engine.render_block(&mut block);
+            }
+            (engine.output_rms(), engine.take_peak())
+        };
+        let (flat, _) = rms_of(AudioSettings {
+            frequency_bands: [0.5; MAX_BANDS],
+            ..settings
+        });
+        let (boosted, peak) = rms_of(settings);
+        assert!(
+            (20.0 * (boosted / flat).log10()).abs() < 1.0,
+            "{boosted} {flat}"
+        );
+        assert!(peak < 0.8, "{peak}");
+        let (unmanaged, _) = rms_of(AudioSettings {
+            headroom: false,
+            ..settings
+        });
+        assert!(unmanaged > boosted * 2.0);
+    }
+
+    #[test]
+    fn the_peak_estimate_tracks_the_engine_and_flags_a_boost_into_the_limiter() {
+        let mut settings = AudioSettings {
+            volume: 0.3,
+            ..AudioSettings::default()
+        };
+        settings.set_mix(SourceMix::solo(SoundStyle::Pink));
+        settings.frequency_bands[1] = 0.75;
+        let mut engine = AudioEngine::with_seed(48_000.0, settings, None, Some(7)).unwrap();
+        let mut block = [[0.0; 2]; 4_800];
+        for _ in 0..10 {
+            engine.render_block(&mut block);
+        }
+        let measured = 20.0 * engine.output_rms().log10() + NOISE_CREST_DB;
+        let estimate = estimated_peak_db(settings);
+        assert!(
+            (measured - estimate).abs() < 1.5,
+            "{measured} vs {estimate}"
+        );
+        assert_eq!(limiter_excess_db(settings), None);
+        assert_eq!(trimmed_volume(settings), None);
+
+        // Flat at full volume stays clear; every band at +12 dB does not,
+        // and the trim or the headroom brings it back.
+        settings.volume = 1.0;
+        settings.frequency_bands = [0.5; MAX_BANDS];
+        assert_eq!(limiter_excess_db(settings), None);
+        settings.frequency_bands = [1.0; MAX_BANDS];
+        let excess = limiter_excess_db(settings).unwrap();
+        assert!(excess > 8.0 && excess < 10.0, "{excess}");
+        let trimmed = AudioSettings {
+            volume: trimmed_volume(settings).unwrap(),
+            ..settings
+        };
+        assert!((estimated_peak_db(trimmed) - settings.limiter.threshold_db).abs() < 0.01);
+        assert_eq!(limiter_excess_db(trimmed), None);
+        settings.headroom = true;
+        assert_eq!(limiter_excess_db(settings), None);
+    }
+
+    #[test]
+    fn band_mute_and_solo_overlay_the_sliders() {
+        let mut settings = AudioSettings::default();
+        settings.frequency_bands[3] = 1.0;
+        settings.band_overlay.toggle_mute(3);
+        assert_eq!(band_gain_db(settings, 3), SILENCED_BAND_DB);
+        assert_eq!(settings.frequency_bands[3], 1.0);
+        settings.band_overlay.toggle_solo(3);
+        assert_eq!(band_gain_db(settings, 3), 12.0);
+        assert_eq!(band_gain_db(settings, 5), SILENCED_BAND_DB);
+
+        // A tone in band 6 through the running EQ: a mute cuts it, a solo
+        // of another band cuts it, and ending both brings it back.
+        let center = FREQUENCY_BANDS[6].center_frequency();
+        let tone_rms = |eq: &mut GraphicEq| {
+            let mut total = 0.0;
+            for frame in 0..48_000 {
+                let phase = frame as f32 * center * std::f32::consts::TAU / 48_000.0;
+                let output = eq.process([phase.sin(); 2])[0];
+                if frame >= 24_000 {
+                    total += output * output;
+                }
+            }
+            10.0 * (total / 24_000.0 * 2.0).log10()
+        };
+        let mut settings = AudioSettings::default();
+        let mut eq = GraphicEq::new(48_000.0, settings);
+        assert!(tone_rms(&mut eq).abs() < 0.5);
+        settings.band_overlay.toggle_mute(6);
+        eq.update(settings);
+        assert!(tone_rms(&mut eq) < -15.0);
+        settings.band_overlay.toggle_mute(6);
+        settings.band_overlay.toggle_solo(2);
+        eq.update(settings);
+        assert!(tone_rms(&mut eq) < -15.0);
+        settings.band_overlay.toggle_solo(2);
+        eq.update(settings);
+        assert!(tone_rms(&mut eq).abs() < 0.5);
+    }
+
+    #[test]
+    fn a_custom_band_layout_shapes_the_eq() {
+        static NOTCH_LAYOUT: [FrequencyBand; 2] = [
+            FrequencyBand {
+                name: std::borrow::Cow::Borrowed("Body"),
+                label_key: None,
+                min_freq: 20.0,
+                max_freq: 5_000.0,
+                q: None,
+            },
+            FrequencyBand {
+                name: std::borrow::Cow::Borrowed("Notch"),
+                label_key: None,
+                min_freq: 5_800.0,
+                max_freq: 6_200.0,
+                q: Some(8.0),
+            },
+        ];
+        let tone_db = |eq: &mut GraphicEq, hz: f32| {
+            let mut total = 0.0;
+            for frame in 0..48_000 {
+                let phase = frame as f32 * hz * std::f32::consts::TAU / 48_000.0;
+                let output = eq.process([phase.sin(); 2])[0];
+                if frame >= 24_000 {
+                    total += output * output;
+                }
+            }
+            10.0 * (total / 24_000.0 * 2.0).log10()
+        };
+        let mut settings = AudioSettings::default();
+        settings.frequency_bands[1] = 0.0;
+        let mut eq = GraphicEq::with_bands(48_000.0, settings, &NOTCH_LAYOUT, BandFilters::Peaking);
+        assert!(matches!(&eq.stage, BandStage::Peaking(filters) if filters.len() == 2));
+        // The notch takes the full cut at its center and leaves an octave
+        // below alone.
+        assert!((tone_db(&mut eq, 5_997.0) + 12.0).abs() < 0.5);
+        assert!(tone_db(&mut eq, 3_000.0).abs() < 0.5);
+
+        // The contour is evaluated at each band's own center, and a change
+        // of listening level reaches the filters.
+        let contour = AudioSettings {
+            listening_contour: true,
+            listening_phon: 40.0,
+            ..settings
+        };
+        let notch_gain = gain_for_band(contour, 1, &NOTCH_LAYOUT[1]);
+        assert_eq!(
+            notch_gain,
+            slider_to_db(0.0) + contour_db(NOTCH_LAYOUT[1].center_frequency(), 40.0)
+        );
+        let low_gain = |settings| gain_for_band(settings, 0, &NOTCH_LAYOUT[0]);
+        assert!(low_gain(contour) > 1.0, "{}", low_gain(contour));
+        let louder = AudioSettings {
+            listening_phon: 70.0,
+            ..contour
+        };
+        assert!(low_gain(louder) < low_gain(contour));
+        eq.update(contour);
+        eq.update(louder);
+        assert_eq!(eq.last_contour, Some(70.0));
+    }
+
     #[test]
     fn neutral_eq_is_transparent() {
         let settings = AudioSettings::default();
-        let mut eq = GraphicEq::new(48_000.0, settings);
+        let mut eq =
+            GraphicEq::with_bands(48_000.0, settings, &FREQUENCY_BANDS, BandFilters::Peaking);
         let input = [0.0, 0.25, -0.5, 0.75, -0.1];
-        let output = input.map(|sample| eq.process(sample));
+        let output = input.map(|sample| eq.process([sample; 2])[0]);
 
         assert_eq!(input, output);
     }
 
+    #[test]
+    fn mid_side_mode_shapes_the_side_chain_apart() {
+        // Each chain's level of a Mid-center tone, in dB.
+        let tone_db = |eq: &mut GraphicEq| {
+            let mut totals = [0.0; 2];
+            for frame in 0..48_000 {
+                let phase = frame as f32 * 1_000.0 * std::f32::consts::TAU / 48_000.0;
+                let output = eq.process([phase.sin(); 2]);
+                if frame >= 24_000 {
+                    for (total, sample) in totals.iter_mut().zip(output) {
+                        *total += sample * sample;
+                    }
+                }
+            }
+            totals.map(|total| 10.0 * (total / 24_000.0 * 2.0).log10())
+        };
+        let mut settings = AudioSettings::default();
+        settings.side_bands[3] = 0.0;
+        for filters in [BandFilters::Crossover, BandFilters::Peaking] {
+            let mut eq = GraphicEq::with_bands(48_000.0, settings, &FREQUENCY_BANDS, filters);
+            // Off, the side's sliders wait and both chains play the mid's.
+            let [mid, side] = tone_db(&mut eq);
+            assert!(
+                mid.abs() < 0.5 && side.abs() < 0.5,
+                "{filters:?}: {mid} {side}"
+            );
+
+            settings.mid_side = true;
+            eq.update(settings);
+            let [mid, side] = tone_db(&mut eq);
+            assert!(mid.abs() < 0.5, "{filters:?}: {mid}");
+            assert!((side + 12.0).abs() < 1.0, "{filters:?}: {side}");
+            settings.mid_side = false;
+        }
+
+        // The headroom estimate weighs the side by its share of the power:
+        // none in mono, half at full width.
+        let boosted = AudioSettings {
+            mid_side: true,
+            side_bands: [1.0; MAX_BANDS],
+            stereo_width: 0.0,
+            ..AudioSettings::default()
+        };
+        assert!(eq_rms_gain_db(boosted).abs() < 1e-3);
+        let wide = eq_rms_gain_db(AudioSettings {
+            stereo_width: 1.0,
+            ..boosted
+        });
+        let half = 10.0 * ((1.0 + 10.0_f32.powf(12.0 / 10.0)) / 2.0).log10();
+        assert!((wide - half).abs() < 0.2, "{wide} {half}");
+    }
+
+    #[test]
+    fn crossover_bands_stay_in_their_own_range() {
+        let tone_db = |eq: &mut GraphicEq, hz: f32| {
+            let mut total = 0.0;
+            for frame in 0..48_000 {
+                let phase = frame as f32 * hz * std::f32::consts::TAU / 48_000.0;
+                let output = eq.process([phase.sin(); 2])[0];
+                if frame >= 24_000 {
+                    total += output * output;
+                }
+            }
+            10.0 * (total / 24_000.0 * 2.0).log10()
+        };
+        let eq = |settings, filters| {
+            GraphicEq::with_bands(48_000.0, settings, &FREQUENCY_BANDS, filters)
+        };
+        let mut muted = AudioSettings::default();
+        muted.band_overlay.toggle_mute(3);
+        let mid = FREQUENCY_BANDS[3].center_frequency();
+        // Tones an octave past Mid's edges.
+        let neighbors = [250.0, 4_000.0];
+
+        // Neutral, the crossover is flat wherever it is measured.
+        let mut neutral = eq(AudioSettings::default(), BandFilters::Crossover);
+        for hz in [40.0, mid, 8_000.0, 16_000.0] {
+            assert!(tone_db(&mut neutral, hz).abs() < 0.05, "{hz} Hz");
+        }
+
+        // Muting Mid silences its center and leaves its neighbors, where
+        // the peaking filter's skirts take decibels off them as well.
+        let mut crossover = eq(muted, BandFilters::Crossover);
+        assert!(tone_db(&mut crossover, mid) < -40.0);
+        let mut peaking = eq(muted, BandFilters::Peaking);
+        for hz in neighbors {
+            let leaked = tone_db(&mut crossover, hz);
+            assert!(leaked.abs() < 0.2, "{hz} Hz: {leaked:.2} dB");
+            let skirt = tone_db(&mut peaking, hz);
+            assert!(skirt < -1.0, "{hz} Hz: {skirt:.2} dB");
+        }
+
+        // The estimate follows the same split: muting Mid's two octaves
+        // takes about a fifth of pink noise's power away.
+        let mut pink = muted;
+        pink.set_mix(SourceMix::solo(SoundStyle::Pink));
+        let estimate = band_rms_contribution_db(pink, 3);
+        assert!(
+            (estimate - 10.0 * 0.8_f32.log10()).abs() < 0.3,
+            "{estimate:.2} dB"
+        );
+        assert_eq!(
+            crossovers_hz(&FREQUENCY_BANDS[..3], 48_000.0),
+            Some(vec![60.0, 250.0])
+        );
+        let reversed = [FREQUENCY_BANDS[2].clone(), FREQUENCY_BANDS[1].clone()];
+        assert_eq!(crossovers_hz(&reversed, 48_000.0), None);
+    }
+
+    #[test]
+    fn band_tilt_leans_the_band_toward_one_edge_and_keeps_its_center() {
+        const SAMPLE_RATE: f32 = 48_000.0;
+        let settings = AudioSettings {
+            band_tilts: [0.0, 6.0],
+            ..AudioSettings::default()
+        };
+        let sine_db = |frequency: f32| {
+            let mut eq = GraphicEq::new(SAMPLE_RATE, settings);
+            let samples: Vec<f32> = (0..48_000)
+                .map(|n| {
+                    let sample =
+                        (2.0 * std::f32::consts::PI * frequency * n as f32 / SAMPLE_RATE).sin();
+                    eq.process([sample; 2])[0]
+                })
+                .skip(24_000)
+                .collect();
+            let rms = (samples.iter().map(|sample| sample * sample).sum::<f32>()
+                / samples.len() as f32)
+                .sqrt();
+            20.0 * (rms * std::f32::consts::SQRT_2).log10()
+        };
+        let mid = &FREQUENCY_BANDS[TILT_BANDS[1]];
+
+        assert!(sine_db(mid.center_frequency()).abs() < 0.05);
+        let measured = sine_db(mid.max_freq) - sine_db(mid.min_freq);
+        let estimate = tilt_edge_difference_db(1, 6.0);
+        assert!(
+            (measured - estimate).abs() < 0.1,
+            "{measured} vs {estimate}"
+        );
+        assert!(
+            estimate > 4.0 && estimate < 6.0,
+            "edges {estimate} dB apart"
+        );
+        assert_eq!(tilt_edge_difference_db(0, 0.0), 0.0);
+    }
+
     #[test]
     fn neutral_white_source_has_expected_statistics() {
         let settings = AudioSettings {
             volume: 1.0,
             ..AudioSettings::default()
         };
-        let mut engine = AudioEngine::new(48_000.0, settings).unwrap();
-        engine.rng = SmallRng::seed_from_u64(42);
+        let mut engine = AudioEngine::new(48_000.0, settings, None).unwrap();
+        engine.chains[0].rng = NoiseGenerator::Small(SmallRng::seed_from_u64(42));
+        engine.chains[1].rng = NoiseGenerator::Small(SmallRng::seed_from_u64(43));
 
         // Let the startup volume ramp finish before measuring the source.
         for _ in 0..3_000 {
@@ -828,7 +3577,7 @@ mod tests {
             sound_style: SoundStyle::Rain,
             ..AudioSettings::default()
         };
-        let mut engine = AudioEngine::new(48_000.0, settings).unwrap();
+        let mut engine = AudioEngine::new(48_000.0, settings, None).unwrap();
 
         for _ in 0..3_000 {
             engine.next_sample();
@@ -851,12 +3600,12 @@ mod tests {
         for style in SoundStyle::ALL {
             let settings = AudioSettings {
                 volume: 1.0,
-                frequency_bands: [1.0; FREQUENCY_BANDS.len()],
+                frequency_bands: [1.0; MAX_BANDS],
                 listening_contour: true,
                 sound_style: style,
                 ..AudioSettings::default()
             };
-            let mut engine = AudioEngine::new(48_000.0, settings).unwrap();
+            let mut engine = AudioEngine::new(48_000.0, settings, None).unwrap();
 
             for _ in 0..100_000 {
                 let sample = engine.next_sample();
@@ -873,65 +3622,13 @@ mod tests {
             .collect()
     }
 
-    // Deterministic magnitude response of the realized (f32) filter: capture
-    // its impulse response and evaluate the DFT at each target frequency.
-    fn impulse_octave_slopes_db(mut source: impl FnMut(f32) -> f32, sample_rate: f32) -> Vec<f64> {
-        let length = 1 << 18;
-        let impulse_response: Vec<f32> = (0..length)
-            .map(|index| source(if index == 0 { 1.0 } else { 0.0 }))
-            .collect();
-
-        let response_db = |frequency: f64| {
-            let omega = 2.0 * std::f64::consts::PI * frequency / f64::from(sample_rate);
-            let (mut re, mut im) = (0.0_f64, 0.0_f64);
-            for (index, &h) in impulse_response.iter().enumerate() {
-                let phase = omega * index as f64;
-                re += f64::from(h) * phase.cos();
-                im -= f64::from(h) * phase.sin();
-            }
-            10.0 * (re * re + im * im).log10()
-        };
-
-        let frequencies = [125.0_f64, 250.0, 500.0, 1_000.0, 2_000.0, 4_000.0, 8_000.0];
-        let levels: Vec<f64> = frequencies.iter().map(|f| response_db(*f)).collect();
-        levels.windows(2).map(|pair| pair[1] - pair[0]).collect()
-    }
-
-    #[test]
-    fn pink_noise_falls_three_db_per_octave() {
-        for sample_rate in [44_100.0_f32, 48_000.0, 192_000.0] {
-            let mut pink = PinkNoise::new(sample_rate, COLORED_NOISE_TARGET_RMS);
-            let slopes = impulse_octave_slopes_db(|sample| pink.process(sample), sample_rate);
-            for (octave, slope) in slopes.iter().enumerate() {
-                assert!(
-                    (slope - -3.01).abs() < 0.5,
-                    "pink octave {octave} slope was {slope:.2} dB at {sample_rate} Hz"
-                );
-            }
-        }
-    }
-
-    #[test]
-    fn brown_noise_falls_six_db_per_octave() {
-        for sample_rate in [44_100.0_f32, 48_000.0, 192_000.0] {
-            let mut brown = BrownNoise::new(sample_rate, COLORED_NOISE_TARGET_RMS);
-            let slopes = impulse_octave_slopes_db(|sample| brown.process(sample), sample_rate);
-            for (octave, slope) in slopes.iter().enumerate() {
-                // A digital one-pole flattens slightly approaching Nyquist,
-                // so the top octave sits near -5.7 dB at 44.1 kHz.
-                assert!(
-                    (slope - -6.02).abs() < 0.6,
-                    "brown octave {octave} slope was {slope:.2} dB at {sample_rate} Hz"
-                );
-            }
-        }
-    }
-
     #[test]
     fn colored_noise_levels_match_the_white_source() {
         for sample_rate in [44_100.0_f32, 48_000.0, 192_000.0] {
             let mut pink = PinkNoise::new(sample_rate, COLORED_NOISE_TARGET_RMS);
             let mut brown = BrownNoise::new(sample_rate, COLORED_NOISE_TARGET_RMS);
+            let mut blue = BlueNoise::new(sample_rate, COLORED_NOISE_TARGET_RMS);
+            let mut violet = VioletNoise::new(COLORED_NOISE_TARGET_RMS);
             for (name, samples) in [
                 (
                     "pink",
@@ -941,6 +3638,14 @@ mod tests {
                     "brown",
                     collect_colored(|white| brown.process(white), 480_000),
                 ),
+                (
+                    "blue",
+                    collect_colored(|white| blue.process(white), 480_000),
+                ),
+                (
+                    "violet",
+                    collect_colored(|white| violet.process(white), 480_000),
+                ),
             ] {
                 // Skip the leaky integrator's settle-in before measuring.
                 let settled = &samples[samples.len() / 4..];
@@ -969,10 +3674,23 @@ mod tests {
             white: 0.5,
             pink: 0.0,
             brown: 0.5,
+            blue: 0.0,
+            violet: 0.0,
             rain: 0.0,
+
+            ocean: 0.0,
+
+            wind: 0.0,
+
+            fire: 0.0,
+
+            fan: 0.0,
+
+            sample: 0.0,
         });
-        let mut engine = AudioEngine::new(48_000.0, settings).unwrap();
-        engine.rng = SmallRng::seed_from_u64(11);
+        let mut engine = AudioEngine::new(48_000.0, settings, None).unwrap();
+        engine.chains[0].rng = NoiseGenerator::Small(SmallRng::seed_from_u64(11));
+        engine.chains[1].rng = NoiseGenerator::Small(SmallRng::seed_from_u64(12));
 
         // Let the volume ramp and the brown integrator settle.
         for _ in 0..48_000 {
@@ -990,7 +3708,7 @@ mod tests {
     fn full_mix_of_every_source_stays_bounded() {
         let mut settings = AudioSettings {
             volume: 1.0,
-            frequency_bands: [1.0; FREQUENCY_BANDS.len()],
+            frequency_bands: [1.0; MAX_BANDS],
             listening_contour: true,
             ..AudioSettings::default()
         };
@@ -998,9 +3716,21 @@ mod tests {
             white: 1.0,
             pink: 1.0,
             brown: 1.0,
+            blue: 1.0,
+            violet: 1.0,
             rain: 1.0,
+
+            ocean: 1.0,
+
+            wind: 1.0,
+
+            fire: 1.0,
+
+            fan: 1.0,
+
+            sample: 1.0,
         });
-        let mut engine = AudioEngine::new(48_000.0, settings).unwrap();
+        let mut engine = AudioEngine::new(48_000.0, settings, Some(&rain_sample())).unwrap();
 
         for _ in 0..100_000 {
             let sample = engine.next_sample();
@@ -1015,7 +3745,7 @@ mod tests {
             volume: 1.0,
             ..AudioSettings::default()
         };
-        let mut engine = AudioEngine::new(48_000.0, settings).unwrap();
+        let mut engine = AudioEngine::new(48_000.0, settings, None).unwrap();
         for _ in 0..10_000 {
             engine.next_sample();
         }
@@ -1024,7 +3754,19 @@ mod tests {
             white: 0.0,
             pink: 0.3,
             brown: 0.3,
+            blue: 0.0,
+            violet: 0.0,
             rain: 0.4,
+
+            ocean: 0.0,
+
+            wind: 0.0,
+
+            fire: 0.0,
+
+            fan: 0.0,
+
+            sample: 0.0,
         });
         engine.update_settings(settings);
         for _ in 0..50_000 {
@@ -1040,7 +3782,7 @@ mod tests {
             volume: 1.0,
             ..AudioSettings::default()
         };
-        let mut engine = AudioEngine::new(48_000.0, settings).unwrap();
+        let mut engine = AudioEngine::new(48_000.0, settings, None).unwrap();
 
         // Retarget faster than the 200 ms crossfade completes, repeatedly.
         let mut style = settings.sound_style;
@@ -1087,7 +3829,7 @@ mod tests {
                 eq.update(settings);
                 for _ in 0..keypress_samples {
                     let input = (rng.random::<f32>() * 2.0 - 1.0) * WHITE_NOISE_GAIN;
-                    let sample = eq.process(input);
+                    let sample = eq.process([input; 2])[0];
                     assert!(sample.is_finite());
                     assert!(
                         sample.abs() < 4.0,
@@ -1101,28 +3843,741 @@ mod tests {
     #[test]
     fn eq_recovers_after_non_finite_input() {
         let settings = AudioSettings {
-            frequency_bands: [1.0; FREQUENCY_BANDS.len()],
+            frequency_bands: [1.0; MAX_BANDS],
             ..AudioSettings::default()
         };
-        let mut eq = GraphicEq::new(48_000.0, settings);
-        for _ in 0..1_000 {
-            eq.process(0.1);
+        // Each bad sample flushes the first filter it meets, or the first
+        // section of both halves of the lowest split.
+        for (filters, expected) in [(BandFilters::Peaking, 2), (BandFilters::Crossover, 4)] {
+            let mut eq = GraphicEq::with_bands(48_000.0, settings, &FREQUENCY_BANDS, filters);
+            for _ in 0..1_000 {
+                eq.process([0.1; 2]);
+            }
+
+            eq.process([f32::NAN, 0.1]);
+            eq.process([f32::INFINITY, 0.1]);
+
+            for _ in 0..1_000 {
+                assert!(eq.process([0.1; 2]).iter().all(|sample| sample.is_finite()));
+            }
+            assert_eq!(eq.resets(), expected);
+        }
+    }
+
+    #[test]
+    fn engine_reports_the_output_peak_and_the_limiters_gain_reduction() {
+        let settings = AudioSettings {
+            volume: 1.0,
+            frequency_bands: [1.0; MAX_BANDS],
+            limiter: LimiterSettings {
+                threshold_db: -6.0,
+                ..LimiterSettings::default()
+            },
+            ..AudioSettings::default()
+        };
+        let threshold = settings.limiter.threshold();
+        let mut engine = AudioEngine::new(48_000.0, settings, None).unwrap();
+        let mut output_peak = 0.0_f32;
+        for _ in 0..48_000 {
+            for channel in engine.next_frame() {
+                output_peak = output_peak.max(channel.abs());
+            }
+        }
+
+        // Full boost drives the limiter, which holds every peak under the
+        // threshold and says how far it turned the output down.
+        assert_eq!(engine.take_peak(), output_peak);
+        assert!(output_peak <= threshold && output_peak > threshold * 0.9);
+        assert!(engine.take_reduction() > 3.0);
+        assert_eq!(engine.take_peak(), 0.0);
+
+        // The meter's RMS follows the limited output, not the boost.
+        let mut block = [[0.0; 2]; 4_800];
+        let mut power = 0.0;
+        for _ in 0..10 {
+            engine.render_block(&mut block);
+            power = block
+                .iter()
+                .map(|[l, r]| (l * l + r * r) * 0.5)
+                .sum::<f32>()
+                / 4_800.0;
+        }
+        let rms = engine.output_rms();
+        assert!((20.0 * (rms / power.sqrt()).log10()).abs() < 1.0, "{rms}");
+        assert!(rms < output_peak);
+
+        // A flat EQ at a moderate volume never reaches it.
+        engine.update_settings(AudioSettings {
+            volume: 0.3,
+            frequency_bands: [0.5; MAX_BANDS],
+            ..settings
+        });
+        for _ in 0..100 {
+            engine.render_block(&mut block);
+        }
+        engine.take_reduction();
+        engine.render_block(&mut block);
+        assert_eq!(engine.take_reduction(), 0.0);
+
+        let monitor = AudioMonitor::default();
+        for value in [0.3_f32, 1.7, 0.9] {
+            monitor.peak.fetch_max(value.to_bits(), Ordering::Relaxed);
+            monitor
+                .reduction
+                .fetch_max(value.to_bits(), Ordering::Relaxed);
+        }
+        assert_eq!(monitor.take_peak(), 1.7);
+        assert_eq!(monitor.take_peak(), 0.0);
+        assert_eq!(monitor.take_reduction(), 1.7);
+        assert_eq!(monitor.take_reduction(), 0.0);
+    }
+
+    #[test]
+    fn only_a_broken_stream_counts_as_a_lost_output() {
+        for kind in [
+            ErrorKind::Xrun,
+            ErrorKind::DeviceChanged,
+            ErrorKind::RealtimeDenied,
+        ] {
+            assert!(!loses_output(&cpal::Error::new(kind)), "{kind:?}");
+        }
+        for kind in [
+            ErrorKind::DeviceNotAvailable,
+            ErrorKind::StreamInvalidated,
+            ErrorKind::BackendError,
+        ] {
+            assert!(loses_output(&cpal::Error::new(kind)), "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn engine_totals_dsp_resets_across_stages() {
+        let settings = AudioSettings {
+            volume: 0.5,
+            ..AudioSettings::default()
+        };
+        let mut engine = AudioEngine::new(48_000.0, settings, None).unwrap();
+        assert_eq!(engine.dsp_resets(), 0);
+
+        engine.eq.process([f32::NAN, 0.0]);
+        engine.chains[0].pink.process(f32::INFINITY);
+        engine.chains[1].brown.process(f32::NAN);
+        // The EQ's NaN flushes both halves of the crossover's lowest split.
+        assert_eq!(engine.dsp_resets(), 4);
+
+        for _ in 0..4_800 {
+            assert!(engine.next_sample().is_finite());
+        }
+        assert_eq!(engine.dsp_resets(), 4);
+    }
+
+    /// Correlation and RMS of each channel over `count` frames.
+    fn channel_statistics(engine: &mut AudioEngine, count: usize) -> (f64, [f64; 2]) {
+        let (mut left, mut right, mut product) = (0.0_f64, 0.0_f64, 0.0_f64);
+        for _ in 0..count {
+            let [l, r] = engine.next_frame().map(f64::from);
+            left += l * l;
+            right += r * r;
+            product += l * r;
+        }
+        let correlation = product / (left * right).sqrt();
+        let rms = [left, right].map(|power| (power / count as f64).sqrt());
+        (correlation, rms)
+    }
+
+    #[test]
+    fn stereo_width_sets_the_channel_correlation_at_a_constant_level() {
+        for style in SoundStyle::ALL {
+            let mut settings = AudioSettings {
+                volume: 1.0,
+                sound_style: style,
+                ..AudioSettings::default()
+            };
+            let mut engine = AudioEngine::new(48_000.0, settings, Some(&rain_sample())).unwrap();
+            engine.chains[0].rng = NoiseGenerator::Small(SmallRng::seed_from_u64(5));
+            engine.chains[1].rng = NoiseGenerator::Small(SmallRng::seed_from_u64(6));
+            for _ in 0..48_000 {
+                engine.next_frame();
+            }
+
+            for width in [0.0, 0.5, 1.0] {
+                settings.stereo_width = width;
+                engine.update_settings(settings);
+                for _ in 0..4_800 {
+                    engine.next_frame();
+                }
+                // A loop's halves come from one recording (here the rain for
+                // both loops), so they are only roughly uncorrelated; the
+                // noise chains are independent.
+                let tolerance = if matches!(style, SoundStyle::Rain | SoundStyle::Sample) {
+                    0.2
+                } else {
+                    0.1
+                };
+                let (correlation, [left, right]) = channel_statistics(&mut engine, 96_000);
+                let expected = f64::from(stereo_correlation(width));
+                assert!(
+                    (correlation - expected).abs() < tolerance,
+                    "{style:?} at width {width}: correlation {correlation:.3}"
+                );
+                assert!(
+                    (left / right - 1.0).abs() < 0.15,
+                    "{style:?} at width {width}: channel RMS {left:.3} vs {right:.3}"
+                );
+            }
+        }
+        assert_eq!(stereo_correlation(0.0), 1.0);
+        assert!(stereo_correlation(1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn band_widths_keep_the_low_end_mono_and_the_top_wide() {
+        let mut settings = AudioSettings {
+            volume: 1.0,
+            sound_style: SoundStyle::Pink,
+            stereo_width: 1.0,
+            ..AudioSettings::default()
+        };
+        settings.band_widths[..2].fill(0.0);
+        let mut engine = AudioEngine::new(48_000.0, settings, None).unwrap();
+        engine.chains[0].rng = NoiseGenerator::Small(SmallRng::seed_from_u64(5));
+        engine.chains[1].rng = NoiseGenerator::Small(SmallRng::seed_from_u64(6));
+        for _ in 0..48_000 {
+            engine.next_frame();
+        }
+        // Crossover filters pick out the lows under 100 Hz and the highs
+        // over 4 kHz, the same in each channel.
+        let mut lows = BandSplitter::new(48_000.0, &[100.0, 4_000.0], &[1.0, 0.0, 0.0]);
+        let mut highs = BandSplitter::new(48_000.0, &[100.0, 4_000.0], &[0.0, 0.0, 1.0]);
+        let mut sums = [[0.0_f64; 3]; 2];
+        for _ in 0..192_000 {
+            let frame = engine.next_frame();
+            for (sums, [left, right]) in sums
+                .iter_mut()
+                .zip([lows.process(frame), highs.process(frame)])
+            {
+                let [left, right] = [left, right].map(f64::from);
+                sums[0] += left * left;
+                sums[1] += right * right;
+                sums[2] += left * right;
+            }
+        }
+        let [low, high] = sums.map(|[left, right, product]| product / (left * right).sqrt());
+        assert!(low > 0.98, "lows correlate {low:.3}");
+        assert!(high.abs() < 0.1, "highs correlate {high:.3}");
+
+        // Back to following the stereo width, the split fades out and stops.
+        settings.band_widths = [1.0; MAX_BANDS];
+        engine.update_settings(settings);
+        for _ in 0..4_800 {
+            engine.next_frame();
+        }
+        let widths = engine.band_widths.as_mut().unwrap();
+        assert!(!widths.process_block(&mut [[0.0; 2]]));
+    }
+
+    #[test]
+    fn mono_width_makes_identical_channels() {
+        let settings = AudioSettings {
+            volume: 1.0,
+            stereo_width: 0.0,
+            ..AudioSettings::default()
+        };
+        let mut engine = AudioEngine::new(48_000.0, settings, None).unwrap();
+        for _ in 0..10_000 {
+            let [left, right] = engine.next_frame();
+            assert_eq!(left, right);
+        }
+    }
+
+    fn rain_sample() -> UserSample {
+        UserSample::measure(RAIN_WAV_DATA.to_vec()).unwrap()
+    }
+
+    fn silent_mix() -> SourceMix {
+        let mut mix = SourceMix::solo(SoundStyle::White);
+        mix.set_level(SoundStyle::White, 0.0);
+        mix
+    }
+
+    #[test]
+    fn binaural_tone_puts_each_carrier_in_one_ear_at_any_width() {
+        let mut settings = AudioSettings {
+            volume: 1.0,
+            stereo_width: 0.0,
+            tone: ToneSettings {
+                mode: ToneMode::Binaural,
+                level: 1.0,
+                ..ToneSettings::default()
+            },
+            ..AudioSettings::default()
+        };
+        settings.set_mix(silent_mix());
+        let mut engine = AudioEngine::new(48_000.0, settings, None).unwrap();
+        for _ in 0..48_000 {
+            engine.next_frame();
+        }
+        let (correlation, [left, right]) = channel_statistics(&mut engine, 48_000);
+        // 198 Hz against 202 Hz averages out to no correlation over a second.
+        assert!(correlation.abs() < 0.05, "correlation {correlation:.3}");
+        let expected = f64::from(COLORED_NOISE_TARGET_RMS);
+        for rms in [left, right] {
+            assert!((rms / expected - 1.0).abs() < 0.02, "tone RMS {rms:.4}");
+        }
+    }
+
+    #[test]
+    fn tone_mode_changes_fade_through_silence_without_clicks() {
+        let mut settings = AudioSettings {
+            volume: 1.0,
+            tone: ToneSettings {
+                mode: ToneMode::Binaural,
+                level: 1.0,
+                ..ToneSettings::default()
+            },
+            ..AudioSettings::default()
+        };
+        settings.set_mix(silent_mix());
+        let mut engine = AudioEngine::new(48_000.0, settings, None).unwrap();
+        for _ in 0..48_000 {
+            engine.next_frame();
+        }
+
+        let mut previous = engine.next_frame();
+        let mut largest_step: f32 = 0.0;
+        let mut reached_silence = false;
+        for mode in [ToneMode::Isochronic, ToneMode::Off] {
+            settings.tone.mode = mode;
+            engine.update_settings(settings);
+            for _ in 0..48_000 {
+                let frame = engine.next_frame();
+                reached_silence |= frame == [0.0; 2];
+                for (now, before) in frame.iter().zip(previous) {
+                    assert!(now.is_finite());
+                    largest_step = largest_step.max((now - before).abs());
+                }
+                previous = frame;
+            }
+            assert_eq!(engine.tone_playing, mode);
+        }
+        assert!(reached_silence);
+        assert_eq!(previous, [0.0; 2]);
+        // A 202 Hz sine at this level moves at most about 0.006 per sample.
+        assert!(largest_step < 0.01, "step {largest_step}");
+    }
+
+    #[test]
+    fn sleep_fade_scales_volume_without_touching_the_setting() {
+        let settings = AudioSettings {
+            volume: 0.8,
+            ..AudioSettings::default()
+        };
+        let mut engine = AudioEngine::new(48_000.0, settings, None).unwrap();
+        engine.set_fade_gain(0.25);
+        for _ in 0..4_800 {
+            engine.next_sample();
+        }
+        assert!((engine.volume.next_value() - 0.2).abs() < 1e-6);
+
+        // A volume change mid-fade keeps the fade applied.
+        engine.update_settings(AudioSettings {
+            volume: 0.4,
+            ..settings
+        });
+        engine.set_fade_gain(0.0);
+        for _ in 0..4_800 {
+            engine.next_sample();
+        }
+        assert_eq!(engine.next_sample(), 0.0);
+
+        engine.set_fade_gain(1.0);
+        for _ in 0..4_800 {
+            engine.next_sample();
+        }
+        assert!((engine.volume.next_value() - 0.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn a_generator_fills_interleaved_buffers_and_follows_new_settings() {
+        let paused = AudioSettings {
+            volume: 0.5,
+            paused: true,
+            ..AudioSettings::default()
+        };
+        let options = StreamOptions {
+            block_size: 100,
+            ..StreamOptions::default()
+        };
+        let mut generator = Generator::new(48_000.0, 4, paused, options).unwrap();
+        let mut buffer = vec![1.0_f32; 4 * 4_801];
+        generator.fill_buffer(&mut buffer);
+        assert!(buffer.iter().all(|sample| *sample == 0.0));
+
+        generator.set_settings(AudioSettings {
+            paused: false,
+            ..paused
+        });
+        assert!(!generator.settings().paused);
+        generator.fill_buffer(&mut buffer);
+        assert!(buffer.iter().all(|sample| sample.abs() <= 1.0));
+        // The front pair's sides repeat on the rear pair.
+        for frame in buffer.chunks(4) {
+            assert_eq!(frame[0], frame[2]);
+            assert_eq!(frame[1], frame[3]);
+        }
+        assert!(generator.take_peak() > 0.0);
+        assert!(generator.output_rms() > 0.01);
+    }
+
+    #[test]
+    fn bypass_plays_the_raw_mix_at_the_processed_loudness() {
+        const SAMPLE_RATE: f32 = 48_000.0;
+        let processed = AudioSettings {
+            volume: 0.3,
+            frequency_bands: [0.75; MAX_BANDS],
+            ceiling_hz: Some(1_000.0),
+            listening_contour: true,
+            crossfeed: true,
+            ..AudioSettings::default()
+        };
+        // Loudness and brightness (first-difference RMS over RMS) of the
+        // left channel, after the meters and ramps settle.
+        let measure = |settings: AudioSettings| {
+            let mut engine = AudioEngine::new(SAMPLE_RATE, settings, None).unwrap();
+            let mut meter = LoudnessMeter::new(SAMPLE_RATE);
+            let (mut power, mut change, mut previous) = (0.0, 0.0, 0.0);
+            for n in 0..6 * 48_000 {
+                let [left, _] = engine.next_frame();
+                meter.process(left);
+                if n >= 3 * 48_000 {
+                    power += f64::from(left * left);
+                    change += f64::from((left - previous) * (left - previous));
+                }
+                previous = left;
+            }
+            (10.0 * meter.mean_square().log10(), (change / power).sqrt())
+        };
+        let (processed_db, processed_brightness) = measure(processed);
+        let (bypassed_db, bypassed_brightness) = measure(AudioSettings {
+            bypass: true,
+            ..processed
+        });
+        assert!(
+            (processed_db - bypassed_db).abs() < 1.0,
+            "processed {processed_db:.1} dB, bypassed {bypassed_db:.1} dB"
+        );
+        // White noise's first difference carries about sqrt(2) of its RMS;
+        // the 1 kHz ceiling leaves a small fraction of that.
+        assert!(bypassed_brightness > 1.3, "{bypassed_brightness:.2}");
+        assert!(processed_brightness < 0.3, "{processed_brightness:.2}");
+    }
+
+    #[test]
+    fn a_night_ceiling_eases_in_and_holds_whatever_the_volume() {
+        let loud = AudioSettings {
+            volume: 1.0,
+            frequency_bands: [1.0; MAX_BANDS],
+            ..AudioSettings::default()
+        };
+        let night = AudioSettings {
+            night_ceiling_db: Some(-20.0),
+            ..loud
+        };
+        let ceiling = 10_f32.powf(-20.0 / 20.0);
+        let peak = |engine: &mut AudioEngine, frames: usize| {
+            (0..frames)
+                .flat_map(|_| engine.next_frame())
+                .fold(0.0_f32, |peak, sample| peak.max(sample.abs()))
+        };
+
+        let mut engine = AudioEngine::new(48_000.0, loud, None).unwrap();
+        peak(&mut engine, 4_800);
+        engine.update_settings(night);
+        // Half a second in, the ceiling is still on its way down.
+        assert!(peak(&mut engine, 24_000) > 2.0 * ceiling);
+        peak(&mut engine, 120_000);
+        assert!(peak(&mut engine, 48_000) <= ceiling);
+
+        // Started inside the window, the ceiling holds from the first frame.
+        let mut engine = AudioEngine::new(48_000.0, night, None).unwrap();
+        assert!(peak(&mut engine, 48_000) <= ceiling);
+        engine.update_settings(loud);
+        peak(&mut engine, 48_000 * 4);
+        assert!(peak(&mut engine, 48_000) > 0.5);
+    }
+
+    #[test]
+    fn an_spl_cap_holds_the_output_level_once_calibrated() {
+        let rms_db = |engine: &mut AudioEngine, frames: usize| {
+            let sum: f32 = (0..frames)
+                .map(|_| {
+                    let [left, right] = engine.next_frame();
+                    (left * left + right * right) * 0.5
+                })
+                .sum();
+            10.0 * (sum / frames as f32).log10()
+        };
+        // 90 dB SPL at full scale puts a 60 dB cap at -30 dBFS RMS.
+        let capped = AudioSettings {
+            volume: 1.0,
+            spl: SplCalibration {
+                offset_db: Some(90.0),
+                max_db: Some(60.0),
+            },
+            ..AudioSettings::default()
+        };
+        let mut engine = AudioEngine::new(48_000.0, capped, None).unwrap();
+        rms_db(&mut engine, 48_000);
+        let level = rms_db(&mut engine, 48_000);
+        assert!((level + 30.0).abs() < 0.5, "{level}");
+
+        // Without the calibration there is nothing to read the cap by.
+        let uncalibrated = AudioSettings {
+            spl: SplCalibration {
+                offset_db: None,
+                ..capped.spl
+            },
+            ..capped
+        };
+        engine.update_settings(uncalibrated);
+        rms_db(&mut engine, 48_000 * 10);
+        assert!(rms_db(&mut engine, 48_000) > -20.0);
+    }
+
+    #[test]
+    fn a_duck_lowers_the_sound_and_brings_it_back() {
+        let playing = AudioSettings {
+            volume: 0.5,
+            ..AudioSettings::default()
+        };
+        let ducked = AudioSettings {
+            duck_db: Some(20.0),
+            ..playing
+        };
+        let rms = |engine: &mut AudioEngine| {
+            let power: f32 = (0..48_000)
+                .flat_map(|_| engine.next_frame())
+                .map(|sample| sample * sample)
+                .sum();
+            (power / 96_000.0).sqrt()
+        };
+
+        let mut engine = AudioEngine::new(48_000.0, playing, None).unwrap();
+        rms(&mut engine);
+        let full = rms(&mut engine);
+        engine.update_settings(ducked);
+        rms(&mut engine);
+        let ratio = rms(&mut engine) / full;
+        assert!((0.08..0.12).contains(&ratio), "{ratio}");
+        engine.update_settings(playing);
+        rms(&mut engine);
+        let ratio = rms(&mut engine) / full;
+        assert!((0.9..1.1).contains(&ratio), "{ratio}");
+    }
+
+    #[test]
+    fn a_scene_filters_and_swells_its_layers() {
+        let playing = AudioSettings {
+            volume: 0.5,
+            ..AudioSettings::default()
+        };
+        let window_rms = |engine: &mut AudioEngine, frames: usize| {
+            let power: f32 = (0..frames)
+                .map(|_| engine.next_frame()[0])
+                .map(|sample| sample * sample)
+                .sum();
+            (power / frames as f32).sqrt()
+        };
+
+        let mut engine = AudioEngine::new(48_000.0, playing, None).unwrap();
+        window_rms(&mut engine, 24_000);
+        let full = window_rms(&mut engine, 48_000);
+        let mut shaped = playing;
+        shaped.layers[0].low_pass_hz = Some(500.0);
+        engine.update_settings(shaped);
+        window_rms(&mut engine, 24_000);
+        let ratio = window_rms(&mut engine, 48_000) / full;
+        assert!(ratio < 0.4, "{ratio}");
+
+        // A full-depth swell over two seconds empties the layer halfway.
+        shaped.layers[0] = LayerShape {
+            modulation: Modulation {
+                depth: 1.0,
+                period_seconds: 2.0,
+            },
+            ..LayerShape::default()
+        };
+        let mut engine = AudioEngine::new(48_000.0, shaped, None).unwrap();
+        let levels: Vec<f32> = (0..20)
+            .map(|_| window_rms(&mut engine, 4_800) / full)
+            .collect();
+        // The new engine's volume rises from silence over the first half of
+        // window 0, which leaves it near sqrt(2/3) of the full level and
+        // below the window after it.
+        assert!((0.7..0.9).contains(&levels[0]), "{levels:?}");
+        assert!(levels[0] < levels[1] && levels[1] > 0.8, "{levels:?}");
+        assert!(levels[9] < 0.1 || levels[10] < 0.1, "{levels:?}");
+        assert!(levels[19] > 0.8, "{levels:?}");
+    }
+
+    #[test]
+    fn a_silenced_layer_fades_out_over_the_gate() {
+        let playing = AudioSettings {
+            volume: 0.5,
+            ..AudioSettings::default()
+        };
+        let window_rms = |engine: &mut AudioEngine, frames: usize| {
+            let power: f32 = (0..frames)
+                .map(|_| engine.next_frame()[0])
+                .map(|sample| sample * sample)
+                .sum();
+            (power / frames as f32).sqrt()
+        };
+        let mut engine = AudioEngine::new(8_000.0, playing, None).unwrap();
+        window_rms(&mut engine, 4_000);
+        let full = window_rms(&mut engine, 8_000);
+        let mut silenced = playing;
+        silenced.layers[0].silenced = true;
+        engine.update_settings(silenced);
+        let levels: Vec<f32> = (0..9)
+            .map(|_| window_rms(&mut engine, 8_000) / full)
+            .collect();
+        assert!((0.35..0.65).contains(&levels[3]), "{levels:?}");
+        assert!(levels[8] < 0.01, "{levels:?}");
+    }
+
+    #[test]
+    fn pausing_gates_the_output_and_resuming_restores_the_volume() {
+        let settings = AudioSettings {
+            volume: 0.8,
+            ..AudioSettings::default()
+        };
+        let mut engine = AudioEngine::new(48_000.0, settings, None).unwrap();
+        engine.update_settings(AudioSettings {
+            paused: true,
+            ..settings
+        });
+        for _ in 0..4_800 {
+            engine.next_sample();
         }
+        assert_eq!(engine.next_sample(), 0.0);
+        assert_eq!(engine.user_volume, 0.8);
 
-        eq.process(f32::NAN);
-        eq.process(f32::INFINITY);
+        // The sleep fade still tracks its own gain while paused.
+        engine.set_fade_gain(0.5);
+        assert_eq!(engine.next_sample(), 0.0);
 
-        for _ in 0..1_000 {
-            assert!(eq.process(0.1).is_finite());
+        engine.update_settings(settings);
+        for _ in 0..4_800 {
+            engine.next_sample();
         }
+        assert!((engine.volume.next_value() - 0.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn a_pause_holds_the_sources_in_place_and_a_mute_lets_them_run() {
+        let mut settings = AudioSettings {
+            volume: 0.5,
+            ..AudioSettings::default()
+        };
+        settings.set_mix(SourceMix::solo(SoundStyle::Rain));
+        let mut engine = AudioEngine::new(48_000.0, settings, None).unwrap();
+        let run = |engine: &mut AudioEngine, update: AudioSettings| {
+            engine.update_settings(update);
+            for _ in 0..4_800 {
+                engine.next_frame();
+            }
+            let before = engine.chains[0].rain_player.position;
+            let frame = engine.next_frame();
+            (frame, engine.chains[0].rain_player.position != before)
+        };
+
+        let (frame, moving) = run(
+            &mut engine,
+            AudioSettings {
+                muted: true,
+                ..settings
+            },
+        );
+        assert_eq!(frame, [0.0; 2]);
+        assert!(moving);
+
+        let (frame, moving) = run(
+            &mut engine,
+            AudioSettings {
+                paused: true,
+                ..settings
+            },
+        );
+        assert_eq!(frame, [0.0; 2]);
+        assert!(!moving);
+
+        let (frame, moving) = run(&mut engine, settings);
+        assert_ne!(frame, [0.0; 2]);
+        assert!(moving);
     }
 
     #[test]
-    fn soft_limiter_is_continuous_and_bounded() {
-        assert_eq!(soft_limit(0.8), 0.8);
-        assert!(soft_limit(0.800_001) >= 0.8);
-        assert!(soft_limit(100.0) <= 1.0);
-        assert!(soft_limit(-100.0) >= -1.0);
-        assert_eq!(soft_limit(f32::NAN), 0.0);
+    fn room_tone_plays_on_through_a_pause_and_a_style_switch() {
+        let mut settings = AudioSettings {
+            volume: 0.5,
+            room_tone: 1.0,
+            ..AudioSettings::default()
+        };
+        settings.set_mix(SourceMix::solo(SoundStyle::Brown));
+        let mut engine = AudioEngine::new(48_000.0, settings, None).unwrap();
+        let rms_after = |engine: &mut AudioEngine, update: AudioSettings| {
+            engine.update_settings(update);
+            for _ in 0..4_800 {
+                engine.next_frame();
+            }
+            let sum: f64 = (0..48_000)
+                .map(|_| f64::from(engine.next_sample()).powi(2))
+                .sum();
+            (sum / 48_000.0).sqrt() as f32
+        };
+
+        let playing = rms_after(&mut engine, settings);
+        let paused = rms_after(
+            &mut engine,
+            AudioSettings {
+                paused: true,
+                ..settings
+            },
+        );
+        // 20 dB under the source, scaled by the volume like everything else.
+        let expected = ROOM_TONE_FULL_RMS * 0.5;
+        assert!(
+            (paused / expected - 1.0).abs() < 0.1,
+            "paused {paused}, expected {expected}"
+        );
+        assert!(playing > 5.0 * paused, "playing {playing}, paused {paused}");
+
+        // A switch to the Sample layer with no sample loaded fades the
+        // sources out entirely, and the room tone still never drops out.
+        let mut silent = settings;
+        silent.set_mix(SourceMix::solo(SoundStyle::Sample));
+        engine.update_settings(silent);
+        for _ in 0..48_000 {
+            assert_ne!(engine.next_frame(), [0.0; 2]);
+        }
+
+        let muted = rms_after(
+            &mut engine,
+            AudioSettings {
+                muted: true,
+                ..settings
+            },
+        );
+        assert_eq!(muted, 0.0);
+        let off = rms_after(
+            &mut engine,
+            AudioSettings {
+                room_tone: 0.0,
+                paused: true,
+                ..settings
+            },
+        );
+        assert_eq!(off, 0.0);
     }
 }
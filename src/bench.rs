@@ -0,0 +1,239 @@
+//! Offline rendering benchmark. Nothing here touches an audio device, so it
+//! runs the same on a headless Pi as on a desktop.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, ensure};
+use rand::prelude::{RngExt, SmallRng};
+
+use crate::audio::{
+    AudioEngine, COLORED_NOISE_TARGET_RMS, GraphicEq, NoiseGenerator, RAIN_WAV_DATA,
+    RainSamplePlayer, UserSample, WHITE_NOISE_GAIN,
+};
+use crate::dsp::{BeatTone, BlueNoise, BrownNoise, PeakLimiter, PinkNoise, VioletNoise};
+use crate::settings::{AudioSettings, BandFilters, NoiseRng, SoundStyle, SourceMix, bands};
+
+/// Wall-clock cost of rendering one case.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    pub name: &'static str,
+    pub samples: usize,
+    pub elapsed: Duration,
+}
+
+impl Measurement {
+    pub fn nanos_per_sample(&self) -> f64 {
+        self.elapsed.as_secs_f64() * 1e9 / self.samples.max(1) as f64
+    }
+
+    /// Seconds of audio rendered per second of wall time.
+    pub fn realtime_ratio(&self, sample_rate: u32) -> f64 {
+        let audio_seconds = self.samples as f64 / f64::from(sample_rate);
+        audio_seconds / self.elapsed.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+
+    /// Share of one CPU core needed to keep up in real time.
+    pub fn core_percent(&self, sample_rate: u32) -> f64 {
+        100.0 / self.realtime_ratio(sample_rate)
+    }
+}
+
+fn time(name: &'static str, samples: usize, mut render: impl FnMut()) -> Measurement {
+    let start = Instant::now();
+    for _ in 0..samples {
+        render();
+    }
+    Measurement {
+        name,
+        samples,
+        elapsed: start.elapsed(),
+    }
+}
+
+/// Renders each solo source, and every source at once, through the full
+/// engine: source, EQ, volume ramp, and limiter. The embedded rain recording
+/// stands in for a user sample so the Sample layer has a loop to play.
+pub fn measure_sources(sample_rate: u32, samples: usize) -> Result<Vec<Measurement>> {
+    let all = SourceMix {
+        white: 1.0,
+        pink: 1.0,
+        brown: 1.0,
+        blue: 1.0,
+        violet: 1.0,
+        rain: 1.0,
+        ocean: 1.0,
+        wind: 1.0,
+        fire: 1.0,
+        fan: 1.0,
+        sample: 1.0,
+    };
+    let sample = UserSample::measure(RAIN_WAV_DATA.to_vec())?;
+    let cases = SoundStyle::ALL
+        .map(|style| (style.label(), SourceMix::solo(style)))
+        .into_iter()
+        .chain([("All sources", all)]);
+
+    let mut measurements = Vec::new();
+    for (name, mix) in cases {
+        let mut settings = AudioSettings {
+            volume: 0.5,
+            ..AudioSettings::default()
+        };
+        settings.set_mix(mix);
+        let mut engine = AudioEngine::new(sample_rate as f32, settings, Some(&sample))?;
+        measurements.push(time(name, samples, || {
+            black_box(engine.next_sample());
+        }));
+    }
+    Ok(measurements)
+}
+
+/// Times each DSP stage on its own, fed the same white noise, so the cost of
+/// a source can be told apart from the cost of the shared EQ and limiter.
+/// The EQ and the limiter take both channels at once, so theirs is the cost
+/// of a stereo frame.
+pub fn measure_stages(sample_rate: u32, samples: usize) -> Result<Vec<Measurement>> {
+    let sample_rate = sample_rate as f32;
+    let mut rng: SmallRng = rand::make_rng();
+    let input: Vec<f32> = (0..samples)
+        .map(|_| (rng.random::<f32>() * 2.0 - 1.0) * WHITE_NOISE_GAIN)
+        .collect();
+    let mut inputs = input.iter().copied().cycle();
+
+    // Each `--noise-rng` choice, behind the same dispatch the engine uses.
+    let [mut small, mut xoshiro, mut chacha] =
+        [NoiseRng::Small, NoiseRng::Xoshiro, NoiseRng::Chacha]
+            .map(|kind| NoiseGenerator::new(kind, &mut rng));
+
+    let mut pink = PinkNoise::new(sample_rate, COLORED_NOISE_TARGET_RMS);
+    let mut brown = BrownNoise::new(sample_rate, COLORED_NOISE_TARGET_RMS);
+    let mut blue = BlueNoise::new(sample_rate, COLORED_NOISE_TARGET_RMS);
+    let mut violet = VioletNoise::new(COLORED_NOISE_TARGET_RMS);
+    let mut rain = RainSamplePlayer::embedded(sample_rate)?;
+    let mut tone = BeatTone::new(sample_rate);
+    let mut limiter = PeakLimiter::new(sample_rate, 0.5);
+    let [mut crossover, mut peaking] =
+        [BandFilters::Crossover, BandFilters::Peaking].map(|filters| {
+            GraphicEq::with_bands(sample_rate, AudioSettings::default(), bands(), filters)
+        });
+
+    Ok(vec![
+        time("small RNG", samples, || {
+            black_box(small.random::<f32>() * 2.0 - 1.0);
+        }),
+        time("xoshiro RNG", samples, || {
+            black_box(xoshiro.random::<f32>() * 2.0 - 1.0);
+        }),
+        time("chacha RNG", samples, || {
+            black_box(chacha.random::<f32>() * 2.0 - 1.0);
+        }),
+        time("pink filter", samples, || {
+            black_box(pink.process(inputs.next().unwrap_or_default()));
+        }),
+        time("brown filter", samples, || {
+            black_box(brown.process(inputs.next().unwrap_or_default()));
+        }),
+        time("blue filter", samples, || {
+            black_box(blue.process(inputs.next().unwrap_or_default()));
+        }),
+        time("violet filter", samples, || {
+            black_box(violet.process(inputs.next().unwrap_or_default()));
+        }),
+        time("rain player", samples, || {
+            black_box(rain.next_sample(&mut rng));
+        }),
+        time("binaural tone", samples, || {
+            black_box(tone.binaural(200.0, 4.0));
+        }),
+        time("crossover EQ", samples, || {
+            black_box(crossover.process([inputs.next().unwrap_or_default(); 2]));
+        }),
+        time("peaking EQ", samples, || {
+            black_box(peaking.process([inputs.next().unwrap_or_default(); 2]));
+        }),
+        time("limiter", samples, || {
+            let sample = inputs.next().unwrap_or_default() * 4.0;
+            black_box(limiter.process([sample; 2], 1.0));
+        }),
+    ])
+}
+
+pub fn run(seconds: f32, sample_rate: u32) -> Result<()> {
+    ensure!(sample_rate > 0, "sample rate must be above 0 Hz");
+    let samples = (f64::from(seconds) * f64::from(sample_rate)).round() as usize;
+    ensure!(samples > 0, "benchmark duration is too short to render");
+
+    println!("Rendering {seconds} s of audio per case at {sample_rate} Hz (stereo, one core).");
+    println!();
+    println!("{:<16} {:>12} {:>12}", "Source", "Realtime", "Core use");
+    for measurement in measure_sources(sample_rate, samples)? {
+        println!(
+            "{:<16} {:>11.0}x {:>11.3}%",
+            measurement.name,
+            measurement.realtime_ratio(sample_rate),
+            measurement.core_percent(sample_rate)
+        );
+    }
+
+    println!();
+    println!("{:<16} {:>12} {:>12}", "Stage", "ns/sample", "Core use");
+    for measurement in measure_stages(sample_rate, samples)? {
+        println!(
+            "{:<16} {:>12.1} {:>11.3}%",
+            measurement.name,
+            measurement.nanos_per_sample(),
+            measurement.core_percent(sample_rate)
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measurement_ratios_are_consistent() {
+        let measurement = Measurement {
+            name: "case",
+            samples: 48_000,
+            elapsed: Duration::from_millis(10),
+        };
+        assert!((measurement.realtime_ratio(48_000) - 100.0).abs() < 1e-9);
+        assert!((measurement.core_percent(48_000) - 1.0).abs() < 1e-9);
+        assert!((measurement.nanos_per_sample() - 10e6 / 48_000.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn every_source_and_stage_is_measured() {
+        let sources = measure_sources(48_000, 256).unwrap();
+        let names: Vec<_> = sources.iter().map(|measurement| measurement.name).collect();
+        assert_eq!(
+            names,
+            [
+                "White Noise",
+                "Pink Noise",
+                "Brown Noise",
+                "Blue Noise",
+                "Violet Noise",
+                "Rain",
+                "Ocean",
+                "Wind",
+                "Campfire",
+                "Fan",
+                "Sample",
+                "All sources"
+            ]
+        );
+
+        let stages = measure_stages(48_000, 256).unwrap();
+        assert_eq!(stages.len(), 12);
+        assert!(
+            sources
+                .iter()
+                .chain(&stages)
+                .all(|measurement| measurement.samples == 256)
+        );
+    }
+}
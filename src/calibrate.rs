@@ -0,0 +1,363 @@
+//! Room correction from a microphone: plays a sine sweep through the output,
+//! records it from an input device, and turns the room's coarse response in
+//! each EQ band into a correction applied under the user's EQ.
+
+use std::f32::consts::PI;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::{Data, Device, FromSample, I24, Sample, SampleFormat, SizedSample, StreamConfig, U24};
+
+use crate::device::display_name;
+use crate::dsp::Cutoff;
+use crate::settings::{FrequencyBand, MAX_CORRECTION_BOOST_DB, MAX_CORRECTION_CUT_DB, bands};
+
+const SWEEP_SECONDS: f32 = 10.0;
+const SWEEP_FROM_HZ: f32 = 20.0;
+/// -12 dBFS leaves room for the system volume to do the rest.
+const SWEEP_LEVEL: f32 = 0.25;
+const SWEEP_FADE_SECONDS: f32 = 0.05;
+/// Recorded after the sweep ends, for the output's latency and the room's
+/// decay.
+const TAIL_SECONDS: f32 = 1.5;
+/// A recording quieter than this (-60 dBFS RMS) heard nothing useful.
+const MIN_RECORDING_RMS: f32 = 0.001;
+/// Bands centered from Bass to Brilliance set the reference level. Sub Bass
+/// and Air depend as much on the microphone as on the room, so they are
+/// corrected but do not move the rest.
+const REFERENCE_HZ: std::ops::RangeInclusive<f32> = 60.0..=12_000.0;
+
+/// Plays the sweep through `output`, records it from `input`, and prints the
+/// measured response and the correction. Returns the correction when the
+/// user accepts it, or straight away with `save`.
+pub fn run(
+    output: &Device,
+    config: StreamConfig,
+    sample_format: SampleFormat,
+    input: &Device,
+    save: bool,
+) -> Result<Option<Vec<f32>>> {
+    let output_rate = config.sample_rate as f32;
+    let sweep = log_sweep(output_rate, SWEEP_SECONDS);
+    let input_default = input
+        .default_input_config()
+        .context("failed to query the input device's default format")?;
+    let input_rate = input_default.sample_rate() as f32;
+    let input_config = input_default.config();
+
+    println!(
+        "Playing a {SWEEP_SECONDS:.0} s sweep through {} and recording it with {}.",
+        display_name(output),
+        display_name(input)
+    );
+    println!(
+        "Set a normal listening volume, place the microphone where you listen, and keep the room quiet."
+    );
+
+    let recording = Arc::new(Mutex::new(Vec::with_capacity(
+        (input_rate * (SWEEP_SECONDS + TAIL_SECONDS)) as usize,
+    )));
+    let failed = Arc::new(AtomicBool::new(false));
+    let input_stream = {
+        let channels = usize::from(input_config.channels).max(1);
+        let recording = Arc::clone(&recording);
+        let failed = Arc::clone(&failed);
+        input
+            .build_input_stream_raw(
+                input_config,
+                input_default.sample_format(),
+                move |data, _| {
+                    // The main thread only locks once both streams are gone.
+                    if let Ok(mut recording) = recording.try_lock() {
+                        record_first_channel(data, channels, &mut recording);
+                    }
+                },
+                move |error| {
+                    eprintln!("audio input error: {error}");
+                    failed.store(true, Ordering::Relaxed);
+                },
+                None,
+            )
+            .context("failed to open the input audio stream")?
+    };
+    let output_stream = {
+        let channels = usize::from(config.channels).max(1);
+        let played = sweep.clone();
+        let mut position = 0;
+        let failed = Arc::clone(&failed);
+        output
+            .build_output_stream_raw(
+                config,
+                sample_format,
+                move |data, _| {
+                    write_mono(data, channels, &mut || {
+                        let sample = played.get(position).copied().unwrap_or(0.0);
+                        position += 1;
+                        sample
+                    });
+                },
+                move |error| {
+                    eprintln!("audio stream error: {error}");
+                    failed.store(true, Ordering::Relaxed);
+                },
+                None,
+            )
+            .context("failed to open the output audio stream")?
+    };
+    input_stream.play().context("failed to start recording")?;
+    output_stream.play().context("failed to start playback")?;
+    thread::sleep(Duration::from_secs_f32(SWEEP_SECONDS + TAIL_SECONDS));
+    drop(output_stream);
+    drop(input_stream);
+    if failed.load(Ordering::Relaxed) {
+        bail!("the audio streams failed during the sweep; nothing was measured");
+    }
+
+    let recording = recording
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let response = band_response(&sweep, output_rate, &recording, input_rate)?;
+    let correction = correction_for(&response);
+    println!();
+    println!("{:<12} {:>9} {:>11}", "Band", "Measured", "Correction");
+    for ((band, measured), correction) in bands().iter().zip(&response).zip(&correction) {
+        println!(
+            "{:<12} {:>+6.1} dB {:>+8.1} dB",
+            band.name, measured, correction
+        );
+    }
+
+    if save {
+        return Ok(Some(correction));
+    }
+    if !io::stdin().is_terminal() {
+        println!("Not saved; run in a terminal to confirm, or pass --yes.");
+        return Ok(None);
+    }
+    print!(
+        "Apply this correction whenever {} plays? [y/N] ",
+        display_name(output)
+    );
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "Yes").then_some(correction))
+}
+
+/// An exponential sine sweep from 20 Hz to 20 kHz, or to 0.45 of the rate
+/// below 44.4 kHz, with short raised-cosine fades. It spends equal time in
+/// every octave, so each band's share of its energy is known.
+pub(crate) fn log_sweep(sample_rate: f32, seconds: f32) -> Vec<f32> {
+    let to_hz = 20_000.0_f32.min(sample_rate * 0.45);
+    let frames = (sample_rate * seconds) as usize;
+    let fade = (sample_rate * SWEEP_FADE_SECONDS) as usize;
+    let octaves = (to_hz / SWEEP_FROM_HZ).ln();
+    (0..frames)
+        .map(|index| {
+            // Phase in double precision; ten seconds of phase in f32 would
+            // smear the top octave.
+            let time = index as f64 / f64::from(sample_rate);
+            let phase = 2.0 * std::f64::consts::PI * f64::from(SWEEP_FROM_HZ) * f64::from(seconds)
+                / f64::from(octaves)
+                * ((time * f64::from(octaves) / f64::from(seconds)).exp() - 1.0);
+            let edge = index.min(frames - 1 - index);
+            let envelope = if edge < fade {
+                0.5 - 0.5 * (PI * edge as f32 / fade as f32).cos()
+            } else {
+                1.0
+            };
+            SWEEP_LEVEL * envelope * phase.sin() as f32
+        })
+        .collect()
+}
+
+/// How much louder each band of the layout is in the recording than in the
+/// sweep as played, in dB. Both signals go through the same band filters, so the
+/// sweep's own spectrum and the output's latency cancel out.
+pub(crate) fn band_response(
+    sweep: &[f32],
+    sweep_rate: f32,
+    recording: &[f32],
+    recording_rate: f32,
+) -> Result<Vec<f32>> {
+    let rms = (recording.iter().map(|sample| sample * sample).sum::<f32>()
+        / recording.len().max(1) as f32)
+        .sqrt();
+    if rms < MIN_RECORDING_RMS {
+        bail!("the microphone heard almost nothing; check the input device and the output volume");
+    }
+    Ok(bands()
+        .iter()
+        .map(|band| {
+            let played = band_energy(sweep, sweep_rate, band);
+            let heard = band_energy(recording, recording_rate, band);
+            10.0 * (heard.max(f32::MIN_POSITIVE) / played.max(f32::MIN_POSITIVE)).log10()
+        })
+        .collect())
+}
+
+/// Energy in seconds of full scale, so recordings at different rates compare.
+fn band_energy(signal: &[f32], sample_rate: f32, band: &FrequencyBand) -> f32 {
+    let mut high_pass = Cutoff::high_pass(sample_rate, Some(band.min_freq), 24);
+    let mut low_pass = Cutoff::low_pass(sample_rate, Some(band.max_freq));
+    signal
+        .iter()
+        .map(|sample| low_pass.process(high_pass.process(*sample)).powi(2))
+        .sum::<f32>()
+        / sample_rate
+}
+
+/// The inverse of the response around the reference bands' average, in
+/// half-dB steps. Boosts stop at a few dB: a dip is often a cancellation at
+/// the microphone that more level will not fill. A layout with no band in
+/// the reference range takes its average over every band.
+pub(crate) fn correction_for(response: &[f32]) -> Vec<f32> {
+    let in_reference: Vec<f32> = bands()
+        .iter()
+        .zip(response)
+        .filter(|(band, _)| REFERENCE_HZ.contains(&band.center_frequency()))
+        .map(|(_, db)| *db)
+        .collect();
+    let reference_dbs = if in_reference.is_empty() {
+        response
+    } else {
+        &in_reference
+    };
+    let reference = reference_dbs.iter().sum::<f32>() / reference_dbs.len() as f32;
+    response
+        .iter()
+        .map(|db| {
+            let correction =
+                (reference - db).clamp(-MAX_CORRECTION_CUT_DB, MAX_CORRECTION_BOOST_DB);
+            (correction * 2.0).round() / 2.0
+        })
+        .collect()
+}
+
+fn record_first_channel(data: &Data, channels: usize, recording: &mut Vec<f32>) {
+    match data.sample_format() {
+        SampleFormat::I8 => record::<i8>(data, channels, recording),
+        SampleFormat::I16 => record::<i16>(data, channels, recording),
+        SampleFormat::I24 => record::<I24>(data, channels, recording),
+        SampleFormat::I32 => record::<i32>(data, channels, recording),
+        SampleFormat::I64 => record::<i64>(data, channels, recording),
+        SampleFormat::U8 => record::<u8>(data, channels, recording),
+        SampleFormat::U16 => record::<u16>(data, channels, recording),
+        SampleFormat::U24 => record::<U24>(data, channels, recording),
+        SampleFormat::U32 => record::<u32>(data, channels, recording),
+        SampleFormat::U64 => record::<u64>(data, channels, recording),
+        SampleFormat::F32 => record::<f32>(data, channels, recording),
+        SampleFormat::F64 => record::<f64>(data, channels, recording),
+        // Anything else records silence, which band_response refuses.
+        _ => {}
+    }
+}
+
+/// Appends without reallocating; a full recording drops the rest.
+fn record<T>(data: &Data, channels: usize, recording: &mut Vec<f32>)
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let Some(samples) = data.as_slice::<T>() else {
+        return;
+    };
+    let room = recording.capacity() - recording.len();
+    recording.extend(
+        samples
+            .iter()
+            .step_by(channels)
+            .take(room)
+            .map(|sample| f32::from_sample(*sample)),
+    );
+}
+
+fn write_mono(data: &mut Data, channels: usize, next: &mut impl FnMut() -> f32) {
+    match data.sample_format() {
+        SampleFormat::I8 => play::<i8>(data, channels, next),
+        SampleFormat::I16 => play::<i16>(data, channels, next),
+        SampleFormat::I24 => play::<I24>(data, channels, next),
+        SampleFormat::I32 => play::<i32>(data, channels, next),
+        SampleFormat::I64 => play::<i64>(data, channels, next),
+        SampleFormat::U8 => play::<u8>(data, channels, next),
+        SampleFormat::U16 => play::<u16>(data, channels, next),
+        SampleFormat::U24 => play::<U24>(data, channels, next),
+        SampleFormat::U32 => play::<u32>(data, channels, next),
+        SampleFormat::U64 => play::<u64>(data, channels, next),
+        SampleFormat::F32 => play::<f32>(data, channels, next),
+        SampleFormat::F64 => play::<f64>(data, channels, next),
+        _ => {}
+    }
+}
+
+/// Every channel of a frame gets the same sample.
+fn play<T>(data: &mut Data, channels: usize, next: &mut impl FnMut() -> f32)
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let Some(samples) = data.as_slice_mut::<T>() else {
+        return;
+    };
+    for frame in samples.chunks_mut(channels) {
+        frame.fill(T::from_sample(next()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::Biquad;
+
+    #[test]
+    fn the_sweep_fades_in_and_out_and_stays_under_its_level() {
+        let sweep = log_sweep(48_000.0, 2.0);
+        assert_eq!(sweep.len(), 96_000);
+        assert!(sweep[0].abs() < 1e-6 && sweep[sweep.len() - 1].abs() < 1e-3);
+        let peak = sweep
+            .iter()
+            .fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+        assert!(
+            peak <= SWEEP_LEVEL && peak > SWEEP_LEVEL * 0.99,
+            "peak {peak}"
+        );
+    }
+
+    #[test]
+    fn a_boomy_bass_band_gets_a_cut_and_level_and_latency_do_not_matter() {
+        // The "room": 6 dB too much Bass, 12 dB quieter overall, 30 ms late,
+        // and recorded at a different rate from the sweep.
+        let sweep = log_sweep(48_000.0, 4.0);
+        let recording_rate = 44_100.0;
+        let recorded_sweep = log_sweep(recording_rate, 4.0);
+        let bass = &bands()[1];
+        let mut room = Biquad::new(recording_rate, bass.center_frequency(), bass.q(), 6.0);
+        let recording: Vec<f32> = std::iter::repeat_n(0.0, 1_323)
+            .chain(recorded_sweep)
+            .chain(std::iter::repeat_n(0.0, 44_100))
+            .map(|sample| room.process(sample) * 0.25)
+            .collect();
+
+        let response = band_response(&sweep, 48_000.0, &recording, recording_rate).unwrap();
+        let correction = correction_for(&response);
+        assert!(correction[1] <= -3.0, "{correction:?}");
+        for index in [3, 4, 5, 6] {
+            assert!(correction[index].abs() <= 1.0, "{correction:?}");
+        }
+    }
+
+    #[test]
+    fn corrections_are_limited_and_silence_is_refused() {
+        let correction = correction_for(&[20.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -30.0]);
+        assert_eq!(correction[0], -MAX_CORRECTION_CUT_DB);
+        assert_eq!(correction[7], MAX_CORRECTION_BOOST_DB);
+        assert_eq!(correction[3], 0.0);
+
+        let sweep = log_sweep(8_000.0, 1.0);
+        assert!(band_response(&sweep, 8_000.0, &[0.0; 8_000], 8_000.0).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+//! The clock that sleep timers, fades, and the schedule run on. `Instant`
+//! stops while the machine is suspended on Linux and macOS, so a 45-minute
+//! timer set before a laptop slept for six hours would still have most of
+//! its time left when it woke, and a fade would pick up where it stopped.
+//! `now` is an `Instant` moved on by the time spent suspended: each call
+//! compares how far the wall clock and the monotonic clock have come since
+//! the first, and a gap that grows by more than a couple of seconds is
+//! taken as a suspend and added from then on. Deadlines set before the
+//! suspend have then passed, or come closer, by the time the machine was
+//! asleep, and the next check after waking acts on them.
+//!
+//! The gap only ever grows, so the wall clock being set back changes
+//! nothing; setting it forward while the player runs counts like a
+//! suspend. Measurements that must not count the time asleep, such as the
+//! noise dose, keep using `Instant::now`.
+
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+/// A gap this much larger than the one already counted is a suspend. The
+/// wall clock's own corrections, a few milliseconds at a time, stay under
+/// it.
+const MIN_SUSPEND: Duration = Duration::from_secs(2);
+
+static START: OnceLock<(Instant, SystemTime)> = OnceLock::new();
+static SUSPENDED_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// The current instant, counting time spent suspended.
+pub fn now() -> Instant {
+    let monotonic = Instant::now();
+    let (start, wall_start) = *START.get_or_init(|| (monotonic, SystemTime::now()));
+    let counted = Duration::from_nanos(SUSPENDED_NANOS.load(Ordering::Relaxed));
+    let suspended = match SystemTime::now().duration_since(wall_start) {
+        Ok(wall) => suspended_after(counted, wall, monotonic.duration_since(start)),
+        Err(_) => counted,
+    };
+    if suspended > counted {
+        let nanos = u64::try_from(suspended.as_nanos()).unwrap_or(u64::MAX);
+        SUSPENDED_NANOS.fetch_max(nanos, Ordering::Relaxed);
+    }
+    monotonic + suspended
+}
+
+/// The suspended time to count, given the time already counted and how far
+/// the wall and monotonic clocks have each come since the start.
+fn suspended_after(counted: Duration, wall: Duration, monotonic: Duration) -> Duration {
+    let gap = wall.saturating_sub(monotonic);
+    if gap >= counted + MIN_SUSPEND {
+        gap
+    } else {
+        counted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_a_gap_that_grows_past_the_threshold_counts_as_a_suspend() {
+        let minutes = |minutes: u64| Duration::from_secs(minutes * 60);
+        // Clocks that agree, or nearly, add nothing.
+        assert_eq!(
+            suspended_after(Duration::ZERO, minutes(30), minutes(30)),
+            Duration::ZERO
+        );
+        let drift = minutes(30) + Duration::from_millis(300);
+        assert_eq!(
+            suspended_after(Duration::ZERO, drift, minutes(30)),
+            Duration::ZERO
+        );
+        // Six hours asleep 30 minutes in.
+        assert_eq!(
+            suspended_after(Duration::ZERO, minutes(390), minutes(30)),
+            minutes(360)
+        );
+        // Once counted, the same gap adds nothing more, and a wall clock set
+        // back takes nothing away.
+        assert_eq!(
+            suspended_after(minutes(360), minutes(400), minutes(40)),
+            minutes(360)
+        );
+        assert_eq!(
+            suspended_after(minutes(360), minutes(10), minutes(50)),
+            minutes(360)
+        );
+    }
+
+    #[test]
+    fn now_runs_with_the_monotonic_clock_while_awake() {
+        let before = Instant::now();
+        let now = now();
+        assert!(now >= before);
+        assert!(now.duration_since(before) < Duration::from_secs(1));
+    }
+}
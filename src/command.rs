@@ -0,0 +1,1268 @@
+//! Textual commands for the interactive `:` prompt, such as `band air 20` or
+//! `timer 45m`, with completion and a persisted history of recent lines.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+
+use crate::audio::{headroom_db, trimmed_volume};
+use crate::contour::{MAX_LISTENING_PHON, MIN_LISTENING_PHON};
+use crate::preset::{
+    apply_preset, delete_preset, list_presets, load_preset_with_warnings, save_preset, summary,
+};
+use crate::scene::{apply_scene, list_scenes, load_scene, scenes_beside};
+use crate::settings::{
+    AudioSettings, BandOverlay, EQ_MAX_DB, EQ_MIN_DB, FREQUENCY_BANDS, HIGH_PASS_SLOPES_DB,
+    Listener, MAX_BANDS, MAX_BEAT_HZ, MAX_CARRIER_HZ, MAX_MAX_SPL_DB, MAX_SPL_OFFSET_DB,
+    MAX_TILT_DB, MIN_BEAT_HZ, MIN_CARRIER_HZ, MIN_MAX_SPL_DB, MIN_SPL_OFFSET_DB, OutputProfile,
+    SoundStyle, SourceMix, TILT_BANDS, ToneMode, bands, config_path, db_to_slider, format_hz,
+    slider_to_db,
+};
+use crate::share::ShareCode;
+use crate::timer::{SleepTimer, TimerAlarm, format_remaining, parse_duration};
+use crate::trainer::{DEFAULT_TRAINER_DB, Drill, MAX_TRAINER_DB, MIN_TRAINER_DB};
+use crate::{
+    parse_ceiling, parse_high_pass, parse_mix, parse_notch, parse_notch_width, parse_percentage,
+    parse_room_tone, parse_slope, parse_width,
+};
+
+const COMMANDS: [&str; 25] = [
+    "volume",
+    "band",
+    "tilt",
+    "ceiling",
+    "notch",
+    "highpass",
+    "style",
+    "mix",
+    "width",
+    "crossfeed",
+    "headroom",
+    "trim",
+    "profile",
+    "tone",
+    "roomtone",
+    "contour",
+    "spl",
+    "listener",
+    "reset",
+    "timer",
+    "preset",
+    "scene",
+    "share",
+    "train",
+    "quit",
+];
+const USAGE: &str = "commands: volume PERCENT|DB, band NAME PERCENT|DB, tilt bass|mid DB, ceiling HZ|off, notch HZ|off, notch width OCTAVES, highpass HZ|off, highpass slope 12|24, style SOURCE, mix SOURCE=PERCENT,..., width mono|normal|wide|PERCENT, crossfeed on|off, headroom on|off, trim, profile NAME, tone off|binaural|isochronic, tone carrier|beat HZ, tone level PERCENT, roomtone PERCENT|off, contour on|off|PHON, spl DB, spl offset DB|off, spl max DB|off, listener [a|b], reset, timer DURATION [fade|visual]|off, preset load|save|delete NAME, scene NAME, share [CODE], train colors|bands [DB], quit";
+const HISTORY_LIMIT: usize = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Volume(f32),
+    /// Band index and slider position; 50 percent is 0 dB.
+    Band(usize, f32),
+    /// `TILT_BANDS` index and tilt in dB.
+    Tilt(usize, f32),
+    /// Low-pass corner in Hz; `None` is off.
+    Ceiling(Option<f32>),
+    /// Notch center in Hz; `None` is off.
+    Notch(Option<f32>),
+    /// Notch width in octaves.
+    NotchWidth(f32),
+    /// High-pass corner in Hz; `None` is off.
+    HighPass(Option<f32>),
+    /// High-pass slope in dB per octave.
+    HighPassSlope(u32),
+    Style(SoundStyle),
+    Mix(SourceMix),
+    /// Stereo width; 0 is mono.
+    Width(f32),
+    Crossfeed(bool),
+    Headroom(bool),
+    /// Lowers the volume until the estimated peaks sit at the limiter's
+    /// threshold.
+    Trim,
+    Profile(OutputProfile),
+    ToneMode(ToneMode),
+    ToneCarrier(f32),
+    ToneBeat(f32),
+    ToneLevel(f32),
+    /// Room tone level as a power fraction; 0 is off.
+    RoomTone(f32),
+    Contour(bool),
+    /// Listening level in phon; also turns the contour on.
+    ContourLevel(f32),
+    /// dB SPL a meter reads at the listening position right now; the
+    /// interface turns it into an offset with its output meter.
+    SplReading(f32),
+    /// dB SPL at full-scale RMS; `None` forgets the calibration.
+    SplOffset(Option<f32>),
+    /// Cap on the output in dB SPL; `None` is off.
+    SplMax(Option<f32>),
+    /// Play a listener's volume and EQ; `None` is the other one.
+    Listener(Option<Listener>),
+    ResetEq,
+    /// A timer with the session's alarm (see `--alarm`), or none.
+    Timer(Option<Duration>),
+    TimerWithAlarm(Duration, TimerAlarm),
+    LoadPreset(String),
+    SavePreset(String),
+    DeletePreset(String),
+    LoadScene(String),
+    /// Show the share code for the sound playing.
+    Share,
+    LoadShareCode(ShareCode),
+    /// Open the ear trainer, which the interface runs.
+    Train(Drill),
+    Quit,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> std::result::Result<Self, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((name, arguments)) = words.split_first() else {
+            return Err(USAGE.to_owned());
+        };
+        let command = match (name.to_lowercase().as_str(), arguments) {
+            ("volume", [level]) => Self::Volume(parse_volume(level)?),
+            ("band", [band, percent]) => {
+                let index = band_index(band).ok_or_else(|| {
+                    format!("unknown band '{band}' (valid: {})", band_names().join(", "))
+                })?;
+                Self::Band(index, parse_band_level(percent)?)
+            }
+            ("tilt", [band, db]) => {
+                let tilt = tilt_band_names()
+                    .iter()
+                    .position(|name| name_key(name) == name_key(band))
+                    .ok_or_else(|| {
+                        format!("only {} take a tilt", tilt_band_names().join(" and "))
+                    })?;
+                Self::Tilt(tilt, parse_tilt(db)?)
+            }
+            ("ceiling", [hz]) => Self::Ceiling(parse_ceiling(hz)?),
+            ("notch", [setting, octaves]) if setting.eq_ignore_ascii_case("width") => {
+                Self::NotchWidth(parse_notch_width(octaves)?)
+            }
+            ("notch", [hz]) => Self::Notch(parse_notch(hz)?),
+            ("highpass", [setting, slope]) if setting.eq_ignore_ascii_case("slope") => {
+                Self::HighPassSlope(parse_slope(slope)?)
+            }
+            ("highpass", [hz]) => Self::HighPass(parse_high_pass(hz)?),
+            ("style", [source]) => Self::Style(
+                SoundStyle::from_str(source, true)
+                    .map_err(|_| format!("unknown source '{source}'"))?,
+            ),
+            ("mix", pairs) if !pairs.is_empty() => Self::Mix(parse_mix(&pairs.join(""))?),
+            ("width", [width]) => Self::Width(parse_width(width)?),
+            ("crossfeed", [state]) => match state.to_lowercase().as_str() {
+                "on" => Self::Crossfeed(true),
+                "off" => Self::Crossfeed(false),
+                _ => return Err("crossfeed takes on or off".to_owned()),
+            },
+            ("headroom", [state]) => match state.to_lowercase().as_str() {
+                "on" => Self::Headroom(true),
+                "off" => Self::Headroom(false),
+                _ => return Err("headroom takes on or off".to_owned()),
+            },
+            ("trim", []) => Self::Trim,
+            ("profile", [name]) => {
+                Self::Profile(OutputProfile::from_str(name, true).map_err(|_| {
+                    format!(
+                        "unknown profile '{name}' (valid: {})",
+                        profile_names().join(", ")
+                    )
+                })?)
+            }
+            ("tone", [setting, value]) => match setting.to_lowercase().as_str() {
+                "carrier" => {
+                    Self::ToneCarrier(parse_hz(value, "carrier", MIN_CARRIER_HZ, MAX_CARRIER_HZ)?)
+                }
+                "beat" => Self::ToneBeat(parse_hz(value, "beat", MIN_BEAT_HZ, MAX_BEAT_HZ)?),
+                "level" => Self::ToneLevel(
+                    parse_percentage(value)
+                        .map_err(|_| "tone level must be a number from 0 to 100".to_owned())?,
+                ),
+                _ => return Err("tone takes carrier HZ, beat HZ, or level PERCENT".to_owned()),
+            },
+            ("tone", [mode]) => Self::ToneMode(
+                ToneMode::from_str(mode, true)
+                    .map_err(|_| "tone takes off, binaural, or isochronic".to_owned())?,
+            ),
+            ("roomtone", [level]) => Self::RoomTone(parse_room_tone(level)?),
+            ("contour", [state]) => match state.to_lowercase().as_str() {
+                "on" => Self::Contour(true),
+                "off" => Self::Contour(false),
+                _ => Self::ContourLevel(parse_phon(state)?),
+            },
+            ("spl", [setting, db]) if setting.eq_ignore_ascii_case("offset") => Self::SplOffset(
+                parse_spl(db, "spl offset", MIN_SPL_OFFSET_DB, MAX_SPL_OFFSET_DB)?,
+            ),
+            ("spl", [setting, db]) if setting.eq_ignore_ascii_case("max") => {
+                Self::SplMax(parse_spl(db, "spl max", MIN_MAX_SPL_DB, MAX_MAX_SPL_DB)?)
+            }
+            ("spl", [db]) => Self::SplReading(
+                parse_spl(db, "an SPL reading", MIN_MAX_SPL_DB, MAX_MAX_SPL_DB)?
+                    .ok_or_else(|| "spl takes the dB SPL your meter reads now".to_owned())?,
+            ),
+            ("listener", []) => Self::Listener(None),
+            ("listener", [name]) if name.eq_ignore_ascii_case("a") => {
+                Self::Listener(Some(Listener::A))
+            }
+            ("listener", [name]) if name.eq_ignore_ascii_case("b") => {
+                Self::Listener(Some(Listener::B))
+            }
+            ("listener", _) => return Err("listener takes a or b, or nothing to swap".to_owned()),
+            ("reset", []) => Self::ResetEq,
+            ("timer", [length]) if length.eq_ignore_ascii_case("off") => Self::Timer(None),
+            ("timer", [length]) => Self::Timer(Some(parse_duration(length)?)),
+            ("timer", [length, alarm]) => Self::TimerWithAlarm(
+                parse_duration(length)?,
+                TimerAlarm::from_str(alarm, true)
+                    .map_err(|_| "a timer's alarm is fade or visual".to_owned())?,
+            ),
+            ("preset", [action, name @ ..]) if !name.is_empty() => {
+                let name = name.join(" ");
+                match action.to_lowercase().as_str() {
+                    "load" => Self::LoadPreset(name),
+                    "save" => Self::SavePreset(name),
+                    "delete" => Self::DeletePreset(name),
+                    _ => return Err("preset takes load, save, or delete and a name".to_owned()),
+                }
+            }
+            ("scene", name) if !name.is_empty() => Self::LoadScene(name.join(" ")),
+            ("share", []) => Self::Share,
+            // A code read out in groups may come back with spaces for dashes.
+            ("share", code) => Self::LoadShareCode(code.join("-").parse()?),
+            ("train", []) => Self::Train(Drill::Colors),
+            ("train", [drill]) if drill.eq_ignore_ascii_case("colors") => {
+                Self::Train(Drill::Colors)
+            }
+            ("train", [drill]) if drill.eq_ignore_ascii_case("bands") => {
+                Self::Train(Drill::Bands(DEFAULT_TRAINER_DB))
+            }
+            ("train", [drill, db]) if drill.eq_ignore_ascii_case("bands") => {
+                Self::Train(Drill::Bands(parse_trainer_db(db)?))
+            }
+            ("train", _) => return Err("train takes colors, or bands and a dB change".to_owned()),
+            ("quit", []) => Self::Quit,
+            _ => return Err(USAGE.to_owned()),
+        };
+        Ok(command)
+    }
+
+    /// Applies the command to the shared settings and describes the result,
+    /// or the error. Preset commands read and write files in `presets`, and
+    /// scenes are read from the `scenes/` folder beside it.
+    /// Quitting is left to the caller.
+    pub fn apply(
+        self,
+        settings: &mut AudioSettings,
+        fade_out: Duration,
+        now: Instant,
+        presets: &Path,
+    ) -> String {
+        match self {
+            Self::Volume(volume) => {
+                settings.volume = volume;
+                format!("volume {:.0}%", volume * 100.0)
+            }
+            Self::Band(index, value) => {
+                settings.frequency_bands[index] = value;
+                format!(
+                    "{} {:.0}% = {:+.1} dB",
+                    bands()[index].name,
+                    value * 100.0,
+                    slider_to_db(value)
+                )
+            }
+            Self::Tilt(tilt, db) => {
+                settings.band_tilts[tilt] = db;
+                format!(
+                    "{} tilt {db:+.1} dB",
+                    FREQUENCY_BANDS[TILT_BANDS[tilt]].name
+                )
+            }
+            Self::Ceiling(ceiling) => {
+                settings.ceiling_hz = ceiling;
+                match ceiling {
+                    Some(hz) => format!("ceiling {hz:.0} Hz"),
+                    None => "ceiling off".to_owned(),
+                }
+            }
+            Self::Notch(notch) => {
+                settings.notch.hz = notch;
+                match notch {
+                    Some(hz) => format!("notch {} Hz", format_hz(hz)),
+                    None => "notch off".to_owned(),
+                }
+            }
+            Self::NotchWidth(octaves) => {
+                settings.notch.width_octaves = octaves;
+                format!("notch width {octaves:.1} octaves")
+            }
+            Self::HighPass(high_pass) => {
+                settings.high_pass.hz = high_pass;
+                match high_pass {
+                    Some(hz) => format!("high-pass {} Hz", format_hz(hz)),
+                    None => "high-pass off".to_owned(),
+                }
+            }
+            Self::HighPassSlope(slope_db) => {
+                settings.high_pass.slope_db = slope_db;
+                format!("high-pass slope {slope_db} dB/octave")
+            }
+            Self::Style(style) => {
+                settings.set_mix(SourceMix::solo(style));
+                format!("source {}", style.label())
+            }
+            Self::Mix(mix) => {
+                settings.set_mix(mix);
+                format!("source {}", settings.mix().describe())
+            }
+            Self::Width(width) => {
+                settings.stereo_width = width;
+                format!("stereo width {:.0}%", width * 100.0)
+            }
+            Self::Crossfeed(enabled) => {
+                settings.crossfeed = enabled;
+                format!("crossfeed {}", if enabled { "on" } else { "off" })
+            }
+            Self::Headroom(enabled) => {
+                settings.headroom = enabled;
+                let db = headroom_db(*settings);
+                if db < 0.0 {
+                    format!("headroom on, volume {db:+.1} dB for the EQ boosts")
+                } else {
+                    format!("headroom {}", if enabled { "on" } else { "off" })
+                }
+            }
+            Self::Trim => match trimmed_volume(*settings) {
+                Some(volume) => {
+                    let db = 20.0 * (volume / settings.volume).log10();
+                    settings.volume = volume;
+                    format!(
+                        "volume {:.0}%, {db:+.1} dB to keep the peaks under the limiter",
+                        volume * 100.0
+                    )
+                }
+                None => "nothing to trim; the peaks stay under the limiter".to_owned(),
+            },
+            Self::Profile(profile) => {
+                profile.apply(settings);
+                format!("{} profile", profile.name())
+            }
+            Self::ToneMode(mode) => {
+                settings.tone.mode = mode;
+                format!("tone {}", settings.tone.describe())
+            }
+            Self::ToneCarrier(hz) => {
+                settings.tone.carrier_hz = hz;
+                format!("tone carrier {hz:.0} Hz")
+            }
+            Self::ToneBeat(hz) => {
+                settings.tone.beat_hz = hz;
+                format!("tone beat {} Hz", format_hz(hz))
+            }
+            Self::ToneLevel(level) => {
+                settings.tone.level = level;
+                format!("tone level {:.0}%", level * 100.0)
+            }
+            Self::RoomTone(level) => {
+                settings.room_tone = level;
+                if level > 0.0 {
+                    format!("room tone {:.0}%", level * 100.0)
+                } else {
+                    "room tone off".to_owned()
+                }
+            }
+            Self::Contour(enabled) => {
+                settings.listening_contour = enabled;
+                format!("listening contour {}", if enabled { "on" } else { "off" })
+            }
+            Self::ContourLevel(phon) => {
+                settings.listening_contour = true;
+                settings.listening_phon = phon;
+                format!("listening contour on at {phon:.0} phon")
+            }
+            Self::SplReading(_) => {
+                "an SPL reading needs the interface's output meter; spl offset DB sets the calibration directly".to_owned()
+            }
+            Self::SplOffset(offset) => {
+                settings.spl.offset_db = offset;
+                match offset {
+                    Some(db) => format!("SPL calibration {db:.1} dB at full scale"),
+                    None => "SPL calibration off".to_owned(),
+                }
+            }
+            Self::SplMax(max) => {
+                settings.spl.max_db = max;
+                match (max, settings.spl.offset_db) {
+                    (Some(db), Some(_)) => format!("SPL cap {db:.0} dB"),
+                    (Some(db), None) => {
+                        format!("SPL cap {db:.0} dB, held once a reading calibrates it (spl DB)")
+                    }
+                    (None, _) => "SPL cap off".to_owned(),
+                }
+            }
+            Self::Listener(listener) => {
+                let to = listener.unwrap_or(settings.listeners.active.other());
+                settings.switch_listener(to);
+                format!(
+                    "listener {} at {:.0}% volume",
+                    to.label(),
+                    settings.volume * 100.0
+                )
+            }
+            Self::ResetEq => {
+                settings.frequency_bands = [0.5; MAX_BANDS];
+                settings.band_tilts = [0.0; TILT_BANDS.len()];
+                settings.band_overlay = BandOverlay::default();
+                "EQ reset to 0 dB".to_owned()
+            }
+            Self::Timer(Some(length)) => Self::TimerWithAlarm(length, TimerAlarm::Fade)
+                .apply(settings, fade_out, now, presets),
+            Self::TimerWithAlarm(length, alarm) => {
+                settings.sleep_timer =
+                    Some(SleepTimer::new(length, fade_out, now).with_alarm(alarm));
+                let kind = match alarm {
+                    TimerAlarm::Fade => "sleep",
+                    TimerAlarm::Visual => "visual",
+                };
+                format!("{kind} timer {}", format_remaining(length))
+            }
+            Self::Timer(None) => {
+                settings.sleep_timer = None;
+                "sleep timer off".to_owned()
+            }
+            Self::LoadPreset(name) => match load_preset_with_warnings(presets, &name) {
+                Ok((preset, warnings)) => {
+                    apply_preset(preset, settings);
+                    with_warnings(format!("loaded preset {}", name.trim()), &warnings)
+                }
+                Err(error) => format!("{error:#}"),
+            },
+            Self::SavePreset(name) => match save_preset(presets, &name, settings) {
+                Ok(()) => format!("saved preset {}", name.trim()),
+                Err(error) => format!("{error:#}"),
+            },
+            Self::DeletePreset(name) => match delete_preset(presets, &name) {
+                Ok(()) => format!("deleted preset {}", name.trim()),
+                Err(error) => format!("{error:#}"),
+            },
+            Self::LoadScene(name) => match load_scene(&scenes_beside(presets), &name) {
+                Ok(scene) => {
+                    apply_scene(&scene, settings, fade_out, now);
+                    with_warnings(format!("loaded scene {}", name.trim()), &scene.warnings)
+                }
+                Err(error) => format!("{error:#}"),
+            },
+            Self::Share => format!("share code {}", ShareCode::of(settings)),
+            Self::LoadShareCode(code) => {
+                code.apply(settings);
+                format!("loaded share code: {}", summary(settings))
+            }
+            Self::Train(_) => "the ear trainer runs in the interface".to_owned(),
+            Self::Quit => "quitting".to_owned(),
+        }
+    }
+}
+
+/// A reply with a file's compatibility warnings after it, on one line.
+fn with_warnings(mut reply: String, warnings: &[String]) -> String {
+    for warning in warnings {
+        reply.push_str(&format!("; warning: {warning}"));
+    }
+    reply
+}
+
+fn parse_hz(value: &str, name: &str, min: f32, max: f32) -> std::result::Result<f32, String> {
+    let invalid = || format!("{name} must be a number of Hz from {min} to {max}");
+    let hz = value
+        .trim_end_matches("Hz")
+        .trim_end_matches("hz")
+        .parse::<f32>()
+        .map_err(|_| invalid())?;
+    if !hz.is_finite() || !(min..=max).contains(&hz) {
+        return Err(invalid());
+    }
+    Ok(hz)
+}
+
+/// `Sub Bass` is typed as `sub-bass`; spaces, hyphens, and case are ignored.
+fn band_names() -> Vec<String> {
+    bands().iter().map(|band| typed_name(&band.name)).collect()
+}
+
+fn typed_name(name: &str) -> String {
+    name.to_lowercase().replace(' ', "-")
+}
+
+fn name_key(text: &str) -> String {
+    text.to_lowercase().replace([' ', '-', '_'], "")
+}
+
+fn profile_names() -> Vec<String> {
+    OutputProfile::value_variants()
+        .iter()
+        .map(|profile| profile.name().to_owned())
+        .collect()
+}
+
+/// The tilts sit on the built-in Bass and Mid whatever the band layout.
+fn tilt_band_names() -> Vec<String> {
+    TILT_BANDS
+        .map(|index| typed_name(&FREQUENCY_BANDS[index].name))
+        .to_vec()
+}
+
+/// A dB SPL figure from `min` to `max`, or `off` for None.
+fn parse_spl(
+    value: &str,
+    name: &str,
+    min: f32,
+    max: f32,
+) -> std::result::Result<Option<f32>, String> {
+    if value.eq_ignore_ascii_case("off") {
+        return Ok(None);
+    }
+    let invalid = || format!("{name} must be off or a number of dB from {min} to {max}");
+    let db = value
+        .to_lowercase()
+        .trim_end_matches("spl")
+        .trim_end_matches("db")
+        .parse::<f32>()
+        .map_err(|_| invalid())?;
+    if !db.is_finite() || !(min..=max).contains(&db) {
+        return Err(invalid());
+    }
+    Ok(Some(db))
+}
+
+fn parse_phon(value: &str) -> std::result::Result<f32, String> {
+    let invalid = || {
+        format!(
+            "contour takes on, off, or a listening level of {MIN_LISTENING_PHON} to {MAX_LISTENING_PHON} phon"
+        )
+    };
+    let phon = value
+        .to_lowercase()
+        .trim_end_matches("phon")
+        .parse::<f32>()
+        .map_err(|_| invalid())?;
+    if !phon.is_finite() || !(MIN_LISTENING_PHON..=MAX_LISTENING_PHON).contains(&phon) {
+        return Err(invalid());
+    }
+    Ok(phon)
+}
+
+/// A percentage, or a gain in dB up to 0 (`-12dB`).
+fn parse_volume(value: &str) -> std::result::Result<f32, String> {
+    let Some(db) = db_value(value) else {
+        return parse_percentage(value);
+    };
+    let invalid = || "volume in dB must be a number up to 0dB".to_owned();
+    let db = db.parse::<f32>().map_err(|_| invalid())?;
+    if db.is_nan() || db > 0.0 {
+        return Err(invalid());
+    }
+    Ok(10_f32.powf(db / 20.0))
+}
+
+/// A slider percentage, or a gain in dB within the EQ range (`-3dB`).
+fn parse_band_level(value: &str) -> std::result::Result<f32, String> {
+    let Some(db) = db_value(value) else {
+        return parse_percentage(value)
+            .map_err(|_| "band level must be a number from 0 to 100".to_owned());
+    };
+    let invalid = || format!("band level in dB must be a number from {EQ_MIN_DB} to +{EQ_MAX_DB}");
+    let db = db.parse::<f32>().map_err(|_| invalid())?;
+    if !db.is_finite() || !(EQ_MIN_DB..=EQ_MAX_DB).contains(&db) {
+        return Err(invalid());
+    }
+    Ok(db_to_slider(db))
+}
+
+/// `value` without a `dB` suffix, or None when it has none.
+fn db_value(value: &str) -> Option<&str> {
+    value
+        .strip_suffix("dB")
+        .or_else(|| value.strip_suffix("db"))
+}
+
+fn parse_tilt(value: &str) -> std::result::Result<f32, String> {
+    let invalid = || format!("tilt must be a number of dB from -{MAX_TILT_DB} to {MAX_TILT_DB}");
+    let db = value
+        .trim_end_matches("dB")
+        .trim_end_matches("db")
+        .parse::<f32>()
+        .map_err(|_| invalid())?;
+    if !db.is_finite() || db.abs() > MAX_TILT_DB {
+        return Err(invalid());
+    }
+    Ok(db)
+}
+
+fn parse_trainer_db(value: &str) -> std::result::Result<f32, String> {
+    let invalid = || format!("the trainer moves a band by {MIN_TRAINER_DB} to {MAX_TRAINER_DB} dB");
+    let db = value
+        .trim_end_matches("dB")
+        .trim_end_matches("db")
+        .parse::<f32>()
+        .map_err(|_| invalid())?;
+    if !db.is_finite() || !(MIN_TRAINER_DB..=MAX_TRAINER_DB).contains(&db) {
+        return Err(invalid());
+    }
+    Ok(db)
+}
+
+fn band_index(name: &str) -> Option<usize> {
+    bands()
+        .iter()
+        .position(|band| name_key(&band.name) == name_key(name))
+}
+
+/// Tab completion of the last word, including preset and scene names found
+/// in `presets` and beside it. A unique match is completed with a trailing space; several
+/// matches extend to their common prefix and are returned so the prompt can
+/// list them.
+pub fn complete(line: &str, presets: &Path) -> (String, Vec<String>) {
+    let mut words: Vec<&str> = line.split_whitespace().collect();
+    let current = if line.ends_with(char::is_whitespace) {
+        ""
+    } else {
+        words.pop().unwrap_or_default()
+    };
+    let candidates: Vec<String> = match words.as_slice() {
+        [] => COMMANDS.map(str::to_owned).to_vec(),
+        [command] => match command.to_lowercase().as_str() {
+            "band" => band_names(),
+            "tilt" => tilt_band_names(),
+            "ceiling" => vec!["off".to_owned()],
+            "notch" => vec!["off".to_owned(), "width".to_owned()],
+            "highpass" => vec!["off".to_owned(), "slope".to_owned()],
+            "style" => SoundStyle::value_variants()
+                .iter()
+                .filter_map(|style| style.to_possible_value())
+                .map(|value| value.get_name().to_owned())
+                .collect(),
+            "width" => ["mono", "normal", "wide"].map(str::to_owned).to_vec(),
+            "crossfeed" | "headroom" => vec!["on".to_owned(), "off".to_owned()],
+            "profile" => profile_names(),
+            "tone" => ["off", "binaural", "isochronic", "carrier", "beat", "level"]
+                .map(str::to_owned)
+                .to_vec(),
+            "roomtone" => vec!["off".to_owned()],
+            "contour" => vec!["on".to_owned(), "off".to_owned()],
+            "listener" => vec!["a".to_owned(), "b".to_owned()],
+            "spl" => vec!["offset".to_owned(), "max".to_owned()],
+            "timer" => vec!["off".to_owned()],
+            "preset" => vec!["load".to_owned(), "save".to_owned(), "delete".to_owned()],
+            "scene" => list_scenes(&scenes_beside(presets)).unwrap_or_default(),
+            _ => Vec::new(),
+        },
+        [command, length]
+            if command.eq_ignore_ascii_case("timer") && !length.eq_ignore_ascii_case("off") =>
+        {
+            vec!["fade".to_owned(), "visual".to_owned()]
+        }
+        [command, setting]
+            if command.eq_ignore_ascii_case("highpass")
+                && setting.eq_ignore_ascii_case("slope") =>
+        {
+            HIGH_PASS_SLOPES_DB.map(|slope| slope.to_string()).to_vec()
+        }
+        [command, action] if command.eq_ignore_ascii_case("preset") => {
+            match action.to_lowercase().as_str() {
+                "load" | "delete" => list_presets(presets).unwrap_or_default(),
+                _ => Vec::new(),
+            }
+        }
+        _ => Vec::new(),
+    };
+    let prefix = current.to_lowercase();
+    let matches: Vec<String> = candidates
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(&prefix))
+        .collect();
+
+    let stem = &line[..line.len() - current.len()];
+    match matches.as_slice() {
+        [] => (line.to_owned(), matches),
+        [only] => (format!("{stem}{only} "), Vec::new()),
+        [first, rest @ ..] => {
+            let common = rest.iter().fold(first.len(), |length, candidate| {
+                first
+                    .bytes()
+                    .zip(candidate.bytes())
+                    .take(length)
+                    .take_while(|(left, right)| left == right)
+                    .count()
+            });
+            (format!("{stem}{}", &first[..common]), matches)
+        }
+    }
+}
+
+/// Recent prompt lines, oldest first, with Up/Down browsing.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CommandHistory {
+    entries: VecDeque<String>,
+    // Index into entries while browsing; None means a fresh line.
+    browsing: Option<usize>,
+}
+
+impl CommandHistory {
+    /// Records a submitted line, skipping blanks and immediate repeats.
+    pub fn push(&mut self, line: &str) {
+        self.browsing = None;
+        let line = line.trim();
+        if line.is_empty() || self.entries.back().is_some_and(|last| last == line) {
+            return;
+        }
+        if self.entries.len() == HISTORY_LIMIT {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(line.to_owned());
+    }
+
+    pub fn older(&mut self) -> Option<&str> {
+        let index = match self.browsing {
+            None => self.entries.len().checked_sub(1)?,
+            Some(index) => index.saturating_sub(1),
+        };
+        self.browsing = Some(index);
+        self.entries.get(index).map(String::as_str)
+    }
+
+    /// Steps toward the newest entry; past it, returns to a blank line.
+    pub fn newer(&mut self) -> Option<&str> {
+        let index = self.browsing? + 1;
+        if index >= self.entries.len() {
+            self.browsing = None;
+            return None;
+        }
+        self.browsing = Some(index);
+        self.entries.get(index).map(String::as_str)
+    }
+
+    pub fn stop_browsing(&mut self) {
+        self.browsing = None;
+    }
+}
+
+/// Kept beside settings.toml, one command per line.
+fn history_path() -> PathBuf {
+    config_path().with_file_name("command_history")
+}
+
+pub fn load_command_history() -> Result<CommandHistory> {
+    load_history_from(&history_path())
+}
+
+fn load_history_from(path: &Path) -> Result<CommandHistory> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(CommandHistory::default()),
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to read {}", path.display()));
+        }
+    };
+    let mut history = CommandHistory::default();
+    for line in content.lines() {
+        history.push(line);
+    }
+    Ok(history)
+}
+
+pub fn save_command_history(history: &CommandHistory) -> Result<()> {
+    save_history_to(&history_path(), history)
+}
+
+fn save_history_to(path: &Path, history: &CommandHistory) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let mut content = String::new();
+    for line in &history.entries {
+        content.push_str(line);
+        content.push('\n');
+    }
+    fs::write(path, content).with_context(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_presets(label: &str) -> PathBuf {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "whitenoise-command-test-{}-{label}",
+            std::process::id()
+        ));
+        path.push("presets");
+        path
+    }
+
+    #[test]
+    fn commands_parse_with_their_arguments() {
+        assert_eq!(Command::parse("volume 20"), Ok(Command::Volume(0.2)));
+        assert_eq!(Command::parse("band air 20"), Ok(Command::Band(7, 0.2)));
+        assert_eq!(
+            Command::parse("BAND Sub-Bass 100"),
+            Ok(Command::Band(0, 1.0))
+        );
+        assert_eq!(Command::parse("band lowmid 50"), Ok(Command::Band(2, 0.5)));
+        let Ok(Command::Volume(volume)) = Command::parse("volume -6dB") else {
+            panic!("volume in dB");
+        };
+        assert!((volume - 0.501).abs() < 0.001, "{volume}");
+        assert_eq!(Command::parse("band air +6db"), Ok(Command::Band(7, 0.75)));
+        assert_eq!(Command::parse("band air -12dB"), Ok(Command::Band(7, 0.0)));
+        assert_eq!(Command::parse("headroom on"), Ok(Command::Headroom(true)));
+        assert_eq!(Command::parse("trim"), Ok(Command::Trim));
+        assert_eq!(
+            Command::parse("tilt mid -2.5dB"),
+            Ok(Command::Tilt(1, -2.5))
+        );
+        assert_eq!(
+            Command::parse("ceiling 8k"),
+            Ok(Command::Ceiling(Some(8_000.0)))
+        );
+        assert_eq!(Command::parse("ceiling OFF"), Ok(Command::Ceiling(None)));
+        assert_eq!(
+            Command::parse("notch 6.5k"),
+            Ok(Command::Notch(Some(6_500.0)))
+        );
+        assert_eq!(Command::parse("notch off"), Ok(Command::Notch(None)));
+        assert_eq!(
+            Command::parse("notch width 0.5oct"),
+            Ok(Command::NotchWidth(0.5))
+        );
+        assert!(Command::parse("notch width 3").is_err());
+        assert!(Command::parse("notch 100").is_err());
+        assert_eq!(
+            Command::parse("highpass 40"),
+            Ok(Command::HighPass(Some(40.0)))
+        );
+        assert_eq!(
+            Command::parse("highpass slope 12"),
+            Ok(Command::HighPassSlope(12))
+        );
+        assert!(Command::parse("highpass slope 6").is_err());
+        assert_eq!(
+            Command::parse("style rain"),
+            Ok(Command::Style(SoundStyle::Rain))
+        );
+        assert!(matches!(
+            Command::parse("mix rain=60, brown=40"),
+            Ok(Command::Mix(mix)) if mix.rain == 0.6 && mix.brown == 0.4
+        ));
+        assert_eq!(Command::parse("width wide"), Ok(Command::Width(1.0)));
+        assert_eq!(Command::parse("width 30"), Ok(Command::Width(0.3)));
+        assert_eq!(Command::parse("crossfeed ON"), Ok(Command::Crossfeed(true)));
+        assert_eq!(
+            Command::parse("profile Small-Speaker"),
+            Ok(Command::Profile(OutputProfile::SmallSpeaker))
+        );
+        assert!(Command::parse("profile earbuds").is_err());
+        assert_eq!(
+            Command::parse("tone Binaural"),
+            Ok(Command::ToneMode(ToneMode::Binaural))
+        );
+        assert_eq!(
+            Command::parse("tone carrier 150hz"),
+            Ok(Command::ToneCarrier(150.0))
+        );
+        assert_eq!(Command::parse("tone beat 4.5"), Ok(Command::ToneBeat(4.5)));
+        assert_eq!(Command::parse("tone level 20"), Ok(Command::ToneLevel(0.2)));
+        assert_eq!(Command::parse("roomtone 10"), Ok(Command::RoomTone(0.1)));
+        assert_eq!(Command::parse("roomtone off"), Ok(Command::RoomTone(0.0)));
+        assert_eq!(Command::parse("contour on"), Ok(Command::Contour(true)));
+        assert_eq!(
+            Command::parse("contour 50phon"),
+            Ok(Command::ContourLevel(50.0))
+        );
+        assert!(Command::parse("contour 90").is_err());
+        assert_eq!(Command::parse("spl 58"), Ok(Command::SplReading(58.0)));
+        assert_eq!(
+            Command::parse("spl offset 92.5dB"),
+            Ok(Command::SplOffset(Some(92.5)))
+        );
+        assert_eq!(
+            Command::parse("spl max 65"),
+            Ok(Command::SplMax(Some(65.0)))
+        );
+        assert_eq!(Command::parse("SPL max off"), Ok(Command::SplMax(None)));
+        assert!(Command::parse("spl off").is_err());
+        assert!(Command::parse("spl max 200").is_err());
+        assert_eq!(Command::parse("listener"), Ok(Command::Listener(None)));
+        assert_eq!(
+            Command::parse("listener B"),
+            Ok(Command::Listener(Some(Listener::B)))
+        );
+        assert!(Command::parse("listener c").is_err());
+        assert_eq!(Command::parse("  reset "), Ok(Command::ResetEq));
+        assert_eq!(
+            Command::parse("timer 45m"),
+            Ok(Command::Timer(Some(Duration::from_secs(45 * 60))))
+        );
+        assert_eq!(Command::parse("timer off"), Ok(Command::Timer(None)));
+        assert_eq!(
+            Command::parse("timer 25m Visual"),
+            Ok(Command::TimerWithAlarm(
+                Duration::from_secs(25 * 60),
+                TimerAlarm::Visual
+            ))
+        );
+        assert_eq!(
+            Command::parse("preset save tinnitus mask"),
+            Ok(Command::SavePreset("tinnitus mask".to_owned()))
+        );
+        assert_eq!(
+            Command::parse("Preset LOAD focus"),
+            Ok(Command::LoadPreset("focus".to_owned()))
+        );
+        assert_eq!(
+            Command::parse("scene attic rain"),
+            Ok(Command::LoadScene("attic rain".to_owned()))
+        );
+        assert_eq!(Command::parse("quit"), Ok(Command::Quit));
+    }
+
+    #[test]
+    fn malformed_commands_explain_themselves() {
+        for line in ["", "louder", "volume", "volume 20 30", "reset now", "mix"] {
+            assert_eq!(Command::parse(line), Err(USAGE.to_owned()), "{line}");
+        }
+        assert!(
+            Command::parse("band treble 20")
+                .unwrap_err()
+                .contains("sub-bass")
+        );
+        assert!(Command::parse("band air 120").is_err());
+        assert!(
+            Command::parse("tilt air 3")
+                .unwrap_err()
+                .contains("bass and mid")
+        );
+        assert!(Command::parse("tilt bass 7").is_err());
+        assert!(Command::parse("volume -1").is_err());
+        assert!(Command::parse("volume 3dB").is_err());
+        assert!(Command::parse("band air 13dB").is_err());
+        assert!(Command::parse("headroom maybe").is_err());
+        assert!(Command::parse("style thunder").is_err());
+        assert!(Command::parse("contour maybe").is_err());
+        assert!(Command::parse("tone loud").is_err());
+        assert!(
+            Command::parse("tone beat 80")
+                .unwrap_err()
+                .contains("0.5 to 40")
+        );
+        assert!(Command::parse("tone carrier 10").is_err());
+        assert!(Command::parse("tone pitch 200").is_err());
+        assert!(Command::parse("timer soon").is_err());
+        assert!(Command::parse("timer 25m loud").is_err());
+        assert!(Command::parse("mix rain=0").is_err());
+        assert!(Command::parse("preset focus").is_err());
+        assert!(Command::parse("preset rename focus").is_err());
+    }
+
+    #[test]
+    fn commands_apply_to_the_settings() {
+        let mut settings = AudioSettings::default();
+        let now = Instant::now();
+        let fade = Duration::from_secs(60);
+        let presets = scratch_presets("unused");
+
+        let message = Command::Band(7, 0.25).apply(&mut settings, fade, now, &presets);
+        assert_eq!(settings.frequency_bands[7], 0.25);
+        assert_eq!(message, "Air 25% = -6.0 dB");
+
+        Command::Volume(0.3).apply(&mut settings, fade, now, &presets);
+        Command::Style(SoundStyle::Brown).apply(&mut settings, fade, now, &presets);
+        Command::Contour(true).apply(&mut settings, fade, now, &presets);
+        let message = Command::ContourLevel(45.0).apply(&mut settings, fade, now, &presets);
+        assert_eq!(message, "listening contour on at 45 phon");
+        assert_eq!(settings.listening_phon, 45.0);
+        let message = Command::Width(0.0).apply(&mut settings, fade, now, &presets);
+        assert_eq!(message, "stereo width 0%");
+        assert_eq!(settings.stereo_width, 0.0);
+        Command::ToneCarrier(300.0).apply(&mut settings, fade, now, &presets);
+        Command::ToneBeat(10.0).apply(&mut settings, fade, now, &presets);
+        let message =
+            Command::ToneMode(ToneMode::Isochronic).apply(&mut settings, fade, now, &presets);
+        assert_eq!(message, "tone Isochronic 300 Hz, 10 Hz beat");
+        let message = Command::ToneLevel(0.05).apply(&mut settings, fade, now, &presets);
+        assert_eq!(message, "tone level 5%");
+        assert_eq!(settings.tone.level, 0.05);
+        let message = Command::RoomTone(0.2).apply(&mut settings, fade, now, &presets);
+        assert_eq!(message, "room tone 20%");
+        assert_eq!(settings.room_tone, 0.2);
+        let message = Command::SplMax(Some(60.0)).apply(&mut settings, fade, now, &presets);
+        assert!(message.starts_with("SPL cap 60 dB, held once"), "{message}");
+        Command::SplReading(58.0).apply(&mut settings, fade, now, &presets);
+        assert_eq!(settings.spl.offset_db, None);
+        let message = Command::SplOffset(Some(90.0)).apply(&mut settings, fade, now, &presets);
+        assert_eq!(message, "SPL calibration 90.0 dB at full scale");
+        assert_eq!(settings.spl.max_db, Some(60.0));
+        assert_eq!(settings.volume, 0.3);
+        assert_eq!(settings.mix(), SourceMix::solo(SoundStyle::Brown));
+        assert!(settings.listening_contour);
+
+        let message = Command::Timer(Some(Duration::from_secs(2700))).apply(
+            &mut settings,
+            fade,
+            now,
+            &presets,
+        );
+        assert_eq!(message, "sleep timer 45:00");
+        assert_eq!(
+            settings.sleep_timer.unwrap().remaining(now),
+            Duration::from_secs(2700)
+        );
+        let message = Command::TimerWithAlarm(Duration::from_secs(60), TimerAlarm::Visual).apply(
+            &mut settings,
+            fade,
+            now,
+            &presets,
+        );
+        assert_eq!(message, "visual timer 1:00");
+        assert_eq!(settings.sleep_timer.unwrap().alarm(), TimerAlarm::Visual);
+        settings.frequency_bands = [1.0; MAX_BANDS];
+        let message = Command::Headroom(true).apply(&mut settings, fade, now, &presets);
+        assert!(message.starts_with("headroom on, volume -1"), "{message}");
+        assert!(settings.headroom);
+        let message = Command::Headroom(false).apply(&mut settings, fade, now, &presets);
+        assert_eq!(message, "headroom off");
+        let volume = settings.volume;
+        settings.volume = 1.0;
+        let message = Command::Trim.apply(&mut settings, fade, now, &presets);
+        assert!(
+            message.ends_with("to keep the peaks under the limiter"),
+            "{message}"
+        );
+        assert!(settings.volume < 0.4, "{}", settings.volume);
+        let message = Command::Trim.apply(&mut settings, fade, now, &presets);
+        assert_eq!(message, "nothing to trim; the peaks stay under the limiter");
+        settings.volume = volume;
+        settings.frequency_bands = [0.5; MAX_BANDS];
+        let message = Command::Tilt(0, 3.0).apply(&mut settings, fade, now, &presets);
+        assert_eq!(message, "Bass tilt +3.0 dB");
+        assert_eq!(settings.band_tilts, [3.0, 0.0]);
+        let message = Command::Ceiling(Some(6_300.0)).apply(&mut settings, fade, now, &presets);
+        assert_eq!(message, "ceiling 6300 Hz");
+        assert_eq!(settings.ceiling_hz, Some(6_300.0));
+        let message = Command::Notch(Some(6_500.0)).apply(&mut settings, fade, now, &presets);
+        assert_eq!(message, "notch 6500 Hz");
+        let message = Command::NotchWidth(0.5).apply(&mut settings, fade, now, &presets);
+        assert_eq!(message, "notch width 0.5 octaves");
+        assert_eq!(settings.notch.hz, Some(6_500.0));
+        assert_eq!(settings.notch.width_octaves, 0.5);
+        let message = Command::HighPass(Some(31.5)).apply(&mut settings, fade, now, &presets);
+        assert_eq!(message, "high-pass 31.5 Hz");
+        assert_eq!(settings.high_pass.hz, Some(31.5));
+        let message =
+            Command::Profile(OutputProfile::Headphones).apply(&mut settings, fade, now, &presets);
+        assert_eq!(message, "headphones profile");
+        assert_eq!(settings.high_pass.hz, None);
+        assert!(settings.crossfeed);
+        let message = Command::Crossfeed(false).apply(&mut settings, fade, now, &presets);
+        assert_eq!(message, "crossfeed off");
+        assert!(!settings.crossfeed);
+        Command::Timer(None).apply(&mut settings, fade, now, &presets);
+        settings.band_overlay.toggle_solo(2);
+        Command::ResetEq.apply(&mut settings, fade, now, &presets);
+        assert_eq!(settings.sleep_timer, None);
+        assert_eq!(settings.frequency_bands, [0.5; MAX_BANDS]);
+        assert_eq!(settings.band_tilts, [0.0; TILT_BANDS.len()]);
+        assert_eq!(settings.band_overlay, BandOverlay::default());
+    }
+
+    #[test]
+    fn preset_commands_save_load_and_delete_files() {
+        let presets = scratch_presets("presets");
+        let now = Instant::now();
+        let fade = Duration::from_secs(60);
+        let mut settings = AudioSettings {
+            volume: 0.3,
+            ..AudioSettings::default()
+        };
+
+        let message =
+            Command::SavePreset("focus".to_owned()).apply(&mut settings, fade, now, &presets);
+        assert_eq!(message, "saved preset focus");
+        assert_eq!(complete("preset load f", &presets).0, "preset load focus ");
+
+        settings.volume = 0.9;
+        let message =
+            Command::LoadPreset("focus".to_owned()).apply(&mut settings, fade, now, &presets);
+        assert_eq!(message, "loaded preset focus");
+        assert_eq!(settings.volume, 0.3);
+
+        Command::DeletePreset("focus".to_owned()).apply(&mut settings, fade, now, &presets);
+        let message =
+            Command::LoadPreset("focus".to_owned()).apply(&mut settings, fade, now, &presets);
+        assert_eq!(message, "no preset named 'focus'");
+
+        // Scenes come from the folder beside the presets.
+        let scenes = scenes_beside(&presets);
+        fs::create_dir_all(&scenes).unwrap();
+        fs::write(
+            scenes.join("attic.toml"),
+            "[[layers]]\nsource = \"rain\"\nlevel = 0.5\n",
+        )
+        .unwrap();
+        assert_eq!(complete("scene a", &presets).0, "scene attic ");
+        let message =
+            Command::LoadScene("attic".to_owned()).apply(&mut settings, fade, now, &presets);
+        assert_eq!(message, "loaded scene attic");
+        assert_eq!(settings.mix().level(SoundStyle::Rain), 0.5);
+        assert_eq!(settings.volume, 0.3);
+
+        fs::remove_dir_all(presets.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn share_commands_show_and_load_a_code() {
+        let now = Instant::now();
+        let fade = Duration::from_secs(60);
+        let presets = scratch_presets("unused");
+        let mut shared = AudioSettings::default();
+        Command::Volume(0.4).apply(&mut shared, fade, now, &presets);
+        Command::Style(SoundStyle::Pink).apply(&mut shared, fade, now, &presets);
+        Command::Band(7, 0.25).apply(&mut shared, fade, now, &presets);
+        let message = Command::Share.apply(&mut shared, fade, now, &presets);
+        let code = message.strip_prefix("share code ").unwrap();
+
+        // Read out in groups, with spaces for the dashes.
+        let spoken = format!("share {}", code.replace('-', " ").to_lowercase());
+        let command = Command::parse(&spoken).unwrap();
+        assert!(matches!(command, Command::LoadShareCode(_)));
+        let mut settings = AudioSettings::default();
+        let message = command.apply(&mut settings, fade, now, &presets);
+        assert_eq!(message, "loaded share code: Pink Noise at 40% volume");
+        assert_eq!(settings.frequency_bands, shared.frequency_bands);
+        assert_eq!(Command::parse("share"), Ok(Command::Share));
+        assert!(Command::parse("share 04S0-00B4-01RH").is_err());
+    }
+
+    #[test]
+    fn train_opens_a_colors_or_bands_drill() {
+        assert_eq!(Command::parse("train"), Ok(Command::Train(Drill::Colors)));
+        assert_eq!(
+            Command::parse("train Colors"),
+            Ok(Command::Train(Drill::Colors))
+        );
+        assert_eq!(
+            Command::parse("train bands"),
+            Ok(Command::Train(Drill::Bands(DEFAULT_TRAINER_DB)))
+        );
+        assert_eq!(
+            Command::parse("train bands 3dB"),
+            Ok(Command::Train(Drill::Bands(3.0)))
+        );
+        for bad in [
+            "train bands 0.5",
+            "train bands 20",
+            "train colors 6",
+            "train eq",
+        ] {
+            assert!(Command::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn completion_finishes_unique_words_and_lists_ambiguous_ones() {
+        let presets = scratch_presets("unused");
+        assert_eq!(
+            complete("vol", &presets),
+            ("volume ".to_owned(), Vec::new())
+        );
+        assert_eq!(
+            complete("band a", &presets),
+            ("band air ".to_owned(), Vec::new())
+        );
+        assert_eq!(
+            complete("style r", &presets),
+            ("style rain ".to_owned(), Vec::new())
+        );
+
+        let (line, candidates) = complete("band b", &presets);
+        assert_eq!(line, "band b");
+        assert_eq!(candidates, ["bass", "brilliance"]);
+
+        let (line, candidates) = complete("band ", &presets);
+        assert_eq!(line, "band ");
+        assert_eq!(candidates.len(), bands().len());
+
+        assert_eq!(complete("band s", &presets).0, "band sub-bass ");
+        assert_eq!(complete("contour o", &presets).0, "contour o");
+        assert_eq!(complete("tone i", &presets).0, "tone isochronic ");
+        assert_eq!(complete("timer 25m v", &presets).0, "timer 25m visual ");
+        assert_eq!(complete("tilt m", &presets).0, "tilt mid ");
+        assert_eq!(complete("ceiling o", &presets).0, "ceiling off ");
+        assert_eq!(complete("notch w", &presets).0, "notch width ");
+        assert_eq!(
+            complete("profile s", &presets).1,
+            ["small-speaker", "studio"]
+        );
+        assert_eq!(
+            complete("highpass slope 2", &presets).0,
+            "highpass slope 24 "
+        );
+        assert_eq!(
+            complete("volume 2", &presets),
+            ("volume 2".to_owned(), Vec::new())
+        );
+        assert_eq!(complete("xyz", &presets).1, Vec::<String>::new());
+    }
+
+    #[test]
+    fn history_browses_recent_lines_and_drops_the_oldest() {
+        let mut history = CommandHistory::default();
+        assert_eq!(history.older(), None);
+        for line in ["volume 20", "volume 20", " ", "band air 20", "timer 45m"] {
+            history.push(line);
+        }
+        assert_eq!(history.entries.len(), 3);
+
+        assert_eq!(history.older(), Some("timer 45m"));
+        assert_eq!(history.older(), Some("band air 20"));
+        assert_eq!(history.older(), Some("volume 20"));
+        assert_eq!(history.older(), Some("volume 20"));
+        assert_eq!(history.newer(), Some("band air 20"));
+        assert_eq!(history.newer(), Some("timer 45m"));
+        assert_eq!(history.newer(), None);
+        assert_eq!(history.newer(), None);
+
+        for index in 0..HISTORY_LIMIT + 5 {
+            history.push(&format!("volume {index}"));
+        }
+        assert_eq!(history.entries.len(), HISTORY_LIMIT);
+        assert_eq!(history.entries.front().unwrap(), "volume 5");
+    }
+
+    #[test]
+    fn history_survives_a_save_and_load_round_trip() {
+        let mut path = std::env::temp_dir();
+        path.push(format!("whitenoise-history-test-{}", std::process::id()));
+        path.push("command_history");
+        assert_eq!(load_history_from(&path).unwrap(), CommandHistory::default());
+
+        let mut history = CommandHistory::default();
+        history.push("band air 20");
+        history.push("timer 45m");
+        save_history_to(&path, &history).unwrap();
+        assert_eq!(load_history_from(&path).unwrap(), history);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
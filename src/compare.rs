@@ -0,0 +1,221 @@
+//! `whitenoise compare A B`: how two presets differ, in numbers. Each is
+//! rendered offline from the same seed, so the noise underneath is the
+//! same and only the settings tell them apart, then split into the EQ's
+//! bands with the same crossover the engine uses and measured band by band.
+
+use anyhow::{Context, Result, ensure};
+
+use crate::audio::{Generator, StreamOptions, crossovers_hz};
+use crate::dsp::{BandSplitter, LoudnessMeter};
+use crate::render::for_file;
+use crate::settings::{AudioSettings, bands};
+
+/// Used when no `--seed` is given, so two runs of the same comparison
+/// print the same table.
+pub const COMPARE_SEED: u64 = 0x5eed;
+/// Discarded before measuring, as a render does, so the startup volume
+/// ramp does not count.
+const PRE_ROLL_SECONDS: f32 = 1.0;
+/// A band quieter than this prints as silent rather than as a huge number.
+const FLOOR_DB: f32 = -120.0;
+
+/// One preset's measured sound.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Levels {
+    /// RMS of each band of `bands()`, in dBFS.
+    pub bands_db: Vec<f32>,
+    /// Ungated K-weighted loudness of the whole, in LUFS.
+    pub loudness_lufs: f32,
+}
+
+/// Renders `seconds` of each preset and prints the band-by-band table.
+pub fn run(
+    a: (&str, AudioSettings),
+    b: (&str, AudioSettings),
+    options: StreamOptions,
+    sample_rate: u32,
+    seconds: f32,
+) -> Result<()> {
+    let frames = (seconds * sample_rate as f32) as usize;
+    ensure!(frames > 0, "the comparison is too short to measure");
+    println!(
+        "Comparing {} and {}: {seconds} s of each at {sample_rate} Hz from the same seed",
+        a.0, b.0
+    );
+    let first = measure(a.1, options, sample_rate, frames)?;
+    let second = measure(b.1, options, sample_rate, frames)?;
+    print!("{}", report(a.0, &first, b.0, &second));
+    Ok(())
+}
+
+/// Renders `frames` of `settings`, mono as a render is, and measures each
+/// band and the loudness of the whole.
+pub fn measure(
+    settings: AudioSettings,
+    options: StreamOptions,
+    sample_rate: u32,
+    frames: usize,
+) -> Result<Levels> {
+    let rate = sample_rate as f32;
+    let crossovers = crossovers_hz(bands(), rate)
+        .context("compare needs the [[bands]] layout in rising order")?;
+    let mut generator = Generator::new(rate, 1, for_file(settings), options)?;
+    for _ in 0..(rate * PRE_ROLL_SECONDS) as usize {
+        generator.next_sample();
+    }
+    let samples: Vec<f32> = (0..frames).map(|_| generator.next_sample()).collect();
+
+    let bands_db = (0..bands().len())
+        .map(|band| {
+            let gains: Vec<f32> = (0..bands().len())
+                .map(|index| if index == band { 1.0 } else { 0.0 })
+                .collect();
+            let mut splitter = BandSplitter::new(rate, &crossovers, &gains);
+            let total: f64 = samples
+                .iter()
+                .map(|sample| f64::from(splitter.process([*sample; 2])[0]).powi(2))
+                .sum();
+            to_db(total / frames as f64)
+        })
+        .collect();
+
+    let mut meter = LoudnessMeter::new(rate);
+    let mut total = 0.0_f64;
+    for sample in &samples {
+        meter.process(*sample);
+        total += f64::from(meter.mean_square());
+    }
+    let mean_square = (total / frames as f64).max(1e-12);
+    Ok(Levels {
+        bands_db,
+        loudness_lufs: -0.691 + 10.0 * mean_square.log10() as f32,
+    })
+}
+
+fn to_db(mean_square: f64) -> f32 {
+    ((10.0 * mean_square.max(1e-30).log10()) as f32).max(FLOOR_DB)
+}
+
+/// The table: a row per band with both levels and the second's difference
+/// from the first, then the loudness.
+pub fn report(a_name: &str, a: &Levels, b_name: &str, b: &Levels) -> String {
+    let level = |db: f32| {
+        if db <= FLOOR_DB {
+            "silent".to_owned()
+        } else {
+            format!("{db:.1} dB")
+        }
+    };
+    let difference = |a: f32, b: f32| {
+        if a <= FLOOR_DB && b <= FLOOR_DB {
+            "-".to_owned()
+        } else {
+            format!("{:+.1} dB", b - a)
+        }
+    };
+    let mut rows: Vec<[String; 4]> = vec![[
+        "Band".to_owned(),
+        a_name.to_owned(),
+        b_name.to_owned(),
+        "Difference".to_owned(),
+    ]];
+    for (band, (a_db, b_db)) in bands().iter().zip(a.bands_db.iter().zip(&b.bands_db)) {
+        rows.push([
+            band.name.to_string(),
+            level(*a_db),
+            level(*b_db),
+            difference(*a_db, *b_db),
+        ]);
+    }
+    rows.push([
+        "Loudness".to_owned(),
+        format!("{:.1} LUFS", a.loudness_lufs),
+        format!("{:.1} LUFS", b.loudness_lufs),
+        format!("{:+.1} dB", b.loudness_lufs - a.loudness_lufs),
+    ]);
+
+    let widths: Vec<usize> = (0..4)
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut table = String::new();
+    for row in &rows {
+        let line = format!(
+            "{:<w0$}  {:>w1$}  {:>w2$}  {:>w3$}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3],
+        );
+        table.push_str(line.trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{SoundStyle, SourceMix, db_to_slider};
+
+    fn brown() -> AudioSettings {
+        let mut settings = AudioSettings {
+            volume: 0.5,
+            ..AudioSettings::default()
+        };
+        settings.set_mix(SourceMix::solo(SoundStyle::Brown));
+        settings
+    }
+
+    #[test]
+    fn a_boosted_band_stands_out_from_the_same_sound_flat() {
+        let options = StreamOptions {
+            seed: Some(COMPARE_SEED),
+            ..StreamOptions::default()
+        };
+        let flat = measure(brown(), options, 16_000, 32_000).unwrap();
+        assert_eq!(flat.bands_db.len(), bands().len());
+        // The same seed and settings measure the same.
+        assert_eq!(measure(brown(), options, 16_000, 32_000).unwrap(), flat);
+
+        let mut boosted = brown();
+        boosted.frequency_bands[3] = db_to_slider(6.0);
+        let boosted = measure(boosted, options, 16_000, 32_000).unwrap();
+        for (band, (flat_db, boosted_db)) in flat.bands_db.iter().zip(&boosted.bands_db).enumerate()
+        {
+            let difference = boosted_db - flat_db;
+            if band == 3 {
+                assert!(difference > 3.0, "{difference}");
+            } else {
+                assert!(difference < 2.0, "band {band}: {difference}");
+            }
+        }
+        assert!(boosted.loudness_lufs > flat.loudness_lufs);
+
+        let table = report("flat", &flat, "mids", &boosted);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), bands().len() + 2);
+        assert!(lines[0].starts_with("Band") && lines[0].ends_with("Difference"));
+        assert!(lines[4].starts_with("Mid ") && lines[4].contains("+"));
+        assert!(lines.last().unwrap().starts_with("Loudness"));
+    }
+
+    #[test]
+    fn silent_bands_print_as_silent() {
+        let silent = Levels {
+            bands_db: vec![FLOOR_DB; bands().len()],
+            loudness_lufs: -70.0,
+        };
+        let table = report("a", &silent, "b", &silent);
+        assert!(table.lines().nth(1).unwrap().contains("silent"));
+        assert!(table.lines().nth(1).unwrap().ends_with('-'));
+    }
+}
@@ -0,0 +1,135 @@
+//! The listening contour: ISO 226:2003 equal-loudness compensation. The ear
+//! loses the extreme lows and highs faster than the mids as playback gets
+//! quieter, so a mix balanced at a loud reference level sounds thin when
+//! played softly. The contour adds back the difference between the
+//! equal-loudness contour at the listening level and the one at the
+//! reference, measured against 1 kHz, so the balance heard at the chosen
+//! level matches the balance heard at the reference.
+
+pub const MIN_LISTENING_PHON: f32 = 20.0;
+/// At the reference level the contour is flat.
+pub const MAX_LISTENING_PHON: f32 = REFERENCE_PHON;
+/// Close to the gentle curve the contour used before it followed the
+/// standard: about +4.5 dB in the sub bass and +1.5 dB of air.
+pub const DEFAULT_LISTENING_PHON: f32 = 70.0;
+// Roughly where music is mixed and mastered.
+const REFERENCE_PHON: f32 = 80.0;
+
+// ISO 226:2003 table 1: the one-third-octave frequencies, the exponent for
+// loudness perception, the magnitude of the linear transfer function
+// normalized at 1 kHz, and the threshold of hearing.
+const FREQUENCIES_HZ: [f32; 29] = [
+    20.0, 25.0, 31.5, 40.0, 50.0, 63.0, 80.0, 100.0, 125.0, 160.0, 200.0, 250.0, 315.0, 400.0,
+    500.0, 630.0, 800.0, 1_000.0, 1_250.0, 1_600.0, 2_000.0, 2_500.0, 3_150.0, 4_000.0, 5_000.0,
+    6_300.0, 8_000.0, 10_000.0, 12_500.0,
+];
+const EXPONENTS: [f32; 29] = [
+    0.532, 0.506, 0.480, 0.455, 0.432, 0.409, 0.387, 0.367, 0.349, 0.330, 0.315, 0.301, 0.288,
+    0.276, 0.267, 0.259, 0.253, 0.250, 0.246, 0.244, 0.243, 0.243, 0.243, 0.242, 0.242, 0.245,
+    0.254, 0.271, 0.301,
+];
+const TRANSFER_DB: [f32; 29] = [
+    -31.6, -27.2, -23.0, -19.1, -15.9, -13.0, -10.3, -8.1, -6.2, -4.5, -3.1, -2.0, -1.1, -0.4, 0.0,
+    0.3, 0.5, 0.0, -2.7, -4.1, -1.0, 1.7, 2.5, 1.2, -2.1, -7.1, -11.2, -10.7, -3.1,
+];
+const THRESHOLD_DB: [f32; 29] = [
+    78.5, 68.7, 59.5, 51.1, 44.0, 37.5, 31.5, 26.5, 22.1, 17.9, 14.4, 11.4, 8.6, 6.2, 4.4, 3.0,
+    2.2, 2.4, 3.5, 1.7, -1.3, -4.2, -6.0, -5.4, -1.5, 6.0, 12.6, 13.9, 12.3,
+];
+const ONE_KHZ: usize = 17;
+
+/// Sound pressure level in dB of a tone at table entry `index` that sounds
+/// as loud as `phon`, from the standard's equation (1).
+fn entry_spl(index: usize, phon: f32) -> f32 {
+    let exponent = EXPONENTS[index];
+    let transfer = TRANSFER_DB[index];
+    let loudness = 4.47e-3 * (10_f32.powf(0.025 * phon) - 1.15)
+        + (0.4 * 10_f32.powf((THRESHOLD_DB[index] + transfer) / 10.0 - 9.0)).powf(exponent);
+    10.0 / exponent * loudness.log10() - transfer + 94.0
+}
+
+/// The equal-loudness contour for `phon` at `frequency`, interpolated on a
+/// log-frequency axis between the table's frequencies and held flat beyond
+/// 20 Hz and 12.5 kHz, where the standard stops.
+pub fn equal_loudness_spl(frequency: f32, phon: f32) -> f32 {
+    let last = FREQUENCIES_HZ.len() - 1;
+    if frequency.is_nan() || frequency <= FREQUENCIES_HZ[0] {
+        return entry_spl(0, phon);
+    }
+    if frequency >= FREQUENCIES_HZ[last] {
+        return entry_spl(last, phon);
+    }
+    let upper = FREQUENCIES_HZ.partition_point(|&table_hz| table_hz < frequency);
+    let (low_hz, high_hz) = (FREQUENCIES_HZ[upper - 1], FREQUENCIES_HZ[upper]);
+    let position = (frequency / low_hz).ln() / (high_hz / low_hz).ln();
+    let (low, high) = (entry_spl(upper - 1, phon), entry_spl(upper, phon));
+    low + (high - low) * position
+}
+
+/// The contour's gain at `frequency` for listening at `phon`: 0 dB at
+/// 1 kHz, a boost where hearing at that level falls behind hearing at the
+/// reference, and flat everywhere when listening at the reference itself.
+pub fn contour_db(frequency: f32, phon: f32) -> f32 {
+    let phon = phon.clamp(MIN_LISTENING_PHON, MAX_LISTENING_PHON);
+    let relative = |phon| equal_loudness_spl(frequency, phon) - entry_spl(ONE_KHZ, phon);
+    relative(phon) - relative(REFERENCE_PHON)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contours_match_the_standard() {
+        // At 1 kHz a phon is a dB SPL by definition.
+        for phon in [20.0, 40.0, 60.0, 80.0] {
+            assert!((equal_loudness_spl(1_000.0, phon) - phon).abs() < 0.05);
+        }
+        // Points on the standard's 40 and 80 phon contours.
+        for (frequency, phon, spl) in [
+            (20.0, 40.0, 99.85),
+            (100.0, 40.0, 64.4),
+            (4_000.0, 40.0, 36.6),
+            (100.0, 80.0, 92.5),
+            (12_500.0, 80.0, 85.4),
+        ] {
+            let found = equal_loudness_spl(frequency, phon);
+            assert!(
+                (found - spl).abs() < 0.1,
+                "{frequency} Hz at {phon}: {found}"
+            );
+        }
+        // Held flat past the table.
+        assert_eq!(
+            equal_loudness_spl(10.0, 60.0),
+            equal_loudness_spl(20.0, 60.0)
+        );
+        assert_eq!(
+            equal_loudness_spl(16_000.0, 60.0),
+            equal_loudness_spl(12_500.0, 60.0)
+        );
+        let between = equal_loudness_spl(1_100.0, 60.0);
+        let (low, high) = (
+            equal_loudness_spl(1_000.0, 60.0),
+            equal_loudness_spl(1_250.0, 60.0),
+        );
+        assert!(between > low && between < high, "{between}");
+    }
+
+    #[test]
+    fn quieter_listening_lifts_the_lows_more() {
+        assert!(contour_db(1_000.0, 40.0).abs() < 1e-4);
+        assert!(contour_db(31.5, MAX_LISTENING_PHON).abs() < 1e-3);
+        let lows = [20.0, 40.0, 60.0].map(|phon| contour_db(31.5, phon));
+        assert!(
+            lows[0] > lows[1] && lows[1] > lows[2] && lows[2] > 5.0,
+            "{lows:?}"
+        );
+        // Below the range it clamps rather than running away.
+        assert_eq!(contour_db(31.5, 0.0), lows[0]);
+        // The default stays gentle: a few dB at the extremes, little between.
+        let default = |frequency| contour_db(frequency, DEFAULT_LISTENING_PHON);
+        assert!((3.0..6.0).contains(&default(35.0)), "{}", default(35.0));
+        assert!(default(2_800.0).abs() < 0.5, "{}", default(2_800.0));
+    }
+}
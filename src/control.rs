@@ -0,0 +1,493 @@
+//! Remote control for headless playback: a Unix domain socket that takes one
+//! JSON request per line, such as `{"cmd":"style","value":"rain"}`, and the
+//! `ctl` client that sends them. Requests other than `set_volume`, `status`,
+//! and `state` are the `:` prompt's commands, parsed by `command.rs`.
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+
+use crate::clock;
+use crate::command::Command;
+use crate::describe::describe;
+use crate::exit::Failure;
+use crate::settings::{
+    AudioSettings, SharedSettings, SoundStyle, bands, config_path, slider_to_db,
+};
+use crate::share::ShareCode;
+
+/// A client that sends nothing for this long is dropped so the next one can
+/// connect.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `$XDG_RUNTIME_DIR/whitenoise.sock`, or `control.sock` beside the
+/// settings file where there is no runtime directory.
+pub fn default_socket_path() -> PathBuf {
+    runtime_dir()
+        .map(|dir| dir.join("whitenoise.sock"))
+        .unwrap_or_else(settings_socket_path)
+}
+
+/// `$XDG_RUNTIME_DIR/whitenoise-NAME.sock` for a named instance, or
+/// `control.sock` in its own settings folder.
+pub fn instance_socket_path(name: &str) -> PathBuf {
+    runtime_dir()
+        .map(|dir| dir.join(format!("whitenoise-{name}.sock")))
+        .unwrap_or_else(settings_socket_path)
+}
+
+/// `control.sock` beside the settings file, which a settings file of its
+/// own (`--config`) keeps apart from every other player's.
+pub fn settings_socket_path() -> PathBuf {
+    config_path().with_file_name("control.sock")
+}
+
+/// `$XDG_RUNTIME_DIR` when it belongs to this user. `su` and `sudo -E`
+/// carry the variable over from whoever ran them, and their directory would
+/// either refuse this player's socket or put it where theirs goes.
+fn runtime_dir() -> Option<PathBuf> {
+    dirs::runtime_dir().filter(|dir| owned_by_this_user(dir))
+}
+
+#[cfg(target_os = "linux")]
+fn owned_by_this_user(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    // /proc/self belongs to the process's effective user.
+    match (std::fs::metadata("/proc/self"), std::fs::metadata(path)) {
+        (Ok(process), Ok(dir)) => process.uid() == dir.uid(),
+        (Err(_), _) => true,
+        (_, Err(_)) => false,
+    }
+}
+
+/// Only Linux has a runtime directory to check.
+#[cfg(not(target_os = "linux"))]
+fn owned_by_this_user(_path: &Path) -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub cmd: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reply {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The answer to `state`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<Value>,
+}
+
+impl Reply {
+    fn ok(message: String) -> Self {
+        Self {
+            ok: true,
+            message: Some(message),
+            error: None,
+            state: None,
+        }
+    }
+
+    fn error(error: String) -> Self {
+        Self {
+            ok: false,
+            message: None,
+            error: Some(error),
+            state: None,
+        }
+    }
+
+    fn state(state: Value) -> Self {
+        Self {
+            ok: true,
+            message: None,
+            error: None,
+            state: Some(state),
+        }
+    }
+}
+
+/// What a running server needs from the session: the shared settings, the
+/// flag `quit` clears, and what the `timer` and `preset` commands use.
+pub struct Session {
+    pub settings: Arc<SharedSettings>,
+    pub running: Arc<AtomicBool>,
+    pub fade_out: Duration,
+    pub presets: PathBuf,
+}
+
+/// Removes the socket file when playback ends.
+pub struct ControlSocket {
+    path: PathBuf,
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Listens on `path` on a background thread. A leftover socket from a run
+/// that crashed is replaced, but one that still answers is refused.
+#[cfg(unix)]
+pub fn serve(path: &Path, session: Session) -> Result<ControlSocket> {
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            bail!(
+                "another whitenoise is already listening on {}",
+                path.display()
+            );
+        }
+        std::fs::remove_file(path)
+            .with_context(|| format!("failed to remove the stale socket {}", path.display()))?;
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    // Other users on the machine can neither drive this player nor read its
+    // state, wherever --socket puts it. The socket is bound and restricted
+    // in a directory only this user may enter, so there is no moment when
+    // it is open to them, and then linked to its name, which fails as
+    // binding would if another player took the name meanwhile.
+    let staging = staging_dir(path);
+    let listener = private_dir(&staging)
+        .and_then(|()| {
+            let bound = staging.join("socket");
+            let listener = UnixListener::bind(&bound)?;
+            std::fs::set_permissions(&bound, std::fs::Permissions::from_mode(0o600))?;
+            std::fs::hard_link(&bound, path)?;
+            Ok(listener)
+        })
+        .with_context(|| format!("failed to listen on {}", path.display()));
+    let _ = std::fs::remove_dir_all(&staging);
+    let listener = listener?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // One client at a time; requests are tiny and a client that
+            // idles is dropped.
+            let _ = stream.set_read_timeout(Some(IDLE_TIMEOUT));
+            let Ok(reader) = stream.try_clone() else {
+                continue;
+            };
+            let mut writer = stream;
+            for line in BufReader::new(reader).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let reply = handle(&line, &session, clock::now());
+                let Ok(mut text) = serde_json::to_string(&reply) else {
+                    break;
+                };
+                text.push('\n');
+                if writer.write_all(text.as_bytes()).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    Ok(ControlSocket {
+        path: path.to_owned(),
+    })
+}
+
+/// Where `serve` binds the socket for `path` before it takes that name: a
+/// hidden directory beside it, named for this process.
+#[cfg(unix)]
+fn staging_dir(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}.{}", std::process::id()))
+}
+
+/// Creates `dir` for this user alone. One left by a crashed run with the
+/// same process id is replaced.
+#[cfg(unix)]
+fn private_dir(dir: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+
+    let mut builder = std::fs::DirBuilder::new();
+    builder.mode(0o700);
+    builder.create(dir).or_else(|_| {
+        std::fs::remove_dir_all(dir)?;
+        builder.create(dir)
+    })
+}
+
+#[cfg(not(unix))]
+pub fn serve(_path: &Path, _session: Session) -> Result<ControlSocket> {
+    bail!("remote control needs Unix domain sockets, which this platform lacks")
+}
+
+/// Sends one request to the server on `path` and waits for its reply.
+#[cfg(unix)]
+pub fn send(path: &Path, request: &Request) -> Result<Reply> {
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = UnixStream::connect(path)
+        .with_context(|| {
+            format!(
+                "nothing is listening on {}; start whitenoise with --non-interactive or top first",
+                path.display()
+            )
+        })
+        .context(Failure::NotRunning)?;
+    stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+    let mut reply = String::new();
+    BufReader::new(stream)
+        .read_line(&mut reply)
+        .context("no reply from the player")?;
+    serde_json::from_str(&reply).context("the player sent an unreadable reply")
+}
+
+#[cfg(not(unix))]
+pub fn send(_path: &Path, _request: &Request) -> Result<Reply> {
+    bail!("remote control needs Unix domain sockets, which this platform lacks")
+}
+
+/// Answers one request line.
+fn handle(line: &str, session: &Session, now: Instant) -> Reply {
+    let request: Request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(error) => return Reply::error(format!("not a JSON request: {error}")),
+    };
+    let value = match &request.value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Number(number)) => number.to_string(),
+        Some(_) => return Reply::error("value must be a string or a number".to_owned()),
+    };
+    let lock = || {
+        session
+            .settings
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    };
+
+    match request.cmd.to_lowercase().as_str() {
+        "status" => Reply::ok(describe(&lock(), now)),
+        "state" => Reply::state(state(*lock(), now)),
+        "set_volume" => match value.trim().parse::<f32>() {
+            Ok(volume) if (0.0..=1.0).contains(&volume) => {
+                lock().volume = volume;
+                Reply::ok(format!("volume {:.0}%", volume * 100.0))
+            }
+            _ => Reply::error("set_volume takes a number from 0 to 1".to_owned()),
+        },
+        cmd => match Command::parse(&format!("{cmd} {value}")) {
+            Ok(Command::Quit) => {
+                session.running.store(false, Ordering::Relaxed);
+                Reply::ok("quitting".to_owned())
+            }
+            Ok(command) => {
+                Reply::ok(command.apply(&mut lock(), session.fade_out, now, &session.presets))
+            }
+            Err(error) => Reply::error(error),
+        },
+    }
+}
+
+/// The sound for scripts: levels in percent, the mix by source, the EQ by
+/// band, and the timer in seconds.
+fn state(settings: AudioSettings, now: Instant) -> Value {
+    let mix = settings.mix();
+    let sources: Map<String, Value> = SoundStyle::ALL
+        .into_iter()
+        .filter(|style| mix.level(*style) > 0.0)
+        .filter_map(|style| match serde_json::to_value(style) {
+            Ok(Value::String(name)) => Some((name, json!((mix.level(style) * 100.0).round()))),
+            _ => None,
+        })
+        .collect();
+    let bands: Vec<Value> = bands()
+        .iter()
+        .zip(settings.frequency_bands)
+        .map(|(band, value)| {
+            json!({
+                "name": band.name,
+                "value": (value * 100.0).round(),
+                "db": (slider_to_db(value) * 10.0).round() / 10.0,
+            })
+        })
+        .collect();
+    json!({
+        "status": describe(&settings, now),
+        "volume": (settings.volume * 100.0).round(),
+        "paused": settings.paused,
+        "muted": settings.muted,
+        "mix": sources,
+        "bands": bands,
+        "timer_seconds": settings
+            .sleep_timer
+            .map(|timer| timer.remaining(now).as_secs()),
+        "share_code": ShareCode::of(&settings).to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{SoundStyle, SourceMix};
+
+    fn session() -> Session {
+        Session {
+            settings: Arc::new(SharedSettings::new(AudioSettings {
+                volume: 0.2,
+                ..AudioSettings::default()
+            })),
+            running: Arc::new(AtomicBool::new(true)),
+            fade_out: Duration::from_secs(60),
+            presets: std::env::temp_dir().join("whitenoise-control-test-presets"),
+        }
+    }
+
+    #[test]
+    fn requests_change_the_shared_settings() {
+        let session = session();
+        let now = Instant::now();
+        let reply = handle(r#"{"cmd":"set_volume","value":0.4}"#, &session, now);
+        assert_eq!(reply, Reply::ok("volume 40%".to_owned()));
+        let reply = handle(r#"{"cmd":"style","value":"rain"}"#, &session, now);
+        assert!(reply.ok, "{reply:?}");
+        let reply = handle(r#"{"cmd":"band","value":"air 20"}"#, &session, now);
+        assert!(reply.ok, "{reply:?}");
+        handle(r#"{"cmd":"width","value":30}"#, &session, now);
+
+        let settings = *session.settings.lock().unwrap();
+        assert_eq!(settings.volume, 0.4);
+        assert_eq!(settings.mix(), SourceMix::solo(SoundStyle::Rain));
+        assert_eq!(settings.frequency_bands[7], 0.2);
+        assert_eq!(settings.stereo_width, 0.3);
+
+        let reply = handle(r#"{"cmd":"timer","value":"45m"}"#, &session, now);
+        assert!(reply.ok, "{reply:?}");
+        let status = handle(r#"{"cmd":"status"}"#, &session, now);
+        assert_eq!(
+            status.message.as_deref(),
+            Some("Rain, much less air, 40% volume, sleep timer 45 min remaining")
+        );
+        session.settings.lock().unwrap().paused = true;
+        let status = handle(r#"{"cmd":"status"}"#, &session, now);
+        assert_eq!(
+            status.message.as_deref(),
+            Some("Rain, much less air, 40% volume, paused, sleep timer 45 min remaining")
+        );
+        let state = handle(r#"{"cmd":"state"}"#, &session, now).state.unwrap();
+        assert_eq!(state["volume"], 40.0);
+        assert_eq!(state["paused"], true);
+        assert_eq!(state["mix"], serde_json::json!({ "rain": 100.0 }));
+        assert_eq!(state["bands"][7]["value"], 20.0);
+        assert_eq!(state["timer_seconds"], 2700);
+        assert_eq!(state["status"], status.message.unwrap());
+        assert!(
+            state["share_code"]
+                .as_str()
+                .unwrap()
+                .parse::<ShareCode>()
+                .is_ok()
+        );
+
+        assert!(handle(r#"{"cmd":"quit"}"#, &session, now).ok);
+        assert!(!session.running.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn bad_requests_are_refused_without_touching_the_settings() {
+        let session = session();
+        let now = Instant::now();
+        for line in [
+            "volume 20",
+            r#"{"value":1}"#,
+            r#"{"cmd":"set_volume","value":40}"#,
+            r#"{"cmd":"set_volume","value":[0.4]}"#,
+            r#"{"cmd":"style","value":"thunder"}"#,
+            r#"{"cmd":"launch"}"#,
+        ] {
+            let reply = handle(line, &session, now);
+            assert!(!reply.ok && reply.error.is_some(), "{line}: {reply:?}");
+        }
+        assert_eq!(session.settings.lock().unwrap().volume, 0.2);
+        assert_eq!(
+            serde_json::to_string(&Reply::error("no".to_owned())).unwrap(),
+            r#"{"ok":false,"error":"no"}"#
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ctl_talks_to_a_running_server_over_the_socket() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!(
+            "whitenoise-control-test-{}.sock",
+            std::process::id()
+        ));
+        let session = session();
+        let settings = Arc::clone(&session.settings);
+        let socket = serve(&path, session).unwrap();
+        assert!(serve(&path, self::session()).is_err());
+        // Only this user may connect.
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(!staging_dir(&path).exists());
+
+        let reply = send(
+            &path,
+            &Request {
+                cmd: "volume".to_owned(),
+                value: Some(Value::from("35")),
+            },
+        )
+        .unwrap();
+        assert_eq!(reply, Reply::ok("volume 35%".to_owned()));
+        assert_eq!(settings.lock().unwrap().volume, 0.35);
+
+        drop(socket);
+        assert!(!path.exists());
+        assert!(
+            send(
+                &path,
+                &Request {
+                    cmd: "status".to_owned(),
+                    value: None
+                }
+            )
+            .is_err()
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn only_a_runtime_directory_of_this_users_own_is_used() {
+        let dir =
+            std::env::temp_dir().join(format!("whitenoise-runtime-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(owned_by_this_user(&dir));
+        std::fs::remove_dir(&dir).unwrap();
+        assert!(!owned_by_this_user(Path::new(
+            "/nonexistent/whitenoise-runtime"
+        )));
+    }
+}
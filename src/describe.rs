@@ -0,0 +1,247 @@
+//! "Describe my sound": the settings in a sentence a person can read back
+//! over the phone or paste into a bug report, such as "Brown-leaning white
+//! noise, strong sub bass, rolled-off highs above 6 kHz, 28% volume, sleep
+//! timer 42 min remaining". `whitenoise describe` prints it, and a running
+//! player's `status` answers with it.
+
+use std::time::{Duration, Instant};
+
+use crate::settings::{
+    AudioSettings, DEFAULT_STEREO_WIDTH, SoundStyle, TILT_BANDS, ToneMode, bands, format_hz,
+    slider_to_db,
+};
+
+/// Band and tilt changes smaller than this, in dB, go unmentioned.
+const NOTICEABLE_DB: f32 = 1.0;
+// A secondary source at this share of the loudest one or more is "nearly as
+// much"; below the second, "a touch".
+const NEARLY_AS_MUCH: f32 = 0.75;
+const SOME: f32 = 0.35;
+
+/// The whole sound in one line: the sources, the EQ as far as it departs
+/// from flat, the other shaping, the volume, and the timer.
+pub fn describe(settings: &AudioSettings, now: Instant) -> String {
+    let mut parts = vec![sources(settings)];
+    parts.extend(equalizer(settings));
+    if let Some(hz) = settings.ceiling_hz {
+        parts.push(format!("rolled-off highs above {}", frequency(hz)));
+    }
+    if let Some(hz) = settings.notch.hz {
+        parts.push(format!("a notch at {}", frequency(hz)));
+    }
+    if let Some(phon) = settings.contour_phon() {
+        parts.push(format!("listening contour at {phon:.0} phon"));
+    }
+    if settings.stereo_width <= 0.0 {
+        parts.push("mono".to_owned());
+    } else if settings.stereo_width >= 0.85 {
+        parts.push("wide stereo".to_owned());
+    } else if settings.stereo_width < DEFAULT_STEREO_WIDTH / 2.0 {
+        parts.push("narrow stereo".to_owned());
+    }
+    if settings.tone.mode != ToneMode::Off {
+        parts.push(format!(
+            "{} tone at {} Hz",
+            settings.tone.mode.label().to_lowercase(),
+            format_hz(settings.tone.carrier_hz)
+        ));
+    }
+    if settings.drift.enabled {
+        parts.push(format!("drifting {:.0}%", settings.drift.depth * 100.0));
+    }
+    parts.push(format!("{:.0}% volume", settings.volume * 100.0));
+    if settings.paused {
+        parts.push("paused".to_owned());
+    } else if settings.muted {
+        parts.push("muted".to_owned());
+    }
+    if let Some(timer) = settings.sleep_timer {
+        parts.push(format!(
+            "sleep timer {} remaining",
+            minutes(timer.remaining(now))
+        ));
+    }
+    parts.join(", ")
+}
+
+/// The loudest source, with the others by how much of it they make up: a
+/// second noise color leans it ("Brown-leaning white noise"), and anything
+/// else rides along ("Rain with some fan").
+fn sources(settings: &AudioSettings) -> String {
+    let mix = settings.mix();
+    if mix.total() <= 0.0 {
+        return "Silence".to_owned();
+    }
+    let main = mix.dominant();
+    let loudest = mix.level(main);
+    let others: Vec<SoundStyle> = SoundStyle::ALL
+        .into_iter()
+        .filter(|style| *style != main && mix.level(*style) > 0.0)
+        .collect();
+    let name = |style: SoundStyle| style.label().to_lowercase();
+    let main_name = name(main);
+    if let [other] = others[..] {
+        if is_color(main) && is_color(other) && mix.level(other) < loudest * NEARLY_AS_MUCH {
+            let color = name(other);
+            let color = color.split_whitespace().next().unwrap_or_default();
+            return capitalized(&format!("{color}-leaning {main_name}"));
+        }
+    }
+    let riders: Vec<String> = others
+        .into_iter()
+        .map(|style| {
+            let share = mix.level(style) / loudest;
+            let amount = if share >= NEARLY_AS_MUCH {
+                "nearly as much"
+            } else if share >= SOME {
+                "some"
+            } else {
+                "a touch of"
+            };
+            format!("{amount} {}", name(style))
+        })
+        .collect();
+    let mut line = capitalized(&main_name);
+    if !riders.is_empty() {
+        line.push_str(" with ");
+        line.push_str(&and_list(&riders));
+    }
+    line
+}
+
+/// Each band and tilt away from 0 dB, as more or less of its range.
+fn equalizer(settings: &AudioSettings) -> Vec<String> {
+    let mut parts: Vec<String> = bands()
+        .iter()
+        .zip(settings.frequency_bands)
+        .filter_map(|(band, value)| {
+            let db = slider_to_db(value);
+            let name = band.label().to_lowercase();
+            let amount = match db {
+                _ if db.abs() < NOTICEABLE_DB => return None,
+                6.0.. => "strong",
+                3.0.. => "more",
+                0.0.. => "a little more",
+                ..=-6.0 => "much less",
+                ..=-3.0 => "less",
+                _ => "a little less",
+            };
+            Some(format!("{amount} {name}"))
+        })
+        .collect();
+    for (index, tilt) in TILT_BANDS.iter().zip(settings.band_tilts) {
+        if tilt.abs() >= NOTICEABLE_DB {
+            let band = bands()
+                .get(*index)
+                .map_or_else(String::new, |band| band.label().to_lowercase());
+            let way = if tilt > 0.0 { "brighter" } else { "darker" };
+            parts.push(format!("{band} tilted {way}"));
+        }
+    }
+    parts
+}
+
+fn is_color(style: SoundStyle) -> bool {
+    matches!(
+        style,
+        SoundStyle::White
+            | SoundStyle::Pink
+            | SoundStyle::Brown
+            | SoundStyle::Blue
+            | SoundStyle::Violet
+    )
+}
+
+/// "6 kHz", "6.3 kHz", or "800 Hz".
+fn frequency(hz: f32) -> String {
+    if hz >= 1_000.0 {
+        format!("{} kHz", format_hz((hz / 100.0).round() / 10.0))
+    } else {
+        format!("{hz:.0} Hz")
+    }
+}
+
+/// Whole minutes, rounded up so a timer never reads as done early.
+fn minutes(remaining: Duration) -> String {
+    let minutes = remaining.as_secs().div_ceil(60);
+    if minutes >= 60 {
+        format!("{} h {} min", minutes / 60, minutes % 60)
+    } else {
+        format!("{minutes} min")
+    }
+}
+
+fn capitalized(text: &str) -> String {
+    let mut chars = text.chars();
+    chars.next().map_or_else(String::new, |first| {
+        first.to_uppercase().chain(chars).collect()
+    })
+}
+
+fn and_list(items: &[String]) -> String {
+    match items {
+        [] => String::new(),
+        [only] => only.clone(),
+        [rest @ .., last] => format!("{} and {last}", rest.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{SourceMix, db_to_slider};
+    use crate::timer::SleepTimer;
+
+    #[test]
+    fn a_sound_reads_as_a_sentence() {
+        let now = Instant::now();
+        let mut settings = AudioSettings {
+            volume: 0.28,
+            ceiling_hz: Some(6_000.0),
+            ..AudioSettings::default()
+        };
+        let mut mix = SourceMix::solo(SoundStyle::White);
+        mix.set_level(SoundStyle::Brown, 0.3);
+        settings.set_mix(mix);
+        settings.frequency_bands[0] = db_to_slider(9.0);
+        settings.sleep_timer = Some(SleepTimer::new(
+            Duration::from_secs(41 * 60 + 30),
+            Duration::from_secs(10),
+            now,
+        ));
+        assert_eq!(
+            describe(&settings, now),
+            "Brown-leaning white noise, strong sub bass, rolled-off highs above 6 kHz, 28% volume, sleep timer 42 min remaining"
+        );
+
+        let mut settings = AudioSettings {
+            volume: 0.5,
+            paused: true,
+            stereo_width: 0.0,
+            ..AudioSettings::default()
+        };
+        let mut mix = SourceMix::solo(SoundStyle::Rain);
+        mix.set_level(SoundStyle::Fan, 0.4);
+        mix.set_level(SoundStyle::Pink, 0.1);
+        settings.set_mix(mix);
+        settings.frequency_bands[3] = db_to_slider(-4.0);
+        settings.band_tilts[0] = 2.0;
+        settings.notch.hz = Some(3_150.0);
+        assert_eq!(
+            describe(&settings, now),
+            "Rain with a touch of pink noise and some fan, less mid, bass tilted brighter, a notch at 3.2 kHz, mono, 50% volume, paused"
+        );
+    }
+
+    #[test]
+    fn silence_and_long_timers_read_plainly() {
+        let mut settings = AudioSettings::default();
+        let mut mix = SourceMix::default();
+        mix.set_level(SoundStyle::White, 0.0);
+        settings.set_mix(mix);
+        assert!(describe(&settings, Instant::now()).starts_with("Silence, "));
+        assert_eq!(minutes(Duration::from_secs(90 * 60)), "1 h 30 min");
+        assert_eq!(minutes(Duration::from_secs(1)), "1 min");
+        assert_eq!(frequency(800.0), "800 Hz");
+    }
+}
@@ -1,6 +1,11 @@
 use anyhow::{Context, Result, bail};
 use cpal::traits::{DeviceTrait, HostTrait};
-use cpal::{Device, Host};
+use cpal::{
+    BufferSize, Device, DeviceDirection, Host, SampleFormat, StreamConfig, SupportedBufferSize,
+    SupportedStreamConfig, SupportedStreamConfigRange,
+};
+use serde::Serialize;
+use serde_json::json;
 
 pub fn select_host(requested: Option<&str>) -> Result<Host> {
     let Some(requested) = requested else {
@@ -26,17 +31,32 @@ pub fn select_host(requested: Option<&str>) -> Result<Host> {
     }
 }
 
-pub fn list_hosts() {
+pub fn list_hosts(json: bool) -> Result<()> {
     let default = cpal::default_host().id();
+    if json {
+        let hosts: Vec<HostListing> = cpal::available_hosts()
+            .into_iter()
+            .map(|host| HostListing {
+                name: host.to_string(),
+                default: host == default,
+            })
+            .collect();
+        println!("{}", serde_json::to_string(&json!({ "hosts": hosts }))?);
+        return Ok(());
+    }
     println!("Available audio hosts:");
     for host in cpal::available_hosts() {
         let suffix = if host == default { " (default)" } else { "" };
         println!("  {host}{suffix}");
     }
+    Ok(())
 }
 
-pub fn list_audio_devices(host: &Host) -> Result<()> {
+pub fn list_audio_devices(host: &Host, json: bool) -> Result<()> {
     let default = host.default_output_device();
+    if json {
+        return print_devices_json(host, default.as_ref());
+    }
     println!("Audio devices on {}:", host.id());
 
     // Do not probe every ALSA plugin for a supported configuration here. Some
@@ -57,6 +77,103 @@ pub fn list_audio_devices(host: &Host) -> Result<()> {
     Ok(())
 }
 
+#[derive(Debug, PartialEq, Serialize)]
+struct HostListing {
+    name: String,
+    default: bool,
+}
+
+/// One device as `--list-devices --json` prints it.
+#[derive(Debug, PartialEq, Serialize)]
+struct DeviceListing {
+    name: String,
+    /// `input`, `output`, `duplex`, or `unknown`.
+    direction: String,
+    /// Whether it is the host's default output.
+    default: bool,
+    /// The channel counts it plays or records, fewest first.
+    channels: Vec<u16>,
+    /// The lowest and highest sample rates of its formats; None when the
+    /// host lists no formats for it.
+    sample_rates: Option<RateRange>,
+    default_sample_rate: Option<u32>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct RateRange {
+    min: u32,
+    max: u32,
+}
+
+/// Unlike the plain listing, this probes each device's formats, which the
+/// sample rates and channels need; on a system whose ALSA bridge plugins
+/// have no server to reach, that can take a while.
+fn print_devices_json(host: &Host, default: Option<&Device>) -> Result<()> {
+    let mut devices = Vec::new();
+    for device in host.devices()? {
+        let description = device.description()?;
+        let direction = description.direction();
+        let (ranges, default_config): (Vec<_>, _) = match direction {
+            DeviceDirection::Input => (
+                device
+                    .supported_input_configs()
+                    .map(Iterator::collect)
+                    .unwrap_or_default(),
+                device.default_input_config().ok(),
+            ),
+            _ => (
+                device
+                    .supported_output_configs()
+                    .map(Iterator::collect)
+                    .unwrap_or_default(),
+                device.default_output_config().ok(),
+            ),
+        };
+        devices.push(listing(
+            description.name(),
+            direction,
+            default.is_some_and(|candidate| candidate == &device),
+            &ranges,
+            default_config.map(|config| config.sample_rate()),
+        ));
+    }
+    let listing = json!({ "host": host.id().to_string(), "devices": devices });
+    println!("{}", serde_json::to_string(&listing)?);
+    Ok(())
+}
+
+// Kept apart from CPAL devices so it is testable. DSD formats are left
+// out, as they are when choosing a format to play.
+fn listing(
+    name: &str,
+    direction: DeviceDirection,
+    default: bool,
+    ranges: &[SupportedStreamConfigRange],
+    default_sample_rate: Option<u32>,
+) -> DeviceListing {
+    let ranges: Vec<_> = ranges
+        .iter()
+        .filter(|range| !range.sample_format().is_dsd())
+        .collect();
+    let mut channels: Vec<u16> = ranges.iter().map(|range| range.channels()).collect();
+    channels.sort_unstable();
+    channels.dedup();
+    let sample_rates = ranges
+        .iter()
+        .map(|range| range.min_sample_rate())
+        .min()
+        .zip(ranges.iter().map(|range| range.max_sample_rate()).max())
+        .map(|(min, max)| RateRange { min, max });
+    DeviceListing {
+        name: name.to_owned(),
+        direction: format!("{direction:?}").to_lowercase(),
+        default,
+        channels,
+        sample_rates,
+        default_sample_rate,
+    }
+}
+
 pub fn select_output_device(host: &Host, requested: Option<&str>) -> Result<Device> {
     match requested {
         Some(name) => find_device_by_name(host, name),
@@ -66,14 +183,37 @@ pub fn select_output_device(host: &Host, requested: Option<&str>) -> Result<Devi
     }
 }
 
+/// The microphone for `whitenoise calibrate`.
+pub fn select_input_device(host: &Host, requested: Option<&str>) -> Result<Device> {
+    match requested {
+        Some(name) => find_device_by_name(host, name),
+        None => host
+            .default_input_device()
+            .context("no default input device is available"),
+    }
+}
+
+/// The names of the host's output devices, for the interface's picker.
+pub fn output_device_names(host: &Host) -> Result<Vec<String>> {
+    Ok(host
+        .output_devices()?
+        .map(|device| display_name(&device))
+        .collect())
+}
+
+/// The name `--list-devices` prints and `[devices]` tables are keyed by.
+pub fn display_name(device: &Device) -> String {
+    device
+        .description()
+        .map(|description| description.name().to_owned())
+        .unwrap_or_else(|_| device.to_string())
+}
+
 fn find_device_by_name(host: &Host, requested: &str) -> Result<Device> {
     let devices: Vec<(Device, String)> = host
         .devices()?
         .map(|device| {
-            let name = device
-                .description()
-                .map(|description| description.name().to_owned())
-                .unwrap_or_else(|_| device.to_string());
+            let name = display_name(&device);
             (device, name)
         })
         .collect();
@@ -111,7 +251,7 @@ fn match_device_name(names: &[String], requested: &str) -> Result<usize> {
                 .map(String::as_str)
                 .collect::<Vec<_>>()
                 .join(", ");
-            bail!("output device was not found (available: {names})")
+            bail!("device was not found (available: {names})")
         }
         matches => {
             let names = matches
@@ -124,6 +264,86 @@ fn match_device_name(names: &[String], requested: &str) -> Result<usize> {
     }
 }
 
+/// Opens the device at its default format, or, when a sample rate or buffer
+/// size was asked for, at the supported format closest to the request. A
+/// request the device cannot meet exactly is moved to the nearest value it
+/// can, with a note on stderr.
+pub fn select_output_config(
+    device: &Device,
+    sample_rate: Option<u32>,
+    buffer_size: Option<u32>,
+) -> Result<(StreamConfig, SampleFormat)> {
+    let default = device
+        .default_output_config()
+        .context("failed to query the default output format")?;
+    if sample_rate.is_none() && buffer_size.is_none() {
+        return Ok((default.config(), default.sample_format()));
+    }
+    let ranges: Vec<_> = device
+        .supported_output_configs()
+        .context("failed to query the supported output formats")?
+        .collect();
+    let (config, sample_format) = closest_config(&default, &ranges, sample_rate, buffer_size);
+    if let Some(requested) = sample_rate.filter(|&rate| rate != config.sample_rate) {
+        eprintln!(
+            "note: the device cannot play {requested} Hz; using {} Hz",
+            config.sample_rate
+        );
+    }
+    if let (Some(requested), BufferSize::Fixed(frames)) = (buffer_size, config.buffer_size) {
+        if requested != frames {
+            eprintln!(
+                "note: the device cannot use {requested}-frame buffers; using {frames} frames"
+            );
+        }
+    }
+    Ok((config, sample_format))
+}
+
+// The format-choosing contract, kept separate from CPAL devices so it is
+// testable: the nearest reachable sample rate wins, then the default's
+// channel count, then its sample format. A buffer size is clamped into the
+// chosen range's limits, and passed through when the backend reports none.
+fn closest_config(
+    default: &SupportedStreamConfig,
+    ranges: &[SupportedStreamConfigRange],
+    sample_rate: Option<u32>,
+    buffer_size: Option<u32>,
+) -> (StreamConfig, SampleFormat) {
+    let wanted_rate = sample_rate.unwrap_or(default.sample_rate());
+    let chosen = ranges
+        .iter()
+        .filter(|range| !range.sample_format().is_dsd())
+        .min_by_key(|range| {
+            let rate = wanted_rate.clamp(range.min_sample_rate(), range.max_sample_rate());
+            (
+                rate.abs_diff(wanted_rate),
+                range.channels() != default.channels(),
+                range.sample_format() != default.sample_format(),
+            )
+        })
+        .map(|range| {
+            let rate = wanted_rate.clamp(range.min_sample_rate(), range.max_sample_rate());
+            SupportedStreamConfig::new(
+                range.channels(),
+                rate,
+                *range.buffer_size(),
+                range.sample_format(),
+            )
+        })
+        .unwrap_or(*default);
+
+    let mut config = chosen.config();
+    config.buffer_size = match (buffer_size, chosen.buffer_size()) {
+        (None, _) => BufferSize::Default,
+        (Some(frames), SupportedBufferSize::Range { min, max }) => {
+            BufferSize::Fixed(frames.clamp(*min, *max))
+        }
+        (Some(frames), SupportedBufferSize::Unknown) => BufferSize::Fixed(frames),
+    };
+    (config, chosen.sample_format())
+}
+
 fn host_names() -> Vec<String> {
     cpal::available_hosts()
         .into_iter()
@@ -182,6 +402,43 @@ mod tests {
         assert!(error.contains("HDMI Output, USB Headphones"));
     }
 
+    #[test]
+    fn requested_formats_resolve_to_the_closest_supported_one() {
+        let buffers = SupportedBufferSize::Range { min: 64, max: 4096 };
+        let default = SupportedStreamConfig::new(2, 44_100, buffers, SampleFormat::F32);
+        let ranges = [
+            SupportedStreamConfigRange::new(2, 44_100, 44_100, buffers, SampleFormat::F32),
+            SupportedStreamConfigRange::new(6, 48_000, 48_000, buffers, SampleFormat::F32),
+            SupportedStreamConfigRange::new(2, 48_000, 48_000, buffers, SampleFormat::I16),
+            SupportedStreamConfigRange::new(2, 8_000, 192_000, buffers, SampleFormat::DsdU8),
+        ];
+
+        // 48 kHz exists only in other layouts: stereo beats the default's
+        // sample format, and DSD is never picked.
+        let (config, format) = closest_config(&default, &ranges, Some(48_000), None);
+        assert_eq!((config.channels, config.sample_rate), (2, 48_000));
+        assert_eq!(format, SampleFormat::I16);
+        assert_eq!(config.buffer_size, BufferSize::Default);
+
+        // An unsupported rate moves to the nearest one, and a buffer size
+        // alone keeps the default rate but is clamped into range.
+        let (config, format) = closest_config(&default, &ranges, Some(96_000), None);
+        assert_eq!(config.sample_rate, 48_000);
+        assert_eq!(format, SampleFormat::I16);
+        let (config, format) = closest_config(&default, &ranges, None, Some(16));
+        assert_eq!((config.sample_rate, format), (44_100, SampleFormat::F32));
+        assert_eq!(config.buffer_size, BufferSize::Fixed(64));
+
+        // Backends that report no buffer limits get the request as is, and
+        // a device that lists nothing falls back to its default format.
+        let (config, _) = closest_config(&default, &[], Some(48_000), Some(128));
+        assert_eq!(config.sample_rate, 44_100);
+        let unknown =
+            SupportedStreamConfig::new(2, 48_000, SupportedBufferSize::Unknown, SampleFormat::F32);
+        let (config, _) = closest_config(&unknown, &[], None, Some(100_000));
+        assert_eq!(config.buffer_size, BufferSize::Fixed(100_000));
+    }
+
     #[test]
     fn duplicate_names_resolve_to_the_first_device() {
         // ALSA can expose identical descriptions; matching must stay
@@ -190,4 +447,38 @@ mod tests {
         let index = match_device_name(&devices, "duplicate").unwrap();
         assert_eq!(index, 0);
     }
+
+    #[test]
+    fn json_listing_gives_channels_and_the_span_of_sample_rates() {
+        let buffers = SupportedBufferSize::Unknown;
+        let ranges = [
+            SupportedStreamConfigRange::new(2, 44_100, 48_000, buffers, SampleFormat::F32),
+            SupportedStreamConfigRange::new(6, 48_000, 96_000, buffers, SampleFormat::I16),
+            SupportedStreamConfigRange::new(2, 44_100, 48_000, buffers, SampleFormat::I16),
+            SupportedStreamConfigRange::new(8, 8_000, 384_000, buffers, SampleFormat::DsdU8),
+        ];
+        let dac = listing(
+            "USB DAC",
+            DeviceDirection::Output,
+            true,
+            &ranges,
+            Some(48_000),
+        );
+        assert_eq!(
+            serde_json::to_value(&dac).unwrap(),
+            json!({
+                "name": "USB DAC",
+                "direction": "output",
+                "default": true,
+                "channels": [2, 6],
+                "sample_rates": { "min": 44_100, "max": 96_000 },
+                "default_sample_rate": 48_000,
+            })
+        );
+
+        let silent = listing("Monitor", DeviceDirection::Input, false, &[], None);
+        assert_eq!(silent.channels, Vec::<u16>::new());
+        assert_eq!(silent.sample_rates, None);
+        assert_eq!(silent.direction, "input");
+    }
 }
@@ -0,0 +1,2162 @@
+//! Device-free DSP building blocks. Every stage is an explicit state struct
+//! with a per-sample process function and no CPAL or settings types, so each
+//! one can be characterized and stress-tested in isolation. Filters take
+//! several channels at once in lanes, and the crossover a block at a time.
+
+use std::collections::VecDeque;
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+
+// RMS of the uniform [-1, 1) white input that drives the colored sources.
+pub const UNIFORM_INPUT_RMS: f64 = 0.577_350_269_189_625_8;
+const PINK_LADDER_START_HZ: f64 = 8.0;
+const PINK_LADDER_RATIO: f64 = 4.0;
+const BROWN_LEAK_HZ: f64 = 8.0;
+const EQ_SMOOTHING_SECONDS: f32 = 0.03;
+const EQ_GAIN_SNAP_DB: f32 = 0.01;
+// A gliding corner frequency snaps once it is within 0.1% of its target.
+const FREQUENCY_SNAP_RATIO: f32 = 0.001;
+// Section Qs of a fourth-order Butterworth low- or high-pass.
+const BUTTERWORTH_Q: [f32; 2] = [0.541_196_1, 1.306_563];
+// A lifted high-pass glides down to here, below anything a speaker
+// reproduces, before it is bypassed.
+const HIGH_PASS_OPEN_HZ: f32 = 10.0;
+// Crossfeed shares the other channel below this corner, where a head
+// barely shadows sound from a speaker on the far side.
+const CROSSFEED_CORNER_HZ: f32 = 700.0;
+// Share of the other channel's lows each ear gets at full crossfeed.
+const CROSSFEED_FEED: f32 = 0.5;
+// Share of each isochronic period spent rising, and again falling, so the
+// pulses have no clicks even at 40 Hz.
+const PULSE_EDGE: f64 = 0.1;
+// K-weighting from ITU-R BS.1770: a +4 dB shelf for the head's effect on
+// the highs, and a high-pass for the ear's indifference to deep bass.
+const K_SHELF_HZ: f32 = 1_500.0;
+const K_SHELF_DB: f32 = 4.0;
+const K_HIGH_PASS_HZ: f32 = 38.0;
+// A notch switched on or off fades over this long rather than cutting in.
+const NOTCH_FADE_SECONDS: f32 = 0.2;
+// Where a notch built without a center waits; its output goes unused.
+const NOTCH_IDLE_HZ: f32 = 1_000.0;
+// A crossover band's gain glides this long, so a mute fades rather than
+// clicks.
+const BAND_GAIN_SECONDS: f32 = 0.05;
+// Frames a crossover split takes in one loop: enough to keep its state in
+// registers for a while, few enough to sit on the stack.
+const CROSSOVER_RUN: usize = 64;
+// Long enough that noise reads steady, short enough to follow the rain
+// loop and slider moves within a breath.
+const LOUDNESS_SECONDS: f32 = 1.0;
+// The peak limiter sees this far ahead, and its gain comes down over the
+// same time, so it reaches a peak's gain just as the peak goes out.
+const LIMITER_LOOKAHEAD_SECONDS: f32 = 0.002;
+// The true-peak detector interpolates the points between two samples from
+// this many on either side, reading at four times the sample rate as
+// BS.1770 does.
+const TRUE_PEAK_HALF_TAPS: usize = 4;
+const TRUE_PEAK_TAPS: usize = 2 * TRUE_PEAK_HALF_TAPS;
+const TRUE_PEAK_PHASES: usize = 3;
+// A recovering limiter gain this close to where it is heading goes the rest
+// of the way, so it comes back to exactly 1 rather than ever nearer.
+const LIMITER_SETTLED: f64 = 1e-4;
+// The level cap reads the RMS over about a VU meter's time, turns down
+// within a fraction of a second, and comes back up slowly enough not to
+// pump with the rain's swells.
+const LEVEL_CAP_WINDOW_SECONDS: f32 = 0.3;
+const LEVEL_CAP_ATTACK_SECONDS: f32 = 0.1;
+const LEVEL_CAP_RELEASE_SECONDS: f32 = 2.0;
+
+/// Which response a `Biquad` rebuilds as its gain moves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shape {
+    Peaking,
+    /// Full gain below the corner, unity above; the slope ignores Q.
+    LowShelf,
+    /// Unity below the corner, full gain above; the slope ignores Q.
+    HighShelf,
+    /// 12 dB/octave above the corner; the gain is ignored.
+    LowPass,
+    /// 12 dB/octave below the corner; the gain is ignored.
+    HighPass,
+    /// Silent at the center, -3 dB a bandwidth set by Q apart; the gain is
+    /// ignored.
+    Notch,
+    /// Flat everywhere, with the phase turning through the corner; the gain
+    /// is ignored.
+    AllPass,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Coefficients {
+    pub b0: f32,
+    pub b1: f32,
+    pub b2: f32,
+    pub a1: f32,
+    pub a2: f32,
+}
+
+impl Coefficients {
+    pub const IDENTITY: Self = Self {
+        b0: 1.0,
+        b1: 0.0,
+        b2: 0.0,
+        a1: 0.0,
+        a2: 0.0,
+    };
+
+    pub fn peaking(sample_rate: f32, frequency: f32, q: f32, gain_db: f32) -> Self {
+        if gain_db.abs() < f32::EPSILON || frequency >= sample_rate * 0.48 {
+            return Self::IDENTITY;
+        }
+
+        let omega = 2.0 * PI * frequency / sample_rate;
+        let (sin_omega, cos_omega) = omega.sin_cos();
+        let alpha = sin_omega / (2.0 * q.max(0.1));
+        let amplitude = 10.0_f32.powf(gain_db / 40.0);
+
+        let b0 = 1.0 + alpha * amplitude;
+        let b1 = -2.0 * cos_omega;
+        let b2 = 1.0 - alpha * amplitude;
+        let a0 = 1.0 + alpha / amplitude;
+        let a1 = -2.0 * cos_omega;
+        let a2 = 1.0 - alpha / amplitude;
+
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    /// A shelf from the audio EQ cookbook at its steepest slope without
+    /// overshoot (S = 1). `frequency` is where the response is half the gain
+    /// in dB.
+    pub fn shelf(shape: Shape, sample_rate: f32, frequency: f32, gain_db: f32) -> Self {
+        if gain_db.abs() < f32::EPSILON || frequency >= sample_rate * 0.48 {
+            return Self::IDENTITY;
+        }
+
+        let omega = 2.0 * PI * frequency / sample_rate;
+        let (sin_omega, cos_omega) = omega.sin_cos();
+        let amplitude = 10.0_f32.powf(gain_db / 40.0);
+        let alpha = sin_omega / 2.0 * std::f32::consts::SQRT_2;
+        let lift = 2.0 * amplitude.sqrt() * alpha;
+        let (plus, minus) = (amplitude + 1.0, amplitude - 1.0);
+
+        let (b0, b1, b2, a0, a1, a2) = if shape == Shape::LowShelf {
+            (
+                amplitude * (plus - minus * cos_omega + lift),
+                2.0 * amplitude * (minus - plus * cos_omega),
+                amplitude * (plus - minus * cos_omega - lift),
+                plus + minus * cos_omega + lift,
+                -2.0 * (minus + plus * cos_omega),
+                plus + minus * cos_omega - lift,
+            )
+        } else {
+            (
+                amplitude * (plus + minus * cos_omega + lift),
+                -2.0 * amplitude * (minus + plus * cos_omega),
+                amplitude * (plus + minus * cos_omega - lift),
+                plus - minus * cos_omega + lift,
+                2.0 * (minus - plus * cos_omega),
+                plus - minus * cos_omega - lift,
+            )
+        };
+
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    pub fn low_pass(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        if frequency >= sample_rate * 0.48 {
+            return Self::IDENTITY;
+        }
+
+        let omega = 2.0 * PI * frequency / sample_rate;
+        let (sin_omega, cos_omega) = omega.sin_cos();
+        let alpha = sin_omega / (2.0 * q.max(0.1));
+        let a0 = 1.0 + alpha;
+        let b0 = (1.0 - cos_omega) / 2.0 / a0;
+
+        Self {
+            b0,
+            b1: 2.0 * b0,
+            b2: b0,
+            a1: -2.0 * cos_omega / a0,
+            a2: (1.0 - alpha) / a0,
+        }
+    }
+
+    pub fn high_pass(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let omega = 2.0 * PI * frequency / sample_rate;
+        let (sin_omega, cos_omega) = omega.sin_cos();
+        let alpha = sin_omega / (2.0 * q.max(0.1));
+        let a0 = 1.0 + alpha;
+        let b0 = (1.0 + cos_omega) / 2.0 / a0;
+
+        Self {
+            b0,
+            b1: -2.0 * b0,
+            b2: b0,
+            a1: -2.0 * cos_omega / a0,
+            a2: (1.0 - alpha) / a0,
+        }
+    }
+
+    /// The cookbook band-reject: a zero pair on the unit circle at the
+    /// center, so that frequency is removed entirely. Its bandwidth form is
+    /// used, so the -3 dB points stay where `q` puts them in octaves even
+    /// high up, where the bilinear transform would squeeze them together.
+    pub fn notch(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        if frequency >= sample_rate * 0.48 {
+            return Self::IDENTITY;
+        }
+
+        let omega = 2.0 * PI * frequency / sample_rate;
+        let (sin_omega, cos_omega) = omega.sin_cos();
+        let alpha = sin_omega * ((0.5 / q.max(0.1)).asinh() * omega / sin_omega).sinh();
+        let a0 = 1.0 + alpha;
+
+        Self {
+            b0: 1.0 / a0,
+            b1: -2.0 * cos_omega / a0,
+            b2: 1.0 / a0,
+            a1: -2.0 * cos_omega / a0,
+            a2: (1.0 - alpha) / a0,
+        }
+    }
+
+    pub fn all_pass(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let omega = 2.0 * PI * frequency / sample_rate;
+        let (sin_omega, cos_omega) = omega.sin_cos();
+        let alpha = sin_omega / (2.0 * q.max(0.1));
+        let a0 = 1.0 + alpha;
+
+        Self {
+            b0: (1.0 - alpha) / a0,
+            b1: -2.0 * cos_omega / a0,
+            b2: 1.0,
+            a1: -2.0 * cos_omega / a0,
+            a2: (1.0 - alpha) / a0,
+        }
+    }
+
+    fn for_shape(shape: Shape, sample_rate: f32, frequency: f32, q: f32, gain_db: f32) -> Self {
+        match shape {
+            Shape::Peaking => Self::peaking(sample_rate, frequency, q, gain_db),
+            Shape::LowShelf | Shape::HighShelf => {
+                Self::shelf(shape, sample_rate, frequency, gain_db)
+            }
+            Shape::LowPass => Self::low_pass(sample_rate, frequency, q),
+            Shape::HighPass => Self::high_pass(sample_rate, frequency, q),
+            Shape::Notch => Self::notch(sample_rate, frequency, q),
+            Shape::AllPass => Self::all_pass(sample_rate, frequency, q),
+        }
+    }
+
+    /// Magnitude response in dB at `frequency`, evaluated in f64 so tests
+    /// can characterize the realized f32 coefficients precisely.
+    pub fn magnitude_db(&self, sample_rate: f32, frequency: f32) -> f64 {
+        let omega = 2.0 * std::f64::consts::PI * f64::from(frequency) / f64::from(sample_rate);
+        let (sin1, cos1) = omega.sin_cos();
+        let (sin2, cos2) = (2.0 * omega).sin_cos();
+        let [b0, b1, b2, a1, a2] = [self.b0, self.b1, self.b2, self.a1, self.a2].map(f64::from);
+
+        let num_re = b0 + b1 * cos1 + b2 * cos2;
+        let num_im = -(b1 * sin1 + b2 * sin2);
+        let den_re = 1.0 + a1 * cos1 + a2 * cos2;
+        let den_im = -(a1 * sin1 + a2 * sin2);
+        10.0 * ((num_re * num_re + num_im * num_im) / (den_re * den_re + den_im * den_im)).log10()
+    }
+}
+
+/// The feedback state and coefficients of one section run over `N`
+/// independent signals side by side, one lane each. A lone section is a
+/// single lane, so a signal comes out the same whichever width carries it.
+/// Written lane by lane the arithmetic vectorizes, and sections in a chain
+/// overlap instead of each waiting on the last.
+#[derive(Debug, Clone, Copy)]
+struct Lanes<const N: usize> {
+    b0: [f32; N],
+    b1: [f32; N],
+    b2: [f32; N],
+    a1: [f32; N],
+    a2: [f32; N],
+    x1: [f32; N],
+    x2: [f32; N],
+    y1: [f32; N],
+    y2: [f32; N],
+    resets: u32,
+}
+
+impl<const N: usize> Lanes<N> {
+    fn new(coefficients: [Coefficients; N]) -> Self {
+        Self {
+            b0: coefficients.map(|c| c.b0),
+            b1: coefficients.map(|c| c.b1),
+            b2: coefficients.map(|c| c.b2),
+            a1: coefficients.map(|c| c.a1),
+            a2: coefficients.map(|c| c.a2),
+            x1: [0.0; N],
+            x2: [0.0; N],
+            y1: [0.0; N],
+            y2: [0.0; N],
+            resets: 0,
+        }
+    }
+
+    /// Gives every lane `coefficients`, keeping the state.
+    fn set(&mut self, coefficients: Coefficients) {
+        *self = Self {
+            x1: self.x1,
+            x2: self.x2,
+            y1: self.y1,
+            y2: self.y2,
+            resets: self.resets,
+            ..Self::new([coefficients; N])
+        };
+    }
+
+    #[inline]
+    fn process(&mut self, input: [f32; N]) -> [f32; N] {
+        let output = self.filter(input);
+        if output.iter().all(|sample| sample.is_finite()) {
+            output
+        } else {
+            self.flush(output)
+        }
+    }
+
+    /// `process` without the check for non-finite output, for a caller
+    /// that checks a whole run of samples at the end.
+    #[inline]
+    fn filter(&mut self, input: [f32; N]) -> [f32; N] {
+        // The input's term goes in last, so a chain of sections waits one
+        // multiply and one add on each rather than a whole sum.
+        let output: [f32; N] = std::array::from_fn(|lane| {
+            self.b1[lane] * self.x1[lane] + self.b2[lane] * self.x2[lane]
+                - self.a2[lane] * self.y2[lane]
+                - self.a1[lane] * self.y1[lane]
+                + self.b0[lane] * input[lane]
+        });
+
+        self.x2 = self.x1;
+        self.x1 = input;
+        self.y2 = self.y1;
+        self.y1 = output;
+        output
+    }
+
+    // A non-finite value in the feedback state would poison the lane
+    // forever; flush it so the lane recovers on the next sample.
+    #[cold]
+    fn flush(&mut self, output: [f32; N]) -> [f32; N] {
+        std::array::from_fn(|lane| {
+            if output[lane].is_finite() {
+                return output[lane];
+            }
+            self.x1[lane] = 0.0;
+            self.x2[lane] = 0.0;
+            self.y1[lane] = 0.0;
+            self.y2[lane] = 0.0;
+            self.resets = self.resets.wrapping_add(1);
+            0.0
+        })
+    }
+}
+
+/// A gliding biquad. `Biquad` filters one signal; `Biquad<2>` filters
+/// both channels with the coefficients worked out once for the two.
+#[derive(Debug)]
+pub struct Biquad<const N: usize = 1> {
+    shape: Shape,
+    sample_rate: f32,
+    frequency: f32,
+    target_frequency: f32,
+    q: f32,
+    current_gain_db: f32,
+    target_gain_db: f32,
+    smoothing: f32,
+    coefficients: Coefficients,
+    state: Lanes<N>,
+}
+
+impl<const N: usize> Biquad<N> {
+    pub fn new(sample_rate: f32, frequency: f32, q: f32, gain_db: f32) -> Self {
+        Self::with_shape(Shape::Peaking, sample_rate, frequency, q, gain_db)
+    }
+
+    pub fn with_shape(
+        shape: Shape,
+        sample_rate: f32,
+        frequency: f32,
+        q: f32,
+        gain_db: f32,
+    ) -> Self {
+        let smoothing = 1.0 - (-1.0 / (EQ_SMOOTHING_SECONDS * sample_rate)).exp();
+        let coefficients = Coefficients::for_shape(shape, sample_rate, frequency, q, gain_db);
+        Self {
+            shape,
+            sample_rate,
+            frequency,
+            target_frequency: frequency,
+            q,
+            current_gain_db: gain_db,
+            target_gain_db: gain_db,
+            smoothing,
+            coefficients,
+            state: Lanes::new([coefficients; N]),
+        }
+    }
+
+    pub fn set_target_gain(&mut self, gain_db: f32) {
+        self.target_gain_db = gain_db;
+    }
+
+    /// Glides the corner to `frequency` on a log scale, at the same rate
+    /// as gain changes.
+    pub fn set_target_frequency(&mut self, frequency: f32) {
+        self.target_frequency = frequency;
+    }
+
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    /// Switches Q at once, for a cutoff changing its slope.
+    pub fn set_q(&mut self, q: f32) {
+        if q != self.q {
+            self.q = q;
+            self.coefficients = Coefficients::for_shape(
+                self.shape,
+                self.sample_rate,
+                self.frequency,
+                q,
+                self.current_gain_db,
+            );
+            self.state.set(self.coefficients);
+        }
+    }
+
+    /// Filters one sample of each of the `N` signals.
+    pub fn process_lanes(&mut self, input: [f32; N]) -> [f32; N] {
+        // Smooth in the gain domain and rebuild the coefficients from the
+        // smoothed gain. Interpolating raw biquad coefficients is unstable for
+        // the near-unit-circle poles of the low bands; every filter produced
+        // this way is a genuine peaking or shelving filter and therefore
+        // stable.
+        if self.current_gain_db != self.target_gain_db || self.frequency != self.target_frequency {
+            self.current_gain_db += (self.target_gain_db - self.current_gain_db) * self.smoothing;
+            if (self.current_gain_db - self.target_gain_db).abs() < EQ_GAIN_SNAP_DB {
+                self.current_gain_db = self.target_gain_db;
+            }
+            let ratio = self.target_frequency / self.frequency;
+            self.frequency *= ratio.powf(self.smoothing);
+            if (ratio - 1.0).abs() < FREQUENCY_SNAP_RATIO {
+                self.frequency = self.target_frequency;
+            }
+            self.coefficients = Coefficients::for_shape(
+                self.shape,
+                self.sample_rate,
+                self.frequency,
+                self.q,
+                self.current_gain_db,
+            );
+            self.state.set(self.coefficients);
+        }
+        self.state.process(input)
+    }
+
+    /// How many times a non-finite output forced a state flush, counting
+    /// each lane.
+    pub fn resets(&self) -> u32 {
+        self.state.resets
+    }
+}
+
+impl Biquad {
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.process_lanes([input])[0]
+    }
+}
+
+/// A Butterworth low- or high-pass built from two gliding sections: 24
+/// dB/octave with both engaged, 12 with only the first. With no corner set
+/// it glides out to `open_hz` and then passes its input through untouched,
+/// but keeps its state running so engaging it again starts without a click.
+/// `Cutoff<2>` filters both channels at once.
+#[derive(Debug)]
+pub struct Cutoff<const N: usize = 1> {
+    shape: Shape,
+    sections: [Biquad<N>; 2],
+    open_hz: f32,
+    corner_hz: Option<f32>,
+    stages: usize,
+}
+
+impl<const N: usize> Cutoff<N> {
+    pub fn low_pass(sample_rate: f32, corner_hz: Option<f32>) -> Self {
+        // Stay under the 0.48 * rate guard so the glide never jumps to an
+        // identity filter.
+        let open_hz = 20_000.0_f32.min(sample_rate * 0.45);
+        Self::new(Shape::LowPass, sample_rate, open_hz, corner_hz, 24)
+    }
+
+    pub fn high_pass(sample_rate: f32, corner_hz: Option<f32>, slope_db: u32) -> Self {
+        Self::new(
+            Shape::HighPass,
+            sample_rate,
+            HIGH_PASS_OPEN_HZ,
+            corner_hz,
+            slope_db,
+        )
+    }
+
+    fn new(
+        shape: Shape,
+        sample_rate: f32,
+        open_hz: f32,
+        corner_hz: Option<f32>,
+        slope_db: u32,
+    ) -> Self {
+        let mut filter = Self {
+            shape,
+            sections: BUTTERWORTH_Q
+                .map(|q| Biquad::with_shape(shape, sample_rate, open_hz, q, 0.0)),
+            open_hz,
+            corner_hz,
+            stages: 2,
+        };
+        filter.set_slope(slope_db);
+        let targets = filter.targets();
+        for (section, target) in filter.sections.iter_mut().zip(targets) {
+            *section = Biquad::with_shape(shape, sample_rate, target, section.q, 0.0);
+        }
+        filter
+    }
+
+    pub fn set_corner(&mut self, corner_hz: Option<f32>) {
+        self.corner_hz = corner_hz;
+        self.retarget();
+    }
+
+    /// 12 dB/octave or steeper; anything above 12 takes both sections.
+    pub fn set_slope(&mut self, slope_db: u32) {
+        self.stages = if slope_db > 12 { 2 } else { 1 };
+        let qs = if self.stages == 2 {
+            BUTTERWORTH_Q
+        } else {
+            [FRAC_1_SQRT_2, BUTTERWORTH_Q[1]]
+        };
+        for (section, q) in self.sections.iter_mut().zip(qs) {
+            section.set_q(q);
+        }
+        self.retarget();
+    }
+
+    fn retarget(&mut self) {
+        let targets = self.targets();
+        for (section, target) in self.sections.iter_mut().zip(targets) {
+            section.set_target_frequency(target);
+        }
+    }
+
+    // Engaged sections head for the corner, the rest for `open_hz`.
+    fn targets(&self) -> [f32; 2] {
+        let corner = match (self.corner_hz, self.shape) {
+            (Some(hz), Shape::HighPass) => hz.max(self.open_hz),
+            (Some(hz), _) => hz.min(self.open_hz),
+            (None, _) => self.open_hz,
+        };
+        std::array::from_fn(|index| {
+            if index < self.stages {
+                corner
+            } else {
+                self.open_hz
+            }
+        })
+    }
+
+    pub fn process_lanes(&mut self, input: [f32; N]) -> [f32; N] {
+        let engaged = self.corner_hz.is_some();
+        let mut lanes = input;
+        for (index, section) in self.sections.iter_mut().enumerate() {
+            let output = section.process_lanes(lanes);
+            if (engaged && index < self.stages) || section.frequency() != self.open_hz {
+                lanes = output;
+            }
+        }
+        lanes
+    }
+
+    pub fn resets(&self) -> u32 {
+        self.sections
+            .iter()
+            .fold(0, |total, section| total.wrapping_add(section.resets()))
+    }
+}
+
+impl Cutoff {
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.process_lanes([input])[0]
+    }
+}
+
+/// A notch that fades in and out. With no center it blends back to its
+/// input and then leaves the filter's output unused, but keeps the filter
+/// running so engaging it again starts without a click. A new center
+/// glides like a corner; a new width takes effect at once.
+#[derive(Debug)]
+pub struct NotchFilter<const N: usize = 1> {
+    filter: Biquad<N>,
+    depth: LinearRamp,
+}
+
+impl<const N: usize> NotchFilter<N> {
+    pub fn new(sample_rate: f32, center_hz: Option<f32>, q: f32) -> Self {
+        Self {
+            filter: Biquad::with_shape(
+                Shape::Notch,
+                sample_rate,
+                center_hz.unwrap_or(NOTCH_IDLE_HZ),
+                q,
+                0.0,
+            ),
+            depth: LinearRamp::new(
+                if center_hz.is_some() { 1.0 } else { 0.0 },
+                sample_rate,
+                NOTCH_FADE_SECONDS,
+            ),
+        }
+    }
+
+    pub fn set(&mut self, center_hz: Option<f32>, q: f32) {
+        self.filter.set_q(q);
+        if let Some(hz) = center_hz {
+            if self.depth.settled() == Some(0.0) {
+                // Fully out, so nothing is heard if it starts over at the
+                // new center instead of gliding there.
+                let resets = self.filter.resets();
+                self.filter = Biquad::with_shape(Shape::Notch, self.filter.sample_rate, hz, q, 0.0);
+                self.filter.state.resets = resets;
+            } else {
+                self.filter.set_target_frequency(hz);
+            }
+        }
+        self.depth
+            .set_target(if center_hz.is_some() { 1.0 } else { 0.0 });
+    }
+
+    pub fn process_lanes(&mut self, input: [f32; N]) -> [f32; N] {
+        let notched = self.filter.process_lanes(input);
+        let depth = self.depth.next_value();
+        if depth == 0.0 {
+            input
+        } else {
+            std::array::from_fn(|lane| input[lane] + (notched[lane] - input[lane]) * depth)
+        }
+    }
+
+    pub fn resets(&self) -> u32 {
+        self.filter.resets()
+    }
+}
+
+impl NotchFilter {
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.process_lanes([input])[0]
+    }
+}
+
+/// An eighth-order Linkwitz-Riley crossover: at each crossover frequency
+/// the sound is split into low and high halves, each a fourth-order
+/// Butterworth run twice. The halves are 6 dB down there, fall 48 dB an
+/// octave past it, and stay in phase, so they sum flat. Each band then
+/// takes its own linear gain, so a band at 0 goes silent instead of
+/// dipping, and may take a different one in each channel, as the per-band
+/// stereo width weighs the mid and side chains.
+///
+/// The splits run upward, each one taking what the last passed above its
+/// crossover. The bands below a split must go through the same phase turn
+/// as the two halves it makes, so their running sum passes an allpass at
+/// each later crossover: one allpass per split rather than per band.
+///
+/// It takes both channels, a block of frames at a time. The fixed sections
+/// never glide, so each split runs as one chain of four lanes, both
+/// channels' low and high halves side by side, and the allpasses as a chain
+/// of two. A split takes a whole run of frames in one loop, with its state
+/// in registers, and checks them for non-finite values once at the end; a
+/// run that has one is done over sample by sample, each section flushing
+/// on its own as a `Biquad` would.
+#[derive(Debug)]
+pub struct BandSplitter {
+    splits: Vec<[Lanes<4>; 4]>,
+    all_passes: Vec<[Lanes<2>; 2]>,
+    gains: Vec<[LinearRamp; 2]>,
+}
+
+impl BandSplitter {
+    /// `crossovers_hz` must rise, and `gains` holds one more entry: a gain
+    /// for each band, lowest first.
+    pub fn new(sample_rate: f32, crossovers_hz: &[f32], gains: &[f32]) -> Self {
+        debug_assert_eq!(gains.len(), crossovers_hz.len() + 1);
+        let section = |shape, hz, q| Coefficients::for_shape(shape, sample_rate, hz, q, 0.0);
+        Self {
+            // Each half is the Butterworth pair run twice; the lanes are
+            // the left and right lows, then the left and right highs.
+            splits: crossovers_hz
+                .iter()
+                .map(|&hz| {
+                    [0, 1, 0, 1].map(|index| {
+                        let q = BUTTERWORTH_Q[index];
+                        let [low, high] =
+                            [Shape::LowPass, Shape::HighPass].map(|shape| section(shape, hz, q));
+                        Lanes::new([low, low, high, high])
+                    })
+                })
+                .collect(),
+            all_passes: crossovers_hz
+                .iter()
+                .skip(1)
+                .map(|&hz| BUTTERWORTH_Q.map(|q| Lanes::new([section(Shape::AllPass, hz, q); 2])))
+                .collect(),
+            gains: gains
+                .iter()
+                .map(|&gain| [LinearRamp::new(gain, sample_rate, BAND_GAIN_SECONDS); 2])
+                .collect(),
+        }
+    }
+
+    /// The response in dB at `frequency` of the half of a split at
+    /// `crossover_hz` that `shape`, `Shape::LowPass` or `Shape::HighPass`,
+    /// picks out.
+    pub fn half_response_db(
+        shape: Shape,
+        sample_rate: f32,
+        crossover_hz: f32,
+        frequency: f32,
+    ) -> f64 {
+        2.0 * BUTTERWORTH_Q
+            .iter()
+            .map(|&q| {
+                Coefficients::for_shape(shape, sample_rate, crossover_hz, q, 0.0)
+                    .magnitude_db(sample_rate, frequency)
+            })
+            .sum::<f64>()
+    }
+
+    pub fn set_gain(&mut self, band: usize, gain: f32) {
+        self.set_channel_gains(band, [gain; 2]);
+    }
+
+    /// A band's gain in the left and right channels apart.
+    pub fn set_channel_gains(&mut self, band: usize, gains: [f32; 2]) {
+        for (ramp, gain) in self.gains[band].iter_mut().zip(gains) {
+            ramp.set_target(gain);
+        }
+    }
+
+    pub fn process(&mut self, frame: [f32; 2]) -> [f32; 2] {
+        let mut block = [frame];
+        self.process_block(&mut block);
+        block[0]
+    }
+
+    /// Splits, weighs, and sums `block` in place.
+    pub fn process_block(&mut self, block: &mut [[f32; 2]]) {
+        for above in block.chunks_mut(CROSSOVER_RUN) {
+            let mut below = [[0.0; 2]; CROSSOVER_RUN];
+            let below = &mut below[..above.len()];
+            for index in 0..self.splits.len() {
+                self.split_run(index, above, below);
+            }
+            let [left, right] = self.gains.last_mut().expect("a gain per band");
+            for (above, below) in above.iter_mut().zip(below) {
+                *above = [
+                    below[0] + above[0] * left.next_value(),
+                    below[1] + above[1] * right.next_value(),
+                ];
+            }
+        }
+    }
+
+    /// Takes the split at `index` over a run, leaving what passes above it
+    /// in `above` and adding its band to `below`. It works on copies, kept
+    /// only if every frame came out finite.
+    fn split_run(&mut self, index: usize, above: &mut [[f32; 2]], below: &mut [[f32; 2]]) {
+        let mut sections = self.splits[index];
+        let mut all_pass = index.checked_sub(1).map(|index| self.all_passes[index]);
+        let mut gain = self.gains[index];
+        let mut highs = [[0.0; 2]; CROSSOVER_RUN];
+        let mut lows = [[0.0; 2]; CROSSOVER_RUN];
+        let (highs, lows) = (&mut highs[..above.len()], &mut lows[..above.len()]);
+        highs.copy_from_slice(above);
+        lows.copy_from_slice(below);
+        split_frames(
+            &mut sections,
+            all_pass.as_mut(),
+            &mut gain,
+            highs,
+            lows,
+            Lanes::filter,
+            Lanes::filter,
+        );
+
+        if highs
+            .iter()
+            .chain(&*lows)
+            .flatten()
+            .all(|sample| sample.is_finite())
+        {
+            self.splits[index] = sections;
+            if let Some(all_pass) = all_pass {
+                self.all_passes[index - 1] = all_pass;
+            }
+            self.gains[index] = gain;
+            above.copy_from_slice(highs);
+            below.copy_from_slice(lows);
+        } else {
+            split_frames(
+                &mut self.splits[index],
+                index
+                    .checked_sub(1)
+                    .map(|index| &mut self.all_passes[index]),
+                &mut self.gains[index],
+                above,
+                below,
+                Lanes::process,
+                Lanes::process,
+            );
+        }
+    }
+
+    /// How many times a non-finite output forced a state flush, counting
+    /// each lane.
+    pub fn resets(&self) -> u32 {
+        self.splits
+            .iter()
+            .flatten()
+            .map(|section| section.resets)
+            .chain(
+                self.all_passes
+                    .iter()
+                    .flatten()
+                    .map(|section| section.resets),
+            )
+            .fold(0, u32::wrapping_add)
+    }
+}
+
+/// One split of a `BandSplitter` over a run of frames, each section taken
+/// by `split` or `all_pass`: checked or unchecked.
+fn split_frames(
+    sections: &mut [Lanes<4>; 4],
+    mut all_pass: Option<&mut [Lanes<2>; 2]>,
+    gain: &mut [LinearRamp; 2],
+    above: &mut [[f32; 2]],
+    below: &mut [[f32; 2]],
+    split: impl Fn(&mut Lanes<4>, [f32; 4]) -> [f32; 4],
+    all_passed: impl Fn(&mut Lanes<2>, [f32; 2]) -> [f32; 2],
+) {
+    for (above, below) in above.iter_mut().zip(below) {
+        let [low_left, low_right, high_left, high_right] = sections.iter_mut().fold(
+            [above[0], above[1], above[0], above[1]],
+            |lanes, section| split(section, lanes),
+        );
+        *above = [high_left, high_right];
+        if let Some(all_pass) = all_pass.as_deref_mut() {
+            *below = all_pass
+                .iter_mut()
+                .fold(*below, |lanes, section| all_passed(section, lanes));
+        }
+        below[0] += low_left * gain[0].next_value();
+        below[1] += low_right * gain[1].next_value();
+    }
+}
+
+/// Headphone crossfeed: below about 700 Hz each ear also hears part of the
+/// other channel, as it would from a pair of speakers, so a wide image no
+/// longer sits inside the head. Highs pass untouched, and the shared lows
+/// are normalized so a mono signal keeps its level.
+#[derive(Debug)]
+pub struct Crossfeed {
+    smoothing: f32,
+    lows: [f32; 2],
+}
+
+impl Crossfeed {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            smoothing: 1.0 - (-2.0 * PI * CROSSFEED_CORNER_HZ / sample_rate).exp(),
+            lows: [0.0; 2],
+        }
+    }
+
+    /// `amount` runs from 0, an exact bypass, to 1. The low-pass state keeps
+    /// running while bypassed, so turning crossfeed on does not click.
+    pub fn process(&mut self, frame: [f32; 2], amount: f32) -> [f32; 2] {
+        for (low, input) in self.lows.iter_mut().zip(frame) {
+            *low += (input - *low) * self.smoothing;
+            if !low.is_finite() {
+                *low = 0.0;
+            }
+        }
+        if amount <= 0.0 {
+            return frame;
+        }
+        let feed = amount.min(1.0) * CROSSFEED_FEED;
+        let [left_low, right_low] = self.lows;
+        [
+            frame[0] - left_low + (left_low + feed * right_low) / (1.0 + feed),
+            frame[1] - right_low + (right_low + feed * left_low) / (1.0 + feed),
+        ]
+    }
+}
+
+/// Holds a stereo signal's true peak under a threshold without clipping it.
+/// Each frame waits a couple of milliseconds in a delay line while a
+/// detector reads its peak, including the peaks a converter would
+/// reconstruct between samples, so the gain is already down by the time
+/// the frame goes out. Both channels share one gain, so the image never
+/// shifts, and the gain recovers over the release once the peak has passed.
+#[derive(Debug)]
+pub struct PeakLimiter {
+    // The last TRUE_PEAK_TAPS input samples of each channel, oldest first.
+    history: [[f32; TRUE_PEAK_TAPS]; 2],
+    // Windowed-sinc taps for the points between the middle two samples of
+    // the history, a quarter, half, and three quarters of the way across.
+    taps: [[f32; TRUE_PEAK_TAPS]; TRUE_PEAK_PHASES],
+    // The largest sum of the taps' magnitudes over the phases.
+    taps_gain: f32,
+    // The loudest point between the judged sample and the one before it.
+    between: f32,
+    // Frames waiting to go out: the detector's latency plus the lookahead.
+    delay: VecDeque<[f32; 2]>,
+    // The frame count and gain each frame in the lookahead needs, rising
+    // from the front, so the front holds the least gain in the window.
+    needed: VecDeque<(u64, f32)>,
+    frames: u64,
+    // The least needed gain, let back up at the release's pace. A long
+    // release moves it so little each frame that in f32 it would stall
+    // short of 1.
+    held: f64,
+    release: f64,
+    sample_rate: f32,
+    // The last `lookahead` held gains and their sum; their average is the
+    // gain applied, which falls to a peak's over the whole lookahead.
+    averaged: Vec<f32>,
+    averaged_sum: f64,
+    next: usize,
+    gain: f32,
+}
+
+impl PeakLimiter {
+    pub fn new(sample_rate: f32, release_seconds: f32) -> Self {
+        let lookahead = ((LIMITER_LOOKAHEAD_SECONDS * sample_rate).round() as usize).max(1);
+        let taps: [[f32; TRUE_PEAK_TAPS]; TRUE_PEAK_PHASES] = std::array::from_fn(|phase| {
+            let offset = (phase + 1) as f32 / (TRUE_PEAK_PHASES + 1) as f32;
+            let mut taps: [f32; TRUE_PEAK_TAPS] = std::array::from_fn(|tap| {
+                // Tap positions run from TRUE_PEAK_HALF_TAPS - 1 samples
+                // before the point's left neighbour to as many after it.
+                let distance = (tap as f32 - (TRUE_PEAK_HALF_TAPS - 1) as f32) - offset;
+                let window = (PI * distance / (2.0 * TRUE_PEAK_HALF_TAPS as f32))
+                    .cos()
+                    .powi(2);
+                let sinc = if distance == 0.0 {
+                    1.0
+                } else {
+                    (PI * distance).sin() / (PI * distance)
+                };
+                sinc * window
+            });
+            let sum: f32 = taps.iter().sum();
+            for tap in &mut taps {
+                *tap /= sum;
+            }
+            taps
+        });
+        let taps_gain = taps
+            .iter()
+            .map(|taps| taps.iter().map(|tap| tap.abs()).sum())
+            .fold(0.0, f32::max);
+        let mut limiter = Self {
+            history: [[0.0; TRUE_PEAK_TAPS]; 2],
+            taps,
+            taps_gain,
+            between: 0.0,
+            delay: VecDeque::with_capacity(TRUE_PEAK_HALF_TAPS + lookahead),
+            needed: VecDeque::with_capacity(lookahead + 1),
+            frames: 0,
+            held: 1.0,
+            release: 0.0,
+            sample_rate,
+            averaged: vec![1.0; lookahead],
+            averaged_sum: lookahead as f64,
+            next: 0,
+            gain: 1.0,
+        };
+        limiter
+            .delay
+            .extend(std::iter::repeat_n([0.0; 2], limiter.latency()));
+        limiter.set_release(release_seconds);
+        limiter
+    }
+
+    /// How many frames late the output is.
+    pub fn latency(&self) -> usize {
+        TRUE_PEAK_HALF_TAPS + self.averaged.len() - 1
+    }
+
+    pub fn set_release(&mut self, seconds: f32) {
+        let frames = f64::from(seconds.max(f32::MIN_POSITIVE) * self.sample_rate);
+        self.release = 1.0 - (-1.0 / frames).exp();
+    }
+
+    /// The gain applied to the frame last returned; 1 while not limiting.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Takes a frame in and returns the one `latency()` frames before it,
+    /// under `threshold`, a linear true-peak amplitude. Anything that is
+    /// not a number comes out as silence.
+    pub fn process(&mut self, frame: [f32; 2], threshold: f32) -> [f32; 2] {
+        let frame = frame.map(|sample| if sample.is_finite() { sample } else { 0.0 });
+        let threshold = if threshold.is_nan() {
+            1.0
+        } else {
+            threshold.max(f32::MIN_POSITIVE)
+        };
+        let judged = TRUE_PEAK_HALF_TAPS - 1;
+        let mut peak = self.between;
+        let mut loudest = 0.0_f32;
+        for (history, sample) in self.history.iter_mut().zip(frame) {
+            history.copy_within(1.., 0);
+            history[TRUE_PEAK_TAPS - 1] = sample;
+            peak = peak.max(history[judged].abs());
+            loudest = history
+                .iter()
+                .fold(loudest, |loudest, sample| loudest.max(sample.abs()));
+        }
+        // No point between the samples can pass the loudest of them times
+        // the taps' total magnitude, so a stretch that bound keeps under the
+        // threshold skips the interpolation.
+        let bound = loudest * self.taps_gain;
+        let between = if bound < threshold {
+            bound
+        } else {
+            let mut between = 0.0_f32;
+            for history in &self.history {
+                for taps in &self.taps {
+                    let point: f32 = taps.iter().zip(history).map(|(tap, x)| tap * x).sum();
+                    between = between.max(point.abs());
+                }
+            }
+            between
+        };
+        self.between = between;
+        let peak = peak.max(between);
+        let needed = if peak > threshold {
+            threshold / peak
+        } else {
+            1.0
+        };
+
+        let lookahead = self.averaged.len() as u64;
+        while self.needed.back().is_some_and(|(_, gain)| *gain >= needed) {
+            self.needed.pop_back();
+        }
+        self.needed.push_back((self.frames, needed));
+        while self
+            .needed
+            .front()
+            .is_some_and(|(frame, _)| frame + lookahead <= self.frames)
+        {
+            self.needed.pop_front();
+        }
+        self.frames += 1;
+        let least = self
+            .needed
+            .front()
+            .map_or(1.0, |(_, gain)| f64::from(*gain));
+        self.held = if least < self.held || least - self.held < LIMITER_SETTLED {
+            least
+        } else {
+            self.held + (least - self.held) * self.release
+        };
+
+        let held = self.held as f32;
+        self.averaged_sum += f64::from(held) - f64::from(self.averaged[self.next]);
+        self.averaged[self.next] = held;
+        self.next = (self.next + 1) % self.averaged.len();
+        if self.next == 0 {
+            // Start each lap from an exact sum, so rounding cannot build up.
+            self.averaged_sum = self.averaged.iter().map(|gain| f64::from(*gain)).sum();
+        }
+        self.gain = (self.averaged_sum / lookahead as f64) as f32;
+
+        self.delay.push_back(frame);
+        let out = self.delay.pop_front().unwrap_or_default();
+        // The gain already holds the peaks under the threshold; the clamp
+        // only catches rounding and a threshold that moved meanwhile.
+        out.map(|sample| (sample * self.gain).clamp(-threshold, threshold))
+    }
+}
+
+/// Holds a stereo signal's RMS under a cap, as a slow automatic volume
+/// rather than a limiter: the power of both channels before the cap sets
+/// a gain that falls quickly when the level passes the cap and recovers
+/// slowly once it drops back.
+#[derive(Debug)]
+pub struct LevelCap {
+    window: f32,
+    attack: f32,
+    release: f32,
+    mean_square: f32,
+    gain: f32,
+}
+
+impl LevelCap {
+    pub fn new(sample_rate: f32) -> Self {
+        let smoothing = |seconds: f32| 1.0 - (-1.0 / (seconds * sample_rate)).exp();
+        Self {
+            window: smoothing(LEVEL_CAP_WINDOW_SECONDS),
+            attack: smoothing(LEVEL_CAP_ATTACK_SECONDS),
+            release: smoothing(LEVEL_CAP_RELEASE_SECONDS),
+            mean_square: 0.0,
+            gain: 1.0,
+        }
+    }
+
+    /// `cap` is an RMS amplitude; without one the gain returns to 1, while
+    /// the level is still followed so a cap set later starts from it.
+    pub fn process(&mut self, frame: [f32; 2], cap: Option<f32>) -> [f32; 2] {
+        let [left, right] = frame;
+        let power = (left * left + right * right) * 0.5;
+        if power.is_finite() {
+            self.mean_square += (power - self.mean_square) * self.window;
+        }
+        let rms = self.mean_square.sqrt();
+        let target = match cap {
+            Some(cap) if rms > cap => cap / rms,
+            _ => 1.0,
+        };
+        let smoothing = if target < self.gain {
+            self.attack
+        } else {
+            self.release
+        };
+        self.gain += (target - self.gain) * smoothing;
+        frame.map(|sample| sample * self.gain)
+    }
+}
+
+/// Short-term loudness: the mean square of a K-weighted signal, smoothed
+/// with a one-pole average. Two meters compare the loudness of two signals;
+/// their absolute scale means nothing on its own.
+#[derive(Debug)]
+pub struct LoudnessMeter {
+    shelf: Biquad,
+    high_pass: Biquad,
+    smoothing: f32,
+    mean_square: f32,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            shelf: Biquad::with_shape(
+                Shape::HighShelf,
+                sample_rate,
+                K_SHELF_HZ,
+                FRAC_1_SQRT_2,
+                K_SHELF_DB,
+            ),
+            high_pass: Biquad::with_shape(
+                Shape::HighPass,
+                sample_rate,
+                K_HIGH_PASS_HZ,
+                FRAC_1_SQRT_2,
+                0.0,
+            ),
+            smoothing: 1.0 - (-1.0 / (LOUDNESS_SECONDS * sample_rate)).exp(),
+            mean_square: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) {
+        let weighted = self.high_pass.process(self.shelf.process(input));
+        self.mean_square += (weighted * weighted - self.mean_square) * self.smoothing;
+        if !self.mean_square.is_finite() {
+            self.mean_square = 0.0;
+        }
+    }
+
+    pub fn mean_square(&self) -> f32 {
+        self.mean_square
+    }
+}
+
+// One matched-Z first-order stage: H(z) = (1 - zero*z^-1) / (1 - pole*z^-1).
+#[derive(Debug, Clone, Copy)]
+struct OnePoleZero {
+    zero: f32,
+    pole: f32,
+    x1: f32,
+    y1: f32,
+}
+
+impl OnePoleZero {
+    fn process(&mut self, input: f32) -> f32 {
+        let output = input - self.zero * self.x1 + self.pole * self.y1;
+        self.x1 = input;
+        self.y1 = output;
+        output
+    }
+}
+
+fn stage_power(zero: f64, pole: f64, cos_omega: f64) -> f64 {
+    (1.0 - 2.0 * zero * cos_omega + zero * zero) / (1.0 - 2.0 * pole * cos_omega + pole * pole)
+}
+
+fn ladder_power(stages: &[(f64, f64)], cos_omega: f64) -> f64 {
+    stages
+        .iter()
+        .map(|&(zero, pole)| stage_power(zero, pole, cos_omega))
+        .product()
+}
+
+// Mean of |H|^2 over the digital band, i.e. the white-to-output variance gain.
+fn ladder_variance_gain(stages: &[(f64, f64)]) -> f64 {
+    const STEPS: usize = 16_384;
+    (0..STEPS)
+        .map(|step| {
+            let omega = std::f64::consts::PI * (step as f64 + 0.5) / STEPS as f64;
+            ladder_power(stages, omega.cos())
+        })
+        .sum::<f64>()
+        / STEPS as f64
+}
+
+/// Pink noise (-3 dB per octave) built for the actual output sample rate: a
+/// ladder of matched-Z pole/zero stages spaced two octaves apart approximates
+/// the slope, and one correction zero solved at startup flattens the response
+/// near Nyquist. The result stays within about 0.25 dB of ideal pink from
+/// 20 Hz to 20 kHz at any common sample rate.
+#[derive(Debug)]
+pub struct PinkNoise {
+    stages: Vec<OnePoleZero>,
+    gain: f32,
+    resets: u32,
+}
+
+impl PinkNoise {
+    pub fn new(sample_rate: f32, target_rms: f32) -> Self {
+        Self::tilted(sample_rate, target_rms, &[], -1.0)
+    }
+
+    /// Builds the pink ladder after `leading` stages, solving the correction
+    /// zero so the whole chain follows a power spectrum proportional to
+    /// f^`exponent`.
+    fn tilted(sample_rate: f32, target_rms: f32, leading: &[(f64, f64)], exponent: f64) -> Self {
+        let fs = f64::from(sample_rate);
+        let radius = |frequency: f64| (-2.0 * std::f64::consts::PI * frequency / fs).exp();
+
+        let mut stages: Vec<(f64, f64)> = leading.to_vec();
+        let mut pole_hz = PINK_LADDER_START_HZ;
+        while pole_hz < fs {
+            let zero_hz = pole_hz * PINK_LADDER_RATIO.sqrt();
+            stages.push((radius(zero_hz), radius(pole_hz)));
+            pole_hz *= PINK_LADDER_RATIO;
+        }
+
+        // The raw chain drifts from its ideal slope approaching Nyquist: the
+        // pink ladder runs hot, a differentiator runs cold. Solve one
+        // correction zero (1 - a*z^-1) so the deviation from the ideal line
+        // (anchored at 1 kHz) is zero at the band top.
+        let deviation_db = |correction: f64, frequency: f64| {
+            let response = |f: f64| {
+                let cos_omega = (2.0 * std::f64::consts::PI * f / fs).cos();
+                let power =
+                    ladder_power(&stages, cos_omega) * stage_power(correction, 0.0, cos_omega);
+                10.0 * power.log10() - 10.0 * exponent * f.log10()
+            };
+            response(frequency) - response(1_000.0)
+        };
+        let solve_at = (0.40 * fs).min(18_000.0);
+        let mut low = -0.6_f64;
+        let mut high = 0.6_f64;
+        for _ in 0..60 {
+            let mid = 0.5 * (low + high);
+            if deviation_db(mid, solve_at) > 0.0 {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+        stages.push((0.5 * (low + high), 0.0));
+
+        let gain =
+            f64::from(target_rms) / (UNIFORM_INPUT_RMS * ladder_variance_gain(&stages).sqrt());
+
+        Self {
+            stages: stages
+                .into_iter()
+                .map(|(zero, pole)| OnePoleZero {
+                    zero: zero as f32,
+                    pole: pole as f32,
+                    x1: 0.0,
+                    y1: 0.0,
+                })
+                .collect(),
+            gain: gain as f32,
+            resets: 0,
+        }
+    }
+
+    pub fn process(&mut self, white: f32) -> f32 {
+        let mut sample = white;
+        for stage in &mut self.stages {
+            sample = stage.process(sample);
+        }
+        if sample.is_finite() {
+            sample * self.gain
+        } else {
+            // The ladder's poles keep a non-finite value circulating forever;
+            // restart from silence instead.
+            for stage in &mut self.stages {
+                stage.x1 = 0.0;
+                stage.y1 = 0.0;
+            }
+            self.resets = self.resets.wrapping_add(1);
+            0.0
+        }
+    }
+
+    /// How many times a non-finite value forced a state reset.
+    pub fn resets(&self) -> u32 {
+        self.resets
+    }
+}
+
+/// Blue noise (+3 dB per octave): a first difference followed by the pink
+/// ladder, with the correction zero re-solved for the rising slope.
+#[derive(Debug)]
+pub struct BlueNoise {
+    shaped: PinkNoise,
+}
+
+impl BlueNoise {
+    pub fn new(sample_rate: f32, target_rms: f32) -> Self {
+        // A zero at DC with no pole is the differentiator 1 - z^-1.
+        Self {
+            shaped: PinkNoise::tilted(sample_rate, target_rms, &[(1.0, 0.0)], 1.0),
+        }
+    }
+
+    pub fn process(&mut self, white: f32) -> f32 {
+        self.shaped.process(white)
+    }
+
+    /// How many times a non-finite value forced a state reset.
+    pub fn resets(&self) -> u32 {
+        self.shaped.resets()
+    }
+}
+
+/// Violet noise (+6 dB per octave): the first difference of white noise. The
+/// difference of two independent samples has exactly twice their variance,
+/// so the output gain is exact. Like brown in reverse, the slope eases
+/// slightly approaching Nyquist.
+#[derive(Debug)]
+pub struct VioletNoise {
+    gain: f32,
+    x1: f32,
+    resets: u32,
+}
+
+impl VioletNoise {
+    pub fn new(target_rms: f32) -> Self {
+        let gain = f64::from(target_rms) / (UNIFORM_INPUT_RMS * 2.0_f64.sqrt());
+        Self {
+            gain: gain as f32,
+            x1: 0.0,
+            resets: 0,
+        }
+    }
+
+    pub fn process(&mut self, white: f32) -> f32 {
+        let difference = white - self.x1;
+        self.x1 = white;
+        if difference.is_finite() {
+            difference * self.gain
+        } else {
+            self.x1 = 0.0;
+            self.resets = self.resets.wrapping_add(1);
+            0.0
+        }
+    }
+
+    /// How many times a non-finite value forced a state reset.
+    pub fn resets(&self) -> u32 {
+        self.resets
+    }
+}
+
+/// Brown noise (-6 dB per octave): a leaky integrator with the leak below the
+/// audible band. The output gain is exact, from the closed-form variance of a
+/// one-pole filter driven by white noise.
+#[derive(Debug)]
+pub struct BrownNoise {
+    pole: f32,
+    gain: f32,
+    y1: f32,
+    resets: u32,
+}
+
+impl BrownNoise {
+    pub fn new(sample_rate: f32, target_rms: f32) -> Self {
+        let fs = f64::from(sample_rate);
+        let pole = (-2.0 * std::f64::consts::PI * BROWN_LEAK_HZ / fs).exp();
+        let variance_gain = 1.0 / (1.0 - pole * pole);
+        let gain = f64::from(target_rms) / (UNIFORM_INPUT_RMS * variance_gain.sqrt());
+        Self {
+            pole: pole as f32,
+            gain: gain as f32,
+            y1: 0.0,
+            resets: 0,
+        }
+    }
+
+    pub fn process(&mut self, white: f32) -> f32 {
+        self.y1 = white + self.pole * self.y1;
+        if self.y1.is_finite() {
+            self.y1 * self.gain
+        } else {
+            self.y1 = 0.0;
+            self.resets = self.resets.wrapping_add(1);
+            0.0
+        }
+    }
+
+    /// How many times a non-finite value forced a state reset.
+    pub fn resets(&self) -> u32 {
+        self.resets
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LinearRamp {
+    current: f32,
+    target: f32,
+    step: f32,
+    remaining: u32,
+    ramp_samples: u32,
+}
+
+impl LinearRamp {
+    pub fn new(value: f32, sample_rate: f32, seconds: f32) -> Self {
+        Self {
+            current: value,
+            target: value,
+            step: 0.0,
+            remaining: 0,
+            ramp_samples: (sample_rate * seconds).round().max(1.0) as u32,
+        }
+    }
+
+    pub fn set_target(&mut self, target: f32) {
+        if (self.target - target).abs() < f32::EPSILON {
+            return;
+        }
+        self.target = target;
+        self.remaining = self.ramp_samples;
+        self.step = (self.target - self.current) / self.remaining as f32;
+    }
+
+    /// The value once a ramp has finished, or None mid-ramp.
+    pub fn settled(&self) -> Option<f32> {
+        (self.remaining == 0).then_some(self.current)
+    }
+
+    /// Steps the ramp by one sample and returns the value there.
+    pub fn next_value(&mut self) -> f32 {
+        if self.remaining > 0 {
+            self.current += self.step;
+            self.remaining -= 1;
+            if self.remaining == 0 {
+                self.current = self.target;
+            }
+        }
+        self.current
+    }
+}
+
+/// Beat tones at a peak amplitude of 1. Phases accumulate in f64 and run
+/// continuously, so retuning the carrier or beat never clicks.
+#[derive(Debug)]
+pub struct BeatTone {
+    sample_rate: f64,
+    phases: [f64; 2],
+    pulse_phase: f64,
+}
+
+impl BeatTone {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate: f64::from(sample_rate),
+            phases: [0.0; 2],
+            pulse_phase: 0.0,
+        }
+    }
+
+    /// Left at `carrier - beat / 2` and right at `carrier + beat / 2`.
+    pub fn binaural(&mut self, carrier: f32, beat: f32) -> [f32; 2] {
+        let half_beat = f64::from(beat) / 2.0;
+        let frequencies = [
+            f64::from(carrier) - half_beat,
+            f64::from(carrier) + half_beat,
+        ];
+        let mut frame = [0.0; 2];
+        for ((phase, frequency), sample) in self.phases.iter_mut().zip(frequencies).zip(&mut frame)
+        {
+            *sample = advance(phase, frequency, self.sample_rate);
+        }
+        frame
+    }
+
+    /// The carrier in both channels, on for the first half of each beat
+    /// period with raised-cosine edges and silent for the second.
+    pub fn isochronic(&mut self, carrier: f32, beat: f32) -> [f32; 2] {
+        let tone = advance(&mut self.phases[0], f64::from(carrier), self.sample_rate);
+        let position = self.pulse_phase;
+        advance(&mut self.pulse_phase, f64::from(beat), self.sample_rate);
+        let edge = (position.min(0.5 - position) / PULSE_EDGE).clamp(0.0, 1.0);
+        let envelope = 0.5 - 0.5 * (edge * std::f64::consts::PI).cos();
+        let sample = tone * envelope as f32;
+        [sample, sample]
+    }
+}
+
+/// The sine at `phase` (in cycles), then steps the phase one sample on.
+fn advance(phase: &mut f64, frequency: f64, sample_rate: f64) -> f32 {
+    let sample = (*phase * std::f64::consts::TAU).sin() as f32;
+    *phase = (*phase + frequency / sample_rate).fract();
+    sample
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::prelude::{RngExt, SmallRng};
+
+    const TARGET_RMS: f32 = 0.16;
+
+    // Deterministic magnitude response of the realized (f32) filter: capture
+    // its impulse response and evaluate the DFT at each target frequency.
+    fn impulse_octave_slopes_db(mut source: impl FnMut(f32) -> f32, sample_rate: f32) -> Vec<f64> {
+        let length = 1 << 18;
+        let impulse_response: Vec<f32> = (0..length)
+            .map(|index| source(if index == 0 { 1.0 } else { 0.0 }))
+            .collect();
+
+        let response_db = |frequency: f64| {
+            let omega = 2.0 * std::f64::consts::PI * frequency / f64::from(sample_rate);
+            let (mut re, mut im) = (0.0_f64, 0.0_f64);
+            for (index, &h) in impulse_response.iter().enumerate() {
+                let phase = omega * index as f64;
+                re += f64::from(h) * phase.cos();
+                im -= f64::from(h) * phase.sin();
+            }
+            10.0 * (re * re + im * im).log10()
+        };
+
+        let frequencies = [125.0_f64, 250.0, 500.0, 1_000.0, 2_000.0, 4_000.0, 8_000.0];
+        let levels: Vec<f64> = frequencies.iter().map(|f| response_db(*f)).collect();
+        levels.windows(2).map(|pair| pair[1] - pair[0]).collect()
+    }
+
+    #[test]
+    fn pink_noise_falls_three_db_per_octave() {
+        for sample_rate in [44_100.0_f32, 48_000.0, 192_000.0] {
+            let mut pink = PinkNoise::new(sample_rate, TARGET_RMS);
+            let slopes = impulse_octave_slopes_db(|sample| pink.process(sample), sample_rate);
+            for (octave, slope) in slopes.iter().enumerate() {
+                assert!(
+                    (slope - -3.01).abs() < 0.5,
+                    "pink octave {octave} slope was {slope:.2} dB at {sample_rate} Hz"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn brown_noise_falls_six_db_per_octave() {
+        for sample_rate in [44_100.0_f32, 48_000.0, 192_000.0] {
+            let mut brown = BrownNoise::new(sample_rate, TARGET_RMS);
+            let slopes = impulse_octave_slopes_db(|sample| brown.process(sample), sample_rate);
+            for (octave, slope) in slopes.iter().enumerate() {
+                // A digital one-pole flattens slightly approaching Nyquist,
+                // so the top octave sits near -5.7 dB at 44.1 kHz.
+                assert!(
+                    (slope - -6.02).abs() < 0.6,
+                    "brown octave {octave} slope was {slope:.2} dB at {sample_rate} Hz"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn blue_noise_rises_three_db_per_octave() {
+        for sample_rate in [44_100.0_f32, 48_000.0, 192_000.0] {
+            let mut blue = BlueNoise::new(sample_rate, TARGET_RMS);
+            let slopes = impulse_octave_slopes_db(|sample| blue.process(sample), sample_rate);
+            for (octave, slope) in slopes.iter().enumerate() {
+                assert!(
+                    (slope - 3.01).abs() < 0.5,
+                    "blue octave {octave} slope was {slope:.2} dB at {sample_rate} Hz"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn violet_noise_rises_six_db_per_octave() {
+        for sample_rate in [44_100.0_f32, 48_000.0, 192_000.0] {
+            let mut violet = VioletNoise::new(TARGET_RMS);
+            let slopes = impulse_octave_slopes_db(|sample| violet.process(sample), sample_rate);
+            for (octave, slope) in slopes.iter().enumerate() {
+                // |1 - e^-jw| = 2 sin(w/2) eases below the ideal line near
+                // Nyquist, so the top octave sits near 5.7 dB at 44.1 kHz.
+                assert!(
+                    (slope - 6.02).abs() < 0.6,
+                    "violet octave {octave} slope was {slope:.2} dB at {sample_rate} Hz"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn peaking_response_hits_the_requested_gain_at_center() {
+        for sample_rate in [44_100.0_f32, 48_000.0, 96_000.0] {
+            for gain_db in [-12.0_f32, -3.0, 3.0, 12.0] {
+                for frequency in [40.0_f32, 1_000.0, 15_000.0] {
+                    let coefficients = Coefficients::peaking(sample_rate, frequency, 1.0, gain_db);
+                    let center = coefficients.magnitude_db(sample_rate, frequency);
+                    assert!(
+                        (center - f64::from(gain_db)).abs() < 0.05,
+                        "{frequency} Hz at {sample_rate} Hz peaked at {center:.3} dB, wanted {gain_db}"
+                    );
+                    // Far from the center the filter returns to unity.
+                    let far = if frequency < 1_000.0 { 10.0 } else { 0.1 };
+                    let edge = coefficients.magnitude_db(sample_rate, frequency * far);
+                    assert!(
+                        edge.abs() < 1.0,
+                        "response {far}x from center was {edge:.2} dB"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn peaking_is_identity_at_zero_gain_and_near_nyquist() {
+        assert_eq!(
+            Coefficients::peaking(48_000.0, 1_000.0, 1.0, 0.0),
+            Coefficients::IDENTITY
+        );
+        assert_eq!(
+            Coefficients::peaking(22_050.0, 16_000.0, 1.0, 6.0),
+            Coefficients::IDENTITY
+        );
+    }
+
+    #[test]
+    fn notch_silences_its_center_and_is_3_db_down_a_bandwidth_apart() {
+        const SAMPLE_RATE: f32 = 48_000.0;
+        let notch = crate::settings::Notch {
+            hz: Some(6_500.0),
+            width_octaves: 1.0,
+        };
+        let coefficients = Coefficients::notch(SAMPLE_RATE, 6_500.0, notch.q());
+        let (low, high) = notch.edges_hz(6_500.0);
+        assert!(coefficients.magnitude_db(SAMPLE_RATE, 6_500.0) < -60.0);
+        for edge in [low, high] {
+            let response = coefficients.magnitude_db(SAMPLE_RATE, edge);
+            // Still a little lopsided this high, but only by tenths of a dB.
+            assert!(
+                (response + 3.01).abs() < 0.3,
+                "edge {edge:.0} Hz: {response:.2} dB"
+            );
+        }
+        for far in [500.0, 16_000.0] {
+            assert!(coefficients.magnitude_db(SAMPLE_RATE, far).abs() < 0.5);
+        }
+        assert_eq!(
+            Coefficients::notch(22_050.0, 16_000.0, 1.0),
+            Coefficients::IDENTITY
+        );
+
+        // Turned off, it blends back to an exact bypass; turned on at a new
+        // center, it fades in there without a jump.
+        let mut filter = NotchFilter::new(SAMPLE_RATE, Some(6_500.0), notch.q());
+        let tone = |n: usize| (n as f32 * std::f32::consts::TAU * 6_500.0 / SAMPLE_RATE).sin();
+        let mut last = 0.0_f32;
+        for n in 0..48_000 {
+            last = filter.process(tone(n));
+        }
+        assert!(last.abs() < 0.01, "{last}");
+        filter.set(None, notch.q());
+        for n in 0..48_000 {
+            filter.process(tone(n));
+        }
+        assert_eq!(filter.process(0.25), 0.25);
+        filter.set(Some(1_000.0), notch.q());
+        let engaged = filter.process(0.25);
+        assert!((engaged - 0.25).abs() < 0.01, "{engaged}");
+        assert_eq!(filter.resets(), 0);
+    }
+
+    #[test]
+    fn crossover_bands_sum_flat_and_a_silenced_band_stays_in_its_range() {
+        const SAMPLE_RATE: f32 = 48_000.0;
+        let response_db = |splitter: &mut BandSplitter, frequency: f64| {
+            let omega = std::f64::consts::TAU * frequency / f64::from(SAMPLE_RATE);
+            let (mut re, mut im) = (0.0_f64, 0.0_f64);
+            for index in 0..1 << 16 {
+                let impulse = if index == 0 { 1.0 } else { 0.0 };
+                let [left, right] = splitter.process([impulse, impulse]);
+                assert_eq!(left, right);
+                let h = f64::from(left);
+                re += h * (omega * f64::from(index)).cos();
+                im -= h * (omega * f64::from(index)).sin();
+            }
+            10.0 * (re * re + im * im).log10()
+        };
+        let frequencies = [40.0, 125.0, 250.0, 1_000.0, 2_000.0, 8_000.0, 15_000.0];
+
+        let mut flat = BandSplitter::new(SAMPLE_RATE, &[250.0, 2_000.0], &[1.0; 3]);
+        for frequency in frequencies {
+            let response = response_db(&mut flat, frequency);
+            assert!(response.abs() < 0.01, "{frequency} Hz: {response:.3} dB");
+        }
+
+        // The middle band alone: flat inside, 6 dB down at the crossovers,
+        // and more than 47 dB down an octave past them.
+        let mut middle = BandSplitter::new(SAMPLE_RATE, &[250.0, 2_000.0], &[0.0, 1.0, 0.0]);
+        for (frequency, expected) in [(250.0, -6.02), (707.0, 0.0), (2_000.0, -6.02)] {
+            let response = response_db(&mut middle, frequency);
+            assert!(
+                (response - expected).abs() < 0.1,
+                "{frequency} Hz: {response:.2} dB"
+            );
+        }
+        for frequency in [125.0, 4_000.0] {
+            let response = response_db(&mut middle, frequency);
+            assert!(response < -47.0, "{frequency} Hz: {response:.2} dB");
+        }
+
+        // Silencing it takes 1 kHz down by more than 40 dB and leaves
+        // 8 kHz alone once the gain has glided there.
+        flat.set_gain(1, 0.0);
+        for _ in 0..SAMPLE_RATE as usize {
+            flat.process([0.0; 2]);
+        }
+        assert!(response_db(&mut flat, 1_000.0) < -40.0);
+        assert!(response_db(&mut flat, 8_000.0).abs() < 0.05);
+        assert_eq!(flat.resets(), 0);
+        let estimate =
+            BandSplitter::half_response_db(Shape::HighPass, SAMPLE_RATE, 2_000.0, 1_000.0);
+        assert!((estimate + 48.2).abs() < 0.5, "{estimate:.2} dB");
+    }
+
+    #[test]
+    fn shelves_reach_their_gain_on_one_side_and_half_of_it_at_the_corner() {
+        for sample_rate in [44_100.0_f32, 48_000.0, 96_000.0] {
+            for gain_db in [-6.0_f32, -1.5, 3.0, 6.0] {
+                for frequency in [125.0_f32, 1_000.0] {
+                    let low = Coefficients::shelf(Shape::LowShelf, sample_rate, frequency, gain_db);
+                    let high =
+                        Coefficients::shelf(Shape::HighShelf, sample_rate, frequency, gain_db);
+                    let gain = f64::from(gain_db);
+                    for (coefficients, shelf_side, open_side) in
+                        [(low, 0.05, 16.0), (high, 16.0, 0.05)]
+                    {
+                        let corner = coefficients.magnitude_db(sample_rate, frequency);
+                        let shelf = coefficients.magnitude_db(sample_rate, frequency * shelf_side);
+                        let open = coefficients.magnitude_db(sample_rate, frequency * open_side);
+                        assert!((corner - gain / 2.0).abs() < 0.05, "corner {corner:.3} dB");
+                        assert!((shelf - gain).abs() < 0.15, "shelf {shelf:.3} dB");
+                        assert!(open.abs() < 0.15, "open side {open:.3} dB");
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            Coefficients::shelf(Shape::HighShelf, 48_000.0, 1_000.0, 0.0),
+            Coefficients::IDENTITY
+        );
+    }
+
+    #[test]
+    fn low_pass_falls_24_db_per_octave_and_opens_to_an_exact_bypass() {
+        const SAMPLE_RATE: f32 = 48_000.0;
+        let sections = BUTTERWORTH_Q.map(|q| Coefficients::low_pass(SAMPLE_RATE, 2_000.0, q));
+        let response = |frequency: f32| -> f64 {
+            sections
+                .iter()
+                .map(|section| section.magnitude_db(SAMPLE_RATE, frequency))
+                .sum()
+        };
+        assert!((response(2_000.0) + 3.01).abs() < 0.05);
+        assert!(response(500.0).abs() < 0.05);
+        // Well below Nyquist, where the bilinear transform barely warps it.
+        let octave = response(8_000.0) - response(4_000.0);
+        assert!((octave + 24.0).abs() < 3.0, "octave slope {octave:.1} dB");
+
+        // A ceiling that is lifted glides open and then bypasses exactly.
+        let mut filter = Cutoff::low_pass(SAMPLE_RATE, Some(4_000.0));
+        filter.set_corner(None);
+        for n in 0..48_000 {
+            filter.process((n as f32 * 0.1).sin());
+        }
+        assert_eq!(filter.process(0.25), 0.25);
+        filter.set_corner(Some(2_000.0));
+        let engaged = filter.process(0.25);
+        assert!(engaged.is_finite() && engaged != 0.25);
+    }
+
+    #[test]
+    fn high_pass_slopes_follow_the_section_count_and_open_to_a_bypass() {
+        const SAMPLE_RATE: f32 = 48_000.0;
+        let response = |slope_db: u32, frequency: f32| -> f64 {
+            let filter: Cutoff = Cutoff::high_pass(SAMPLE_RATE, Some(40.0), slope_db);
+            filter
+                .sections
+                .iter()
+                .take(filter.stages)
+                .map(|section| section.coefficients.magnitude_db(SAMPLE_RATE, frequency))
+                .sum()
+        };
+        for slope_db in [12, 24] {
+            assert!((response(slope_db, 40.0) + 3.01).abs() < 0.05, "{slope_db}");
+            assert!(response(slope_db, 1_000.0).abs() < 0.05, "{slope_db}");
+            let octave = response(slope_db, 20.0) - response(slope_db, 10.0);
+            assert!(
+                (octave - f64::from(slope_db)).abs() < 1.5,
+                "{slope_db} dB slope measured {octave:.1} dB"
+            );
+        }
+
+        // Changing the slope keeps the output finite and bounded.
+        let mut filter = Cutoff::high_pass(SAMPLE_RATE, Some(40.0), 24);
+        for n in 0..48_000 {
+            if n == 24_000 {
+                filter.set_slope(12);
+            }
+            let output = filter.process((n as f32 * 0.05).sin());
+            assert!(output.is_finite() && output.abs() < 2.0);
+        }
+        filter.set_corner(None);
+        for n in 0..48_000 {
+            filter.process((n as f32 * 0.05).sin());
+        }
+        assert_eq!(filter.process(0.25), 0.25);
+    }
+
+    #[test]
+    fn crossfeed_blends_only_the_lows_and_keeps_mono_level() {
+        const SAMPLE_RATE: f32 = 48_000.0;
+        // RMS of the left channel for a sine played in antiphase.
+        let antiphase_rms = |frequency: f32, amount: f32| {
+            let mut crossfeed = Crossfeed::new(SAMPLE_RATE);
+            let mut sum = 0.0;
+            for n in 0..96_000 {
+                let sine = (2.0 * PI * frequency * n as f32 / SAMPLE_RATE).sin();
+                let [left, _] = crossfeed.process([sine, -sine], amount);
+                if n >= 48_000 {
+                    sum += f64::from(left * left);
+                }
+            }
+            (sum / 48_000.0).sqrt()
+        };
+        let reference = antiphase_rms(100.0, 0.0);
+        assert!((reference - 0.5_f64.sqrt()).abs() < 1e-3);
+        // Antiphase lows cancel toward (1 - feed) / (1 + feed) of the input.
+        let lows = antiphase_rms(100.0, 1.0) / reference;
+        assert!((lows - 1.0 / 3.0).abs() < 0.05, "lows kept {lows:.3}");
+        let highs = antiphase_rms(12_000.0, 1.0) / antiphase_rms(12_000.0, 0.0);
+        assert!(highs > 0.95, "highs kept {highs:.3}");
+
+        let mut mono = Crossfeed::new(SAMPLE_RATE);
+        let mut bypassed = Crossfeed::new(SAMPLE_RATE);
+        for n in 0..1_000 {
+            let sample = (n as f32 * 0.3).sin();
+            let [left, right] = mono.process([sample, sample], 1.0);
+            assert!((left - sample).abs() < 1e-5 && (right - sample).abs() < 1e-5);
+            assert_eq!(bypassed.process([sample, -sample], 0.0), [sample, -sample]);
+        }
+    }
+
+    #[test]
+    fn loudness_meter_weights_highs_up_and_deep_bass_down() {
+        const SAMPLE_RATE: f32 = 48_000.0;
+        let loudness_db = |frequency: f32, amplitude: f32| {
+            let mut meter = LoudnessMeter::new(SAMPLE_RATE);
+            for n in 0..5 * 48_000 {
+                meter.process(amplitude * (2.0 * PI * frequency * n as f32 / SAMPLE_RATE).sin());
+            }
+            10.0 * meter.mean_square().log10()
+        };
+        let mid = loudness_db(1_000.0, 0.5);
+        assert!((loudness_db(1_000.0, 1.0) - mid - 6.02).abs() < 0.1);
+        let highs = loudness_db(8_000.0, 0.5) - mid;
+        assert!((2.5..4.5).contains(&highs), "8 kHz reads {highs:+.1} dB");
+        let lows = loudness_db(20.0, 0.5) - mid;
+        assert!(lows < -8.0, "20 Hz reads {lows:+.1} dB");
+    }
+
+    #[test]
+    fn biquad_output_stays_finite_for_random_parameters_and_input() {
+        // A small seeded fuzz pass over the whole parameter space the EQ can
+        // reach, including abrupt retargets and hostile input values.
+        let mut rng = SmallRng::seed_from_u64(99);
+        for _ in 0..200 {
+            let sample_rate =
+                [22_050.0_f32, 44_100.0, 48_000.0, 96_000.0, 192_000.0][rng.random_range(0..5)];
+            let frequency = 20.0 * 1_000.0_f32.powf(rng.random::<f32>());
+            let q = 0.5 + rng.random::<f32>() * 2.5;
+            let mut biquad =
+                Biquad::new(sample_rate, frequency, q, rng.random::<f32>() * 30.0 - 18.0);
+            for step in 0..2_000 {
+                if step % 250 == 0 {
+                    biquad.set_target_gain(rng.random::<f32>() * 30.0 - 18.0);
+                }
+                let input = match rng.random_range(0..100) {
+                    0 => f32::NAN,
+                    1 => f32::INFINITY,
+                    2 => 1.0e30,
+                    _ => rng.random::<f32>() * 2.0 - 1.0,
+                };
+                assert!(biquad.process(input).is_finite());
+            }
+        }
+    }
+
+    #[test]
+    fn shaping_filters_reset_after_non_finite_input_and_count_it() {
+        let mut pink = PinkNoise::new(48_000.0, TARGET_RMS);
+        let mut brown = BrownNoise::new(48_000.0, TARGET_RMS);
+        let mut blue = BlueNoise::new(48_000.0, TARGET_RMS);
+        let mut violet = VioletNoise::new(TARGET_RMS);
+        for _ in 0..1_000 {
+            pink.process(0.5);
+            brown.process(0.5);
+            blue.process(0.5);
+            violet.process(0.5);
+        }
+
+        assert_eq!(pink.process(f32::NAN), 0.0);
+        assert_eq!(brown.process(f32::INFINITY), 0.0);
+        assert_eq!(blue.process(f32::NAN), 0.0);
+        assert_eq!(violet.process(f32::NEG_INFINITY), 0.0);
+        assert_eq!(pink.resets(), 1);
+        assert_eq!(brown.resets(), 1);
+        assert_eq!(blue.resets(), 1);
+        assert_eq!(violet.resets(), 1);
+        for _ in 0..1_000 {
+            assert!(blue.process(0.5).is_finite());
+            assert!(violet.process(0.5).is_finite());
+        }
+
+        // Both restart from silence rather than staying poisoned.
+        for _ in 0..1_000 {
+            assert!(pink.process(0.5).is_finite());
+            assert!(brown.process(0.5).is_finite());
+        }
+        assert_eq!(pink.resets(), 1);
+        assert_eq!(brown.resets(), 1);
+    }
+
+    #[test]
+    fn biquad_counts_state_flushes() {
+        let mut biquad = Biquad::new(48_000.0, 100.0, 1.0, 6.0);
+        biquad.process(0.1);
+        assert_eq!(biquad.resets(), 0);
+        biquad.process(f32::NAN);
+        assert_eq!(biquad.resets(), 1);
+        assert!(biquad.process(0.1).is_finite());
+    }
+
+    #[test]
+    fn peak_limiter_holds_true_peaks_under_the_threshold() {
+        let sample_rate = 48_000.0;
+        let threshold = 0.1;
+        let mut limiter = PeakLimiter::new(sample_rate, 0.5);
+        let mut rng = SmallRng::seed_from_u64(5);
+        let mut late_peak = 0.0_f32;
+        for index in 0..48_000 {
+            let input = [rng.random_range(-0.9..0.9), rng.random_range(-0.9..0.9)];
+            let output = limiter.process(input, threshold);
+            assert!(output.iter().all(|sample| sample.abs() <= threshold));
+            if index >= 24_000 {
+                late_peak = late_peak.max(output[0].abs()).max(output[1].abs());
+            }
+        }
+        // The gain follows the peaks rather than flattening the signal
+        // against the threshold, which is what a clipper would do.
+        assert!(late_peak < threshold, "{late_peak}");
+
+        // A quarter-rate sine sampled 45 degrees off its crests: every
+        // sample is at 0.71, but the wave between them reaches 1.
+        let mut limiter = PeakLimiter::new(sample_rate, 0.5);
+        let mut sample_peak = 0.0_f32;
+        for index in 0..4_800 {
+            let phase = (index as f32 / 4.0 + 0.125) * 2.0 * PI;
+            let output = limiter.process([phase.sin(); 2], 0.8);
+            if index >= 480 {
+                sample_peak = sample_peak.max(output[0].abs());
+            }
+        }
+        assert!(sample_peak < 0.8 * FRAC_1_SQRT_2 * 1.05, "{sample_peak}");
+        assert!(limiter.gain() < 0.85);
+    }
+
+    #[test]
+    fn peak_limiter_is_down_before_a_transient_and_lets_go_after_it() {
+        let sample_rate = 48_000.0;
+        let mut limiter = PeakLimiter::new(sample_rate, 0.1);
+        let latency = limiter.latency();
+        // Quiet material comes through exactly, only late.
+        let quiet: Vec<[f32; 2]> = (0..1_000)
+            .map(|index| [0.001 * (index % 7) as f32, -0.002])
+            .collect();
+        let out: Vec<[f32; 2]> = quiet
+            .iter()
+            .map(|frame| limiter.process(*frame, 0.5))
+            .collect();
+        assert!(out[..latency].iter().all(|frame| *frame == [0.0; 2]));
+        assert_eq!(out[latency..], quiet[..quiet.len() - latency]);
+        assert_eq!(limiter.gain(), 1.0);
+
+        // A lone full-scale click never passes the threshold, not even its
+        // first sample.
+        let mut outputs = vec![limiter.process([1.0, -1.0], 0.5)];
+        outputs.extend((0..48_000).map(|_| limiter.process([0.001, 0.001], 0.5)));
+        assert!(outputs.iter().flatten().all(|sample| sample.abs() <= 0.5));
+        assert!(outputs[latency][0] > 0.45, "{:?}", outputs[latency]);
+        // A second after it the gain is all the way back.
+        assert_eq!(limiter.gain(), 1.0);
+
+        let mut late = limiter.process([f32::NAN, 0.001], 0.5);
+        for _ in 0..latency {
+            late = limiter.process([f32::INFINITY, 0.0], 0.5);
+        }
+        assert_eq!(late, [0.0, 0.001]);
+    }
+
+    #[test]
+    fn level_cap_holds_the_rms_at_the_cap_and_lets_go_without_one() {
+        let sample_rate = 48_000.0;
+        let mut cap = LevelCap::new(sample_rate);
+        let mut rng = SmallRng::seed_from_u64(6);
+        // Uniform noise in ±0.5 has an RMS of about 0.29.
+        let mut rms = |cap: &mut LevelCap, limit: Option<f32>, frames: usize| {
+            let sum: f32 = (0..frames)
+                .map(|_| {
+                    let input = [rng.random_range(-0.5..0.5), rng.random_range(-0.5..0.5)];
+                    let [left, right] = cap.process(input, limit);
+                    (left * left + right * right) * 0.5
+                })
+                .sum();
+            (sum / frames as f32).sqrt()
+        };
+        assert!((rms(&mut cap, None, 48_000) - 0.289).abs() < 0.01);
+        rms(&mut cap, Some(0.1), 48_000);
+        let capped = rms(&mut cap, Some(0.1), 48_000);
+        assert!((capped - 0.1).abs() < 0.005, "{capped}");
+        rms(&mut cap, None, 48_000 * 10);
+        assert!((rms(&mut cap, None, 48_000) - 0.289).abs() < 0.01);
+    }
+
+    /// Upward zero crossings, which count whole cycles of a sine.
+    fn cycles(samples: &[f32]) -> usize {
+        samples
+            .windows(2)
+            .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+            .count()
+    }
+
+    #[test]
+    fn binaural_channels_sit_half_a_beat_either_side_of_the_carrier() {
+        let mut tone = BeatTone::new(48_000.0);
+        let frames: Vec<[f32; 2]> = (0..48_000).map(|_| tone.binaural(200.0, 4.0)).collect();
+        let left: Vec<f32> = frames.iter().map(|frame| frame[0]).collect();
+        let right: Vec<f32> = frames.iter().map(|frame| frame[1]).collect();
+        assert!(cycles(&left).abs_diff(198) <= 1, "{}", cycles(&left));
+        assert!(cycles(&right).abs_diff(202) <= 1, "{}", cycles(&right));
+        assert!(
+            frames
+                .iter()
+                .flatten()
+                .all(|sample| sample.is_finite() && sample.abs() <= 1.0)
+        );
+    }
+
+    #[test]
+    fn isochronic_pulses_are_gated_smoothly_at_the_beat_rate() {
+        let mut tone = BeatTone::new(48_000.0);
+        let samples: Vec<f32> = (0..48_000)
+            .map(|_| {
+                let [left, right] = tone.isochronic(200.0, 10.0);
+                assert_eq!(left, right);
+                left
+            })
+            .collect();
+        // Each 100 ms period is silent for its second half.
+        for period in samples.chunks(4_800) {
+            assert!(period[2_400..].iter().all(|sample| *sample == 0.0));
+            assert!(period[..2_400].iter().any(|sample| sample.abs() > 0.99));
+        }
+        // No step is larger than the carrier's own slope allows.
+        let slope = std::f32::consts::TAU * 200.0 / 48_000.0;
+        let largest_step = samples
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]).abs())
+            .fold(0.0, f32::max);
+        assert!(largest_step < slope * 1.5, "step {largest_step}");
+    }
+
+    #[test]
+    fn beat_tones_stay_bounded_at_extreme_settings() {
+        let mut tone = BeatTone::new(8_000.0);
+        for (carrier, beat) in [(40.0, 40.0), (1_000.0, 0.5), (3_999.0, 40.0)] {
+            for _ in 0..8_000 {
+                for sample in tone
+                    .binaural(carrier, beat)
+                    .into_iter()
+                    .chain(tone.isochronic(carrier, beat))
+                {
+                    assert!(sample.is_finite() && sample.abs() <= 1.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn linear_ramp_reaches_its_target_exactly() {
+        let mut ramp = LinearRamp::new(0.0, 1_000.0, 0.01);
+        ramp.set_target(0.3);
+        let values: Vec<f32> = (0..12).map(|_| ramp.next_value()).collect();
+        assert!(values.windows(2).all(|pair| pair[1] >= pair[0]));
+        assert_eq!(values[9], 0.3);
+        assert_eq!(values[11], 0.3);
+    }
+}
@@ -0,0 +1,425 @@
+//! Ducking: with `--duck-on-playback`, the noise drops by `--duck-db` or
+//! pauses while another program plays through PulseAudio or PipeWire's
+//! PulseAudio server, and comes back once it stops. `pactl subscribe`
+//! announces stream changes and `pactl list sink-inputs` shows what is
+//! playing. A corked stream does not count, so a paused video, or music a
+//! call has corked through its media role, lets the noise come back.
+//!
+//! `--duck-on-calls` does the same for voice calls alone, by `--call-duck-db`
+//! and on a gain of its own, so the two can be on together. A call is a
+//! playback or capture stream that a call app, or PipeWire's session
+//! manager on its behalf, tags with the `phone` or `communication` media
+//! role, as WebRTC, Zoom, Teams, and softphones do; the audio itself does
+//! not matter, so a muted microphone or a silent line still counts.
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, ensure};
+
+use crate::settings::{AudioSettings, SettingsGuard, SharedSettings};
+
+pub const MAX_DUCK_DB: f32 = 60.0;
+// Other audio has to stay quiet this long before the noise returns, so the
+// gap between two tracks or a notification's blip does not bounce it.
+const RESTORE_DELAY: Duration = Duration::from_secs(2);
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What `--duck-db` asks for while other audio plays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DuckMode {
+    /// Lower the noise by this many dB.
+    Lower(f32),
+    Pause,
+}
+
+/// `--duck-db`: a number of dB from 1 to 60, or `pause`.
+pub fn parse_duck(value: &str) -> std::result::Result<DuckMode, String> {
+    if value.trim().eq_ignore_ascii_case("pause") {
+        return Ok(DuckMode::Pause);
+    }
+    let error = || format!("ducking must be 1 to {MAX_DUCK_DB:.0} dB, or pause");
+    let db = value
+        .trim()
+        .trim_end_matches("dB")
+        .trim_end_matches("db")
+        .trim()
+        .parse::<f32>()
+        .map_err(|_| error())?;
+    if !db.is_finite() || !(1.0..=MAX_DUCK_DB).contains(&db) {
+        return Err(error());
+    }
+    Ok(DuckMode::Lower(db))
+}
+
+/// What a ducker answers to, and so which gain of the settings it sets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cue {
+    /// Any other program playing.
+    Playback,
+    /// A voice call, playing or capturing.
+    Calls,
+}
+
+impl Cue {
+    fn gain(self, settings: &mut AudioSettings) -> &mut Option<f32> {
+        match self {
+            Self::Playback => &mut settings.duck_db,
+            Self::Calls => &mut settings.call_duck_db,
+        }
+    }
+
+    /// Whether a listing from `list` calls for the duck.
+    fn heard(self, listing: &str, own_pid: u32) -> bool {
+        match self {
+            Self::Playback => others_playing(listing, own_pid),
+            Self::Calls => in_call(listing, own_pid),
+        }
+    }
+
+    /// The sink inputs, and for calls the source outputs as well.
+    fn list(self) -> Result<String> {
+        let mut listing = list("sink-inputs")?;
+        if self == Self::Calls {
+            listing.push_str(&list("source-outputs")?);
+        }
+        Ok(listing)
+    }
+
+    /// Whether a `pactl subscribe` line announces a stream this cue lists.
+    fn announced(self, line: &str) -> bool {
+        line.contains("sink-input") || (self == Self::Calls && line.contains("source-output"))
+    }
+}
+
+/// Whether any stream in a `pactl list sink-inputs` listing other than this
+/// process's own is playing: uncorked, unmuted, and not an event sound.
+fn others_playing(listing: &str, own_pid: u32) -> bool {
+    let own_pid = own_pid.to_string();
+    listing.split("Sink Input #").skip(1).any(|stream| {
+        field(stream, "Corked:") != Some("yes")
+            && field(stream, "Mute:") != Some("yes")
+            && property(stream, "application.process.id") != Some(own_pid.as_str())
+            && property(stream, "media.role") != Some("event")
+    })
+}
+
+/// Whether any uncorked stream in a listing of sink inputs and source
+/// outputs, other than this process's own, carries a call's media role.
+fn in_call(listing: &str, own_pid: u32) -> bool {
+    let own_pid = own_pid.to_string();
+    listing
+        .split("Sink Input #")
+        .flat_map(|part| part.split("Source Output #"))
+        .skip(1)
+        .any(|stream| {
+            field(stream, "Corked:") != Some("yes")
+                && property(stream, "application.process.id") != Some(own_pid.as_str())
+                && property(stream, "media.role").is_some_and(|role| {
+                    role.eq_ignore_ascii_case("phone") || role.eq_ignore_ascii_case("communication")
+                })
+        })
+}
+
+fn field<'a>(stream: &'a str, name: &str) -> Option<&'a str> {
+    stream
+        .lines()
+        .find_map(|line| line.trim().strip_prefix(name))
+        .map(str::trim)
+}
+
+fn property<'a>(stream: &'a str, name: &str) -> Option<&'a str> {
+    stream
+        .lines()
+        .find_map(|line| {
+            line.trim()
+                .strip_prefix(name)?
+                .trim_start()
+                .strip_prefix('=')
+        })
+        .map(|value| value.trim().trim_matches('"'))
+}
+
+/// Ducks and restores the live settings as other audio starts and stops.
+#[derive(Debug)]
+struct Ducker {
+    cue: Cue,
+    mode: DuckMode,
+    ducked: bool,
+    // Set when the duck itself paused playback, so a pause the listener
+    // made is not undone on restore.
+    paused_here: bool,
+    quiet_since: Option<Instant>,
+}
+
+impl Ducker {
+    fn new(cue: Cue, mode: DuckMode) -> Self {
+        Self {
+            cue,
+            mode,
+            ducked: false,
+            paused_here: false,
+            quiet_since: None,
+        }
+    }
+
+    fn update(&mut self, others_playing: bool, settings: &mut AudioSettings, now: Instant) {
+        if others_playing {
+            self.quiet_since = None;
+            if !self.ducked {
+                self.ducked = true;
+                match self.mode {
+                    DuckMode::Lower(db) => *self.cue.gain(settings) = Some(db),
+                    DuckMode::Pause => {
+                        self.paused_here = !settings.paused;
+                        settings.paused = true;
+                    }
+                }
+            }
+            return;
+        }
+        if !self.ducked {
+            return;
+        }
+        let quiet_since = *self.quiet_since.get_or_insert(now);
+        if now.saturating_duration_since(quiet_since) >= RESTORE_DELAY {
+            self.restore(settings);
+        }
+    }
+
+    fn restore(&mut self, settings: &mut AudioSettings) {
+        if !self.ducked {
+            return;
+        }
+        *self.cue.gain(settings) = None;
+        if self.paused_here {
+            settings.paused = false;
+        }
+        self.ducked = false;
+        self.paused_here = false;
+        self.quiet_since = None;
+    }
+}
+
+/// Starts following other playback, or calls, until `running` clears.
+/// Fails when `pactl` cannot be run or no sound server answers it.
+pub fn watch(
+    cue: Cue,
+    mode: DuckMode,
+    settings: Arc<SharedSettings>,
+    running: Arc<AtomicBool>,
+) -> Result<()> {
+    // Listing first reports a missing server before anything is spawned.
+    cue.list()?;
+    let mut subscription = pactl(&["subscribe"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("failed to run `pactl subscribe`")?;
+    let stdout = subscription
+        .stdout
+        .take()
+        .context("`pactl subscribe` has no output")?;
+
+    let (changes, changed) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            let Ok(line) = line else { break };
+            if cue.announced(&line) && changes.send(()).is_err() {
+                break;
+            }
+        }
+    });
+    std::thread::spawn(move || {
+        let _subscription = Subscription(subscription);
+        let own_pid = std::process::id();
+        let mut ducker = Ducker::new(cue, mode);
+        let mut playing = false;
+        // The first pass checks what was already playing at start.
+        let mut stale = true;
+        while running.load(Ordering::Relaxed) {
+            if stale {
+                playing = cue.list().is_ok_and(|listing| cue.heard(&listing, own_pid));
+            }
+            ducker.update(playing, &mut lock(&settings), Instant::now());
+            stale = match changed.recv_timeout(CHECK_INTERVAL) {
+                Ok(()) => {
+                    while changed.try_recv().is_ok() {}
+                    true
+                }
+                Err(RecvTimeoutError::Timeout) => false,
+                // pactl exited with the sound server; undo any duck.
+                Err(RecvTimeoutError::Disconnected) => {
+                    ducker.restore(&mut lock(&settings));
+                    break;
+                }
+            };
+        }
+    });
+    Ok(())
+}
+
+/// Stops `pactl subscribe` with the watcher.
+struct Subscription(Child);
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// `pactl` with its output in English, which is what the listing is read as.
+fn pactl(args: &[&str]) -> Command {
+    let mut command = Command::new("pactl");
+    command.args(args).env("LC_ALL", "C");
+    command
+}
+
+/// `pactl list` of `kind`, such as `sink-inputs`.
+fn list(kind: &str) -> Result<String> {
+    let output = pactl(&["list", kind])
+        .stdin(Stdio::null())
+        .output()
+        .context("failed to run pactl (install pulseaudio-utils to duck)")?;
+    ensure!(
+        output.status.success(),
+        "pactl could not reach a PulseAudio or PipeWire server"
+    );
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn lock(settings: &SharedSettings) -> SettingsGuard<'_> {
+    settings
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LISTING: &str = "Sink Input #71
+\tDriver: PipeWire
+\tCorked: no
+\tMute: no
+\tProperties:
+\t\tapplication.name = \"whitenoise\"
+\t\tapplication.process.id = \"4242\"
+
+Sink Input #80
+\tDriver: PipeWire
+\tCorked: yes
+\tMute: no
+\tProperties:
+\t\tapplication.name = \"Firefox\"
+\t\tapplication.process.id = \"1000\"
+\t\tmedia.role = \"music\"
+
+Sink Input #81
+\tDriver: PipeWire
+\tCorked: no
+\tMute: no
+\tProperties:
+\t\tapplication.name = \"GNOME Shell\"
+\t\tmedia.role = \"event\"
+";
+
+    #[test]
+    fn only_other_uncorked_streams_count_as_playing() {
+        assert!(!others_playing(LISTING, 4242));
+        assert!(others_playing(LISTING, 1));
+        let uncorked = LISTING.replacen("Corked: yes", "Corked: no", 1);
+        assert!(others_playing(&uncorked, 4242));
+        let muted = uncorked.replacen("Corked: no\n\tMute: no", "Corked: no\n\tMute: yes", 2);
+        assert!(!others_playing(&muted, 4242));
+        assert!(!others_playing("", 4242));
+
+        assert_eq!(parse_duck("12"), Ok(DuckMode::Lower(12.0)));
+        assert_eq!(parse_duck("20 dB"), Ok(DuckMode::Lower(20.0)));
+        assert_eq!(parse_duck("Pause"), Ok(DuckMode::Pause));
+        for bad in ["0", "61", "-6", "loud"] {
+            assert!(parse_duck(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn a_duck_restores_once_other_audio_has_been_quiet_a_while() {
+        let start = Instant::now();
+        let mut settings = AudioSettings::default();
+        let mut ducker = Ducker::new(Cue::Playback, DuckMode::Lower(15.0));
+        ducker.update(true, &mut settings, start);
+        assert_eq!(settings.duck_db, Some(15.0));
+        ducker.update(false, &mut settings, start + Duration::from_secs(1));
+        assert_eq!(settings.duck_db, Some(15.0));
+        // A new track before the delay keeps the duck.
+        ducker.update(true, &mut settings, start + Duration::from_secs(2));
+        ducker.update(false, &mut settings, start + Duration::from_secs(3));
+        ducker.update(false, &mut settings, start + Duration::from_secs(4));
+        assert_eq!(settings.duck_db, Some(15.0));
+        ducker.update(false, &mut settings, start + Duration::from_secs(5));
+        assert_eq!(settings.duck_db, None);
+
+        // A pause the listener made stays after the other audio stops.
+        let mut pausing = Ducker::new(Cue::Playback, DuckMode::Pause);
+        pausing.update(true, &mut settings, start);
+        assert!(settings.paused);
+        pausing.update(false, &mut settings, start + RESTORE_DELAY);
+        pausing.update(false, &mut settings, start + RESTORE_DELAY * 2);
+        assert!(!settings.paused);
+        settings.paused = true;
+        pausing.update(true, &mut settings, start);
+        pausing.update(false, &mut settings, start + RESTORE_DELAY);
+        pausing.update(false, &mut settings, start + RESTORE_DELAY * 2);
+        assert!(settings.paused);
+    }
+
+    #[test]
+    fn calls_are_streams_with_a_call_role_and_duck_on_their_own_gain() {
+        let call = "Source Output #12
+\tDriver: PipeWire
+\tCorked: no
+\tMute: yes
+\tProperties:
+\t\tapplication.name = \"Chromium\"
+\t\tapplication.process.id = \"2000\"
+\t\tmedia.role = \"Communication\"
+";
+        // Music playing is not a call, and an uncorked call is one even
+        // with its microphone muted.
+        let uncorked = LISTING.replacen("Corked: yes", "Corked: no", 1);
+        assert!(!in_call(&uncorked, 4242));
+        assert!(in_call(&format!("{LISTING}{call}"), 4242));
+        assert!(!in_call(call, 2000));
+        let on_hold = call.replacen("Corked: no", "Corked: yes", 1);
+        assert!(!in_call(&on_hold, 4242));
+        let phone = LISTING.replacen("\"music\"", "\"phone\"", 1);
+        assert!(!in_call(&phone, 4242));
+        assert!(in_call(
+            &phone.replacen("Corked: yes", "Corked: no", 1),
+            4242
+        ));
+
+        let start = Instant::now();
+        let mut settings = AudioSettings::default();
+        let mut playback = Ducker::new(Cue::Playback, DuckMode::Lower(15.0));
+        let mut calls = Ducker::new(Cue::Calls, DuckMode::Lower(25.0));
+        playback.update(true, &mut settings, start);
+        calls.update(true, &mut settings, start);
+        assert_eq!(settings.duck_db, Some(15.0));
+        assert_eq!(settings.call_duck_db, Some(25.0));
+        // The call ending leaves the duck for other playback in place.
+        calls.update(false, &mut settings, start + Duration::from_secs(1));
+        calls.update(
+            false,
+            &mut settings,
+            start + Duration::from_secs(1) + RESTORE_DELAY,
+        );
+        assert_eq!(settings.call_duck_db, None);
+        assert_eq!(settings.duck_db, Some(15.0));
+    }
+}
@@ -0,0 +1,74 @@
+//! Exit statuses for scripts. Errors that a wrapper may want to tell apart
+//! carry a `Failure` as context, and `main` exits with its status; any
+//! other error exits with 1, and clap's usage errors with 2.
+
+use std::fmt;
+use std::process::ExitCode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// `--host` names a backend this build lacks or cannot start.
+    HostUnavailable,
+    /// No device matches `--device`, more than one does, or there is no
+    /// default device.
+    DeviceNotFound,
+    /// The device would not open or start a stream.
+    StreamFailed,
+    /// `ctl` or `status` found no player listening on the socket.
+    NotRunning,
+}
+
+impl Failure {
+    pub fn status(self) -> u8 {
+        match self {
+            Self::HostUnavailable => 3,
+            Self::DeviceNotFound => 4,
+            Self::StreamFailed => 5,
+            Self::NotRunning => 6,
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(match self {
+            Self::HostUnavailable => "the audio host is unavailable",
+            Self::DeviceNotFound => "could not choose an audio device",
+            Self::StreamFailed => "the audio stream failed",
+            Self::NotRunning => "no player is running",
+        })
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// The status to exit with after `error`: its `Failure`'s, or 1.
+pub fn exit_code(error: &anyhow::Error) -> ExitCode {
+    ExitCode::from(
+        error
+            .downcast_ref::<Failure>()
+            .map_or(1, |failure| failure.status()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{Context, anyhow};
+
+    use super::*;
+
+    #[test]
+    fn a_tagged_error_exits_with_its_own_status() {
+        let plain = anyhow!("device was not found (available: HDMI)");
+        assert_eq!(exit_code(&plain), ExitCode::from(1));
+        let tagged = Err::<(), _>(plain)
+            .context(Failure::DeviceNotFound)
+            .context("failed to calibrate")
+            .unwrap_err();
+        assert_eq!(exit_code(&tagged), ExitCode::from(4));
+        assert_eq!(
+            format!("{tagged:#}"),
+            "failed to calibrate: could not choose an audio device: device was not found (available: HDMI)"
+        );
+    }
+}
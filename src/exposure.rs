@@ -0,0 +1,284 @@
+//! Noise exposure: with an SPL calibration, a background thread turns the
+//! output's estimated level into a share of a daily noise dose and warns
+//! when a night at the current level would pass it. The dose follows the
+//! NIOSH recommended limit, 85 dB for 8 hours with every 3 dB more halving
+//! the time, which is occupational guidance rather than a medical limit.
+//! It is counted over the last 24 hours in hourly steps kept in a file
+//! beside the settings, so a night split across restarts is one night.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::audio::AudioMonitor;
+use crate::i18n::{text, text_with};
+use crate::settings::{SharedSettings, config_path};
+use crate::timer::format_remaining;
+
+/// The level a full dose allows for `REFERENCE_TIME`.
+pub const REFERENCE_DB: f32 = 85.0;
+pub const REFERENCE_TIME: Duration = Duration::from_secs(8 * 3600);
+/// Each step this much louder halves the time allowed.
+pub const EXCHANGE_DB: f32 = 3.0;
+/// How long a night is taken to last when no sleep timer ends it sooner.
+pub const NIGHT: Duration = Duration::from_secs(8 * 3600);
+const HOURS_KEPT: u64 = 24;
+// Short, so joining the thread at exit does not hold up the quit.
+const TRACK_INTERVAL: Duration = Duration::from_millis(250);
+// A longer gap between reads means the machine slept, and nothing played.
+const MAX_STEP: Duration = Duration::from_secs(5);
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+// Once warned, the projection has to fall this far before another warning,
+// so a level hovering at the limit does not send one a minute.
+const WARN_AGAIN_BELOW: f32 = 0.9;
+
+/// The share of a full daily dose one second at `level_db` SPL uses.
+pub fn dose_per_second(level_db: f32) -> f64 {
+    2_f64.powf(f64::from(level_db - REFERENCE_DB) / f64::from(EXCHANGE_DB))
+        / REFERENCE_TIME.as_secs_f64()
+}
+
+/// One hour's share of the dose, keyed by hours since the Unix epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct HourDose {
+    hour: u64,
+    dose: f64,
+}
+
+/// The dose of the last 24 hours, as `exposure.toml` keeps it.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExposureLog {
+    #[serde(default)]
+    hours: Vec<HourDose>,
+}
+
+impl ExposureLog {
+    /// Adds `dose` to the hour `hour`, dropping hours that fell out of the
+    /// window.
+    pub fn add(&mut self, hour: u64, dose: f64) {
+        self.hours
+            .retain(|entry| entry.hour + HOURS_KEPT > hour && entry.hour <= hour);
+        match self.hours.iter_mut().find(|entry| entry.hour == hour) {
+            Some(entry) => entry.dose += dose,
+            None => self.hours.push(HourDose { hour, dose }),
+        }
+    }
+
+    /// The dose of the 24 hours up to and including `hour`, where 1.0 is a
+    /// full day's.
+    pub fn dose(&self, hour: u64) -> f64 {
+        self.hours
+            .iter()
+            .filter(|entry| entry.hour + HOURS_KEPT > hour && entry.hour <= hour)
+            .map(|entry| entry.dose)
+            .filter(|dose| dose.is_finite() && *dose > 0.0)
+            .sum()
+    }
+}
+
+/// The dose so far plus what `remaining` more at `level_db` would add.
+pub fn projected_dose(dose: f64, level_db: Option<f32>, remaining: Duration) -> f64 {
+    dose + level_db.map_or(0.0, |db| remaining.as_secs_f64() * dose_per_second(db))
+}
+
+/// The hour `wall` falls in, counted from the Unix epoch.
+pub fn hour_of(wall: SystemTime) -> u64 {
+    wall.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / 3600
+}
+
+/// `exposure.toml` beside settings.toml.
+pub fn exposure_path() -> PathBuf {
+    config_path().with_file_name("exposure.toml")
+}
+
+pub fn load_exposure(path: &Path) -> Result<ExposureLog> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(ExposureLog::default()),
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to read {}", path.display()));
+        }
+    };
+    toml::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+pub fn save_exposure(path: &Path, log: &ExposureLog) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    fs::write(path, toml::to_string(log)?)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// What the tracking thread last worked out, for the interface to show.
+#[derive(Debug, Default)]
+pub struct Exposure {
+    // f32 bits of the last 24 hours' dose and of that plus the rest of
+    // the night at the current level.
+    dose: AtomicU32,
+    projected: AtomicU32,
+}
+
+impl Exposure {
+    /// The last 24 hours' share of a daily dose; 1.0 is all of it.
+    pub fn dose(&self) -> f32 {
+        f32::from_bits(self.dose.load(Ordering::Relaxed))
+    }
+
+    /// The dose by the end of the night, or of the sleep timer, if the
+    /// level stays where it is.
+    pub fn projected(&self) -> f32 {
+        f32::from_bits(self.projected.load(Ordering::Relaxed))
+    }
+
+    /// Whether the night at this level would pass a full dose.
+    pub fn over(&self) -> bool {
+        self.projected() > 1.0
+    }
+
+    /// Publishes a new dose and projection.
+    pub fn set(&self, dose: f64, projected: f64) {
+        self.dose.store((dose as f32).to_bits(), Ordering::Relaxed);
+        self.projected
+            .store((projected as f32).to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Counts the dose while the output plays with an SPL calibration,
+/// publishing it through `exposure`, sending a desktop notification
+/// through `notify` when the night would pass a full dose, and keeping
+/// `path` up to date. Join the handle before exiting so the last minute is
+/// written.
+pub fn track(
+    path: PathBuf,
+    mut log: ExposureLog,
+    settings: Arc<SharedSettings>,
+    monitor: Arc<AudioMonitor>,
+    exposure: Arc<Exposure>,
+    running: Arc<AtomicBool>,
+    notify: fn(&str, &str) -> Result<()>,
+) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut last = Instant::now();
+        let mut saved = Instant::now();
+        let mut unsaved = false;
+        let mut warned = false;
+        while running.load(Ordering::Relaxed) {
+            std::thread::sleep(TRACK_INTERVAL);
+            let now = Instant::now();
+            let step = now.duration_since(last).min(MAX_STEP);
+            last = now;
+            let current = *settings
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            let rms = monitor.rms();
+            let level = current
+                .spl
+                .estimate_db(20.0 * rms.log10())
+                .filter(|db| rms > 0.0 && db.is_finite());
+            let hour = hour_of(SystemTime::now());
+            if let Some(db) = level {
+                log.add(hour, step.as_secs_f64() * dose_per_second(db));
+                unsaved = true;
+            }
+            let remaining = current
+                .sleep_timer
+                .map_or(NIGHT, |timer| timer.remaining(now));
+            let dose = log.dose(hour);
+            let projected = projected_dose(dose, level, remaining);
+            exposure.set(dose, projected);
+
+            if let Some(db) = level.filter(|_| projected > 1.0 && !warned) {
+                // Best effort, as for the alarm: the interface shows it too.
+                let _ = notify(
+                    text("exposure.title"),
+                    &text_with(
+                        "exposure.message",
+                        &[
+                            ("spl", &format!("{db:.0}")),
+                            ("time", &format_remaining(remaining)),
+                            ("percent", &format!("{:.0}", projected * 100.0)),
+                        ],
+                    ),
+                );
+                warned = true;
+            } else if projected < f64::from(WARN_AGAIN_BELOW) {
+                warned = false;
+            }
+            if unsaved && saved.elapsed() >= SAVE_INTERVAL {
+                // Best effort: a warning here would scribble over the
+                // interface, and the count carries on in memory.
+                let _ = save_exposure(&path, &log);
+                saved = now;
+                unsaved = false;
+            }
+        }
+        if unsaved {
+            let _ = save_exposure(&path, &log);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_full_dose_is_85_db_for_8_hours_halving_every_3_db() {
+        let hours = |db: f32, hours: f64| hours * 3600.0 * dose_per_second(db);
+        assert!((hours(85.0, 8.0) - 1.0).abs() < 1e-9);
+        assert!((hours(88.0, 4.0) - 1.0).abs() < 1e-9);
+        assert!((hours(82.0, 16.0) - 1.0).abs() < 1e-9);
+        // A typical masking level uses a sliver of the allowance overnight.
+        assert!(hours(55.0, 8.0) < 0.01);
+    }
+
+    #[test]
+    fn the_log_counts_the_last_24_hours() {
+        let mut log = ExposureLog::default();
+        log.add(1_000, 0.25);
+        log.add(1_000, 0.25);
+        log.add(1_010, 0.125);
+        assert_eq!(log.dose(1_010), 0.625);
+        assert_eq!(log.dose(1_023), 0.625);
+        assert_eq!(log.dose(1_024), 0.125);
+        // Adding later drops the hours that fell out of the window.
+        log.add(1_030, 0.0);
+        assert_eq!(log.hours.len(), 2);
+        assert_eq!(log.dose(1_030), 0.125);
+    }
+
+    #[test]
+    fn a_night_projects_the_current_level_forward() {
+        assert_eq!(projected_dose(0.2, None, NIGHT), 0.2);
+        let night = projected_dose(0.2, Some(REFERENCE_DB), NIGHT);
+        assert!((night - 1.2).abs() < 1e-9, "{night}");
+        let short = projected_dose(0.0, Some(REFERENCE_DB), Duration::from_secs(3600));
+        assert!((short - 0.125).abs() < 1e-9, "{short}");
+    }
+
+    #[test]
+    fn the_log_survives_a_restart() {
+        let dir = std::env::temp_dir().join(format!("whitenoise-exposure-{}", std::process::id()));
+        let path = dir.join("exposure.toml");
+        assert_eq!(load_exposure(&path).unwrap(), ExposureLog::default());
+        let mut log = ExposureLog::default();
+        log.add(hour_of(SystemTime::now()), 0.5);
+        save_exposure(&path, &log).unwrap();
+        assert_eq!(load_exposure(&path).unwrap(), log);
+        fs::write(&path, "hours = 3").unwrap();
+        assert!(load_exposure(&path).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
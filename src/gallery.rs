@@ -0,0 +1,227 @@
+//! The community preset gallery: a plain JSON endpoint set by the `gallery`
+//! key in settings.toml, browsed with G on the Presets page and through
+//! `whitenoise presets gallery`, `fetch`, and `submit`. Nothing is sent or
+//! fetched without that key, a submission carries nothing but the preset
+//! file, a name, and a description, and the network client is built only
+//! with the `gallery` feature.
+//!
+//! The endpoint answers three requests: `GET URL` lists the presets as
+//! `[{"id", "name", "description", "downloads"}]`, `GET URL/ID` returns one
+//! as `presets export` writes it, and `POST URL` takes
+//! `{"name", "description", "preset"}` and answers `{"id"}`.
+
+use std::sync::mpsc::{self, Receiver};
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+
+#[cfg(feature = "gallery")]
+use std::time::Duration;
+
+// A gallery that stalls should fail the request, not leave the page
+// waiting all night.
+#[cfg(feature = "gallery")]
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+/// One preset in the gallery's listing.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Entry {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub downloads: u64,
+}
+
+impl Entry {
+    /// One line for listings: `rainy focus: steady pink under rain (120 downloads)`.
+    pub fn line(&self) -> String {
+        let downloads = match self.downloads {
+            1 => "1 download".to_owned(),
+            count => format!("{count} downloads"),
+        };
+        if self.description.is_empty() {
+            format!("{} ({downloads})", self.name)
+        } else {
+            format!("{}: {} ({downloads})", self.name, self.description)
+        }
+    }
+}
+
+/// What a request made in the background brought back.
+#[derive(Debug)]
+pub enum Reply {
+    List(Result<Vec<Entry>>),
+    /// The preset file, for `preset::import_preset`.
+    Preset(Result<String>),
+}
+
+/// The `gallery` address from settings.toml, or why there is none.
+pub fn configured(url: Option<&str>) -> Result<&str> {
+    url.context("no preset gallery is set; add gallery = \"https://...\" to settings.toml")
+}
+
+/// The gallery's listing, most downloaded first.
+pub fn list(url: &str) -> Result<Vec<Entry>> {
+    parse_list(&get(checked(url)?)?)
+}
+
+/// One preset's file, by its id in the listing.
+pub fn fetch(url: &str, id: &str) -> Result<String> {
+    check_id(id)?;
+    get(&format!("{}/{id}", checked(url)?))
+}
+
+/// Sends a preset file under `name` with `description`, answering the id the
+/// gallery gave it.
+#[cfg(feature = "gallery")]
+pub fn submit(url: &str, name: &str, description: &str, preset: &str) -> Result<String> {
+    #[derive(serde::Serialize)]
+    struct Submission<'a> {
+        name: &'a str,
+        description: &'a str,
+        preset: &'a str,
+    }
+    #[derive(Deserialize)]
+    struct Submitted {
+        id: String,
+    }
+
+    let body = serde_json::to_string(&Submission {
+        name,
+        description,
+        preset,
+    })?;
+    let reply: Submitted = serde_json::from_str(&post(checked(url)?, &body)?)
+        .context("the gallery's answer to the submission is not an id")?;
+    check_id(&reply.id)?;
+    Ok(reply.id)
+}
+
+/// `list` on a thread of its own, so the interface keeps drawing.
+pub fn request_list(url: String) -> Receiver<Reply> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(Reply::List(list(&url)));
+    });
+    receiver
+}
+
+/// `fetch` on a thread of its own.
+pub fn request_preset(url: String, id: String) -> Receiver<Reply> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(Reply::Preset(fetch(&url, &id)));
+    });
+    receiver
+}
+
+/// The endpoint without a trailing slash. Only HTTPS is spoken, so what is
+/// fetched is what the gallery sent.
+fn checked(url: &str) -> Result<&str> {
+    let url = url.trim().trim_end_matches('/');
+    if !url.starts_with("https://") || url.len() == "https://".len() {
+        bail!("the gallery address '{url}' is not an https:// URL");
+    }
+    Ok(url)
+}
+
+/// Ids go into the request's path, so only letters, digits, `-`, and `_`.
+fn check_id(id: &str) -> Result<()> {
+    if id.is_empty()
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        bail!("'{id}' is not a gallery preset id");
+    }
+    Ok(())
+}
+
+/// Entries with an id that cannot be asked for are left out.
+fn parse_list(body: &str) -> Result<Vec<Entry>> {
+    let mut entries: Vec<Entry> =
+        serde_json::from_str(body).context("the gallery's answer is not a preset listing")?;
+    entries.retain(|entry| check_id(&entry.id).is_ok());
+    entries.sort_by(|a, b| b.downloads.cmp(&a.downloads).then(a.name.cmp(&b.name)));
+    Ok(entries)
+}
+
+#[cfg(feature = "gallery")]
+fn agent() -> ureq::Agent {
+    ureq::Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .build()
+        .into()
+}
+
+#[cfg(feature = "gallery")]
+fn get(url: &str) -> Result<String> {
+    let mut response = agent()
+        .get(url)
+        .call()
+        .with_context(|| format!("failed to reach the gallery at {url}"))?;
+    response
+        .body_mut()
+        .read_to_string()
+        .context("failed to read the gallery's answer")
+}
+
+#[cfg(feature = "gallery")]
+fn post(url: &str, body: &str) -> Result<String> {
+    let mut response = agent()
+        .post(url)
+        .header("Content-Type", "application/json")
+        .send(body)
+        .with_context(|| format!("failed to reach the gallery at {url}"))?;
+    response
+        .body_mut()
+        .read_to_string()
+        .context("failed to read the gallery's answer")
+}
+
+#[cfg(not(feature = "gallery"))]
+fn get(_url: &str) -> Result<String> {
+    bail!("this build has no preset gallery; build it with --features gallery")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_listing_puts_the_most_downloaded_first() {
+        let entries = parse_list(
+            r#"[
+                {"id": "a1", "name": "desk fan", "downloads": 3},
+                {"id": "b2", "name": "deep sleep", "description": "brown, low and slow", "downloads": 120, "author": "ignored"},
+                {"id": "../etc", "name": "sneaky", "downloads": 900},
+                {"id": "c3", "name": "rain", "downloads": 1}
+            ]"#,
+        )
+        .unwrap();
+        let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, ["deep sleep", "desk fan", "rain"]);
+        assert_eq!(
+            entries[0].line(),
+            "deep sleep: brown, low and slow (120 downloads)"
+        );
+        assert_eq!(entries[2].line(), "rain (1 download)");
+        assert!(parse_list("{}").is_err());
+    }
+
+    #[test]
+    fn only_https_addresses_and_plain_ids_are_asked_for() {
+        assert_eq!(
+            checked(" https://presets.example.org/api/ ").unwrap(),
+            "https://presets.example.org/api"
+        );
+        assert!(checked("http://presets.example.org").is_err());
+        assert!(checked("https://").is_err());
+        assert!(check_id("deep-sleep_2").is_ok());
+        assert!(check_id("a/b").is_err());
+        assert!(check_id("").is_err());
+        assert!(fetch("ftp://example.org", "a1").is_err());
+    }
+}
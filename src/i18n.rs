@@ -0,0 +1,212 @@
+//! Interface strings by key, with English built in and translations picked
+//! from the locale environment. Translations are TOML files shaped like
+//! `locales/en.toml`: bundled ones are compiled in, and a file under the
+//! config directory's `locales/` folder takes precedence so a translator can
+//! try changes without rebuilding.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+
+use crate::settings::config_path;
+
+const ENGLISH: &str = include_str!("../locales/en.toml");
+/// Translations compiled into the binary, by language tag. A new
+/// translation adds its file to `locales/` and a line here.
+const BUNDLED: &[(&str, &str)] = &[("en", ENGLISH)];
+
+static ACTIVE: OnceLock<Catalog> = OnceLock::new();
+
+/// Dotted keys (`mixer.width`) to message text.
+#[derive(Debug, Default)]
+struct Catalog {
+    messages: HashMap<String, String>,
+}
+
+impl Catalog {
+    fn parse(source: &str) -> Result<Self> {
+        let table: toml::Table = toml::from_str(source)?;
+        let mut catalog = Self::default();
+        catalog.flatten("", &table);
+        Ok(catalog)
+    }
+
+    fn flatten(&mut self, prefix: &str, table: &toml::Table) {
+        for (name, value) in table {
+            let key = format!("{prefix}{name}");
+            match value {
+                toml::Value::String(text) => {
+                    self.messages.insert(key, text.clone());
+                }
+                toml::Value::Table(nested) => self.flatten(&format!("{key}."), nested),
+                _ => {}
+            }
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.messages.get(key).map(String::as_str)
+    }
+}
+
+fn english() -> &'static Catalog {
+    static CATALOG: OnceLock<Catalog> = OnceLock::new();
+    CATALOG.get_or_init(|| Catalog::parse(ENGLISH).expect("locales/en.toml is valid TOML"))
+}
+
+/// Loads the translation for the first of LC_ALL, LC_MESSAGES, and LANG
+/// that is set. English, `C`, or an unknown language leaves the built-in
+/// strings in place. Only the first call has any effect.
+pub fn init() -> Result<()> {
+    let Some(locale) = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .find_map(|name| env::var(name).ok().filter(|value| !value.is_empty()))
+    else {
+        return Ok(());
+    };
+    let user_dir = config_path().with_file_name("locales");
+    if let Some(catalog) = find_translation(&locale, &user_dir)? {
+        let _ = ACTIVE.set(catalog);
+    }
+    Ok(())
+}
+
+/// `pt_BR.UTF-8@euro` tries `pt_BR`, then `pt`; each in `user_dir`, then
+/// among the bundled translations.
+fn find_translation(locale: &str, user_dir: &Path) -> Result<Option<Catalog>> {
+    for tag in language_tags(locale) {
+        let path = user_dir.join(format!("{tag}.toml"));
+        match fs::read_to_string(&path) {
+            Ok(source) => {
+                return Catalog::parse(&source)
+                    .with_context(|| format!("failed to parse {}", path.display()))
+                    .map(Some);
+            }
+            Err(error) if error.kind() == ErrorKind::NotFound => {}
+            Err(error) => {
+                return Err(error).with_context(|| format!("failed to read {}", path.display()));
+            }
+        }
+        if tag == "en" {
+            return Ok(None);
+        }
+        if let Some((_, source)) = BUNDLED.iter().find(|(bundled, _)| *bundled == tag) {
+            return Catalog::parse(source).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// The most specific tag first. `C` and `POSIX` name no language.
+fn language_tags(locale: &str) -> Vec<String> {
+    let tag = locale
+        .split(['.', '@'])
+        .next()
+        .unwrap_or_default()
+        .replace('-', "_");
+    if tag.is_empty() || tag == "C" || tag == "POSIX" {
+        return Vec::new();
+    }
+    let mut tags = vec![tag.clone()];
+    if let Some((language, _)) = tag.split_once('_') {
+        tags.push(language.to_owned());
+    }
+    tags
+}
+
+/// The message for `key` in the active language, falling back to English.
+pub fn text(key: &'static str) -> &'static str {
+    let english = english().get(key);
+    debug_assert!(english.is_some(), "no English string for {key}");
+    ACTIVE
+        .get()
+        .and_then(|catalog| catalog.get(key))
+        .or(english)
+        .unwrap_or(key)
+}
+
+/// `text(key)` with each `{name}` replaced by its value.
+pub fn text_with(key: &'static str, values: &[(&str, &dyn Display)]) -> String {
+    fill(text(key), values)
+}
+
+fn fill(template: &str, values: &[(&str, &dyn Display)]) -> String {
+    let mut message = template.to_owned();
+    for (name, value) in values {
+        message = message.replace(&format!("{{{name}}}"), &value.to_string());
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn scratch_dir(label: &str) -> PathBuf {
+        let mut path = env::temp_dir();
+        path.push(format!(
+            "whitenoise-i18n-test-{}-{label}",
+            std::process::id()
+        ));
+        path
+    }
+
+    #[test]
+    fn locale_names_reduce_to_language_tags() {
+        assert_eq!(language_tags("pt_BR.UTF-8@euro"), ["pt_BR", "pt"]);
+        assert_eq!(language_tags("de-AT"), ["de_AT", "de"]);
+        assert_eq!(language_tags("fr"), ["fr"]);
+        assert!(language_tags("C.UTF-8").is_empty());
+        assert!(language_tags("POSIX").is_empty());
+    }
+
+    #[test]
+    fn placeholders_are_filled_and_english_is_the_fallback() {
+        assert_eq!(text("page.mixer"), "Mixer");
+        assert_eq!(
+            text_with("presets.position", &[("index", &3), ("count", &12)]),
+            "3 of 12"
+        );
+        // An unknown placeholder name is left for the reader to spot.
+        assert_eq!(fill("{a} and {b}", &[("a", &"x")]), "x and {b}");
+    }
+
+    #[test]
+    fn user_translations_override_by_most_specific_tag() {
+        let dir = scratch_dir("user");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("de.toml"), "[page]\nmixer = \"Mischpult\"\n").unwrap();
+
+        let catalog = find_translation("de_CH.UTF-8", &dir).unwrap().unwrap();
+        assert_eq!(catalog.get("page.mixer"), Some("Mischpult"));
+        // Keys a translation leaves out fall back to English in text().
+        assert_eq!(catalog.get("page.help"), None);
+
+        assert!(find_translation("en_GB.UTF-8", &dir).unwrap().is_none());
+        assert!(find_translation("xx_YY", &dir).unwrap().is_none());
+
+        fs::write(dir.join("fr.toml"), "[page\n").unwrap();
+        let error = format!("{:#}", find_translation("fr_FR", &dir).unwrap_err());
+        assert!(error.contains("fr.toml"), "{error}");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn every_bundled_translation_parses_with_known_keys() {
+        for (tag, source) in BUNDLED {
+            let catalog = Catalog::parse(source).unwrap();
+            for key in catalog.messages.keys() {
+                assert!(english().get(key).is_some(), "{tag}: unknown key {key}");
+            }
+        }
+    }
+}
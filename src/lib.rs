@@ -0,0 +1,46 @@
+//! The sound engine behind the `whitenoise` command, for programs that
+//! want to play it themselves.
+//!
+//! [`audio::Generator`] renders the whole sound, from the noise colors and
+//! the rain loop through the EQ, stereo width, and limiter, into buffers of
+//! interleaved samples, and takes new [`settings::AudioSettings`] at any
+//! time. The filters and noise generators it is built from are in [`dsp`],
+//! the band layout and the settings in [`settings`], and named presets and
+//! scenes in [`preset`] and [`scene`].
+//!
+//! ```
+//! use whitenoise::audio::{Generator, StreamOptions};
+//! use whitenoise::settings::{AudioSettings, SoundStyle, SourceMix};
+//!
+//! let mut settings = AudioSettings::default();
+//! settings.set_mix(SourceMix::solo(SoundStyle::Brown));
+//! let mut generator = Generator::new(48_000.0, 2, settings, StreamOptions::default())?;
+//! let mut buffer = [0.0_f32; 1024];
+//! generator.fill_buffer(&mut buffer);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+#![forbid(unsafe_code)]
+
+pub mod ambient;
+pub mod audio;
+pub mod clock;
+pub mod contour;
+pub mod describe;
+pub mod dsp;
+pub mod duck;
+pub mod exposure;
+pub mod i18n;
+pub mod mirror;
+pub mod night;
+pub mod preset;
+pub mod record;
+pub mod scene;
+pub mod schedule;
+pub mod settings;
+pub mod share;
+pub mod sink;
+pub mod soundscape;
+pub mod sun;
+pub mod timer;
+pub mod watchdog;
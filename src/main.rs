@@ -1,28 +1,91 @@
 #![forbid(unsafe_code)]
 
-mod audio;
+mod bench;
+mod calibrate;
+mod command;
+mod compare;
+mod control;
 mod device;
-mod settings;
+mod exit;
+mod gallery;
+#[cfg(target_os = "linux")]
+mod mpris;
+mod mqtt;
+mod notify;
+mod output;
+mod qr;
+mod render;
+mod sample_cache;
+mod service;
+mod terminal;
+mod trainer;
 mod ui;
+#[cfg(feature = "update")]
+mod update;
+#[cfg(feature = "web")]
+mod web;
 
 use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 
-use anyhow::{Context, Result, bail};
-use clap::Parser;
-use cpal::traits::{DeviceTrait, StreamTrait};
+use anyhow::{Context, Result, bail, ensure};
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+use cpal::BufferSize;
 
-use crate::audio::build_output_stream;
-use crate::device::{list_audio_devices, list_hosts, select_host, select_output_device};
-use crate::settings::{AudioSettings, SoundStyle, SourceMix, load_settings, save_settings};
-use crate::ui::InteractiveUi;
+// The sound engine and its settings are the library; the modules declared
+// above are the command's own.
+use whitenoise::{
+    ambient, audio, clock, contour, describe, dsp, duck, exposure, i18n, mirror, night, preset,
+    record, scene, schedule, settings, share, sink, sun, timer, watchdog,
+};
+
+use crate::ambient::AmbientLevel;
+use crate::audio::{
+    AudioMonitor, DEFAULT_BLOCK_SIZE, StreamOptions, UserSample, loop_crossfade_limits,
+};
+use crate::command::{CommandHistory, load_command_history, save_command_history};
+use crate::device::{
+    display_name, list_audio_devices, list_hosts, select_host, select_input_device,
+    select_output_config, select_output_device,
+};
+use crate::duck::{Cue, DuckMode, parse_duck};
+use crate::exit::Failure;
+use crate::exposure::{Exposure, ExposureLog, exposure_path, load_exposure};
+use crate::night::ClockTime;
+use crate::output::{OutputSetup, VirtualSetup};
+use crate::preset::{
+    apply_preset, delete_preset, list_presets, load_preset, load_preset_with_warnings, presets_dir,
+    save_preset,
+};
+use crate::record::Recording;
+use crate::scene::{apply_scene, load_scene, scenes_dir};
+use crate::schedule::{ScheduleEntry, ScheduleTime};
+use crate::settings::{
+    AudioSettings, BandFilters, DEFAULT_STEREO_WIDTH, HIGH_PASS_SLOPES_DB, LoopSettings,
+    MAX_CEILING_HZ, MAX_HIGH_PASS_HZ, MAX_NOTCH_HZ, MAX_NOTCH_OCTAVES, MIN_CEILING_HZ,
+    MIN_HIGH_PASS_HZ, MIN_NOTCH_HZ, MIN_NOTCH_OCTAVES, NoiseRng, OutputProfile,
+    SHUFFLE_MAX_SECONDS, SMALL_SPEAKER_HIGH_PASS_HZ, SettingsFile, SharedSettings, SoundStyle,
+    SourceMix, ToneMode, bands, instance_config_path, load_settings, save_settings,
+    set_band_filters, set_band_layout, set_config_path, set_noise_rng,
+};
+use crate::share::ShareCode;
+use crate::sink::SinkTarget;
+use crate::sun::SunTimes;
+use crate::timer::{SleepTimer, TimerAlarm, format_remaining, parse_duration};
+use crate::ui::{InteractiveUi, alarm_message, run_status_line};
 
 #[derive(Debug, Parser)]
 #[command(name = "whitenoise", version)]
-#[command(about = "Interactive white/pink/brown noise and rain ambience generator")]
+#[command(about = "Interactive colored noise and rain ambience generator")]
+#[command(group = ArgGroup::new("listing").args(["list_hosts", "list_devices"]).multiple(true))]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// List audio backends compiled into this build
     #[arg(long)]
     list_hosts: bool,
@@ -31,30 +94,1010 @@ struct Args {
     #[arg(short, long)]
     list_devices: bool,
 
+    /// Print --list-hosts or --list-devices as one line of JSON; devices
+    /// then also give their channel counts and sample rates
+    #[arg(long, requires = "listing")]
+    json: bool,
+
     /// Audio backend to use (for example: alsa or pulseaudio)
     #[arg(long, value_name = "HOST")]
     host: Option<String>,
 
-    /// Output device name (an unambiguous substring is accepted)
+    /// Output device name (an unambiguous substring is accepted); give it
+    /// again to play the same sound on more devices at once
     #[arg(short, long, value_name = "DEVICE")]
-    device: Option<String>,
+    device: Vec<String>,
 
     /// Run without the terminal interface, using saved settings
     #[arg(long)]
     non_interactive: bool,
 
+    /// Show only a large volume bar and accept only volume and quit keys
+    #[arg(long, conflicts_with = "non_interactive")]
+    exam_mode: bool,
+
+    /// Play --scene on a view-only screen that ignores every key but
+    /// Ctrl+Q, for shared spaces
+    #[arg(long, requires = "scene", conflicts_with_all = ["non_interactive", "exam_mode"])]
+    kiosk: bool,
+
     /// Initial master volume as a percentage from 0 to 100
     #[arg(short, long, value_name = "PERCENT", value_parser = parse_percentage)]
     volume: Option<f32>,
 
-    /// Initial sound source
-    #[arg(short, long, value_enum, conflicts_with = "mix")]
-    style: Option<SoundStyle>,
+    /// Start from a saved preset; --volume, --style, and --mix override it
+    #[arg(short, long, value_name = "NAME")]
+    preset: Option<String>,
+
+    /// Start from a scene, a file in the scenes folder with layers, their
+    /// filters and swells, and a timer; --volume, --style, and --mix
+    /// override it
+    #[arg(long, value_name = "NAME", conflicts_with = "preset")]
+    scene: Option<String>,
+
+    /// Start from a share code, as the `:share` command shows one (example:
+    /// --share-code 04S0-00B4-01RG); --volume, --style, and --mix override it
+    #[arg(
+        long,
+        value_name = "CODE",
+        value_parser = parse_share_code,
+        conflicts_with_all = ["preset", "scene"]
+    )]
+    share_code: Option<ShareCode>,
+
+    /// Initial sound: a source, the sample loop by its file name, or a
+    /// saved preset's mix of sources
+    #[arg(short, long, value_name = "NAME", conflicts_with = "mix")]
+    style: Option<String>,
 
     /// Play several sources at once, as SOURCE=PERCENT pairs
     /// (example: --mix rain=60,brown=40)
     #[arg(short, long, value_name = "MIX", value_parser = parse_mix)]
     mix: Option<SourceMix>,
+
+    /// Stereo width: mono, normal, wide, or a percentage (0 is mono)
+    #[arg(short, long, value_name = "WIDTH", value_parser = parse_width)]
+    width: Option<f32>,
+
+    /// Low-pass ceiling in Hz (1000 to 20000, examples: 8000, 8k), or off
+    // The inner `Option` is spelled out so clap parses `off` through
+    // `parse_ceiling` instead of treating it as an optional value.
+    #[arg(long, value_name = "HZ", value_parser = parse_ceiling)]
+    ceiling: Option<std::option::Option<f32>>,
+
+    /// Notch center in Hz (250 to 16000, examples: 6500, 6.5k), or off;
+    /// cuts a notch out of the noise at your tinnitus pitch
+    // Spelled out for the same reason as `ceiling`.
+    #[arg(long, value_name = "HZ", value_parser = parse_notch)]
+    notch: Option<std::option::Option<f32>>,
+
+    /// Notch width in octaves between its -3 dB points (0.1 to 2)
+    #[arg(long, value_name = "OCTAVES", value_parser = parse_notch_width)]
+    notch_width: Option<f32>,
+
+    /// High-pass corner in Hz (20 to 125, example: 40), or off; protects
+    /// small speakers from sub-bass
+    // Spelled out for the same reason as `ceiling`.
+    #[arg(long, value_name = "HZ", value_parser = parse_high_pass)]
+    high_pass: Option<std::option::Option<f32>>,
+
+    /// High-pass slope in dB per octave: 12 or 24
+    #[arg(long, value_name = "DB", value_parser = parse_slope)]
+    high_pass_slope: Option<u32>,
+
+    /// How the EQ bands split the sound: a crossover that keeps each band
+    /// to its own range, or lighter peaking filters that overlap
+    #[arg(long, value_enum, value_name = "FILTERS")]
+    band_filters: Option<BandFilters>,
+
+    /// Which generator draws the noise: small, xoshiro for 32-bit boards,
+    /// or chacha seeded from the system's entropy
+    #[arg(long, value_enum, value_name = "RNG")]
+    noise_rng: Option<NoiseRng>,
+
+    /// Output profile: sets the high-pass, crossfeed, width, and listening
+    /// contour together; the individual options still override it
+    #[arg(long, value_enum, value_name = "NAME")]
+    profile: Option<OutputProfile>,
+
+    /// Tone layer under the noise, using the saved carrier, beat, and level
+    #[arg(long, value_enum, value_name = "MODE")]
+    tone: Option<ToneMode>,
+
+    /// Quiet pink room tone that plays on through pauses and source
+    /// changes, as a percentage (100 is 20 dB under a full source), or off
+    #[arg(long, value_name = "PERCENT", value_parser = parse_room_tone)]
+    room_tone: Option<f32>,
+
+    /// WAV file to loop as the Sample source (see --mix sample=PERCENT)
+    #[arg(long, value_name = "PATH")]
+    sample: Option<PathBuf>,
+
+    /// Level the sample by its ReplayGain track gain tag, where it has one,
+    /// instead of measuring it
+    #[arg(long)]
+    sample_replay_gain: bool,
+
+    /// Stop playback after this long (examples: 45m, 1h30m, 90s)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    timer: Option<Duration>,
+
+    /// Fade up from silence over this long at start (examples: 10s, 2m)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    fade_in: Option<Duration>,
+
+    /// How long the sleep timer fades the volume out before stopping
+    #[arg(long, value_name = "DURATION", default_value = "60s", value_parser = parse_duration)]
+    fade_out: Duration,
+
+    /// What happens when the timer runs out
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = TimerAlarm::Fade)]
+    alarm: TimerAlarm,
+
+    /// Frames the engine processes per internal block, independent of the
+    /// device's buffer size
+    #[arg(long, value_name = "FRAMES", default_value_t = DEFAULT_BLOCK_SIZE, value_parser = parse_block_size)]
+    block_size: usize,
+
+    /// Seed every noise generator, so a run or a render with the same seed
+    /// and settings plays the same sound
+    #[arg(long, value_name = "N")]
+    seed: Option<u64>,
+
+    /// Sample rate to ask the device for, in Hz; the closest supported rate
+    /// is used
+    #[arg(long, value_name = "HZ", value_parser = clap::value_parser!(u32).range(8_000..=384_000))]
+    sample_rate: Option<u32>,
+
+    /// Device buffer size in frames, for lower latency or fewer underruns;
+    /// clamped to what the device supports
+    #[arg(long, value_name = "FRAMES", value_parser = clap::value_parser!(u32).range(16..=16_384))]
+    buffer_size: Option<u32>,
+
+    /// Duck the sound while another program plays through PulseAudio or
+    /// PipeWire, and bring it back when that stops (needs pactl)
+    #[arg(long)]
+    duck_on_playback: bool,
+
+    /// How far --duck-on-playback lowers the sound, in dB (1 to 60), or
+    /// pause to pause it instead
+    #[arg(long, value_name = "DB", default_value = "15", value_parser = parse_duck, requires = "duck_on_playback")]
+    duck_db: DuckMode,
+
+    /// Duck the sound while a voice call is on, as PulseAudio or PipeWire
+    /// tags it, apart from --duck-on-playback (needs pactl)
+    #[arg(long)]
+    duck_on_calls: bool,
+
+    /// How far --duck-on-calls lowers the sound, in dB (1 to 60), or pause
+    #[arg(long, value_name = "DB", default_value = "20", value_parser = parse_duck, requires = "duck_on_calls")]
+    call_duck_db: DuckMode,
+
+    /// Listen to the room through a microphone and raise or lower the sound
+    /// to stay above its noise, as the `[ambient]` table sets out
+    #[arg(long)]
+    ambient_level: bool,
+
+    /// Microphone for --ambient-level (an unambiguous substring is
+    /// accepted); the `[ambient]` table's input or the default otherwise
+    #[arg(long, value_name = "DEVICE", requires = "ambient_level")]
+    ambient_input: Option<String>,
+
+    /// Replace the sound engine, and say so, when the output stays silent
+    /// for 20 seconds while the settings say it should play
+    #[arg(long)]
+    silence_watchdog: bool,
+
+    /// Settings file to read and save instead of the default; the timer
+    /// file, presets, scenes, and command history sit beside it
+    #[arg(long, value_name = "PATH", conflicts_with = "instance")]
+    config: Option<PathBuf>,
+
+    /// Play as a separate named instance, with its own settings folder
+    /// (instances/NAME in the config directory), control socket, and service
+    #[arg(long, value_name = "NAME", value_parser = mqtt::parse_name)]
+    instance: Option<String>,
+
+    /// Play as the instance of this login session, seat-NAME on a seat or
+    /// session-ID without one, so each seat or session of a shared machine
+    /// keeps its own settings, socket, and service
+    #[arg(long, conflicts_with_all = ["instance", "config"])]
+    per_session: bool,
+
+    /// Control socket for non-interactive playback, top, and ctl
+    /// (default: $XDG_RUNTIME_DIR/whitenoise.sock, whitenoise-NAME.sock for
+    /// an --instance, or control.sock beside a --config file)
+    #[arg(long, value_name = "PATH")]
+    socket: Option<PathBuf>,
+
+    /// Publish state to an MQTT broker and take commands from it, with Home
+    /// Assistant discovery ([USER:PASSWORD@]HOST[:PORT], default port 1883)
+    #[arg(long, value_name = "BROKER", value_parser = mqtt::parse_broker, conflicts_with = "exam_mode")]
+    mqtt: Option<mqtt::Broker>,
+
+    /// Name for this player's MQTT topics and Home Assistant entity
+    #[arg(long, value_name = "NAME", default_value = "whitenoise", value_parser = mqtt::parse_name, requires = "mqtt")]
+    mqtt_name: String,
+
+    /// Serve a remote-control web page and JSON API on this port, for this
+    /// machine only, or on an address such as 127.0.0.1:8080
+    #[cfg(feature = "web")]
+    #[arg(long, value_name = "PORT|ADDRESS", conflicts_with = "exam_mode")]
+    listen: Option<web::Listen>,
+
+    /// Let other machines reach the web remote: a bare --listen port is
+    /// served on every interface, and an address beyond this machine is
+    /// allowed
+    #[cfg(feature = "web")]
+    #[arg(long, requires = "listen")]
+    listen_lan: bool,
+
+    /// The token the web remote's changes must carry, instead of a new one
+    /// each run; it is part of the address the player prints
+    #[cfg(feature = "web")]
+    #[arg(long, value_name = "TOKEN", value_parser = web::parse_token, requires = "listen")]
+    listen_token: Option<String>,
+
+    /// Play at a sound card's pace into TARGET instead of a device: a .wav
+    /// path, - for 16-bit PCM on stdout, tcp://HOST:PORT for the same over
+    /// the network, node or node:NAME for a virtual sink of its own in
+    /// PipeWire or PulseAudio, or null
+    #[arg(long, value_name = "TARGET", conflicts_with = "device")]
+    output_to: Option<SinkTarget>,
+
+    /// Record what plays into a 32-bit float WAV file at PATH until the
+    /// player stops; Ctrl+R stops and starts recording in the interface
+    #[arg(long, value_name = "PATH")]
+    record: Option<PathBuf>,
+
+    /// Play into a virtual device that writes a 32-bit float WAV file at
+    /// the pace of a sound card, instead of into one; for tests of the
+    /// whole program
+    #[arg(
+        long,
+        value_name = "PATH",
+        hide = true,
+        conflicts_with_all = ["device", "output_to"]
+    )]
+    virtual_output: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Render audio offline and report realtime ratio and per-stage CPU cost
+    Bench {
+        /// Seconds of audio to render for each source and stage
+        #[arg(long, value_name = "SECONDS", default_value_t = 10.0, value_parser = parse_seconds)]
+        seconds: f32,
+
+        /// Sample rate to render at, in Hz
+        #[arg(long, value_name = "HZ", default_value_t = 48_000, value_parser = clap::value_parser!(u32).range(8_000..=384_000))]
+        sample_rate: u32,
+    },
+    /// Play without the full-screen UI, showing a single self-updating status line
+    Top,
+    /// Write the current sound to a seamlessly looping WAV file instead of playing it
+    Render {
+        /// Length of the file (examples: 10m, 1h, 90s)
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        duration: Duration,
+
+        /// WAV file to create
+        #[arg(long, value_name = "PATH", required_unless_present = "all_presets")]
+        output: Option<PathBuf>,
+
+        /// Render every saved preset to its own file in --out-dir, each
+        /// normalized to the same loudness
+        #[arg(long, conflicts_with = "output", requires = "out_dir")]
+        all_presets: bool,
+
+        /// Directory for --all-presets, created if missing; files are named
+        /// after the presets
+        #[arg(long, value_name = "DIR", requires = "all_presets")]
+        out_dir: Option<PathBuf>,
+
+        /// Sample rate to render at, in Hz
+        #[arg(long, value_name = "HZ", default_value_t = 48_000, value_parser = clap::value_parser!(u32).range(8_000..=384_000))]
+        sample_rate: u32,
+
+        /// Add a cue marker labeled with the elapsed time at the start and
+        /// every interval after it (examples: 15m, 1h)
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        chapters: Option<Duration>,
+    },
+    /// Render two presets from the same seed and print each EQ band's level
+    /// in both and the difference (example: whitenoise compare focus sleep)
+    Compare {
+        /// The preset to measure from
+        a: String,
+
+        /// The preset to measure against it
+        b: String,
+
+        /// Seconds of each to render and measure
+        #[arg(long, value_name = "SECONDS", default_value_t = 10.0, value_parser = parse_seconds)]
+        seconds: f32,
+
+        /// Sample rate to render at, in Hz
+        #[arg(long, value_name = "HZ", default_value_t = 48_000, value_parser = clap::value_parser!(u32).range(8_000..=384_000))]
+        sample_rate: u32,
+    },
+    /// Measure the room through a microphone and offer an EQ correction for
+    /// the output device
+    Calibrate {
+        /// Input device that hears the speakers (an unambiguous substring is
+        /// accepted); defaults to the system's default input
+        #[arg(long, value_name = "NAME")]
+        input: Option<String>,
+
+        /// Save the correction without asking
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Send a command to a player started with --non-interactive, top, or
+    /// daemon (examples: volume 40, style rain, set_volume 0.4, status, quit)
+    Ctl {
+        /// Any command the `:` prompt takes, set_volume, or status
+        cmd: String,
+
+        /// The command's arguments
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        value: Vec<String>,
+    },
+    /// Show what a player started with --non-interactive, top, or daemon is
+    /// playing
+    Status {
+        /// Print the state as one line of JSON: volume, mix, bands, timer,
+        /// and share code
+        #[arg(long)]
+        json: bool,
+    },
+    /// Describe the sound in a sentence, for support or sharing: the
+    /// running player's, or else what the next run would play
+    Describe,
+    /// Play headless under a service manager: log to the journal or syslog,
+    /// keep a pidfile beside the control socket, stop on SIGTERM, and read
+    /// settings.toml again on SIGHUP
+    Daemon {
+        /// Pidfile to write (default: the control socket's path with a .pid
+        /// extension)
+        #[arg(long, value_name = "PATH")]
+        pidfile: Option<PathBuf>,
+
+        /// Write a user systemd unit (Linux) or launchd agent (macOS) that
+        /// runs this command line without this option, then exit
+        #[arg(long)]
+        install_service: bool,
+    },
+    /// List, save, delete, export, or import named presets without playing
+    /// anything
+    #[command(alias = "preset")]
+    Presets {
+        #[command(subcommand)]
+        action: PresetsCommand,
+    },
+    /// List the scenes in the scenes folder with their layers and timer
+    Scenes,
+    /// Show the [[schedule]] entries from settings.toml and which is in force
+    Schedule,
+    /// Ask the release feed whether a newer whitenoise is out
+    #[cfg(feature = "update")]
+    Update {
+        /// Report the latest release against this one; nothing is installed
+        #[arg(long, required_unless_present = "download")]
+        check: bool,
+
+        /// Also download this platform's build of a newer release into DIR,
+        /// created if missing
+        #[arg(long, value_name = "DIR")]
+        download: Option<PathBuf>,
+
+        /// Release feed to ask, in GitHub's latest-release format
+        #[arg(long, value_name = "URL", default_value = update::RELEASE_FEED)]
+        feed: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum PresetsCommand {
+    /// List saved presets with their source and volume
+    List,
+    /// Save the saved settings, with any --preset, --volume, --style, or
+    /// --mix applied, as a preset
+    Save {
+        /// Letters, digits, spaces, '-' and '_' (example: focus)
+        name: String,
+    },
+    /// Delete a preset
+    Delete { name: String },
+    /// Print a preset as a file to share, with the band layout its sliders
+    /// were set against (example: whitenoise presets export focus > focus.wnp)
+    Export { name: String },
+    /// Save a shared preset file, mapping its sliders onto this band layout
+    Import {
+        /// File written by export, or - to read standard input
+        file: PathBuf,
+
+        /// Save it under this name instead of the one in the file
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Replace a preset of the same name
+        #[arg(long)]
+        force: bool,
+    },
+    /// List the presets in the gallery settings.toml names, most
+    /// downloaded first
+    #[cfg(feature = "gallery")]
+    Gallery,
+    /// Download a preset from the gallery by its id and save it
+    #[cfg(feature = "gallery")]
+    Fetch {
+        id: String,
+
+        /// Save it under this name instead of the gallery's
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Replace a preset of the same name
+        #[arg(long)]
+        force: bool,
+    },
+    /// Send a saved preset to the gallery, with nothing but its name and a
+    /// description
+    #[cfg(feature = "gallery")]
+    Submit {
+        name: String,
+
+        /// One line on what the preset is for
+        #[arg(long, default_value = "")]
+        description: String,
+    },
+}
+
+fn print_import(import: &preset::Import) {
+    for warning in &import.warnings {
+        eprintln!("warning: {warning}");
+    }
+    if import.remapped {
+        println!("The file's sliders were mapped onto this player's bands.");
+    }
+    println!(
+        "Imported preset {}: {}",
+        import.name,
+        preset::summary(&import.preset)
+    );
+}
+
+/// The `scenes` subcommand: each scene's layers and timer, with its
+/// warnings, or why it cannot be read.
+fn print_scenes(dir: &Path) -> Result<()> {
+    let rows = scene::scene_rows(dir)?;
+    if rows.is_empty() {
+        println!(
+            "{}",
+            i18n::text_with("scenes.none", &[("dir", &dir.display())])
+        );
+        println!(
+            "{}\n\n{}",
+            i18n::text("scenes.example"),
+            scene::EXAMPLE_SCENE
+        );
+        return Ok(());
+    }
+    let width = rows
+        .iter()
+        .map(|row| row.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    for row in rows {
+        let summary = row
+            .summary
+            .unwrap_or_else(|error| i18n::text_with("scenes.unreadable", &[("error", &error)]));
+        println!("{:<width$}  {summary}", row.name);
+        for warning in &row.warnings {
+            let warning = i18n::text_with("scenes.warning", &[("warning", warning)]);
+            println!("{:width$}  {warning}", "");
+        }
+    }
+    Ok(())
+}
+
+/// The `schedule` subcommand. Entries at sunrise or sunset show today's
+/// time beside them.
+fn print_schedule(entries: &[ScheduleEntry], sun: &SunTimes, presets: &Path, now: ClockTime) {
+    if entries.is_empty() {
+        println!(
+            "{}\n\n{}",
+            i18n::text("schedule.none"),
+            schedule::EXAMPLE_SCHEDULE
+        );
+        return;
+    }
+    for row in schedule::schedule_rows(entries, sun, presets, now) {
+        let at = match (row.at, row.time) {
+            (ScheduleTime::Clock(time), _) => time.to_string(),
+            (at, Some(time)) => format!("{time} ({at})"),
+            (at, None) => format!(
+                "--:-- ({})",
+                i18n::text_with("schedule.not_today", &[("at", &at)])
+            ),
+        };
+        let mut line = format!("{at}  {}", row.summary);
+        if row.in_force {
+            line.push_str("  ");
+            line.push_str(i18n::text("schedule.in_force"));
+        }
+        if let Some(problem) = row.problem {
+            line.push_str("  ");
+            line.push_str(&i18n::text_with("schedule.warning", &[("error", &problem)]));
+        }
+        println!("{line}");
+    }
+}
+
+/// Also puts the file's `[[bands]]` layout and the band filters in force,
+/// before anything reads the bands.
+fn load_settings_file(args: &Args) -> SettingsFile {
+    load_settings_file_noting(args).0
+}
+
+/// As `load_settings_file`, also returning the warnings it printed so the
+/// interface can show them once the screen is its own.
+fn load_settings_file_noting(args: &Args) -> (SettingsFile, Vec<String>) {
+    let (settings_file, notes) = match load_settings() {
+        Ok((settings_file, report)) => (settings_file, report.warnings()),
+        Err(error) => (
+            SettingsFile::default(),
+            vec![format!("{error:#}; using default settings")],
+        ),
+    };
+    for note in &notes {
+        eprintln!("warning: {note}");
+    }
+    if let Err(error) = set_band_layout(&settings_file.bands) {
+        eprintln!("warning: {error:#}; using the built-in bands");
+    }
+    set_band_filters(
+        args.band_filters
+            .or(settings_file.output.band_filters)
+            .unwrap_or_default(),
+    );
+    set_noise_rng(
+        args.noise_rng
+            .or(settings_file.output.noise_rng)
+            .unwrap_or_default(),
+    );
+    (settings_file, notes)
+}
+
+/// The --device names, or the file's `output_devices` when none is given:
+/// the first is the output, the rest mirror it. Empty means the default.
+fn output_devices(args: &Args, settings_file: &SettingsFile) -> Vec<String> {
+    if args.device.is_empty() {
+        settings_file.output_devices.clone()
+    } else {
+        args.device.clone()
+    }
+}
+
+/// A --sample path applies to this run only; the saved `sample` key stays.
+/// The loop's loudness is measured here, read from the sample cache, or
+/// with --sample-replay-gain taken from its tag, so the note names the gain
+/// the players will apply.
+fn read_sample(args: &Args, settings_file: &SettingsFile) -> Result<Option<UserSample>> {
+    let Some(path) = sample_path(args, settings_file) else {
+        return Ok(None);
+    };
+    let data = std::fs::read(path)
+        .with_context(|| format!("failed to read the sample loop {}", path.display()))?;
+    let honor_tags = args.sample_replay_gain || settings_file.sample_replay_gain;
+    let sample = sample_cache::load(data, &sample_cache::cache_dir(), honor_tags)?;
+    let level = sample.level;
+    if level.tagged {
+        eprintln!(
+            "note: the sample's ReplayGain tag of {:+.1} dB puts it at {:.1} LUFS, so it plays at {:+.1} dB to match the built-in sources",
+            level.replay_gain_db(),
+            level.loudness_lufs,
+            level.gain_db
+        );
+    } else {
+        eprintln!(
+            "note: the sample measures {:.1} LUFS (ReplayGain {:+.1} dB) and plays at {:+.1} dB to match the built-in sources",
+            level.loudness_lufs,
+            level.replay_gain_db(),
+            level.gain_db
+        );
+    }
+    Ok(Some(sample))
+}
+
+fn sample_path<'a>(args: &'a Args, settings_file: &'a SettingsFile) -> Option<&'a Path> {
+    args.sample.as_deref().or(settings_file.sample.as_deref())
+}
+
+/// Where the sound plays: a device of the audio host, with the format
+/// chosen for it, or the sink `--output-to` or `--virtual-output` names.
+enum Playback {
+    Device(Box<cpal::Device>, cpal::StreamConfig, cpal::SampleFormat),
+    Virtual(SinkTarget),
+}
+
+/// The saved settings, `[output]` high-pass, crossfeed, headroom, room tone, SPL
+/// calibration, and limiter, and `[listeners]` with --preset, --scene,
+/// --share-code, or the `default_style`, then
+/// --profile, --mix or --style, --width, --tone, --room-tone, --ceiling,
+/// the notch, and the high-pass options applied.
+fn starting_settings(args: &Args, settings_file: &SettingsFile) -> Result<AudioSettings> {
+    let mut settings = settings_file.audio;
+    settings.high_pass = settings_file.output.high_pass();
+    settings.crossfeed = settings_file.output.crossfeed;
+    settings.headroom = settings_file.output.headroom;
+    settings.room_tone = settings_file.output.room_tone();
+    settings.spl = settings_file.output.spl();
+    settings.limiter = settings_file.output.limiter();
+    settings.listeners = settings_file.listeners;
+    settings.loops = settings_file.loops;
+    let mix = chosen_mix(args, settings_file)?;
+    if let Some(name) = &args.preset {
+        let (preset, warnings) = load_preset_with_warnings(&presets_dir(), name)?;
+        for warning in warnings {
+            eprintln!("warning: {warning}");
+        }
+        apply_preset(preset, &mut settings);
+    } else if let Some(name) = &args.scene {
+        let scene = load_scene(&scenes_dir(), name)?;
+        for warning in &scene.warnings {
+            eprintln!("warning: {warning}");
+        }
+        apply_scene(&scene, &mut settings, args.fade_out, clock::now());
+    } else if let Some(code) = &args.share_code {
+        code.apply(&mut settings);
+    } else if let Some(name) = settings_file
+        .default_style
+        .as_deref()
+        .filter(|_| mix.is_none())
+    {
+        // A stale name in the file should not stop playback.
+        match resolve_style(name, sample_path(args, settings_file), &presets_dir()) {
+            Ok(style) => settings.set_mix(style),
+            Err(error) => eprintln!("warning: {error:#}; default_style is ignored"),
+        }
+    }
+    apply_source_overrides(args, mix, &mut settings);
+    Ok(settings)
+}
+
+/// The mix --mix or --style asks for, if either was given.
+fn chosen_mix(args: &Args, settings_file: &SettingsFile) -> Result<Option<SourceMix>> {
+    if let Some(mix) = args.mix {
+        return Ok(Some(mix));
+    }
+    args.style
+        .as_deref()
+        .map(|name| resolve_style(name, sample_path(args, settings_file), &presets_dir()))
+        .transpose()
+}
+
+/// A style by name: a source such as `rain`, the sample loop by its file
+/// name, or a saved preset, whose mix of sources it takes and nothing else.
+/// An unknown name lists every one that would have worked.
+fn resolve_style(name: &str, sample: Option<&Path>, presets: &Path) -> Result<SourceMix> {
+    let name = name.trim();
+    if let Ok(style) = <SoundStyle as ValueEnum>::from_str(name, true) {
+        return Ok(SourceMix::solo(style));
+    }
+    let sample_name = sample
+        .and_then(Path::file_stem)
+        .and_then(|stem| stem.to_str());
+    if sample_name.is_some_and(|stem| stem.eq_ignore_ascii_case(name)) {
+        return Ok(SourceMix::solo(SoundStyle::Sample));
+    }
+    let presets_found = list_presets(presets).unwrap_or_default();
+    if let Some(preset) = presets_found
+        .iter()
+        .find(|preset| preset.eq_ignore_ascii_case(name))
+    {
+        return Ok(load_preset(presets, preset)?.mix());
+    }
+    let mut choices: Vec<String> = SoundStyle::value_variants()
+        .iter()
+        .filter_map(ValueEnum::to_possible_value)
+        .map(|value| value.get_name().to_owned())
+        .collect();
+    choices.extend(sample_name.map(|stem| format!("{stem} (the sample loop)")));
+    choices.extend(
+        presets_found
+            .iter()
+            .map(|preset| format!("{preset} (preset)")),
+    );
+    bail!(
+        "no style named '{name}'; choose from {}",
+        choices.join(", ")
+    )
+}
+
+fn apply_source_overrides(args: &Args, mix: Option<SourceMix>, settings: &mut AudioSettings) {
+    if let Some(profile) = args.profile {
+        profile.apply(settings);
+    }
+    if let Some(mix) = mix {
+        settings.set_mix(mix);
+    }
+    if let Some(width) = args.width {
+        settings.stereo_width = width;
+    }
+    if let Some(mode) = args.tone {
+        settings.tone.mode = mode;
+    }
+    if let Some(level) = args.room_tone {
+        settings.room_tone = level;
+    }
+    if let Some(ceiling) = args.ceiling {
+        settings.ceiling_hz = ceiling;
+    }
+    if let Some(notch) = args.notch {
+        settings.notch.hz = notch;
+    }
+    if let Some(octaves) = args.notch_width {
+        settings.notch.width_octaves = octaves;
+    }
+    if let Some(hz) = args.high_pass {
+        settings.high_pass.hz = hz;
+    }
+    if let Some(slope_db) = args.high_pass_slope {
+        settings.high_pass.slope_db = slope_db;
+    }
+}
+
+/// Puts back the values chosen before the device's profile and small mark
+/// applied, wherever the session ended with what the device set. Those stay
+/// with the device instead of following the user to every other output.
+fn keep_device_choices_local(
+    mut settings: AudioSettings,
+    chosen: &AudioSettings,
+    device: &AudioSettings,
+) -> AudioSettings {
+    if settings.high_pass == device.high_pass {
+        settings.high_pass = chosen.high_pass;
+    }
+    if settings.crossfeed == device.crossfeed {
+        settings.crossfeed = chosen.crossfeed;
+    }
+    if settings.stereo_width == device.stereo_width {
+        settings.stereo_width = chosen.stereo_width;
+    }
+    if settings.listening_contour == device.listening_contour {
+        settings.listening_contour = chosen.listening_contour;
+    }
+    settings
+}
+
+/// How often a playing session writes its sound to settings.toml, so a
+/// crash or power cut loses little.
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Writes the session's sound back to settings.toml, now and then while it
+/// plays and once more at the end.
+struct SessionSaver {
+    file: SettingsFile,
+    chosen: AudioSettings,
+    device: AudioSettings,
+    starting_loops: LoopSettings,
+    saved: AudioSettings,
+}
+
+impl SessionSaver {
+    /// Saves only when the sound changed since the last save or reload, so
+    /// an idle player never writes over a hand edit to settings.toml.
+    fn save_if_changed(&mut self, settings: AudioSettings) -> Result<()> {
+        if settings == self.saved {
+            return Ok(());
+        }
+        self.save(settings)
+    }
+
+    fn save(&mut self, settings: AudioSettings) -> Result<()> {
+        let merged = keep_device_choices_local(settings, &self.chosen, &self.device);
+        self.file.audio = merged;
+        self.file.output.set_high_pass(merged.high_pass);
+        self.file.output.crossfeed = merged.crossfeed;
+        self.file.output.headroom = merged.headroom;
+        self.file.output.set_room_tone(merged.room_tone);
+        self.file.output.set_spl(merged.spl);
+        self.file.output.set_limiter(merged.limiter);
+        self.file.listeners = merged.listeners;
+        // A crossfade shortened to fit this run's loop keeps its saved length.
+        let (loops, saved) = (merged.loops, &mut self.file.loops);
+        if loops.rain_crossfade_seconds != self.starting_loops.rain_crossfade_seconds {
+            saved.rain_crossfade_seconds = loops.rain_crossfade_seconds;
+        }
+        if loops.sample_crossfade_seconds != self.starting_loops.sample_crossfade_seconds {
+            saved.sample_crossfade_seconds = loops.sample_crossfade_seconds;
+        }
+        saved.rain_shuffle = loops.rain_shuffle;
+        saved.sample_shuffle = loops.sample_shuffle;
+        save_settings(&self.file)?;
+        self.saved = settings;
+        Ok(())
+    }
+
+    /// Plays what a settings.toml read again holds, keeping this run's
+    /// timer, pause, and loops, and the output device's profile and small
+    /// mark over the file's values as at startup.
+    fn reload(&mut self, file: SettingsFile, settings: &mut AudioSettings) {
+        let mut chosen = *settings;
+        apply_preset(file.audio, &mut chosen);
+        chosen.high_pass = file.output.high_pass();
+        chosen.crossfeed = file.output.crossfeed;
+        chosen.headroom = file.output.headroom;
+        chosen.room_tone = file.output.room_tone();
+        chosen.spl = file.output.spl();
+        chosen.limiter = file.output.limiter();
+        chosen.listeners = file.listeners;
+        let mut playing = chosen;
+        if self.device.high_pass != self.chosen.high_pass {
+            playing.high_pass = self.device.high_pass;
+        }
+        if self.device.crossfeed != self.chosen.crossfeed {
+            playing.crossfeed = self.device.crossfeed;
+        }
+        if self.device.stereo_width != self.chosen.stereo_width {
+            playing.stereo_width = self.device.stereo_width;
+        }
+        if self.device.listening_contour != self.chosen.listening_contour {
+            playing.listening_contour = self.device.listening_contour;
+        }
+        self.device = playing;
+        self.chosen = chosen;
+        self.file = file;
+        self.saved = playing;
+        *settings = playing;
+    }
+}
+
+/// Saves the session every `AUTOSAVE_INTERVAL` until playback ends.
+fn autosave(
+    saver: Arc<Mutex<SessionSaver>>,
+    settings: Arc<SharedSettings>,
+    running: Arc<AtomicBool>,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut last = Instant::now();
+        while running.load(Ordering::Relaxed) {
+            std::thread::sleep(Duration::from_millis(100));
+            if last.elapsed() < AUTOSAVE_INTERVAL {
+                continue;
+            }
+            last = Instant::now();
+            let current = *settings
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            // Best effort, as for the timer file: a warning here would
+            // scribble over the interface, and the save at exit reports one.
+            let _ = saver
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .save_if_changed(current);
+        }
+    })
+}
+
+/// The volume playback starts at, or `None` to keep the one already set:
+/// --volume, then a preset's, scene's, or schedule's (`chosen`), then
+/// `start_volume`. Otherwise an interactive session starts silent, which
+/// avoids headphone surprises, unless `restore_last_volume` is set.
+fn start_volume(
+    args: &Args,
+    settings_file: &SettingsFile,
+    non_interactive: bool,
+    chosen: bool,
+) -> Option<f32> {
+    if args.volume.is_some() {
+        return args.volume;
+    }
+    if chosen {
+        return None;
+    }
+    settings_file
+        .start_volume
+        .or((!non_interactive && !settings_file.restore_last_volume).then_some(0.0))
+}
+
+fn socket_path(args: &Args) -> PathBuf {
+    if let Some(socket) = &args.socket {
+        return socket.clone();
+    }
+    match (&args.instance, &args.config) {
+        (Some(name), _) => control::instance_socket_path(name),
+        (None, Some(_)) => control::settings_socket_path(),
+        (None, None) => control::default_socket_path(),
+    }
+}
+
+/// The settings file --config or --instance picks, if either does.
+fn chosen_config_path(args: &Args) -> Option<PathBuf> {
+    args.config
+        .clone()
+        .or_else(|| args.instance.as_deref().map(instance_config_path))
+}
+
+/// The Sample source is silent without a loop, so a mix that leans on it
+/// alone would play nothing.
+fn check_sample_source(settings: &AudioSettings, sample_loaded: bool) -> Result<()> {
+    let mix = settings.mix();
+    if mix.sample <= 0.0 || sample_loaded {
+        return Ok(());
+    }
+    ensure!(
+        mix.total() > mix.sample,
+        "the mix plays only the Sample source, but no sample is loaded; pass --sample PATH"
+    );
+    eprintln!(
+        "note: no sample is loaded, so the Sample source at {:.0}% is silent; pass --sample PATH",
+        mix.sample * 100.0
+    );
+    Ok(())
+}
+
+/// Shortens any loop crossfade longer than a third of its loop, with a
+/// note, and returns each loop's limit. Shuffling a sample too long for it
+/// gets a note too.
+fn fit_loop_crossfades(
+    settings: &mut AudioSettings,
+    sample: Option<&UserSample>,
+) -> Result<LoopSettings> {
+    let limits = loop_crossfade_limits(sample)?;
+    let fitted = settings.loops.within(limits);
+    for (name, wanted, kept, limit) in [
+        (
+            "rain",
+            settings.loops.rain_crossfade_seconds,
+            fitted.rain_crossfade_seconds,
+            limits.rain_crossfade_seconds,
+        ),
+        (
+            "sample",
+            settings.loops.sample_crossfade_seconds,
+            fitted.sample_crossfade_seconds,
+            limits.sample_crossfade_seconds,
+        ),
+    ] {
+        if kept < wanted {
+            eprintln!(
+                "note: the {name} loop is {:.1} s long, so its crossfade is {kept:.1} s instead of {wanted:.1} s; a crossfade can take at most a third of the loop",
+                limit * 3.0
+            );
+        }
+    }
+    let sample_seconds = limits.sample_crossfade_seconds * 3.0;
+    if settings.loops.sample_shuffle && sample.is_some() && sample_seconds >= SHUFFLE_MAX_SECONDS {
+        eprintln!(
+            "note: the sample loop is {sample_seconds:.1} s long, so it plays straight through; shuffling applies to loops under {SHUFFLE_MAX_SECONDS:.0} s"
+        );
+    }
+    settings.loops = fitted;
+    Ok(limits)
+}
+
+fn parse_seconds(value: &str) -> std::result::Result<f32, String> {
+    let seconds = value
+        .parse::<f32>()
+        .map_err(|_| "duration must be a number of seconds from 0.1 to 3600".to_owned())?;
+    if !seconds.is_finite() || !(0.1..=3600.0).contains(&seconds) {
+        return Err("duration must be a number of seconds from 0.1 to 3600".to_owned());
+    }
+    Ok(seconds)
+}
+
+fn parse_block_size(value: &str) -> std::result::Result<usize, String> {
+    let frames = value
+        .parse::<usize>()
+        .map_err(|_| "block size must be a whole number of frames from 16 to 8192".to_owned())?;
+    if !(16..=8192).contains(&frames) {
+        return Err("block size must be a whole number of frames from 16 to 8192".to_owned());
+    }
+    Ok(frames)
 }
 
 fn parse_percentage(value: &str) -> std::result::Result<f32, String> {
@@ -67,12 +1110,106 @@ fn parse_percentage(value: &str) -> std::result::Result<f32, String> {
     Ok(percent / 100.0)
 }
 
+/// A percentage, or `off` for 0.
+fn parse_room_tone(value: &str) -> std::result::Result<f32, String> {
+    if value.trim().eq_ignore_ascii_case("off") {
+        return Ok(0.0);
+    }
+    parse_percentage(value)
+        .map_err(|_| "room tone must be off or a number from 0 to 100".to_owned())
+}
+
+/// A percentage, or `mono`, `normal`, or `wide` for 0, 50, and 100.
+fn parse_width(value: &str) -> std::result::Result<f32, String> {
+    match value.trim().to_lowercase().as_str() {
+        "mono" => Ok(0.0),
+        "normal" => Ok(DEFAULT_STEREO_WIDTH),
+        "wide" => Ok(1.0),
+        _ => parse_percentage(value)
+            .map_err(|_| "width must be mono, normal, wide, or a number from 0 to 100".to_owned()),
+    }
+}
+
+fn parse_share_code(value: &str) -> std::result::Result<ShareCode, String> {
+    value.parse()
+}
+
+fn parse_ceiling(value: &str) -> std::result::Result<Option<f32>, String> {
+    parse_corner(value, "ceiling", MIN_CEILING_HZ, MAX_CEILING_HZ)
+}
+
+fn parse_notch(value: &str) -> std::result::Result<Option<f32>, String> {
+    parse_corner(value, "notch", MIN_NOTCH_HZ, MAX_NOTCH_HZ)
+}
+
+/// Octaves, with or without an `oct` suffix.
+fn parse_notch_width(value: &str) -> std::result::Result<f32, String> {
+    value
+        .trim()
+        .to_lowercase()
+        .trim_end_matches("oct")
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|octaves| (MIN_NOTCH_OCTAVES..=MAX_NOTCH_OCTAVES).contains(octaves))
+        .ok_or_else(|| {
+            format!("notch width must be from {MIN_NOTCH_OCTAVES} to {MAX_NOTCH_OCTAVES} octaves")
+        })
+}
+
+fn parse_high_pass(value: &str) -> std::result::Result<Option<f32>, String> {
+    parse_corner(value, "high-pass", MIN_HIGH_PASS_HZ, MAX_HIGH_PASS_HZ)
+}
+
+/// A frequency in Hz or kHz (`8000`, `8k`, `8kHz`), or `off`.
+fn parse_corner(
+    value: &str,
+    name: &str,
+    min: f32,
+    max: f32,
+) -> std::result::Result<Option<f32>, String> {
+    let value = value.trim().to_lowercase();
+    if value == "off" {
+        return Ok(None);
+    }
+    let invalid = || format!("{name} must be off or a frequency from {min} to {max} Hz");
+    let number = value.trim_end_matches("hz");
+    let hz = match number.strip_suffix('k') {
+        Some(khz) => khz.parse::<f32>().map(|khz| khz * 1_000.0),
+        None => number.parse::<f32>(),
+    }
+    .map_err(|_| invalid())?;
+    if !hz.is_finite() || !(min..=max).contains(&hz) {
+        return Err(invalid());
+    }
+    Ok(Some(hz))
+}
+
+/// `12` or `24`, with or without a `dB` suffix.
+fn parse_slope(value: &str) -> std::result::Result<u32, String> {
+    value
+        .trim()
+        .to_lowercase()
+        .trim_end_matches("db")
+        .parse::<u32>()
+        .ok()
+        .filter(|slope| HIGH_PASS_SLOPES_DB.contains(slope))
+        .ok_or_else(|| "slope must be 12 or 24 dB per octave".to_owned())
+}
+
 fn parse_mix(value: &str) -> std::result::Result<SourceMix, String> {
     let mut mix = SourceMix {
         white: 0.0,
         pink: 0.0,
         brown: 0.0,
+        blue: 0.0,
+        violet: 0.0,
         rain: 0.0,
+        ocean: 0.0,
+        wind: 0.0,
+        fire: 0.0,
+        fan: 0.0,
+        sample: 0.0,
     };
     let mut seen: Vec<SoundStyle> = Vec::new();
 
@@ -87,10 +1224,17 @@ fn parse_mix(value: &str) -> std::result::Result<SourceMix, String> {
             "white" | "vanilla" => SoundStyle::White,
             "pink" => SoundStyle::Pink,
             "brown" => SoundStyle::Brown,
+            "blue" => SoundStyle::Blue,
+            "violet" => SoundStyle::Violet,
             "rain" => SoundStyle::Rain,
+            "ocean" => SoundStyle::Ocean,
+            "wind" => SoundStyle::Wind,
+            "fire" => SoundStyle::Fire,
+            "fan" => SoundStyle::Fan,
+            "sample" => SoundStyle::Sample,
             other => {
                 return Err(format!(
-                    "unknown source '{other}' (valid: white, pink, brown, rain)"
+                    "unknown source '{other}' (valid: white, pink, brown, blue, violet, rain, ocean, wind, fire, fan, sample)"
                 ));
             }
         };
@@ -117,103 +1261,1003 @@ fn parse_mix(value: &str) -> std::result::Result<SourceMix, String> {
     Ok(mix)
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-
-    if args.list_hosts {
-        list_hosts();
-        return Ok(());
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {error:?}");
+            exit::exit_code(&error)
+        }
     }
+}
 
-    let host = select_host(args.host.as_deref())?;
-    if args.list_devices {
-        return list_audio_devices(&host);
+fn run() -> Result<()> {
+    let mut args = Args::parse();
+    if args.per_session {
+        args.instance = Some(service::session_instance(
+            std::env::var("XDG_SEAT").ok().as_deref(),
+            std::env::var("XDG_SESSION_ID").ok().as_deref(),
+        )?);
     }
-
-    if !args.non_interactive && (!io::stdin().is_terminal() || !io::stdout().is_terminal()) {
-        bail!("interactive mode requires a terminal; use --non-interactive");
+    // Before anything reads the settings folder, translations included.
+    if let Some(path) = chosen_config_path(&args) {
+        set_config_path(std::path::absolute(&path).unwrap_or(path));
     }
-
-    let device = select_output_device(&host, args.device.as_deref())?;
-    let device_name = device
-        .description()
-        .map(|description| description.name().to_owned())
-        .unwrap_or_else(|_| device.to_string());
-    let supported_config = device
-        .default_output_config()
-        .context("failed to query the default output format")?;
-    let sample_format = supported_config.sample_format();
-    let stream_config = supported_config.config();
-
-    let mut initial_settings = load_settings().unwrap_or_else(|error| {
-        eprintln!("warning: {error:#}; using default settings");
-        AudioSettings::default()
-    });
-    if let Some(mix) = args.mix {
-        initial_settings.set_mix(mix);
-    } else if let Some(style) = args.style {
-        initial_settings.set_mix(SourceMix::solo(style));
+    if let Err(error) = i18n::init() {
+        eprintln!("warning: {error:#}; using English");
     }
-    if let Some(volume) = args.volume {
-        initial_settings.volume = volume;
-    } else if !args.non_interactive {
-        // Starting an interactive session muted avoids headphone surprises.
-        initial_settings.volume = 0.0;
+
+    if let Some(Command::Bench {
+        seconds,
+        sample_rate,
+    }) = args.command
+    {
+        return bench::run(seconds, sample_rate);
     }
-    if args.non_interactive && initial_settings.volume <= 0.0 {
-        bail!(
-            "non-interactive mode has no audible volume; pass --volume PERCENT or save a non-zero volume in interactive mode"
+
+    if let Some(Command::Render {
+        duration,
+        all_presets: true,
+        ref out_dir,
+        sample_rate,
+        chapters,
+        ..
+    }) = args.command
+    {
+        let settings_file = load_settings_file(&args);
+        let sample_data = read_sample(&args, &settings_file)?;
+        let mut base = starting_settings(&args, &settings_file)?;
+        fit_loop_crossfades(&mut base, sample_data.as_ref())?;
+        let dir = presets_dir();
+        let mut presets = Vec::new();
+        for name in list_presets(&dir)? {
+            let mut settings = base;
+            apply_preset(load_preset(&dir, &name)?, &mut settings);
+            if settings.volume <= 0.0 || settings.mix().total() <= 0.0 {
+                eprintln!("Skipping preset {name}: it is silent");
+                continue;
+            }
+            if let Err(error) = check_sample_source(&settings, sample_data.is_some()) {
+                eprintln!("Skipping preset {name}: {error:#}");
+                continue;
+            }
+            presets.push((name, settings));
+        }
+        return render::run_all(
+            out_dir
+                .as_deref()
+                .context("--all-presets needs --out-dir")?,
+            &presets,
+            StreamOptions {
+                sample: sample_data.as_ref(),
+                block_size: args.block_size,
+                seed: args.seed,
+            },
+            sample_rate,
+            duration,
+            chapters,
         );
     }
-    if args.non_interactive && initial_settings.mix().total() <= 0.0 {
+
+    if let Some(Command::Render {
+        duration,
+        ref output,
+        sample_rate,
+        chapters,
+        ..
+    }) = args.command
+    {
+        let settings_file = load_settings_file(&args);
+        let sample_data = read_sample(&args, &settings_file)?;
+        let mut settings = starting_settings(&args, &settings_file)?;
+        fit_loop_crossfades(&mut settings, sample_data.as_ref())?;
+        if let Some(volume) = args.volume {
+            settings.volume = volume;
+        }
+        ensure!(
+            settings.volume > 0.0,
+            "render has no audible volume; pass --volume PERCENT"
+        );
+        ensure!(
+            settings.mix().total() > 0.0,
+            "render has no audible source; every mix level is zero, pass --mix or --style"
+        );
+        check_sample_source(&settings, sample_data.is_some())?;
+        return render::run(
+            output
+                .as_deref()
+                .context("render needs --output or --all-presets")?,
+            settings,
+            StreamOptions {
+                sample: sample_data.as_ref(),
+                block_size: args.block_size,
+                seed: args.seed,
+            },
+            sample_rate,
+            duration,
+            chapters,
+        );
+    }
+
+    if let Some(Command::Compare {
+        ref a,
+        ref b,
+        seconds,
+        sample_rate,
+    }) = args.command
+    {
+        let settings_file = load_settings_file(&args);
+        let sample_data = read_sample(&args, &settings_file)?;
+        let mut base = starting_settings(&args, &settings_file)?;
+        fit_loop_crossfades(&mut base, sample_data.as_ref())?;
+        let dir = presets_dir();
+        let mut compared = Vec::new();
+        for name in [a, b] {
+            let mut settings = base;
+            apply_preset(load_preset(&dir, name)?, &mut settings);
+            ensure!(
+                settings.volume > 0.0 && settings.mix().total() > 0.0,
+                "preset {} is silent, so there is nothing to compare",
+                name.trim()
+            );
+            check_sample_source(&settings, sample_data.is_some())?;
+            compared.push((name.trim(), settings));
+        }
+        return compare::run(
+            compared[0],
+            compared[1],
+            StreamOptions {
+                sample: sample_data.as_ref(),
+                block_size: args.block_size,
+                seed: Some(args.seed.unwrap_or(compare::COMPARE_SEED)),
+            },
+            sample_rate,
+            seconds,
+        );
+    }
+
+    if let Some(Command::Ctl { ref cmd, ref value }) = args.command {
+        let request = control::Request {
+            cmd: cmd.clone(),
+            value: (!value.is_empty()).then(|| value.join(" ").into()),
+        };
+        let reply = control::send(&socket_path(&args), &request)?;
+        match (reply.ok, reply.message, reply.error) {
+            (true, message, _) => println!("{}", message.unwrap_or_default()),
+            (false, _, error) => bail!("{}", error.unwrap_or_default()),
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Status { json }) = args.command {
+        let request = control::Request {
+            cmd: if json { "state" } else { "status" }.to_owned(),
+            value: None,
+        };
+        let reply = control::send(&socket_path(&args), &request)?;
+        match (reply.ok, reply.message, reply.error, reply.state) {
+            (true, _, _, Some(state)) => println!("{}", serde_json::to_string(&state)?),
+            (true, message, _, None) => println!("{}", message.unwrap_or_default()),
+            (false, _, error, _) => bail!("{}", error.unwrap_or_default()),
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Describe) = args.command {
+        let request = control::Request {
+            cmd: "status".to_owned(),
+            value: None,
+        };
+        match control::send(&socket_path(&args), &request) {
+            Ok(reply) if reply.ok => println!("{}", reply.message.unwrap_or_default()),
+            Ok(reply) => bail!("{}", reply.error.unwrap_or_default()),
+            Err(error) if error.downcast_ref::<Failure>() == Some(&Failure::NotRunning) => {
+                eprintln!("note: no player is running; describing what the next run would play");
+                let mut settings = starting_settings(&args, &load_settings_file(&args))?;
+                if let Some(volume) = args.volume {
+                    settings.volume = volume;
+                }
+                println!("{}", describe::describe(&settings, clock::now()));
+            }
+            Err(error) => return Err(error),
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Daemon {
+        install_service: true,
+        ..
+    }) = args.command
+    {
+        // The service starts in another working directory.
+        ensure!(
+            !args.config.as_ref().is_some_and(|path| path.is_relative()),
+            "--install-service needs an absolute --config path"
+        );
+        let program = std::env::current_exe().context("failed to find this program's path")?;
+        // The service manager runs outside the login session, so the
+        // session's instance is named outright.
+        let arguments: Vec<String> = std::env::args_os()
+            .skip(1)
+            .map(|argument| argument.to_string_lossy().into_owned())
+            .filter(|argument| argument != "--install-service")
+            .flat_map(|argument| match (&args.instance, argument.as_str()) {
+                (Some(name), "--per-session") => vec!["--instance".to_owned(), name.clone()],
+                _ => vec![argument],
+            })
+            .collect();
+        return service::install(&program, &arguments, args.instance.as_deref());
+    }
+    let daemon = matches!(args.command, Some(Command::Daemon { .. }));
+    if daemon && !cfg!(unix) {
+        bail!("the daemon needs Unix signals and sockets, which this platform lacks");
+    }
+
+    if let Some(Command::Presets { ref action }) = args.command {
+        let dir = presets_dir();
+        return match action {
+            PresetsCommand::List => preset::print_presets(&dir),
+            PresetsCommand::Save { name } => {
+                let mut settings = starting_settings(&args, &load_settings_file(&args))?;
+                if let Some(volume) = args.volume {
+                    settings.volume = volume;
+                }
+                save_preset(&dir, name, &settings)?;
+                println!(
+                    "Saved preset {}: {}",
+                    name.trim(),
+                    preset::summary(&settings)
+                );
+                Ok(())
+            }
+            PresetsCommand::Delete { name } => {
+                delete_preset(&dir, name)?;
+                println!("Deleted preset {}", name.trim());
+                Ok(())
+            }
+            PresetsCommand::Export { name } => {
+                load_settings_file(&args);
+                let (content, warnings) = preset::export_preset(&dir, name)?;
+                for warning in warnings {
+                    eprintln!("warning: {warning}");
+                }
+                print!("{content}");
+                Ok(())
+            }
+            PresetsCommand::Import { file, name, force } => {
+                load_settings_file(&args);
+                let content = if file.as_os_str() == "-" {
+                    io::read_to_string(io::stdin()).context("failed to read standard input")?
+                } else {
+                    std::fs::read_to_string(file)
+                        .with_context(|| format!("failed to read {}", file.display()))?
+                };
+                let import = preset::import_preset(&dir, &content, name.as_deref(), *force)?;
+                print_import(&import);
+                Ok(())
+            }
+            #[cfg(feature = "gallery")]
+            PresetsCommand::Gallery => {
+                let settings_file = load_settings_file(&args);
+                let url = gallery::configured(settings_file.gallery.as_deref())?;
+                let entries = gallery::list(url)?;
+                if entries.is_empty() {
+                    println!("The gallery has no presets yet.");
+                }
+                for entry in entries {
+                    println!("{:<12} {}", entry.id, entry.line());
+                }
+                Ok(())
+            }
+            #[cfg(feature = "gallery")]
+            PresetsCommand::Fetch { id, name, force } => {
+                let settings_file = load_settings_file(&args);
+                let url = gallery::configured(settings_file.gallery.as_deref())?;
+                let content = gallery::fetch(url, id)?;
+                let import = preset::import_preset(&dir, &content, name.as_deref(), *force)?;
+                print_import(&import);
+                Ok(())
+            }
+            #[cfg(feature = "gallery")]
+            PresetsCommand::Submit { name, description } => {
+                let settings_file = load_settings_file(&args);
+                let url = gallery::configured(settings_file.gallery.as_deref())?;
+                let (content, warnings) = preset::export_preset(&dir, name)?;
+                for warning in warnings {
+                    eprintln!("warning: {warning}");
+                }
+                let id = gallery::submit(url, name.trim(), description.trim(), &content)?;
+                println!("Sent preset {} to the gallery as {id}", name.trim());
+                Ok(())
+            }
+        };
+    }
+
+    if let Some(Command::Scenes) = args.command {
+        return print_scenes(&scenes_dir());
+    }
+
+    #[cfg(feature = "update")]
+    if let Some(Command::Update {
+        ref download,
+        ref feed,
+        ..
+    }) = args.command
+    {
+        return update::run(feed, download.as_deref());
+    }
+    if let Some(Command::Schedule) = args.command {
+        let settings_file = load_settings_file(&args);
+        schedule::check(&settings_file.schedule, settings_file.location)?;
+        print_schedule(
+            &settings_file.schedule,
+            &SunTimes::today(settings_file.location),
+            &presets_dir(),
+            ClockTime::now(),
+        );
+        return Ok(());
+    }
+
+    if args.list_hosts {
+        return list_hosts(args.json);
+    }
+
+    let host = select_host(args.host.as_deref()).context(Failure::HostUnavailable)?;
+    if args.list_devices {
+        return list_audio_devices(&host, args.json);
+    }
+
+    if let Some(Command::Calibrate { ref input, yes }) = args.command {
+        let mut settings_file = load_settings_file(&args);
+        let output_devices = output_devices(&args, &settings_file);
+        let device = select_output_device(&host, output_devices.first().map(String::as_str))
+            .context(Failure::DeviceNotFound)?;
+        let device_name = display_name(&device);
+        let (stream_config, sample_format) = select_output_config(
+            &device,
+            args.sample_rate.or(settings_file.output.sample_rate),
+            args.buffer_size.or(settings_file.output.buffer_size),
+        )?;
+        let microphone =
+            select_input_device(&host, input.as_deref()).context(Failure::DeviceNotFound)?;
+        let Some(correction) =
+            calibrate::run(&device, stream_config, sample_format, &microphone, yes)?
+        else {
+            return Ok(());
+        };
+        settings_file.device_mut(&device_name).room_correction_db = Some(correction);
+        save_settings(&settings_file)?;
+        println!("Saved the correction for {device_name}; it applies whenever that device plays.");
+        return Ok(());
+    }
+
+    // The status line takes no input, so it plays by the non-interactive rules.
+    let mut status_line = matches!(args.command, Some(Command::Top));
+    if !args.non_interactive && !status_line && !daemon {
+        if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+            bail!("interactive mode requires a terminal; use --non-interactive");
+        }
+        if let Some(reason) = terminal::full_screen_unsupported() {
+            eprintln!(
+                "note: {reason}, so the full-screen interface cannot draw; showing the status line instead, as `whitenoise top` does"
+            );
+            status_line = true;
+        }
+    }
+    let non_interactive = args.non_interactive || status_line || daemon;
+    let target = args
+        .output_to
+        .clone()
+        .or_else(|| args.virtual_output.clone().map(SinkTarget::File));
+    let to_stdout = target == Some(SinkTarget::Stdout);
+    if to_stdout && (status_line || !non_interactive) {
+        bail!("stdout carries the sound with --output-to -; use --non-interactive");
+    }
+
+    let (settings_file, settings_notes) = load_settings_file_noting(&args);
+    let output_devices = output_devices(&args, &settings_file);
+    let playback = match target {
+        Some(target) => Playback::Virtual(target),
+        None => {
+            let device = select_output_device(&host, output_devices.first().map(String::as_str))
+                .context(Failure::DeviceNotFound)?;
+            let (stream_config, sample_format) = select_output_config(
+                &device,
+                args.sample_rate.or(settings_file.output.sample_rate),
+                args.buffer_size.or(settings_file.output.buffer_size),
+            )
+            .context(Failure::StreamFailed)?;
+            Playback::Device(Box::new(device), stream_config, sample_format)
+        }
+    };
+    let device_name = match &playback {
+        Playback::Device(device, ..) => display_name(device),
+        Playback::Virtual(_) => output::VIRTUAL_DEVICE_NAME.to_owned(),
+    };
+    let sample_data = read_sample(&args, &settings_file)?;
+
+    let mut initial_settings = starting_settings(&args, &settings_file)?;
+    let chosen = chosen_mix(&args, &settings_file)?;
+    let schedule = match schedule::check(&settings_file.schedule, settings_file.location) {
+        Ok(()) => settings_file.schedule.clone(),
+        Err(error) => {
+            eprintln!("warning: {error:#}; the schedule is off");
+            Vec::new()
+        }
+    };
+    // The entry in force sets the starting sound unless --preset, --scene,
+    // or --share-code chose one.
+    let scheduled = (args.preset.is_none() && args.scene.is_none() && args.share_code.is_none())
+        .then(|| {
+            let sun = SunTimes::today(settings_file.location);
+            schedule::in_force(&schedule, &sun, ClockTime::now())
+        })
+        .flatten();
+    if let Some(entry) = scheduled {
+        match schedule::load_entry(entry, &presets_dir()) {
+            Ok(Some(scene)) => {
+                apply_scene(&scene, &mut initial_settings, args.fade_out, clock::now());
+                apply_source_overrides(&args, chosen, &mut initial_settings);
+            }
+            Ok(None) => {
+                initial_settings.paused = true;
+                eprintln!(
+                    "note: the schedule stopped the sound at {}, so playback starts paused",
+                    entry.at
+                );
+            }
+            Err(error) => eprintln!(
+                "warning: {error:#}; the schedule's {} entry is skipped",
+                entry.at
+            ),
+        }
+    }
+    let loop_limits = fit_loop_crossfades(&mut initial_settings, sample_data.as_ref())?;
+    let starting_loops = initial_settings.loops;
+    let chosen_volume = args.preset.is_some()
+        || args.scene.is_some()
+        || args.share_code.is_some()
+        || scheduled.is_some();
+    if let Some(volume) = start_volume(&args, &settings_file, non_interactive, chosen_volume) {
+        initial_settings.volume = volume;
+    }
+    if non_interactive && initial_settings.volume <= 0.0 {
+        bail!(
+            "non-interactive mode has no audible volume; pass --volume PERCENT, set start_volume, or save a non-zero volume in interactive mode"
+        );
+    }
+    if non_interactive && initial_settings.mix().total() <= 0.0 {
         bail!(
             "non-interactive mode has no audible source; every mix level is zero, pass --mix or --style"
         );
     }
+    if non_interactive {
+        check_sample_source(&initial_settings, sample_data.is_some())?;
+    }
 
-    println!(
-        "Using {} via {} ({} channels, {} Hz, {})",
-        device_name,
-        host.id(),
-        stream_config.channels,
-        stream_config.sample_rate,
-        sample_format
-    );
+    // Only this run gets the device's profile and small-speaker high-pass;
+    // see `keep_device_choices_local`.
+    let chosen_settings = initial_settings;
+    let device_settings = settings_file.device(&device_name);
+    if let Some(profile) = device_settings.profile.filter(|_| args.profile.is_none()) {
+        profile.apply(&mut initial_settings);
+        // The individual options still win over the device's profile.
+        apply_source_overrides(&args, chosen, &mut initial_settings);
+        eprintln!(
+            "note: {device_name} uses the {} profile; --profile picks another",
+            profile.name()
+        );
+    }
+    if device_settings.small && initial_settings.high_pass.hz.is_none() && args.high_pass.is_none()
+    {
+        initial_settings.high_pass.hz = Some(SMALL_SPEAKER_HIGH_PASS_HZ);
+        eprintln!(
+            "note: {device_name} is marked small, so the high-pass is on at {SMALL_SPEAKER_HIGH_PASS_HZ} Hz; --high-pass off plays its full range"
+        );
+    }
+    let device_choices = initial_settings;
+    // Not part of the saved sound at all, so it needs no restoring.
+    // The output keeper brings it in again for any device it moves to.
+    match (
+        device_settings.room_correction(),
+        &device_settings.room_correction_db,
+    ) {
+        (Some(correction), _) => {
+            initial_settings.room_correction_db = correction;
+            eprintln!(
+                "note: {device_name} plays with its room correction from `whitenoise calibrate`"
+            );
+        }
+        (None, Some(correction)) => eprintln!(
+            "note: {device_name}'s room correction was measured for {} bands and the layout has {}, so it is off; run `whitenoise calibrate` again",
+            correction.len(),
+            bands().len()
+        ),
+        (None, None) => {}
+    }
 
-    let settings = Arc::new(Mutex::new(initial_settings));
-    let running = Arc::new(AtomicBool::new(true));
-    let signal_running = Arc::clone(&running);
-    ctrlc::set_handler(move || signal_running.store(false, Ordering::Relaxed))?;
+    let virtual_device = sink::VirtualDevice {
+        sample_rate: args
+            .sample_rate
+            .or(settings_file.output.sample_rate)
+            .unwrap_or(48_000),
+        ..sink::VirtualDevice::default()
+    };
+    match &playback {
+        Playback::Device(_, stream_config, sample_format) => {
+            let buffer = match stream_config.buffer_size {
+                BufferSize::Fixed(frames) => format!(", {frames}-frame buffer"),
+                BufferSize::Default => String::new(),
+            };
+            println!(
+                "Using {} via {} ({} channels, {} Hz, {}{buffer})",
+                device_name,
+                host.id(),
+                stream_config.channels,
+                stream_config.sample_rate,
+                sample_format
+            );
+        }
+        Playback::Virtual(target) => eprintln!(
+            "Using the {device_name}, writing {target} ({} channels, {} Hz)",
+            virtual_device.channels, virtual_device.sample_rate
+        ),
+    }
 
-    let stream = build_output_stream(
-        &device,
-        stream_config,
-        sample_format,
+    // --timer replaces any timer a scene started.
+    if let Some(length) = args.timer {
+        initial_settings.sleep_timer =
+            Some(SleepTimer::new(length, args.fade_out, clock::now()).with_alarm(args.alarm));
+    }
+    // A timer the last run left behind means it ended without stopping, so
+    // the timer it was running carries on over any new one.
+    let timer_path = timer::saved_timer_path();
+    match timer::load_saved_timer(&timer_path) {
+        Ok(saved) => {
+            let restored = saved.and_then(|saved| saved.restore(clock::now(), SystemTime::now()));
+            if let Some(timer) = restored {
+                eprintln!(
+                    "note: the last run's sleep timer carries on, {} left",
+                    format_remaining(timer.remaining(clock::now()))
+                );
+                initial_settings.sleep_timer = Some(timer);
+            }
+        }
+        Err(error) => eprintln!("warning: the last run's sleep timer was not restored: {error:#}"),
+    }
+    if let Some(limit) = settings_file.night_limit {
+        initial_settings.night_ceiling_db = limit.ceiling_at(ClockTime::now());
+        if let Some(ceiling_db) = initial_settings.night_ceiling_db {
+            eprintln!(
+                "note: the night limit holds the output under {ceiling_db:.0} dBFS until {}",
+                limit.until
+            );
+        }
+    }
+    if let Some(length) = args.fade_in.filter(|_| !initial_settings.paused) {
+        initial_settings.schedule_fade = Some(schedule::ScheduleFade::rising(clock::now(), length));
+    }
+    let settings = Arc::new(SharedSettings::new(initial_settings));
+    let running = Arc::new(AtomicBool::new(true));
+    let reload = Arc::new(AtomicBool::new(false));
+    if daemon {
+        service::watch_signals(Arc::clone(&running), Arc::clone(&reload))?;
+    } else {
+        let signal_running = Arc::clone(&running);
+        ctrlc::set_handler(move || signal_running.store(false, Ordering::Relaxed))?;
+    }
+    if let Some(limit) = settings_file.night_limit {
+        night::watch(limit, Arc::clone(&settings), Arc::clone(&running));
+    }
+    let timer_file = timer::persist(timer_path, Arc::clone(&settings), Arc::clone(&running));
+    // Even without entries, the thread times a scene's layers.
+    schedule::watch(
+        schedule,
+        settings_file.location,
         Arc::clone(&settings),
         Arc::clone(&running),
-    )?;
-    stream.play().context("failed to start audio playback")?;
+        presets_dir(),
+    );
+    if args.duck_on_playback {
+        if let Err(error) = duck::watch(
+            Cue::Playback,
+            args.duck_db,
+            Arc::clone(&settings),
+            Arc::clone(&running),
+        ) {
+            eprintln!("note: {error:#}; the sound will not duck for other playback");
+        }
+    }
+    if args.duck_on_calls {
+        if let Err(error) = duck::watch(
+            Cue::Calls,
+            args.call_duck_db,
+            Arc::clone(&settings),
+            Arc::clone(&running),
+        ) {
+            eprintln!("note: {error:#}; the sound will not duck for calls");
+        }
+    }
+    let monitor = Arc::new(AudioMonitor::default());
+    // --ambient-level turns it on with the table's choices or the defaults.
+    let ambient_level = match &settings_file.ambient {
+        Some(level) => Some(level.clone()),
+        None => args.ambient_level.then(AmbientLevel::default),
+    };
+    if let Some(mut level) = ambient_level {
+        if args.ambient_input.is_some() {
+            level.input.clone_from(&args.ambient_input);
+        }
+        if let Err(error) =
+            select_input_device(&host, level.input.as_deref()).and_then(|microphone| {
+                ambient::watch(
+                    level,
+                    &microphone,
+                    Arc::clone(&settings),
+                    Arc::clone(&running),
+                    Arc::clone(&monitor),
+                )
+            })
+        {
+            eprintln!("note: {error:#}; the sound will not follow the room's noise");
+        }
+    }
+    let exposure = Arc::new(Exposure::default());
+    let exposure_log = load_exposure(&exposure_path()).unwrap_or_else(|error| {
+        eprintln!("warning: {error:#}; counting the noise dose from zero");
+        ExposureLog::default()
+    });
+    let exposure_file = exposure::track(
+        exposure_path(),
+        exposure_log,
+        Arc::clone(&settings),
+        Arc::clone(&monitor),
+        Arc::clone(&exposure),
+        Arc::clone(&running),
+        notify::send,
+    );
 
-    if args.non_interactive {
-        println!(
-            "Playing {} at {:.0}% volume. Press Ctrl+C to stop.",
-            initial_settings.mix().describe(),
-            initial_settings.volume * 100.0
+    let sample_loaded = sample_data.is_some();
+    if args.silence_watchdog {
+        watchdog::watch(
+            Arc::clone(&settings),
+            Arc::clone(&monitor),
+            Arc::clone(&running),
+            sample_loaded,
         );
+    }
+    let devices = settings_file.clone();
+    let output = match playback {
+        Playback::Device(device, config, sample_format) => output::start(OutputSetup {
+            sample_rate: args.sample_rate.or(settings_file.output.sample_rate),
+            buffer_size: args.buffer_size.or(settings_file.output.buffer_size),
+            preferred: output_devices.first().cloned(),
+            mirrors: output_devices.iter().skip(1).cloned().collect(),
+            host,
+            device: *device,
+            config,
+            sample_format,
+            settings: Arc::clone(&settings),
+            running: Arc::clone(&running),
+            monitor: Arc::clone(&monitor),
+            sample: sample_data,
+            block_size: args.block_size,
+            seed: args.seed,
+            room_correction: Box::new(move |name| devices.device(name).room_correction()),
+        })
+        .context(Failure::StreamFailed)?,
+        Playback::Virtual(target) => output::start_virtual(VirtualSetup {
+            sink: target.open(virtual_device.sample_rate, virtual_device.channels)?,
+            device: virtual_device,
+            settings: Arc::clone(&settings),
+            running: Arc::clone(&running),
+            monitor: Arc::clone(&monitor),
+            sample: sample_data,
+            block_size: args.block_size,
+            seed: args.seed,
+        })?,
+    };
+
+    let mut recording = match &args.record {
+        Some(path) => Some(Recording::start(path, Arc::clone(&monitor))?),
+        None => None,
+    };
+
+    // Headless playback has no prompt, so `whitenoise ctl` stands in for it.
+    let _control = if non_interactive {
+        let session = control::Session {
+            settings: Arc::clone(&settings),
+            running: Arc::clone(&running),
+            fade_out: args.fade_out,
+            presets: presets_dir(),
+        };
+        match control::serve(&socket_path(&args), session) {
+            Ok(socket) => Some(socket),
+            // A daemon is reached only through its socket.
+            Err(error) if daemon => return Err(error),
+            Err(error) => {
+                eprintln!("note: {error:#}; `whitenoise ctl` cannot reach this player");
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let _pidfile = match args.command {
+        Some(Command::Daemon { ref pidfile, .. }) => {
+            Some(service::PidFile::write(&pidfile.clone().unwrap_or_else(
+                || service::pidfile_beside(&socket_path(&args)),
+            ))?)
+        }
+        _ => None,
+    };
+
+    // Exam mode locks out everything but volume, and a kiosk everything, so
+    // media keys stay out too. Without a desktop session there is no bus to
+    // join, and nothing to say.
+    #[cfg(target_os = "linux")]
+    let _media_keys =
+        if args.exam_mode || args.kiosk || std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_none() {
+            None
+        } else {
+            mpris::serve(Arc::clone(&settings), Arc::clone(&running))
+                .inspect_err(|error| {
+                    eprintln!("note: {error:#}; media keys will not reach this player")
+                })
+                .ok()
+        };
+
+    let _mqtt = args.mqtt.clone().and_then(|broker| {
+        let session = mqtt::Session {
+            settings: Arc::clone(&settings),
+            running: Arc::clone(&running),
+            fade_out: args.fade_out,
+            presets: presets_dir(),
+            name: args.mqtt_name.clone(),
+        };
+        mqtt::serve(broker, session)
+            .inspect_err(|error| eprintln!("note: {error:#}; MQTT control is off"))
+            .ok()
+    });
+
+    #[cfg(feature = "web")]
+    let _web = args.listen.and_then(|listen| {
+        let session = web::Session {
+            settings: Arc::clone(&settings),
+            running: Arc::clone(&running),
+            fade_out: args.fade_out,
+            presets: presets_dir(),
+            token: args.listen_token.clone().unwrap_or_else(web::new_token),
+        };
+        listen
+            .address(args.listen_lan)
+            .and_then(|address| web::serve(address, session))
+            .inspect(|server| {
+                if let Some(url) = server.url() {
+                    eprintln!("note: remote control at {url}");
+                }
+            })
+            .inspect_err(|error| eprintln!("note: {error:#}; the web remote is off"))
+            .ok()
+    });
+
+    // A kiosk's sound is the scene's, not a choice to resume next time.
+    let saver = (!args.kiosk).then(|| {
+        Arc::new(Mutex::new(SessionSaver {
+            file: settings_file.clone(),
+            chosen: chosen_settings,
+            device: device_choices,
+            starting_loops,
+            saved: initial_settings,
+        }))
+    });
+    let autosaver = saver.as_ref().map(|saver| {
+        autosave(
+            Arc::clone(saver),
+            Arc::clone(&settings),
+            Arc::clone(&running),
+        )
+    });
+
+    if status_line {
+        run_status_line(&settings, &running, &monitor, &exposure)?;
+    } else if non_interactive {
+        let log = if daemon {
+            service::Log::for_service()
+        } else if to_stdout {
+            service::Log::Stderr
+        } else {
+            service::Log::Terminal
+        };
+        log.info(&format!(
+            "Playing {} at {:.0}% volume.{}",
+            initial_settings.mix().describe(),
+            initial_settings.volume * 100.0,
+            if daemon { "" } else { " Press Ctrl+C to stop." }
+        ));
+        match initial_settings.sleep_timer {
+            Some(timer) if timer.alarm() == TimerAlarm::Visual => log.info(&format!(
+                "Visual timer: alarm in {}; the sound keeps playing.",
+                format_remaining(timer.remaining(clock::now()))
+            )),
+            Some(timer) => log.info(&format!(
+                "Sleep timer: stopping in {}, fading out over the last {}.",
+                format_remaining(timer.remaining(clock::now())),
+                format_remaining(timer.fade())
+            )),
+            None => {}
+        }
+        let mut reported_resets = 0;
+        let mut reported_restarts = 0;
+        let mut reported_silences = 0;
+        let mut reported_refused = 0;
+        let mut reported_lost = false;
+        let mut reported_over = false;
+        let handle = output.handle();
         while running.load(Ordering::Relaxed) {
             std::thread::sleep(Duration::from_millis(100));
+            let lost = monitor.output_lost();
+            if lost != reported_lost {
+                if lost {
+                    log.note("the output device was lost; reconnecting");
+                } else {
+                    log.note(&format!("playing on {}", handle.device_name()));
+                }
+                reported_lost = lost;
+            }
+            if exposure.over() != reported_over {
+                reported_over = exposure.over();
+                if reported_over {
+                    log.warning(&format!(
+                        "at this level the night would reach {:.0}% of a daily noise dose",
+                        exposure.projected() * 100.0
+                    ));
+                }
+            }
+            let reloading = reload.swap(false, Ordering::Relaxed);
+            if let Some(saver) = saver.as_ref().filter(|_| reloading) {
+                match load_settings() {
+                    Ok((file, report)) => {
+                        for warning in report.warnings() {
+                            log.warning(&warning);
+                        }
+                        let mut settings = settings
+                            .lock()
+                            .unwrap_or_else(|poisoned| poisoned.into_inner());
+                        saver
+                            .lock()
+                            .unwrap_or_else(|poisoned| poisoned.into_inner())
+                            .reload(file, &mut settings);
+                        log.info(&format!(
+                            "Read settings.toml again: playing {} at {:.0}% volume.",
+                            settings.mix().describe(),
+                            settings.volume * 100.0
+                        ));
+                    }
+                    Err(error) => {
+                        log.warning(&format!("{error:#}; the settings in force stay"));
+                    }
+                }
+            }
+            // Read back each time, since `ctl timer` can set or clear it.
+            let sleep_timer = settings
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .sleep_timer;
+            if let Some(timer) = sleep_timer.filter(|timer| timer.expired(clock::now())) {
+                if timer.alarm() == TimerAlarm::Fade {
+                    break;
+                }
+                settings
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .sleep_timer = None;
+                let message = alarm_message(timer.length());
+                log.info(&message);
+                if let Err(error) = notify::send(i18n::text("alarm.title"), &message) {
+                    log.warning(&format!("{error:#}"));
+                }
+            }
+            let resets = monitor.dsp_resets();
+            if resets != reported_resets {
+                log.warning(&format!(
+                    "DSP produced NaN/Inf and was reset ({resets} total)"
+                ));
+                reported_resets = resets;
+            }
+            let restarts = monitor.engine_restarts();
+            if restarts != reported_restarts {
+                log.warning(&format!(
+                    "the sound engine panicked and was restarted ({restarts} total)"
+                ));
+                reported_restarts = restarts;
+            }
+            let silences = monitor.silence_restarts();
+            if silences != reported_silences {
+                log.warning(&format!(
+                    "the output stayed silent for {} s while it should have played, so the sound engine was restarted ({silences} total)",
+                    watchdog::SILENCE_LIMIT.as_secs()
+                ));
+                reported_silences = silences;
+            }
+            let refused = monitor.refused_buffer();
+            if refused != reported_refused {
+                if refused > 0 {
+                    log.note(&format!(
+                        "the device refused {refused}-frame buffers; playing with its own buffer size"
+                    ));
+                }
+                reported_refused = refused;
+            }
         }
     } else {
-        InteractiveUi::new(Arc::clone(&settings), Arc::clone(&running)).run()?;
+        let mut ui = InteractiveUi::new(
+            Arc::clone(&settings),
+            Arc::clone(&running),
+            Arc::clone(&monitor),
+        )
+        .with_exposure(Arc::clone(&exposure))
+        .with_fade_out(args.fade_out)
+        .with_timer_alarm(args.alarm)
+        .with_theme(settings_file.theme)
+        .with_command_history(load_command_history().unwrap_or_else(|error| {
+            eprintln!("warning: {error:#}; starting with an empty command history");
+            CommandHistory::default()
+        }));
+        if !settings_notes.is_empty() {
+            ui = ui.with_note(settings_notes.join("; "));
+        }
+        if args.exam_mode {
+            ui = ui.with_exam_mode();
+        }
+        if args.kiosk {
+            ui = ui.with_kiosk();
+        }
+        if sample_loaded {
+            ui = ui.with_sample_loaded();
+        }
+        ui = ui.with_output(output.handle());
+        ui = ui.with_loop_limits(loop_limits);
+        ui = ui.with_recording(recording.take());
+        #[cfg(feature = "web")]
+        let remote_url = _web.as_ref().and_then(web::WebServer::url);
+        #[cfg(not(feature = "web"))]
+        let remote_url = None;
+        ui = ui.with_remote_url(remote_url);
+        ui = ui.with_gallery_url(settings_file.gallery.clone());
+        ui.run()?;
+        recording = ui.take_recording();
+        if let Err(error) = save_command_history(ui.command_history()) {
+            eprintln!("warning: command history was not saved: {error:#}");
+        }
     }
 
     running.store(false, Ordering::Relaxed);
-    drop(stream);
+    output.stop();
+    // Stopped after the output, so the file keeps the fade to the end.
+    if let Some(recording) = recording {
+        match recording.stop() {
+            Ok(recorded) => eprintln!("{}", recorded.summary()),
+            Err(error) => eprintln!("warning: {error:#}"),
+        }
+    }
+    let _ = timer_file.join();
+    let _ = exposure_file.join();
+    if let Some(autosaver) = autosaver {
+        let _ = autosaver.join();
+    }
 
+    let Some(saver) = saver else {
+        return Ok(());
+    };
     let final_settings = *settings
         .lock()
         .unwrap_or_else(|poisoned| poisoned.into_inner());
-    if let Err(error) = save_settings(&final_settings) {
+    if let Err(error) = saver
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .save(final_settings)
+    {
         eprintln!("warning: settings were not saved: {error:#}");
     }
     Ok(())
@@ -238,6 +2282,617 @@ mod tests {
         assert!(parse_percentage("loud").is_err());
     }
 
+    #[test]
+    fn width_parser_accepts_names_and_percentages() {
+        assert_eq!(parse_width("mono"), Ok(0.0));
+        assert_eq!(parse_width("Normal"), Ok(DEFAULT_STEREO_WIDTH));
+        assert_eq!(parse_width("wide"), Ok(1.0));
+        assert_eq!(parse_width("25"), Ok(0.25));
+        assert!(
+            parse_width("extra-wide")
+                .unwrap_err()
+                .starts_with("width must be")
+        );
+        assert!(parse_width("150").is_err());
+    }
+
+    #[test]
+    fn seconds_parser_enforces_a_sane_range() {
+        assert_eq!(parse_seconds("0.1").unwrap(), 0.1);
+        assert_eq!(parse_seconds("60").unwrap(), 60.0);
+        assert!(parse_seconds("0").is_err());
+        assert!(parse_seconds("3601").is_err());
+        assert!(parse_seconds("inf").is_err());
+        assert!(parse_seconds("long").is_err());
+    }
+
+    #[test]
+    fn block_size_parser_enforces_a_sane_range() {
+        assert_eq!(parse_block_size("16").unwrap(), 16);
+        assert_eq!(parse_block_size("8192").unwrap(), 8192);
+        assert!(parse_block_size("15").is_err());
+        assert!(parse_block_size("8193").is_err());
+        assert!(parse_block_size("128.5").is_err());
+
+        let args = Args::try_parse_from(["whitenoise"]).unwrap();
+        assert_eq!(args.block_size, DEFAULT_BLOCK_SIZE);
+    }
+
+    #[test]
+    fn bench_subcommand_parses_with_defaults() {
+        let args = Args::try_parse_from(["whitenoise", "bench"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Bench {
+                seconds: 10.0,
+                sample_rate: 48_000
+            })
+        ));
+        assert!(Args::try_parse_from(["whitenoise", "bench", "--sample-rate", "100"]).is_err());
+    }
+
+    #[test]
+    fn compare_takes_two_presets() {
+        let args = Args::try_parse_from(["whitenoise", "compare", "focus", "deep sleep"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Compare { ref a, ref b, seconds: 10.0, sample_rate: 48_000 })
+                if a == "focus" && b == "deep sleep"
+        ));
+        assert!(Args::try_parse_from(["whitenoise", "compare", "focus"]).is_err());
+        assert!(
+            Args::try_parse_from(["whitenoise", "compare", "a", "b", "--seconds", "0"]).is_err()
+        );
+    }
+
+    #[cfg(feature = "gallery")]
+    #[test]
+    fn gallery_presets_are_listed_fetched_and_submitted() {
+        let args = Args::try_parse_from(["whitenoise", "presets", "fetch", "b2", "--name", "nap"])
+            .unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Presets {
+                action: PresetsCommand::Fetch { ref id, ref name, force: false }
+            }) if id == "b2" && name.as_deref() == Some("nap")
+        ));
+        let args = Args::try_parse_from(["whitenoise", "presets", "submit", "focus"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Presets {
+                action: PresetsCommand::Submit { ref name, ref description }
+            }) if name == "focus" && description.is_empty()
+        ));
+        assert!(Args::try_parse_from(["whitenoise", "presets", "gallery"]).is_ok());
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn update_asks_for_a_check_or_a_download() {
+        assert!(Args::try_parse_from(["whitenoise", "update"]).is_err());
+        let args = Args::try_parse_from(["whitenoise", "update", "--check"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Update { check: true, download: None, ref feed }) if feed == update::RELEASE_FEED
+        ));
+        let args =
+            Args::try_parse_from(["whitenoise", "update", "--download", "/srv/staging"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Update { download: Some(ref dir), .. }) if dir == Path::new("/srv/staging")
+        ));
+    }
+
+    #[test]
+    fn output_to_takes_a_sink_in_place_of_a_device() {
+        let args = Args::try_parse_from([
+            "whitenoise",
+            "--non-interactive",
+            "--output-to",
+            "tcp://127.0.0.1:4953",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.output_to,
+            Some(SinkTarget::Tcp("127.0.0.1:4953".to_owned()))
+        );
+        assert!(Args::try_parse_from(["whitenoise", "--output-to", "noise.flac"]).is_err());
+        assert!(
+            Args::try_parse_from(["whitenoise", "--output-to", "-", "--device", "USB"]).is_err()
+        );
+    }
+
+    #[test]
+    fn record_takes_a_path_beside_any_output() {
+        let args = Args::try_parse_from([
+            "whitenoise",
+            "--non-interactive",
+            "--output-to",
+            "null",
+            "--record",
+            "session.wav",
+        ])
+        .unwrap();
+        assert_eq!(args.record.as_deref(), Some(Path::new("session.wav")));
+        assert!(Args::try_parse_from(["whitenoise", "--record"]).is_err());
+    }
+
+    #[test]
+    fn a_share_code_sets_the_starting_sound() {
+        let args = Args::try_parse_from(["whitenoise", "--share-code", "04s0 00b4 01rg"]).unwrap();
+        let settings = starting_settings(&args, &SettingsFile::default()).unwrap();
+        assert_eq!(settings.volume, 0.5);
+        assert_eq!(settings.mix(), SourceMix::solo(SoundStyle::White));
+        let args = Args::try_parse_from([
+            "whitenoise",
+            "--share-code",
+            "04S0-00B4-01RG",
+            "--style",
+            "brown",
+        ])
+        .unwrap();
+        let settings = starting_settings(&args, &SettingsFile::default()).unwrap();
+        assert_eq!(settings.mix(), SourceMix::solo(SoundStyle::Brown));
+
+        assert!(Args::try_parse_from(["whitenoise", "--share-code", "04S0-00B4-01RH"]).is_err());
+        assert!(
+            Args::try_parse_from([
+                "whitenoise",
+                "--share-code",
+                "04S0-00B4-01RG",
+                "--preset",
+                "focus"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn render_subcommand_requires_a_duration_and_output() {
+        let args = Args::try_parse_from([
+            "whitenoise",
+            "--volume",
+            "20",
+            "--style",
+            "brown",
+            "render",
+            "--duration",
+            "1h",
+            "--output",
+            "noise.wav",
+        ])
+        .unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Render {
+                duration,
+                output: Some(ref output),
+                all_presets: false,
+                out_dir: None,
+                sample_rate: 48_000,
+                chapters: None,
+            }) if duration == Duration::from_secs(3600) && output.as_os_str() == "noise.wav"
+        ));
+        let args = Args::try_parse_from([
+            "whitenoise",
+            "render",
+            "--duration",
+            "8h",
+            "--output",
+            "night.wav",
+            "--chapters",
+            "30m",
+        ])
+        .unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Render { chapters: Some(every), .. }) if every == Duration::from_secs(1800)
+        ));
+        assert!(Args::try_parse_from(["whitenoise", "render", "--duration", "1h"]).is_err());
+        assert!(Args::try_parse_from(["whitenoise", "render", "--output", "noise.wav"]).is_err());
+
+        let args = Args::try_parse_from([
+            "whitenoise",
+            "render",
+            "--all-presets",
+            "--duration",
+            "10m",
+            "--out-dir",
+            "renders",
+        ])
+        .unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Render { all_presets: true, output: None, out_dir: Some(ref dir), .. })
+                if dir.as_os_str() == "renders"
+        ));
+        for extra in [
+            &["--all-presets"][..],
+            &["--out-dir", "renders"],
+            &["--all-presets", "--out-dir", "renders", "--output", "a.wav"],
+        ] {
+            let mut line = vec!["whitenoise", "render", "--duration", "10m"];
+            line.extend(extra);
+            assert!(Args::try_parse_from(line).is_err(), "{extra:?}");
+        }
+    }
+
+    #[test]
+    fn start_volume_comes_from_the_flag_then_the_sound_then_the_file() {
+        let plain = Args::try_parse_from(["whitenoise"]).unwrap();
+        let flagged =
+            Args::try_parse_from(["whitenoise", "--volume", "30", "--fade-in", "10s"]).unwrap();
+        assert_eq!(flagged.fade_in, Some(Duration::from_secs(10)));
+        let mut file = SettingsFile::default();
+
+        // The interface starts silent and headless playback as it was left.
+        assert_eq!(start_volume(&plain, &file, false, false), Some(0.0));
+        assert_eq!(start_volume(&plain, &file, true, false), None);
+        assert_eq!(start_volume(&plain, &file, false, true), None);
+        assert_eq!(start_volume(&flagged, &file, false, true), Some(0.3));
+
+        file.restore_last_volume = true;
+        assert_eq!(start_volume(&plain, &file, false, false), None);
+        file.start_volume = Some(0.25);
+        assert_eq!(start_volume(&plain, &file, false, false), Some(0.25));
+        assert_eq!(start_volume(&plain, &file, true, false), Some(0.25));
+        assert_eq!(start_volume(&plain, &file, true, true), None);
+        assert_eq!(start_volume(&flagged, &file, true, false), Some(0.3));
+    }
+
+    #[test]
+    fn kiosk_needs_a_scene_and_an_interface() {
+        let args =
+            Args::try_parse_from(["whitenoise", "--kiosk", "--scene", "waiting-room"]).unwrap();
+        assert!(args.kiosk);
+        for line in [
+            &["--kiosk"][..],
+            &["--kiosk", "--scene", "rain", "--non-interactive"],
+            &["--kiosk", "--scene", "rain", "--exam-mode"],
+        ] {
+            let mut full = vec!["whitenoise"];
+            full.extend(line);
+            assert!(Args::try_parse_from(full).is_err(), "{line:?}");
+        }
+    }
+
+    #[test]
+    fn ctl_subcommand_passes_the_rest_of_the_line_as_the_value() {
+        let args = Args::try_parse_from([
+            "whitenoise",
+            "--socket",
+            "/tmp/wn.sock",
+            "ctl",
+            "tilt",
+            "mid",
+            "-2.5",
+        ])
+        .unwrap();
+        assert_eq!(socket_path(&args), PathBuf::from("/tmp/wn.sock"));
+        assert!(matches!(
+            args.command,
+            Some(Command::Ctl { ref cmd, ref value }) if cmd == "tilt" && value == &["mid", "-2.5"]
+        ));
+        let args = Args::try_parse_from(["whitenoise", "ctl", "status"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Ctl { ref value, .. }) if value.is_empty()));
+        assert!(Args::try_parse_from(["whitenoise", "ctl"]).is_err());
+    }
+
+    #[test]
+    fn an_instance_or_a_config_file_keeps_its_own_settings_and_socket() {
+        let args =
+            Args::try_parse_from(["whitenoise", "--instance", "bedroom", "ctl", "status"]).unwrap();
+        assert_eq!(
+            chosen_config_path(&args),
+            Some(instance_config_path("bedroom"))
+        );
+        assert!(
+            instance_config_path("bedroom").ends_with("whitenoise/instances/bedroom/settings.toml")
+        );
+        assert_eq!(socket_path(&args), control::instance_socket_path("bedroom"));
+
+        let args = Args::try_parse_from(["whitenoise", "--config", "/srv/office.toml"]).unwrap();
+        assert_eq!(
+            chosen_config_path(&args).as_deref(),
+            Some(Path::new("/srv/office.toml"))
+        );
+        assert_eq!(socket_path(&args), control::settings_socket_path());
+        let args = Args::try_parse_from([
+            "whitenoise",
+            "--instance",
+            "office",
+            "--socket",
+            "/tmp/wn.sock",
+        ])
+        .unwrap();
+        assert_eq!(socket_path(&args), Path::new("/tmp/wn.sock"));
+
+        assert_eq!(chosen_config_path(&Args::parse_from(["whitenoise"])), None);
+        assert!(Args::try_parse_from(["whitenoise", "--instance", "bed room"]).is_err());
+        assert!(
+            Args::try_parse_from(["whitenoise", "--instance", "a", "--config", "b.toml"]).is_err()
+        );
+        assert!(Args::try_parse_from(["whitenoise", "--per-session", "ctl", "status"]).is_ok());
+        assert!(Args::try_parse_from(["whitenoise", "--per-session", "--instance", "a"]).is_err());
+        assert!(
+            Args::try_parse_from(["whitenoise", "--per-session", "--config", "b.toml"]).is_err()
+        );
+    }
+
+    #[test]
+    fn daemon_subcommand_takes_a_pidfile_and_installs_its_service() {
+        let args = Args::try_parse_from([
+            "whitenoise",
+            "--volume",
+            "20",
+            "daemon",
+            "--pidfile",
+            "/tmp/wn.pid",
+        ])
+        .unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Daemon { ref pidfile, install_service: false })
+                if pidfile.as_deref() == Some(Path::new("/tmp/wn.pid"))
+        ));
+        let args = Args::try_parse_from(["whitenoise", "daemon", "--install-service"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Daemon {
+                pidfile: None,
+                install_service: true
+            })
+        ));
+    }
+
+    #[test]
+    fn repeated_devices_play_the_same_sound() {
+        let args = Args::try_parse_from(["whitenoise", "-d", "DAC", "--device", "JBL Go"]).unwrap();
+        assert_eq!(args.device, ["DAC", "JBL Go"]);
+        let file = SettingsFile {
+            output_devices: vec!["Speaker".to_owned()],
+            ..SettingsFile::default()
+        };
+        assert_eq!(output_devices(&args, &file), ["DAC", "JBL Go"]);
+        let args = Args::try_parse_from(["whitenoise"]).unwrap();
+        assert_eq!(output_devices(&args, &file), ["Speaker"]);
+    }
+
+    #[test]
+    fn calibrate_subcommand_takes_an_input_device() {
+        let args = Args::try_parse_from([
+            "whitenoise",
+            "--device",
+            "JBL",
+            "calibrate",
+            "--input",
+            "USB mic",
+            "--yes",
+        ])
+        .unwrap();
+        assert_eq!(args.device, ["JBL"]);
+        assert!(matches!(
+            args.command,
+            Some(Command::Calibrate { ref input, yes: true }) if input.as_deref() == Some("USB mic")
+        ));
+        let args = Args::try_parse_from(["whitenoise", "calibrate"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Calibrate {
+                input: None,
+                yes: false
+            })
+        ));
+    }
+
+    #[test]
+    fn json_goes_with_a_listing_or_status() {
+        let args = Args::try_parse_from(["whitenoise", "--list-devices", "--json"]).unwrap();
+        assert!(args.list_devices && args.json);
+        assert!(Args::try_parse_from(["whitenoise", "--list-hosts", "--json"]).is_ok());
+        assert!(Args::try_parse_from(["whitenoise", "--json"]).is_err());
+        let args = Args::try_parse_from(["whitenoise", "status", "--json"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Status { json: true })));
+        let args = Args::try_parse_from(["whitenoise", "--instance", "nursery", "status"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Status { json: false })
+        ));
+        let args = Args::try_parse_from(["whitenoise", "--preset", "Sleep", "describe"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Describe)));
+        assert_eq!(args.preset.as_deref(), Some("Sleep"));
+    }
+
+    #[test]
+    fn top_subcommand_takes_playback_options_before_it() {
+        let args = Args::try_parse_from(["whitenoise", "--volume", "20", "--style", "rain", "top"])
+            .unwrap();
+        assert!(matches!(args.command, Some(Command::Top)));
+        assert_eq!(args.volume, Some(0.2));
+        assert_eq!(args.style.as_deref(), Some("rain"));
+
+        let args = Args::try_parse_from(["whitenoise", "--tone", "binaural", "top"]).unwrap();
+        assert_eq!(args.tone, Some(ToneMode::Binaural));
+        let args = Args::try_parse_from(["whitenoise", "--room-tone", "15"]).unwrap();
+        assert_eq!(args.room_tone, Some(0.15));
+        let args = Args::try_parse_from(["whitenoise", "--room-tone", "off"]).unwrap();
+        assert_eq!(args.room_tone, Some(0.0));
+        assert!(Args::try_parse_from(["whitenoise", "--room-tone", "loud"]).is_err());
+        assert!(Args::try_parse_from(["whitenoise", "--tone", "gamma"]).is_err());
+
+        let args = Args::try_parse_from(["whitenoise", "--ceiling", "8kHz"]).unwrap();
+        assert_eq!(args.ceiling, Some(Some(8_000.0)));
+        let args = Args::try_parse_from(["whitenoise", "--ceiling", "OFF"]).unwrap();
+        assert_eq!(args.ceiling, Some(None));
+        for bad in ["500", "30k", "loud"] {
+            assert!(Args::try_parse_from(["whitenoise", "--ceiling", bad]).is_err());
+        }
+        assert!(Args::try_parse_from(["whitenoise", "--ceiling"]).is_err());
+
+        let args = Args::try_parse_from(["whitenoise", "--duck-on-playback"]).unwrap();
+        assert!(args.duck_on_playback);
+        assert_eq!(args.duck_db, DuckMode::Lower(15.0));
+        let args = Args::try_parse_from(["whitenoise", "--duck-on-playback", "--duck-db", "pause"])
+            .unwrap();
+        assert_eq!(args.duck_db, DuckMode::Pause);
+        assert!(Args::try_parse_from(["whitenoise", "--duck-db", "20"]).is_err());
+        let args = Args::try_parse_from(["whitenoise", "--duck-on-calls"]).unwrap();
+        assert!(args.duck_on_calls && !args.duck_on_playback);
+        assert_eq!(args.call_duck_db, DuckMode::Lower(20.0));
+        assert!(Args::try_parse_from(["whitenoise", "--call-duck-db", "30"]).is_err());
+        let args = Args::try_parse_from([
+            "whitenoise",
+            "--ambient-level",
+            "--ambient-input",
+            "USB Mic",
+        ])
+        .unwrap();
+        assert!(args.ambient_level);
+        assert_eq!(args.ambient_input.as_deref(), Some("USB Mic"));
+        assert!(Args::try_parse_from(["whitenoise", "--ambient-input", "USB Mic"]).is_err());
+
+        let args =
+            Args::try_parse_from(["whitenoise", "--notch", "6.5k", "--notch-width", "0.5oct"])
+                .unwrap();
+        let mut settings = AudioSettings::default();
+        apply_source_overrides(&args, None, &mut settings);
+        assert_eq!(settings.notch.hz, Some(6_500.0));
+        assert_eq!(settings.notch.width_octaves, 0.5);
+        for (flag, bad) in [("--notch", "20k"), ("--notch-width", "3")] {
+            assert!(Args::try_parse_from(["whitenoise", flag, bad]).is_err());
+        }
+
+        let args = Args::try_parse_from([
+            "whitenoise",
+            "--high-pass",
+            "40",
+            "--high-pass-slope",
+            "12dB",
+        ])
+        .unwrap();
+        assert_eq!(args.high_pass, Some(Some(40.0)));
+        assert_eq!(args.high_pass_slope, Some(12));
+        let args = Args::try_parse_from(["whitenoise", "--high-pass", "off"]).unwrap();
+        assert_eq!(args.high_pass, Some(None));
+        assert!(Args::try_parse_from(["whitenoise", "--high-pass", "8k"]).is_err());
+        assert!(Args::try_parse_from(["whitenoise", "--high-pass-slope", "18"]).is_err());
+    }
+
+    #[test]
+    fn the_profile_flag_applies_before_the_individual_options() {
+        let args = Args::try_parse_from([
+            "whitenoise",
+            "--profile",
+            "small-speaker",
+            "--width",
+            "wide",
+        ])
+        .unwrap();
+        let mut settings = AudioSettings::default();
+        apply_source_overrides(&args, None, &mut settings);
+        assert_eq!(settings.high_pass.hz, Some(SMALL_SPEAKER_HIGH_PASS_HZ));
+        assert_eq!(settings.stereo_width, 1.0);
+        assert!(Args::try_parse_from(["whitenoise", "--profile", "earbuds"]).is_err());
+    }
+
+    #[test]
+    fn device_choices_are_not_saved_unless_changed_during_the_session() {
+        let chosen = AudioSettings::default();
+        let mut device = chosen;
+        OutputProfile::Headphones.apply(&mut device);
+        device.high_pass.hz = Some(SMALL_SPEAKER_HIGH_PASS_HZ);
+
+        let untouched = keep_device_choices_local(device, &chosen, &device);
+        assert_eq!(untouched.high_pass, chosen.high_pass);
+        assert_eq!(untouched.crossfeed, chosen.crossfeed);
+        assert_eq!(untouched.listening_contour, chosen.listening_contour);
+
+        // A value the user moved away from the device's is theirs to keep.
+        let mut changed = device;
+        changed.crossfeed = false;
+        changed.stereo_width = 0.8;
+        let saved = keep_device_choices_local(changed, &chosen, &device);
+        assert!(!saved.crossfeed);
+        assert_eq!(saved.stereo_width, 0.8);
+        assert_eq!(saved.high_pass, chosen.high_pass);
+    }
+
+    #[test]
+    fn a_reload_plays_the_file_but_keeps_the_run_and_the_device() {
+        let chosen = AudioSettings::default();
+        let mut device = chosen;
+        device.high_pass.hz = Some(SMALL_SPEAKER_HIGH_PASS_HZ);
+        let mut saver = SessionSaver {
+            file: SettingsFile::default(),
+            chosen,
+            device,
+            starting_loops: chosen.loops,
+            saved: device,
+        };
+        let mut file = SettingsFile::default();
+        file.audio.volume = 0.35;
+        file.output.crossfeed = true;
+        let mut playing = AudioSettings {
+            paused: true,
+            ..device
+        };
+        saver.reload(file, &mut playing);
+        assert_eq!(playing.volume, 0.35);
+        assert!(playing.crossfeed && playing.paused);
+        assert_eq!(playing.high_pass.hz, Some(SMALL_SPEAKER_HIGH_PASS_HZ));
+        // Nothing moved since, so the autosave leaves the file alone.
+        assert!(saver.save_if_changed(playing).is_ok());
+        assert_eq!(saver.file.audio.volume, 0.35);
+        assert_eq!(
+            keep_device_choices_local(playing, &saver.chosen, &saver.device).high_pass,
+            chosen.high_pass
+        );
+    }
+
+    #[test]
+    fn presets_subcommand_and_preset_flag_parse() {
+        let args = Args::try_parse_from(["whitenoise", "--preset", "focus"]).unwrap();
+        assert_eq!(args.preset.as_deref(), Some("focus"));
+        assert!(args.command.is_none());
+
+        let args =
+            Args::try_parse_from(["whitenoise", "--mix", "rain=60", "presets", "save", "wet"])
+                .unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Presets { action: PresetsCommand::Save { ref name } }) if name == "wet"
+        ));
+        assert!(matches!(
+            Args::try_parse_from(["whitenoise", "presets", "list"])
+                .unwrap()
+                .command,
+            Some(Command::Presets {
+                action: PresetsCommand::List
+            })
+        ));
+        assert!(Args::try_parse_from(["whitenoise", "presets", "delete"]).is_err());
+        assert!(matches!(
+            Args::try_parse_from(["whitenoise", "preset", "export", "focus"])
+                .unwrap()
+                .command,
+            Some(Command::Presets { action: PresetsCommand::Export { ref name } }) if name == "focus"
+        ));
+        assert!(matches!(
+            Args::try_parse_from(["whitenoise", "presets", "import", "-", "--name", "shared", "--force"])
+                .unwrap()
+                .command,
+            Some(Command::Presets {
+                action: PresetsCommand::Import { ref file, name: Some(ref name), force: true }
+            }) if file.as_os_str() == "-" && name == "shared"
+        ));
+        assert!(matches!(
+            Args::try_parse_from(["whitenoise", "schedule"])
+                .unwrap()
+                .command,
+            Some(Command::Schedule)
+        ));
+    }
+
     #[test]
     fn mix_parser_accepts_pairs_and_whitespace() {
         let mix = parse_mix("rain=60, brown=40").unwrap();
@@ -252,12 +2907,71 @@ mod tests {
         // The legacy source name still works, matching --style.
         let legacy = parse_mix("vanilla=50").unwrap();
         assert!((legacy.white - 0.5).abs() < 1e-6);
+
+        let layered = parse_mix("rain=60,sample=30").unwrap();
+        assert!((layered.sample - 0.3).abs() < 1e-6);
+        assert!((layered.rain - 0.6).abs() < 1e-6);
+
+        let campsite = parse_mix("fire=50,wind=20").unwrap();
+        assert!((campsite.fire - 0.5).abs() < 1e-6);
+        assert!((campsite.wind - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn styles_name_sources_the_sample_loop_and_presets() {
+        let presets = std::env::temp_dir()
+            .join(format!("whitenoise-main-test-{}", std::process::id()))
+            .join("presets");
+        let mut evening = AudioSettings::default();
+        evening.set_mix(parse_mix("rain=60,brown=40").unwrap());
+        save_preset(&presets, "evening", &evening).unwrap();
+        let sample = Path::new("/home/me/Creek.wav");
+
+        assert_eq!(
+            resolve_style("Vanilla", None, &presets).unwrap(),
+            SourceMix::solo(SoundStyle::White)
+        );
+        assert_eq!(
+            resolve_style("creek", Some(sample), &presets).unwrap(),
+            SourceMix::solo(SoundStyle::Sample)
+        );
+        assert_eq!(
+            resolve_style(" Evening ", Some(sample), &presets).unwrap(),
+            evening.mix()
+        );
+        assert_eq!(
+            resolve_style("ocean", Some(sample), &presets).unwrap(),
+            SourceMix::solo(SoundStyle::Ocean)
+        );
+        let error = resolve_style("thunder", Some(sample), &presets)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            error,
+            "no style named 'thunder'; choose from white, pink, brown, blue, violet, rain, ocean, wind, fire, fan, sample, Creek (the sample loop), evening (preset)"
+        );
+        // Without the loop, its name is neither accepted nor offered.
+        let error = resolve_style("creek", None, &presets).unwrap_err();
+        assert!(!error.to_string().contains("Creek"));
+        let _ = std::fs::remove_dir_all(presets.parent().unwrap());
+    }
+
+    #[test]
+    fn a_mix_of_only_the_unloaded_sample_is_refused() {
+        let mut settings = AudioSettings::default();
+        settings.set_mix(SourceMix::solo(SoundStyle::Sample));
+        assert!(check_sample_source(&settings, false).is_err());
+        assert!(check_sample_source(&settings, true).is_ok());
+
+        // Beside another source it only earns a note.
+        settings.set_mix(parse_mix("rain=60,sample=30").unwrap());
+        assert!(check_sample_source(&settings, false).is_ok());
     }
 
     #[test]
     fn mix_parser_rejects_malformed_input() {
         assert!(parse_mix("rain").is_err());
-        assert!(parse_mix("ocean=50").is_err());
+        assert!(parse_mix("thunder=50").is_err());
         assert!(parse_mix("rain=60,rain=40").is_err());
         assert!(parse_mix("rain=101").is_err());
         assert!(parse_mix("rain=-5").is_err());
@@ -0,0 +1,335 @@
+//! Mirrors: the same sound on more devices than one, such as the living
+//! room's DAC and a Bluetooth speaker. The first device's callback renders
+//! as always and hands each block to the monitor's `MirrorTap`, which copies
+//! it into one ring per extra device without locking or allocating. Each
+//! extra device runs a stream of its own at its own sample rate, and its
+//! callback reads the ring through a `MirrorReader`, interpolating between
+//! frames. No two sound cards keep quite the same time, so the reader
+//! plays a little faster while its ring fills past the target latency and
+//! a little slower while it runs low, and a ring that runs dry goes silent
+//! until it has filled again.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use cpal::{Device, FromSample, Sample, SampleFormat, Stream, StreamConfig};
+
+use crate::audio::{AudioMonitor, FillOutput, Frame, open_stream, write_interleaved_frames};
+
+// Samples each ring holds: over two seconds of stereo at 48 kHz.
+const RING_SAMPLES: usize = 1 << 18;
+/// How far behind the first device a mirror plays, in seconds of ring.
+pub const MIRROR_LATENCY_SECONDS: f64 = 0.1;
+// How quickly the reader's view of its ring's fill follows the fill, per
+// buffer, and how far it may bend the playback rate to hold the target.
+// Two clocks are usually within 0.01% of each other.
+const FILL_SMOOTHING: f64 = 0.02;
+const CORRECTION_PER_LATENCY: f64 = 0.01;
+const MAX_CORRECTION: f64 = 0.005;
+// The fade in after a (re)start, in seconds, so joining mid-sound does not
+// click.
+const FADE_IN_SECONDS: f32 = 0.05;
+
+/// Interleaved stereo samples between the first device's callback, which
+/// writes, and one mirror's callback, which reads. Each side only ever
+/// moves its own counter.
+#[derive(Debug)]
+struct MirrorRing {
+    samples: Box<[AtomicU32]>,
+    written: AtomicUsize,
+    read: AtomicUsize,
+    // The rate of the latest block, which changes when the first device
+    // is switched to another.
+    sample_rate: AtomicU32,
+    dropped: AtomicU64,
+}
+
+impl MirrorRing {
+    fn new(capacity: usize) -> Self {
+        Self {
+            samples: (0..capacity).map(|_| AtomicU32::new(0)).collect(),
+            written: AtomicUsize::new(0),
+            read: AtomicUsize::new(0),
+            sample_rate: AtomicU32::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Copies in what fits; the rest is dropped and counted.
+    fn push(&self, frames: &[Frame], sample_rate: u32) {
+        self.sample_rate.store(sample_rate, Ordering::Relaxed);
+        let capacity = self.samples.len();
+        let written = self.written.load(Ordering::Relaxed);
+        let free = capacity - written.wrapping_sub(self.read.load(Ordering::Acquire));
+        let fits = frames.len().min(free / 2);
+        for (index, sample) in frames[..fits].iter().flatten().enumerate() {
+            self.samples[written.wrapping_add(index) % capacity]
+                .store(sample.to_bits(), Ordering::Relaxed);
+        }
+        self.written
+            .store(written.wrapping_add(fits * 2), Ordering::Release);
+        self.dropped
+            .fetch_add((frames.len() - fits) as u64, Ordering::Relaxed);
+    }
+
+    fn frame(&self, position: usize) -> Frame {
+        let capacity = self.samples.len();
+        [0, 1].map(|channel| {
+            f32::from_bits(
+                self.samples[position.wrapping_add(channel) % capacity].load(Ordering::Relaxed),
+            )
+        })
+    }
+}
+
+/// Where the audio callback copies its output for the mirrored devices.
+#[derive(Debug, Default)]
+pub struct MirrorTap {
+    active: AtomicBool,
+    // Locked by the callback only with try_lock, and otherwise only when a
+    // mirror's stream opens or closes.
+    rings: Mutex<Vec<Arc<MirrorRing>>>,
+}
+
+impl MirrorTap {
+    pub(crate) fn push(&self, frames: &[Frame], sample_rate: f32) {
+        if !self.active.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Ok(rings) = self.rings.try_lock() {
+            for ring in rings.iter() {
+                ring.push(frames, sample_rate as u32);
+            }
+        }
+    }
+
+    /// Frames dropped for mirrors that fell behind, such as one whose
+    /// device went away, since the start.
+    pub fn dropped_frames(&self) -> u64 {
+        self.rings()
+            .iter()
+            .map(|ring| ring.dropped.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    fn attach(&self, ring: &Arc<MirrorRing>) {
+        let mut rings = self.rings();
+        rings.push(Arc::clone(ring));
+        self.active.store(true, Ordering::Relaxed);
+    }
+
+    fn detach(&self, ring: &Arc<MirrorRing>) {
+        let mut rings = self.rings();
+        rings.retain(|attached| !Arc::ptr_eq(attached, ring));
+        self.active.store(!rings.is_empty(), Ordering::Relaxed);
+    }
+
+    fn rings(&self) -> std::sync::MutexGuard<'_, Vec<Arc<MirrorRing>>> {
+        self.rings
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// One mirrored device's side of its ring: reads the first device's frames
+/// at this device's rate and channel count. Dropping it, with the stream
+/// that owns it, takes its ring off the tap.
+pub struct MirrorReader {
+    monitor: Arc<AudioMonitor>,
+    ring: Arc<MirrorRing>,
+    // The reader's own copy of `ring.read`, published after each buffer.
+    cursor: usize,
+    sample_rate: f64,
+    channels: usize,
+    // The two frames the output falls between, and how far between.
+    previous: Frame,
+    next: Frame,
+    fraction: f64,
+    // The ring's fill in frames, smoothed, for the rate correction.
+    fill: f64,
+    primed: bool,
+    gain: f32,
+}
+
+impl MirrorReader {
+    pub fn new(monitor: Arc<AudioMonitor>, sample_rate: u32, channels: usize) -> Self {
+        let ring = Arc::new(MirrorRing::new(RING_SAMPLES));
+        monitor.mirrors().attach(&ring);
+        Self {
+            monitor,
+            ring,
+            cursor: 0,
+            sample_rate: f64::from(sample_rate.max(1)),
+            channels: channels.max(1),
+            previous: [0.0; 2],
+            next: [0.0; 2],
+            fraction: 0.0,
+            fill: 0.0,
+            primed: false,
+            gain: 0.0,
+        }
+    }
+
+    /// How far the playback rate is bent to hold the target latency, as a
+    /// factor on the source rate.
+    fn correction(&self, target: f64) -> f64 {
+        let error = (self.fill - target) / target;
+        1.0 + (error * CORRECTION_PER_LATENCY).clamp(-MAX_CORRECTION, MAX_CORRECTION)
+    }
+}
+
+impl FillOutput for MirrorReader {
+    fn fill<T>(&mut self, data: &mut [T])
+    where
+        T: Sample + FromSample<f32>,
+    {
+        let source_rate = f64::from(self.ring.sample_rate.load(Ordering::Relaxed));
+        let written = self.ring.written.load(Ordering::Acquire);
+        let available = written.wrapping_sub(self.cursor) as f64 / 2.0;
+        let target = source_rate * MIRROR_LATENCY_SECONDS;
+        if !self.primed {
+            if source_rate <= 0.0 || available < target {
+                data.fill(T::from_sample(0.0));
+                return;
+            }
+            // Anything past the target is old: skip to it.
+            let skipped = (available - target) as usize;
+            self.cursor = self.cursor.wrapping_add(skipped * 2);
+            self.primed = true;
+            self.gain = 0.0;
+            self.fill = available - skipped as f64;
+        }
+        let available = written.wrapping_sub(self.cursor) as f64 / 2.0;
+        self.fill += (available - self.fill) * FILL_SMOOTHING;
+        let step = source_rate / self.sample_rate * self.correction(target);
+        let fade_step = 1.0 / (FADE_IN_SECONDS * self.sample_rate as f32);
+        let Self {
+            ring,
+            cursor,
+            previous,
+            next,
+            fraction,
+            primed,
+            gain,
+            channels,
+            ..
+        } = self;
+        write_interleaved_frames(data, *channels, || {
+            if !*primed {
+                return [0.0; 2];
+            }
+            let between = *fraction as f32;
+            let frame = [0, 1].map(|channel| {
+                (previous[channel] + (next[channel] - previous[channel]) * between) * *gain
+            });
+            *gain = (*gain + fade_step).min(1.0);
+            *fraction += step;
+            while *fraction >= 1.0 {
+                *fraction -= 1.0;
+                if *cursor == written {
+                    // Dry: silence until the ring holds the target again.
+                    *primed = false;
+                    break;
+                }
+                *previous = *next;
+                *next = ring.frame(*cursor);
+                *cursor = cursor.wrapping_add(2);
+            }
+            frame
+        });
+        self.ring.read.store(self.cursor, Ordering::Release);
+    }
+}
+
+impl Drop for MirrorReader {
+    fn drop(&mut self) {
+        self.monitor.mirrors().detach(&self.ring);
+    }
+}
+
+/// Opens a stream on `device` that plays what `monitor`'s callback renders
+/// for the first device, setting `lost` when the stream stops playing.
+pub fn build_mirror_stream(
+    device: &Device,
+    config: StreamConfig,
+    sample_format: SampleFormat,
+    monitor: &Arc<AudioMonitor>,
+    lost: Arc<AtomicBool>,
+) -> Result<Stream> {
+    let reader = MirrorReader::new(
+        Arc::clone(monitor),
+        config.sample_rate,
+        usize::from(config.channels),
+    );
+    open_stream(device, config, sample_format, reader, move || {
+        lost.store(true, Ordering::Relaxed);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A ramp on the left and its negative on the right, one step a frame.
+    fn blocks(start: usize, count: usize) -> Vec<Frame> {
+        (start..start + count)
+            .map(|index| [index as f32, -(index as f32)])
+            .collect()
+    }
+
+    #[test]
+    fn a_mirror_waits_for_its_latency_then_follows_the_source() {
+        let monitor = Arc::new(AudioMonitor::default());
+        let mut reader = MirrorReader::new(Arc::clone(&monitor), 48_000, 2);
+        let mut data = [1.0_f32; 512];
+        reader.fill(&mut data);
+        assert!(data.iter().all(|sample| *sample == 0.0));
+
+        // Under 100 ms at 48 kHz is not enough to start.
+        monitor.mirrors().push(&blocks(0, 4_000), 48_000.0);
+        reader.fill(&mut data);
+        assert!(data.iter().all(|sample| *sample == 0.0));
+
+        monitor.mirrors().push(&blocks(4_000, 4_000), 48_000.0);
+        let mut long = vec![0.0_f32; 2 * 4_000];
+        reader.fill(&mut long);
+        // Past the fade in, at the same rate, one source frame per frame,
+        // the right channel mirroring the left.
+        let frames: Vec<&[f32]> = long.chunks(2).collect();
+        for pair in frames[2_500..].windows(2) {
+            assert!((pair[1][0] - pair[0][0] - 1.0).abs() < 0.01, "{pair:?}");
+            assert_eq!(pair[1][1], -pair[1][0]);
+        }
+        assert_eq!(monitor.mirrors().dropped_frames(), 0);
+
+        // Dropping the reader takes its ring off the tap.
+        drop(reader);
+        assert!(!monitor.mirrors().active.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn a_mirror_resamples_and_bends_its_rate_to_hold_the_latency() {
+        let monitor = Arc::new(AudioMonitor::default());
+        // A mono device at 44.1 kHz under a 48 kHz source.
+        let mut reader = MirrorReader::new(Arc::clone(&monitor), 44_100, 1);
+        monitor.mirrors().push(&blocks(0, 4_800), 48_000.0);
+        let mut data = vec![0.0_f32; 4_000];
+        reader.fill(&mut data);
+        let step = data[3_999] - data[3_998];
+        assert!((step - 48_000.0 / 44_100.0).abs() < 0.01, "{step}");
+
+        // A ring far past its target plays a little faster, at most 0.5%.
+        reader.fill = 48_000.0;
+        let fast = reader.correction(4_800.0);
+        assert!(fast > 1.0 && fast <= 1.005, "{fast}");
+        reader.fill = 2_400.0;
+        assert!(reader.correction(4_800.0) < 1.0);
+
+        // Running dry goes silent until the ring has filled again.
+        let mut rest = vec![1.0_f32; 4_410];
+        reader.fill(&mut rest);
+        assert_eq!(*rest.last().unwrap(), 0.0);
+        assert!(!reader.primed);
+    }
+}
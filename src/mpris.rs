@@ -0,0 +1,347 @@
+//! MPRIS on the D-Bus session bus, so desktop media keys, `playerctl`, and
+//! panel widgets can pause, resume, and change the volume of a running
+//! player. There are no tracks: the title is the current mix, and seeking
+//! and skipping are not offered.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use zbus::blocking::Connection;
+use zbus::blocking::connection::Builder;
+use zbus::interface;
+use zbus::zvariant::{ObjectPath, Value};
+
+use crate::settings::{AudioSettings, SettingsGuard, SharedSettings};
+
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.whitenoise";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const TRACK_ID: &str = "/org/whitenoise/Mix";
+
+/// How often changes made elsewhere (the UI, `ctl`, a preset) are looked
+/// for and announced to the bus.
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Keeps the bus name while playback runs; dropping it leaves the bus.
+pub struct MediaKeys {
+    _connection: Connection,
+}
+
+/// Claims the MPRIS name on the session bus. A second player takes the
+/// `.instance<pid>` name the specification reserves for that case.
+pub fn serve(settings: Arc<SharedSettings>, running: Arc<AtomicBool>) -> Result<MediaKeys> {
+    let connection = match connect(BUS_NAME, &settings, &running) {
+        Err(zbus::Error::NameTaken) => connect(
+            &format!("{BUS_NAME}.instance{}", std::process::id()),
+            &settings,
+            &running,
+        ),
+        other => other,
+    }
+    .context("failed to register on the D-Bus session bus")?;
+
+    let watcher = connection.clone();
+    std::thread::spawn(move || {
+        let mut announced = State::of(&lock(&settings));
+        while running.load(Ordering::Relaxed) {
+            std::thread::sleep(WATCH_INTERVAL);
+            let state = State::of(&lock(&settings));
+            if state != announced {
+                let _ = announce(&watcher, &state);
+                announced = state;
+            }
+        }
+    });
+    Ok(MediaKeys {
+        _connection: connection,
+    })
+}
+
+fn connect(
+    name: &str,
+    settings: &Arc<SharedSettings>,
+    running: &Arc<AtomicBool>,
+) -> zbus::Result<Connection> {
+    Builder::session()?
+        .name(name)?
+        .serve_at(
+            OBJECT_PATH,
+            Root {
+                running: Arc::clone(running),
+            },
+        )?
+        .serve_at(
+            OBJECT_PATH,
+            Player {
+                settings: Arc::clone(settings),
+            },
+        )?
+        .build()
+}
+
+/// What the Player interface reports and announces when it changes.
+#[derive(Debug, Clone, PartialEq)]
+struct State {
+    paused: bool,
+    volume: f32,
+    title: String,
+}
+
+impl State {
+    fn of(settings: &AudioSettings) -> Self {
+        Self {
+            paused: settings.paused,
+            volume: settings.volume,
+            title: settings.mix().describe(),
+        }
+    }
+
+    fn playback_status(&self) -> &'static str {
+        if self.paused { "Paused" } else { "Playing" }
+    }
+
+    fn metadata(&self) -> HashMap<&'static str, Value<'static>> {
+        HashMap::from([
+            (
+                "mpris:trackid",
+                Value::from(ObjectPath::from_static_str_unchecked(TRACK_ID)),
+            ),
+            ("xesam:title", Value::from(self.title.clone())),
+        ])
+    }
+}
+
+/// `PropertiesChanged` for the Player properties that can change.
+fn announce(connection: &Connection, state: &State) -> zbus::Result<()> {
+    let changed = HashMap::from([
+        ("PlaybackStatus", Value::from(state.playback_status())),
+        ("Volume", Value::from(f64::from(state.volume))),
+        ("Metadata", Value::from(state.metadata())),
+    ]);
+    connection.emit_signal(
+        None::<()>,
+        OBJECT_PATH,
+        "org.freedesktop.DBus.Properties",
+        "PropertiesChanged",
+        &(PLAYER_INTERFACE, changed, Vec::<&str>::new()),
+    )
+}
+
+fn lock(settings: &SharedSettings) -> SettingsGuard<'_> {
+    settings
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+struct Root {
+    running: Arc<AtomicBool>,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) {}
+
+    fn quit(&self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> &str {
+        "whitenoise"
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+struct Player {
+    settings: Arc<SharedSettings>,
+}
+
+impl Player {
+    fn state(&self) -> State {
+        State::of(&lock(&self.settings))
+    }
+}
+
+/// Stop pauses too: the stream stays open either way, and Play resumes.
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    fn play(&self) {
+        lock(&self.settings).paused = false;
+    }
+
+    fn pause(&self) {
+        lock(&self.settings).paused = true;
+    }
+
+    fn play_pause(&self) {
+        let mut settings = lock(&self.settings);
+        settings.paused = !settings.paused;
+    }
+
+    fn stop(&self) {
+        self.pause();
+    }
+
+    fn next(&self) {}
+
+    fn previous(&self) {}
+
+    fn seek(&self, _offset: i64) {}
+
+    fn set_position(&self, _track_id: ObjectPath<'_>, _position: i64) {}
+
+    fn open_uri(&self, _uri: &str) {}
+
+    #[zbus(property)]
+    fn playback_status(&self) -> &str {
+        self.state().playback_status()
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<&'static str, Value<'static>> {
+        self.state().metadata()
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        f64::from(lock(&self.settings).volume)
+    }
+
+    #[zbus(property)]
+    fn set_volume(&self, volume: f64) {
+        // The specification treats negative volumes as 0; whitenoise has
+        // no headroom above 1.
+        lock(&self.settings).volume = if volume.is_finite() {
+            volume.clamp(0.0, 1.0) as f32
+        } else {
+            0.0
+        };
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn position(&self) -> i64 {
+        0
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{SoundStyle, SourceMix};
+
+    #[test]
+    fn media_keys_pause_resume_and_set_the_volume() {
+        let settings = Arc::new(SharedSettings::new(AudioSettings {
+            volume: 0.3,
+            ..AudioSettings::default()
+        }));
+        let player = Player {
+            settings: Arc::clone(&settings),
+        };
+        assert_eq!(player.playback_status(), "Playing");
+        player.play_pause();
+        assert!(settings.lock().unwrap().paused);
+        assert_eq!(player.playback_status(), "Paused");
+        player.play_pause();
+        assert!(!settings.lock().unwrap().paused);
+        player.stop();
+        assert_eq!(player.playback_status(), "Paused");
+        player.play();
+        assert_eq!(player.playback_status(), "Playing");
+
+        player.set_volume(0.55);
+        assert_eq!(settings.lock().unwrap().volume, 0.55);
+        player.set_volume(-0.2);
+        assert_eq!(player.volume(), 0.0);
+        player.set_volume(4.0);
+        assert_eq!(player.volume(), 1.0);
+    }
+
+    #[test]
+    fn metadata_names_the_mix_and_changes_with_it() {
+        let mut settings = AudioSettings::default();
+        settings.set_mix(SourceMix::solo(SoundStyle::Rain));
+        let before = State::of(&settings);
+        assert_eq!(
+            before.metadata()["xesam:title"],
+            Value::from(SourceMix::solo(SoundStyle::Rain).describe())
+        );
+
+        settings.set_mix(SourceMix::solo(SoundStyle::Brown));
+        assert_ne!(State::of(&settings), before);
+        settings.set_mix(SourceMix::solo(SoundStyle::Rain));
+        assert_eq!(State::of(&settings), before);
+    }
+}
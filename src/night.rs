@@ -0,0 +1,195 @@
+//! Night limit: a lower output ceiling for part of each day, such as
+//! 22:00 to 07:00, so nothing chosen late at night, a preset or a volume
+//! change alike, can play louder than a level set while awake. A background
+//! thread reads the local clock and hands the ceiling to the engine through
+//! the shared settings.
+
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use chrono::Timelike;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::SharedSettings;
+
+pub const DEFAULT_NIGHT_CEILING_DB: f32 = -20.0;
+pub const MIN_NIGHT_CEILING_DB: f32 = -60.0;
+const MINUTES_PER_DAY: u16 = 24 * 60;
+// The clock is read this often; a window opens within a second of its
+// start, and the engine eases the ceiling in from there.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A time of day to the minute, written `HH:MM` on a 24-hour clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ClockTime {
+    minutes: u16,
+}
+
+impl ClockTime {
+    pub fn new(hour: u16, minute: u16) -> Option<Self> {
+        (hour < 24 && minute < 60).then_some(Self {
+            minutes: hour * 60 + minute,
+        })
+    }
+
+    pub fn now() -> Self {
+        let now = chrono::Local::now();
+        Self {
+            minutes: (now.hour() * 60 + now.minute()) as u16 % MINUTES_PER_DAY,
+        }
+    }
+
+    /// Whether this time falls in the window from `from` until `until`,
+    /// which may run past midnight and covers the whole day when the two
+    /// are the same minute.
+    pub fn within(self, from: Self, until: Self) -> bool {
+        if from < until {
+            from <= self && self < until
+        } else {
+            self >= from || self < until
+        }
+    }
+
+    /// Minutes from `earlier` forward to this time, wrapping past midnight.
+    pub fn minutes_after(self, earlier: Self) -> u16 {
+        (self.minutes + MINUTES_PER_DAY - earlier.minutes) % MINUTES_PER_DAY
+    }
+
+    /// This time moved by `minutes`, either way, wrapping past midnight.
+    pub fn plus_minutes(self, minutes: i32) -> Self {
+        Self {
+            minutes: (i32::from(self.minutes) + minutes).rem_euclid(i32::from(MINUTES_PER_DAY))
+                as u16,
+        }
+    }
+}
+
+impl TryFrom<String> for ClockTime {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        let error = || format!("{text:?} is not a time of day; use HH:MM, such as 22:30");
+        let (hour, minute) = text.trim().split_once(':').ok_or_else(error)?;
+        if minute.len() != 2 {
+            return Err(error());
+        }
+        let (Ok(hour), Ok(minute)) = (hour.parse(), minute.parse()) else {
+            return Err(error());
+        };
+        Self::new(hour, minute).ok_or_else(error)
+    }
+}
+
+impl From<ClockTime> for String {
+    fn from(time: ClockTime) -> Self {
+        time.to_string()
+    }
+}
+
+impl fmt::Display for ClockTime {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "{:02}:{:02}",
+            self.minutes / 60,
+            self.minutes % 60
+        )
+    }
+}
+
+/// The `[night_limit]` table: from `from` until `until`, the output never
+/// peaks above `ceiling_db` dBFS. A window may run past midnight; one that
+/// starts and ends at the same minute covers the whole day.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NightLimit {
+    pub from: ClockTime,
+    pub until: ClockTime,
+    #[serde(default = "default_ceiling_db")]
+    pub ceiling_db: f32,
+}
+
+fn default_ceiling_db() -> f32 {
+    DEFAULT_NIGHT_CEILING_DB
+}
+
+impl NightLimit {
+    pub fn covers(&self, time: ClockTime) -> bool {
+        time.within(self.from, self.until)
+    }
+
+    /// The ceiling in force at `time`, if any, clamped to -60 to 0 dBFS.
+    pub fn ceiling_at(&self, time: ClockTime) -> Option<f32> {
+        let ceiling_db = if self.ceiling_db.is_finite() {
+            self.ceiling_db.clamp(MIN_NIGHT_CEILING_DB, 0.0)
+        } else {
+            DEFAULT_NIGHT_CEILING_DB
+        };
+        self.covers(time).then_some(ceiling_db)
+    }
+}
+
+/// Keeps `night_ceiling_db` in `settings` in step with the clock until
+/// playback stops.
+pub fn watch(limit: NightLimit, settings: Arc<SharedSettings>, running: Arc<AtomicBool>) {
+    std::thread::spawn(move || {
+        while running.load(Ordering::Relaxed) {
+            let ceiling = limit.ceiling_at(ClockTime::now());
+            settings
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .night_ceiling_db = ceiling;
+            std::thread::sleep(CHECK_INTERVAL);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(text: &str) -> ClockTime {
+        ClockTime::try_from(text.to_owned()).unwrap()
+    }
+
+    #[test]
+    fn clock_times_read_and_write_as_hours_and_minutes() {
+        assert_eq!(time("22:00"), ClockTime::new(22, 0).unwrap());
+        assert_eq!(time(" 7:05 ").to_string(), "07:05");
+        for bad in ["24:00", "12:60", "7", "7:5", "noon", "-1:00"] {
+            assert!(ClockTime::try_from(bad.to_owned()).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn a_window_may_run_past_midnight() {
+        let night = NightLimit {
+            from: time("22:00"),
+            until: time("07:00"),
+            ceiling_db: -20.0,
+        };
+        assert_eq!(night.ceiling_at(time("23:30")), Some(-20.0));
+        assert_eq!(night.ceiling_at(time("00:00")), Some(-20.0));
+        assert_eq!(night.ceiling_at(time("06:59")), Some(-20.0));
+        assert_eq!(night.ceiling_at(time("07:00")), None);
+        assert_eq!(night.ceiling_at(time("21:59")), None);
+
+        let afternoon = NightLimit {
+            from: time("13:00"),
+            until: time("15:00"),
+            ceiling_db: -90.0,
+        };
+        assert!(afternoon.covers(time("14:00")) && !afternoon.covers(time("16:00")));
+        assert_eq!(
+            afternoon.ceiling_at(time("13:00")),
+            Some(MIN_NIGHT_CEILING_DB)
+        );
+        let all_day = NightLimit {
+            until: time("13:00"),
+            ..afternoon
+        };
+        assert!(all_day.covers(time("03:00")));
+    }
+}
@@ -0,0 +1,57 @@
+//! Desktop notifications through the platform's own command-line tool
+//! (`notify-send` on Linux and the BSDs, `osascript` on macOS), so no
+//! notification library or D-Bus binding is linked in.
+
+use std::process::{Command, Stdio};
+use std::thread;
+
+use anyhow::{Context, Result, bail};
+
+/// Shows `body` under `title` without waiting for the notifier to finish; a
+/// background thread reaps the process.
+pub fn send(title: &str, body: &str) -> Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(format!(
+            "display notification {} with title {}",
+            applescript_string(body),
+            applescript_string(title)
+        ));
+        command
+    } else if cfg!(windows) {
+        bail!("desktop notifications are not supported on Windows");
+    } else {
+        let mut command = Command::new("notify-send");
+        command.args(["--app-name", "whitenoise", title, body]);
+        command
+    };
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("failed to run {program}"))?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// A double-quoted AppleScript literal. Only backslash and the quote itself
+/// need escaping.
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applescript_literals_escape_quotes_and_backslashes() {
+        assert_eq!(applescript_string("25:00 is up"), "\"25:00 is up\"");
+        assert_eq!(
+            applescript_string(r#"say "hi" \ bye"#),
+            r#""say \"hi\" \\ bye""#
+        );
+    }
+}
@@ -0,0 +1,459 @@
+//! The output stream's keeper: a thread that owns the stream, opens it
+//! again when the device goes away, and moves playback to another device on
+//! request. Unplugging USB headphones drops to the configured device, or
+//! the default one, within a few seconds, and the interface's device picker
+//! switches outputs without a restart. Each new stream starts a fresh
+//! engine from the shared settings, so the volume ramps back in.
+//! Extra `--device`s are mirrors: each plays the first device's sound
+//! through a stream of its own, and the keeper opens a lost one again
+//! without disturbing the rest.
+//! `--output-to` plays through a virtual device into a sink instead, with
+//! a keeper of its own that has no other device to move to.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow};
+use cpal::traits::StreamTrait;
+use cpal::{BufferSize, Device, Host, SampleFormat, Stream, StreamConfig};
+
+use crate::audio::{AudioMonitor, StreamOptions, UserSample, build_output_stream};
+use crate::device::{
+    display_name, output_device_names, select_output_config, select_output_device,
+};
+use crate::mirror::build_mirror_stream;
+use crate::settings::{MAX_BANDS, SharedSettings};
+use crate::sink::{NullSink, OutputSink, VirtualDevice, VirtualStream, play_virtual};
+
+const CHECK_INTERVAL: Duration = Duration::from_millis(250);
+// A lost device is looked for again this often, so an unplugged output
+// does not have the host enumerating devices nonstop.
+const RETRY_INTERVAL: Duration = Duration::from_secs(2);
+// How long the interface waits on the keeper before giving up.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+/// The name a virtual device plays under.
+pub const VIRTUAL_DEVICE_NAME: &str = "virtual output";
+
+/// Everything needed to open the stream again on any device.
+pub struct OutputSetup {
+    pub host: Host,
+    pub device: Device,
+    pub config: StreamConfig,
+    pub sample_format: SampleFormat,
+    /// The `--device` or `[output]` choices, asked of every device opened.
+    pub sample_rate: Option<u32>,
+    pub buffer_size: Option<u32>,
+    /// The device asked for by name; a lost output comes back on it when
+    /// it is present, and on the default device otherwise.
+    pub preferred: Option<String>,
+    /// Devices that play the same sound alongside, each by name.
+    pub mirrors: Vec<String>,
+    pub settings: Arc<SharedSettings>,
+    pub running: Arc<AtomicBool>,
+    pub monitor: Arc<AudioMonitor>,
+    pub sample: Option<UserSample>,
+    pub block_size: usize,
+    pub seed: Option<u64>,
+    pub room_correction: RoomCorrection,
+}
+
+/// A device's room correction, or None when it has none that fits.
+pub type RoomCorrection = Box<dyn Fn(&str) -> Option<[f32; MAX_BANDS]> + Send>;
+
+enum Request {
+    Devices(Sender<Result<Vec<String>>>),
+    Switch(String, Sender<Result<String>>),
+    Stop,
+}
+
+/// The interface's way to the keeper thread. Clones share the thread.
+#[derive(Clone)]
+pub struct OutputHandle {
+    requests: Sender<Request>,
+    current: Arc<Mutex<String>>,
+}
+
+impl OutputHandle {
+    /// The device playing now, or the one last played while reconnecting.
+    pub fn device_name(&self) -> String {
+        lock(&self.current).clone()
+    }
+
+    /// Output devices the host lists now.
+    pub fn devices(&self) -> Result<Vec<String>> {
+        let (reply, answer) = mpsc::channel();
+        self.ask(Request::Devices(reply), &answer)
+    }
+
+    /// Moves playback to `name`, leaving the old stream playing if the new
+    /// one cannot be opened. Returns a line for the user.
+    pub fn switch_to(&self, name: &str) -> Result<String> {
+        let (reply, answer) = mpsc::channel();
+        self.ask(Request::Switch(name.to_owned(), reply), &answer)
+    }
+
+    fn ask<T>(&self, request: Request, answer: &Receiver<Result<T>>) -> Result<T> {
+        self.requests
+            .send(request)
+            .map_err(|_| anyhow!("the output has stopped"))?;
+        answer
+            .recv_timeout(REPLY_TIMEOUT)
+            .map_err(|_| anyhow!("the audio host did not answer"))?
+    }
+
+    /// A handle with no keeper behind it: `devices` lists `names` and a
+    /// switch succeeds, for interface tests.
+    #[cfg(test)]
+    pub fn fake(names: &[&str]) -> Self {
+        let (requests, incoming) = mpsc::channel();
+        let current = Arc::new(Mutex::new(names[0].to_owned()));
+        let names: Vec<String> = names.iter().map(|name| (*name).to_owned()).collect();
+        let playing = Arc::clone(&current);
+        std::thread::spawn(move || {
+            for request in incoming {
+                match request {
+                    Request::Devices(reply) => {
+                        let _ = reply.send(Ok(names.clone()));
+                    }
+                    Request::Switch(name, reply) => {
+                        *lock(&playing) = name.clone();
+                        let _ = reply.send(Ok(format!("playing on {name}")));
+                    }
+                    Request::Stop => break,
+                }
+            }
+        });
+        Self { requests, current }
+    }
+}
+
+/// The running keeper; `stop` closes the stream.
+pub struct Output {
+    handle: OutputHandle,
+    thread: JoinHandle<()>,
+}
+
+impl Output {
+    pub fn handle(&self) -> OutputHandle {
+        self.handle.clone()
+    }
+
+    pub fn stop(self) {
+        let _ = self.handle.requests.send(Request::Stop);
+        let _ = self.thread.join();
+    }
+}
+
+/// Opens the stream on `setup.device` and hands it to the keeper thread.
+/// Failing to open it at all is an error here, as it was before the keeper.
+pub fn start(setup: OutputSetup) -> Result<Output> {
+    let stream = open(&setup, &setup.device, setup.config, setup.sample_format)?;
+    let current = Arc::new(Mutex::new(display_name(&setup.device)));
+    let mirrors = setup
+        .mirrors
+        .iter()
+        .map(|name| {
+            let mut mirror = Mirror::new(name);
+            match mirror.open(&setup) {
+                Ok((device, config)) => println!(
+                    "Also using {device} ({} channels, {} Hz)",
+                    config.channels, config.sample_rate
+                ),
+                Err(error) => {
+                    eprintln!("warning: {error:#}; {name} joins in when it can be opened");
+                }
+            }
+            mirror
+        })
+        .collect();
+    let (requests, incoming) = mpsc::channel();
+    let mut keeper = Keeper {
+        current: Arc::clone(&current),
+        stream: Some(stream),
+        retry_at: None,
+        mirrors,
+        setup,
+    };
+    let thread = std::thread::spawn(move || keeper.run(&incoming));
+    Ok(Output {
+        handle: OutputHandle { requests, current },
+        thread,
+    })
+}
+
+struct Keeper {
+    setup: OutputSetup,
+    current: Arc<Mutex<String>>,
+    stream: Option<Stream>,
+    retry_at: Option<Instant>,
+    mirrors: Vec<Mirror>,
+}
+
+/// A device playing the first one's sound, with its stream while it has
+/// one.
+struct Mirror {
+    name: String,
+    stream: Option<Stream>,
+    lost: Arc<AtomicBool>,
+    retry_at: Option<Instant>,
+}
+
+impl Mirror {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            stream: None,
+            lost: Arc::new(AtomicBool::new(false)),
+            retry_at: None,
+        }
+    }
+
+    /// Opens the device at its own default format, since the sample rate
+    /// and buffer size options are the first device's, and returns its name
+    /// and format.
+    fn open(&mut self, setup: &OutputSetup) -> Result<(String, StreamConfig)> {
+        self.stream = None;
+        let device = select_output_device(&setup.host, Some(&self.name))?;
+        let (config, sample_format) = select_output_config(&device, None, None)?;
+        self.lost.store(false, Ordering::Relaxed);
+        let stream = build_mirror_stream(
+            &device,
+            config,
+            sample_format,
+            &setup.monitor,
+            Arc::clone(&self.lost),
+        )?;
+        stream
+            .play()
+            .with_context(|| format!("failed to start playback on {}", self.name))?;
+        self.stream = Some(stream);
+        Ok((display_name(&device), config))
+    }
+
+    /// Opens a lost or never-opened mirror again, no more often than every
+    /// RETRY_INTERVAL.
+    fn keep(&mut self, setup: &OutputSetup, now: Instant) {
+        let playing = self.stream.is_some() && !self.lost.load(Ordering::Relaxed);
+        if playing || self.retry_at.is_some_and(|at| now < at) {
+            return;
+        }
+        self.retry_at = Some(now + RETRY_INTERVAL);
+        if self.open(setup).is_ok() {
+            self.retry_at = None;
+        }
+    }
+}
+
+impl Keeper {
+    fn run(&mut self, incoming: &Receiver<Request>) {
+        while self.setup.running.load(Ordering::Relaxed) {
+            match incoming.recv_timeout(CHECK_INTERVAL) {
+                Ok(Request::Devices(reply)) => {
+                    let _ = reply.send(output_device_names(&self.setup.host));
+                }
+                Ok(Request::Switch(name, reply)) => {
+                    let _ = reply.send(self.switch(&name));
+                }
+                Ok(Request::Stop) | Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+            if self.setup.monitor.take_engine_failure() {
+                self.restart_engine(Instant::now());
+            }
+            if self.setup.monitor.output_lost() {
+                self.reconnect(Instant::now());
+            }
+            for mirror in &mut self.mirrors {
+                mirror.keep(&self.setup, Instant::now());
+            }
+        }
+    }
+
+    fn switch(&mut self, name: &str) -> Result<String> {
+        let device = select_output_device(&self.setup.host, Some(name))?;
+        let name = display_name(&device);
+        if name == *lock(&self.current) && !self.setup.monitor.output_lost() {
+            return Ok(format!("already playing on {name}"));
+        }
+        self.play_on(&device)?;
+        self.setup.preferred = Some(name.clone());
+        Ok(format!("playing on {name}"))
+    }
+
+    /// Tries the preferred device, then the default, no more often than
+    /// every RETRY_INTERVAL; the broken stream is closed on the first try.
+    fn reconnect(&mut self, now: Instant) {
+        if self.retry_at.is_some_and(|at| now < at) {
+            return;
+        }
+        self.stream = None;
+        self.retry_at = Some(now + RETRY_INTERVAL);
+        let preferred = self
+            .setup
+            .preferred
+            .as_deref()
+            .and_then(|name| select_output_device(&self.setup.host, Some(name)).ok());
+        let candidates = preferred
+            .into_iter()
+            .chain(select_output_device(&self.setup.host, None).ok());
+        for device in candidates {
+            if self.play_on(&device).is_ok() {
+                return;
+            }
+        }
+    }
+
+    /// The engine panicked and the old stream plays silence: a new stream
+    /// brings a fresh engine from the shared settings, on the same device
+    /// choice, and its volume ramps back in. Failing that, it is retried as
+    /// a lost output.
+    fn restart_engine(&mut self, now: Instant) {
+        self.retry_at = None;
+        self.reconnect(now);
+        if self.stream.is_none() {
+            self.setup.monitor.set_output_lost(true);
+        }
+    }
+
+    /// Opens a stream on `device` with the same format choices and, once it
+    /// plays, drops the old one and brings in the device's room correction.
+    fn play_on(&mut self, device: &Device) -> Result<()> {
+        let (config, sample_format) =
+            select_output_config(device, self.setup.sample_rate, self.setup.buffer_size)?;
+        let stream = open(&self.setup, device, config, sample_format)?;
+        let name = display_name(device);
+        self.setup
+            .settings
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .room_correction_db = (self.setup.room_correction)(&name).unwrap_or_default();
+        self.setup.monitor.set_output_lost(false);
+        self.stream = Some(stream);
+        self.retry_at = None;
+        *lock(&self.current) = name;
+        Ok(())
+    }
+}
+
+/// Builds and starts a stream, falling back to the device's own buffer
+/// size when a fixed one is refused.
+fn open(
+    setup: &OutputSetup,
+    device: &Device,
+    config: StreamConfig,
+    sample_format: SampleFormat,
+) -> Result<Stream> {
+    let build = |config: StreamConfig| {
+        build_output_stream(
+            device,
+            config,
+            sample_format,
+            Arc::clone(&setup.settings),
+            Arc::clone(&setup.running),
+            Arc::clone(&setup.monitor),
+            StreamOptions {
+                sample: setup.sample.as_ref(),
+                block_size: setup.block_size,
+                seed: setup.seed,
+            },
+        )
+    };
+    let (stream, refused) = match (build(config), config.buffer_size) {
+        (Ok(stream), _) => (stream, 0),
+        // Some backends list a buffer range they then refuse; the device's
+        // own buffer size still plays. The keeper may be opening it under the
+        // interface, so the monitor carries the notice rather than stderr.
+        (Err(_), BufferSize::Fixed(frames)) => {
+            let stream = build(StreamConfig {
+                buffer_size: BufferSize::Default,
+                ..config
+            })?;
+            (stream, frames)
+        }
+        (Err(error), BufferSize::Default) => return Err(error),
+    };
+    setup.monitor.set_refused_buffer(refused);
+    stream.play().context("failed to start audio playback")?;
+    Ok(stream)
+}
+
+/// What the virtual device needs: the sink it writes to and the same
+/// session state as a device's stream.
+pub struct VirtualSetup {
+    pub device: VirtualDevice,
+    pub sink: Box<dyn OutputSink>,
+    pub settings: Arc<SharedSettings>,
+    pub running: Arc<AtomicBool>,
+    pub monitor: Arc<AudioMonitor>,
+    pub sample: Option<UserSample>,
+    pub block_size: usize,
+    pub seed: Option<u64>,
+}
+
+impl VirtualSetup {
+    fn play(&self, sink: Box<dyn OutputSink>) -> Result<VirtualStream> {
+        play_virtual(
+            self.device,
+            sink,
+            Arc::clone(&self.settings),
+            Arc::clone(&self.running),
+            Arc::clone(&self.monitor),
+            StreamOptions {
+                sample: self.sample.as_ref(),
+                block_size: self.block_size,
+                seed: self.seed,
+            },
+        )
+    }
+}
+
+/// Starts the virtual device and its keeper, which restarts a panicked
+/// engine on the same sink and finishes the sink when the output stops.
+pub fn start_virtual(mut setup: VirtualSetup) -> Result<Output> {
+    let sink = std::mem::replace(&mut setup.sink, Box::new(NullSink));
+    let mut stream = setup.play(sink)?;
+    let current = Arc::new(Mutex::new(VIRTUAL_DEVICE_NAME.to_owned()));
+    let (requests, incoming) = mpsc::channel();
+    let thread = std::thread::spawn(move || {
+        while setup.running.load(Ordering::Relaxed) {
+            match incoming.recv_timeout(CHECK_INTERVAL) {
+                Ok(Request::Devices(reply)) => {
+                    let _ = reply.send(Ok(vec![VIRTUAL_DEVICE_NAME.to_owned()]));
+                }
+                Ok(Request::Switch(name, reply)) => {
+                    let _ = reply.send(Err(anyhow!(
+                        "{name} is not available; this player only has its {VIRTUAL_DEVICE_NAME}"
+                    )));
+                }
+                Ok(Request::Stop) | Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+            if setup.monitor.take_engine_failure() {
+                let sink = stream.stop();
+                match setup.play(sink) {
+                    Ok(restarted) => stream = restarted,
+                    Err(error) => {
+                        eprintln!("warning: {error:#}; the virtual output stopped");
+                        return;
+                    }
+                }
+            }
+        }
+        if let Err(error) = stream.stop().finish() {
+            eprintln!("warning: {error:#}");
+        }
+    });
+    Ok(Output {
+        handle: OutputHandle { requests, current },
+        thread,
+    })
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,649 @@
+//! Named presets: snapshots of the volume, EQ, contour, and source mix, one
+//! TOML file each under the config directory's `presets/` folder.
+//!
+//! Preset and scene files are shared between people, so each starts with the
+//! release that wrote it and the version of its format. A file from a later
+//! release still loads: what this one understands plays, and a warning names
+//! the keys it skipped instead of dropping them without a word.
+//!
+//! `export_preset` adds the preset's name and the band layout its sliders
+//! were set against, so `import_preset` can map the sliders onto another
+//! player's layout.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::settings::{
+    AudioSettings, FrequencyBand, MAX_BANDS, ToneMode, bands, check_band_layout, config_path,
+};
+
+const MAX_NAME_LENGTH: usize = 48;
+
+/// The preset and scene format this release writes. Raise it when a change
+/// means an older release would play a file differently than intended.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The two stamp keys; files from before them are version 1.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Stamp {
+    schema_version: Option<u32>,
+    app_version: Option<String>,
+}
+
+/// The keys every saved preset and scene starts with.
+pub(crate) fn stamp() -> toml::Table {
+    let mut table = toml::Table::new();
+    table.insert(
+        "schema_version".to_owned(),
+        i64::from(SCHEMA_VERSION).into(),
+    );
+    table.insert("app_version".to_owned(), env!("CARGO_PKG_VERSION").into());
+    table
+}
+
+/// Reads the sound in a preset or scene file, with a warning for a file
+/// from a newer format and one naming every key this release skipped.
+/// `own_keys` are top-level keys the caller reads itself, such as a scene's
+/// layers.
+pub(crate) fn parse_sound(
+    content: &str,
+    file: &str,
+    own_keys: &[&str],
+) -> Result<(AudioSettings, Vec<String>)> {
+    let stamp: Stamp = toml::from_str(content)?;
+    let mut skipped = Vec::new();
+    let settings: AudioSettings =
+        serde_ignored::deserialize(toml::Deserializer::parse(content)?, |path| {
+            let path = path.to_string();
+            if !own_keys.contains(&path.as_str())
+                && !matches!(path.as_str(), "schema_version" | "app_version")
+            {
+                skipped.push(path);
+            }
+        })?;
+
+    let mut warnings = Vec::new();
+    let release = stamp.app_version.as_deref().unwrap_or("a newer release");
+    if let Some(version) = stamp
+        .schema_version
+        .filter(|version| *version > SCHEMA_VERSION)
+    {
+        warnings.push(format!(
+            "{file} was saved by whitenoise {release} in format {version}; this release \
+             reads format {SCHEMA_VERSION}, so it may not sound as intended"
+        ));
+    }
+    if !skipped.is_empty() {
+        let from = stamp
+            .app_version
+            .filter(|version| version != env!("CARGO_PKG_VERSION"))
+            .map(|version| format!(" (saved by whitenoise {version})"))
+            .unwrap_or_default();
+        warnings.push(format!(
+            "{file}{from} has settings this release does not know, which are skipped: {}",
+            skipped.join(", ")
+        ));
+    }
+    Ok((settings, warnings))
+}
+
+pub fn presets_dir() -> PathBuf {
+    config_path().with_file_name("presets")
+}
+
+/// Names become file names, so they are limited to letters, digits, spaces,
+/// hyphens, and underscores. `tinnitus mask` is fine; `../focus` is not.
+pub fn validate_name(name: &str) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        bail!("preset names must be 1 to {MAX_NAME_LENGTH} characters");
+    }
+    if !name
+        .chars()
+        .all(|character| character.is_alphanumeric() || matches!(character, ' ' | '-' | '_'))
+    {
+        bail!("preset name '{name}' may only use letters, digits, spaces, '-' and '_'");
+    }
+    Ok(name)
+}
+
+fn preset_path(dir: &Path, name: &str) -> Result<PathBuf> {
+    Ok(dir.join(format!("{}.toml", validate_name(name)?)))
+}
+
+/// Preset names in `dir`, sorted. A missing directory has none.
+pub fn list_presets(dir: &Path) -> Result<Vec<String>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to read {}", dir.display()));
+        }
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok()?.path().file_name()?.to_str().map(str::to_owned))
+        .filter_map(|file| file.strip_suffix(".toml").map(str::to_owned))
+        .filter(|name| validate_name(name).is_ok_and(|valid| valid == name))
+        .collect();
+    names.sort_by_key(|name| name.to_lowercase());
+    Ok(names)
+}
+
+pub fn load_preset(dir: &Path, name: &str) -> Result<AudioSettings> {
+    load_preset_with_warnings(dir, name).map(|(preset, _)| preset)
+}
+
+/// The preset and any compatibility warnings `parse_sound` has for it.
+pub fn load_preset_with_warnings(dir: &Path, name: &str) -> Result<(AudioSettings, Vec<String>)> {
+    let path = preset_path(dir, name)?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            bail!("no preset named '{}'", name.trim());
+        }
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to read {}", path.display()));
+        }
+    };
+    let file = format!("preset '{}'", name.trim());
+    parse_sound(&content, &file, &[])
+        .with_context(|| format!("failed to parse {}", path.display()))
+        .map(|(preset, warnings)| (preset.sanitize(), warnings))
+}
+
+/// Overwrites any preset of the same name.
+pub fn save_preset(dir: &Path, name: &str, settings: &AudioSettings) -> Result<()> {
+    let path = preset_path(dir, name)?;
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let mut content = stamp().to_string();
+    content.push('\n');
+    content.push_str(&toml::to_string_pretty(&settings.sanitize())?);
+    fs::write(&path, content).with_context(|| format!("failed to write {}", path.display()))
+}
+
+pub fn delete_preset(dir: &Path, name: &str) -> Result<()> {
+    let path = preset_path(dir, name)?;
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            bail!("no preset named '{}'", name.trim())
+        }
+        Err(error) => Err(error).with_context(|| format!("failed to delete {}", path.display())),
+    }
+}
+
+/// The keys an exported preset adds to the sound.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Package {
+    #[serde(skip_serializing)]
+    name: Option<String>,
+    bands: Vec<FrequencyBand>,
+}
+
+/// A preset written to pass on: the stamp, its name, the sound, and the
+/// band layout in use, with any warnings from reading the saved preset.
+pub fn export_preset(dir: &Path, name: &str) -> Result<(String, Vec<String>)> {
+    let (preset, warnings) = load_preset_with_warnings(dir, name)?;
+    let mut head = stamp();
+    head.insert("name".to_owned(), validate_name(name)?.into());
+    let layout = Package {
+        bands: bands().to_vec(),
+        ..Package::default()
+    };
+    let content = format!(
+        "# A whitenoise preset: whitenoise presets import FILE\n{head}\n{}\n{}",
+        toml::to_string_pretty(&preset)?,
+        toml::to_string(&layout)?
+    );
+    Ok((content, warnings))
+}
+
+/// What `import_preset` saved.
+#[derive(Debug)]
+pub struct Import {
+    pub name: String,
+    pub preset: AudioSettings,
+    /// The file's sliders were set against another band layout and were
+    /// mapped onto this one.
+    pub remapped: bool,
+    pub warnings: Vec<String>,
+}
+
+/// Saves an exported preset under `name`, or else the name inside it,
+/// refusing to overwrite a preset of that name unless `replace`. A file
+/// from a newer release imports with warnings as a saved one loads, and
+/// sliders set against another band layout are mapped onto this one.
+pub fn import_preset(
+    dir: &Path,
+    content: &str,
+    name: Option<&str>,
+    replace: bool,
+) -> Result<Import> {
+    // Editors on Windows may start the file with a byte order mark.
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let package: Package = toml::from_str(content).context("not a whitenoise preset file")?;
+    let name = match name.or(package.name.as_deref()) {
+        Some(name) => validate_name(name)?.to_owned(),
+        None => bail!("the file does not name its preset; pass --name"),
+    };
+    check_band_layout(&package.bands).context("the file's band layout is invalid")?;
+    let (mut preset, warnings) = parse_sound(
+        content,
+        &format!("preset file '{name}'"),
+        &["name", "bands"],
+    )?;
+    let remapped = !package.bands.is_empty() && !same_layout(&package.bands, bands());
+    if remapped {
+        preset.frequency_bands = remap(&preset.frequency_bands, &package.bands, bands());
+        preset.band_widths = remap(&preset.band_widths, &package.bands, bands());
+    }
+    let path = preset_path(dir, &name)?;
+    if !replace && path.exists() {
+        bail!(
+            "a preset named '{name}' already exists; pass --name to keep both or --force to replace it"
+        );
+    }
+    save_preset(dir, &name, &preset)?;
+    Ok(Import {
+        name,
+        preset: preset.sanitize(),
+        remapped,
+        warnings,
+    })
+}
+
+fn same_layout(a: &[FrequencyBand], b: &[FrequencyBand]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(a, b)| (a.min_freq, a.max_freq) == (b.min_freq, b.max_freq))
+}
+
+/// Per-band values set against `from`, read off at the centers of `to`:
+/// straight lines between the centers of `from` on a log frequency scale,
+/// level past the outer ones.
+fn remap(
+    values: &[f32; MAX_BANDS],
+    from: &[FrequencyBand],
+    to: &[FrequencyBand],
+) -> [f32; MAX_BANDS] {
+    let mut points: Vec<(f32, f32)> = from
+        .iter()
+        .zip(values)
+        .map(|(band, value)| (band.center_frequency().log2(), *value))
+        .collect();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut mapped = *values;
+    for (slot, band) in mapped.iter_mut().zip(to) {
+        let octave = band.center_frequency().log2();
+        let above = points.partition_point(|(center, _)| *center < octave);
+        *slot = match (
+            above.checked_sub(1).map(|below| points[below]),
+            points.get(above).copied(),
+        ) {
+            (Some((low, low_value)), Some((high, high_value))) => {
+                low_value + (high_value - low_value) * (octave - low) / (high - low)
+            }
+            (Some((_, value)), None) | (None, Some((_, value))) => value,
+            (None, None) => *slot,
+        };
+    }
+    mapped
+}
+
+/// One line for listings: `Brown Noise at 30% volume, contour at 70 phon`, plus the
+/// notch when it is on, the tone layer when it plays, and the drift.
+pub fn summary(settings: &AudioSettings) -> String {
+    let mut line = format!(
+        "{} at {:.0}% volume",
+        settings.mix().describe(),
+        settings.volume * 100.0
+    );
+    if let Some(phon) = settings.contour_phon() {
+        line.push_str(&format!(", contour at {phon:.0} phon"));
+    }
+    if let Some(hz) = settings.notch.hz {
+        line.push_str(&format!(", notch {hz:.0} Hz"));
+    }
+    if settings.tone.mode != ToneMode::Off {
+        line.push_str(&format!(
+            ", {} tone",
+            settings.tone.mode.label().to_lowercase()
+        ));
+    }
+    if settings.drift.enabled {
+        line.push_str(&format!(", drift {:.0}%", settings.drift.depth * 100.0));
+    }
+    line
+}
+
+pub fn print_presets(dir: &Path) -> Result<()> {
+    let names = list_presets(dir)?;
+    if names.is_empty() {
+        println!("No presets in {}", dir.display());
+        println!("Save one with P in the interface or `whitenoise presets save NAME`.");
+        return Ok(());
+    }
+    let width = names
+        .iter()
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    for name in names {
+        match load_preset_with_warnings(dir, &name) {
+            Ok((settings, warnings)) => {
+                println!("{name:<width$}  {}", summary(&settings));
+                for warning in warnings {
+                    println!("{:width$}  warning: {warning}", "");
+                }
+            }
+            Err(error) => println!("{name:<width$}  unreadable: {error:#}"),
+        }
+    }
+    Ok(())
+}
+
+/// Replaces everything a preset stores, leaving session state such as a
+/// running sleep timer, a pause, or a mute, the output's high-pass, crossfeed, headroom, limiter,
+/// and room correction, the night limit, a scheduled fade, a duck, the loop crossfades, and band mutes and solos alone.
+pub fn apply_preset(preset: AudioSettings, settings: &mut AudioSettings) {
+    *settings = AudioSettings {
+        sleep_timer: settings.sleep_timer,
+        paused: settings.paused,
+        muted: settings.muted,
+        bypass: settings.bypass,
+        high_pass: settings.high_pass,
+        crossfeed: settings.crossfeed,
+        headroom: settings.headroom,
+        loops: settings.loops,
+        room_tone: settings.room_tone,
+        spl: settings.spl,
+        limiter: settings.limiter,
+        listeners: settings.listeners,
+        night_ceiling_db: settings.night_ceiling_db,
+        schedule_fade: settings.schedule_fade,
+        duck_db: settings.duck_db,
+        call_duck_db: settings.call_duck_db,
+        ambient_gain_db: settings.ambient_gain_db,
+        room_correction_db: settings.room_correction_db,
+        band_overlay: settings.band_overlay,
+        ..preset
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::settings::{
+        HighPass, LoopSettings, SoundStyle, SourceMix, SplCalibration, band_values,
+    };
+    use crate::timer::SleepTimer;
+
+    fn scratch_dir(label: &str) -> PathBuf {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "whitenoise-presets-test-{}-{label}",
+            std::process::id()
+        ));
+        path.push("presets");
+        path
+    }
+
+    #[test]
+    fn names_are_checked_before_touching_the_filesystem() {
+        assert_eq!(validate_name(" tinnitus mask ").unwrap(), "tinnitus mask");
+        assert!(validate_name("focus_2-b").is_ok());
+        for invalid in ["", "   ", "../focus", "a/b", "sleep.toml", "x\ny"] {
+            assert!(validate_name(invalid).is_err(), "{invalid:?}");
+        }
+        assert!(validate_name(&"a".repeat(MAX_NAME_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn presets_save_list_load_and_delete() {
+        let dir = scratch_dir("round-trip");
+        assert_eq!(list_presets(&dir).unwrap(), Vec::<String>::new());
+
+        let mut focus = AudioSettings {
+            volume: 0.3,
+            frequency_bands: band_values(&[0.5, 0.5, 0.4, 0.5, 0.6, 0.7, 0.5, 0.2]),
+            listening_contour: true,
+            ..AudioSettings::default()
+        };
+        focus.set_mix(SourceMix::solo(SoundStyle::Pink));
+        save_preset(&dir, "focus", &focus).unwrap();
+        save_preset(&dir, "Tinnitus mask", &AudioSettings::default()).unwrap();
+        save_preset(&dir, "sleep", &AudioSettings::default()).unwrap();
+        fs::write(dir.join("notes.txt"), "not a preset").unwrap();
+
+        assert_eq!(
+            list_presets(&dir).unwrap(),
+            ["focus", "sleep", "Tinnitus mask"]
+        );
+        assert_eq!(load_preset(&dir, "focus").unwrap(), focus.sanitize());
+
+        delete_preset(&dir, "sleep").unwrap();
+        assert_eq!(list_presets(&dir).unwrap(), ["focus", "Tinnitus mask"]);
+        let missing = format!("{:#}", load_preset(&dir, "sleep").unwrap_err());
+        assert_eq!(missing, "no preset named 'sleep'");
+        assert!(delete_preset(&dir, "sleep").is_err());
+        assert!(save_preset(&dir, "../escape", &focus).is_err());
+
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn presets_from_newer_releases_load_with_warnings() {
+        let dir = scratch_dir("versions");
+        save_preset(&dir, "mine", &AudioSettings::default()).unwrap();
+        let saved = fs::read_to_string(dir.join("mine.toml")).unwrap();
+        assert!(saved.contains("schema_version = 1\n"), "{saved}");
+        assert!(saved.contains(&format!("app_version = \"{}\"", env!("CARGO_PKG_VERSION"))));
+        let (_, warnings) = load_preset_with_warnings(&dir, "mine").unwrap();
+        assert_eq!(warnings, Vec::<String>::new());
+
+        fs::write(
+            dir.join("shared.toml"),
+            "schema_version = 2\napp_version = \"9.0.0\"\nvolume = 0.4\nshimmer = 0.2\n\n\
+             [tone]\nmode = \"binaural\"\ndrift_hz = 1.5\n",
+        )
+        .unwrap();
+        let (preset, warnings) = load_preset_with_warnings(&dir, "shared").unwrap();
+        assert_eq!(preset.volume, 0.4);
+        assert_eq!(preset.tone.mode, ToneMode::Binaural);
+        assert_eq!(
+            warnings,
+            [
+                "preset 'shared' was saved by whitenoise 9.0.0 in format 2; this release \
+                 reads format 1, so it may not sound as intended",
+                "preset 'shared' (saved by whitenoise 9.0.0) has settings this release \
+                 does not know, which are skipped: shimmer, tone.drift_hz",
+            ]
+        );
+
+        // Files from before the stamp are the first format, and the
+        // settings' old key names are understood, not skipped.
+        fs::write(
+            dir.join("old.toml"),
+            "volume = 0.2\nperceptual_normalization = true\n",
+        )
+        .unwrap();
+        let (preset, warnings) = load_preset_with_warnings(&dir, "old").unwrap();
+        assert!(preset.listening_contour);
+        assert_eq!(warnings, Vec::<String>::new());
+
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn exported_presets_import_under_their_name_without_overwriting() {
+        let from = scratch_dir("export");
+        let to = scratch_dir("import");
+        let mut focus = AudioSettings {
+            volume: 0.35,
+            frequency_bands: band_values(&[0.2, 0.5, 0.5, 0.5, 0.5, 0.5, 0.7, 0.9]),
+            ..AudioSettings::default()
+        };
+        focus.set_mix(SourceMix::solo(SoundStyle::Brown));
+        focus.drift.enabled = true;
+        focus.band_widths[0] = 0.0;
+        save_preset(&from, "focus", &focus).unwrap();
+
+        let (content, warnings) = export_preset(&from, "focus").unwrap();
+        assert!(warnings.is_empty());
+        assert!(content.contains("schema_version = 1\n"));
+        assert!(content.contains("name = \"focus\""));
+        assert!(content.contains("[[bands]]\nname = \"Air\"\nmin_hz = 12000.0"));
+
+        // A byte order mark and Windows line endings read the same.
+        let windows = format!("\u{feff}{}", content.replace('\n', "\r\n"));
+        let import = import_preset(&to, &windows, None, false).unwrap();
+        assert_eq!(import.name, "focus");
+        assert!(!import.remapped && import.warnings.is_empty());
+        assert_eq!(load_preset(&to, "focus").unwrap(), focus.sanitize());
+
+        let error = import_preset(&to, &content, None, false).unwrap_err();
+        assert!(error.to_string().contains("already exists"), "{error}");
+        import_preset(&to, &content, Some("focus 2"), false).unwrap();
+        import_preset(&to, &content, None, true).unwrap();
+        assert_eq!(list_presets(&to).unwrap(), ["focus", "focus 2"]);
+
+        assert!(import_preset(&to, "volume = 0.5", None, false).is_err());
+        assert!(import_preset(&to, "name = \"../x\"", None, false).is_err());
+        assert!(import_preset(&to, "not toml [", Some("x"), false).is_err());
+        let _ = fs::remove_dir_all(from.parent().unwrap());
+        let _ = fs::remove_dir_all(to.parent().unwrap());
+    }
+
+    #[test]
+    fn sliders_from_another_band_layout_map_onto_this_one() {
+        let dir = scratch_dir("remap");
+        // Three bands centered on 40 Hz, 640 Hz, and 10.24 kHz, four
+        // octaves apart; Low Mid's 354 Hz center lies between the first two.
+        let content = "schema_version = 1
+name = \"three\"
+frequency_bands = [0.0, 1.0, 0.5]
+band_widths = [0.0, 1.0, 2.0]
+future_key = true
+
+[[bands]]
+name = \"Low\"
+min_hz = 20.0
+max_hz = 80.0
+
+[[bands]]
+name = \"Middle\"
+min_hz = 320.0
+max_hz = 1280.0
+
+[[bands]]
+name = \"High\"
+min_hz = 5120.0
+max_hz = 20480.0
+";
+        let error = import_preset(&dir, content, None, false).unwrap_err();
+        assert!(format!("{error:#}").contains("High must run"), "{error:#}");
+        let content = content.replace("20480.0", "20000.0");
+        let import = import_preset(&dir, &content, None, false).unwrap();
+        assert!(import.remapped);
+        assert_eq!(import.warnings.len(), 1);
+        assert!(import.warnings[0].ends_with("skipped: future_key"));
+        let sliders = import.preset.frequency_bands;
+        let widths = import.preset.band_widths;
+        // Sub Bass sits under the lowest center and keeps its value.
+        assert_eq!((sliders[0], widths[0]), (0.0, 0.0));
+        let low_mid = (354.0_f32.log2() - 40.0_f32.log2()) / 4.0;
+        assert!((sliders[2] - low_mid).abs() < 0.01, "{sliders:?}");
+        // Mid's 1 kHz center is a sixth of the way from 640 Hz to 10.1 kHz.
+        assert!((sliders[3] - 0.92).abs() < 0.01, "{sliders:?}");
+        assert!((widths[7] - 2.0).abs() < 0.01, "{widths:?}");
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[test]
+    fn summaries_name_the_source_volume_and_contour() {
+        let mut settings = AudioSettings {
+            volume: 0.3,
+            listening_contour: true,
+            ..AudioSettings::default()
+        };
+        settings.set_mix(SourceMix::solo(SoundStyle::Brown));
+        assert_eq!(
+            summary(&settings),
+            "Brown Noise at 30% volume, contour at 70 phon"
+        );
+
+        settings.tone.mode = ToneMode::Binaural;
+        assert_eq!(
+            summary(&settings),
+            "Brown Noise at 30% volume, contour at 70 phon, binaural tone"
+        );
+
+        settings.notch.hz = Some(6_500.0);
+        assert_eq!(
+            summary(&settings),
+            "Brown Noise at 30% volume, contour at 70 phon, notch 6500 Hz, binaural tone"
+        );
+
+        settings.drift.enabled = true;
+        assert!(summary(&settings).ends_with("binaural tone, drift 50%"));
+    }
+
+    #[test]
+    fn applying_a_preset_keeps_the_sleep_timer_and_output_settings() {
+        let timer = SleepTimer::new(
+            Duration::from_secs(600),
+            Duration::from_secs(60),
+            Instant::now(),
+        );
+        let mut settings = AudioSettings {
+            volume: 0.8,
+            sleep_timer: Some(timer),
+            high_pass: HighPass {
+                hz: Some(40.0),
+                slope_db: 12,
+            },
+            crossfeed: true,
+            loops: LoopSettings {
+                rain_crossfade_seconds: 4.0,
+                ..LoopSettings::default()
+            },
+            room_tone: 0.05,
+            spl: SplCalibration {
+                offset_db: Some(90.0),
+                max_db: Some(60.0),
+            },
+            night_ceiling_db: Some(-20.0),
+            duck_db: Some(15.0),
+            paused: true,
+            muted: true,
+            ..AudioSettings::default()
+        };
+        let mut preset = AudioSettings {
+            volume: 0.2,
+            ..AudioSettings::default()
+        };
+        preset.set_mix(SourceMix::solo(SoundStyle::Rain));
+
+        apply_preset(preset, &mut settings);
+        assert_eq!(settings.volume, 0.2);
+        assert_eq!(settings.mix(), SourceMix::solo(SoundStyle::Rain));
+        assert_eq!(settings.sleep_timer, Some(timer));
+        assert_eq!(settings.high_pass.hz, Some(40.0));
+        assert!(settings.crossfeed);
+        assert_eq!(settings.loops.rain_crossfade_seconds, 4.0);
+        assert_eq!(settings.room_tone, 0.05);
+        assert_eq!(settings.spl.max_db, Some(60.0));
+        assert_eq!(settings.night_ceiling_db, Some(-20.0));
+        assert_eq!(settings.duck_db, Some(15.0));
+        assert!(settings.paused && settings.muted);
+    }
+}
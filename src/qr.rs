@@ -0,0 +1,547 @@
+//! QR codes for the web remote's address, so a phone's camera can open it
+//! without anyone typing an IP address: byte mode at error correction level
+//! M, versions 1 to 9, which hold up to 180 bytes, drawn by the interface
+//! two modules to a character cell with half blocks.
+
+// Error correction codewords per block, and blocks, at level M by version.
+const ECC_PER_BLOCK: [usize; 10] = [0, 10, 16, 26, 18, 24, 16, 18, 22, 22];
+const BLOCKS: [usize; 10] = [0, 1, 1, 1, 2, 2, 4, 4, 4, 5];
+const MAX_VERSION: usize = 9;
+// Level M's two format bits.
+const LEVEL_M: u32 = 0;
+/// The light border a reader needs around the code, in modules.
+pub const QUIET_ZONE: usize = 4;
+
+pub struct QrCode {
+    size: usize,
+    /// Row by row; true is dark.
+    modules: Vec<bool>,
+    function: Vec<bool>,
+}
+
+impl QrCode {
+    /// The smallest code that holds `data`, or None when it is too long.
+    pub fn encode(data: &[u8]) -> Option<Self> {
+        let version = (1..=MAX_VERSION)
+            .find(|version| 4 + 8 + 8 * data.len() <= data_codewords(*version) * 8)?;
+        let mut code = Self {
+            size: version * 4 + 17,
+            modules: Vec::new(),
+            function: Vec::new(),
+        };
+        code.modules = vec![false; code.size * code.size];
+        code.function = vec![false; code.size * code.size];
+        code.draw_function_patterns(version);
+        code.draw_codewords(&with_error_correction(
+            &data_codewords_for(data, version),
+            version,
+        ));
+
+        let mask = (0..8)
+            .min_by_key(|mask| {
+                code.apply_mask(*mask);
+                code.draw_format_bits(*mask);
+                let penalty = code.penalty();
+                // Masking twice unmasks.
+                code.apply_mask(*mask);
+                penalty
+            })
+            .expect("there are eight masks");
+        code.apply_mask(mask);
+        code.draw_format_bits(mask);
+        Some(code)
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn dark(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+
+    /// The code with its quiet zone as lines of `▀`, `▄`, `█`, and spaces,
+    /// each line two modules tall, for dark glyphs on a light background.
+    pub fn half_block_rows(&self) -> Vec<String> {
+        let span = self.size + 2 * QUIET_ZONE;
+        let dark = |x: usize, y: usize| {
+            (QUIET_ZONE..QUIET_ZONE + self.size).contains(&x)
+                && (QUIET_ZONE..QUIET_ZONE + self.size).contains(&y)
+                && self.dark(x - QUIET_ZONE, y - QUIET_ZONE)
+        };
+        (0..span)
+            .step_by(2)
+            .map(|y| {
+                (0..span)
+                    .map(|x| match (dark(x, y), dark(x, y + 1)) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+        self.function[y * self.size + x] = true;
+    }
+
+    /// The finders, their separators, the timing lines, the alignment
+    /// patterns, and the version, with room kept for the format bits.
+    fn draw_function_patterns(&mut self, version: usize) {
+        let size = self.size;
+        for i in 0..size {
+            self.set_function(6, i, i % 2 == 0);
+            self.set_function(i, 6, i % 2 == 0);
+        }
+        for (x, y) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            for dy in -4_isize..=4 {
+                for dx in -4_isize..=4 {
+                    let (Some(column), Some(row)) =
+                        (x.checked_add_signed(dx), y.checked_add_signed(dy))
+                    else {
+                        continue;
+                    };
+                    if column < size && row < size {
+                        let distance = dx.abs().max(dy.abs());
+                        self.set_function(column, row, distance != 2 && distance != 4);
+                    }
+                }
+            }
+        }
+        let centres = alignment_centres(version);
+        let last = centres.len().saturating_sub(1);
+        for (i, x) in centres.iter().enumerate() {
+            for (j, y) in centres.iter().enumerate() {
+                // Those three would sit on the finders.
+                if (i, j) == (0, 0) || (i, j) == (0, last) || (i, j) == (last, 0) {
+                    continue;
+                }
+                for dy in -2_isize..=2 {
+                    for dx in -2_isize..=2 {
+                        self.set_function(
+                            x.wrapping_add_signed(dx),
+                            y.wrapping_add_signed(dy),
+                            dx.abs().max(dy.abs()) != 1,
+                        );
+                    }
+                }
+            }
+        }
+        self.draw_format_bits(0);
+        if version >= 7 {
+            let bits = version_bits(version);
+            for i in 0..18 {
+                let dark = bits >> i & 1 != 0;
+                let (a, b) = (size - 11 + i % 3, i / 3);
+                self.set_function(a, b, dark);
+                self.set_function(b, a, dark);
+            }
+        }
+    }
+
+    /// Both copies of the level and mask, and the dark module beside them.
+    fn draw_format_bits(&mut self, mask: u32) {
+        let bits = format_bits(mask);
+        let bit = |i: usize| bits >> i & 1 != 0;
+        let size = self.size;
+        for i in 0..6 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(i));
+        }
+        self.set_function(8, size - 8, true);
+    }
+
+    /// Fills the other modules in the zigzag of two-module columns, from
+    /// the bottom right, skipping the vertical timing line.
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let size = self.size;
+        let mut index = 0;
+        let mut right = size - 1;
+        while right >= 1 {
+            if right == 6 {
+                right = 5;
+            }
+            for vertical in 0..size {
+                for j in 0..2 {
+                    let x = right - j;
+                    let upward = (right + 1) & 2 == 0;
+                    let y = if upward {
+                        size - 1 - vertical
+                    } else {
+                        vertical
+                    };
+                    if !self.function[y * size + x] && index < codewords.len() * 8 {
+                        self.modules[y * size + x] =
+                            codewords[index / 8] >> (7 - index % 8) & 1 != 0;
+                        index += 1;
+                    }
+                }
+            }
+            if right < 2 {
+                break;
+            }
+            right -= 2;
+        }
+    }
+
+    fn apply_mask(&mut self, mask: u32) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let flip = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                let index = y * self.size + x;
+                if flip && !self.function[index] {
+                    self.modules[index] = !self.modules[index];
+                }
+            }
+        }
+    }
+
+    /// The standard's four penalties, by which the mask is chosen: long
+    /// runs of one colour, 2×2 blocks, shapes that look like a finder, and
+    /// an imbalance of dark and light.
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let lines = (0..size).flat_map(|line| {
+            [
+                (0..size).map(|i| self.dark(i, line)).collect::<Vec<_>>(),
+                (0..size).map(|i| self.dark(line, i)).collect(),
+            ]
+        });
+        let finder_like = [
+            true, false, true, true, true, false, true, false, false, false, false,
+        ];
+        let mut penalty = 0;
+        for line in lines {
+            for run in line.chunk_by(|a, b| a == b) {
+                if run.len() >= 5 {
+                    penalty += run.len() - 2;
+                }
+            }
+            penalty += 40
+                * line
+                    .windows(finder_like.len())
+                    .filter(|window| {
+                        window.iter().eq(finder_like.iter())
+                            || window.iter().eq(finder_like.iter().rev())
+                    })
+                    .count();
+        }
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let colour = self.dark(x, y);
+                if colour == self.dark(x + 1, y)
+                    && colour == self.dark(x, y + 1)
+                    && colour == self.dark(x + 1, y + 1)
+                {
+                    penalty += 3;
+                }
+            }
+        }
+        let dark = self.modules.iter().filter(|dark| **dark).count();
+        let percent = dark * 100 / self.modules.len();
+        penalty + percent.abs_diff(50) / 5 * 10
+    }
+}
+
+fn data_codewords(version: usize) -> usize {
+    raw_modules(version) / 8 - ECC_PER_BLOCK[version] * BLOCKS[version]
+}
+
+/// Modules left for data and error correction once the function patterns
+/// and format and version bits are placed.
+fn raw_modules(version: usize) -> usize {
+    let mut modules = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let alignments = version / 7 + 2;
+        modules -= (25 * alignments - 10) * alignments - 55;
+        if version >= 7 {
+            modules -= 36;
+        }
+    }
+    modules
+}
+
+/// The rows and columns of the alignment patterns' centres.
+fn alignment_centres(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let count = version / 7 + 2;
+    let step = (version * 8 + count * 3 + 5) / (count * 4 - 4) * 2;
+    let last = version * 4 + 10;
+    let mut centres: Vec<usize> = (0..count - 1).map(|i| last - i * step).collect();
+    centres.push(6);
+    centres.reverse();
+    centres
+}
+
+/// The byte-mode segment, its terminator, and the padding bytes.
+fn data_codewords_for(data: &[u8], version: usize) -> Vec<u8> {
+    let capacity = data_codewords(version) * 8;
+    let mut bits: Vec<bool> = Vec::with_capacity(capacity);
+    let mut push = |value: usize, length: usize| {
+        bits.extend((0..length).rev().map(|i| value >> i & 1 != 0));
+    };
+    push(0b0100, 4);
+    // Versions 1 to 9 count bytes in 8 bits.
+    push(data.len(), 8);
+    for byte in data {
+        push(usize::from(*byte), 8);
+    }
+    let terminator = (capacity - bits.len()).min(4);
+    bits.extend(std::iter::repeat_n(false, terminator));
+    bits.extend(std::iter::repeat_n(
+        false,
+        bits.len().next_multiple_of(8) - bits.len(),
+    ));
+    let mut codewords: Vec<u8> = bits
+        .chunks(8)
+        .map(|byte| {
+            byte.iter()
+                .fold(0, |value, bit| value << 1 | u8::from(*bit))
+        })
+        .collect();
+    for pad in [0xEC, 0x11].into_iter().cycle() {
+        if codewords.len() * 8 >= capacity {
+            break;
+        }
+        codewords.push(pad);
+    }
+    codewords
+}
+
+/// Splits the data into the version's blocks, adds each block's
+/// Reed-Solomon codewords, and interleaves them all.
+fn with_error_correction(data: &[u8], version: usize) -> Vec<u8> {
+    let blocks = BLOCKS[version];
+    let ecc_length = ECC_PER_BLOCK[version];
+    let raw_codewords = raw_modules(version) / 8;
+    let short_blocks = blocks - raw_codewords % blocks;
+    let short_length = raw_codewords / blocks;
+    let divisor = reed_solomon_divisor(ecc_length);
+    let mut split = Vec::with_capacity(blocks);
+    let mut start = 0;
+    for block in 0..blocks {
+        let length = short_length - ecc_length + usize::from(block >= short_blocks);
+        let data = &data[start..start + length];
+        start += length;
+        split.push((data, reed_solomon_remainder(data, &divisor)));
+    }
+    let mut codewords = Vec::with_capacity(raw_codewords);
+    for i in 0..=short_length - ecc_length {
+        codewords.extend(split.iter().filter_map(|(data, _)| data.get(i)));
+    }
+    for i in 0..ecc_length {
+        codewords.extend(split.iter().map(|(_, ecc)| ecc[i]));
+    }
+    codewords
+}
+
+/// Multiplies in GF(2⁸) modulo x⁸ + x⁴ + x³ + x² + 1.
+fn gf_multiply(x: u8, y: u8) -> u8 {
+    let mut product: u16 = 0;
+    for i in (0..8).rev() {
+        product = (product << 1) ^ ((product >> 7) * 0x11D);
+        product ^= u16::from(y >> i & 1) * u16::from(x);
+    }
+    product as u8
+}
+
+fn reed_solomon_divisor(degree: usize) -> Vec<u8> {
+    let mut divisor = vec![0; degree];
+    divisor[degree - 1] = 1;
+    let mut root = 1;
+    for _ in 0..degree {
+        for j in 0..degree {
+            divisor[j] = gf_multiply(divisor[j], root);
+            if j + 1 < degree {
+                divisor[j] ^= divisor[j + 1];
+            }
+        }
+        root = gf_multiply(root, 0x02);
+    }
+    divisor
+}
+
+fn reed_solomon_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut remainder = vec![0; divisor.len()];
+    for byte in data {
+        let factor = byte ^ remainder.remove(0);
+        remainder.push(0);
+        for (value, coefficient) in remainder.iter_mut().zip(divisor) {
+            *value ^= gf_multiply(*coefficient, factor);
+        }
+    }
+    remainder
+}
+
+/// The level and mask with their BCH check bits, masked so they are never
+/// all light.
+fn format_bits(mask: u32) -> u32 {
+    let data = LEVEL_M << 3 | mask;
+    let mut remainder = data;
+    for _ in 0..10 {
+        remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+    }
+    (data << 10 | remainder) ^ 0x5412
+}
+
+fn version_bits(version: usize) -> usize {
+    let mut remainder = version;
+    for _ in 0..12 {
+        remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1F25);
+    }
+    version << 12 | remainder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_correction_and_format_bits_match_the_standard() {
+        // "HELLO WORLD" at 1-M, the usual worked example.
+        let data = [
+            32, 91, 11, 120, 209, 114, 220, 77, 67, 64, 236, 17, 236, 17, 236, 17,
+        ];
+        assert_eq!(
+            reed_solomon_remainder(&data, &reed_solomon_divisor(10)),
+            [196, 35, 39, 119, 235, 215, 231, 226, 93, 23]
+        );
+        assert_eq!(format_bits(0), 0b101010000010010);
+        assert_eq!(format_bits(5), 0b100000011001110);
+        assert_eq!(version_bits(7), 0b000111110010010100);
+        assert_eq!(alignment_centres(7), [6, 22, 38]);
+        assert_eq!(data_codewords(1), 16);
+        assert_eq!(data_codewords(9), 182);
+    }
+
+    /// Reads the code back the way a scanner would once it has the grid:
+    /// the format bits, the mask, the zigzag, and the blocks.
+    fn read(code: &QrCode) -> Vec<u8> {
+        let size = code.size;
+        let format = (0..6)
+            .map(|i| code.dark(8, i))
+            .chain([code.dark(8, 7), code.dark(8, 8), code.dark(7, 8)])
+            .chain((9..15).map(|i| code.dark(14 - i, 8)))
+            .enumerate()
+            .fold(0, |bits, (i, dark)| bits | u32::from(dark) << i);
+        let mask = (0..8)
+            .find(|mask| format_bits(*mask) == format)
+            .expect("a valid format");
+        let mut unmasked = QrCode {
+            size,
+            modules: code.modules.clone(),
+            function: code.function.clone(),
+        };
+        unmasked.apply_mask(mask);
+
+        let mut bits = Vec::new();
+        let mut right = size - 1;
+        loop {
+            if right == 6 {
+                right = 5;
+            }
+            for vertical in 0..size {
+                for j in 0..2 {
+                    let x = right - j;
+                    let y = if (right + 1) & 2 == 0 {
+                        size - 1 - vertical
+                    } else {
+                        vertical
+                    };
+                    if !code.function[y * size + x] {
+                        bits.push(unmasked.dark(x, y));
+                    }
+                }
+            }
+            if right < 2 {
+                break;
+            }
+            right -= 2;
+        }
+        let codewords: Vec<u8> = bits
+            .chunks_exact(8)
+            .map(|byte| {
+                byte.iter()
+                    .fold(0, |value, bit| value << 1 | u8::from(*bit))
+            })
+            .collect();
+
+        let version = (size - 17) / 4;
+        let blocks = BLOCKS[version];
+        let ecc_length = ECC_PER_BLOCK[version];
+        let short_blocks = blocks - codewords.len() % blocks;
+        let short_length = codewords.len() / blocks;
+        let mut split: Vec<Vec<u8>> = vec![Vec::new(); blocks];
+        let mut next = codewords.iter().copied();
+        // The long blocks' last data codeword comes alone, between the
+        // others' data and the error correction.
+        for i in 0..=short_length {
+            for (block, words) in split.iter_mut().enumerate() {
+                if i != short_length - ecc_length || block >= short_blocks {
+                    words.push(next.next().unwrap());
+                }
+            }
+        }
+        let divisor = reed_solomon_divisor(ecc_length);
+        let mut data = Vec::new();
+        for words in split {
+            let (block, ecc) = words.split_at(words.len() - ecc_length);
+            assert_eq!(reed_solomon_remainder(block, &divisor), ecc);
+            data.extend_from_slice(block);
+        }
+        assert_eq!(data[0] >> 4, 0b0100, "byte mode");
+        let length = usize::from(data[0] << 4 | data[1] >> 4);
+        (0..length)
+            .map(|i| data[1 + i] << 4 | data[2 + i] >> 4)
+            .collect()
+    }
+
+    #[test]
+    fn a_code_reads_back_as_its_text() {
+        for text in [
+            "http://192.168.1.23:8080/",
+            "http://[fe80::1c2b:3aff:fe4d:5e6f]:8080/",
+            &"x".repeat(180),
+        ] {
+            let code = QrCode::encode(text.as_bytes()).unwrap();
+            assert_eq!(read(&code), text.as_bytes(), "{text}");
+        }
+        let code = QrCode::encode(b"http://192.168.1.23:8080/").unwrap();
+        assert_eq!(code.size(), 25);
+        // A finder's corner, its separator, and the timing line.
+        assert!(code.dark(0, 0) && !code.dark(7, 0) && code.dark(8, 6));
+        let rows = code.half_block_rows();
+        assert_eq!(rows.len(), (25 + 2 * QUIET_ZONE).div_ceil(2));
+        assert!(
+            rows.iter()
+                .all(|row| row.chars().count() == 25 + 2 * QUIET_ZONE)
+        );
+        assert!(QrCode::encode(&[b'x'; 181]).is_none());
+    }
+}
@@ -0,0 +1,346 @@
+//! Recording: a copy of what the output plays, written to a WAV file while
+//! it plays. The audio callback hands each block it renders to the
+//! monitor's `Tap`, which copies it into a ring of atomics without locking
+//! or allocating, and a writer thread drains the ring into a 32-bit float
+//! WAV through a `WavSink`. A block that finds the ring full is dropped and
+//! counted rather than waited for, so a slow disk never stalls the sound.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::audio::{AudioMonitor, Frame};
+use crate::i18n::{text, text_with};
+use crate::settings::config_path;
+use crate::sink::{OutputSink, WavSink};
+
+// Samples the ring holds: about five seconds of stereo at 48 kHz, against
+// a writer that drains it every 50 ms.
+const RING_SAMPLES: usize = 1 << 19;
+const DRAIN_INTERVAL: Duration = Duration::from_millis(50);
+
+/// `recordings/` beside settings.toml, where the interface's recordings go.
+pub fn recordings_dir() -> PathBuf {
+    config_path().with_file_name("recordings")
+}
+
+/// A new file in `dir` named for the local time, such as
+/// `whitenoise-2026-10-17-2230.wav`, with a count added if that is taken.
+pub fn timestamped_path(dir: &Path) -> PathBuf {
+    let stem = chrono::Local::now()
+        .format("whitenoise-%Y-%m-%d-%H%M")
+        .to_string();
+    let mut path = dir.join(format!("{stem}.wav"));
+    let mut count = 2;
+    while path.exists() {
+        path = dir.join(format!("{stem}-{count}.wav"));
+        count += 1;
+    }
+    path
+}
+
+/// Interleaved stereo samples between the callback, which writes, and the
+/// writer thread, which reads. Each side only ever moves its own counter.
+#[derive(Debug)]
+struct Ring {
+    samples: Box<[AtomicU32]>,
+    written: AtomicUsize,
+    read: AtomicUsize,
+    dropped: AtomicU64,
+    // The rate of the first block, 0 before it. Blocks at another rate,
+    // from a device switched to mid-recording, are left out.
+    sample_rate: AtomicU32,
+    changed_rate: AtomicBool,
+}
+
+impl Ring {
+    fn new(capacity: usize) -> Self {
+        Self {
+            samples: (0..capacity).map(|_| AtomicU32::new(0)).collect(),
+            written: AtomicUsize::new(0),
+            read: AtomicUsize::new(0),
+            dropped: AtomicU64::new(0),
+            sample_rate: AtomicU32::new(0),
+            changed_rate: AtomicBool::new(false),
+        }
+    }
+
+    fn push(&self, frames: &[Frame], sample_rate: u32) {
+        let first =
+            self.sample_rate
+                .compare_exchange(0, sample_rate, Ordering::Relaxed, Ordering::Relaxed);
+        if first.is_err_and(|rate| rate != sample_rate) {
+            self.changed_rate.store(true, Ordering::Relaxed);
+            return;
+        }
+        let capacity = self.samples.len();
+        let written = self.written.load(Ordering::Relaxed);
+        let free = capacity - written.wrapping_sub(self.read.load(Ordering::Acquire));
+        let fits = frames.len().min(free / 2);
+        for (index, sample) in frames[..fits].iter().flatten().enumerate() {
+            self.samples[written.wrapping_add(index) % capacity]
+                .store(sample.to_bits(), Ordering::Relaxed);
+        }
+        self.written
+            .store(written.wrapping_add(fits * 2), Ordering::Release);
+        self.dropped
+            .fetch_add((frames.len() - fits) as u64, Ordering::Relaxed);
+    }
+
+    /// Moves everything written so far onto the end of `into`.
+    fn drain(&self, into: &mut Vec<f32>) {
+        let capacity = self.samples.len();
+        let read = self.read.load(Ordering::Relaxed);
+        let written = self.written.load(Ordering::Acquire);
+        let count = written.wrapping_sub(read);
+        into.extend((0..count).map(|index| {
+            f32::from_bits(
+                self.samples[read.wrapping_add(index) % capacity].load(Ordering::Relaxed),
+            )
+        }));
+        self.read.store(written, Ordering::Release);
+    }
+}
+
+/// Where the audio callback copies its output while a recording runs.
+#[derive(Debug, Default)]
+pub struct Tap {
+    recording: AtomicBool,
+    // Locked by the callback only with try_lock, and otherwise only when a
+    // recording starts or stops.
+    ring: Mutex<Option<Arc<Ring>>>,
+}
+
+impl Tap {
+    pub(crate) fn push(&self, frames: &[Frame], sample_rate: f32) {
+        if !self.recording.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Ok(ring) = self.ring.try_lock() {
+            if let Some(ring) = ring.as_ref() {
+                ring.push(frames, sample_rate as u32);
+            }
+        }
+    }
+
+    fn attach(&self, ring: Option<Arc<Ring>>) {
+        self.recording.store(ring.is_some(), Ordering::Relaxed);
+        *self
+            .ring
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = ring;
+    }
+}
+
+/// What a finished recording holds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recorded {
+    pub path: PathBuf,
+    pub seconds: f32,
+    /// Frames that found the ring full and are missing from the file.
+    pub dropped_frames: u64,
+    /// Whether the output changed to another sample rate, where the file
+    /// stops.
+    pub changed_rate: bool,
+}
+
+impl Recorded {
+    /// One line on where the recording went and anything missing from it.
+    pub fn summary(&self) -> String {
+        let mut line = text_with(
+            "record.saved",
+            &[
+                ("seconds", &format!("{:.1}", self.seconds)),
+                ("path", &self.path.display()),
+            ],
+        );
+        if self.dropped_frames > 0 {
+            line.push(' ');
+            line.push_str(&text_with(
+                "record.dropped",
+                &[("frames", &self.dropped_frames)],
+            ));
+        }
+        if self.changed_rate {
+            line.push(' ');
+            line.push_str(text("record.changed_rate"));
+        }
+        line
+    }
+}
+
+/// A recording in progress: the monitor's tap feeds it, and its thread
+/// writes the file. Stop it to finish the file.
+pub struct Recording {
+    path: PathBuf,
+    monitor: Arc<AudioMonitor>,
+    stop: Arc<AtomicBool>,
+    writer: JoinHandle<Result<Recorded>>,
+}
+
+impl Recording {
+    /// Starts copying everything `monitor`'s callback renders into a new
+    /// WAV file at `path`, creating its folder if need be.
+    pub fn start(path: &Path, monitor: Arc<AudioMonitor>) -> Result<Self> {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        // Opened here, so a path that cannot be written fails at once; the
+        // header waits for the first block's sample rate.
+        let file =
+            File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+        let ring = Arc::new(Ring::new(RING_SAMPLES));
+        monitor.tap().attach(Some(Arc::clone(&ring)));
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopping = Arc::clone(&stop);
+        let target = path.to_path_buf();
+        let writer = std::thread::spawn(move || write(file, &target, &ring, &stopping));
+        Ok(Self {
+            path: path.to_path_buf(),
+            monitor,
+            stop,
+            writer,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Stops taking blocks, writes what is left, and finishes the file.
+    pub fn stop(self) -> Result<Recorded> {
+        self.monitor.tap().attach(None);
+        self.stop.store(true, Ordering::Relaxed);
+        self.writer
+            .join()
+            .map_err(|_| anyhow!("the recording's writer panicked"))?
+    }
+}
+
+/// The writer thread: drains `ring` into the file until told to stop.
+fn write(file: File, path: &Path, ring: &Ring, stop: &AtomicBool) -> Result<Recorded> {
+    let mut file = Some(file);
+    let mut sink = None;
+    let mut buffer = Vec::new();
+    let mut samples = 0_u64;
+    loop {
+        // Read before draining, so nothing the callback wrote before the
+        // stop is left behind.
+        let stopping = stop.load(Ordering::Relaxed);
+        ring.drain(&mut buffer);
+        if !buffer.is_empty() {
+            let sink = match &mut sink {
+                Some(sink) => sink,
+                None => {
+                    let rate = ring.sample_rate.load(Ordering::Relaxed);
+                    let file = file.take().context("the recording's file is gone")?;
+                    sink.insert(WavSink::new(file, rate, 2)?)
+                }
+            };
+            sink.write(&buffer)
+                .with_context(|| format!("failed to write {}", path.display()))?;
+            samples += buffer.len() as u64;
+            buffer.clear();
+        }
+        if stopping {
+            break;
+        }
+        std::thread::sleep(DRAIN_INTERVAL);
+    }
+    let Some(mut sink) = sink else {
+        drop(file);
+        let _ = fs::remove_file(path);
+        bail!("nothing played while recording, so no file was kept");
+    };
+    sink.finish()?;
+    let rate = ring.sample_rate.load(Ordering::Relaxed).max(1);
+    Ok(Recorded {
+        path: path.to_path_buf(),
+        seconds: (samples / 2) as f32 / rate as f32,
+        dropped_frames: ring.dropped.load(Ordering::Relaxed),
+        changed_rate: ring.changed_rate.load(Ordering::Relaxed),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::StreamOptions;
+    use crate::settings::{AudioSettings, SharedSettings};
+    use crate::sink::{CaptureSink, VirtualDevice, play_virtual};
+
+    #[test]
+    fn the_ring_keeps_order_and_drops_what_does_not_fit() {
+        let ring = Ring::new(8);
+        ring.push(&[[0.1, 0.2], [0.3, 0.4], [0.5, 0.6]], 48_000);
+        let mut drained = Vec::new();
+        ring.drain(&mut drained);
+        assert_eq!(drained, [0.1, 0.2, 0.3, 0.4, 0.5, 0.6]);
+        // Wrapping around the end, with one frame too many.
+        ring.push(&[[1.0, -1.0]; 5], 48_000);
+        assert_eq!(ring.dropped.load(Ordering::Relaxed), 1);
+        drained.clear();
+        ring.drain(&mut drained);
+        assert_eq!(drained, [1.0, -1.0].repeat(4));
+        ring.push(&[[0.0; 2]], 44_100);
+        assert!(ring.changed_rate.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn a_recording_holds_exactly_what_the_output_played() {
+        let dir = std::env::temp_dir().join(format!("whitenoise-record-{}", std::process::id()));
+        let path = dir.join("session.wav");
+        let monitor = Arc::new(AudioMonitor::default());
+        let recording = Recording::start(&path, Arc::clone(&monitor)).unwrap();
+        assert_eq!(recording.path(), path);
+
+        let played = CaptureSink::default();
+        let device = VirtualDevice {
+            paced: false,
+            ..VirtualDevice::default()
+        };
+        let stream = play_virtual(
+            device,
+            Box::new(played.clone()),
+            Arc::new(SharedSettings::new(AudioSettings::default())),
+            Arc::new(AtomicBool::new(true)),
+            Arc::clone(&monitor),
+            StreamOptions {
+                seed: Some(1),
+                ..StreamOptions::default()
+            },
+        )
+        .unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        stream.stop();
+        let recorded = recording.stop().unwrap();
+        assert_eq!(recorded.dropped_frames, 0);
+        assert!(!recorded.changed_rate);
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().sample_rate, 48_000);
+        let samples: Vec<f32> = reader.into_samples().map(Result::unwrap).collect();
+        let played = played.samples();
+        // The callback renders a block ahead of what the device has taken,
+        // so the file may run past the last buffer but never differs.
+        assert!(played.len() >= 8_192 && samples.len() >= played.len());
+        assert_eq!(samples[..played.len()], played[..]);
+        assert!((recorded.seconds - samples.len() as f32 / 96_000.0).abs() < 1e-6);
+
+        // A recording that never heard a block leaves no file behind.
+        let empty = dir.join("empty.wav");
+        let recording = Recording::start(&empty, Arc::new(AudioMonitor::default())).unwrap();
+        assert!(recording.stop().is_err());
+        assert!(!empty.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
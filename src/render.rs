@@ -0,0 +1,588 @@
+//! Offline rendering to a WAV file, for phones and embedded players that
+//! cannot run whitenoise itself. Nothing here touches an audio device.
+
+use std::f32::consts::FRAC_PI_2;
+use std::fs::{self, File};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, ensure};
+
+use crate::audio::{Generator, StreamOptions};
+use crate::dsp::LoudnessMeter;
+use crate::settings::{AudioSettings, SplCalibration};
+use crate::timer::format_remaining;
+
+/// Discarded before capture so the startup volume ramp and the brown
+/// integrator have settled.
+const PRE_ROLL_SECONDS: f32 = 1.0;
+const LOOP_CROSSFADE_SECONDS: f32 = 2.0;
+/// 16-bit mono samples that fit in a WAV's 32-bit data length.
+const MAX_WAV_FRAMES: usize = (u32::MAX as usize - 44) / 2;
+/// Bytes per marker in the `cue ` chunk, and the most a `labl` entry for
+/// an `H:MM:SS` label can take.
+const CUE_POINT_BYTES: usize = 24;
+const MAX_LABEL_BYTES: usize = 24;
+/// Where `--all-presets` puts every file, as ungated single-channel
+/// BS.1770 loudness. Leaves peaks of even brown noise well clear of 0 dBFS.
+const BATCH_LOUDNESS_LUFS: f32 = -20.0;
+/// How much of each preset is measured before its file is rendered.
+const ANALYSIS_SECONDS: f32 = 30.0;
+/// The gain applies before quantization, so a quiet preset loses nothing by
+/// being raised; the limit only keeps a near-silent one from becoming hiss.
+const MAX_NORMALIZE_DB: f32 = 60.0;
+
+pub fn run(
+    path: &Path,
+    settings: AudioSettings,
+    options: StreamOptions,
+    sample_rate: u32,
+    duration: Duration,
+    chapters: Option<Duration>,
+) -> Result<()> {
+    let (frames, chapter_frames) = layout(sample_rate, duration, chapters)?;
+    println!(
+        "Rendering {} of {} at {:.0}% volume, {sample_rate} Hz mono, to {}",
+        format_remaining(duration),
+        settings.mix().describe(),
+        settings.volume * 100.0,
+        path.display()
+    );
+    let start = Instant::now();
+    write(
+        path,
+        settings,
+        options,
+        sample_rate,
+        frames,
+        chapter_frames,
+        1.0,
+    )?;
+    match chapter_frames {
+        Some(every) => println!(
+            "Done in {:.1} s, with {} chapter markers.",
+            start.elapsed().as_secs_f32(),
+            frames.div_ceil(every)
+        ),
+        None => println!("Done in {:.1} s.", start.elapsed().as_secs_f32()),
+    }
+    Ok(())
+}
+
+/// Renders each named preset to `<name>.wav` in `dir`, every file brought
+/// to the same loudness so that moving between them on a device does not
+/// jump in level. The presets' own volumes only matter to the limiter.
+pub fn run_all(
+    dir: &Path,
+    presets: &[(String, AudioSettings)],
+    options: StreamOptions,
+    sample_rate: u32,
+    duration: Duration,
+    chapters: Option<Duration>,
+) -> Result<()> {
+    ensure!(
+        !presets.is_empty(),
+        "no presets to render; save one with `whitenoise presets save NAME`"
+    );
+    let (frames, chapter_frames) = layout(sample_rate, duration, chapters)?;
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    println!(
+        "Rendering {} presets, {} each at {sample_rate} Hz mono and {BATCH_LOUDNESS_LUFS:.0} LUFS, to {}",
+        presets.len(),
+        format_remaining(duration),
+        dir.display()
+    );
+    let start = Instant::now();
+    for (name, settings) in presets {
+        let measured = measure_loudness(*settings, options, sample_rate, frames)?;
+        let gain_db = (BATCH_LOUDNESS_LUFS - measured).min(MAX_NORMALIZE_DB);
+        let path = dir.join(format!("{name}.wav"));
+        write(
+            &path,
+            *settings,
+            options,
+            sample_rate,
+            frames,
+            chapter_frames,
+            10_f32.powf(gain_db / 20.0),
+        )?;
+        println!(
+            "  {name}: {}, measured {measured:.1} LUFS, {gain_db:+.1} dB",
+            settings.mix().describe()
+        );
+    }
+    println!("Done in {:.1} s.", start.elapsed().as_secs_f32());
+    Ok(())
+}
+
+/// The render's length in frames and its chapter interval, checked against
+/// each other and the WAV size limit.
+fn layout(
+    sample_rate: u32,
+    duration: Duration,
+    chapters: Option<Duration>,
+) -> Result<(usize, Option<usize>)> {
+    let frames = (duration.as_secs_f64() * f64::from(sample_rate)).round() as usize;
+    ensure!(frames > 0, "render duration is too short");
+    let chapter_frames = chapters
+        .map(|every| (every.as_secs_f64() * f64::from(sample_rate)).round() as usize)
+        .map(|every| {
+            ensure!(
+                every > 0 && every < frames,
+                "the chapter interval must be shorter than the render"
+            );
+            Ok(every)
+        })
+        .transpose()?;
+    // The markers, and the two chunk headers around them, in frames' worth
+    // of bytes.
+    let marker_frames = chapter_frames.map_or(0, |every| {
+        (frames.div_ceil(every) * (CUE_POINT_BYTES + MAX_LABEL_BYTES) + 24) / 2
+    });
+    ensure!(
+        frames + marker_frames <= MAX_WAV_FRAMES,
+        "{} at {sample_rate} Hz exceeds the 4 GiB WAV size limit; render a shorter loop",
+        format_remaining(duration)
+    );
+    Ok((frames, chapter_frames))
+}
+
+fn write(
+    path: &Path,
+    settings: AudioSettings,
+    options: StreamOptions,
+    sample_rate: u32,
+    frames: usize,
+    chapter_frames: Option<usize>,
+    gain: f32,
+) -> Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+    render(
+        BufWriter::new(file),
+        settings,
+        options,
+        sample_rate,
+        frames,
+        chapter_frames,
+        gain,
+    )
+    .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// The SPL calibration belongs to the speakers it was measured on, so its
+/// cap does not follow the sound into a file.
+pub(crate) fn for_file(settings: AudioSettings) -> AudioSettings {
+    AudioSettings {
+        spl: SplCalibration::default(),
+        ..settings
+    }
+}
+
+/// The K-weighted loudness of the first stretch a render would capture, up
+/// to `ANALYSIS_SECONDS`. Noise and the loops are steady, so that stretch
+/// stands for the whole file.
+fn measure_loudness(
+    settings: AudioSettings,
+    options: StreamOptions,
+    sample_rate: u32,
+    frames: usize,
+) -> Result<f32> {
+    let rate = sample_rate as f32;
+    let mut generator = Generator::new(rate, 1, for_file(settings), options)?;
+    let mut meter = LoudnessMeter::new(rate);
+    // The pre-roll also fills the meter's one-second average.
+    for _ in 0..(rate * PRE_ROLL_SECONDS) as usize {
+        meter.process(generator.next_sample());
+    }
+    let measured = frames.min((rate * ANALYSIS_SECONDS) as usize);
+    let mut total = 0.0_f64;
+    for _ in 0..measured {
+        meter.process(generator.next_sample());
+        total += f64::from(meter.mean_square());
+    }
+    let mean_square = (total / measured as f64).max(1e-12);
+    Ok(-0.691 + 10.0 * mean_square.log10() as f32)
+}
+
+/// Writes `frames` samples of 16-bit mono WAV that loop without a seam: the
+/// last stretch crossfades, equal-power, into the audio just before the
+/// file's first sample. With `chapter_frames`, a marker labeled with its
+/// elapsed time starts the file and every interval after it. `gain` scales
+/// the engine's output before it is quantized.
+///
+/// The audio comes from a [`Generator`], block by block like an output
+/// stream, so with a seed in `options` the file is that stream's left
+/// channel from just past the pre-roll and the crossfade's lead-in.
+pub(crate) fn render<W: Write + Seek>(
+    mut writer: W,
+    settings: AudioSettings,
+    options: StreamOptions,
+    sample_rate: u32,
+    frames: usize,
+    chapter_frames: Option<usize>,
+    gain: f32,
+) -> Result<()> {
+    let rate = sample_rate as f32;
+    let mut generator = Generator::new(rate, 1, for_file(settings), options)?;
+    for _ in 0..(rate * PRE_ROLL_SECONDS) as usize {
+        generator.next_sample();
+    }
+
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut wav = hound::WavWriter::new(&mut writer, spec)?;
+    let to_pcm =
+        |sample: f32| ((sample * gain).clamp(-1.0, 1.0) * f32::from(i16::MAX)).round() as i16;
+
+    let crossfade = ((rate * LOOP_CROSSFADE_SECONDS) as usize).min(frames / 3);
+    let head: Vec<f32> = (0..crossfade).map(|_| generator.next_sample()).collect();
+    for _ in 0..frames - crossfade {
+        wav.write_sample(to_pcm(generator.next_sample()))?;
+    }
+    for (index, lead_in) in head.iter().enumerate() {
+        // Ends exactly on the last head sample, which precedes the first
+        // sample written above.
+        let angle = (index + 1) as f32 / crossfade as f32 * FRAC_PI_2;
+        let blended = generator.next_sample() * angle.cos() + lead_in * angle.sin();
+        wav.write_sample(to_pcm(blended))?;
+    }
+    wav.finalize()?;
+    if let Some(every) = chapter_frames {
+        append_markers(&mut writer, frames, every, sample_rate)?;
+    }
+    Ok(())
+}
+
+/// Appends a `cue ` chunk and the `LIST`/`adtl` labels that name its points,
+/// the markers that Audacity, foobar2000, and most DAWs show and can jump
+/// between, then fixes up the RIFF size. hound writes neither chunk.
+fn append_markers<W: Write + Seek>(
+    writer: &mut W,
+    frames: usize,
+    every: usize,
+    sample_rate: u32,
+) -> Result<()> {
+    let positions: Vec<u32> = (0..frames)
+        .step_by(every)
+        .map(|frame| frame as u32)
+        .collect();
+
+    let mut cue = Vec::with_capacity(4 + positions.len() * CUE_POINT_BYTES);
+    cue.extend((positions.len() as u32).to_le_bytes());
+    let mut labels = b"adtl".to_vec();
+    for (index, position) in positions.iter().enumerate() {
+        let id = index as u32 + 1;
+        cue.extend(id.to_le_bytes());
+        cue.extend(position.to_le_bytes());
+        cue.extend(b"data");
+        cue.extend(0_u32.to_le_bytes());
+        cue.extend(0_u32.to_le_bytes());
+        cue.extend(position.to_le_bytes());
+
+        let elapsed = Duration::from_secs_f64(f64::from(*position) / f64::from(sample_rate));
+        let mut text = id.to_le_bytes().to_vec();
+        text.extend(format_remaining(elapsed).as_bytes());
+        text.push(0);
+        labels.extend(chunk(b"labl", &text));
+    }
+
+    writer.seek(SeekFrom::End(0))?;
+    writer.write_all(&chunk(b"cue ", &cue))?;
+    writer.write_all(&chunk(b"LIST", &labels))?;
+    let riff_size = writer.stream_position()? - 8;
+    writer.seek(SeekFrom::Start(4))?;
+    writer.write_all(&(riff_size as u32).to_le_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// One RIFF chunk, padded to an even length as the format requires.
+fn chunk(id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut bytes = id.to_vec();
+    bytes.extend((body.len() as u32).to_le_bytes());
+    bytes.extend(body);
+    if body.len() % 2 == 1 {
+        bytes.push(0);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::audio::UserSample;
+    use crate::settings::{SoundStyle, SourceMix};
+
+    fn decode(data: Vec<u8>) -> (hound::WavSpec, Vec<i16>) {
+        let reader = hound::WavReader::new(Cursor::new(data)).unwrap();
+        let spec = reader.spec();
+        let samples = reader.into_samples::<i16>().map(Result::unwrap).collect();
+        (spec, samples)
+    }
+
+    #[test]
+    fn render_writes_the_requested_length_of_audible_mono_pcm() {
+        let mut settings = AudioSettings {
+            volume: 0.5,
+            ..AudioSettings::default()
+        };
+        settings.set_mix(SourceMix::solo(SoundStyle::Brown));
+        let mut output = Cursor::new(Vec::new());
+        render(
+            &mut output,
+            settings,
+            StreamOptions::default(),
+            8_000,
+            16_000,
+            None,
+            1.0,
+        )
+        .unwrap();
+
+        let (spec, samples) = decode(output.into_inner());
+        assert_eq!((spec.channels, spec.sample_rate), (1, 8_000));
+        assert_eq!(samples.len(), 16_000);
+        let rms = (samples
+            .iter()
+            .map(|sample| f64::from(*sample).powi(2))
+            .sum::<f64>()
+            / samples.len() as f64)
+            .sqrt();
+        assert!(rms > 100.0, "render is nearly silent: {rms}");
+    }
+
+    #[test]
+    fn a_seeded_render_matches_the_live_stream() {
+        let mut settings = AudioSettings {
+            volume: 0.5,
+            ..AudioSettings::default()
+        };
+        settings.set_mix(SourceMix {
+            pink: 0.5,
+            ocean: 0.5,
+            ..SourceMix::default()
+        });
+        let options = StreamOptions {
+            seed: Some(21),
+            ..StreamOptions::default()
+        };
+        let mut output = Cursor::new(Vec::new());
+        render(&mut output, settings, options, 8_000, 48_000, None, 1.0).unwrap();
+        let (_, samples) = decode(output.into_inner());
+
+        // An output stream hands its callbacks buffers of any size.
+        let mut live = Generator::new(8_000.0, 2, settings, options).unwrap();
+        let mut played = Vec::new();
+        let mut buffer = vec![0.0; 2 * 300];
+        while played.len() < 2 * 56_000 {
+            live.fill_buffer(&mut buffer);
+            played.extend_from_slice(&buffer);
+        }
+        // The file starts after the pre-roll and the lead-in that the loop
+        // fades into at its end.
+        let skip = 8_000 + 16_000;
+        let left = played.iter().step_by(2).skip(skip);
+        for (written, live) in samples[..48_000 - 16_000].iter().zip(left) {
+            let live = (live.clamp(-1.0, 1.0) * f32::from(i16::MAX)).round() as i16;
+            assert_eq!(*written, live);
+        }
+
+        let mut unseeded = Cursor::new(Vec::new());
+        render(
+            &mut unseeded,
+            settings,
+            StreamOptions::default(),
+            8_000,
+            48_000,
+            None,
+            1.0,
+        )
+        .unwrap();
+        assert_ne!(decode(unseeded.into_inner()).1, samples);
+    }
+
+    #[test]
+    fn rendered_file_loops_without_a_seam() {
+        // A slow sine as the sample makes any discontinuity measurable.
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 8_000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut wav = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut wav, spec).unwrap();
+        for frame in 0..8_000 * 3 {
+            let phase = frame as f32 * 110.0 / 8_000.0 * std::f32::consts::TAU;
+            writer
+                .write_sample((phase.sin() * 16_000.0) as i16)
+                .unwrap();
+        }
+        writer.finalize().unwrap();
+        let sample = UserSample::measure(wav.into_inner()).unwrap();
+
+        let mut settings = AudioSettings {
+            volume: 0.5,
+            ..AudioSettings::default()
+        };
+        settings.set_mix(SourceMix::solo(SoundStyle::Sample));
+        let mut output = Cursor::new(Vec::new());
+        render(
+            &mut output,
+            settings,
+            StreamOptions {
+                sample: Some(&sample),
+                ..StreamOptions::default()
+            },
+            8_000,
+            20_000,
+            None,
+            1.0,
+        )
+        .unwrap();
+
+        let (_, samples) = decode(output.into_inner());
+        let step = |a: i16, b: i16| (i32::from(a) - i32::from(b)).abs();
+        let largest_step = samples
+            .windows(2)
+            .map(|pair| step(pair[0], pair[1]))
+            .max()
+            .unwrap();
+        let seam = step(samples[samples.len() - 1], samples[0]);
+        assert!(largest_step > 0);
+        assert!(seam <= largest_step, "seam {seam} vs body {largest_step}");
+    }
+
+    #[test]
+    fn chapters_add_labeled_cue_points_that_players_can_read() {
+        let mut output = Cursor::new(Vec::new());
+        let settings = AudioSettings {
+            volume: 0.5,
+            ..AudioSettings::default()
+        };
+        render(
+            &mut output,
+            settings,
+            StreamOptions::default(),
+            8_000,
+            20_000,
+            Some(8_000),
+            1.0,
+        )
+        .unwrap();
+        let data = output.into_inner();
+
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+        assert_eq!(u32_at(4) as usize, data.len() - 8);
+        let cue = data.windows(4).position(|id| id == b"cue ").unwrap();
+        assert_eq!(u32_at(cue + 8), 3);
+        let offsets: Vec<u32> = (0..3)
+            .map(|point| u32_at(cue + 12 + point * 24 + 20))
+            .collect();
+        assert_eq!(offsets, [0, 8_000, 16_000]);
+        let list = String::from_utf8_lossy(&data[cue..]);
+        assert!(list.contains("adtl"));
+        for label in ["0:00\0", "0:01\0", "0:02\0"] {
+            assert!(list.contains(label), "{label:?} missing");
+        }
+
+        // Readers that skip unknown chunks still see all the audio.
+        let (_, samples) = decode(data);
+        assert_eq!(samples.len(), 20_000);
+
+        assert!(
+            run(
+                Path::new("unused.wav"),
+                settings,
+                StreamOptions::default(),
+                8_000,
+                Duration::from_secs(60),
+                Some(Duration::from_secs(60)),
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn all_presets_render_to_one_loudness_whatever_their_volume() {
+        let preset = |style, volume| {
+            let mut settings = AudioSettings {
+                volume,
+                ..AudioSettings::default()
+            };
+            settings.set_mix(SourceMix::solo(style));
+            settings
+        };
+        let presets = [
+            ("bright".to_owned(), preset(SoundStyle::White, 0.6)),
+            ("deep sleep".to_owned(), preset(SoundStyle::Brown, 0.1)),
+        ];
+        let dir =
+            std::env::temp_dir().join(format!("whitenoise-render-test-{}", std::process::id()));
+        run_all(
+            &dir,
+            &presets,
+            StreamOptions::default(),
+            8_000,
+            Duration::from_secs(6),
+            None,
+        )
+        .unwrap();
+
+        let loudness: Vec<f32> = presets
+            .iter()
+            .map(|(name, _)| {
+                let data = fs::read(dir.join(format!("{name}.wav"))).unwrap();
+                let (_, samples) = decode(data);
+                assert_eq!(samples.len(), 48_000);
+                let mut meter = LoudnessMeter::new(8_000.0);
+                let mut total = 0.0;
+                for (index, sample) in samples.iter().enumerate() {
+                    meter.process(f32::from(*sample) / f32::from(i16::MAX));
+                    if index >= 8_000 {
+                        total += f64::from(meter.mean_square());
+                    }
+                }
+                -0.691 + 10.0 * (total / 40_000.0).log10() as f32
+            })
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+        for measured in &loudness {
+            assert!((measured - BATCH_LOUDNESS_LUFS).abs() < 1.0, "{loudness:?}");
+        }
+        assert!(
+            run_all(
+                &dir,
+                &[],
+                StreamOptions::default(),
+                8_000,
+                Duration::from_secs(6),
+                None
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn renders_too_large_for_a_wav_are_refused() {
+        let error = run(
+            Path::new("unused.wav"),
+            AudioSettings::default(),
+            StreamOptions::default(),
+            384_000,
+            Duration::from_secs(24 * 3600),
+            None,
+        )
+        .unwrap_err();
+        assert!(format!("{error:#}").contains("4 GiB"));
+    }
+}
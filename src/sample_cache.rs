@@ -0,0 +1,244 @@
+//! What measuring a user sample found, kept between runs so a long loop is
+//! decoded and measured once rather than at every start. Each sample gets
+//! a small TOML file named after a hash of its contents, so a renamed or
+//! moved file still hits and an edited one misses. The entry reads like a
+//! file's ReplayGain tags: the loudness and the track gain a player that
+//! honors them would apply.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::audio::{SampleLevel, UserSample};
+use crate::settings::config_path;
+
+/// Raised whenever the measurement changes, so entries from an older
+/// binary are measured again instead of trusted.
+const ANALYSIS_VERSION: u32 = 2;
+
+/// `$XDG_CACHE_HOME/whitenoise/samples`, or `cache/samples` beside the
+/// settings file where there is no cache directory.
+pub fn cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .map(|dir| dir.join("whitenoise"))
+        .unwrap_or_else(|| config_path().with_file_name("cache"))
+        .join("samples")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    version: u32,
+    /// Checked as well as the hash, so a collision would also need the
+    /// same length.
+    bytes: u64,
+    loudness_lufs: f32,
+    /// ReplayGain 2.0 track gain, for reading alongside other players' tags.
+    replay_gain_db: f32,
+}
+
+/// The sample in `data` with its level from the cache in `dir`, measuring
+/// it and storing the result on a miss. With `honor_tags`, a file's own
+/// ReplayGain track gain is taken instead and nothing is measured. The
+/// cache is only an optimization: an unreadable entry is measured again
+/// and a failed write is reported and otherwise ignored.
+pub fn load(data: Vec<u8>, dir: &Path, honor_tags: bool) -> Result<UserSample> {
+    let tagged = if honor_tags {
+        SampleLevel::from_tag(&data)
+    } else {
+        None
+    };
+    if let Some(level) = tagged {
+        // Decoding still has to succeed, or the tag would vouch for garbage.
+        hound::WavReader::new(std::io::Cursor::new(&data))
+            .context("failed to decode the sample loop (only WAV is supported)")?;
+        return Ok(UserSample { data, level });
+    }
+    let path = dir.join(format!("{:016x}.toml", fingerprint(&data)));
+    let cached = read_entry(&path).filter(|entry| {
+        entry.version == ANALYSIS_VERSION
+            && entry.bytes == data.len() as u64
+            && entry.loudness_lufs.is_finite()
+    });
+    if let Some(entry) = cached {
+        return Ok(UserSample {
+            data,
+            level: SampleLevel::from_loudness(entry.loudness_lufs),
+        });
+    }
+
+    let sample = UserSample::measure(data)?;
+    let entry = Entry {
+        version: ANALYSIS_VERSION,
+        bytes: sample.data.len() as u64,
+        loudness_lufs: sample.level.loudness_lufs,
+        replay_gain_db: sample.level.replay_gain_db(),
+    };
+    if let Err(error) = write_entry(&path, entry) {
+        eprintln!("warning: {error:#}; the sample will be measured again next time");
+    }
+    Ok(sample)
+}
+
+fn read_entry(path: &Path) -> Option<Entry> {
+    toml::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+fn write_entry(path: &Path, entry: Entry) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    fs::write(path, toml::to_string(&entry)?)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// 64-bit FNV-1a: stable across builds and platforms, unlike std's
+/// `DefaultHasher`, and far cheaper than decoding the file.
+fn fingerprint(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn wav(amplitude: f32) -> Vec<u8> {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 8_000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut wav = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut wav, spec).unwrap();
+        for frame in 0..8_000 {
+            let value = (frame as f32 * 0.3).sin() * amplitude * 32_767.0;
+            writer.write_sample(value as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        wav.into_inner()
+    }
+
+    #[test]
+    fn a_sample_is_measured_once_and_read_back_after() {
+        let dir = std::env::temp_dir().join(format!(
+            "whitenoise-sample-cache-test-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+
+        let measured = load(wav(0.5), &dir, false).unwrap();
+        assert_eq!(measured, UserSample::measure(wav(0.5)).unwrap());
+        let path = dir.join(format!("{:016x}.toml", fingerprint(&wav(0.5))));
+        let entry = read_entry(&path).unwrap();
+        assert_eq!(entry.loudness_lufs, measured.level.loudness_lufs);
+
+        // A planted loudness proves the second load reads the entry.
+        fs::write(
+            &path,
+            toml::to_string(&Entry {
+                loudness_lufs: -40.0,
+                ..entry
+            })
+            .unwrap(),
+        )
+        .unwrap();
+        let cached = load(wav(0.5), &dir, false).unwrap();
+        assert_eq!(cached.level, SampleLevel::from_loudness(-40.0));
+        assert_eq!(cached.data, wav(0.5));
+
+        // Other contents, a stale version, and garbage are measured afresh.
+        let quieter = load(wav(0.05), &dir, false).unwrap();
+        assert!(quieter.level.loudness_lufs < measured.level.loudness_lufs - 15.0);
+        for text in [
+            toml::to_string(&Entry {
+                version: ANALYSIS_VERSION + 1,
+                loudness_lufs: -40.0,
+                ..entry
+            })
+            .unwrap(),
+            "not toml".to_owned(),
+        ] {
+            fs::write(&path, text).unwrap();
+            assert_eq!(load(wav(0.5), &dir, false).unwrap(), measured);
+            assert_eq!(read_entry(&path), Some(entry));
+        }
+
+        assert!(load(b"not a wav".to_vec(), &dir, false).is_err());
+        assert_ne!(fingerprint(b"ab"), fingerprint(b"ba"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    /// `data` with a chunk of `id` holding `text` after its audio.
+    fn tagged(mut data: Vec<u8>, id: &[u8; 4], text: &[u8]) -> Vec<u8> {
+        data.extend_from_slice(id);
+        data.extend_from_slice(&(text.len() as u32).to_le_bytes());
+        data.extend_from_slice(text);
+        if text.len() % 2 == 1 {
+            data.push(0);
+        }
+        let riff = (data.len() - 8) as u32;
+        data[4..8].copy_from_slice(&riff.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn a_replay_gain_tag_stands_in_for_the_measurement_when_honored() {
+        let dir = std::env::temp_dir().join(format!(
+            "whitenoise-sample-tags-test-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+
+        let list = tagged(
+            wav(0.5),
+            b"LIST",
+            b"INFOICMT\x1f\0\0\0replaygain_track_gain=-6.50 dB\0",
+        );
+        let honored = load(list.clone(), &dir, true).unwrap();
+        assert!(honored.level.tagged);
+        assert!((honored.level.replay_gain_db() + 6.5).abs() < 1e-4);
+        assert!((honored.level.loudness_lufs + 11.5).abs() < 1e-4);
+        assert!(
+            fs::read_dir(&dir).is_err(),
+            "a tagged sample is not measured"
+        );
+
+        // Left alone, the tag changes nothing, and the measurement's own
+        // track gain is kept with it.
+        let measured = load(list, &dir, false).unwrap();
+        assert!(!measured.level.tagged);
+        assert_eq!(measured.level, UserSample::measure(wav(0.5)).unwrap().level);
+        let entry = read_entry(&dir.join(format!("{:016x}.toml", fingerprint(&measured.data))));
+        assert_eq!(
+            entry.map(|entry| entry.replay_gain_db),
+            Some(measured.level.replay_gain_db())
+        );
+
+        // An ID3 frame's tag reads too; an untagged file or an absurd gain
+        // is measured.
+        let id3 = tagged(
+            wav(0.5),
+            b"id3 ",
+            b"ID3\x03\0\0\0\0\0\0TXXX\0\0\0\x20\0\0\0REPLAYGAIN_TRACK_GAIN\0+2.25 dB",
+        );
+        let level = load(id3, &dir, true).unwrap().level;
+        assert!(level.tagged && (level.replay_gain_db() - 2.25).abs() < 1e-4);
+        for data in [
+            wav(0.5),
+            tagged(wav(0.5), b"LIST", b"REPLAYGAIN_TRACK_GAIN=-900 dB"),
+        ] {
+            assert!(!load(data, &dir, true).unwrap().level.tagged);
+        }
+        // A tag does not vouch for a file that does not decode.
+        let mut broken = tagged(wav(0.5), b"LIST", b"REPLAYGAIN_TRACK_GAIN=-6 dB");
+        broken[12..16].copy_from_slice(b"fmX ");
+        assert!(load(broken, &dir, true).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,863 @@
+//! Scenes: a preset's whole sound plus what a preset cannot hold, one TOML
+//! file each under the config directory's `scenes/` folder. A scene lists
+//! its sources as layers, each with its own level, low- and high-pass, and
+//! a slow swell of its level, and may start a sleep timer. A layer may also
+//! play only at certain hours or for a share of the time, which the
+//! schedule's thread works out as the night goes on. `--scene`, the `scene`
+//! command, and `[[schedule]]` entries load them by name, and the
+//! interface's Scenes page edits their layers.
+
+use std::f32::consts::TAU;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use rand::RngExt;
+use serde::{Deserialize, Serialize};
+
+use crate::night::ClockTime;
+
+use crate::preset::{
+    SCHEMA_VERSION, apply_preset, list_presets, parse_sound, presets_dir, stamp, validate_name,
+};
+use crate::settings::{AudioSettings, SoundStyle, SourceMix};
+use crate::timer::{SleepTimer, TimerAlarm, format_remaining, parse_duration};
+
+pub const MIN_LAYER_HZ: f32 = 20.0;
+pub const MAX_LAYER_HZ: f32 = 20_000.0;
+pub const MIN_SWELL_SECONDS: f32 = 2.0;
+pub const MAX_SWELL_SECONDS: f32 = 600.0;
+const DEFAULT_SWELL_SECONDS: f32 = 30.0;
+pub const MIN_SPELL_SECONDS: f32 = 5.0;
+pub const MAX_SPELL_SECONDS: f32 = 3600.0;
+const DEFAULT_SPELL_SECONDS: f32 = 60.0;
+/// Third-octave centers from 20 Hz to 20 kHz, the steps the scene editor
+/// moves a layer's filter corners by.
+pub const LAYER_STEPS_HZ: [f32; 31] = [
+    20.0, 25.0, 31.5, 40.0, 50.0, 63.0, 80.0, 100.0, 125.0, 160.0, 200.0, 250.0, 315.0, 400.0,
+    500.0, 630.0, 800.0, 1_000.0, 1_250.0, 1_600.0, 2_000.0, 2_500.0, 3_150.0, 4_000.0, 5_000.0,
+    6_300.0, 8_000.0, 10_000.0, 12_500.0, 16_000.0, 20_000.0,
+];
+/// The swell periods the scene editor steps through.
+pub const SWELL_STEPS_SECONDS: [f32; 14] = [
+    2.0, 5.0, 10.0, 15.0, 20.0, 30.0, 45.0, 60.0, 90.0, 120.0, 180.0, 300.0, 450.0, 600.0,
+];
+
+pub fn scenes_dir() -> PathBuf {
+    scenes_beside(&presets_dir())
+}
+
+/// The `scenes/` folder next to a `presets/` one, which is how commands
+/// given only the presets folder find the scenes.
+pub fn scenes_beside(presets: &Path) -> PathBuf {
+    presets.with_file_name("scenes")
+}
+
+/// A slow rise and fall of one layer's level: down by `depth` of it at
+/// the bottom of each `period_seconds` cycle. A depth of 0 holds it still.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Modulation {
+    pub depth: f32,
+    pub period_seconds: f32,
+}
+
+impl Default for Modulation {
+    fn default() -> Self {
+        Self {
+            depth: 0.0,
+            period_seconds: DEFAULT_SWELL_SECONDS,
+        }
+    }
+}
+
+impl Modulation {
+    /// The level's multiplier at `phase`, a fraction of the period; full at
+    /// the start of each cycle.
+    pub fn gain(&self, phase: f32) -> f32 {
+        1.0 - self.depth * 0.5 * (1.0 - (TAU * phase).cos())
+    }
+
+    fn sanitize(self) -> Self {
+        Self {
+            depth: if self.depth.is_finite() {
+                self.depth.clamp(0.0, 1.0)
+            } else {
+                0.0
+            },
+            period_seconds: if self.period_seconds.is_finite() {
+                self.period_seconds
+                    .clamp(MIN_SWELL_SECONDS, MAX_SWELL_SECONDS)
+            } else {
+                DEFAULT_SWELL_SECONDS
+            },
+        }
+    }
+}
+
+/// When a layer plays: inside a daily window from `from` until `until`,
+/// and with a `chance`, only in the spells of `spell_seconds` a draw lets
+/// it have. A layer with neither plays throughout.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LayerTiming {
+    pub window: Option<(ClockTime, ClockTime)>,
+    pub chance: Option<f32>,
+    pub spell_seconds: Option<f32>,
+}
+
+impl LayerTiming {
+    /// Whether the layer comes and goes at all.
+    pub fn timed(&self) -> bool {
+        self.window.is_some() || self.chance.is_some()
+    }
+
+    fn spell(&self) -> Duration {
+        Duration::from_secs_f32(self.spell_seconds.unwrap_or(DEFAULT_SPELL_SECONDS))
+    }
+
+    fn sanitize(self) -> Self {
+        Self {
+            window: self.window,
+            chance: self
+                .chance
+                .filter(|chance| chance.is_finite())
+                .map(|chance| chance.clamp(0.0, 1.0)),
+            spell_seconds: self
+                .spell_seconds
+                .filter(|seconds| seconds.is_finite())
+                .map(|seconds| seconds.clamp(MIN_SPELL_SECONDS, MAX_SPELL_SECONDS)),
+        }
+    }
+}
+
+/// What a scene does to one source beyond its level, kept in the live
+/// settings so the audio callback sees it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LayerShape {
+    pub low_pass_hz: Option<f32>,
+    pub high_pass_hz: Option<f32>,
+    pub modulation: Modulation,
+    pub timing: LayerTiming,
+    /// Set while the timing keeps the layer out; the engine fades it.
+    pub silenced: bool,
+}
+
+impl LayerShape {
+    pub fn sanitize(self) -> Self {
+        let corner = |hz: Option<f32>| {
+            hz.filter(|hz| hz.is_finite())
+                .map(|hz| hz.clamp(MIN_LAYER_HZ, MAX_LAYER_HZ))
+        };
+        Self {
+            low_pass_hz: corner(self.low_pass_hz),
+            high_pass_hz: corner(self.high_pass_hz),
+            modulation: self.modulation.sanitize(),
+            timing: self.timing.sanitize(),
+            silenced: self.silenced,
+        }
+    }
+}
+
+/// One `[[layers]]` table: a source at a level, a power fraction as in
+/// `[mix]`, with its own filters and swell, and when it plays.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Layer {
+    pub source: SoundStyle,
+    pub level: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub low_pass_hz: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub high_pass_hz: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modulation: Option<Modulation>,
+    /// Given together, the daily window the layer plays in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<ClockTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<ClockTime>,
+    /// The share of the time it plays, drawn afresh for each spell.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chance: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spell_seconds: Option<f32>,
+}
+
+impl Layer {
+    /// `source` at `level` with no filters or swell.
+    pub fn new(source: SoundStyle, level: f32) -> Self {
+        Self {
+            source,
+            level,
+            low_pass_hz: None,
+            high_pass_hz: None,
+            modulation: None,
+            from: None,
+            until: None,
+            chance: None,
+            spell_seconds: None,
+        }
+    }
+
+    /// Starts silent when timed, for the schedule's thread to let in.
+    fn shape(&self) -> LayerShape {
+        let timing = LayerTiming {
+            window: self.from.zip(self.until),
+            chance: self.chance,
+            spell_seconds: self.spell_seconds,
+        };
+        LayerShape {
+            low_pass_hz: self.low_pass_hz,
+            high_pass_hz: self.high_pass_hz,
+            modulation: self.modulation.unwrap_or_default(),
+            timing,
+            silenced: timing.timed(),
+        }
+        .sanitize()
+    }
+
+    /// `Rain 60% (low-pass 4000 Hz, swell 30% every 40 s, 06:00 to 08:00,
+    /// 10% of the time in 60 s spells)`.
+    pub fn describe(&self) -> String {
+        let shape = self.shape();
+        let mut effects = Vec::new();
+        if let Some(hz) = shape.high_pass_hz {
+            effects.push(format!("high-pass {hz:.0} Hz"));
+        }
+        if let Some(hz) = shape.low_pass_hz {
+            effects.push(format!("low-pass {hz:.0} Hz"));
+        }
+        if shape.modulation.depth > 0.0 {
+            effects.push(format!(
+                "swell {:.0}% every {:.0} s",
+                shape.modulation.depth * 100.0,
+                shape.modulation.period_seconds
+            ));
+        }
+        if let Some((from, until)) = shape.timing.window {
+            effects.push(format!("{from} to {until}"));
+        }
+        if let Some(chance) = shape.timing.chance {
+            effects.push(format!(
+                "{:.0}% of the time in {:.0} s spells",
+                chance * 100.0,
+                shape.timing.spell().as_secs_f32()
+            ));
+        }
+        let mut line = format!("{} {:.0}%", self.source.label(), self.level * 100.0);
+        if !effects.is_empty() {
+            line.push_str(&format!(" ({})", effects.join(", ")));
+        }
+        line
+    }
+}
+
+/// A scene's `[timer]`: a sleep timer that starts with the scene unless one
+/// is already running. The lengths read like `--timer`'s.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneTimer {
+    pub length: String,
+    /// The fade at the end; `--fade-out`'s length when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fade_out: Option<String>,
+    #[serde(default)]
+    pub alarm: TimerAlarm,
+}
+
+impl SceneTimer {
+    fn lengths(&self, fade_out: Duration) -> Result<(Duration, Duration)> {
+        let length = parse_duration(&self.length).map_err(anyhow::Error::msg)?;
+        let fade = match &self.fade_out {
+            Some(fade) => parse_duration(fade).map_err(anyhow::Error::msg)?,
+            None => fade_out,
+        };
+        Ok((length, fade))
+    }
+}
+
+/// One scene file. Everything a preset holds may appear at the top level,
+/// with the same defaults; `[[layers]]`, when present, replace its mix.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Scene {
+    /// Unlike a preset's, a scene's volume is optional; without one the
+    /// volume stays where it is.
+    pub volume: Option<f32>,
+    #[serde(flatten)]
+    pub sound: AudioSettings,
+    pub layers: Vec<Layer>,
+    pub timer: Option<SceneTimer>,
+    /// What `load_scene` could not fully read, for the caller to show.
+    #[serde(skip)]
+    pub warnings: Vec<String>,
+}
+
+impl Scene {
+    /// A preset as the scene that plays the same: its volume, its mix, and
+    /// no layer shaping.
+    pub fn from_preset(preset: AudioSettings) -> Self {
+        Self {
+            volume: Some(preset.volume),
+            sound: preset,
+            ..Self::default()
+        }
+    }
+
+    /// Rejects a source layered twice and a timer that does not parse.
+    fn check(&self) -> Result<()> {
+        for (index, layer) in self.layers.iter().enumerate() {
+            if self.layers[..index]
+                .iter()
+                .any(|earlier| earlier.source == layer.source)
+            {
+                bail!("{} has two layers", layer.source.label());
+            }
+            if layer.from.is_some() != layer.until.is_some() {
+                bail!(
+                    "the {} layer needs both from and until, or neither",
+                    layer.source.label()
+                );
+            }
+        }
+        if let Some(timer) = &self.timer {
+            timer.lengths(Duration::ZERO)?;
+        }
+        Ok(())
+    }
+
+    /// The layers to edit: the scene's own, or one for each source its mix
+    /// plays when it has none.
+    pub fn editable_layers(&self) -> Vec<Layer> {
+        if self.layers.is_empty() {
+            playing_layers(&self.sound)
+        } else {
+            self.layers.clone()
+        }
+    }
+}
+
+/// The layers' levels as a mix, or None without layers.
+fn layers_mix(layers: &[Layer]) -> Option<SourceMix> {
+    let first = layers.first()?;
+    let mut mix = SourceMix::solo(first.source);
+    mix.set_level(first.source, 0.0);
+    for layer in layers {
+        mix.set_level(layer.source, layer.level);
+    }
+    Some(mix)
+}
+
+/// A layer for each source `settings` plays, with its level and any
+/// shaping a scene gave it.
+pub fn playing_layers(settings: &AudioSettings) -> Vec<Layer> {
+    let mix = settings.mix();
+    SoundStyle::ALL
+        .iter()
+        .zip(settings.layers)
+        .filter(|(style, _)| mix.level(**style) > 0.0)
+        .map(|(style, shape)| Layer {
+            low_pass_hz: shape.low_pass_hz,
+            high_pass_hz: shape.high_pass_hz,
+            modulation: (shape.modulation.depth > 0.0).then_some(shape.modulation),
+            from: shape.timing.window.map(|(from, _)| from),
+            until: shape.timing.window.map(|(_, until)| until),
+            chance: shape.timing.chance,
+            spell_seconds: shape.timing.spell_seconds,
+            ..Layer::new(*style, mix.level(*style))
+        })
+        .collect()
+}
+
+/// Sets the mix and shaping from `layers`, leaving the rest of the sound
+/// alone. Sources without a layer lose any shaping; no layers leave the
+/// mix as it is. A timed layer already playing on the same timing carries
+/// on; any other starts silent until the schedule's thread lets it in.
+pub fn play_layers(layers: &[Layer], settings: &mut AudioSettings) {
+    if let Some(mix) = layers_mix(layers) {
+        settings.set_mix(mix);
+    }
+    let playing = std::mem::replace(&mut settings.layers, AudioSettings::default().layers);
+    for layer in layers {
+        let index = SoundStyle::ALL
+            .iter()
+            .position(|style| *style == layer.source)
+            .unwrap_or_default();
+        let mut shape = layer.shape();
+        if playing[index].timing == shape.timing {
+            shape.silenced = playing[index].silenced;
+        }
+        settings.layers[index] = shape;
+    }
+}
+
+/// One draw of a layer that plays by chance: whether it plays until
+/// `until`, made for `timing`.
+#[derive(Debug, Clone, Copy)]
+struct Spell {
+    timing: LayerTiming,
+    playing: bool,
+    until: Instant,
+}
+
+/// The schedule's thread's draws for the layers that play by chance, one
+/// per SoundStyle::ALL entry like the live settings' layers.
+#[derive(Debug, Default)]
+pub struct LayerSpells {
+    spells: [Option<Spell>; SoundStyle::ALL.len()],
+}
+
+impl LayerSpells {
+    /// Silences each of `settings`' timed layers outside its window at
+    /// `time` or out of its spell at `now`, drawing a new spell for a
+    /// layer whose last one ended or whose timing changed.
+    pub fn gate(
+        &mut self,
+        settings: &mut AudioSettings,
+        time: ClockTime,
+        now: Instant,
+        rng: &mut impl RngExt,
+    ) {
+        for (shape, spell) in settings.layers.iter_mut().zip(&mut self.spells) {
+            let timing = shape.timing;
+            let in_window = timing
+                .window
+                .is_none_or(|(from, until)| time.within(from, until));
+            let in_spell = timing.chance.is_none_or(|chance| {
+                let current = spell
+                    .filter(|spell| spell.timing == timing && now < spell.until)
+                    .unwrap_or_else(|| Spell {
+                        timing,
+                        playing: rng.random::<f32>() < chance,
+                        until: now + timing.spell(),
+                    });
+                *spell = Some(current);
+                current.playing
+            });
+            shape.silenced = !(in_window && in_spell);
+        }
+    }
+}
+
+fn scene_path(dir: &Path, name: &str) -> Result<PathBuf> {
+    Ok(dir.join(format!("{}.toml", validate_name(name)?)))
+}
+
+/// Scene names in `dir`, sorted. Names follow the preset rules.
+pub fn list_scenes(dir: &Path) -> Result<Vec<String>> {
+    list_presets(dir)
+}
+
+pub fn load_scene(dir: &Path, name: &str) -> Result<Scene> {
+    let path = scene_path(dir, name)?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            bail!("no scene named '{}'", name.trim());
+        }
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to read {}", path.display()));
+        }
+    };
+    let mut scene = toml::from_str::<Scene>(&content)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+    scene
+        .check()
+        .with_context(|| format!("failed to load {}", path.display()))?;
+    let file = format!("scene '{}'", name.trim());
+    (_, scene.warnings) = parse_sound(&content, &file, &["layers", "timer"])
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+    Ok(scene)
+}
+
+/// Writes `layers` into the scene file `name`, keeping everything else a
+/// hand-written file holds. A new file starts from `sound` as a preset
+/// would save it, without the mix the layers stand in for.
+pub fn save_layers(dir: &Path, name: &str, layers: &[Layer], sound: &AudioSettings) -> Result<()> {
+    let path = scene_path(dir, name)?;
+    let mut table = match fs::read_to_string(&path) {
+        Ok(content) => toml::from_str::<toml::Table>(&content)
+            .with_context(|| format!("failed to parse {}", path.display()))?,
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            let mut table = toml::Table::try_from(sound.sanitize())?;
+            table.remove("mix");
+            table
+        }
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to read {}", path.display()));
+        }
+    };
+    // A file in a newer format keeps its stamp, so this release does not
+    // claim the keys it skipped.
+    let newer = table
+        .get("schema_version")
+        .and_then(toml::Value::as_integer)
+        .is_some_and(|version| version > i64::from(SCHEMA_VERSION));
+    if !newer {
+        table.extend(stamp());
+    }
+    table.insert("layers".to_owned(), toml::Value::try_from(layers)?);
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    fs::write(&path, toml::to_string_pretty(&table)?)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Plays `scene` as a preset would play, then sets its layers' levels and
+/// shaping and starts its timer, with `fade_out` for a timer that names no
+/// fade. A running sleep timer is kept.
+pub fn apply_scene(scene: &Scene, settings: &mut AudioSettings, fade_out: Duration, now: Instant) {
+    let volume = scene.volume.unwrap_or(settings.volume);
+    let playing = settings.layers;
+    apply_preset(scene.sound, settings);
+    settings.volume = volume;
+    // Loading the same scene again leaves its timed layers where they are.
+    settings.layers = playing;
+    play_layers(&scene.layers, settings);
+    if let Some(timer) = scene
+        .timer
+        .as_ref()
+        .filter(|_| settings.sleep_timer.is_none())
+    {
+        if let Ok((length, fade)) = timer.lengths(fade_out) {
+            settings.sleep_timer = Some(SleepTimer::new(length, fade, now).with_alarm(timer.alarm));
+        }
+    }
+    *settings = settings.sanitize();
+}
+
+/// One line for listings: the layers, or the mix when there are none, and
+/// the timer.
+pub fn summary(scene: &Scene) -> String {
+    let mut line = if scene.layers.is_empty() {
+        scene.sound.mix().describe()
+    } else {
+        scene
+            .layers
+            .iter()
+            .map(Layer::describe)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    if let Some(volume) = scene.volume {
+        line.push_str(&format!(" at {:.0}% volume", volume * 100.0));
+    }
+    if let Some((length, _)) = scene
+        .timer
+        .as_ref()
+        .and_then(|timer| timer.lengths(Duration::ZERO).ok())
+    {
+        line.push_str(&format!(", {} timer", format_remaining(length)));
+    }
+    line
+}
+
+/// A scene as the `scenes` subcommand lists it.
+pub struct SceneRow {
+    pub name: String,
+    /// The summary line, or why the scene cannot be read.
+    pub summary: std::result::Result<String, String>,
+    pub warnings: Vec<String>,
+}
+
+/// A row for each scene in `dir`, sorted by name. A scene that cannot be
+/// read keeps its row, with the reason in place of its summary.
+pub fn scene_rows(dir: &Path) -> Result<Vec<SceneRow>> {
+    Ok(list_scenes(dir)?
+        .into_iter()
+        .map(|name| match load_scene(dir, &name) {
+            Ok(scene) => SceneRow {
+                summary: Ok(summary(&scene)),
+                warnings: scene.warnings,
+                name,
+            },
+            Err(error) => SceneRow {
+                summary: Err(format!("{error:#}")),
+                warnings: Vec::new(),
+                name,
+            },
+        })
+        .collect())
+}
+
+/// The scene file the empty listing shows as an example.
+pub const EXAMPLE_SCENE: &str = "[[layers]]\nsource = \"rain\"\nlevel = 0.6\nlow_pass_hz = 6000\n\n[[layers]]\nsource = \"brown\"\nlevel = 0.4\nmodulation = { depth = 0.3, period_seconds = 40 }\n\n[[layers]]\nsource = \"wind\"\nlevel = 0.2\nfrom = \"22:00\"\nuntil = \"02:00\"\nchance = 0.25\n\n[timer]\nlength = \"45m\"";
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::prelude::SmallRng;
+
+    use super::*;
+
+    fn time(text: &str) -> ClockTime {
+        ClockTime::try_from(text.to_owned()).unwrap()
+    }
+
+    const SCENE: &str = r#"
+volume = 0.35
+listening_contour = true
+
+[[layers]]
+source = "rain"
+level = 0.6
+low_pass_hz = 6000
+
+[[layers]]
+source = "brown"
+level = 0.4
+high_pass_hz = 5
+modulation = { depth = 0.3, period_seconds = 40 }
+
+[timer]
+length = "45m"
+alarm = "visual"
+"#;
+
+    fn scratch_scenes(label: &str) -> PathBuf {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "whitenoise-scene-test-{label}-{}",
+            std::process::id()
+        ));
+        path.push("scenes");
+        path
+    }
+
+    #[test]
+    fn a_scene_sets_its_layers_shaping_and_timer() {
+        let dir = scratch_scenes("apply");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("attic rain.toml"), SCENE).unwrap();
+        assert_eq!(list_scenes(&dir).unwrap(), ["attic rain"]);
+        let scene = load_scene(&dir, "attic rain").unwrap();
+        assert_eq!(
+            summary(&scene),
+            "Rain 60% (low-pass 6000 Hz), Brown Noise 40% (high-pass 20 Hz, swell 30% every 40 s) at 35% volume, 45:00 timer"
+        );
+
+        let now = Instant::now();
+        let mut settings = AudioSettings {
+            volume: 0.8,
+            paused: true,
+            ..AudioSettings::default()
+        };
+        apply_scene(&scene, &mut settings, Duration::from_secs(60), now);
+        assert_eq!(settings.volume, 0.35);
+        assert!(settings.listening_contour);
+        assert!(settings.paused);
+        let mix = settings.mix();
+        assert_eq!(mix.level(SoundStyle::Rain), 0.6);
+        assert_eq!(mix.level(SoundStyle::Brown), 0.4);
+        assert_eq!(mix.level(SoundStyle::White), 0.0);
+        let rain = SoundStyle::ALL.iter().position(|s| *s == SoundStyle::Rain);
+        assert_eq!(settings.layers[rain.unwrap()].low_pass_hz, Some(6000.0));
+        let brown = SoundStyle::ALL.iter().position(|s| *s == SoundStyle::Brown);
+        let brown = settings.layers[brown.unwrap()];
+        assert_eq!(brown.high_pass_hz, Some(MIN_LAYER_HZ));
+        assert_eq!(brown.modulation.depth, 0.3);
+        let timer = settings.sleep_timer.unwrap();
+        assert_eq!(timer.alarm(), TimerAlarm::Visual);
+        assert_eq!(timer.remaining(now), Duration::from_secs(45 * 60));
+
+        // A running timer is kept, and a preset clears the shaping.
+        let running = SleepTimer::new(Duration::from_secs(600), Duration::ZERO, now);
+        settings.sleep_timer = Some(running);
+        apply_scene(&scene, &mut settings, Duration::from_secs(60), now);
+        assert_eq!(settings.sleep_timer, Some(running));
+        apply_preset(AudioSettings::default(), &mut settings);
+        assert_eq!(settings.layers, AudioSettings::default().layers);
+
+        fs::write(
+            dir.join("twice.toml"),
+            "[[layers]]\nsource = \"rain\"\nlevel = 1\n[[layers]]\nsource = \"rain\"\nlevel = 0.5\n",
+        )
+        .unwrap();
+        assert!(load_scene(&dir, "twice").is_err());
+        fs::write(dir.join("late.toml"), "[timer]\nlength = \"soon\"\n").unwrap();
+        assert!(load_scene(&dir, "late").is_err());
+        assert!(load_scene(&dir, "missing").is_err());
+
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn saving_layers_keeps_the_rest_of_the_file() {
+        let dir = scratch_scenes("save");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("attic rain.toml"), SCENE).unwrap();
+        let mut layers = load_scene(&dir, "attic rain").unwrap().editable_layers();
+        layers[0].level = 0.8;
+        layers.push(Layer {
+            high_pass_hz: Some(200.0),
+            ..Layer::new(SoundStyle::Pink, 0.2)
+        });
+        save_layers(&dir, "attic rain", &layers, &AudioSettings::default()).unwrap();
+        let scene = load_scene(&dir, "attic rain").unwrap();
+        assert_eq!(scene.layers, layers);
+        assert_eq!(scene.warnings, Vec::<String>::new());
+        assert_eq!(scene.volume, Some(0.35));
+        assert!(scene.sound.listening_contour);
+        assert_eq!(scene.timer.unwrap().alarm, TimerAlarm::Visual);
+
+        // A new scene starts from the sound, and its layers are what plays.
+        let layers = vec![
+            Layer {
+                modulation: Some(Modulation {
+                    depth: 0.3,
+                    period_seconds: 40.0,
+                }),
+                ..Layer::new(SoundStyle::Pink, 0.2)
+            },
+            Layer {
+                low_pass_hz: Some(6000.0),
+                ..Layer::new(SoundStyle::Rain, 0.6)
+            },
+        ];
+        let mut sound = AudioSettings {
+            volume: 0.5,
+            ..AudioSettings::default()
+        };
+        play_layers(&layers, &mut sound);
+        assert_eq!(playing_layers(&sound), layers);
+        save_layers(&dir, "new", &playing_layers(&sound), &sound).unwrap();
+        let scene = load_scene(&dir, "new").unwrap();
+        assert_eq!(scene.volume, Some(0.5));
+        assert_eq!(scene.layers, layers);
+        let mut played = AudioSettings::default();
+        apply_scene(&scene, &mut played, Duration::ZERO, Instant::now());
+        assert_eq!(played.mix(), sound.mix());
+        assert_eq!(played.layers, sound.layers);
+
+        // A scene with only a mix is edited as one layer per source.
+        let solo = Scene::from_preset(AudioSettings::default());
+        assert_eq!(solo.editable_layers(), [Layer::new(SoundStyle::White, 1.0)]);
+
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn scenes_name_the_keys_they_skip_and_keep_a_newer_stamp() {
+        let dir = scratch_scenes("versions");
+        fs::create_dir_all(&dir).unwrap();
+        let newer = format!("schema_version = 3\napp_version = \"9.0.0\"\nreverb = 0.5\n{SCENE}");
+        fs::write(dir.join("shared.toml"), &newer).unwrap();
+        let scene = load_scene(&dir, "shared").unwrap();
+        assert_eq!(scene.volume, Some(0.35));
+        assert_eq!(scene.warnings.len(), 2);
+        assert!(scene.warnings[1].ends_with("which are skipped: reverb"));
+
+        // Editing its layers does not stamp it as this release's format.
+        let layers = scene.editable_layers();
+        save_layers(&dir, "shared", &layers, &AudioSettings::default()).unwrap();
+        let saved = fs::read_to_string(dir.join("shared.toml")).unwrap();
+        assert!(saved.contains("schema_version = 3"), "{saved}");
+        save_layers(&dir, "new", &layers, &AudioSettings::default()).unwrap();
+        let saved = fs::read_to_string(dir.join("new.toml")).unwrap();
+        assert!(saved.contains(&format!("schema_version = {SCHEMA_VERSION}")));
+
+        // The listing keeps a scene it cannot read, with the reason.
+        fs::write(dir.join("broken.toml"), "layers = 3").unwrap();
+        let rows = scene_rows(&dir).unwrap();
+        let names: Vec<&str> = rows.iter().map(|row| row.name.as_str()).collect();
+        assert_eq!(names, ["broken", "new", "shared"]);
+        assert!(rows[0].summary.is_err());
+        assert!(rows[1].warnings.is_empty());
+        assert_eq!(rows[2].warnings, scene.warnings);
+        assert!(rows[2].summary.as_ref().unwrap().ends_with("45:00 timer"));
+
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn timed_layers_play_in_their_window_and_their_spells() {
+        let layers = [
+            Layer {
+                from: Some(time("06:00")),
+                until: Some(time("08:00")),
+                ..Layer::new(SoundStyle::Pink, 0.3)
+            },
+            Layer {
+                chance: Some(0.1),
+                spell_seconds: Some(60.0),
+                ..Layer::new(SoundStyle::Brown, 0.3)
+            },
+            Layer::new(SoundStyle::Rain, 0.5),
+        ];
+        assert_eq!(layers[0].describe(), "Pink Noise 30% (06:00 to 08:00)");
+        assert_eq!(
+            layers[1].describe(),
+            "Brown Noise 30% (10% of the time in 60 s spells)"
+        );
+        let index = |style| SoundStyle::ALL.iter().position(|s| *s == style).unwrap();
+        let (pink, brown, rain) = (
+            index(SoundStyle::Pink),
+            index(SoundStyle::Brown),
+            index(SoundStyle::Rain),
+        );
+        let mut settings = AudioSettings::default();
+        play_layers(&layers, &mut settings);
+        // Timed layers start silent until the schedule's thread looks.
+        assert!(settings.layers[pink].silenced && settings.layers[brown].silenced);
+        assert!(!settings.layers[rain].silenced);
+
+        let mut spells = LayerSpells::default();
+        let mut rng = SmallRng::seed_from_u64(5);
+        let start = Instant::now();
+        spells.gate(&mut settings, time("07:00"), start, &mut rng);
+        assert!(!settings.layers[pink].silenced && !settings.layers[rain].silenced);
+        spells.gate(&mut settings, time("08:00"), start, &mut rng);
+        assert!(settings.layers[pink].silenced);
+
+        // Over a long night the chance layer plays about a tenth of it, and
+        // each draw holds for its spell.
+        let mut playing = 0;
+        for minute in 0..2_000_u64 {
+            let now = start + Duration::from_secs(minute * 60);
+            spells.gate(&mut settings, time("12:00"), now, &mut rng);
+            let drawn = settings.layers[brown].silenced;
+            spells.gate(
+                &mut settings,
+                time("12:00"),
+                now + Duration::from_secs(59),
+                &mut rng,
+            );
+            assert_eq!(settings.layers[brown].silenced, drawn);
+            playing += usize::from(!drawn);
+        }
+        assert!((140..260).contains(&playing), "{playing}");
+
+        // Playing the same layers again keeps their gates; new timing
+        // starts silent.
+        spells.gate(&mut settings, time("07:00"), start, &mut rng);
+        play_layers(&layers, &mut settings);
+        assert!(!settings.layers[pink].silenced);
+        let mut later = layers;
+        later[0].from = Some(time("06:30"));
+        play_layers(&later, &mut settings);
+        assert!(settings.layers[pink].silenced);
+        assert_eq!(playing_layers(&settings)[0].from, Some(time("06:30")));
+
+        let half = Scene {
+            layers: vec![Layer {
+                from: Some(time("06:00")),
+                ..Layer::new(SoundStyle::Pink, 0.3)
+            }],
+            ..Scene::default()
+        };
+        assert!(half.check().is_err());
+    }
+
+    #[test]
+    fn a_swell_dips_by_its_depth_halfway_through() {
+        let swell = Modulation {
+            depth: 0.4,
+            period_seconds: 20.0,
+        };
+        assert!((swell.gain(0.0) - 1.0).abs() < 1e-6);
+        assert!((swell.gain(0.5) - 0.6).abs() < 1e-6);
+        assert!((swell.gain(1.0) - 1.0).abs() < 1e-6);
+        assert_eq!(Modulation::default().gain(0.25), 1.0);
+    }
+}
@@ -0,0 +1,666 @@
+//! Schedule: `[[schedule]]` entries that load a preset or a scene, or stop
+//! the sound, at a time of day, such as fading in a sleep preset at 22:30
+//! and out again at 07:00, or at sunrise or sunset with an offset. A
+//! background thread watches the clock during playback; a stop fades out
+//! and pauses, and the next preset or scene entry resumes with a fade in.
+//! The same thread lets a scene's timed layers in and out.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, anyhow, bail};
+use rand::prelude::SmallRng;
+use serde::{Deserialize, Serialize};
+
+use crate::clock;
+use crate::night::ClockTime;
+use crate::preset::{load_preset, validate_name};
+use crate::scene::{LayerSpells, Scene, apply_scene, load_scene, scenes_beside};
+use crate::settings::{AudioSettings, SettingsGuard, SharedSettings};
+use crate::sun::{Location, SunEvent, SunTimes};
+use crate::timer::DEFAULT_FADE_OUT;
+
+pub const DEFAULT_FADE_SECONDS: f32 = 30.0;
+const MAX_FADE_SECONDS: f32 = 3600.0;
+// How far an entry may sit from sunrise or sunset.
+const MAX_SUN_OFFSET_MINUTES: i32 = 12 * 60;
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// When an entry comes due: a clock time, written `HH:MM`, or sunrise or
+/// sunset moved by an offset, written `sunrise`, `sunset-30m`, or
+/// `sunrise+1h15m`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ScheduleTime {
+    Clock(ClockTime),
+    Sun {
+        event: SunEvent,
+        offset_minutes: i16,
+    },
+}
+
+impl ScheduleTime {
+    /// The clock time this falls at on a day with `sun`'s sunrise and
+    /// sunset, or None on a day without the event it follows.
+    pub fn on(self, sun: &SunTimes) -> Option<ClockTime> {
+        match self {
+            Self::Clock(time) => Some(time),
+            Self::Sun {
+                event,
+                offset_minutes,
+            } => sun
+                .get(event)
+                .map(|time| time.plus_minutes(offset_minutes.into())),
+        }
+    }
+
+    fn follows_the_sun(self) -> bool {
+        matches!(self, Self::Sun { .. })
+    }
+}
+
+impl From<ClockTime> for ScheduleTime {
+    fn from(time: ClockTime) -> Self {
+        Self::Clock(time)
+    }
+}
+
+impl TryFrom<String> for ScheduleTime {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        let trimmed = text.trim();
+        let (event, rest) = if let Some(rest) = trimmed.strip_prefix("sunrise") {
+            (SunEvent::Sunrise, rest)
+        } else if let Some(rest) = trimmed.strip_prefix("sunset") {
+            (SunEvent::Sunset, rest)
+        } else {
+            return ClockTime::try_from(text.clone())
+                .map(Self::Clock)
+                .map_err(|_| {
+                    format!(
+                        "{text:?} is not a time; use HH:MM, such as 22:30, or sunrise or sunset with an offset, such as sunset-30m"
+                    )
+                });
+        };
+        let error = || {
+            format!(
+                "{text:?} has an offset that is not +/- hours and minutes up to 12h, such as sunset-30m or sunrise+1h15m"
+            )
+        };
+        let offset_minutes = match rest.trim() {
+            "" => 0,
+            offset => {
+                let (sign, amount) = if let Some(amount) = offset.strip_prefix('+') {
+                    (1, amount)
+                } else if let Some(amount) = offset.strip_prefix('-') {
+                    (-1, amount)
+                } else {
+                    return Err(error());
+                };
+                let minutes = parse_offset(amount.trim()).ok_or_else(error)?;
+                if minutes > MAX_SUN_OFFSET_MINUTES {
+                    return Err(error());
+                }
+                sign * minutes
+            }
+        };
+        Ok(Self::Sun {
+            event,
+            offset_minutes: offset_minutes as i16,
+        })
+    }
+}
+
+/// `1h15m`, `1h`, or `30m` in minutes.
+fn parse_offset(text: &str) -> Option<i32> {
+    let (hours, minutes) = match text.split_once('h') {
+        Some((hours, minutes)) => (hours.parse::<i32>().ok()?, minutes),
+        None => (0, text),
+    };
+    let minutes = match minutes {
+        "" if text.ends_with('h') => 0,
+        minutes => minutes.strip_suffix('m')?.parse::<i32>().ok()?,
+    };
+    (hours >= 0 && minutes >= 0).then_some(hours * 60 + minutes)
+}
+
+impl From<ScheduleTime> for String {
+    fn from(time: ScheduleTime) -> Self {
+        time.to_string()
+    }
+}
+
+impl fmt::Display for ScheduleTime {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (event, offset) = match *self {
+            Self::Clock(time) => return write!(formatter, "{time}"),
+            Self::Sun {
+                event,
+                offset_minutes,
+            } => (event, offset_minutes),
+        };
+        write!(formatter, "{event}")?;
+        if offset != 0 {
+            let sign = if offset < 0 { '-' } else { '+' };
+            let (hours, minutes) = (offset.unsigned_abs() / 60, offset.unsigned_abs() % 60);
+            write!(formatter, "{sign}")?;
+            if hours > 0 {
+                write!(formatter, "{hours}h")?;
+            }
+            if minutes > 0 {
+                write!(formatter, "{minutes}m")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One `[[schedule]]` table: at `at`, load `preset` or `scene` or, with
+/// `stop = true`, fade out and pause. Exactly one of the three is set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleEntry {
+    pub at: ScheduleTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scene: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stop: bool,
+    #[serde(default = "default_fade_seconds")]
+    pub fade_seconds: f32,
+}
+
+fn default_fade_seconds() -> f32 {
+    DEFAULT_FADE_SECONDS
+}
+
+impl ScheduleEntry {
+    fn fade(&self) -> Duration {
+        let seconds = if self.fade_seconds.is_finite() {
+            self.fade_seconds.clamp(0.0, MAX_FADE_SECONDS)
+        } else {
+            DEFAULT_FADE_SECONDS
+        };
+        Duration::from_secs_f32(seconds)
+    }
+
+    /// `preset sleep, 30 s fade`, `scene attic, 30 s fade`, or
+    /// `stop, 30 s fade`.
+    pub fn describe(&self) -> String {
+        let action = match (&self.preset, &self.scene) {
+            (Some(name), _) => format!("preset {name}"),
+            (None, Some(name)) => format!("scene {name}"),
+            (None, None) => "stop".to_owned(),
+        };
+        format!("{action}, {:.0} s fade", self.fade().as_secs_f32())
+    }
+}
+
+/// Rejects entries that name no action or two, or an impossible preset or
+/// scene name, so a typo is reported at start rather than at 22:30, and
+/// entries at sunrise or sunset without a valid `location`.
+pub fn check(entries: &[ScheduleEntry], location: Option<Location>) -> Result<()> {
+    if let Some(location) = location {
+        location
+            .check()
+            .map_err(|error| anyhow!("[location]: {error}"))?;
+    }
+    for entry in entries {
+        if entry.at.follows_the_sun() && location.is_none() {
+            bail!(
+                "the {} schedule entry needs a [location] table with the latitude and longitude to find the sun",
+                entry.at
+            );
+        }
+        let actions = [entry.preset.is_some(), entry.scene.is_some(), entry.stop];
+        match actions.iter().filter(|set| **set).count() {
+            0 => bail!(
+                "the {} schedule entry needs a preset, a scene, or stop = true",
+                entry.at
+            ),
+            1 => {}
+            _ => bail!(
+                "the {} schedule entry names more than one of preset, scene, and stop",
+                entry.at
+            ),
+        }
+        for name in entry.preset.iter().chain(&entry.scene) {
+            validate_name(name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Each entry with the clock time it falls at on a day with `sun`, leaving
+/// out those following a sunrise or sunset the day does not have.
+fn times<'a>(
+    entries: &'a [ScheduleEntry],
+    sun: &SunTimes,
+) -> impl DoubleEndedIterator<Item = (ClockTime, &'a ScheduleEntry)> {
+    let sun = *sun;
+    entries
+        .iter()
+        .filter_map(move |entry| Some((entry.at.on(&sun)?, entry)))
+}
+
+/// The entry that last took effect at `now`: the latest at or before it,
+/// or yesterday's last one before the day's first. Of entries sharing a
+/// time, the later one in the file wins. Sunrise and sunset are `sun`'s,
+/// today's, for yesterday's entries too; they move by a few minutes a day
+/// at most.
+pub fn in_force<'a>(
+    entries: &'a [ScheduleEntry],
+    sun: &SunTimes,
+    now: ClockTime,
+) -> Option<&'a ScheduleEntry> {
+    times(entries, sun)
+        .rev()
+        .min_by_key(|(at, _)| now.minutes_after(*at))
+        .map(|(_, entry)| entry)
+}
+
+/// The entry to act on after the clock moved from `last` to `now`: of those
+/// whose time passed, the latest, so a machine waking from sleep catches up
+/// on the one that matters rather than replaying the night.
+pub fn due<'a>(
+    entries: &'a [ScheduleEntry],
+    sun: &SunTimes,
+    last: ClockTime,
+    now: ClockTime,
+) -> Option<&'a ScheduleEntry> {
+    let elapsed = now.minutes_after(last);
+    times(entries, sun)
+        .filter(|(at, _)| {
+            let since = at.minutes_after(last);
+            since > 0 && since <= elapsed
+        })
+        .max_by_key(|(at, _)| at.minutes_after(last))
+        .map(|(_, entry)| entry)
+}
+
+/// A scheduled fade, applied on top of the volume like the sleep timer's.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScheduleFade {
+    start: Instant,
+    length: Duration,
+    rising: bool,
+}
+
+impl ScheduleFade {
+    /// A fade up from silence over `length`, as --fade-in starts playback.
+    pub fn rising(start: Instant, length: Duration) -> Self {
+        Self {
+            start,
+            length,
+            rising: true,
+        }
+    }
+
+    pub fn gain(&self, now: Instant) -> f32 {
+        let progress = if self.length.is_zero() {
+            1.0
+        } else {
+            (now.saturating_duration_since(self.start).as_secs_f64() / self.length.as_secs_f64())
+                .min(1.0) as f32
+        };
+        // Squared like the sleep timer's, so the change in dB is even.
+        let level = if self.rising {
+            progress
+        } else {
+            1.0 - progress
+        };
+        level * level
+    }
+
+    fn finished(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.start) >= self.length
+    }
+}
+
+/// Carries out `entry` on the live settings. `sound` is the entry's preset
+/// or scene, already loaded; when it could not be, the sound stays as it is.
+pub fn apply(
+    entry: &ScheduleEntry,
+    sound: Option<Scene>,
+    settings: &mut AudioSettings,
+    now: Instant,
+) {
+    let fade = entry.fade();
+    if entry.stop {
+        if !settings.paused {
+            settings.schedule_fade = Some(ScheduleFade {
+                start: now,
+                length: fade,
+                rising: false,
+            });
+        }
+        return;
+    }
+    let Some(scene) = sound else {
+        return;
+    };
+    apply_scene(&scene, settings, DEFAULT_FADE_OUT, now);
+    let stopping = settings.schedule_fade.is_some_and(|fade| !fade.rising);
+    if settings.paused || stopping {
+        settings.paused = false;
+        settings.schedule_fade = Some(ScheduleFade::rising(now, fade));
+    }
+}
+
+/// Pauses once a scheduled fade-out reaches silence, and drops a finished
+/// fade in.
+pub fn settle(settings: &mut AudioSettings, now: Instant) {
+    let Some(fade) = settings.schedule_fade.filter(|fade| fade.finished(now)) else {
+        return;
+    };
+    if !fade.rising {
+        settings.paused = true;
+    }
+    settings.schedule_fade = None;
+}
+
+/// Follows `entries`, and the timing of whichever scene's layers play,
+/// until playback stops. Presets and scenes are read when their entry
+/// comes due, so one saved during the session is picked up.
+pub fn watch(
+    entries: Vec<ScheduleEntry>,
+    location: Option<Location>,
+    settings: Arc<SharedSettings>,
+    running: Arc<AtomicBool>,
+    presets: PathBuf,
+) {
+    std::thread::spawn(move || {
+        let mut last = ClockTime::now();
+        let mut spells = LayerSpells::default();
+        let mut rng = rand::make_rng::<SmallRng>();
+        while running.load(Ordering::Relaxed) {
+            std::thread::sleep(CHECK_INTERVAL);
+            let now = ClockTime::now();
+            settle(&mut lock(&settings), clock::now());
+            let sun = SunTimes::today(location);
+            if let Some(entry) = due(&entries, &sun, last, now) {
+                // An unreadable one leaves the sound alone; the thread has no
+                // screen to report it on, so `whitenoise schedule` is where
+                // it shows.
+                let sound = load_entry(entry, &presets).ok().flatten();
+                apply(entry, sound, &mut lock(&settings), clock::now());
+            }
+            spells.gate(&mut lock(&settings), now, clock::now(), &mut rng);
+            last = now;
+        }
+    });
+}
+
+fn lock(settings: &SharedSettings) -> SettingsGuard<'_> {
+    settings
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The preset or scene `entry` loads, as a scene, or None for a stop.
+/// Scenes are read from the folder beside `presets`.
+pub fn load_entry(entry: &ScheduleEntry, presets: &Path) -> Result<Option<Scene>> {
+    match (&entry.preset, &entry.scene) {
+        (Some(name), _) => Ok(Some(Scene::from_preset(load_preset(presets, name)?))),
+        (None, Some(name)) => Ok(Some(load_scene(&scenes_beside(presets), name)?)),
+        (None, None) => Ok(None),
+    }
+}
+
+/// A schedule entry as the `schedule` subcommand lists it.
+pub struct ScheduleRow {
+    pub at: ScheduleTime,
+    /// Today's time, or None for a sun event that does not come today.
+    pub time: Option<ClockTime>,
+    pub summary: String,
+    pub in_force: bool,
+    /// Why its preset or scene cannot be loaded.
+    pub problem: Option<String>,
+}
+
+/// The entries in the order they fall today, the one in force marked, and
+/// any whose preset or scene cannot be loaded.
+pub fn schedule_rows(
+    entries: &[ScheduleEntry],
+    sun: &SunTimes,
+    presets: &Path,
+    now: ClockTime,
+) -> Vec<ScheduleRow> {
+    let mut sorted: Vec<&ScheduleEntry> = entries.iter().collect();
+    // Those that do not fall today go last.
+    sorted.sort_by_key(|entry| {
+        let at = entry.at.on(sun);
+        (at.is_none(), at)
+    });
+    let current = in_force(entries, sun, now);
+    sorted
+        .into_iter()
+        .map(|entry| ScheduleRow {
+            at: entry.at,
+            time: entry.at.on(sun),
+            summary: entry.describe(),
+            in_force: current == Some(entry),
+            problem: load_entry(entry, presets)
+                .err()
+                .map(|error| format!("{error:#}")),
+        })
+        .collect()
+}
+
+/// The `[[schedule]]` tables the empty listing shows as an example.
+pub const EXAMPLE_SCHEDULE: &str =
+    "[[schedule]]\nat = \"22:30\"\npreset = \"sleep\"\n\n[[schedule]]\nat = \"07:00\"\nstop = true";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NO_SUN: SunTimes = SunTimes {
+        sunrise: None,
+        sunset: None,
+    };
+
+    fn time(text: &str) -> ClockTime {
+        ClockTime::try_from(text.to_owned()).unwrap()
+    }
+
+    fn entry(at: &str, preset: Option<&str>) -> ScheduleEntry {
+        ScheduleEntry {
+            at: time(at).into(),
+            preset: preset.map(str::to_owned),
+            scene: None,
+            stop: preset.is_none(),
+            fade_seconds: 10.0,
+        }
+    }
+
+    #[test]
+    fn entries_take_over_in_turn_around_the_clock() {
+        let entries = [
+            entry("22:30", Some("sleep")),
+            entry("07:00", None),
+            entry("09:00", Some("focus")),
+        ];
+        assert!(check(&entries, None).is_ok());
+        assert_eq!(
+            in_force(&entries, &NO_SUN, time("23:00")),
+            Some(&entries[0])
+        );
+        assert_eq!(
+            in_force(&entries, &NO_SUN, time("03:00")),
+            Some(&entries[0])
+        );
+        assert_eq!(
+            in_force(&entries, &NO_SUN, time("07:00")),
+            Some(&entries[1])
+        );
+        assert_eq!(
+            in_force(&entries, &NO_SUN, time("12:00")),
+            Some(&entries[2])
+        );
+
+        assert_eq!(
+            due(&entries, &NO_SUN, time("22:29"), time("22:30")),
+            Some(&entries[0])
+        );
+        assert_eq!(due(&entries, &NO_SUN, time("22:30"), time("22:30")), None);
+        assert_eq!(due(&entries, &NO_SUN, time("22:30"), time("22:31")), None);
+        // Waking at 09:30 from a suspend at 22:00 acts on 09:00 alone.
+        assert_eq!(
+            due(&entries, &NO_SUN, time("22:00"), time("09:30")),
+            Some(&entries[2])
+        );
+
+        let mut broken = entry("08:00", Some("focus"));
+        broken.stop = true;
+        assert!(check(&[broken.clone()], None).is_err());
+        broken.preset = None;
+        broken.stop = false;
+        assert!(check(&[broken], None).is_err());
+        assert!(check(&[entry("08:00", Some("../focus"))], None).is_err());
+        let mut scene = entry("08:00", None);
+        scene.stop = false;
+        scene.scene = Some("attic".to_owned());
+        assert!(check(&[scene.clone()], None).is_ok());
+        assert_eq!(scene.describe(), "scene attic, 10 s fade");
+        scene.preset = Some("focus".to_owned());
+        assert!(check(&[scene], None).is_err());
+    }
+
+    #[test]
+    fn entries_may_follow_sunrise_and_sunset() {
+        let at = |text: &str| ScheduleTime::try_from(text.to_owned());
+        assert_eq!(
+            at("sunset-30m"),
+            Ok(ScheduleTime::Sun {
+                event: SunEvent::Sunset,
+                offset_minutes: -30
+            })
+        );
+        assert_eq!(at(" 22:30 "), Ok(time("22:30").into()));
+        for text in ["sunrise", "sunset-30m", "sunrise+1h15m", "sunset+2h"] {
+            assert_eq!(at(text).unwrap().to_string(), text);
+        }
+        assert_eq!(at("sunrise +45m").unwrap().to_string(), "sunrise+45m");
+        for bad in [
+            "sunset-",
+            "sunset30m",
+            "sunrise+13h",
+            "sunrise+1h-5m",
+            "dusk",
+            "sunset-x",
+        ] {
+            assert!(at(bad).is_err(), "{bad}");
+        }
+
+        let sun = SunTimes {
+            sunrise: Some(time("06:10")),
+            sunset: Some(time("19:40")),
+        };
+        let mut fade_out = entry("07:00", None);
+        fade_out.at = at("sunrise+30m").unwrap();
+        let mut evening = entry("22:00", Some("sleep"));
+        evening.at = at("sunset-1h").unwrap();
+        let entries = [evening, fade_out];
+        assert!(check(&entries, None).is_err());
+        let location = Location {
+            latitude: 45.0,
+            longitude: 7.0,
+        };
+        assert!(check(&entries, Some(location)).is_ok());
+        assert_eq!(in_force(&entries, &sun, time("06:39")), Some(&entries[0]));
+        assert_eq!(in_force(&entries, &sun, time("06:40")), Some(&entries[1]));
+        assert_eq!(
+            due(&entries, &sun, time("18:39"), time("18:40")),
+            Some(&entries[0])
+        );
+        // A day the sun never sets has no evening entry.
+        let midsummer = SunTimes {
+            sunset: None,
+            ..sun
+        };
+        assert_eq!(
+            in_force(&entries, &midsummer, time("23:00")),
+            Some(&entries[1])
+        );
+        assert_eq!(in_force(&entries, &NO_SUN, time("23:00")), None);
+
+        // The listing puts the entry that does not come today last and
+        // flags the preset it cannot find.
+        let presets = std::env::temp_dir().join("whitenoise-schedule-test-no-presets");
+        let rows = schedule_rows(&entries, &midsummer, &presets, time("23:00"));
+        assert_eq!(rows[0].at, entries[1].at);
+        assert_eq!(rows[0].time, Some(time("06:40")));
+        assert!(rows[0].in_force && rows[0].problem.is_none());
+        assert_eq!(rows[1].summary, "preset sleep, 10 s fade");
+        assert_eq!(rows[1].time, None);
+        assert!(!rows[1].in_force && rows[1].problem.is_some());
+    }
+
+    #[test]
+    fn a_stop_fades_out_and_pauses_and_a_preset_fades_back_in() {
+        let start = Instant::now();
+        let mut settings = AudioSettings {
+            volume: 0.4,
+            ..AudioSettings::default()
+        };
+        apply(&entry("07:00", None), None, &mut settings, start);
+        let fade = settings.schedule_fade.unwrap();
+        assert_eq!(fade.gain(start), 1.0);
+        assert_eq!(fade.gain(start + Duration::from_secs(5)), 0.25);
+        settle(&mut settings, start + Duration::from_secs(9));
+        assert!(!settings.paused);
+        settle(&mut settings, start + Duration::from_secs(10));
+        assert!(settings.paused && settings.schedule_fade.is_none());
+
+        let preset = AudioSettings {
+            volume: 0.2,
+            ..AudioSettings::default()
+        };
+        let later = start + Duration::from_secs(60);
+        // A preset that failed to load changes nothing.
+        apply(&entry("22:30", Some("sleep")), None, &mut settings, later);
+        assert!(settings.paused);
+        apply(
+            &entry("22:30", Some("sleep")),
+            Some(Scene::from_preset(preset)),
+            &mut settings,
+            later,
+        );
+        assert!(!settings.paused);
+        assert_eq!(settings.volume, 0.2);
+        let fade = settings.schedule_fade.unwrap();
+        assert_eq!(fade.gain(later), 0.0);
+        assert_eq!(fade.gain(later + Duration::from_secs(20)), 1.0);
+        settle(&mut settings, later + Duration::from_secs(10));
+        assert!(!settings.paused && settings.schedule_fade.is_none());
+
+        // While playing, a preset entry switches without a fade.
+        apply(
+            &entry("17:00", Some("quiet")),
+            Some(Scene::from_preset(preset)),
+            &mut settings,
+            later,
+        );
+        assert!(settings.schedule_fade.is_none());
+    }
+
+    #[test]
+    fn entries_read_from_toml() {
+        #[derive(Deserialize)]
+        struct File {
+            schedule: Vec<ScheduleEntry>,
+        }
+        let file: File = toml::from_str(
+            "[[schedule]]\nat = \"22:30\"\npreset = \"sleep\"\n\n[[schedule]]\nat = \"7:00\"\nstop = true\nfade_seconds = 90\n",
+        )
+        .unwrap();
+        assert_eq!(file.schedule[0].describe(), "preset sleep, 30 s fade");
+        assert_eq!(file.schedule[1].describe(), "stop, 90 s fade");
+        assert_eq!(file.schedule[1].at, time("07:00").into());
+    }
+}
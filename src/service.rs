@@ -0,0 +1,437 @@
+//! `whitenoise daemon`: headless playback for a service manager. It logs to
+//! the journal or syslog rather than a terminal, keeps a pidfile beside the
+//! control socket, stops cleanly on SIGTERM, and reads settings.toml again
+//! on SIGHUP. `--install-service` writes the user unit or agent that starts
+//! it: a systemd unit on Linux and a launchd plist on macOS.
+//!
+//! The daemon stays in the foreground, as both service managers expect;
+//! they detach it from the login session and restart it if it fails.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{Context, Result, bail};
+
+/// The launchd label, and the plist's name under ~/Library/LaunchAgents.
+const LAUNCHD_LABEL: &str = "io.github.clearcmos.whitenoise";
+const SYSTEMD_UNIT: &str = "whitenoise";
+// Where syslog listens on Linux and on macOS.
+const SYSLOG_SOCKETS: [&str; 2] = ["/dev/log", "/var/run/syslog"];
+// The syslog facility for user programs.
+const SYSLOG_USER: u8 = 1;
+
+/// How much a message matters, as syslog and the journal rank it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Warning = 4,
+    Notice = 5,
+    Info = 6,
+}
+
+/// Where messages go: the terminal for a player someone started by hand,
+/// the journal or syslog for the daemon.
+#[derive(Debug)]
+pub enum Log {
+    Terminal,
+    /// Every line on stderr, while stdout carries the sound.
+    Stderr,
+    /// stderr, which systemd hands to the journal; each line carries its
+    /// level as a `<N>` prefix.
+    Journal,
+    #[cfg(unix)]
+    Syslog(std::os::unix::net::UnixDatagram),
+}
+
+impl Log {
+    /// The journal when systemd connected stderr to it, syslog where it
+    /// listens, and stderr otherwise, as under launchd, which writes it to
+    /// the plist's log file.
+    pub fn for_service() -> Self {
+        if stderr_is_journal() {
+            return Self::Journal;
+        }
+        #[cfg(unix)]
+        for path in SYSLOG_SOCKETS {
+            let connected = std::os::unix::net::UnixDatagram::unbound()
+                .ok()
+                .filter(|socket| socket.connect(path).is_ok());
+            if let Some(socket) = connected {
+                return Self::Syslog(socket);
+            }
+        }
+        Self::Terminal
+    }
+
+    /// Informational lines go to stdout on a terminal, with notes and
+    /// warnings on stderr as everywhere else.
+    pub fn write(&self, level: Level, message: &str) {
+        match self {
+            Self::Terminal => match level {
+                Level::Info => println!("{message}"),
+                Level::Notice => eprintln!("note: {message}"),
+                Level::Warning => eprintln!("warning: {message}"),
+            },
+            Self::Stderr => match level {
+                Level::Info => eprintln!("{message}"),
+                Level::Notice => eprintln!("note: {message}"),
+                Level::Warning => eprintln!("warning: {message}"),
+            },
+            Self::Journal => eprintln!("{}", journal_line(level, message)),
+            #[cfg(unix)]
+            Self::Syslog(socket) => {
+                let line = syslog_line(level, message, std::process::id());
+                // A syslog that stopped listening should not stop the sound.
+                if socket.send(line.as_bytes()).is_err() {
+                    eprintln!("{message}");
+                }
+            }
+        }
+    }
+
+    pub fn info(&self, message: &str) {
+        self.write(Level::Info, message);
+    }
+
+    pub fn note(&self, message: &str) {
+        self.write(Level::Notice, message);
+    }
+
+    pub fn warning(&self, message: &str) {
+        self.write(Level::Warning, message);
+    }
+}
+
+/// systemd names the journal stream it gave a service in `JOURNAL_STREAM`,
+/// as `DEVICE:INODE`; stderr is the journal only if it still is that stream.
+#[cfg(unix)]
+fn stderr_is_journal() -> bool {
+    use std::os::fd::AsFd;
+    use std::os::unix::fs::MetadataExt;
+
+    let Some(stream) = std::env::var_os("JOURNAL_STREAM") else {
+        return false;
+    };
+    let Ok(stderr) = io::stderr().as_fd().try_clone_to_owned() else {
+        return false;
+    };
+    let Ok(metadata) = fs::File::from(stderr).metadata() else {
+        return false;
+    };
+    stream.to_string_lossy() == format!("{}:{}", metadata.dev(), metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn stderr_is_journal() -> bool {
+    false
+}
+
+/// journald reads a leading `<N>` on each stderr line as its level.
+fn journal_line(level: Level, message: &str) -> String {
+    format!("<{}>{message}", level as u8)
+}
+
+/// An RFC 3164 message without a timestamp, which the local syslog adds.
+fn syslog_line(level: Level, message: &str, pid: u32) -> String {
+    format!(
+        "<{}>whitenoise[{pid}]: {message}",
+        SYSLOG_USER * 8 + level as u8
+    )
+}
+
+/// `whitenoise.pid` beside the control socket, so `--socket` moves both.
+pub fn pidfile_beside(socket: &Path) -> PathBuf {
+    socket.with_extension("pid")
+}
+
+/// Removes the pidfile when the daemon stops.
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Written after the control socket is bound, which already refuses a
+    /// second daemon, so any file left here is stale.
+    pub fn write(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        fs::write(path, format!("{}\n", std::process::id()))
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        Ok(Self {
+            path: path.to_owned(),
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// SIGINT and SIGTERM clear `running`, as Ctrl+C does elsewhere, so the
+/// daemon fades nothing but still saves and removes its files. SIGHUP sets
+/// `reload`.
+#[cfg(unix)]
+pub fn watch_signals(running: Arc<AtomicBool>, reload: Arc<AtomicBool>) -> Result<()> {
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+    use signal_hook::iterator::Signals;
+
+    let mut signals =
+        Signals::new([SIGINT, SIGTERM, SIGHUP]).context("failed to take the daemon's signals")?;
+    std::thread::spawn(move || {
+        for signal in signals.forever() {
+            if signal == SIGHUP {
+                reload.store(true, Ordering::Relaxed);
+            } else {
+                running.store(false, Ordering::Relaxed);
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn watch_signals(_running: Arc<AtomicBool>, _reload: Arc<AtomicBool>) -> Result<()> {
+    bail!("the daemon needs Unix signals, which this platform lacks")
+}
+
+/// The instance `--per-session` plays as: `seat-NAME` for a session on a
+/// seat, so each seat of a shared machine keeps its own player, or
+/// `session-ID` for one without, such as a login over SSH. The values are
+/// `$XDG_SEAT` and `$XDG_SESSION_ID`, which logind sets.
+pub fn session_instance(seat: Option<&str>, session: Option<&str>) -> Result<String> {
+    let name = match (seat, session) {
+        (Some(seat), _) if !seat.is_empty() => format!("seat-{seat}"),
+        (_, Some(session)) if !session.is_empty() => format!("session-{session}"),
+        _ => {
+            bail!("--per-session needs a login session; neither XDG_SEAT nor XDG_SESSION_ID is set")
+        }
+    };
+    crate::mqtt::parse_name(&name)
+        .map_err(|_| anyhow::anyhow!("--per-session cannot name an instance after {name}"))
+}
+
+/// The unit's or agent's name, with a named instance's name added so
+/// several can be installed side by side.
+fn service_name(base: &str, separator: char, instance: Option<&str>) -> String {
+    match instance {
+        Some(name) => format!("{base}{separator}{name}"),
+        None => base.to_owned(),
+    }
+}
+
+/// Writes the user unit or agent that runs `program` with `arguments`, and
+/// says how to start it. An existing file with other contents is left
+/// alone, since it may hold the user's own changes.
+pub fn install(program: &Path, arguments: &[String], instance: Option<&str>) -> Result<()> {
+    let (path, content, next) = if cfg!(target_os = "macos") {
+        let home = dirs::home_dir().context("no home directory to install the agent in")?;
+        let label = service_name(LAUNCHD_LABEL, '.', instance);
+        let log = home.join(format!(
+            "Library/Logs/{}.log",
+            service_name("whitenoise", '-', instance)
+        ));
+        let path = home
+            .join("Library/LaunchAgents")
+            .join(format!("{label}.plist"));
+        let next = format!("launchctl load -w {}", path.display());
+        (path, launchd_plist(&label, program, arguments, &log), next)
+    } else if cfg!(target_os = "linux") {
+        let config = dirs::config_dir().context("no config directory to install the unit in")?;
+        let unit = format!("{}.service", service_name(SYSTEMD_UNIT, '-', instance));
+        let path = config.join("systemd/user").join(&unit);
+        let next =
+            format!("systemctl --user daemon-reload && systemctl --user enable --now {unit}");
+        (path, systemd_unit(program, arguments), next)
+    } else {
+        bail!(
+            "--install-service writes a systemd unit or a launchd agent; this platform has neither"
+        );
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(existing) if existing == content => {
+            println!("{} is already installed", path.display());
+        }
+        Ok(_) => bail!(
+            "{} already exists with other contents; remove it first to write a new one",
+            path.display()
+        ),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("failed to create {}", parent.display()))?;
+            }
+            let mut file = fs::File::create(&path)
+                .with_context(|| format!("failed to create {}", path.display()))?;
+            file.write_all(content.as_bytes())
+                .with_context(|| format!("failed to write {}", path.display()))?;
+            println!("Wrote {}", path.display());
+        }
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to read {}", path.display()));
+        }
+    }
+    println!("Start it now and at every login with: {next}");
+    Ok(())
+}
+
+fn systemd_unit(program: &Path, arguments: &[String]) -> String {
+    let command: Vec<String> = std::iter::once(program.to_string_lossy().into_owned())
+        .chain(arguments.iter().cloned())
+        .map(|word| systemd_quote(&word))
+        .collect();
+    format!(
+        "[Unit]
+Description=whitenoise noise player
+After=pipewire.service pulseaudio.service
+
+[Service]
+ExecStart={}
+ExecReload=kill -HUP $MAINPID
+Restart=on-failure
+
+[Install]
+WantedBy=default.target
+",
+        command.join(" ")
+    )
+}
+
+/// One ExecStart word: quoted, with systemd's `%` specifiers and `$`
+/// variables kept literal.
+fn systemd_quote(word: &str) -> String {
+    let escaped = word
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+    format!("\"{escaped}\"")
+}
+
+fn launchd_plist(label: &str, program: &Path, arguments: &[String], log: &Path) -> String {
+    let words: String = std::iter::once(program.to_string_lossy().into_owned())
+        .chain(arguments.iter().cloned())
+        .map(|word| format!("        <string>{}</string>\n", xml_escape(&word)))
+        .collect();
+    let log = xml_escape(&log.to_string_lossy());
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+{words}    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <dict>
+        <key>SuccessfulExit</key>
+        <false/>
+    </dict>
+    <key>StandardOutPath</key>
+    <string>{log}</string>
+    <key>StandardErrorPath</key>
+    <string>{log}</string>
+</dict>
+</plist>
+"#
+    )
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_lines_carry_their_level() {
+        assert_eq!(
+            journal_line(Level::Warning, "the output device was lost"),
+            "<4>the output device was lost"
+        );
+        assert_eq!(
+            syslog_line(Level::Info, "playing brown noise", 4242),
+            "<14>whitenoise[4242]: playing brown noise"
+        );
+    }
+
+    #[test]
+    fn the_unit_and_the_plist_run_the_daemon_with_its_options() {
+        let program = Path::new("/home/me/.cargo/bin/whitenoise");
+        let arguments = ["--volume".to_owned(), "20".to_owned(), "daemon".to_owned()];
+        let unit = systemd_unit(program, &arguments);
+        assert!(
+            unit.contains(
+                "ExecStart=\"/home/me/.cargo/bin/whitenoise\" \"--volume\" \"20\" \"daemon\"\n"
+            ),
+            "{unit}"
+        );
+        assert!(unit.contains("ExecReload=kill -HUP $MAINPID"));
+        assert_eq!(systemd_quote(r#"50% "rain"$"#), r#""50%% \"rain\"$$""#);
+
+        let plist = launchd_plist(
+            LAUNCHD_LABEL,
+            program,
+            &["--style".to_owned(), "rain & fire".to_owned()],
+            Path::new("/Users/me/Library/Logs/whitenoise.log"),
+        );
+        assert!(
+            plist.contains("<string>rain &amp; fire</string>"),
+            "{plist}"
+        );
+        assert!(plist.contains(&format!("<string>{LAUNCHD_LABEL}</string>")));
+        assert_eq!(
+            service_name(LAUNCHD_LABEL, '.', Some("bedroom")),
+            "io.github.clearcmos.whitenoise.bedroom"
+        );
+        assert_eq!(
+            service_name(SYSTEMD_UNIT, '-', Some("office")),
+            "whitenoise-office"
+        );
+        assert_eq!(service_name(SYSTEMD_UNIT, '-', None), "whitenoise");
+    }
+
+    #[test]
+    fn the_pidfile_sits_beside_the_socket_and_goes_with_the_daemon() {
+        let dir = std::env::temp_dir().join(format!("whitenoise-pid-{}", std::process::id()));
+        let path = pidfile_beside(&dir.join("whitenoise.sock"));
+        assert_eq!(path, dir.join("whitenoise.pid"));
+        let pidfile = PidFile::write(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("{}\n", std::process::id())
+        );
+        drop(pidfile);
+        assert!(!path.exists());
+        let _ = fs::remove_dir(&dir);
+    }
+
+    #[test]
+    fn a_session_is_named_after_its_seat_or_else_its_id() {
+        assert_eq!(
+            session_instance(Some("seat1"), Some("c4")).unwrap(),
+            "seat-seat1"
+        );
+        assert_eq!(
+            session_instance(Some(""), Some("12")).unwrap(),
+            "session-12"
+        );
+        assert!(session_instance(None, None).is_err());
+        assert!(session_instance(None, Some("a/b")).is_err());
+    }
+}
@@ -1,74 +1,353 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fs;
 use std::io::ErrorKind;
+use std::ops::{Deref, DerefMut};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LockResult, Mutex, MutexGuard, OnceLock, PoisonError};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, ensure};
 use clap::ValueEnum;
+use crossbeam_utils::atomic::AtomicCell;
 use serde::{Deserialize, Serialize};
 
+use crate::ambient::{AmbientLevel, MAX_AMBIENT_GAIN_DB};
+use crate::contour::{DEFAULT_LISTENING_PHON, MAX_LISTENING_PHON, MIN_LISTENING_PHON};
+use crate::duck::MAX_DUCK_DB;
+use crate::i18n::{text, text_with};
+use crate::night::{ClockTime, MIN_NIGHT_CEILING_DB, NightLimit};
+use crate::scene::LayerShape;
+use crate::schedule::{ScheduleEntry, ScheduleFade};
+use crate::sun::Location;
+use crate::timer::SleepTimer;
+
 pub const EQ_MIN_DB: f32 = -12.0;
 pub const EQ_MAX_DB: f32 = 12.0;
+/// Normal stereo: the channels are related but clearly not identical.
+pub const DEFAULT_STEREO_WIDTH: f32 = 0.5;
+pub const MIN_CARRIER_HZ: f32 = 40.0;
+pub const MAX_CARRIER_HZ: f32 = 1_000.0;
+pub const MIN_BEAT_HZ: f32 = 0.5;
+pub const MAX_BEAT_HZ: f32 = 40.0;
+pub const MAX_TILT_DB: f32 = 6.0;
+/// A band's width as a multiple of the stereo width: 0 keeps it mono, 1
+/// follows the stereo width, and this reaches twice as wide.
+pub const MAX_BAND_WIDTH: f32 = 2.0;
+/// How long the drift's random walks take between turns, on average.
+pub const MIN_DRIFT_PERIOD_SECONDS: f32 = 10.0;
+pub const MAX_DRIFT_PERIOD_SECONDS: f32 = 600.0;
+pub const MIN_CEILING_HZ: f32 = 1_000.0;
+pub const MAX_CEILING_HZ: f32 = 20_000.0;
+pub const MIN_HIGH_PASS_HZ: f32 = 20.0;
+pub const MAX_HIGH_PASS_HZ: f32 = 125.0;
+/// Where the high-pass sits for a device marked `small`.
+pub const SMALL_SPEAKER_HIGH_PASS_HZ: f32 = 40.0;
+pub const HIGH_PASS_SLOPES_DB: [u32; 2] = [12, 24];
+pub const DEFAULT_HIGH_PASS_SLOPE_DB: u32 = 24;
+/// Where Left and Right step the high-pass, on the same series as the
+/// ceiling.
+pub const HIGH_PASS_STEPS_HZ: [f32; 9] = [20.0, 25.0, 31.5, 40.0, 50.0, 63.0, 80.0, 100.0, 125.0];
+/// Where Left and Right step the ceiling: the third-octave series, as on a
+/// graphic equalizer's faceplate.
+pub const CEILING_STEPS_HZ: [f32; 14] = [
+    1_000.0, 1_250.0, 1_600.0, 2_000.0, 2_500.0, 3_150.0, 4_000.0, 5_000.0, 6_300.0, 8_000.0,
+    10_000.0, 12_500.0, 16_000.0, 20_000.0,
+];
+/// The tinnitus notch's center, which covers where tinnitus is usually
+/// pitched, and its width between the -3 dB points.
+pub const MIN_NOTCH_HZ: f32 = 250.0;
+pub const MAX_NOTCH_HZ: f32 = 16_000.0;
+/// Where Right first puts the notch from off.
+pub const DEFAULT_NOTCH_HZ: f32 = 4_000.0;
+pub const MIN_NOTCH_OCTAVES: f32 = 0.1;
+pub const MAX_NOTCH_OCTAVES: f32 = 2.0;
+pub const DEFAULT_NOTCH_OCTAVES: f32 = 1.0;
+/// Limits on the room correction `whitenoise calibrate` measures, per band.
+pub const MAX_CORRECTION_CUT_DB: f32 = 6.0;
+pub const MAX_CORRECTION_BOOST_DB: f32 = 3.0;
+pub const DEFAULT_LOOP_CROSSFADE_SECONDS: f32 = 2.0;
+pub const MIN_LOOP_CROSSFADE_SECONDS: f32 = 0.5;
+pub const MAX_LOOP_CROSSFADE_SECONDS: f32 = 30.0;
+/// Sample shuffling applies to loops shorter than this; a longer loop
+/// repeats too seldom to be picked out.
+pub const SHUFFLE_MAX_SECONDS: f32 = 30.0;
+/// The dB SPL a full-scale RMS output would reach at the listening
+/// position. Phone meters and real speakers both land well inside this.
+pub const MIN_SPL_OFFSET_DB: f32 = 40.0;
+pub const MAX_SPL_OFFSET_DB: f32 = 160.0;
+pub const MIN_MAX_SPL_DB: f32 = 30.0;
+pub const MAX_MAX_SPL_DB: f32 = 120.0;
+/// The master limiter's ceiling in dBTP, true peak against full scale.
+/// A decibel under full scale leaves room for a converter's own overshoot.
+pub const DEFAULT_LIMITER_THRESHOLD_DB: f32 = -1.0;
+pub const MIN_LIMITER_THRESHOLD_DB: f32 = -12.0;
+/// How long the limiter takes to let go once a peak has passed: long enough
+/// not to pump on noise, short enough that a transient costs no more than
+/// a moment of level.
+pub const DEFAULT_LIMITER_RELEASE_MS: f32 = 500.0;
+pub const MIN_LIMITER_RELEASE_MS: f32 = 20.0;
+pub const MAX_LIMITER_RELEASE_MS: f32 = 5_000.0;
 
-#[derive(Debug, Clone, Copy)]
+/// The most EQ bands a `[[bands]]` layout may define: the Main page has
+/// room for twelve sliders. Settings keep this many slots whatever the
+/// layout, so they stay `Copy` for the audio callback.
+pub const MAX_BANDS: usize = 12;
+pub const MIN_BAND_HZ: f32 = 20.0;
+pub const MAX_BAND_HZ: f32 = 20_000.0;
+pub const MIN_BAND_Q: f32 = 0.1;
+pub const MAX_BAND_Q: f32 = 20.0;
+
+/// One EQ band. The built-in eight are `FREQUENCY_BANDS`; a `[[bands]]`
+/// table in settings.toml replaces them with a layout of its own.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FrequencyBand {
-    pub name: &'static str,
+    /// English name, also the identifier the `band` command matches.
+    pub name: Cow<'static, str>,
+    // Only the built-in bands have translated names.
+    #[serde(skip)]
+    pub(crate) label_key: Option<&'static str>,
+    #[serde(rename = "min_hz")]
     pub min_freq: f32,
+    #[serde(rename = "max_hz")]
     pub max_freq: f32,
+    /// Overrides the Q the band's width gives, for a notch narrower than
+    /// its edges suggest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub q: Option<f32>,
 }
 
 impl FrequencyBand {
-    pub fn center_frequency(self) -> f32 {
+    const fn built_in(
+        name: &'static str,
+        label_key: &'static str,
+        min_freq: f32,
+        max_freq: f32,
+    ) -> Self {
+        Self {
+            name: Cow::Borrowed(name),
+            label_key: Some(label_key),
+            min_freq,
+            max_freq,
+            q: None,
+        }
+    }
+
+    /// The name in the interface language.
+    pub fn label(&self) -> &str {
+        match self.label_key {
+            Some(key) => text(key),
+            None => &self.name,
+        }
+    }
+
+    pub fn center_frequency(&self) -> f32 {
         (self.min_freq * self.max_freq).sqrt()
     }
 
-    pub fn q(self) -> f32 {
-        (self.center_frequency() / (self.max_freq - self.min_freq)).clamp(0.5, 3.0)
+    pub fn q(&self) -> f32 {
+        self.q.unwrap_or_else(|| {
+            (self.center_frequency() / (self.max_freq - self.min_freq)).clamp(0.5, 3.0)
+        })
     }
 }
 
-pub const FREQUENCY_BANDS: [FrequencyBand; 8] = [
-    FrequencyBand {
-        name: "Sub Bass",
-        min_freq: 20.0,
-        max_freq: 60.0,
-    },
-    FrequencyBand {
-        name: "Bass",
-        min_freq: 60.0,
-        max_freq: 250.0,
-    },
-    FrequencyBand {
-        name: "Low Mid",
-        min_freq: 250.0,
-        max_freq: 500.0,
-    },
-    FrequencyBand {
-        name: "Mid",
-        min_freq: 500.0,
-        max_freq: 2_000.0,
-    },
-    FrequencyBand {
-        name: "High Mid",
-        min_freq: 2_000.0,
-        max_freq: 4_000.0,
-    },
-    FrequencyBand {
-        name: "Presence",
-        min_freq: 4_000.0,
-        max_freq: 6_000.0,
-    },
-    FrequencyBand {
-        name: "Brilliance",
-        min_freq: 6_000.0,
-        max_freq: 12_000.0,
-    },
-    FrequencyBand {
-        name: "Air",
-        min_freq: 12_000.0,
-        max_freq: 20_000.0,
-    },
+pub static FREQUENCY_BANDS: [FrequencyBand; 8] = [
+    FrequencyBand::built_in("Sub Bass", "band.sub_bass", 20.0, 60.0),
+    FrequencyBand::built_in("Bass", "band.bass", 60.0, 250.0),
+    FrequencyBand::built_in("Low Mid", "band.low_mid", 250.0, 500.0),
+    FrequencyBand::built_in("Mid", "band.mid", 500.0, 2_000.0),
+    FrequencyBand::built_in("High Mid", "band.high_mid", 2_000.0, 4_000.0),
+    FrequencyBand::built_in("Presence", "band.presence", 4_000.0, 6_000.0),
+    FrequencyBand::built_in("Brilliance", "band.brilliance", 6_000.0, 12_000.0),
+    FrequencyBand::built_in("Air", "band.air", 12_000.0, 20_000.0),
 ];
 
+/// Indexes into `FREQUENCY_BANDS` of the bands two octaves wide (Bass and
+/// Mid), which take an optional tilt on the advanced EQ page. The tilts stay
+/// on these built-in ranges under a custom layout.
+pub const TILT_BANDS: [usize; 2] = [1, 3];
+
+static LAYOUT: OnceLock<Vec<FrequencyBand>> = OnceLock::new();
+static BAND_FILTERS: OnceLock<BandFilters> = OnceLock::new();
+static NOISE_RNG: OnceLock<NoiseRng> = OnceLock::new();
+static CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// How the EQ bands carve up the spectrum.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BandFilters {
+    /// A Linkwitz-Riley crossover splits the sound at the midpoint between
+    /// neighboring bands, 48 dB/octave apart, so a slider moves its own
+    /// range and a muted band goes silent.
+    #[default]
+    Crossover,
+    /// One peaking filter per band in series: about a quarter of the work,
+    /// but each band's skirts reach well into its neighbors.
+    Peaking,
+}
+
+/// The EQ bands in play: the `[[bands]]` layout given to `set_band_layout`,
+/// or the built-in eight.
+pub fn bands() -> &'static [FrequencyBand] {
+    LAYOUT.get().map_or(&FREQUENCY_BANDS, Vec::as_slice)
+}
+
+/// Makes `layout` the bands every part of the player uses, after checking
+/// it. An empty layout keeps the built-in bands. Only the first call has
+/// any effect, so it belongs right after the settings file is read.
+pub fn set_band_layout(layout: &[FrequencyBand]) -> Result<()> {
+    if layout.is_empty() {
+        return Ok(());
+    }
+    check_band_layout(layout)?;
+    let _ = LAYOUT.set(layout.to_vec());
+    Ok(())
+}
+
+/// The band filters in play: the choice given to `set_band_filters`, or
+/// the crossover.
+pub fn band_filters() -> BandFilters {
+    BAND_FILTERS.get().copied().unwrap_or_default()
+}
+
+/// Makes `filters` the way every EQ splits its bands. Like the layout, only
+/// the first call has any effect.
+pub fn set_band_filters(filters: BandFilters) {
+    let _ = BAND_FILTERS.set(filters);
+}
+
+/// Which generator draws the noise. Any of them is far past what an ear
+/// can tell from true randomness: none repeats within the age of the
+/// universe at any sample rate, and none shows structure a filter or the
+/// EQ could bring out. They differ in cost and in where the seed comes from.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum NoiseRng {
+    /// rand's `SmallRng`, Xoshiro256++ on a 64-bit machine: a few
+    /// nanoseconds a sample.
+    #[default]
+    Small,
+    /// Xoshiro128++, which keeps to 32-bit arithmetic and so costs least on
+    /// a 32-bit ARM board such as a Pi Zero, where a 64-bit generator needs
+    /// two registers for every word.
+    Xoshiro,
+    /// ChaCha12 keyed straight from the operating system's entropy source,
+    /// which mixes in the hardware generator where there is one: a
+    /// cryptographic stream at several times the cost, for anyone who wants
+    /// the noise to be unpredictable rather than merely sound random.
+    Chacha,
+}
+
+/// The noise generator in play: the choice given to `set_noise_rng`, or
+/// `SmallRng`.
+pub fn noise_rng() -> NoiseRng {
+    NOISE_RNG.get().copied().unwrap_or_default()
+}
+
+/// Makes `rng` the generator every engine draws its noise from. Like the
+/// band filters, only the first call has any effect.
+pub fn set_noise_rng(rng: NoiseRng) {
+    let _ = NOISE_RNG.set(rng);
+}
+
+pub(crate) fn check_band_layout(layout: &[FrequencyBand]) -> Result<()> {
+    ensure!(
+        layout.len() <= MAX_BANDS,
+        "[[bands]] defines {} bands; at most {MAX_BANDS} fit",
+        layout.len()
+    );
+    let key = |name: &str| name.to_lowercase().replace([' ', '-', '_'], "");
+    for (index, band) in layout.iter().enumerate() {
+        let name = band.name.trim();
+        ensure!(!name.is_empty(), "band {} has no name", index + 1);
+        ensure!(
+            layout[..index]
+                .iter()
+                .all(|other| key(&other.name) != key(name)),
+            "two bands are named {name}"
+        );
+        ensure!(
+            band.min_freq >= MIN_BAND_HZ
+                && band.max_freq <= MAX_BAND_HZ
+                && band.min_freq < band.max_freq,
+            "band {name} must run from a lower to a higher frequency within {MIN_BAND_HZ}-{MAX_BAND_HZ} Hz"
+        );
+        if let Some(q) = band.q {
+            ensure!(
+                (MIN_BAND_Q..=MAX_BAND_Q).contains(&q),
+                "band {name} needs a Q from {MIN_BAND_Q} to {MAX_BAND_Q}"
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Slider values for the first bands, the rest at the neutral middle.
+pub fn band_values(values: &[f32]) -> [f32; MAX_BANDS] {
+    let mut padded = [0.5; MAX_BANDS];
+    for (slot, value) in padded.iter_mut().zip(values) {
+        *slot = *value;
+    }
+    padded
+}
+
+/// Band widths are written only when one leaves the stereo width, one per
+/// band of the layout like the sliders; missing bands follow it.
+mod band_widths {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::{MAX_BANDS, bands};
+
+    pub fn follow(values: &[f32; MAX_BANDS]) -> bool {
+        values.iter().all(|value| *value == 1.0)
+    }
+
+    pub fn serialize<S: Serializer>(
+        values: &[f32; MAX_BANDS],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(&values[..bands().len()])
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<[f32; MAX_BANDS], D::Error> {
+        let values = Vec::<f32>::deserialize(deserializer)?;
+        let mut padded = [1.0; MAX_BANDS];
+        for (slot, value) in padded.iter_mut().zip(values) {
+            *slot = value;
+        }
+        Ok(padded)
+    }
+}
+
+/// Settings files and presets hold one slider value per band in the
+/// layout, so a file written under the built-in bands reads the same in an
+/// older binary.
+mod band_sliders {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::{MAX_BANDS, band_values, bands};
+
+    pub fn serialize<S: Serializer>(
+        values: &[f32; MAX_BANDS],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(&values[..bands().len()])
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<[f32; MAX_BANDS], D::Error> {
+        Vec::<f32>::deserialize(deserializer).map(|values| band_values(&values))
+    }
+
+    pub fn neutral(values: &[f32; MAX_BANDS]) -> bool {
+        values.iter().all(|value| *value == 0.5)
+    }
+}
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
 pub enum SoundStyle {
     #[default]
@@ -84,30 +363,432 @@ pub enum SoundStyle {
     Pink,
     #[serde(rename = "brown", alias = "Brown")]
     Brown,
+    #[serde(rename = "blue", alias = "Blue")]
+    Blue,
+    #[serde(rename = "violet", alias = "Violet")]
+    Violet,
     #[serde(rename = "rain", alias = "Rain")]
     Rain,
+    /// Synthesized, like the noise colors; see `soundscape.rs`.
+    #[serde(rename = "ocean")]
+    Ocean,
+    #[serde(rename = "wind")]
+    Wind,
+    #[serde(rename = "fire")]
+    Fire,
+    #[serde(rename = "fan")]
+    Fan,
+    /// The user's own WAV loop (`--sample`); silent when none is loaded.
+    #[serde(rename = "sample")]
+    Sample,
 }
 
 impl SoundStyle {
-    pub const ALL: [Self; 4] = [Self::White, Self::Pink, Self::Brown, Self::Rain];
+    pub const ALL: [Self; 11] = [
+        Self::White,
+        Self::Pink,
+        Self::Brown,
+        Self::Blue,
+        Self::Violet,
+        Self::Rain,
+        Self::Ocean,
+        Self::Wind,
+        Self::Fire,
+        Self::Fan,
+        Self::Sample,
+    ];
 
     pub fn label(self) -> &'static str {
-        match self {
-            Self::White => "White Noise",
-            Self::Pink => "Pink Noise",
-            Self::Brown => "Brown Noise",
-            Self::Rain => "Rain",
-        }
+        text(match self {
+            Self::White => "source.white",
+            Self::Pink => "source.pink",
+            Self::Brown => "source.brown",
+            Self::Blue => "source.blue",
+            Self::Violet => "source.violet",
+            Self::Rain => "source.rain",
+            Self::Ocean => "source.ocean",
+            Self::Wind => "source.wind",
+            Self::Fire => "source.fire",
+            Self::Fan => "source.fan",
+            Self::Sample => "source.sample",
+        })
     }
 
     pub fn next(self) -> Self {
         match self {
             Self::White => Self::Pink,
             Self::Pink => Self::Brown,
-            Self::Brown => Self::Rain,
-            Self::Rain => Self::White,
+            Self::Brown => Self::Blue,
+            Self::Blue => Self::Violet,
+            Self::Violet => Self::Rain,
+            Self::Rain => Self::Ocean,
+            Self::Ocean => Self::Wind,
+            Self::Wind => Self::Fire,
+            Self::Fire => Self::Fan,
+            Self::Fan => Self::Sample,
+            Self::Sample => Self::White,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ToneMode {
+    #[default]
+    Off,
+    /// A slightly different carrier in each ear; the beat is heard between
+    /// them, so it needs headphones.
+    Binaural,
+    /// One carrier pulsed on and off at the beat rate; works on speakers.
+    Isochronic,
+}
+
+impl ToneMode {
+    pub fn label(self) -> &'static str {
+        text(match self {
+            Self::Off => "tone.off",
+            Self::Binaural => "tone.binaural",
+            Self::Isochronic => "tone.isochronic",
+        })
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Binaural,
+            Self::Binaural => Self::Isochronic,
+            Self::Isochronic => Self::Off,
+        }
+    }
+
+    pub fn previous(self) -> Self {
+        self.next().next()
+    }
+}
+
+/// The optional tone layer under the noise. The level is a power fraction
+/// on the same scale as a source in the mix, so 100% is as loud as a solo
+/// source at full level.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToneSettings {
+    pub mode: ToneMode,
+    pub carrier_hz: f32,
+    pub beat_hz: f32,
+    pub level: f32,
+}
+
+impl Default for ToneSettings {
+    fn default() -> Self {
+        Self {
+            mode: ToneMode::Off,
+            carrier_hz: 200.0,
+            beat_hz: 4.0,
+            level: 0.1,
+        }
+    }
+}
+
+impl ToneSettings {
+    /// `Binaural 200 Hz, 4 Hz beat`, or `Off`.
+    pub fn describe(&self) -> String {
+        if self.mode == ToneMode::Off {
+            return self.mode.label().to_owned();
+        }
+        text_with(
+            "tone.describe",
+            &[
+                ("mode", &self.mode.label()),
+                ("carrier", &format!("{:.0}", self.carrier_hz)),
+                ("beat", &format_hz(self.beat_hz)),
+            ],
+        )
+    }
+
+    fn sanitize(mut self) -> Self {
+        let defaults = Self::default();
+        self.carrier_hz = sanitize_range(
+            self.carrier_hz,
+            MIN_CARRIER_HZ,
+            MAX_CARRIER_HZ,
+            defaults.carrier_hz,
+        );
+        self.beat_hz = sanitize_range(self.beat_hz, MIN_BEAT_HZ, MAX_BEAT_HZ, defaults.beat_hz);
+        self.level = sanitize_unit(self.level, defaults.level);
+        self
+    }
+}
+
+/// Slow drift, which keeps a steady noise from sounding frozen: each band's
+/// gain, the left-right balance, and the loops' playback speed wander on
+/// random walks of their own. Depth scales all three, and the period is
+/// the walks' average time between turns.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DriftSettings {
+    pub enabled: bool,
+    pub depth: f32,
+    pub period_seconds: f32,
+}
+
+impl Default for DriftSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            depth: 0.5,
+            period_seconds: 60.0,
+        }
+    }
+}
+
+impl DriftSettings {
+    /// The depth in play: the setting while drift is on, 0 while it is off.
+    pub fn active_depth(&self) -> f32 {
+        if self.enabled { self.depth } else { 0.0 }
+    }
+
+    fn sanitize(mut self) -> Self {
+        let defaults = Self::default();
+        self.depth = sanitize_unit(self.depth, defaults.depth);
+        self.period_seconds = sanitize_range(
+            self.period_seconds,
+            MIN_DRIFT_PERIOD_SECONDS,
+            MAX_DRIFT_PERIOD_SECONDS,
+            defaults.period_seconds,
+        );
+        self
+    }
+}
+
+/// One of the two listeners the L key swaps between.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Listener {
+    #[default]
+    A,
+    B,
+}
+
+impl Listener {
+    pub fn other(self) -> Self {
+        match self {
+            Self::A => Self::B,
+            Self::B => Self::A,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::A => "A",
+            Self::B => "B",
+        }
+    }
+}
+
+/// A listener's volume and EQ; the sources, timer, and everything else
+/// are shared.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ListenerSound {
+    pub volume: f32,
+    #[serde(with = "band_sliders")]
+    pub frequency_bands: [f32; MAX_BANDS],
+    #[serde(default)]
+    pub band_tilts: [f32; TILT_BANDS.len()],
+}
+
+impl ListenerSound {
+    fn of(settings: &AudioSettings) -> Self {
+        Self {
+            volume: settings.volume,
+            frequency_bands: settings.frequency_bands,
+            band_tilts: settings.band_tilts,
+        }
+    }
+
+    fn sanitize(self) -> Self {
+        let sanitized = AudioSettings {
+            volume: self.volume,
+            frequency_bands: self.frequency_bands,
+            band_tilts: self.band_tilts,
+            ..AudioSettings::default()
+        }
+        .sanitize();
+        Self::of(&sanitized)
+    }
+}
+
+/// The EQ alone, the band sliders, tilts, widths, mid/side curve, ceiling,
+/// and notch, as the interface keeps it for comparing two curves and
+/// undoing changes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EqSnapshot {
+    frequency_bands: [f32; MAX_BANDS],
+    band_tilts: [f32; TILT_BANDS.len()],
+    mid_side: bool,
+    side_bands: [f32; MAX_BANDS],
+    band_widths: [f32; MAX_BANDS],
+    ceiling_hz: Option<f32>,
+    notch: Notch,
+}
+
+impl EqSnapshot {
+    pub fn of(settings: &AudioSettings) -> Self {
+        Self {
+            frequency_bands: settings.frequency_bands,
+            band_tilts: settings.band_tilts,
+            mid_side: settings.mid_side,
+            side_bands: settings.side_bands,
+            band_widths: settings.band_widths,
+            ceiling_hz: settings.ceiling_hz,
+            notch: settings.notch,
+        }
+    }
+
+    /// Plays this EQ, leaving the rest of the sound alone.
+    pub fn apply(self, settings: &mut AudioSettings) {
+        settings.frequency_bands = self.frequency_bands;
+        settings.band_tilts = self.band_tilts;
+        settings.mid_side = self.mid_side;
+        settings.side_bands = self.side_bands;
+        settings.band_widths = self.band_widths;
+        settings.ceiling_hz = self.ceiling_hz;
+        settings.notch = self.notch;
+    }
+}
+
+/// The `[listeners]` table: a volume and EQ for each of two people who
+/// share one player, such as a couple who want different brightness. L
+/// keeps the sound playing as the active listener's and plays the other's.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Listeners {
+    pub active: Listener,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub a: Option<ListenerSound>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub b: Option<ListenerSound>,
+}
+
+impl Listeners {
+    /// Whether neither listener has a sound yet, as before the first swap.
+    pub fn is_unset(&self) -> bool {
+        self.a.is_none() && self.b.is_none()
+    }
+
+    fn slot(&mut self, listener: Listener) -> &mut Option<ListenerSound> {
+        match listener {
+            Listener::A => &mut self.a,
+            Listener::B => &mut self.b,
         }
     }
+
+    fn sanitize(mut self) -> Self {
+        self.a = self.a.map(ListenerSound::sanitize);
+        self.b = self.b.map(ListenerSound::sanitize);
+        self
+    }
+}
+
+/// What a phone SPL meter at the listening position made of the output.
+/// `offset_db` is the dB SPL a full-scale RMS output would reach, so the
+/// estimate is the output's RMS in dBFS plus the offset; it holds for the
+/// speakers and the system volume it was measured with. `max_db` caps the
+/// output's level in dB SPL once the offset is known.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SplCalibration {
+    pub offset_db: Option<f32>,
+    pub max_db: Option<f32>,
+}
+
+impl SplCalibration {
+    /// The calibration that makes the current output, `rms_dbfs`, read as
+    /// `measured_db` SPL.
+    pub fn from_reading(self, measured_db: f32, rms_dbfs: f32) -> Self {
+        Self {
+            offset_db: Some(measured_db - rms_dbfs),
+            ..self
+        }
+        .sanitize()
+    }
+
+    /// The approximate dB SPL of an output at `rms_dbfs`.
+    pub fn estimate_db(&self, rms_dbfs: f32) -> Option<f32> {
+        self.offset_db.map(|offset| rms_dbfs + offset)
+    }
+
+    /// The cap as an RMS amplitude, when there is a cap and an offset to
+    /// read it by.
+    pub fn cap_rms(&self) -> Option<f32> {
+        let (offset, max) = (self.offset_db?, self.max_db?);
+        Some(10_f32.powf((max - offset) / 20.0))
+    }
+
+    fn sanitize(mut self) -> Self {
+        self.offset_db = self
+            .offset_db
+            .filter(|db| db.is_finite())
+            .map(|db| db.clamp(MIN_SPL_OFFSET_DB, MAX_SPL_OFFSET_DB));
+        self.max_db = self
+            .max_db
+            .filter(|db| db.is_finite())
+            .map(|db| db.clamp(MIN_MAX_SPL_DB, MAX_MAX_SPL_DB));
+        self
+    }
+}
+
+/// The master limiter, from `[output]`: the true-peak level nothing the
+/// engine plays may pass, and how long it takes to recover once a peak
+/// has gone by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LimiterSettings {
+    pub threshold_db: f32,
+    pub release_ms: f32,
+}
+
+impl Default for LimiterSettings {
+    fn default() -> Self {
+        Self {
+            threshold_db: DEFAULT_LIMITER_THRESHOLD_DB,
+            release_ms: DEFAULT_LIMITER_RELEASE_MS,
+        }
+    }
+}
+
+impl LimiterSettings {
+    /// The threshold as a linear amplitude.
+    pub fn threshold(&self) -> f32 {
+        10_f32.powf(self.threshold_db / 20.0)
+    }
+
+    pub fn release_seconds(&self) -> f32 {
+        self.release_ms / 1_000.0
+    }
+
+    fn sanitize(self) -> Self {
+        let defaults = Self::default();
+        Self {
+            threshold_db: sanitize_range(
+                self.threshold_db,
+                MIN_LIMITER_THRESHOLD_DB,
+                0.0,
+                defaults.threshold_db,
+            ),
+            release_ms: sanitize_range(
+                self.release_ms,
+                MIN_LIMITER_RELEASE_MS,
+                MAX_LIMITER_RELEASE_MS,
+                defaults.release_ms,
+            ),
+        }
+    }
+}
+
+/// Beat rates step by 0.5 Hz, so whole numbers drop the decimal.
+pub fn format_hz(hz: f32) -> String {
+    if hz.fract() == 0.0 {
+        format!("{hz:.0}")
+    } else {
+        format!("{hz:.1}")
+    }
 }
 
 /// Per-source playback levels as power fractions in [0, 1]. Levels are
@@ -120,7 +801,14 @@ pub struct SourceMix {
     pub white: f32,
     pub pink: f32,
     pub brown: f32,
+    pub blue: f32,
+    pub violet: f32,
     pub rain: f32,
+    pub ocean: f32,
+    pub wind: f32,
+    pub fire: f32,
+    pub fan: f32,
+    pub sample: f32,
 }
 
 impl Default for SourceMix {
@@ -135,7 +823,14 @@ impl SourceMix {
             white: 0.0,
             pink: 0.0,
             brown: 0.0,
+            blue: 0.0,
+            violet: 0.0,
             rain: 0.0,
+            ocean: 0.0,
+            wind: 0.0,
+            fire: 0.0,
+            fan: 0.0,
+            sample: 0.0,
         };
         mix.set_level(style, 1.0);
         mix
@@ -146,7 +841,14 @@ impl SourceMix {
             SoundStyle::White => self.white,
             SoundStyle::Pink => self.pink,
             SoundStyle::Brown => self.brown,
+            SoundStyle::Blue => self.blue,
+            SoundStyle::Violet => self.violet,
             SoundStyle::Rain => self.rain,
+            SoundStyle::Ocean => self.ocean,
+            SoundStyle::Wind => self.wind,
+            SoundStyle::Fire => self.fire,
+            SoundStyle::Fan => self.fan,
+            SoundStyle::Sample => self.sample,
         }
     }
 
@@ -155,7 +857,14 @@ impl SourceMix {
             SoundStyle::White => &mut self.white,
             SoundStyle::Pink => &mut self.pink,
             SoundStyle::Brown => &mut self.brown,
+            SoundStyle::Blue => &mut self.blue,
+            SoundStyle::Violet => &mut self.violet,
             SoundStyle::Rain => &mut self.rain,
+            SoundStyle::Ocean => &mut self.ocean,
+            SoundStyle::Wind => &mut self.wind,
+            SoundStyle::Fire => &mut self.fire,
+            SoundStyle::Fan => &mut self.fan,
+            SoundStyle::Sample => &mut self.sample,
         };
         *slot = value;
     }
@@ -190,14 +899,22 @@ impl SourceMix {
             return style.label().to_owned();
         }
         if self.total() <= 0.0 {
-            return "Silence (all sources at zero)".to_owned();
+            return text("source.silence").to_owned();
         }
         let parts: Vec<String> = SoundStyle::ALL
             .into_iter()
             .filter(|style| self.level(*style) > 0.0)
-            .map(|style| format!("{} {:.0}%", style.label(), self.level(style) * 100.0))
+            .map(|style| {
+                text_with(
+                    "source.mix_part",
+                    &[
+                        ("source", &style.label()),
+                        ("percent", &format!("{:.0}", self.level(style) * 100.0)),
+                    ],
+                )
+            })
             .collect();
-        format!("Mix: {}", parts.join(" + "))
+        text_with("source.mix", &[("sources", &parts.join(" + "))])
     }
 
     fn sanitize(mut self) -> Self {
@@ -208,20 +925,237 @@ impl SourceMix {
     }
 }
 
+/// Per-band mute and solo, laid over the stored slider values: a silenced
+/// band plays at the EQ's deepest cut, and its slider keeps its place.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BandOverlay {
+    pub muted: [bool; MAX_BANDS],
+    pub solo: Option<usize>,
+}
+
+impl BandOverlay {
+    /// A solo silences every other band, muted or not, and overrides a mute
+    /// on the soloed band itself.
+    pub fn silences(self, index: usize) -> bool {
+        match self.solo {
+            Some(solo) => solo != index,
+            None => self.muted[index],
+        }
+    }
+
+    pub fn toggle_mute(&mut self, index: usize) {
+        self.muted[index] = !self.muted[index];
+    }
+
+    /// Soloing the soloed band again ends the solo.
+    pub fn toggle_solo(&mut self, index: usize) {
+        self.solo = (self.solo != Some(index)).then_some(index);
+    }
+
+    fn sanitize(mut self) -> Self {
+        self.solo = self.solo.filter(|band| *band < bands().len());
+        self
+    }
+}
+
+/// The settings every thread of a session shares. The interface, the
+/// control socket, the web remote, MQTT, and the other writers change them
+/// under `lock` as before, and letting go of the lock publishes a copy. The
+/// audio callback reads that copy through `changed_since`, a sequence lock
+/// held only while the copy is made, so it never waits for a writer to
+/// finish what it does under the lock.
+pub struct SharedSettings {
+    settings: Mutex<AudioSettings>,
+    published: AtomicCell<AudioSettings>,
+    // Bumped after each publish, so the callback skips an unchanged copy.
+    version: AtomicU64,
+}
+
+impl SharedSettings {
+    pub fn new(settings: AudioSettings) -> Self {
+        Self {
+            settings: Mutex::new(settings),
+            published: AtomicCell::new(settings),
+            version: AtomicU64::new(0),
+        }
+    }
+
+    /// Waits for the settings, as `Mutex::lock` does; the guard publishes
+    /// them when it goes, even if the thread panicked while holding it.
+    pub fn lock(&self) -> LockResult<SettingsGuard<'_>> {
+        let guard = |guard| SettingsGuard {
+            guard,
+            shared: self,
+        };
+        self.settings
+            .lock()
+            .map(guard)
+            .map_err(|poisoned| PoisonError::new(guard(poisoned.into_inner())))
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.settings.is_poisoned()
+    }
+
+    /// The latest published settings, without waiting on the lock.
+    pub fn snapshot(&self) -> AudioSettings {
+        self.published.load()
+    }
+
+    /// The latest published settings if any were published after `seen`,
+    /// which then moves on to them. Never waits on the lock, so the audio
+    /// callback may call it.
+    pub fn changed_since(&self, seen: &mut u64) -> Option<AudioSettings> {
+        let version = self.version.load(Ordering::Acquire);
+        if version == *seen {
+            return None;
+        }
+        *seen = version;
+        Some(self.published.load())
+    }
+}
+
+/// `SharedSettings::lock`'s guard.
+pub struct SettingsGuard<'a> {
+    guard: MutexGuard<'a, AudioSettings>,
+    shared: &'a SharedSettings,
+}
+
+impl Deref for SettingsGuard<'_> {
+    type Target = AudioSettings;
+
+    fn deref(&self) -> &AudioSettings {
+        &self.guard
+    }
+}
+
+impl DerefMut for SettingsGuard<'_> {
+    fn deref_mut(&mut self) -> &mut AudioSettings {
+        &mut self.guard
+    }
+}
+
+impl Drop for SettingsGuard<'_> {
+    // Still under the lock, so copies are published in the order the
+    // writers made their changes. A guard that only read publishes nothing.
+    fn drop(&mut self) {
+        if self.shared.published.load() != *self.guard {
+            self.shared.published.store(*self.guard);
+            self.shared.version.fetch_add(1, Ordering::Release);
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[serde(default)]
 pub struct AudioSettings {
     pub volume: f32,
-    pub frequency_bands: [f32; FREQUENCY_BANDS.len()],
+    // One slot per band of `bands()`; the slots past the layout stay unused.
+    #[serde(with = "band_sliders")]
+    pub frequency_bands: [f32; MAX_BANDS],
+    // dB per TILT_BANDS entry; positive lifts the band's upper edge over
+    // its lower one.
+    pub band_tilts: [f32; TILT_BANDS.len()],
+    // Mid/side EQ: while on, the band sliders shape the mid and side_bands,
+    // the Side EQ page, the side; see side().
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub mid_side: bool,
+    #[serde(with = "band_sliders", skip_serializing_if = "band_sliders::neutral")]
+    pub side_bands: [f32; MAX_BANDS],
+    // Corner of the global low-pass; None leaves the top end open.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ceiling_hz: Option<f32>,
+    #[serde(skip_serializing_if = "Notch::is_default")]
+    pub notch: Notch,
+    // Runtime copies of the [output] high-pass and crossfeed, and of the
+    // playing device's room correction, so the callback sees them.
+    #[serde(skip)]
+    pub high_pass: HighPass,
+    #[serde(skip)]
+    pub crossfeed: bool,
+    // Runtime copy of the [output] headroom switch: the volume comes down
+    // by as much as the band sliders raise the level.
+    #[serde(skip)]
+    pub headroom: bool,
+    // Runtime copy of the [loops] table.
+    #[serde(skip)]
+    pub loops: LoopSettings,
+    // Runtime copy of the [output] room tone, a power fraction; 0 is off.
+    #[serde(skip)]
+    pub room_tone: f32,
+    // Runtime copy of the [output] SPL calibration and cap.
+    #[serde(skip)]
+    pub spl: SplCalibration,
+    // Runtime copy of the [output] limiter threshold and release.
+    #[serde(skip)]
+    pub limiter: LimiterSettings,
+    // Runtime copy of the [listeners] table.
+    #[serde(skip)]
+    pub listeners: Listeners,
+    // Runtime only: the output's peak ceiling in dBFS while the
+    // [night_limit] window is open.
+    #[serde(skip)]
+    pub night_ceiling_db: Option<f32>,
+    // Runtime only: a [[schedule]] entry's fade in or out.
+    #[serde(skip)]
+    pub schedule_fade: Option<ScheduleFade>,
+    // Runtime only: how far --duck-on-playback lowers the sound while
+    // another program plays.
+    #[serde(skip)]
+    pub duck_db: Option<f32>,
+    // Runtime only: how far --duck-on-calls lowers the sound during a
+    // voice call.
+    #[serde(skip)]
+    pub call_duck_db: Option<f32>,
+    // Runtime only: how far --ambient-level raises or lowers the sound to
+    // stay above the room's noise.
+    #[serde(skip)]
+    pub ambient_gain_db: Option<f32>,
+    // dB per band, added under the sliders.
+    #[serde(skip)]
+    pub room_correction_db: [f32; MAX_BANDS],
+    // Runtime only: the Main page's band mute and solo.
+    #[serde(skip)]
+    pub band_overlay: BandOverlay,
+    // Runtime only: the loaded scene's filters and swell, one per
+    // SoundStyle::ALL entry. A preset has none, so loading one clears them.
+    #[serde(skip)]
+    pub layers: [LayerShape; SoundStyle::ALL.len()],
     #[serde(alias = "perceptual_normalization")]
     pub listening_contour: bool,
+    // The level the contour compensates for, in phon; see contour.rs.
+    pub listening_phon: f32,
+    // 0 is mono, 1 is fully decorrelated left and right.
+    pub stereo_width: f32,
+    // Each band's width as a multiple of stereo_width, from 0 to
+    // MAX_BAND_WIDTH; see band_width().
+    #[serde(with = "band_widths", skip_serializing_if = "band_widths::follow")]
+    pub band_widths: [f32; MAX_BANDS],
     // Kept in the file as the dominant source so pre-mix binaries can still
     // read new settings; at runtime it only anchors legacy migration.
     pub sound_style: SoundStyle,
-    // Access through mix()/set_mix(); pub(crate) only so struct-update
-    // syntax keeps working in the other modules' tests.
+    /// Read and set through mix() and set_mix(), which migrate old files
+    /// and keep sound_style in step. Public only so struct-update syntax
+    /// works outside this module.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub(crate) mix: Option<SourceMix>,
+    pub mix: Option<SourceMix>,
+    pub tone: ToneSettings,
+    pub drift: DriftSettings,
+    // Runtime only: shared with the audio callback so the fade tracks the
+    // clock, but a timer never outlives the session that set it.
+    #[serde(skip)]
+    pub sleep_timer: Option<SleepTimer>,
+    // Runtime only: set by Space or the media keys. The output ramps to
+    // silence and the sources then hold their place; the stream stays open,
+    // so resuming needs no new one.
+    #[serde(skip)]
+    pub paused: bool,
+    // Runtime only: set by M. Silent like a pause, but the sources run on.
+    #[serde(skip)]
+    pub muted: bool,
+    // Runtime only: true while the reference-listening key is held.
+    #[serde(skip)]
+    pub bypass: bool,
 }
 
 impl Default for AudioSettings {
@@ -230,91 +1164,931 @@ impl Default for AudioSettings {
             // Interactive mode deliberately starts muted unless --volume is supplied.
             volume: 0.0,
             // The middle position is a neutral 0 dB graphic EQ.
-            frequency_bands: [0.5; FREQUENCY_BANDS.len()],
+            frequency_bands: [0.5; MAX_BANDS],
+            band_tilts: [0.0; TILT_BANDS.len()],
+            mid_side: false,
+            side_bands: [0.5; MAX_BANDS],
+            ceiling_hz: None,
+            notch: Notch::default(),
+            high_pass: HighPass::default(),
+            crossfeed: false,
+            headroom: false,
+            loops: LoopSettings::default(),
+            room_tone: 0.0,
+            spl: SplCalibration::default(),
+            limiter: LimiterSettings::default(),
+            listeners: Listeners::default(),
+            night_ceiling_db: None,
+            schedule_fade: None,
+            duck_db: None,
+            call_duck_db: None,
+            ambient_gain_db: None,
+            room_correction_db: [0.0; MAX_BANDS],
+            band_overlay: BandOverlay::default(),
+            layers: [LayerShape::default(); SoundStyle::ALL.len()],
             listening_contour: false,
+            listening_phon: DEFAULT_LISTENING_PHON,
+            stereo_width: DEFAULT_STEREO_WIDTH,
+            band_widths: [1.0; MAX_BANDS],
             sound_style: SoundStyle::White,
             mix: None,
+            tone: ToneSettings::default(),
+            drift: DriftSettings::default(),
+            sleep_timer: None,
+            paused: false,
+            muted: false,
+            bypass: false,
+        }
+    }
+}
+
+impl AudioSettings {
+    pub fn sanitize(mut self) -> Self {
+        self.volume = sanitize_unit(self.volume, 0.0);
+        for value in self.frequency_bands.iter_mut().chain(&mut self.side_bands) {
+            *value = sanitize_unit(*value, 0.5);
+        }
+        for tilt in &mut self.band_tilts {
+            *tilt = sanitize_range(*tilt, -MAX_TILT_DB, MAX_TILT_DB, 0.0);
+        }
+        self.ceiling_hz = self
+            .ceiling_hz
+            .filter(|hz| hz.is_finite())
+            .map(|hz| hz.clamp(MIN_CEILING_HZ, MAX_CEILING_HZ));
+        self.notch = self.notch.sanitize();
+        self.high_pass = self.high_pass.sanitize();
+        self.loops = self.loops.sanitize();
+        self.room_tone = sanitize_unit(self.room_tone, 0.0);
+        self.spl = self.spl.sanitize();
+        self.limiter = self.limiter.sanitize();
+        self.listeners = self.listeners.sanitize();
+        self.night_ceiling_db = self
+            .night_ceiling_db
+            .filter(|db| db.is_finite())
+            .map(|db| db.clamp(MIN_NIGHT_CEILING_DB, 0.0));
+        self.duck_db = self
+            .duck_db
+            .filter(|db| db.is_finite())
+            .map(|db| db.clamp(0.0, MAX_DUCK_DB));
+        self.call_duck_db = self
+            .call_duck_db
+            .filter(|db| db.is_finite())
+            .map(|db| db.clamp(0.0, MAX_DUCK_DB));
+        self.ambient_gain_db = self
+            .ambient_gain_db
+            .filter(|db| db.is_finite())
+            .map(|db| db.clamp(-MAX_AMBIENT_GAIN_DB, MAX_AMBIENT_GAIN_DB));
+        self.band_overlay = self.band_overlay.sanitize();
+        self.layers = self.layers.map(LayerShape::sanitize);
+        for db in &mut self.room_correction_db {
+            *db = sanitize_range(*db, -MAX_CORRECTION_CUT_DB, MAX_CORRECTION_BOOST_DB, 0.0);
+        }
+        self.listening_phon = sanitize_range(
+            self.listening_phon,
+            MIN_LISTENING_PHON,
+            MAX_LISTENING_PHON,
+            DEFAULT_LISTENING_PHON,
+        );
+        self.stereo_width = sanitize_unit(self.stereo_width, DEFAULT_STEREO_WIDTH);
+        for width in &mut self.band_widths {
+            *width = sanitize_range(*width, 0.0, MAX_BAND_WIDTH, 1.0);
+        }
+        self.mix = Some(self.mix().sanitize());
+        self.tone = self.tone.sanitize();
+        self.drift = self.drift.sanitize();
+        self
+    }
+
+    /// The stereo width `band` plays at: the stereo width times the band's
+    /// own, at most fully wide.
+    pub fn band_width(&self, band: usize) -> f32 {
+        (self.stereo_width * self.band_widths.get(band).copied().unwrap_or(1.0)).clamp(0.0, 1.0)
+    }
+
+    /// These settings as the side's EQ plays them: with mid/side on, the
+    /// Side EQ page's sliders in place of the band sliders, which then
+    /// shape only the mid.
+    pub fn side(&self) -> Self {
+        if !self.mid_side {
+            return *self;
+        }
+        Self {
+            frequency_bands: self.side_bands,
+            ..*self
+        }
+    }
+
+    /// Whether any band leaves the stereo width.
+    pub fn has_band_widths(&self) -> bool {
+        !band_widths::follow(&self.band_widths)
+    }
+
+    /// The listening level the contour compensates for, or None while the
+    /// contour is off.
+    pub fn contour_phon(&self) -> Option<f32> {
+        self.listening_contour.then_some(self.listening_phon)
+    }
+
+    /// The effective mix. A settings file predating the [mix] table migrates
+    /// to a solo of its legacy sound_style.
+    pub fn mix(&self) -> SourceMix {
+        self.mix
+            .unwrap_or_else(|| SourceMix::solo(self.sound_style))
+    }
+
+    /// Keeps the current volume and EQ as the active listener's, then plays
+    /// `to`'s. A listener with nothing kept yet starts from the current
+    /// sound, so the first swap changes nothing audible.
+    pub fn switch_listener(&mut self, to: Listener) {
+        let current = ListenerSound::of(self);
+        let mut listeners = self.listeners;
+        *listeners.slot(listeners.active) = Some(current);
+        let next = *listeners.slot(to).get_or_insert(current);
+        listeners.active = to;
+        self.listeners = listeners;
+        self.volume = next.volume;
+        self.frequency_bands = next.frequency_bands;
+        self.band_tilts = next.band_tilts;
+    }
+
+    pub fn set_mix(&mut self, mix: SourceMix) {
+        self.mix = Some(mix.sanitize());
+        // Pre-mix binaries played the user's sample in the rain slot and
+        // cannot parse "sample"; nor do they know the soundscapes, of which
+        // brown noise is the nearest color.
+        self.sound_style = match self.mix().dominant() {
+            SoundStyle::Sample => SoundStyle::Rain,
+            SoundStyle::Ocean | SoundStyle::Wind | SoundStyle::Fire | SoundStyle::Fan => {
+                SoundStyle::Brown
+            }
+            style => style,
+        };
+    }
+}
+
+pub fn slider_to_db(value: f32) -> f32 {
+    EQ_MIN_DB + sanitize_unit(value, 0.5) * (EQ_MAX_DB - EQ_MIN_DB)
+}
+
+/// The slider position that plays `db`, clamped to the EQ range.
+pub fn db_to_slider(db: f32) -> f32 {
+    sanitize_unit((db - EQ_MIN_DB) / (EQ_MAX_DB - EQ_MIN_DB), 0.5)
+}
+
+fn sanitize_unit(value: f32, fallback: f32) -> f32 {
+    sanitize_range(value, 0.0, 1.0, fallback)
+}
+
+fn sanitize_range(value: f32, min: f32, max: f32, fallback: f32) -> f32 {
+    if value.is_finite() {
+        value.clamp(min, max)
+    } else {
+        fallback
+    }
+}
+
+/// The settings file: the one `set_config_path` chose, or `settings.toml`
+/// in the platform's config directory. Everything else kept beside it, from
+/// the presets to the timer file, moves with it.
+pub fn config_path() -> PathBuf {
+    CONFIG_PATH
+        .get()
+        .cloned()
+        .unwrap_or_else(|| config_dir().join("settings.toml"))
+}
+
+fn config_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("whitenoise")
+}
+
+/// Makes `path` the settings file for the rest of the process. Call it
+/// before anything reads `config_path`; only the first call has any effect.
+pub fn set_config_path(path: PathBuf) {
+    let _ = CONFIG_PATH.set(path);
+}
+
+/// A named instance's settings file, `instances/NAME/settings.toml` in the
+/// config directory, so each instance keeps its own folder.
+pub fn instance_config_path(name: &str) -> PathBuf {
+    config_dir()
+        .join("instances")
+        .join(name)
+        .join("settings.toml")
+}
+
+/// How the interface marks selection and slider fill. `Color` is the
+/// original look; the other two never rely on hue alone.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Palette {
+    #[default]
+    Color,
+    /// Blue and yellow only, with solid and shaded bars and bold selection,
+    /// so nothing depends on telling red from green.
+    Colorblind,
+    /// No color at all: reverse video for the selection, bold and dim for
+    /// intensity, and solid and shaded bars.
+    Monochrome,
+}
+
+/// The `[theme]` table. It belongs to the terminal, not to a sound, so it
+/// stays out of `AudioSettings` and presets.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThemeSettings {
+    pub palette: Palette,
+    /// Dims the interface each night from this time of day until
+    /// `dim_until`, so the terminal does not light up a bedroom.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dim_from: Option<ClockTime>,
+    /// The end of the nightly dimming, 07:00 unless set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dim_until: Option<ClockTime>,
+    /// Dims the interface while a sleep timer is set, at any hour.
+    pub dim_with_timer: bool,
+}
+
+impl ThemeSettings {
+    /// Whether the interface is dimmed at `time`, with or without a sleep
+    /// timer set.
+    pub fn dims(&self, time: ClockTime, timer_set: bool) -> bool {
+        let until = self.dim_until.or(ClockTime::new(7, 0));
+        (self.dim_with_timer && timer_set)
+            || self
+                .dim_from
+                .zip(until)
+                .is_some_and(|(from, until)| time.within(from, until))
+    }
+}
+
+/// The global high-pass that keeps sub-bass out of small speakers. It
+/// protects the output rather than shaping a sound, so it is stored in
+/// `[output]` and presets leave it alone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HighPass {
+    /// Corner in Hz; `None` is off.
+    pub hz: Option<f32>,
+    /// One of `HIGH_PASS_SLOPES_DB`.
+    pub slope_db: u32,
+}
+
+impl Default for HighPass {
+    fn default() -> Self {
+        Self {
+            hz: None,
+            slope_db: DEFAULT_HIGH_PASS_SLOPE_DB,
+        }
+    }
+}
+
+impl HighPass {
+    fn sanitize(self) -> Self {
+        Self {
+            hz: self
+                .hz
+                .filter(|hz| hz.is_finite())
+                .map(|hz| hz.clamp(MIN_HIGH_PASS_HZ, MAX_HIGH_PASS_HZ)),
+            slope_db: HIGH_PASS_SLOPES_DB
+                .into_iter()
+                .min_by_key(|slope| slope.abs_diff(self.slope_db))
+                .unwrap_or(DEFAULT_HIGH_PASS_SLOPE_DB),
+        }
+    }
+}
+
+/// A notch cut out of the noise at the listener's tinnitus pitch, as in
+/// notched-sound therapy. It shapes the sound, so presets store it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Notch {
+    /// Center in Hz; `None` is off.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hz: Option<f32>,
+    /// Width between the -3 dB points, in octaves.
+    pub width_octaves: f32,
+}
+
+impl Default for Notch {
+    fn default() -> Self {
+        Self {
+            hz: None,
+            width_octaves: DEFAULT_NOTCH_OCTAVES,
+        }
+    }
+}
+
+impl Notch {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The filter Q whose -3 dB points are `width_octaves` apart.
+    pub fn q(self) -> f32 {
+        let ratio = 2.0_f32.powf(self.width_octaves);
+        ratio.sqrt() / (ratio - 1.0)
+    }
+
+    /// The -3 dB edges in Hz, spaced evenly on a log scale around the
+    /// center.
+    pub fn edges_hz(self, center_hz: f32) -> (f32, f32) {
+        let half = 2.0_f32.powf(self.width_octaves / 2.0);
+        (center_hz / half, center_hz * half)
+    }
+
+    fn sanitize(self) -> Self {
+        Self {
+            hz: self
+                .hz
+                .filter(|hz| hz.is_finite())
+                .map(|hz| hz.clamp(MIN_NOTCH_HZ, MAX_NOTCH_HZ)),
+            width_octaves: sanitize_range(
+                self.width_octaves,
+                MIN_NOTCH_OCTAVES,
+                MAX_NOTCH_OCTAVES,
+                DEFAULT_NOTCH_OCTAVES,
+            ),
+        }
+    }
+}
+
+/// The `[loops]` table: how long the rain recording and the user's sample
+/// crossfade from their end back into their start, and whether each plays
+/// in shuffled stretches instead. A longer fade hides a loop whose texture
+/// changes across the seam. Each belongs to its recording, so presets leave
+/// them alone.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoopSettings {
+    pub rain_crossfade_seconds: f32,
+    pub sample_crossfade_seconds: f32,
+    pub rain_shuffle: bool,
+    pub sample_shuffle: bool,
+}
+
+impl Default for LoopSettings {
+    fn default() -> Self {
+        Self {
+            rain_crossfade_seconds: DEFAULT_LOOP_CROSSFADE_SECONDS,
+            sample_crossfade_seconds: DEFAULT_LOOP_CROSSFADE_SECONDS,
+            rain_shuffle: false,
+            sample_shuffle: false,
+        }
+    }
+}
+
+impl LoopSettings {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn sanitize(self) -> Self {
+        let sanitize = |seconds| {
+            sanitize_range(
+                seconds,
+                MIN_LOOP_CROSSFADE_SECONDS,
+                MAX_LOOP_CROSSFADE_SECONDS,
+                DEFAULT_LOOP_CROSSFADE_SECONDS,
+            )
+        };
+        Self {
+            rain_crossfade_seconds: sanitize(self.rain_crossfade_seconds),
+            sample_crossfade_seconds: sanitize(self.sample_crossfade_seconds),
+            ..self
+        }
+    }
+
+    /// Each crossfade shortened to at most the matching one in `limits`.
+    pub fn within(self, limits: Self) -> Self {
+        Self {
+            rain_crossfade_seconds: self
+                .rain_crossfade_seconds
+                .min(limits.rain_crossfade_seconds),
+            sample_crossfade_seconds: self
+                .sample_crossfade_seconds
+                .min(limits.sample_crossfade_seconds),
+            ..self
+        }
+    }
+}
+
+/// The output settings chosen together for one kind of playback device.
+/// A profile only sets values; each of them can still be changed on its own
+/// afterwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputProfile {
+    /// Crossfeed on, default width, and the listening contour; no high-pass.
+    Headphones,
+    /// Full width and the contour, with a gentle 31.5 Hz high-pass.
+    Desktop,
+    /// Mono, no contour lifting the bass, and a steep 40 Hz high-pass.
+    SmallSpeaker,
+    /// Flat: no high-pass, crossfeed, or contour, at default width.
+    Studio,
+}
+
+impl OutputProfile {
+    /// The name `--profile` and `:profile` accept.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Headphones => "headphones",
+            Self::Desktop => "desktop",
+            Self::SmallSpeaker => "small-speaker",
+            Self::Studio => "studio",
+        }
+    }
+
+    pub fn apply(self, settings: &mut AudioSettings) {
+        let (hz, slope_db, crossfeed, width, contour) = match self {
+            Self::Headphones => (
+                None,
+                DEFAULT_HIGH_PASS_SLOPE_DB,
+                true,
+                DEFAULT_STEREO_WIDTH,
+                true,
+            ),
+            Self::Desktop => (Some(31.5), 12, false, 1.0, true),
+            Self::SmallSpeaker => (Some(SMALL_SPEAKER_HIGH_PASS_HZ), 24, false, 0.0, false),
+            Self::Studio => (
+                None,
+                DEFAULT_HIGH_PASS_SLOPE_DB,
+                false,
+                DEFAULT_STEREO_WIDTH,
+                false,
+            ),
+        };
+        settings.high_pass = HighPass { hz, slope_db };
+        settings.crossfeed = crossfeed;
+        settings.stereo_width = width;
+        settings.listening_contour = contour;
+    }
+}
+
+/// The `[output]` table: a sample rate and buffer size to ask the device
+/// for instead of its defaults, the high-pass, crossfeed, headroom, and
+/// the room tone. The matching flags override it, and like the theme it is not part
+/// of a preset.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buffer_size: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub high_pass_hz: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub high_pass_slope_db: Option<u32>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub crossfeed: bool,
+    /// Whether the volume makes room for the band sliders' boosts.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub headroom: bool,
+    /// Room tone level as a power fraction; left out while off.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub room_tone: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub band_filters: Option<BandFilters>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub noise_rng: Option<NoiseRng>,
+    /// dB SPL at full-scale RMS, from a meter reading; see `SplCalibration`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spl_offset_db: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_spl_db: Option<f32>,
+    /// The master limiter's true-peak threshold in dBTP; see
+    /// `LimiterSettings`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limiter_threshold_db: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limiter_release_ms: Option<f32>,
+}
+
+impl OutputSettings {
+    fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn high_pass(&self) -> HighPass {
+        HighPass {
+            hz: self.high_pass_hz,
+            slope_db: self
+                .high_pass_slope_db
+                .unwrap_or(DEFAULT_HIGH_PASS_SLOPE_DB),
+        }
+        .sanitize()
+    }
+
+    /// Writes the slope only when it differs from the default or was
+    /// already in the file.
+    pub fn set_high_pass(&mut self, high_pass: HighPass) {
+        self.high_pass_hz = high_pass.hz;
+        self.high_pass_slope_db = (self.high_pass_slope_db.is_some()
+            || high_pass.slope_db != DEFAULT_HIGH_PASS_SLOPE_DB)
+            .then_some(high_pass.slope_db);
+    }
+
+    pub fn room_tone(&self) -> f32 {
+        sanitize_unit(self.room_tone.unwrap_or(0.0), 0.0)
+    }
+
+    pub fn set_room_tone(&mut self, level: f32) {
+        self.room_tone = (level > 0.0).then_some(level);
+    }
+
+    pub fn spl(&self) -> SplCalibration {
+        SplCalibration {
+            offset_db: self.spl_offset_db,
+            max_db: self.max_spl_db,
+        }
+        .sanitize()
+    }
+
+    pub fn set_spl(&mut self, spl: SplCalibration) {
+        self.spl_offset_db = spl.offset_db;
+        self.max_spl_db = spl.max_db;
+    }
+
+    pub fn limiter(&self) -> LimiterSettings {
+        let defaults = LimiterSettings::default();
+        LimiterSettings {
+            threshold_db: self.limiter_threshold_db.unwrap_or(defaults.threshold_db),
+            release_ms: self.limiter_release_ms.unwrap_or(defaults.release_ms),
+        }
+        .sanitize()
+    }
+
+    /// Writes each value only when it differs from the default or was
+    /// already in the file.
+    pub fn set_limiter(&mut self, limiter: LimiterSettings) {
+        let defaults = LimiterSettings::default();
+        self.limiter_threshold_db = (self.limiter_threshold_db.is_some()
+            || limiter.threshold_db != defaults.threshold_db)
+            .then_some(limiter.threshold_db);
+        self.limiter_release_ms = (self.limiter_release_ms.is_some()
+            || limiter.release_ms != defaults.release_ms)
+            .then_some(limiter.release_ms);
+    }
+}
+
+/// One `[devices."NAME"]` table, keyed by a device name as `--list-devices`
+/// prints it.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceSettings {
+    /// A speaker too small for deep bass. Playing through it turns the
+    /// high-pass on at 40 Hz when none is set.
+    pub small: bool,
+    /// Applied when playing through the device unless `--profile` is given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<OutputProfile>,
+    /// dB per EQ band from `whitenoise calibrate`, applied under the EQ
+    /// while the device plays. It only fits the layout it was measured for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub room_correction_db: Option<Vec<f32>>,
+}
+
+impl DeviceSettings {
+    /// The room correction padded to the engine's bands, or None when there
+    /// is none or it was measured for another layout.
+    pub fn room_correction(&self) -> Option<[f32; MAX_BANDS]> {
+        let correction = self.room_correction_db.as_ref()?;
+        if correction.len() != bands().len() {
+            return None;
+        }
+        let mut padded = [0.0; MAX_BANDS];
+        padded[..correction.len()].copy_from_slice(correction);
+        Some(padded)
+    }
+}
+
+/// Everything stored in settings.toml. The audio callback only ever sees the
+/// Copy `AudioSettings`; options that own heap data, such as file paths, sit
+/// beside it at the top level of the same file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SettingsFile {
+    #[serde(flatten)]
+    pub audio: AudioSettings,
+    /// WAV file to loop in place of the embedded rain recording.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample: Option<PathBuf>,
+    /// Level the sample by its own ReplayGain track gain tag, where it has
+    /// one, instead of measuring it.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub sample_replay_gain: bool,
+    pub theme: ThemeSettings,
+    #[serde(skip_serializing_if = "OutputSettings::is_unset")]
+    pub output: OutputSettings,
+    #[serde(skip_serializing_if = "LoopSettings::is_default")]
+    pub loops: LoopSettings,
+    /// Devices to play on, by name, when no --device is given: the first
+    /// is the output and the rest play the same sound alongside it.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub output_devices: Vec<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub devices: BTreeMap<String, DeviceSettings>,
+    #[serde(skip_serializing_if = "Listeners::is_unset")]
+    pub listeners: Listeners,
+    /// A `[[bands]]` EQ layout in place of the built-in eight.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub bands: Vec<FrequencyBand>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub night_limit: Option<NightLimit>,
+    /// Follows the room's noise through a microphone when present.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ambient: Option<AmbientLevel>,
+    /// Where sunrise and sunset are worked out for the schedule.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<ScheduleEntry>,
+    /// The style every run starts with in place of the last one played,
+    /// named as for --style.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_style: Option<String>,
+    /// The volume, 0 to 1, every run starts at unless --volume, a preset,
+    /// a scene, or the schedule picks one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_volume: Option<f32>,
+    /// Start the interface at the last volume played instead of silence.
+    /// Non-interactive playback always does.
+    pub restore_last_volume: bool,
+    /// The community preset gallery's HTTPS address. Nothing is sent to or
+    /// fetched from a gallery without it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gallery: Option<String>,
+}
+
+impl SettingsFile {
+    fn sanitize(mut self) -> Self {
+        self.audio = self.audio.sanitize();
+        self.loops = self.loops.sanitize();
+        self.listeners = self.listeners.sanitize();
+        self.start_volume = self
+            .start_volume
+            .filter(|volume| volume.is_finite())
+            .map(|volume| volume.clamp(0.0, 1.0));
+        self
+    }
+
+    /// The `[devices]` entry for `name`, matched without regard to case, or
+    /// the defaults when there is none.
+    pub fn device(&self, name: &str) -> DeviceSettings {
+        self.devices
+            .iter()
+            .find(|(device, _)| device.eq_ignore_ascii_case(name))
+            .map(|(_, settings)| settings.clone())
+            .unwrap_or_default()
+    }
+
+    /// The `[devices]` entry for `name`, matched as `device` does, added
+    /// when there is none.
+    pub fn device_mut(&mut self, name: &str) -> &mut DeviceSettings {
+        let key = self
+            .devices
+            .keys()
+            .find(|device| device.eq_ignore_ascii_case(name))
+            .cloned()
+            .unwrap_or_else(|| name.to_owned());
+        self.devices.entry(key).or_default()
+    }
+}
+
+/// The settings.toml format this release writes. Files from before the
+/// `version` key are format 1.
+pub const SETTINGS_VERSION: u32 = 2;
+
+/// The steps from each format to the next, the first taking format 1 to 2.
+const MIGRATIONS: [fn(&mut toml::Table); SETTINGS_VERSION as usize - 1] = [
+    // Format 1 could still name the listening contour by its old name, and
+    // a file holding both names did not load at all.
+    |table| {
+        if let Some(old) = table.remove("perceptual_normalization") {
+            table.entry("listening_contour").or_insert(old);
+        }
+    },
+];
+
+/// What reading settings.toml took beyond parsing it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoadReport {
+    /// The format of a file older than `SETTINGS_VERSION`.
+    pub migrated_from: Option<u32>,
+    /// The format of a file written by a newer release.
+    pub newer: Option<u32>,
+    /// Settings that could not be read and are at their defaults, such as
+    /// `volume` or `schedule[2]`.
+    pub dropped: Vec<String>,
+    /// Where the file was copied before anything rewrites it.
+    pub backup: Option<PathBuf>,
+}
+
+impl LoadReport {
+    /// One line for each thing worth telling the user, none when the file
+    /// read as written.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if let Some(version) = self.migrated_from {
+            warnings.push(format!(
+                "settings.toml was updated from format {version} to {SETTINGS_VERSION}"
+            ));
         }
+        if let Some(version) = self.newer {
+            warnings.push(format!(
+                "settings.toml is in format {version} from a newer release; this release \
+                 writes format {SETTINGS_VERSION}"
+            ));
+        }
+        if !self.dropped.is_empty() {
+            warnings.push(format!(
+                "could not read {} in settings.toml and used the defaults instead",
+                self.dropped.join(", ")
+            ));
+        }
+        if let (Some(backup), false) = (&self.backup, warnings.is_empty()) {
+            warnings.push(format!("the old file is kept at {}", backup.display()));
+        }
+        warnings
     }
 }
 
-impl AudioSettings {
-    pub fn sanitize(mut self) -> Self {
-        self.volume = sanitize_unit(self.volume, 0.0);
-        for value in &mut self.frequency_bands {
-            *value = sanitize_unit(*value, 0.5);
+pub fn load_settings() -> Result<(SettingsFile, LoadReport)> {
+    load_settings_from(&config_path())
+}
+
+/// Reads settings.toml forgivingly: an older format is migrated, and a
+/// setting that does not read leaves just that setting at its default
+/// rather than the whole file. Whenever the next save would change more
+/// than the user did, the file is first copied to `settings.toml.bak`.
+fn load_settings_from(path: &std::path::Path) -> Result<(SettingsFile, LoadReport)> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            return Ok((SettingsFile::default(), LoadReport::default()));
         }
-        self.mix = Some(self.mix().sanitize());
-        self
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to read {}", path.display()));
+        }
+    };
+
+    let mut table = match content.parse::<toml::Table>() {
+        Ok(table) => table,
+        Err(error) => {
+            let kept = match back_up(path) {
+                Ok(backup) => format!("; the file is kept at {}", backup.display()),
+                Err(_) => String::new(),
+            };
+            return Err(error).with_context(|| format!("failed to parse {}{kept}", path.display()));
+        }
+    };
+
+    let mut report = LoadReport::default();
+    let version = match table.remove("version") {
+        None => 1,
+        Some(toml::Value::Integer(version)) if version >= 1 => {
+            u32::try_from(version).unwrap_or(u32::MAX)
+        }
+        Some(_) => {
+            report.dropped.push("version".to_owned());
+            1
+        }
+    };
+    if version > SETTINGS_VERSION {
+        report.newer = Some(version);
+    } else if version < SETTINGS_VERSION {
+        for migrate in &MIGRATIONS[version as usize - 1..] {
+            migrate(&mut table);
+        }
+        report.migrated_from = Some(version);
     }
 
-    /// The effective mix. A settings file predating the [mix] table migrates
-    /// to a solo of its legacy sound_style.
-    pub fn mix(&self) -> SourceMix {
-        self.mix
-            .unwrap_or_else(|| SourceMix::solo(self.sound_style))
+    let mut root = toml::Value::Table(table);
+    if !salvage(&mut Vec::new(), &mut root, &mut report.dropped) {
+        root = toml::Value::Table(toml::Table::new());
     }
+    let settings: SettingsFile = root
+        .try_into()
+        .with_context(|| format!("failed to parse {}", path.display()))?;
 
-    pub fn set_mix(&mut self, mix: SourceMix) {
-        self.mix = Some(mix.sanitize());
-        self.sound_style = self.mix().dominant();
+    if report != LoadReport::default() {
+        report.backup = back_up(path).ok();
     }
+    Ok((settings.sanitize(), report))
 }
 
-pub fn slider_to_db(value: f32) -> f32 {
-    EQ_MIN_DB + sanitize_unit(value, 0.5) * (EQ_MAX_DB - EQ_MIN_DB)
+/// Copies the settings file to `<name>.bak` beside it.
+fn back_up(path: &std::path::Path) -> Result<PathBuf> {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(".bak");
+    let backup = path.with_file_name(name);
+    fs::copy(path, &backup).with_context(|| format!("failed to copy to {}", backup.display()))?;
+    Ok(backup)
 }
 
-fn sanitize_unit(value: f32, fallback: f32) -> f32 {
-    if value.is_finite() {
-        value.clamp(0.0, 1.0)
-    } else {
-        fallback
-    }
+/// One step from a settings.toml table down to a value in it.
+enum Step {
+    Key(String),
+    Index(usize),
 }
 
-pub fn config_path() -> PathBuf {
-    let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
-    path.push("whitenoise");
-    path.push("settings.toml");
-    path
+/// Trims `value`, found at `path`, until a `SettingsFile` holding only it
+/// reads, naming each key or array entry it drops. False when none of it
+/// can be kept; the caller drops and names it instead.
+fn salvage(path: &mut Vec<Step>, value: &mut toml::Value, dropped: &mut Vec<String>) -> bool {
+    if reads_alone(path, value) {
+        return true;
+    }
+    let named = dropped.len();
+    match value {
+        toml::Value::Table(table) => {
+            let keys: Vec<String> = table.keys().cloned().collect();
+            for key in keys {
+                path.push(Step::Key(key.clone()));
+                if !table
+                    .get_mut(&key)
+                    .is_some_and(|entry| salvage(path, entry, dropped))
+                {
+                    dropped.push(path_name(path));
+                    table.remove(&key);
+                }
+                path.pop();
+            }
+        }
+        toml::Value::Array(items) => {
+            let mut index = 0;
+            items.retain_mut(|item| {
+                path.push(Step::Index(index));
+                let keep = salvage(path, item, dropped);
+                if !keep {
+                    dropped.push(path_name(path));
+                }
+                path.pop();
+                index += 1;
+                keep
+            });
+        }
+        _ => return false,
+    }
+    let kept = reads_alone(path, value);
+    if !kept {
+        // A table can fail for want of a field that was never there, as
+        // when one of a night limit's two times is gone; it is dropped
+        // whole, under its own name.
+        dropped.truncate(named);
+    }
+    kept
 }
 
-pub fn load_settings() -> Result<AudioSettings> {
-    load_settings_from(&config_path())
+fn reads_alone(path: &[Step], value: &toml::Value) -> bool {
+    let mut document = value.clone();
+    for step in path.iter().rev() {
+        document = match step {
+            Step::Key(key) => toml::Value::Table(toml::Table::from_iter([(key.clone(), document)])),
+            Step::Index(_) => toml::Value::Array(vec![document]),
+        };
+    }
+    document.try_into::<SettingsFile>().is_ok()
 }
 
-fn load_settings_from(path: &std::path::Path) -> Result<AudioSettings> {
-    let content = match fs::read_to_string(path) {
-        Ok(content) => content,
-        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(AudioSettings::default()),
-        Err(error) => {
-            return Err(error).with_context(|| format!("failed to read {}", path.display()));
+fn path_name(path: &[Step]) -> String {
+    let mut name = String::new();
+    for step in path {
+        match step {
+            Step::Key(key) if name.is_empty() => name.push_str(key),
+            Step::Key(key) => {
+                name.push('.');
+                name.push_str(key);
+            }
+            Step::Index(index) => name.push_str(&format!("[{index}]")),
         }
-    };
-
-    toml::from_str::<AudioSettings>(&content)
-        .with_context(|| format!("failed to parse {}", path.display()))
-        .map(AudioSettings::sanitize)
+    }
+    name
 }
 
-pub fn save_settings(settings: &AudioSettings) -> Result<()> {
+pub fn save_settings(settings: &SettingsFile) -> Result<()> {
     save_settings_to(&config_path(), settings)
 }
 
-fn save_settings_to(path: &std::path::Path, settings: &AudioSettings) -> Result<()> {
+fn save_settings_to(path: &std::path::Path, settings: &SettingsFile) -> Result<()> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)
             .with_context(|| format!("failed to create {}", parent.display()))?;
     }
 
-    let content = toml::to_string_pretty(&settings.sanitize())?;
+    let content = format!(
+        "version = {SETTINGS_VERSION}\n{}",
+        toml::to_string_pretty(&settings.clone().sanitize())?
+    );
     fs::write(path, content).with_context(|| format!("failed to write {}", path.display()))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::night::ClockTime;
 
     #[test]
     fn neutral_slider_is_zero_db() {
@@ -345,7 +2119,9 @@ mod tests {
         let settings: AudioSettings = toml::from_str("sound_style = \"Rain\"").unwrap();
 
         assert_eq!(settings.volume, 0.0);
-        assert_eq!(settings.frequency_bands, [0.5; FREQUENCY_BANDS.len()]);
+        assert_eq!(settings.frequency_bands, [0.5; MAX_BANDS]);
+        assert_eq!(settings.stereo_width, DEFAULT_STEREO_WIDTH);
+        assert_eq!(settings.tone, ToneSettings::default());
         assert_eq!(settings.sound_style, SoundStyle::Rain);
     }
 
@@ -365,19 +2141,152 @@ mod tests {
         let path = scratch_settings_path("round-trip");
         let mut saved = AudioSettings {
             volume: 0.35,
-            frequency_bands: [0.0, 0.1, 0.2, 0.3, 0.6, 0.7, 0.8, 1.0],
+            frequency_bands: band_values(&[0.0, 0.1, 0.2, 0.3, 0.6, 0.7, 0.8, 1.0]),
             listening_contour: true,
+            tone: ToneSettings {
+                mode: ToneMode::Isochronic,
+                carrier_hz: 150.0,
+                beat_hz: 10.5,
+                level: 0.2,
+            },
+            drift: DriftSettings {
+                enabled: true,
+                depth: 0.3,
+                period_seconds: 120.0,
+            },
             ..AudioSettings::default()
         };
         saved.set_mix(SourceMix {
             white: 0.0,
             pink: 0.25,
             brown: 0.5,
+            blue: 0.1,
+            violet: 0.0,
             rain: 0.0,
+
+            ocean: 0.0,
+
+            wind: 0.0,
+
+            fire: 0.0,
+
+            fan: 0.0,
+
+            sample: 0.0,
         });
 
-        save_settings_to(&path, &saved).unwrap();
-        let loaded = load_settings_from(&path).unwrap();
+        let file = SettingsFile {
+            audio: saved,
+            sample: Some(PathBuf::from("/tmp/ocean loop.wav")),
+            sample_replay_gain: true,
+            theme: ThemeSettings {
+                palette: Palette::Monochrome,
+                dim_from: ClockTime::new(21, 30),
+                dim_until: None,
+                dim_with_timer: true,
+            },
+            output: OutputSettings {
+                sample_rate: Some(48_000),
+                buffer_size: Some(256),
+                high_pass_hz: Some(40.0),
+                high_pass_slope_db: Some(12),
+                crossfeed: true,
+                headroom: true,
+                room_tone: Some(0.1),
+                band_filters: Some(BandFilters::Peaking),
+                noise_rng: Some(NoiseRng::Xoshiro),
+                spl_offset_db: Some(92.5),
+                max_spl_db: Some(65.0),
+                limiter_threshold_db: Some(-3.0),
+                limiter_release_ms: Some(250.0),
+            },
+            loops: LoopSettings {
+                rain_crossfade_seconds: 3.5,
+                sample_crossfade_seconds: 6.0,
+                rain_shuffle: true,
+                sample_shuffle: true,
+            },
+            output_devices: vec!["Studio Headphones".to_owned(), "Pocket Speaker".to_owned()],
+            devices: BTreeMap::from([
+                (
+                    "Pocket Speaker".to_owned(),
+                    DeviceSettings {
+                        small: true,
+                        profile: None,
+                        room_correction_db: Some(vec![0.0, -4.5, -2.0, 0.0, 0.0, 0.5, 1.0, 3.0]),
+                    },
+                ),
+                (
+                    "Studio Headphones".to_owned(),
+                    DeviceSettings {
+                        profile: Some(OutputProfile::Headphones),
+                        ..DeviceSettings::default()
+                    },
+                ),
+            ]),
+            listeners: Listeners {
+                active: Listener::B,
+                a: None,
+                b: Some(ListenerSound {
+                    volume: 0.3,
+                    ..ListenerSound::of(&AudioSettings::default())
+                }),
+            },
+            bands: vec![FrequencyBand {
+                name: Cow::Borrowed("Notch"),
+                label_key: None,
+                min_freq: 5_800.0,
+                max_freq: 6_200.0,
+                q: Some(8.0),
+            }],
+            night_limit: Some(NightLimit {
+                from: ClockTime::new(22, 30).unwrap(),
+                until: ClockTime::new(7, 0).unwrap(),
+                ceiling_db: -24.0,
+            }),
+            ambient: Some(AmbientLevel {
+                input: Some("USB Mic".to_owned()),
+                margin_db: 6.0,
+                ..AmbientLevel::default()
+            }),
+            location: Some(Location {
+                latitude: 59.33,
+                longitude: 18.07,
+            }),
+            schedule: vec![ScheduleEntry {
+                at: ClockTime::new(22, 30).unwrap().into(),
+                preset: Some("sleep".to_owned()),
+                scene: None,
+                stop: false,
+                fade_seconds: 45.0,
+            }],
+            default_style: Some("evening".to_owned()),
+            start_volume: Some(0.25),
+            restore_last_volume: true,
+            gallery: Some("https://presets.example.org/api".to_owned()),
+        };
+        save_settings_to(&path, &file).unwrap();
+        assert!(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .contains("[night_limit]\nfrom = \"22:30\"\nuntil = \"07:00\"")
+        );
+        let (loaded, report) = load_settings_from(&path).unwrap();
+        assert_eq!(report, LoadReport::default());
+        assert_eq!(loaded.sample, file.sample);
+        assert_eq!(loaded.output, file.output);
+        assert_eq!(loaded.loops, file.loops);
+        assert_eq!(loaded.devices, file.devices);
+        assert_eq!(loaded.theme, file.theme);
+        assert_eq!(loaded.bands, file.bands);
+        assert_eq!(loaded.night_limit, file.night_limit);
+        assert_eq!(loaded.ambient, file.ambient);
+        assert_eq!(loaded.location, file.location);
+        assert_eq!(loaded.schedule, file.schedule);
+        assert_eq!(loaded.default_style, file.default_style);
+        assert_eq!(loaded.start_volume, file.start_volume);
+        assert!(loaded.restore_last_volume);
+        let loaded = loaded.audio;
         assert_eq!(loaded, saved.sanitize());
         assert_eq!(loaded.mix().brown, 0.5);
         // The dominant source is persisted as sound_style so binaries that
@@ -391,14 +2300,14 @@ mod tests {
     fn missing_settings_file_yields_defaults() {
         let path = scratch_settings_path("missing");
         let loaded = load_settings_from(&path).unwrap();
-        assert_eq!(loaded, AudioSettings::default());
+        assert_eq!(loaded, (SettingsFile::default(), LoadReport::default()));
     }
 
     #[test]
     fn malformed_settings_file_reports_the_path() {
         let path = scratch_settings_path("malformed");
         std::fs::create_dir_all(path.parent().unwrap()).unwrap();
-        std::fs::write(&path, "volume = \"not a number\"").unwrap();
+        std::fs::write(&path, "volume = = 0.4").unwrap();
 
         let error = format!("{:#}", load_settings_from(&path).unwrap_err());
         assert!(
@@ -406,6 +2315,101 @@ mod tests {
             "unexpected error: {error}"
         );
         assert!(error.contains("settings.toml"));
+        // Whatever the next save writes, the file as it was survives.
+        assert!(
+            error.contains("settings.toml.bak"),
+            "unexpected error: {error}"
+        );
+        assert_eq!(
+            std::fs::read_to_string(path.with_file_name("settings.toml.bak")).unwrap(),
+            "volume = = 0.4"
+        );
+
+        std::fs::remove_dir_all(path.ancestors().nth(2).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn unreadable_settings_fall_back_one_at_a_time() {
+        let path = scratch_settings_path("salvage");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let written = r#"
+            version = 2
+            volume = "loud"
+            stereo_width = 0.8
+            start_volume = 0.3
+
+            [output]
+            spl_offset_db = "high"
+            room_tone = 0.1
+
+            [night_limit]
+            from = "22:30"
+
+            [[schedule]]
+            at = "22:00"
+            preset = "sleep"
+
+            [[schedule]]
+            at = "soon"
+            preset = "sleep"
+        "#;
+        std::fs::write(&path, written).unwrap();
+
+        let (loaded, report) = load_settings_from(&path).unwrap();
+        assert_eq!(loaded.audio.volume, 0.0);
+        assert_eq!(loaded.audio.stereo_width, 0.8);
+        assert_eq!(loaded.start_volume, Some(0.3));
+        assert_eq!(loaded.output.spl_offset_db, None);
+        assert_eq!(loaded.output.room_tone, Some(0.1));
+        assert_eq!(loaded.night_limit, None);
+        assert_eq!(loaded.schedule.len(), 1);
+        assert_eq!(
+            report.dropped,
+            [
+                "night_limit",
+                "output.spl_offset_db",
+                "schedule[1]",
+                "volume"
+            ]
+        );
+        assert_eq!(report.migrated_from, None);
+        let backup = path.with_file_name("settings.toml.bak");
+        assert_eq!(report.backup.as_deref(), Some(backup.as_path()));
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), written);
+        assert_eq!(report.warnings().len(), 2);
+
+        std::fs::remove_dir_all(path.ancestors().nth(2).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn older_and_newer_formats_are_reported_and_kept() {
+        let path = scratch_settings_path("versions");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        // Before the format had a version, a file could carry the old and
+        // new names for the listening contour side by side.
+        std::fs::write(
+            &path,
+            "volume = 0.4\nperceptual_normalization = true\nlistening_contour = false\n",
+        )
+        .unwrap();
+
+        let (loaded, report) = load_settings_from(&path).unwrap();
+        assert_eq!(loaded.audio.volume, 0.4);
+        assert!(!loaded.audio.listening_contour);
+        assert_eq!(report.migrated_from, Some(1));
+        assert!(report.dropped.is_empty());
+        assert!(report.backup.is_some());
+
+        save_settings_to(&path, &loaded).unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert!(saved.starts_with(&format!("version = {SETTINGS_VERSION}\n")));
+        assert_eq!(load_settings_from(&path).unwrap().1, LoadReport::default());
+
+        std::fs::write(&path, "version = 9\nvolume = 0.4\nfuture = true\n").unwrap();
+        let (loaded, report) = load_settings_from(&path).unwrap();
+        assert_eq!(loaded.audio.volume, 0.4);
+        assert_eq!(report.newer, Some(9));
+        assert!(report.warnings()[0].contains("format 9"));
 
         std::fs::remove_dir_all(path.ancestors().nth(2).unwrap()).unwrap();
     }
@@ -413,18 +2417,315 @@ mod tests {
     #[test]
     fn out_of_range_values_are_sanitized_on_save() {
         let path = scratch_settings_path("sanitize-on-save");
-        let saved = AudioSettings {
-            volume: 7.0,
-            ..AudioSettings::default()
+        let saved = SettingsFile {
+            audio: AudioSettings {
+                volume: 7.0,
+                ..AudioSettings::default()
+            },
+            sample: None,
+            sample_replay_gain: false,
+            theme: ThemeSettings::default(),
+            output: OutputSettings::default(),
+            loops: LoopSettings {
+                rain_crossfade_seconds: 0.0,
+                sample_crossfade_seconds: f32::NAN,
+                ..LoopSettings::default()
+            },
+            output_devices: Vec::new(),
+            devices: BTreeMap::new(),
+            listeners: Listeners::default(),
+            bands: Vec::new(),
+            night_limit: None,
+            ambient: None,
+            location: None,
+            schedule: Vec::new(),
+            default_style: None,
+            start_volume: Some(f32::NAN),
+            restore_last_volume: false,
+            gallery: None,
         };
 
         save_settings_to(&path, &saved).unwrap();
-        let loaded = load_settings_from(&path).unwrap();
-        assert_eq!(loaded.volume, 1.0);
+        let (loaded, _) = load_settings_from(&path).unwrap();
+        assert_eq!(loaded.audio.volume, 1.0);
+        assert_eq!(loaded.start_volume, None);
+        assert_eq!(
+            loaded.loops,
+            LoopSettings {
+                rain_crossfade_seconds: MIN_LOOP_CROSSFADE_SECONDS,
+                sample_crossfade_seconds: DEFAULT_LOOP_CROSSFADE_SECONDS,
+                ..LoopSettings::default()
+            }
+        );
 
         std::fs::remove_dir_all(path.ancestors().nth(2).unwrap()).unwrap();
     }
 
+    #[test]
+    fn sample_path_sits_beside_the_audio_settings() {
+        let file: SettingsFile = toml::from_str(
+            r#"
+                volume = 0.3
+                sample = "/home/me/fan.wav"
+
+                [mix]
+                rain = 1.0
+            "#,
+        )
+        .unwrap();
+        assert_eq!(file.sample, Some(PathBuf::from("/home/me/fan.wav")));
+        assert_eq!(file.audio.volume, 0.3);
+        assert_eq!(file.audio.mix().rain, 1.0);
+
+        // Without a sample the key is left out, so the file reads as before.
+        let plain = toml::to_string_pretty(&SettingsFile::default()).unwrap();
+        assert!(!plain.contains("sample"));
+    }
+
+    #[test]
+    fn theme_table_picks_the_palette() {
+        let file: SettingsFile = toml::from_str(
+            r#"
+                volume = 0.3
+
+                [theme]
+                palette = "colorblind"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(file.theme.palette, Palette::Colorblind);
+        assert_eq!(file.audio.volume, 0.3);
+
+        // Files from before the table existed keep the original colors.
+        let file: SettingsFile = toml::from_str("volume = 0.3").unwrap();
+        assert_eq!(file.theme.palette, Palette::Color);
+        assert!(toml::from_str::<SettingsFile>("[theme]\npalette = \"sepia\"").is_err());
+    }
+
+    #[test]
+    fn theme_dims_overnight_and_while_a_timer_is_set() {
+        let time = |hour, minute| ClockTime::new(hour, minute).unwrap();
+        let file: SettingsFile = toml::from_str("[theme]\ndim_from = \"22:00\"").unwrap();
+        let theme = file.theme;
+        assert!(theme.dims(time(23, 0), false));
+        assert!(theme.dims(time(6, 59), false));
+        assert!(!theme.dims(time(7, 0), false));
+        assert!(!theme.dims(time(12, 0), true));
+
+        let theme = ThemeSettings {
+            dim_until: Some(time(5, 0)),
+            dim_with_timer: true,
+            ..theme
+        };
+        assert!(!theme.dims(time(6, 0), false));
+        assert!(theme.dims(time(12, 0), true));
+        assert!(!ThemeSettings::default().dims(time(23, 0), true));
+        assert!(toml::from_str::<SettingsFile>("[theme]\ndim_from = \"late\"").is_err());
+    }
+
+    #[test]
+    fn output_table_is_optional_and_written_only_when_set() {
+        let file: SettingsFile = toml::from_str("[output]\nsample_rate = 48000").unwrap();
+        assert_eq!(file.output.sample_rate, Some(48_000));
+        assert_eq!(file.output.buffer_size, None);
+
+        let written = toml::to_string(&SettingsFile::default()).unwrap();
+        assert!(!written.contains("[output]"), "{written}");
+        assert!(!written.contains("devices"), "{written}");
+
+        let mut file: SettingsFile = toml::from_str(
+            "[output]
+room_tone = 4.0",
+        )
+        .unwrap();
+        assert_eq!(file.output.room_tone(), 1.0);
+        file.output.set_room_tone(0.0);
+        assert!(!toml::to_string(&file).unwrap().contains("room_tone"));
+        file.output.set_room_tone(0.1);
+        let written = toml::to_string(&file).unwrap();
+        let read: SettingsFile = toml::from_str(&written).unwrap();
+        assert_eq!(read.output.room_tone(), 0.1, "{written}");
+    }
+
+    #[test]
+    fn listeners_keep_their_own_volume_and_eq_in_the_file() {
+        let mut settings = AudioSettings {
+            volume: 0.4,
+            ..AudioSettings::default()
+        };
+        settings.switch_listener(Listener::B);
+        settings.volume = 0.2;
+        settings.band_tilts[0] = 3.0;
+        settings.switch_listener(Listener::A);
+        assert_eq!((settings.volume, settings.band_tilts[0]), (0.4, 0.0));
+
+        let file = SettingsFile {
+            listeners: settings.listeners,
+            ..SettingsFile::default()
+        };
+        let written = toml::to_string(&file).unwrap();
+        assert!(written.contains("[listeners.b]"), "{written}");
+        let read: SettingsFile = toml::from_str(&written).unwrap();
+        assert_eq!(read.listeners, settings.listeners);
+        assert!(
+            !toml::to_string(&SettingsFile::default())
+                .unwrap()
+                .contains("listeners")
+        );
+
+        let read: SettingsFile = toml::from_str(
+            "[listeners.a]
+volume = 4.0
+frequency_bands = [0.25]",
+        )
+        .unwrap();
+        let a = read.sanitize().listeners.a.unwrap();
+        assert_eq!((a.volume, a.frequency_bands[0]), (1.0, 0.25));
+    }
+
+    #[test]
+    fn the_side_curve_and_band_widths_are_written_only_when_set() {
+        let written = toml::to_string(&AudioSettings::default()).unwrap();
+        for key in ["mid_side", "side_bands", "band_widths"] {
+            assert!(!written.contains(key), "{written}");
+        }
+
+        let mut settings = AudioSettings {
+            mid_side: true,
+            ..AudioSettings::default()
+        };
+        settings.side_bands[7] = 0.8;
+        settings.band_widths[0] = 0.0;
+        let written = toml::to_string(&settings).unwrap();
+        assert!(written.contains("mid_side = true"), "{written}");
+        let read: AudioSettings = toml::from_str(&written).unwrap();
+        assert_eq!(read, settings);
+        assert_eq!(read.side().frequency_bands, settings.side_bands);
+        assert_eq!(
+            AudioSettings {
+                mid_side: false,
+                ..read
+            }
+            .side(),
+            AudioSettings {
+                mid_side: false,
+                ..read
+            }
+        );
+
+        let read: AudioSettings =
+            toml::from_str("side_bands = [2.0]\nband_widths = [5.0, 0.5]").unwrap();
+        let read = read.sanitize();
+        assert_eq!(read.side_bands[..2], [1.0, 0.5]);
+        assert_eq!(read.band_widths[..3], [MAX_BAND_WIDTH, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn an_spl_reading_calibrates_the_estimate_and_the_cap() {
+        let uncalibrated = SplCalibration {
+            offset_db: None,
+            max_db: Some(60.0),
+        };
+        assert_eq!(uncalibrated.estimate_db(-30.0), None);
+        assert_eq!(uncalibrated.cap_rms(), None);
+
+        // The meter read 58 dB while the output ran at -32 dBFS RMS.
+        let spl = uncalibrated.from_reading(58.0, -32.0);
+        assert_eq!(spl.offset_db, Some(90.0));
+        assert_eq!(spl.estimate_db(-20.0), Some(70.0));
+        let cap = spl.cap_rms().unwrap();
+        assert!((20.0 * cap.log10() + 30.0).abs() < 1e-4, "{cap}");
+
+        let mut file: SettingsFile = toml::from_str(
+            "[output]
+spl_offset_db = 500.0
+max_spl_db = 10.0",
+        )
+        .unwrap();
+        assert_eq!(
+            file.output.spl(),
+            SplCalibration {
+                offset_db: Some(MAX_SPL_OFFSET_DB),
+                max_db: Some(MIN_MAX_SPL_DB),
+            }
+        );
+        file.output.set_spl(SplCalibration::default());
+        assert!(!toml::to_string(&file).unwrap().contains("spl"));
+    }
+
+    #[test]
+    fn the_limiter_reads_from_the_output_table_and_stays_out_of_it_by_default() {
+        let mut file = SettingsFile::default();
+        assert_eq!(file.output.limiter(), LimiterSettings::default());
+        file.output.set_limiter(LimiterSettings::default());
+        assert!(!toml::to_string(&file).unwrap().contains("limiter"));
+
+        let file: SettingsFile = toml::from_str(
+            "[output]
+limiter_threshold_db = 3.0
+limiter_release_ms = 1.0",
+        )
+        .unwrap();
+        let limiter = file.output.limiter();
+        assert_eq!(
+            limiter,
+            LimiterSettings {
+                threshold_db: 0.0,
+                release_ms: MIN_LIMITER_RELEASE_MS,
+            }
+        );
+        assert_eq!(limiter.threshold(), 1.0);
+        assert_eq!(limiter.release_seconds(), 0.02);
+    }
+
+    #[test]
+    fn high_pass_lives_in_the_output_table_and_devices_can_be_marked_small() {
+        let mut file: SettingsFile = toml::from_str(
+            "[output]\nhigh_pass_hz = 500.0\nhigh_pass_slope_db = 14\n\n[devices.\"JBL Go 3\"]\nsmall = true\n",
+        )
+        .unwrap();
+        let high_pass = file.output.high_pass();
+        assert_eq!(high_pass.hz, Some(MAX_HIGH_PASS_HZ));
+        assert_eq!(high_pass.slope_db, 12);
+        assert!(file.device("jbl go 3").small);
+        assert!(!file.device("JBL Go").small);
+
+        // The default slope is written only when the file already had one.
+        file.output = OutputSettings::default();
+        file.output.set_high_pass(HighPass {
+            hz: Some(40.0),
+            slope_db: DEFAULT_HIGH_PASS_SLOPE_DB,
+        });
+        assert_eq!(file.output.high_pass_slope_db, None);
+        let written = toml::to_string(&file).unwrap();
+        let read: SettingsFile = toml::from_str(&written).unwrap();
+        assert_eq!(read.output.high_pass().hz, Some(40.0));
+        assert!(read.device("JBL Go 3").small, "{written}");
+        file.device_mut("JBL GO 3").room_correction_db = Some(vec![-1.0; FREQUENCY_BANDS.len()]);
+        assert_eq!(file.devices.len(), 1);
+        assert!(!written.contains("crossfeed"), "{written}");
+
+        // Presets and the flattened audio keys never carry it.
+        assert!(!toml::to_string(&file.audio).unwrap().contains("high_pass"));
+    }
+
+    #[test]
+    fn an_output_device_list_saves_beside_the_devices_table() {
+        let mut file: SettingsFile =
+            toml::from_str("output_devices = [\"DAC\", \"JBL Go 3\"]\n").unwrap();
+        assert_eq!(file.output_devices, ["DAC", "JBL Go 3"]);
+        file.device_mut("JBL Go 3").small = true;
+        let written = toml::to_string(&file).unwrap();
+        let read: SettingsFile = toml::from_str(&written).unwrap();
+        assert_eq!(read.output_devices, file.output_devices, "{written}");
+        assert!(read.device("JBL Go 3").small);
+        assert!(
+            !toml::to_string(&SettingsFile::default())
+                .unwrap()
+                .contains("output_devices")
+        );
+    }
+
     #[test]
     fn legacy_files_without_a_mix_table_migrate_to_a_solo() {
         // Files written before source mixing existed carry only sound_style.
@@ -439,7 +2740,19 @@ mod tests {
             white: 0.0,
             pink: 0.2,
             brown: 0.6,
+            blue: 0.0,
+            violet: 0.0,
             rain: 0.2,
+
+            ocean: 0.0,
+
+            wind: 0.0,
+
+            fire: 0.0,
+
+            fan: 0.0,
+
+            sample: 0.0,
         };
         assert_eq!(mix.solo_style(), None);
         assert_eq!(mix.dominant(), SoundStyle::Brown);
@@ -450,7 +2763,19 @@ mod tests {
             white: 0.5,
             pink: 0.0,
             brown: 0.5,
+            blue: 0.0,
+            violet: 0.0,
             rain: 0.0,
+
+            ocean: 0.0,
+
+            wind: 0.0,
+
+            fire: 0.0,
+
+            fan: 0.0,
+
+            sample: 0.0,
         };
         assert_eq!(tie.dominant(), SoundStyle::White);
 
@@ -458,12 +2783,39 @@ mod tests {
             white: 0.0,
             pink: 0.0,
             brown: 0.0,
+            blue: 0.0,
+            violet: 0.0,
             rain: 0.0,
+
+            ocean: 0.0,
+
+            wind: 0.0,
+
+            fire: 0.0,
+
+            fan: 0.0,
+
+            sample: 0.0,
         };
         assert_eq!(silent.dominant(), SoundStyle::White);
         assert_eq!(silent.solo_style(), None);
     }
 
+    #[test]
+    fn a_sample_led_mix_is_saved_as_rain_for_older_binaries() {
+        let mut settings = AudioSettings::default();
+        settings.set_mix(SourceMix::solo(SoundStyle::Sample));
+        assert_eq!(settings.sound_style, SoundStyle::Rain);
+        assert_eq!(settings.mix().solo_style(), Some(SoundStyle::Sample));
+
+        let saved = toml::to_string_pretty(&settings).unwrap();
+        let loaded: AudioSettings = toml::from_str(&saved).unwrap();
+        assert_eq!(loaded.mix().sample, 1.0);
+        // Files from before the Sample source leave it silent.
+        let legacy: AudioSettings = toml::from_str("[mix]\nrain = 1.0").unwrap();
+        assert_eq!(legacy.mix().sample, 0.0);
+    }
+
     #[test]
     fn mix_describe_names_solos_and_lists_blends() {
         assert_eq!(SourceMix::solo(SoundStyle::Rain).describe(), "Rain");
@@ -471,7 +2823,19 @@ mod tests {
             white: 0.0,
             pink: 0.0,
             brown: 0.4,
+            blue: 0.0,
+            violet: 0.0,
             rain: 0.6,
+
+            ocean: 0.0,
+
+            wind: 0.0,
+
+            fire: 0.0,
+
+            fan: 0.0,
+
+            sample: 0.0,
         };
         assert_eq!(blend.describe(), "Mix: Brown Noise 40% + Rain 60%");
     }
@@ -483,7 +2847,19 @@ mod tests {
             white: f32::NAN,
             pink: 2.0,
             brown: -1.0,
+            blue: 0.0,
+            violet: 0.0,
             rain: 0.5,
+
+            ocean: 0.0,
+
+            wind: 0.0,
+
+            fire: 0.0,
+
+            fan: 0.0,
+
+            sample: 0.0,
         });
         let mix = settings.mix();
         assert_eq!(mix.white, 0.0);
@@ -492,6 +2868,78 @@ mod tests {
         assert_eq!(mix.rain, 0.5);
     }
 
+    #[test]
+    fn tone_settings_are_clamped_to_their_ranges() {
+        let settings: AudioSettings = toml::from_str(
+            r#"
+                [tone]
+                mode = "binaural"
+                carrier_hz = 5000.0
+                beat_hz = 0.1
+                level = 1.5
+            "#,
+        )
+        .unwrap();
+        let tone = settings.sanitize().tone;
+        assert_eq!(tone.mode, ToneMode::Binaural);
+        assert_eq!(tone.carrier_hz, MAX_CARRIER_HZ);
+        assert_eq!(tone.beat_hz, MIN_BEAT_HZ);
+        assert_eq!(tone.level, 1.0);
+        assert_eq!(tone.describe(), "Binaural 1000 Hz, 0.5 Hz beat");
+        assert_eq!(ToneSettings::default().describe(), "Off");
+
+        let broken = AudioSettings {
+            tone: ToneSettings {
+                carrier_hz: f32::NAN,
+                beat_hz: f32::INFINITY,
+                ..ToneSettings::default()
+            },
+            ..AudioSettings::default()
+        };
+        assert_eq!(broken.sanitize().tone, ToneSettings::default());
+    }
+
+    #[test]
+    fn drift_settings_are_clamped_and_only_play_while_on() {
+        let settings: AudioSettings = toml::from_str(
+            r#"
+                [drift]
+                enabled = true
+                depth = 2.0
+                period_seconds = 1.0
+            "#,
+        )
+        .unwrap();
+        let drift = settings.sanitize().drift;
+        assert_eq!(drift.depth, 1.0);
+        assert_eq!(drift.period_seconds, MIN_DRIFT_PERIOD_SECONDS);
+        assert_eq!(drift.active_depth(), 1.0);
+
+        let off = DriftSettings {
+            depth: 0.8,
+            period_seconds: f32::NAN,
+            ..DriftSettings::default()
+        }
+        .sanitize();
+        assert_eq!(off.active_depth(), 0.0);
+        assert_eq!(off.period_seconds, DriftSettings::default().period_seconds);
+        // Files from before drift play without it.
+        assert_eq!(
+            toml::from_str::<AudioSettings>("volume = 0.3")
+                .unwrap()
+                .drift,
+            DriftSettings::default()
+        );
+    }
+
+    #[test]
+    fn tone_mode_cycles_both_ways() {
+        for mode in ToneMode::value_variants() {
+            assert_eq!(mode.next().previous(), *mode);
+        }
+        assert_eq!(ToneMode::Isochronic.next(), ToneMode::Off);
+    }
+
     #[test]
     fn every_style_round_trips_through_toml() {
         for style in SoundStyle::ALL {
@@ -517,16 +2965,94 @@ mod tests {
         assert_eq!(style, SoundStyle::White);
     }
 
+    #[test]
+    fn band_layouts_are_read_and_checked() {
+        let file: SettingsFile = toml::from_str(
+            r#"
+                frequency_bands = [0.3, 0.7, 0.5]
+
+                [[bands]]
+                name = "Low"
+                min_hz = 20
+                max_hz = 500
+
+                [[bands]]
+                name = "Tinnitus notch"
+                min_hz = 5800
+                max_hz = 6200
+                q = 8.0
+
+                [[bands]]
+                name = "High"
+                min_hz = 500
+                max_hz = 20000
+            "#,
+        )
+        .unwrap();
+        assert_eq!(file.bands.len(), 3);
+        let notch = &file.bands[1];
+        assert_eq!(notch.label(), "Tinnitus notch");
+        assert_eq!(notch.q(), 8.0);
+        assert!((file.bands[0].q() - 0.5).abs() < 1e-6);
+        assert!((notch.center_frequency() - 5_996.7).abs() < 0.1);
+        assert_eq!(file.audio.frequency_bands[..4], [0.3, 0.7, 0.5, 0.5]);
+        check_band_layout(&file.bands).unwrap();
+        check_band_layout(&FREQUENCY_BANDS).unwrap();
+        assert_eq!(FREQUENCY_BANDS[0].label(), text("band.sub_bass"));
+
+        let band = |name: &str, min_freq, max_freq, q| FrequencyBand {
+            name: Cow::Owned(name.to_owned()),
+            label_key: None,
+            min_freq,
+            max_freq,
+            q,
+        };
+        for layout in [
+            vec![band("", 100.0, 200.0, None)],
+            vec![
+                band("Mid", 100.0, 200.0, None),
+                band("mid", 300.0, 400.0, None),
+            ],
+            vec![band("Backwards", 400.0, 200.0, None)],
+            vec![band("Infrasonic", 5.0, 20.0, None)],
+            vec![band("Sharp", 100.0, 200.0, Some(50.0))],
+            vec![band("Same", 100.0, 200.0, None); MAX_BANDS + 1],
+        ] {
+            assert!(check_band_layout(&layout).is_err(), "{layout:?}");
+        }
+    }
+
+    #[test]
+    fn tilt_bands_are_the_ones_two_octaves_wide() {
+        let wide: Vec<usize> = (0..FREQUENCY_BANDS.len())
+            .filter(|index| {
+                FREQUENCY_BANDS[*index].max_freq >= 4.0 * FREQUENCY_BANDS[*index].min_freq
+            })
+            .collect();
+        assert_eq!(wide, TILT_BANDS);
+    }
+
     #[test]
     fn invalid_numeric_values_are_sanitized() {
         let settings = AudioSettings {
             volume: f32::NAN,
-            frequency_bands: [2.0, -1.0, 0.5, 0.5, 0.5, 0.5, 0.5, f32::INFINITY],
+            frequency_bands: band_values(&[2.0, -1.0, 0.5, 0.5, 0.5, 0.5, 0.5, f32::INFINITY]),
+            band_tilts: [-20.0, f32::NAN],
+            ceiling_hz: Some(f32::NAN),
+            stereo_width: f32::NAN,
             ..AudioSettings::default()
         }
         .sanitize();
 
+        assert_eq!(settings.band_tilts, [-MAX_TILT_DB, 0.0]);
+        assert_eq!(settings.ceiling_hz, None);
+        let low = AudioSettings {
+            ceiling_hz: Some(10.0),
+            ..AudioSettings::default()
+        };
+        assert_eq!(low.sanitize().ceiling_hz, Some(MIN_CEILING_HZ));
         assert_eq!(settings.volume, 0.0);
+        assert_eq!(settings.stereo_width, DEFAULT_STEREO_WIDTH);
         assert_eq!(settings.frequency_bands[0], 1.0);
         assert_eq!(settings.frequency_bands[1], 0.0);
         assert_eq!(settings.frequency_bands[7], 0.5);
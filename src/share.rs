@@ -0,0 +1,408 @@
+//! Share codes: a sound's volume, sources, and EQ packed into a few groups
+//! of Crockford base32, such as `04S0-00B4-01RG` for plain white noise at
+//! half volume, short enough to read out or paste into a chat instead of
+//! passing a preset file around. Case, dashes, spaces, and the look-alikes
+//! I, L, and O are forgiven on the way back in, and a check byte at the end
+//! turns a mistyped character into an error rather than a different sound.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::settings::{
+    AudioSettings, DEFAULT_STEREO_WIDTH, MAX_BANDS, Notch, SoundStyle, SourceMix, TILT_BANDS, bands,
+};
+
+const VERSION: u8 = 1;
+// No I, L, O, or U, so a code read aloud has one spelling.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const GROUP_LENGTH: usize = 4;
+// Band sliders travel in half-percent steps, a twentieth of a dB or so.
+const SLIDER_STEPS: f32 = 200.0;
+
+// The optional parts after the sources, each present only when it differs
+// from a fresh install's.
+const HAS_BANDS: u8 = 1;
+const HAS_TILTS: u8 = 1 << 1;
+const HAS_CEILING: u8 = 1 << 2;
+const HAS_NOTCH: u8 = 1 << 3;
+const HAS_CONTOUR: u8 = 1 << 4;
+const HAS_WIDTH: u8 = 1 << 5;
+
+/// The shareable part of a sound, rounded to what a code carries.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShareCode {
+    volume: f32,
+    mix: SourceMix,
+    frequency_bands: [f32; MAX_BANDS],
+    band_tilts: [f32; TILT_BANDS.len()],
+    ceiling_hz: Option<f32>,
+    notch: Notch,
+    contour_phon: Option<f32>,
+    stereo_width: f32,
+}
+
+impl ShareCode {
+    /// The code for what `settings` plays.
+    pub fn of(settings: &AudioSettings) -> Self {
+        Self::from_bytes(&Self::bytes_of(settings)).expect("a share code reads back what it wrote")
+    }
+
+    /// Plays the code's volume, sources, and EQ; the timer, output options,
+    /// and everything else about the session stay as they are, as they do
+    /// for a preset.
+    pub fn apply(&self, settings: &mut AudioSettings) {
+        settings.volume = self.volume;
+        settings.set_mix(self.mix);
+        settings.frequency_bands = self.frequency_bands;
+        settings.band_tilts = self.band_tilts;
+        settings.ceiling_hz = self.ceiling_hz;
+        settings.notch = self.notch;
+        settings.listening_contour = self.contour_phon.is_some();
+        if let Some(phon) = self.contour_phon {
+            settings.listening_phon = phon;
+        }
+        settings.stereo_width = self.stereo_width;
+        *settings = settings.sanitize();
+    }
+
+    fn bytes_of(settings: &AudioSettings) -> Vec<u8> {
+        let mix = settings.mix();
+        let audible: Vec<(usize, SoundStyle)> = SoundStyle::ALL
+            .into_iter()
+            .enumerate()
+            .filter(|(_, style)| mix.level(*style) > 0.0)
+            .collect();
+        let mask = audible
+            .iter()
+            .fold(0_u16, |mask, (index, _)| mask | 1 << index);
+        let mut bytes = vec![VERSION, percent(settings.volume)];
+        bytes.extend(mask.to_be_bytes());
+        // A trace of a source still counts as one percent, so it stays in.
+        bytes.extend(
+            audible
+                .iter()
+                .map(|(_, style)| percent(mix.level(*style)).max(1)),
+        );
+
+        let band_count = bands().len();
+        let sliders = &settings.frequency_bands[..band_count];
+        let mut flags = 0;
+        let mut parts = Vec::new();
+        if sliders.iter().any(|value| *value != 0.5) {
+            flags |= HAS_BANDS;
+            parts.push(band_count as u8);
+            parts.extend(
+                sliders
+                    .iter()
+                    .map(|value| (value.clamp(0.0, 1.0) * SLIDER_STEPS).round() as u8),
+            );
+        }
+        if settings.band_tilts.iter().any(|db| *db != 0.0) {
+            flags |= HAS_TILTS;
+            parts.extend(
+                settings
+                    .band_tilts
+                    .iter()
+                    .map(|db| ((db * 2.0).round() as i8).to_be_bytes()[0]),
+            );
+        }
+        if let Some(hz) = settings.ceiling_hz {
+            flags |= HAS_CEILING;
+            parts.extend((hz.round() as u16).to_be_bytes());
+        }
+        if let Some(hz) = settings.notch.hz {
+            flags |= HAS_NOTCH;
+            parts.extend((hz.round() as u16).to_be_bytes());
+            parts.push((settings.notch.width_octaves * 10.0).round() as u8);
+        }
+        if let Some(phon) = settings.contour_phon() {
+            flags |= HAS_CONTOUR;
+            parts.push(phon.round() as u8);
+        }
+        if settings.stereo_width != DEFAULT_STEREO_WIDTH {
+            flags |= HAS_WIDTH;
+            parts.push(percent(settings.stereo_width));
+        }
+        bytes.push(flags);
+        bytes.extend(parts);
+        bytes.push(checksum(&bytes));
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut rest = bytes.iter().copied();
+        let mut next = || {
+            rest.next()
+                .ok_or_else(|| "the share code is cut short".to_owned())
+        };
+        let version = next()?;
+        if version > VERSION {
+            return Err("the share code is from a newer whitenoise".to_owned());
+        }
+        if version != VERSION {
+            return Err("that is not a share code".to_owned());
+        }
+        let volume = f32::from(next()?) / 100.0;
+        let mask = u16::from_be_bytes([next()?, next()?]);
+        // From silence, with each source in the mask added.
+        let mut mix = SourceMix::solo(SoundStyle::White);
+        mix.set_level(SoundStyle::White, 0.0);
+        for (index, style) in SoundStyle::ALL.into_iter().enumerate() {
+            if mask & 1 << index != 0 {
+                mix.set_level(style, f32::from(next()?) / 100.0);
+            }
+        }
+
+        let flags = next()?;
+        let mut frequency_bands = [0.5; MAX_BANDS];
+        if flags & HAS_BANDS != 0 {
+            let count = usize::from(next()?);
+            if count > MAX_BANDS {
+                return Err(format!(
+                    "the share code has {count} bands, more than {MAX_BANDS}"
+                ));
+            }
+            // Slider values are kept by position, as a preset's are under
+            // another band layout.
+            for value in &mut frequency_bands[..count] {
+                *value = f32::from(next()?) / SLIDER_STEPS;
+            }
+        }
+        let mut band_tilts = [0.0; TILT_BANDS.len()];
+        if flags & HAS_TILTS != 0 {
+            for db in &mut band_tilts {
+                *db = f32::from(i8::from_be_bytes([next()?])) / 2.0;
+            }
+        }
+        let ceiling_hz = if flags & HAS_CEILING != 0 {
+            Some(f32::from(u16::from_be_bytes([next()?, next()?])))
+        } else {
+            None
+        };
+        let notch = if flags & HAS_NOTCH != 0 {
+            Notch {
+                hz: Some(f32::from(u16::from_be_bytes([next()?, next()?]))),
+                width_octaves: f32::from(next()?) / 10.0,
+            }
+        } else {
+            Notch::default()
+        };
+        let contour_phon = if flags & HAS_CONTOUR != 0 {
+            Some(f32::from(next()?))
+        } else {
+            None
+        };
+        let stereo_width = if flags & HAS_WIDTH != 0 {
+            f32::from(next()?) / 100.0
+        } else {
+            DEFAULT_STEREO_WIDTH
+        };
+
+        let read = bytes.len() - rest.len();
+        if rest.next() != Some(checksum(&bytes[..read])) {
+            return Err("the share code has a typo; check it and try again".to_owned());
+        }
+        if rest.next().is_some() {
+            return Err("the share code runs on too long".to_owned());
+        }
+        Ok(Self {
+            volume,
+            mix,
+            frequency_bands,
+            band_tilts,
+            ceiling_hz,
+            notch,
+            contour_phon,
+            stereo_width,
+        })
+    }
+}
+
+impl fmt::Display for ShareCode {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut settings = AudioSettings::default();
+        self.apply(&mut settings);
+        let symbols = to_base32(&Self::bytes_of(&settings));
+        let groups: Vec<&str> = symbols
+            .as_bytes()
+            .chunks(GROUP_LENGTH)
+            .map(|group| std::str::from_utf8(group).expect("base32 is ASCII"))
+            .collect();
+        formatter.write_str(&groups.join("-"))
+    }
+}
+
+impl FromStr for ShareCode {
+    type Err = String;
+
+    fn from_str(code: &str) -> Result<Self, String> {
+        if code.trim().is_empty() {
+            return Err("the share code is empty".to_owned());
+        }
+        Self::from_bytes(&from_base32(code)?)
+    }
+}
+
+/// A level from 0 to 1 as a whole percentage.
+fn percent(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 100.0).round() as u8
+}
+
+/// CRC-8 with the polynomial x⁸ + x² + x + 1, which catches any one
+/// mistyped symbol.
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                crc << 1 ^ 0x07
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+fn to_base32(bytes: &[u8]) -> String {
+    let mut symbols = String::new();
+    let mut buffer = 0_u16;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer = buffer << 8 | u16::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            symbols.push(char::from(ALPHABET[usize::from(buffer >> bits & 31)]));
+        }
+        buffer &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        symbols.push(char::from(ALPHABET[usize::from(buffer << (5 - bits) & 31)]));
+    }
+    symbols
+}
+
+/// The bytes of a code, ignoring case, dashes, and spaces, and reading I
+/// and L as 1 and O as 0. The few bits left over at the end are padding,
+/// which the encoder always leaves at zero.
+fn from_base32(code: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut buffer = 0_u16;
+    let mut bits = 0;
+    for symbol in code.chars() {
+        if symbol == '-' || symbol.is_whitespace() {
+            continue;
+        }
+        let symbol = match symbol.to_ascii_uppercase() {
+            'O' => '0',
+            'I' | 'L' => '1',
+            symbol => symbol,
+        };
+        let value = ALPHABET
+            .iter()
+            .position(|known| char::from(*known) == symbol)
+            .ok_or_else(|| format!("'{symbol}' is not in a share code"))?;
+        buffer = buffer << 5 | value as u16;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+    if bits >= 5 {
+        return Err("the share code runs on too long".to_owned());
+    }
+    if buffer != 0 {
+        return Err("the share code has a typo; check it and try again".to_owned());
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shaped() -> AudioSettings {
+        let mut settings = AudioSettings {
+            volume: 0.35,
+            band_tilts: [-2.5, 1.0],
+            ceiling_hz: Some(8_000.0),
+            notch: Notch {
+                hz: Some(4_000.0),
+                width_octaves: 0.5,
+            },
+            listening_contour: true,
+            listening_phon: 60.0,
+            stereo_width: 1.0,
+            ..AudioSettings::default()
+        };
+        settings.frequency_bands[0] = 0.25;
+        settings.frequency_bands[3] = 0.75;
+        let mut mix = SourceMix::solo(SoundStyle::Rain);
+        mix.set_level(SoundStyle::Rain, 0.6);
+        mix.set_level(SoundStyle::Brown, 0.4);
+        settings.set_mix(mix);
+        settings
+    }
+
+    #[test]
+    fn a_code_carries_the_volume_sources_and_eq() {
+        let original = shaped();
+        let code = ShareCode::of(&original).to_string();
+        let mut settings = AudioSettings {
+            high_pass: crate::settings::HighPass {
+                hz: Some(40.0),
+                slope_db: 24,
+            },
+            ..AudioSettings::default()
+        };
+        code.parse::<ShareCode>().unwrap().apply(&mut settings);
+        assert_eq!(settings.volume, original.volume);
+        assert_eq!(settings.mix(), original.mix());
+        assert_eq!(settings.frequency_bands, original.frequency_bands);
+        assert_eq!(settings.band_tilts, original.band_tilts);
+        assert_eq!(settings.ceiling_hz, Some(8_000.0));
+        assert_eq!(settings.notch, original.notch);
+        assert_eq!(settings.contour_phon(), Some(60.0));
+        assert_eq!(settings.stereo_width, 1.0);
+        // The session's own options stay.
+        assert_eq!(settings.high_pass.hz, Some(40.0));
+        assert_eq!(ShareCode::of(&settings).to_string(), code);
+    }
+
+    #[test]
+    fn a_plain_sound_makes_a_short_forgiving_code() {
+        let white = AudioSettings {
+            volume: 0.5,
+            ..AudioSettings::default()
+        };
+        let code = ShareCode::of(&white).to_string();
+        assert_eq!(code, "04S0-00B4-01RG");
+        let sloppy = " 04s0 oob4 0lrg ";
+        assert_eq!(sloppy.parse::<ShareCode>(), Ok(ShareCode::of(&white)));
+    }
+
+    #[test]
+    fn mistyped_and_foreign_codes_are_refused() {
+        let code = ShareCode::of(&shaped()).to_string();
+        for (index, symbol) in code.char_indices().filter(|(_, symbol)| *symbol != '-') {
+            let replacement = if symbol == 'Z' { 'Y' } else { 'Z' };
+            let mut typo = code.clone();
+            typo.replace_range(index..=index, &replacement.to_string());
+            assert!(typo.parse::<ShareCode>().is_err(), "{typo} was accepted");
+        }
+        assert!(code[..code.len() - 3].parse::<ShareCode>().is_err());
+        assert!("".parse::<ShareCode>().is_err());
+        assert!("U2".parse::<ShareCode>().unwrap_err().contains("'U'"));
+        let mut newer = ShareCode::bytes_of(&shaped());
+        newer[0] = VERSION + 1;
+        let last = newer.len() - 1;
+        newer[last] = checksum(&newer[..last]);
+        assert!(
+            to_base32(&newer)
+                .parse::<ShareCode>()
+                .unwrap_err()
+                .contains("newer")
+        );
+    }
+}
@@ -0,0 +1,641 @@
+//! Output sinks: where the sound goes when it does not go to a sound card.
+//! A `VirtualDevice` stands in for one. Its thread asks the engine for a
+//! buffer at a time at the device's pace, through the same callback a real
+//! device's stream runs, and writes each buffer to an `OutputSink`. The
+//! whole program can then play, take commands, and run its timers where
+//! there is no audio hardware, and a test can read back what it played.
+//! A sound card's stream pulls buffers on its own clock instead, so it
+//! drives the same callback from `audio::build_output_stream` rather than
+//! taking them through a sink. `--output-to` picks a sink by `SinkTarget`:
+//! a WAV file, raw PCM on stdout or over TCP, a node of its own in the
+//! PipeWire or PulseAudio graph, or nowhere.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::audio::{AudioMonitor, DeviceCallback, FillOutput, StreamOptions};
+use crate::settings::SharedSettings;
+
+/// Somewhere to put rendered audio, one buffer of interleaved samples in
+/// [-1, 1] at a time.
+pub trait OutputSink: Send {
+    fn write(&mut self, samples: &[f32]) -> Result<()>;
+
+    /// Completes whatever the sink keeps, such as a file's header, once
+    /// the last buffer is written.
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A sink that drops everything, for playing with nowhere to play.
+#[derive(Debug, Default)]
+pub struct NullSink;
+
+impl OutputSink for NullSink {
+    fn write(&mut self, _samples: &[f32]) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A sink that keeps every sample in memory, where clones of it read them.
+#[derive(Debug, Default, Clone)]
+pub struct CaptureSink {
+    samples: Arc<Mutex<Vec<f32>>>,
+}
+
+impl CaptureSink {
+    /// Everything written so far.
+    pub fn samples(&self) -> Vec<f32> {
+        self.samples
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+}
+
+impl OutputSink for CaptureSink {
+    fn write(&mut self, samples: &[f32]) -> Result<()> {
+        self.samples
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .extend_from_slice(samples);
+        Ok(())
+    }
+}
+
+/// A sink that writes a 32-bit float WAV file, so nothing the engine
+/// produced is rounded away.
+pub struct WavSink {
+    writer: Option<hound::WavWriter<BufWriter<File>>>,
+}
+
+impl WavSink {
+    pub fn create(path: &Path, sample_rate: u32, channels: u16) -> Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+        Self::new(file, sample_rate, channels)
+    }
+
+    /// Writes into `file`, already created, from its start.
+    pub fn new(file: File, sample_rate: u32, channels: u16) -> Result<Self> {
+        let spec = hound::WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let writer = hound::WavWriter::new(BufWriter::new(file), spec)
+            .context("failed to start the WAV file")?;
+        Ok(Self {
+            writer: Some(writer),
+        })
+    }
+}
+
+impl OutputSink for WavSink {
+    fn write(&mut self, samples: &[f32]) -> Result<()> {
+        let writer = self
+            .writer
+            .as_mut()
+            .ok_or_else(|| anyhow!("the WAV file is already finished"))?;
+        for sample in samples {
+            writer.write_sample(*sample)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        match self.writer.take() {
+            Some(writer) => writer.finalize().context("failed to finish the WAV file"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A sink that writes interleaved signed 16-bit little-endian PCM with no
+/// header, the format `aplay -f S16_LE`, `pacat`, and Snapcast's pipe and
+/// TCP sources read by default.
+pub struct PcmSink<W: Write + Send> {
+    writer: W,
+    bytes: Vec<u8>,
+}
+
+impl<W: Write + Send> PcmSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            bytes: Vec::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl PcmSink<io::Stdout> {
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl PcmSink<TcpStream> {
+    /// Connects to a listener such as a Snapcast TCP source in server mode.
+    pub fn connect(address: &str) -> Result<Self> {
+        let stream = TcpStream::connect(address)
+            .with_context(|| format!("failed to connect to {address}"))?;
+        // Buffers are already a device's worth; waiting to fill a packet
+        // would only add latency.
+        stream.set_nodelay(true)?;
+        Ok(Self::new(stream))
+    }
+}
+
+impl<W: Write + Send> OutputSink for PcmSink<W> {
+    fn write(&mut self, samples: &[f32]) -> Result<()> {
+        self.bytes.clear();
+        for sample in samples {
+            let value = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)).round() as i16;
+            self.bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        self.writer.write_all(&self.bytes)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// The node `--output-to node` publishes when it is given no name.
+pub const DEFAULT_NODE_NAME: &str = "Whitenoise";
+
+/// A sink that publishes a virtual sink in the PipeWire or PulseAudio
+/// graph and plays into it through `pacat`. The sink's monitor and a
+/// source remapped from it, both named for the node, show up in OBS, call
+/// apps, and patchbays, so the sound can be mixed in on purpose rather
+/// than picked up by a microphone. Nothing reaches the speakers unless the
+/// user routes it there. Modules this sink loaded are unloaded when it
+/// finishes; a node left by another player is reused and left alone.
+pub struct NodeSink {
+    player: Child,
+    stdin: Option<ChildStdin>,
+    modules: Vec<String>,
+    bytes: Vec<u8>,
+}
+
+impl NodeSink {
+    pub fn publish(name: &str, sample_rate: u32, channels: u16) -> Result<Self> {
+        let mut modules = Vec::new();
+        if !lists_node(&pactl(&["list", "short", "sinks"])?, name) {
+            for arguments in node_modules(name) {
+                let arguments: Vec<&str> = arguments.iter().map(String::as_str).collect();
+                match pactl(&arguments) {
+                    Ok(index) => modules.push(index.trim().to_owned()),
+                    Err(error) => {
+                        unload(&modules);
+                        return Err(error);
+                    }
+                }
+            }
+        }
+        let player = Command::new("pacat")
+            .args([
+                "--playback",
+                "--raw",
+                "--format=float32le",
+                &format!("--rate={sample_rate}"),
+                &format!("--channels={channels}"),
+                &format!("--device={name}"),
+                "--client-name=whitenoise",
+                "--stream-name=Noise",
+                "--latency-msec=100",
+            ])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn();
+        let mut player = match player {
+            Ok(player) => player,
+            Err(error) => {
+                unload(&modules);
+                return Err(error).context("failed to start pacat");
+            }
+        };
+        Ok(Self {
+            stdin: player.stdin.take(),
+            player,
+            modules,
+            bytes: Vec::new(),
+        })
+    }
+
+    fn close(&mut self) -> Result<()> {
+        if self.stdin.take().is_some() {
+            self.player.wait().context("pacat did not exit")?;
+        }
+        unload(&std::mem::take(&mut self.modules));
+        Ok(())
+    }
+}
+
+impl OutputSink for NodeSink {
+    fn write(&mut self, samples: &[f32]) -> Result<()> {
+        let stdin = self
+            .stdin
+            .as_mut()
+            .ok_or_else(|| anyhow!("the node is already closed"))?;
+        self.bytes.clear();
+        for sample in samples {
+            self.bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        stdin
+            .write_all(&self.bytes)
+            .context("pacat stopped taking sound")
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.close()
+    }
+}
+
+impl Drop for NodeSink {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+/// The `pactl load-module` arguments for a null sink named `name` and a
+/// source that carries its monitor, for apps that hide monitors from
+/// their microphone lists.
+fn node_modules(name: &str) -> [Vec<String>; 2] {
+    let description = format!("device.description={name}");
+    [
+        vec![
+            "load-module".to_owned(),
+            "module-null-sink".to_owned(),
+            format!("sink_name={name}"),
+            format!("sink_properties={description}"),
+        ],
+        vec![
+            "load-module".to_owned(),
+            "module-remap-source".to_owned(),
+            format!("master={name}.monitor"),
+            format!("source_name={name}-source"),
+            format!("source_properties={description}"),
+        ],
+    ]
+}
+
+/// Whether `pactl list short sinks` output has a sink named `name`.
+fn lists_node(listing: &str, name: &str) -> bool {
+    listing
+        .lines()
+        .any(|line| line.split('\t').nth(1) == Some(name))
+}
+
+/// Runs `pactl` and returns what it printed.
+fn pactl(arguments: &[&str]) -> Result<String> {
+    let output = Command::new("pactl")
+        .args(arguments)
+        .stdin(Stdio::null())
+        .output()
+        .context("failed to run pactl; a node needs PulseAudio, or PipeWire with pipewire-pulse")?;
+    if !output.status.success() {
+        bail!(
+            "pactl {} failed: {}",
+            arguments.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Unloads `modules`, last loaded first.
+fn unload(modules: &[String]) {
+    for module in modules.iter().rev() {
+        if let Err(error) = pactl(&["unload-module", module]) {
+            eprintln!("warning: {error:#}");
+        }
+    }
+}
+
+/// Where `--output-to` sends the sound.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkTarget {
+    /// `null`: rendered and dropped, for a player that only needs to run.
+    Null,
+    /// `-`: raw PCM on stdout.
+    Stdout,
+    /// `tcp://HOST:PORT`: raw PCM to a listener.
+    Tcp(String),
+    /// A path ending in `.wav`: a 32-bit float WAV file.
+    File(PathBuf),
+    /// `node` or `node:NAME`: a virtual sink of its own in the desktop's
+    /// audio graph, named `Whitenoise` unless given a name.
+    Node(String),
+}
+
+impl SinkTarget {
+    pub fn open(&self, sample_rate: u32, channels: u16) -> Result<Box<dyn OutputSink>> {
+        Ok(match self {
+            Self::Null => Box::new(NullSink),
+            Self::Stdout => Box::new(PcmSink::stdout()),
+            Self::Tcp(address) => Box::new(PcmSink::connect(address)?),
+            Self::File(path) => Box::new(WavSink::create(path, sample_rate, channels)?),
+            Self::Node(name) => Box::new(NodeSink::publish(name, sample_rate, channels)?),
+        })
+    }
+}
+
+impl FromStr for SinkTarget {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("null") {
+            Ok(Self::Null)
+        } else if value == "-" {
+            Ok(Self::Stdout)
+        } else if let Some(address) = value.strip_prefix("tcp://") {
+            if address.rsplit_once(':').is_some_and(|(host, port)| {
+                !host.is_empty() && port.parse::<u16>().is_ok_and(|port| port > 0)
+            }) {
+                Ok(Self::Tcp(address.to_owned()))
+            } else {
+                Err(format!("{value} needs a host and port, as tcp://HOST:PORT"))
+            }
+        } else if value.eq_ignore_ascii_case("node") {
+            Ok(Self::Node(DEFAULT_NODE_NAME.to_owned()))
+        } else if let Some(name) = value.strip_prefix("node:") {
+            if !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                Ok(Self::Node(name.to_owned()))
+            } else {
+                Err(format!(
+                    "{value} needs a node name of letters, digits, - and _, as node:NAME"
+                ))
+            }
+        } else if Path::new(value)
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("wav"))
+        {
+            Ok(Self::File(PathBuf::from(value)))
+        } else {
+            Err(format!(
+                "{value} is not an output; use null, - for stdout, tcp://HOST:PORT, node, or a .wav path"
+            ))
+        }
+    }
+}
+
+impl fmt::Display for SinkTarget {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => write!(formatter, "nowhere"),
+            Self::Stdout => write!(formatter, "16-bit PCM to stdout"),
+            Self::Tcp(address) => write!(formatter, "16-bit PCM to tcp://{address}"),
+            Self::File(path) => write!(formatter, "{}", path.display()),
+            Self::Node(name) => write!(formatter, "the {name} node"),
+        }
+    }
+}
+
+/// The format a virtual device plays in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VirtualDevice {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Frames per callback, as a device's buffer size.
+    pub buffer_frames: usize,
+    /// Whether buffers come at the pace a device would ask for them, or as
+    /// fast as they render.
+    pub paced: bool,
+}
+
+impl Default for VirtualDevice {
+    fn default() -> Self {
+        Self {
+            sample_rate: 48_000,
+            channels: 2,
+            buffer_frames: 512,
+            paced: true,
+        }
+    }
+}
+
+/// A playing virtual device. Stopping it hands the sink back, unfinished,
+/// so a new stream can carry on writing to it.
+pub struct VirtualStream {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<Box<dyn OutputSink>>,
+}
+
+impl VirtualStream {
+    pub fn stop(self) -> Box<dyn OutputSink> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.thread
+            .join()
+            .unwrap_or_else(|_| Box::new(NullSink) as Box<dyn OutputSink>)
+    }
+}
+
+/// Starts `device` playing the shared settings into `sink`, as
+/// `audio::build_output_stream` starts a device's stream. A sink that
+/// fails to take a buffer is treated as a lost device: the monitor says so
+/// and the stream writes nothing more.
+pub fn play_virtual(
+    device: VirtualDevice,
+    mut sink: Box<dyn OutputSink>,
+    settings: Arc<SharedSettings>,
+    running: Arc<AtomicBool>,
+    monitor: Arc<AudioMonitor>,
+    options: StreamOptions,
+) -> Result<VirtualStream> {
+    let mut callback = DeviceCallback::new(
+        device.sample_rate as f32,
+        usize::from(device.channels.max(1)),
+        settings,
+        running,
+        Arc::clone(&monitor),
+        options,
+    )?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::clone(&stop);
+    let frames = device.buffer_frames.max(1);
+    let period = Duration::from_secs_f64(frames as f64 / f64::from(device.sample_rate));
+    let thread = std::thread::spawn(move || {
+        let mut buffer = vec![0.0_f32; frames * usize::from(device.channels.max(1))];
+        let mut due = Instant::now();
+        while !stopped.load(Ordering::Relaxed) {
+            callback.fill(&mut buffer);
+            if !monitor.output_lost() && sink.write(&buffer).is_err() {
+                monitor.set_output_lost(true);
+            }
+            if device.paced {
+                due += period;
+                let now = Instant::now();
+                if due > now {
+                    std::thread::sleep(due - now);
+                } else {
+                    // Fallen behind, as after a suspend: carry on from now
+                    // rather than rushing to catch up.
+                    due = now;
+                }
+            }
+        }
+        sink
+    });
+    Ok(VirtualStream { stop, thread })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::AudioSettings;
+
+    #[test]
+    fn a_virtual_device_plays_the_shared_settings_into_its_sink() {
+        let settings = Arc::new(SharedSettings::new(AudioSettings {
+            volume: 0.5,
+            ..AudioSettings::default()
+        }));
+        let running = Arc::new(AtomicBool::new(true));
+        let monitor = Arc::new(AudioMonitor::default());
+        let capture = CaptureSink::default();
+        let device = VirtualDevice {
+            buffer_frames: 256,
+            paced: false,
+            ..VirtualDevice::default()
+        };
+        let stream = play_virtual(
+            device,
+            Box::new(capture.clone()),
+            Arc::clone(&settings),
+            Arc::clone(&running),
+            Arc::clone(&monitor),
+            StreamOptions::default(),
+        )
+        .unwrap();
+        while capture.samples().len() < 48_000 {
+            std::thread::yield_now();
+        }
+        running.store(false, Ordering::Relaxed);
+        // One buffer may have been rendered before the store.
+        let stopped = capture.samples().len() + 512;
+        while capture.samples().len() < stopped + 2_048 {
+            std::thread::yield_now();
+        }
+        stream.stop();
+
+        let samples = capture.samples();
+        assert_eq!(samples.len() % 512, 0);
+        assert!(samples[..48_000].iter().any(|sample| sample.abs() > 0.01));
+        assert!(monitor.rms() > 0.0);
+        // Once playback stops, the buffers that follow are silent.
+        assert!(samples[stopped..].iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn a_wav_sink_keeps_every_sample() {
+        let path = std::env::temp_dir().join(format!("whitenoise-sink-{}.wav", std::process::id()));
+        let mut sink = WavSink::create(&path, 8_000, 2).unwrap();
+        sink.write(&[0.5, -0.25, 0.125, 0.0]).unwrap();
+        sink.finish().unwrap();
+        assert!(sink.write(&[0.0]).is_err());
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().channels, 2);
+        let samples: Vec<f32> = reader.into_samples().map(Result::unwrap).collect();
+        assert_eq!(samples, [0.5, -0.25, 0.125, 0.0]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_pcm_sink_writes_little_endian_16_bit_samples() {
+        let mut sink = PcmSink::new(Vec::new());
+        sink.write(&[0.0, 1.0, -1.0, 0.5]).unwrap();
+        sink.write(&[2.0, f32::NAN]).unwrap();
+        let bytes = sink.into_inner();
+        let samples: Vec<i16> = bytes
+            .chunks_exact(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        assert_eq!(samples, [0, 32_767, -32_767, 16_384, 32_767, 0]);
+    }
+
+    #[test]
+    fn a_tcp_sink_streams_to_its_listener() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let target: SinkTarget = format!("tcp://{address}").parse().unwrap();
+        let mut sink = target.open(48_000, 2).unwrap();
+        let (mut accepted, _) = listener.accept().unwrap();
+        sink.write(&[0.25, -0.25]).unwrap();
+        drop(sink);
+        let mut bytes = Vec::new();
+        io::Read::read_to_end(&mut accepted, &mut bytes).unwrap();
+        assert_eq!(bytes, [0x00, 0x20, 0x00, 0xe0]);
+    }
+
+    #[test]
+    fn sink_targets_parse_from_the_command_line() {
+        assert_eq!("null".parse(), Ok(SinkTarget::Null));
+        assert_eq!("-".parse(), Ok(SinkTarget::Stdout));
+        assert_eq!(
+            "tcp://snapserver.local:4953".parse(),
+            Ok(SinkTarget::Tcp("snapserver.local:4953".to_owned()))
+        );
+        assert_eq!(
+            "night.WAV".parse(),
+            Ok(SinkTarget::File(PathBuf::from("night.WAV")))
+        );
+        assert!("tcp://snapserver.local".parse::<SinkTarget>().is_err());
+        assert!("tcp://:4953".parse::<SinkTarget>().is_err());
+        assert!("night.mp3".parse::<SinkTarget>().is_err());
+        assert_eq!(
+            "node".parse(),
+            Ok(SinkTarget::Node(DEFAULT_NODE_NAME.to_owned()))
+        );
+        assert_eq!(
+            "node:bedroom-noise".parse(),
+            Ok(SinkTarget::Node("bedroom-noise".to_owned()))
+        );
+        assert!("node:".parse::<SinkTarget>().is_err());
+        assert!("node:two words".parse::<SinkTarget>().is_err());
+    }
+
+    #[test]
+    fn a_node_is_published_once_as_a_sink_and_a_source() {
+        let listing = "47\talsa_output.pci.analog-stereo\tPipeWire\ts32le 2ch 48000Hz\tRUNNING\n\
+                       52\tWhitenoise\tPipeWire\tfloat32le 2ch 48000Hz\tIDLE\n";
+        assert!(lists_node(listing, "Whitenoise"));
+        assert!(!lists_node(listing, "Whitenoise2"));
+        assert!(!lists_node("", "Whitenoise"));
+
+        let [sink, source] = node_modules("Whitenoise");
+        assert_eq!(
+            sink,
+            [
+                "load-module",
+                "module-null-sink",
+                "sink_name=Whitenoise",
+                "sink_properties=device.description=Whitenoise"
+            ]
+        );
+        assert_eq!(source[1], "module-remap-source");
+        assert_eq!(source[2], "master=Whitenoise.monitor");
+        assert_eq!(source[3], "source_name=Whitenoise-source");
+    }
+}
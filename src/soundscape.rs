@@ -0,0 +1,414 @@
+//! Synthesized soundscapes: ocean, wind, fire, and fan. Each is shaped noise
+//! with a slow, random movement of its own, so unlike the rain recording it
+//! never repeats. Like the stages in [`crate::dsp`], each is a state struct
+//! with a per-sample function; it draws its texture from its chain's noise
+//! generator and plays at the colored noises' long-run level, so the mix and
+//! the EQ treat it like any other source.
+//!
+//! The waves and gusts are timed by a `movement` RNG that both chains are
+//! given a copy of, so a wave breaks in both ears at once while the surf in
+//! each stays uncorrelated, and stereo width widens them like the noise.
+
+use std::f32::consts::{PI, TAU};
+
+use rand::Rng;
+use rand::prelude::{RngExt, SmallRng};
+
+use crate::dsp::{BrownNoise, PinkNoise};
+
+/// Each wave's length is drawn from this range, in seconds.
+const OCEAN_WAVE_SECONDS: (f32, f32) = (7.0, 13.0);
+// A wave builds over this share of its length, then breaks and washes out.
+const OCEAN_BUILD: f32 = 0.4;
+// The surf's low-pass opens from the trough's corner to the crest's.
+const OCEAN_CORNERS_HZ: (f32, f32) = (250.0, 3_000.0);
+const OCEAN_FLOOR: f32 = 0.2;
+
+// Gusts sweep the band between these centers, gliding to a new strength
+// every one to four seconds.
+const WIND_CENTERS_HZ: (f32, f32) = (200.0, 1_400.0);
+const WIND_Q: f32 = 4.0;
+const WIND_GUST_SECONDS: (f32, f32) = (1.0, 4.0);
+const WIND_GLIDE_SECONDS: f32 = 1.5;
+const WIND_FLOOR: f32 = 0.35;
+
+/// Crackles a second, on average; each is a burst of differentiated noise
+/// that dies away within a few milliseconds.
+const FIRE_CRACKLES_PER_SECOND: f32 = 7.0;
+const FIRE_CRACKLE_SECONDS: (f32, f32) = (0.001, 0.006);
+const FIRE_RUMBLE_HZ: f32 = 300.0;
+
+// The motor's hum and its second harmonic, narrow enough to sound tonal,
+// over the blades' air and their beat.
+const FAN_HUM_HZ: [f32; 2] = [120.0, 240.0];
+const FAN_HUM_Q: f32 = 60.0;
+const FAN_AIR_HZ: f32 = 2_500.0;
+const FAN_BLADE_HZ: f32 = 18.0;
+const FAN_BLADE_DEPTH: f32 = 0.08;
+
+fn white(rng: &mut impl Rng) -> f32 {
+    rng.random::<f32>() * 2.0 - 1.0
+}
+
+/// The coefficient of a one-pole low-pass at `corner_hz`.
+fn one_pole(corner_hz: f32, sample_rate: f32) -> f32 {
+    1.0 - (-TAU * corner_hz / sample_rate).exp()
+}
+
+/// A state-variable band-pass whose center can move every sample without
+/// clicking. Its peak gain is Q, so pink noise through it keeps one level
+/// wherever the center goes.
+#[derive(Debug, Default, Clone, Copy)]
+struct BandPass {
+    low: f32,
+    band: f32,
+}
+
+impl BandPass {
+    fn process(&mut self, input: f32, center_hz: f32, q: f32, sample_rate: f32) -> f32 {
+        // The structure stays stable well below a sixth of the sample rate.
+        let tuning = 2.0 * (PI * center_hz.min(sample_rate / 8.0) / sample_rate).sin();
+        let high = input - self.low - self.band / q;
+        self.band += tuning * high;
+        self.low += tuning * self.band;
+        self.band
+    }
+}
+
+/// Restarts a soundscape's own state from silence when a non-finite value
+/// gets in, the way the noise colors do.
+fn guard(sample: f32, resets: &mut u32, reset: impl FnOnce()) -> f32 {
+    if sample.is_finite() {
+        return sample;
+    }
+    reset();
+    *resets = resets.wrapping_add(1);
+    0.0
+}
+
+/// Waves: surf whose low-pass opens as each wave builds and breaks, over a
+/// brown-noise body, both swelling with the wave. Each wave takes seven to
+/// thirteen seconds.
+#[derive(Debug)]
+pub struct Ocean {
+    sample_rate: f32,
+    surf: PinkNoise,
+    body: BrownNoise,
+    surf_low: f32,
+    movement: SmallRng,
+    phase: f32,
+    step: f32,
+    gain: f32,
+    resets: u32,
+}
+
+impl Ocean {
+    pub fn new(sample_rate: f32, target_rms: f32, movement: SmallRng) -> Self {
+        Self {
+            sample_rate,
+            surf: PinkNoise::new(sample_rate, 1.0),
+            body: BrownNoise::new(sample_rate, 1.0),
+            surf_low: 0.0,
+            movement,
+            phase: 0.0,
+            step: 1.0 / (OCEAN_WAVE_SECONDS.0 * sample_rate),
+            // Measured: the wave envelope and the closing low-pass leave
+            // the unit-level mix at about 0.58 RMS.
+            gain: target_rms / 0.58,
+            resets: 0,
+        }
+    }
+
+    /// From the floor at the trough to 1 as the wave breaks.
+    fn swell(phase: f32) -> f32 {
+        let shape = if phase < OCEAN_BUILD {
+            let rise = phase / OCEAN_BUILD;
+            rise * rise * (3.0 - 2.0 * rise)
+        } else {
+            let wash = 1.0 - (phase - OCEAN_BUILD) / (1.0 - OCEAN_BUILD);
+            wash * wash
+        };
+        OCEAN_FLOOR + (1.0 - OCEAN_FLOOR) * shape
+    }
+
+    pub fn next_sample(&mut self, rng: &mut impl Rng) -> f32 {
+        let swell = Self::swell(self.phase);
+        self.phase += self.step;
+        if self.phase >= 1.0 {
+            self.phase -= 1.0;
+            let (shortest, longest) = OCEAN_WAVE_SECONDS;
+            self.step = 1.0 / (self.movement.random_range(shortest..longest) * self.sample_rate);
+        }
+        let (trough, crest) = OCEAN_CORNERS_HZ;
+        let corner = trough * (crest / trough).powf(swell);
+        let surf = self.surf.process(white(rng));
+        self.surf_low += one_pole(corner, self.sample_rate) * (surf - self.surf_low);
+        let body = self.body.process(white(rng));
+        let sample = (self.surf_low + 0.5 * body) * swell * self.gain;
+        guard(sample, &mut self.resets, || self.surf_low = 0.0)
+    }
+
+    /// How many times a non-finite value forced a state reset.
+    pub fn resets(&self) -> u32 {
+        self.resets
+            .wrapping_add(self.surf.resets())
+            .wrapping_add(self.body.resets())
+    }
+}
+
+/// Wind: pink noise through a resonant band whose center and level rise and
+/// fall with gusts of random strength and spacing.
+#[derive(Debug)]
+pub struct Wind {
+    sample_rate: f32,
+    source: PinkNoise,
+    band: BandPass,
+    movement: SmallRng,
+    gust: f32,
+    target: f32,
+    until_next: u32,
+    glide: f32,
+    gain: f32,
+    resets: u32,
+}
+
+impl Wind {
+    pub fn new(sample_rate: f32, target_rms: f32, movement: SmallRng) -> Self {
+        Self {
+            sample_rate,
+            source: PinkNoise::new(sample_rate, 1.0),
+            band: BandPass::default(),
+            movement,
+            gust: 0.5,
+            target: 0.5,
+            until_next: 0,
+            glide: one_pole(1.0 / (TAU * WIND_GLIDE_SECONDS), sample_rate),
+            // Measured: the band and the gusts leave unit pink at about
+            // 0.57 RMS.
+            gain: target_rms / 0.57,
+            resets: 0,
+        }
+    }
+
+    pub fn next_sample(&mut self, rng: &mut impl Rng) -> f32 {
+        if self.until_next == 0 {
+            self.target = self.movement.random::<f32>();
+            let (shortest, longest) = WIND_GUST_SECONDS;
+            self.until_next =
+                (self.movement.random_range(shortest..longest) * self.sample_rate) as u32;
+        }
+        self.until_next -= 1;
+        self.gust += self.glide * (self.target - self.gust);
+        let (low, high) = WIND_CENTERS_HZ;
+        let center = low * (high / low).powf(self.gust);
+        let band = self.band.process(
+            self.source.process(white(rng)),
+            center,
+            WIND_Q,
+            self.sample_rate,
+        );
+        let sample = band * (WIND_FLOOR + (1.0 - WIND_FLOOR) * self.gust) * self.gain;
+        guard(sample, &mut self.resets, || self.band = BandPass::default())
+    }
+
+    /// How many times a non-finite value forced a state reset.
+    pub fn resets(&self) -> u32 {
+        self.resets.wrapping_add(self.source.resets())
+    }
+}
+
+/// A campfire: a low rumble with crackles of random size at random times.
+#[derive(Debug)]
+pub struct Fire {
+    sample_rate: f32,
+    rumble: BrownNoise,
+    rumble_low: f32,
+    rumble_coefficient: f32,
+    crackle: f32,
+    decay: f32,
+    previous: f32,
+    gain: f32,
+    resets: u32,
+}
+
+impl Fire {
+    pub fn new(sample_rate: f32, target_rms: f32) -> Self {
+        Self {
+            sample_rate,
+            rumble: BrownNoise::new(sample_rate, 1.0),
+            rumble_low: 0.0,
+            rumble_coefficient: one_pole(FIRE_RUMBLE_HZ, sample_rate),
+            crackle: 0.0,
+            decay: 0.0,
+            previous: 0.0,
+            // Measured: the rumble and crackles together come to about
+            // 1.0 RMS before this gain.
+            gain: target_rms,
+            resets: 0,
+        }
+    }
+
+    pub fn next_sample(&mut self, rng: &mut impl Rng) -> f32 {
+        if rng.random::<f32>() < FIRE_CRACKLES_PER_SECOND / self.sample_rate {
+            // Mostly small ticks, now and then a loud pop.
+            self.crackle = 6.0 * rng.random::<f32>().powi(3);
+            let (shortest, longest) = FIRE_CRACKLE_SECONDS;
+            let length = rng.random_range(shortest..longest) * self.sample_rate;
+            self.decay = (-1.0 / length).exp();
+        }
+        let noise = white(rng);
+        let crackle = (noise - self.previous) * self.crackle;
+        self.previous = noise;
+        self.crackle *= self.decay;
+        self.rumble_low +=
+            self.rumble_coefficient * (self.rumble.process(white(rng)) - self.rumble_low);
+        let sample = (self.rumble_low + crackle) * self.gain;
+        guard(sample, &mut self.resets, || {
+            self.rumble_low = 0.0;
+            self.crackle = 0.0;
+        })
+    }
+
+    /// How many times a non-finite value forced a state reset.
+    pub fn resets(&self) -> u32 {
+        self.resets.wrapping_add(self.rumble.resets())
+    }
+}
+
+/// A box fan: the blades' broadband air, beating gently at the blade rate,
+/// with the motor's hum under it.
+#[derive(Debug)]
+pub struct Fan {
+    sample_rate: f32,
+    air: PinkNoise,
+    air_low: f32,
+    air_coefficient: f32,
+    hum: [BandPass; 2],
+    blade_phase: f32,
+    gain: f32,
+    resets: u32,
+}
+
+impl Fan {
+    pub fn new(sample_rate: f32, target_rms: f32) -> Self {
+        Self {
+            sample_rate,
+            air: PinkNoise::new(sample_rate, 1.0),
+            air_low: 0.0,
+            air_coefficient: one_pole(FAN_AIR_HZ, sample_rate),
+            hum: [BandPass::default(); 2],
+            blade_phase: 0.0,
+            // Measured: the air and the hum come to about 0.86 RMS before
+            // this gain.
+            gain: target_rms / 0.86,
+            resets: 0,
+        }
+    }
+
+    pub fn next_sample(&mut self, rng: &mut impl Rng) -> f32 {
+        self.air_low += self.air_coefficient * (self.air.process(white(rng)) - self.air_low);
+        self.blade_phase = (self.blade_phase + FAN_BLADE_HZ / self.sample_rate).fract();
+        let beat = 1.0 - FAN_BLADE_DEPTH * (0.5 + 0.5 * (TAU * self.blade_phase).cos());
+        // Narrow bands of noise rather than sines, so the hum in the two
+        // chains is uncorrelated and stereo width treats it like the air.
+        let mut hum = 0.0;
+        for ((band, center), level) in self.hum.iter_mut().zip(FAN_HUM_HZ).zip([0.06, 0.03]) {
+            hum += band.process(white(rng), center, FAN_HUM_Q, self.sample_rate) * level;
+        }
+        let sample = (self.air_low * beat + hum) * self.gain;
+        guard(sample, &mut self.resets, || {
+            self.hum = [BandPass::default(); 2]
+        })
+    }
+
+    /// How many times a non-finite value forced a state reset.
+    pub fn resets(&self) -> u32 {
+        self.resets.wrapping_add(self.air.resets())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    const SAMPLE_RATE: f32 = 22_050.0;
+
+    /// The RMS of each second over `seconds`, after ten seconds to settle.
+    fn levels(mut source: impl FnMut(&mut SmallRng) -> f32, seconds: usize) -> Vec<f32> {
+        let mut rng = SmallRng::seed_from_u64(9);
+        let second = SAMPLE_RATE as usize;
+        for _ in 0..10 * second {
+            source(&mut rng);
+        }
+        (0..seconds)
+            .map(|_| {
+                let sum: f32 = (0..second).map(|_| source(&mut rng).powi(2)).sum();
+                (sum / second as f32).sqrt()
+            })
+            .collect()
+    }
+
+    fn overall(levels: &[f32]) -> f32 {
+        (levels.iter().map(|level| level * level).sum::<f32>() / levels.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn each_soundscape_plays_at_the_colored_noise_level() {
+        let target = 0.16;
+        let mut ocean = Ocean::new(SAMPLE_RATE, target, SmallRng::seed_from_u64(2));
+        let mut wind = Wind::new(SAMPLE_RATE, target, SmallRng::seed_from_u64(3));
+        let mut fire = Fire::new(SAMPLE_RATE, target);
+        let mut fan = Fan::new(SAMPLE_RATE, target);
+        for (name, levels) in [
+            ("ocean", levels(|rng| ocean.next_sample(rng), 90)),
+            ("wind", levels(|rng| wind.next_sample(rng), 90)),
+            ("fire", levels(|rng| fire.next_sample(rng), 90)),
+            ("fan", levels(|rng| fan.next_sample(rng), 90)),
+        ] {
+            let rms = overall(&levels);
+            assert!((0.136..0.184).contains(&rms), "{name} RMS was {rms:.4}");
+        }
+        assert_eq!(
+            ocean.resets() + wind.resets() + fire.resets() + fan.resets(),
+            0
+        );
+    }
+
+    #[test]
+    fn waves_and_gusts_move_while_the_fan_holds_steady() {
+        let spread = |levels: Vec<f32>| {
+            let loudest = levels.iter().copied().fold(0.0, f32::max);
+            let quietest = levels.iter().copied().fold(f32::MAX, f32::min);
+            loudest / quietest
+        };
+        let mut ocean = Ocean::new(SAMPLE_RATE, 0.16, SmallRng::seed_from_u64(2));
+        let mut wind = Wind::new(SAMPLE_RATE, 0.16, SmallRng::seed_from_u64(3));
+        let mut fan = Fan::new(SAMPLE_RATE, 0.16);
+        assert!(spread(levels(|rng| ocean.next_sample(rng), 30)) > 3.0);
+        assert!(spread(levels(|rng| wind.next_sample(rng), 30)) > 1.5);
+        assert!(spread(levels(|rng| fan.next_sample(rng), 30)) < 1.2);
+
+        // Crackles stand far out of the fire's rumble.
+        let mut fire = Fire::new(SAMPLE_RATE, 0.16);
+        let mut rng = SmallRng::seed_from_u64(4);
+        let samples: Vec<f32> = (0..10 * SAMPLE_RATE as usize)
+            .map(|_| fire.next_sample(&mut rng))
+            .collect();
+        let peak = samples
+            .iter()
+            .copied()
+            .fold(0.0, |peak, s| s.abs().max(peak));
+        assert!(peak > 6.0 * 0.16, "the loudest crackle peaked at {peak:.3}");
+    }
+
+    #[test]
+    fn a_non_finite_value_restarts_from_silence() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut wind = Wind::new(SAMPLE_RATE, 0.16, SmallRng::seed_from_u64(3));
+        wind.band.band = f32::NAN;
+        assert_eq!(wind.next_sample(&mut rng), 0.0);
+        assert_eq!(wind.resets(), 1);
+        assert!(wind.next_sample(&mut rng).is_finite());
+    }
+}
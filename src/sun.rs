@@ -0,0 +1,204 @@
+//! Sunrise and sunset: the local times the sun crosses the horizon at a
+//! `[location]`, worked out on the machine from the date with the usual
+//! sunrise equation, so schedule entries can follow the seasons without a
+//! network lookup. Accurate to a minute or two away from the poles.
+
+use std::f64::consts::PI;
+use std::fmt;
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::night::ClockTime;
+
+// Julian dates: 2000-01-01 12:00 UTC, and 1970-01-01 00:00 UTC.
+const J2000: f64 = 2_451_545.0;
+const UNIX_EPOCH_JULIAN: f64 = 2_440_587.5;
+const SECONDS_PER_DAY: f64 = 86_400.0;
+// The sun's centre sits this far below the horizon at sunrise, for the
+// refraction of the air and the sun's own radius.
+const HORIZON_DEGREES: f64 = -0.833;
+const OBLIQUITY_DEGREES: f64 = 23.4397;
+
+/// The `[location]` table: where the player is, in decimal degrees, north
+/// and east positive.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl Location {
+    /// Rejects a latitude or longitude off the globe.
+    pub fn check(&self) -> Result<(), String> {
+        if !(-90.0..=90.0).contains(&self.latitude) {
+            return Err(format!(
+                "latitude {} is not between -90 and 90",
+                self.latitude
+            ));
+        }
+        if !(-180.0..=180.0).contains(&self.longitude) {
+            return Err(format!(
+                "longitude {} is not between -180 and 180",
+                self.longitude
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Sunrise or sunset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SunEvent {
+    Sunrise,
+    Sunset,
+}
+
+impl fmt::Display for SunEvent {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(match self {
+            Self::Sunrise => "sunrise",
+            Self::Sunset => "sunset",
+        })
+    }
+}
+
+/// One day's sunrise and sunset in local time. Either is None on a day the
+/// sun stays up or down throughout, and both are None without a location.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SunTimes {
+    pub sunrise: Option<ClockTime>,
+    pub sunset: Option<ClockTime>,
+}
+
+impl SunTimes {
+    /// Today's, at `location` if there is one.
+    pub fn today(location: Option<Location>) -> Self {
+        location.map_or_else(Self::default, |location| {
+            Self::on(location, Local::now().date_naive(), &Local)
+        })
+    }
+
+    /// The sunrise and sunset of `date` at `location`, read on the clock of
+    /// `zone`.
+    pub fn on<Zone: TimeZone>(location: Location, date: NaiveDate, zone: &Zone) -> Self {
+        let local = |instant: Option<DateTime<Utc>>| {
+            instant.and_then(|instant| {
+                // To the nearest minute.
+                let time = instant.with_timezone(zone) + chrono::Duration::seconds(30);
+                ClockTime::new(time.hour() as u16, time.minute() as u16)
+            })
+        };
+        let (sunrise, sunset) = events(location, date);
+        Self {
+            sunrise: local(sunrise),
+            sunset: local(sunset),
+        }
+    }
+
+    pub fn get(&self, event: SunEvent) -> Option<ClockTime> {
+        match event {
+            SunEvent::Sunrise => self.sunrise,
+            SunEvent::Sunset => self.sunset,
+        }
+    }
+}
+
+/// The sunrise equation: when the sun's centre crosses `HORIZON_DEGREES`
+/// on the solar day of `date` at `location`.
+fn events(location: Location, date: NaiveDate) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+    let days = date
+        .signed_duration_since(NaiveDate::from_ymd_opt(1970, 1, 1).expect("a valid date"))
+        .num_days() as f64;
+    let day = (days + UNIX_EPOCH_JULIAN - J2000 + 0.0008).ceil();
+    let mean_noon = day - location.longitude / 360.0;
+    let anomaly = (357.5291 + 0.985_600_28 * mean_noon)
+        .rem_euclid(360.0)
+        .to_radians();
+    let centre =
+        1.9148 * anomaly.sin() + 0.02 * (2.0 * anomaly).sin() + 0.0003 * (3.0 * anomaly).sin();
+    let ecliptic = (anomaly.to_degrees() + centre + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+    let transit = J2000 + mean_noon + 0.0053 * anomaly.sin() - 0.0069 * (2.0 * ecliptic).sin();
+    let declination = (ecliptic.sin() * OBLIQUITY_DEGREES.to_radians().sin()).asin();
+    let latitude = location.latitude.to_radians();
+    let cos_hour_angle = (HORIZON_DEGREES.to_radians().sin() - latitude.sin() * declination.sin())
+        / (latitude.cos() * declination.cos());
+    // Beyond ±1 the sun never reaches the horizon that day.
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return (None, None);
+    }
+    let half_day = cos_hour_angle.acos() / (2.0 * PI);
+    let instant = |julian: f64| {
+        let seconds = (julian - UNIX_EPOCH_JULIAN) * SECONDS_PER_DAY;
+        DateTime::from_timestamp(seconds.round() as i64, 0)
+    };
+    (instant(transit - half_day), instant(transit + half_day))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    fn time(text: &str) -> ClockTime {
+        ClockTime::try_from(text.to_owned()).unwrap()
+    }
+
+    fn close(found: Option<ClockTime>, expected: &str) -> bool {
+        found.is_some_and(|found| {
+            let apart = found.minutes_after(time(expected));
+            apart.min(24 * 60 - apart) <= 2
+        })
+    }
+
+    #[test]
+    fn sunrise_and_sunset_follow_the_seasons() {
+        let london = Location {
+            latitude: 51.5074,
+            longitude: -0.1278,
+        };
+        let utc = FixedOffset::east_opt(0).unwrap();
+        let summer = SunTimes::on(london, NaiveDate::from_ymd_opt(2024, 6, 21).unwrap(), &utc);
+        assert!(close(summer.sunrise, "03:43"), "{summer:?}");
+        assert!(close(summer.sunset, "20:21"), "{summer:?}");
+        let winter = SunTimes::on(london, NaiveDate::from_ymd_opt(2024, 12, 21).unwrap(), &utc);
+        assert!(close(winter.sunrise, "08:04"), "{winter:?}");
+        assert!(close(winter.sunset, "15:54"), "{winter:?}");
+
+        // Read on the local clock, west of Greenwich.
+        let new_york = Location {
+            latitude: 40.7128,
+            longitude: -74.006,
+        };
+        let eastern = FixedOffset::west_opt(4 * 3600).unwrap();
+        let autumn = SunTimes::on(
+            new_york,
+            NaiveDate::from_ymd_opt(2024, 9, 22).unwrap(),
+            &eastern,
+        );
+        assert!(close(autumn.sunrise, "06:45"), "{autumn:?}");
+        assert!(close(autumn.sunset, "18:54"), "{autumn:?}");
+        assert_eq!(autumn.get(SunEvent::Sunset), autumn.sunset);
+    }
+
+    #[test]
+    fn the_polar_summer_has_no_sunset() {
+        let tromso = Location {
+            latitude: 69.6492,
+            longitude: 18.9553,
+        };
+        let midsummer = SunTimes::on(tromso, NaiveDate::from_ymd_opt(2024, 6, 21).unwrap(), &Utc);
+        assert_eq!(midsummer, SunTimes::default());
+        assert!(
+            Location {
+                latitude: 95.0,
+                longitude: 0.0
+            }
+            .check()
+            .is_err()
+        );
+        assert!(tromso.check().is_ok());
+    }
+}
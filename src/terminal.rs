@@ -0,0 +1,163 @@
+//! Terminal capability detection. The full-screen UI needs cursor positioning
+//! and a screen clear; serial consoles and `TERM=dumb` have neither, so they
+//! get the plain status line instead of garbled output.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Offsets into a compiled entry's string table, in terminfo's standard
+/// capability order.
+const CLEAR_SCREEN: usize = 5;
+const CURSOR_ADDRESS: usize = 10;
+/// Legacy format with 16-bit numbers, and the ncurses 6.1 format with 32-bit
+/// numbers. Both keep 16-bit string offsets.
+const MAGIC_16_BIT: u16 = 0o432;
+const MAGIC_32_BIT: u16 = 0o1036;
+
+/// Why the full-screen UI cannot draw on this terminal, or None if it can.
+/// A terminal without an alternate screen (the Linux console, vt100) still
+/// qualifies; it only loses the shell's screen contents on exit.
+pub fn full_screen_unsupported() -> Option<String> {
+    // The Windows console is driven through its own API, not terminfo.
+    if cfg!(windows) {
+        return None;
+    }
+    let term = env::var("TERM").unwrap_or_default();
+    if term.is_empty() {
+        return Some("TERM is not set".to_owned());
+    }
+    if term == "dumb" {
+        return Some("TERM=dumb".to_owned());
+    }
+    // An entry that cannot be found or parsed is given the benefit of the
+    // doubt: minimal containers often ship TERM=xterm without a terminfo
+    // database, and the UI worked there before this check existed.
+    let entry = find_entry(&term)?;
+    let missing: Vec<&str> = [
+        (CURSOR_ADDRESS, "cursor positioning"),
+        (CLEAR_SCREEN, "clear"),
+    ]
+    .into_iter()
+    .filter(|(capability, _)| !has_string_capability(&entry, *capability).unwrap_or(true))
+    .map(|(_, name)| name)
+    .collect();
+    (!missing.is_empty()).then(|| format!("TERM={term} has no {}", missing.join(" or ")))
+}
+
+/// Searches the same directories as ncurses, in the same order.
+fn find_entry(term: &str) -> Option<Vec<u8>> {
+    let first = term.chars().next()?;
+    if term.contains(['/', '\\']) || term.starts_with('.') {
+        return None;
+    }
+    let mut directories: Vec<PathBuf> = Vec::new();
+    directories.extend(env::var_os("TERMINFO").map(PathBuf::from));
+    directories.extend(dirs::home_dir().map(|home| home.join(".terminfo")));
+    if let Some(list) = env::var_os("TERMINFO_DIRS") {
+        directories.extend(env::split_paths(&list).filter(|path| !path.as_os_str().is_empty()));
+    }
+    directories.extend(
+        [
+            "/etc/terminfo",
+            "/lib/terminfo",
+            "/usr/share/terminfo",
+            "/usr/lib/terminfo",
+        ]
+        .map(PathBuf::from),
+    );
+
+    // Linux groups entries by first letter; macOS by its hex code.
+    let subdirectories = [first.to_string(), format!("{:x}", u32::from(first))];
+    directories.iter().find_map(|directory| {
+        subdirectories
+            .iter()
+            .find_map(|subdirectory| fs::read(directory.join(subdirectory).join(term)).ok())
+    })
+}
+
+/// Whether a compiled terminfo entry defines string capability `index`, or
+/// None if the entry is malformed.
+fn has_string_capability(entry: &[u8], index: usize) -> Option<bool> {
+    let word = |position: usize| -> Option<u16> {
+        let bytes = entry.get(position * 2..position * 2 + 2)?;
+        Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+    };
+    let number_size = match word(0)? {
+        MAGIC_16_BIT => 2,
+        MAGIC_32_BIT => 4,
+        _ => return None,
+    };
+    let [names, booleans, numbers, strings] =
+        [1, 2, 3, 4].map(|position| word(position).map(usize::from));
+    let (names, booleans, numbers, strings) = (names?, booleans?, numbers?, strings?);
+    if index >= strings {
+        return Some(false);
+    }
+
+    // Numbers start on an even offset, so an odd names-plus-booleans run is
+    // followed by a padding byte.
+    let mut offset = 12 + names + booleans;
+    offset += offset % 2;
+    offset += numbers * number_size + index * 2;
+    let bytes = entry.get(offset..offset + 2)?;
+    // Negative offsets mark an absent (-1) or cancelled (-2) capability.
+    Some(i16::from_le_bytes([bytes[0], bytes[1]]) >= 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A minimal compiled entry with the given string capabilities defined.
+    fn entry(magic: u16, names: &str, defined: &[usize]) -> Vec<u8> {
+        let booleans = 3;
+        let numbers = 2;
+        let strings = 30;
+        let mut bytes = Vec::new();
+        for word in [magic, names.len() as u16 + 1, booleans, numbers, strings, 4] {
+            bytes.extend(word.to_le_bytes());
+        }
+        bytes.extend(names.as_bytes());
+        bytes.push(0);
+        bytes.extend([1, 0, 1]);
+        if bytes.len() % 2 == 1 {
+            bytes.push(0);
+        }
+        let number_size = if magic == MAGIC_32_BIT { 4 } else { 2 };
+        bytes.extend(vec![0xff; usize::from(numbers) * number_size]);
+        for index in 0..usize::from(strings) {
+            let offset: i16 = if defined.contains(&index) { 0 } else { -1 };
+            bytes.extend(offset.to_le_bytes());
+        }
+        bytes.extend(b"\x1b[\0");
+        bytes
+    }
+
+    #[test]
+    fn string_capabilities_are_read_from_both_formats() {
+        for magic in [MAGIC_16_BIT, MAGIC_32_BIT] {
+            // An odd and an even name length exercise the padding byte.
+            for names in ["vt100", "xterm|xterm256"] {
+                let capable = entry(magic, names, &[CLEAR_SCREEN, CURSOR_ADDRESS]);
+                assert_eq!(has_string_capability(&capable, CURSOR_ADDRESS), Some(true));
+                assert_eq!(has_string_capability(&capable, CLEAR_SCREEN), Some(true));
+                assert_eq!(has_string_capability(&capable, 28), Some(false));
+
+                let serial = entry(magic, names, &[]);
+                assert_eq!(has_string_capability(&serial, CURSOR_ADDRESS), Some(false));
+                assert_eq!(has_string_capability(&serial, 99), Some(false));
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_entries_are_reported_as_unreadable() {
+        let valid = entry(MAGIC_16_BIT, "vt100", &[CURSOR_ADDRESS]);
+        assert_eq!(has_string_capability(&[], CURSOR_ADDRESS), None);
+        assert_eq!(has_string_capability(&valid[..20], CURSOR_ADDRESS), None);
+        let mut wrong_magic = valid;
+        wrong_magic[0] = 0;
+        assert_eq!(has_string_capability(&wrong_magic, CURSOR_ADDRESS), None);
+    }
+}
@@ -0,0 +1,455 @@
+//! Sleep timer: stops playback after a set time, fading the volume to zero
+//! over the final stretch so sleep is never interrupted by an abrupt cut. A
+//! visual timer instead leaves the sound alone and only raises an alarm the
+//! UI shows. A running timer is also kept in a file beside the settings,
+//! so a player killed or crashed mid-timer and started again still fades
+//! out when the first one would have.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::clock;
+use crate::settings::{SharedSettings, config_path};
+
+pub const DEFAULT_FADE_OUT: Duration = Duration::from_secs(60);
+
+/// Timer lengths the interactive T key steps through, in minutes.
+const PRESET_MINUTES: [u64; 6] = [15, 30, 45, 60, 90, 120];
+const MAX_DURATION: Duration = Duration::from_secs(24 * 3600);
+// How often the running timer is compared with the one on disk.
+const PERSIST_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What happens when a timer runs out.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TimerAlarm {
+    /// Fade the volume out over the last stretch, then stop
+    #[default]
+    Fade,
+    /// Keep playing; flash the screen and send a desktop notification
+    Visual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SleepTimer {
+    deadline: Instant,
+    length: Duration,
+    fade: Duration,
+    alarm: TimerAlarm,
+}
+
+impl SleepTimer {
+    /// A fade longer than the timer itself starts fading immediately.
+    pub fn new(length: Duration, fade: Duration, now: Instant) -> Self {
+        Self {
+            deadline: now + length,
+            length,
+            fade: fade.min(length),
+            alarm: TimerAlarm::Fade,
+        }
+    }
+
+    /// A visual timer never fades, so its fade length is dropped.
+    pub fn with_alarm(self, alarm: TimerAlarm) -> Self {
+        Self {
+            alarm,
+            fade: match alarm {
+                TimerAlarm::Fade => self.fade,
+                TimerAlarm::Visual => Duration::ZERO,
+            },
+            ..self
+        }
+    }
+
+    pub fn alarm(&self) -> TimerAlarm {
+        self.alarm
+    }
+
+    /// The length the timer was set to, for the alarm message.
+    pub fn length(&self) -> Duration {
+        self.length
+    }
+
+    pub fn fade(&self) -> Duration {
+        self.fade
+    }
+
+    pub fn remaining(&self, now: Instant) -> Duration {
+        self.deadline.saturating_duration_since(now)
+    }
+
+    pub fn expired(&self, now: Instant) -> bool {
+        now >= self.deadline
+    }
+
+    pub fn fading(&self, now: Instant) -> bool {
+        !self.expired(now) && self.remaining(now) < self.fade
+    }
+
+    /// Output gain from 1 down to 0 across the fade. A straight amplitude
+    /// ramp sounds like it holds and then cuts off at the end; squaring it
+    /// spreads the drop in dB more evenly. A visual timer holds 1 throughout.
+    pub fn gain(&self, now: Instant) -> f32 {
+        if self.alarm == TimerAlarm::Visual {
+            return 1.0;
+        }
+        if self.expired(now) {
+            return 0.0;
+        }
+        let remaining = self.remaining(now);
+        if remaining >= self.fade {
+            return 1.0;
+        }
+        let progress = (remaining.as_secs_f64() / self.fade.as_secs_f64()) as f32;
+        progress * progress
+    }
+
+    /// The T key's next step: the shortest preset longer than the time left,
+    /// rounded up to a whole minute, or no timer once past the longest one.
+    /// Rounding keeps a second press from landing on the preset just set.
+    pub fn next_preset(current: Option<Self>, fade: Duration, now: Instant) -> Option<Self> {
+        let minutes_left = current.map_or(0, |timer| timer.remaining(now).as_secs().div_ceil(60));
+        PRESET_MINUTES
+            .into_iter()
+            .find(|minutes| *minutes > minutes_left)
+            .map(|minutes| Self::new(Duration::from_secs(minutes * 60), fade, now))
+    }
+}
+
+/// A running timer as `timer.toml` keeps it. The deadline is on the wall
+/// clock, since an `Instant` means nothing to the next process.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SavedTimer {
+    /// Seconds since the Unix epoch.
+    deadline: f64,
+    length_seconds: f64,
+    fade_seconds: f64,
+    alarm: TimerAlarm,
+}
+
+impl SleepTimer {
+    pub fn saved(&self, now: Instant, wall: SystemTime) -> SavedTimer {
+        let deadline = (wall + self.remaining(now))
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        SavedTimer {
+            deadline: deadline.as_secs_f64(),
+            length_seconds: self.length.as_secs_f64(),
+            fade_seconds: self.fade.as_secs_f64(),
+            alarm: self.alarm,
+        }
+    }
+}
+
+impl SavedTimer {
+    /// The timer with the time it has left, part way through its fade if it
+    /// was, or `None` once the deadline has passed. A deadline more than a
+    /// day off means the clock moved, and is dropped too.
+    pub fn restore(&self, now: Instant, wall: SystemTime) -> Option<SleepTimer> {
+        let wall = wall.duration_since(UNIX_EPOCH).ok()?.as_secs_f64();
+        let seconds = |value: f64| {
+            Duration::try_from_secs_f64(value)
+                .ok()
+                .filter(|duration| *duration <= MAX_DURATION)
+        };
+        let remaining = seconds(self.deadline - wall).filter(|left| !left.is_zero())?;
+        let length = seconds(self.length_seconds)?.max(remaining);
+        Some(SleepTimer {
+            deadline: now + remaining,
+            length,
+            fade: seconds(self.fade_seconds)?.min(length),
+            alarm: self.alarm,
+        })
+    }
+}
+
+/// `timer.toml` beside settings.toml.
+pub fn saved_timer_path() -> PathBuf {
+    config_path().with_file_name("timer.toml")
+}
+
+pub fn load_saved_timer(path: &Path) -> Result<Option<SavedTimer>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to read {}", path.display()));
+        }
+    };
+    toml::from_str(&content)
+        .map(Some)
+        .with_context(|| format!("failed to parse {}", path.display()))
+}
+
+/// Writes `timer`, or removes the file when there is none.
+pub fn save_timer(path: &Path, timer: Option<SavedTimer>) -> Result<()> {
+    let Some(timer) = timer else {
+        return match fs::remove_file(path) {
+            Err(error) if error.kind() != ErrorKind::NotFound => {
+                Err(error).with_context(|| format!("failed to remove {}", path.display()))
+            }
+            _ => Ok(()),
+        };
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    fs::write(path, toml::to_string(&timer)?)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Keeps `path` in step with the running timer: written when one is set or
+/// changed, removed when it is cleared or runs out, and removed when
+/// playback ends on purpose. Only a process that dies leaves it behind.
+/// Join the handle before exiting so that last removal happens.
+pub fn persist(
+    path: PathBuf,
+    settings: Arc<SharedSettings>,
+    running: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut written = None;
+        while running.load(Ordering::Relaxed) {
+            let now = clock::now();
+            let timer = settings
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .sleep_timer
+                .filter(|timer| !timer.expired(now));
+            if timer != written {
+                // Best effort: a warning here would scribble over the
+                // interface, and the timer itself runs either way.
+                let _ = save_timer(
+                    &path,
+                    timer.map(|timer| timer.saved(now, SystemTime::now())),
+                );
+                written = timer;
+            }
+            std::thread::sleep(PERSIST_INTERVAL);
+        }
+        let _ = save_timer(&path, None);
+    })
+}
+
+/// Parses `45m`, `1h30m`, `90s`, or a bare number of minutes, up to 24h.
+pub fn parse_duration(value: &str) -> std::result::Result<Duration, String> {
+    let invalid = || format!("'{value}' is not a duration up to 24h (examples: 45m, 1h30m, 90s)");
+    let value = value.trim();
+    let seconds = match value.parse::<u64>() {
+        Ok(minutes) => minutes.checked_mul(60),
+        Err(_) => parse_units(value),
+    };
+    seconds
+        .map(Duration::from_secs)
+        .filter(|duration| *duration <= MAX_DURATION)
+        .ok_or_else(invalid)
+}
+
+/// Seconds in a run of `<digits><h|m|s>` pairs, or None if malformed.
+fn parse_units(value: &str) -> Option<u64> {
+    let mut total: u64 = 0;
+    let mut digits = String::new();
+    for character in value.chars() {
+        if character.is_ascii_digit() {
+            digits.push(character);
+            continue;
+        }
+        let unit = match character.to_ascii_lowercase() {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        let amount: u64 = digits.parse().ok()?;
+        total = total.checked_add(amount.checked_mul(unit)?)?;
+        digits.clear();
+    }
+    (digits.is_empty() && !value.is_empty()).then_some(total)
+}
+
+/// `44:12`, or `1:05:09` past an hour.
+pub fn format_remaining(remaining: Duration) -> String {
+    let seconds = remaining.as_secs_f64().ceil() as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_parse_with_units_or_as_minutes() {
+        assert_eq!(parse_duration("45").unwrap(), Duration::from_secs(45 * 60));
+        assert_eq!(parse_duration("45m").unwrap(), Duration::from_secs(45 * 60));
+        assert_eq!(
+            parse_duration("1h30m").unwrap(),
+            Duration::from_secs(90 * 60)
+        );
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration(" 2H ").unwrap(), Duration::from_secs(7200));
+        assert_eq!(
+            parse_duration("24h").unwrap(),
+            Duration::from_secs(24 * 3600)
+        );
+        for invalid in [
+            "",
+            "m",
+            "45x",
+            "1h30",
+            "-5m",
+            "1.5h",
+            "25h",
+            "99999999999999999m",
+        ] {
+            assert!(parse_duration(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn a_saved_timer_resumes_with_the_time_it_had_left() {
+        let start = Instant::now();
+        let wall = UNIX_EPOCH + Duration::from_secs(1_800_000_000);
+        let timer = SleepTimer::new(Duration::from_secs(600), Duration::from_secs(60), start);
+        let saved = timer.saved(start + Duration::from_secs(100), wall);
+
+        // Restarted twenty seconds later, in a new process.
+        let later = Instant::now();
+        let restored = saved
+            .restore(later, wall + Duration::from_secs(20))
+            .unwrap();
+        assert_eq!(restored.remaining(later), Duration::from_secs(480));
+        assert_eq!(restored.length(), timer.length());
+        assert_eq!(restored.fade(), timer.fade());
+        // Restarted inside the fade, it picks the fade up where it was.
+        let fading = saved
+            .restore(later, wall + Duration::from_secs(470))
+            .unwrap();
+        assert!(fading.fading(later));
+        assert!((fading.gain(later) - timer.gain(start + Duration::from_secs(570))).abs() < 1e-6);
+
+        assert_eq!(saved.restore(later, wall + Duration::from_secs(500)), None);
+        assert_eq!(saved.restore(later, wall + Duration::from_secs(900)), None);
+        assert_eq!(
+            saved.restore(later, wall - Duration::from_secs(86_400)),
+            None
+        );
+
+        let visual = timer.with_alarm(TimerAlarm::Visual).saved(start, wall);
+        let restored = visual.restore(later, wall).unwrap();
+        assert_eq!(restored.alarm(), TimerAlarm::Visual);
+        assert_eq!(restored.fade(), Duration::ZERO);
+    }
+
+    #[test]
+    fn the_timer_file_is_written_read_and_removed() {
+        let mut path = std::env::temp_dir();
+        path.push(format!("whitenoise-timer-test-{}", std::process::id()));
+        path.push("timer.toml");
+        assert_eq!(load_saved_timer(&path).unwrap(), None);
+
+        let timer = SleepTimer::new(Duration::from_secs(2700), DEFAULT_FADE_OUT, Instant::now())
+            .saved(Instant::now(), SystemTime::now());
+        save_timer(&path, Some(timer)).unwrap();
+        assert_eq!(load_saved_timer(&path).unwrap(), Some(timer));
+        save_timer(&path, None).unwrap();
+        save_timer(&path, None).unwrap();
+        assert!(!path.exists());
+
+        std::fs::write(&path, "deadline = \"soon\"").unwrap();
+        assert!(load_saved_timer(&path).is_err());
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn gain_holds_then_fades_to_silence_at_the_deadline() {
+        let start = Instant::now();
+        let timer = SleepTimer::new(Duration::from_secs(600), Duration::from_secs(60), start);
+
+        assert_eq!(timer.gain(start), 1.0);
+        assert_eq!(timer.gain(start + Duration::from_secs(540)), 1.0);
+        assert!(!timer.fading(start + Duration::from_secs(540)));
+        assert!(timer.fading(start + Duration::from_secs(541)));
+        let halfway = timer.gain(start + Duration::from_secs(570));
+        assert!((halfway - 0.25).abs() < 1e-6);
+        assert_eq!(timer.gain(start + Duration::from_secs(600)), 0.0);
+        assert!(timer.expired(start + Duration::from_secs(600)));
+        assert_eq!(
+            timer.remaining(start + Duration::from_secs(700)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn fade_never_outlasts_the_timer() {
+        let start = Instant::now();
+        let timer = SleepTimer::new(Duration::from_secs(30), Duration::from_secs(60), start);
+        assert!(timer.fading(start + Duration::from_millis(1)));
+        assert!((timer.gain(start + Duration::from_secs(15)) - 0.25).abs() < 1e-6);
+
+        let instant = SleepTimer::new(Duration::ZERO, Duration::from_secs(60), start);
+        assert!(instant.expired(start));
+        assert_eq!(instant.gain(start), 0.0);
+    }
+
+    #[test]
+    fn visual_timers_expire_without_touching_the_gain() {
+        let start = Instant::now();
+        let timer = SleepTimer::new(Duration::from_secs(600), Duration::from_secs(60), start)
+            .with_alarm(TimerAlarm::Visual);
+        assert_eq!(timer.alarm(), TimerAlarm::Visual);
+        assert_eq!(timer.length(), Duration::from_secs(600));
+        assert_eq!(timer.fade(), Duration::ZERO);
+        for seconds in [0, 570, 599, 600, 700] {
+            let now = start + Duration::from_secs(seconds);
+            assert_eq!(timer.gain(now), 1.0, "{seconds} s");
+            assert!(!timer.fading(now));
+        }
+        assert!(timer.expired(start + Duration::from_secs(600)));
+
+        let fading = timer.with_alarm(TimerAlarm::Fade);
+        assert_eq!(fading.gain(start + Duration::from_secs(600)), 0.0);
+    }
+
+    #[test]
+    fn presets_step_up_from_the_time_left_then_turn_off() {
+        let start = Instant::now();
+        let fade = DEFAULT_FADE_OUT;
+        let first = SleepTimer::next_preset(None, fade, start).unwrap();
+        assert_eq!(first.remaining(start), Duration::from_secs(15 * 60));
+
+        let later = start + Duration::from_millis(10);
+        let second = SleepTimer::next_preset(Some(first), fade, later).unwrap();
+        assert_eq!(second.remaining(later), Duration::from_secs(30 * 60));
+
+        // A --timer 40m run steps to the next preset above what is left.
+        let custom = SleepTimer::new(Duration::from_secs(40 * 60), fade, start);
+        let stepped = SleepTimer::next_preset(Some(custom), fade, start).unwrap();
+        assert_eq!(stepped.remaining(start), Duration::from_secs(45 * 60));
+
+        let longest = SleepTimer::new(Duration::from_secs(120 * 60), fade, start);
+        assert_eq!(SleepTimer::next_preset(Some(longest), fade, start), None);
+    }
+
+    #[test]
+    fn remaining_time_is_formatted_as_a_clock() {
+        assert_eq!(format_remaining(Duration::from_secs(44 * 60 + 12)), "44:12");
+        assert_eq!(format_remaining(Duration::from_millis(1500)), "0:02");
+        assert_eq!(format_remaining(Duration::from_secs(3909)), "1:05:09");
+        assert_eq!(format_remaining(Duration::ZERO), "0:00");
+    }
+}
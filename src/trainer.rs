@@ -0,0 +1,291 @@
+//! The ear trainer, `:train colors` or `:train bands`: rounds that each
+//! play two sounds, A and B, with Space to switch between them, and ask what
+//! sets them apart. The colors drill plays two noise colors and asks which
+//! is, say, pink; the bands drill plays the EQ as set against the same EQ
+//! with one band raised, and asks which band moved. It runs on the A/B
+//! comparison's `Listener` sides, over the sound already playing, and puts
+//! the mix and EQ back when it ends.
+
+use rand::prelude::{IndexedRandom, RngExt, SmallRng};
+
+use crate::settings::{
+    AudioSettings, EQ_MAX_DB, Listener, MAX_BANDS, SoundStyle, SourceMix, bands, db_to_slider,
+    slider_to_db,
+};
+
+pub const DEFAULT_TRAINER_DB: f32 = 6.0;
+pub const MIN_TRAINER_DB: f32 = 1.0;
+pub const MAX_TRAINER_DB: f32 = 12.0;
+
+/// The noise colors the colors drill picks from.
+const COLORS: [SoundStyle; 5] = [
+    SoundStyle::White,
+    SoundStyle::Pink,
+    SoundStyle::Brown,
+    SoundStyle::Blue,
+    SoundStyle::Violet,
+];
+
+/// What the rounds ask about.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Drill {
+    Colors,
+    /// One band moves by this many dB, up unless that would pass the top
+    /// of the EQ.
+    Bands(f32),
+}
+
+/// One round's two sounds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Round {
+    /// Two colors, one on each side; the question names `asked`.
+    Colors {
+        a: SoundStyle,
+        b: SoundStyle,
+        asked: SoundStyle,
+    },
+    /// B moves `band` by `db`.
+    Bands { band: usize, db: f32 },
+}
+
+#[derive(Debug)]
+pub struct Trainer {
+    drill: Drill,
+    round: Round,
+    playing: Listener,
+    /// The band the bands drill's answer points at.
+    guess: usize,
+    /// Whether this round was answered, and rightly.
+    answered: Option<bool>,
+    right: u32,
+    rounds: u32,
+    streak: u32,
+    rng: SmallRng,
+    restore: (SourceMix, [f32; MAX_BANDS]),
+}
+
+impl Trainer {
+    /// Starts the first round over what `settings` plays, which is put back
+    /// by `finish`.
+    pub fn new(drill: Drill, settings: &mut AudioSettings, mut rng: SmallRng) -> Self {
+        let round = Self::deal(drill, settings, &mut rng);
+        let trainer = Self {
+            drill,
+            round,
+            playing: Listener::A,
+            guess: 0,
+            answered: None,
+            right: 0,
+            rounds: 0,
+            streak: 0,
+            rng,
+            restore: (settings.mix(), settings.frequency_bands),
+        };
+        trainer.play(settings);
+        trainer
+    }
+
+    fn deal(drill: Drill, settings: &AudioSettings, rng: &mut SmallRng) -> Round {
+        match drill {
+            Drill::Colors => {
+                let pair: Vec<SoundStyle> = COLORS.sample(rng, 2).copied().collect();
+                let asked = pair[usize::from(rng.random::<bool>())];
+                Round::Colors {
+                    a: pair[0],
+                    b: pair[1],
+                    asked,
+                }
+            }
+            Drill::Bands(db) => {
+                let band = rng.random_range(0..bands().len());
+                let level = slider_to_db(settings.frequency_bands[band]);
+                let db = if level + db > EQ_MAX_DB { -db } else { db };
+                Round::Bands { band, db }
+            }
+        }
+    }
+
+    pub fn drill(&self) -> Drill {
+        self.drill
+    }
+
+    pub fn round(&self) -> Round {
+        self.round
+    }
+
+    pub fn playing(&self) -> Listener {
+        self.playing
+    }
+
+    pub fn guess(&self) -> usize {
+        self.guess
+    }
+
+    pub fn answered(&self) -> Option<bool> {
+        self.answered
+    }
+
+    /// Rounds answered rightly, rounds answered, and the run of right ones.
+    pub fn score(&self) -> (u32, u32, u32) {
+        (self.right, self.rounds, self.streak)
+    }
+
+    /// Plays the side of the round that is playing: a color alone for the
+    /// colors drill, the EQ with or without the moved band for the bands
+    /// drill.
+    fn play(&self, settings: &mut AudioSettings) {
+        match (self.round, self.playing) {
+            (Round::Colors { a, .. }, Listener::A) => settings.set_mix(SourceMix::solo(a)),
+            (Round::Colors { b, .. }, Listener::B) => settings.set_mix(SourceMix::solo(b)),
+            (Round::Bands { .. }, Listener::A) => settings.frequency_bands = self.restore.1,
+            (Round::Bands { band, db }, Listener::B) => {
+                settings.frequency_bands = self.restore.1;
+                let level = slider_to_db(self.restore.1[band]);
+                settings.frequency_bands[band] = db_to_slider(level + db);
+            }
+        }
+    }
+
+    /// Plays the other side.
+    pub fn switch(&mut self, settings: &mut AudioSettings) {
+        self.playing = self.playing.other();
+        self.play(settings);
+    }
+
+    /// Moves the bands drill's answer by `step` bands, wrapping around.
+    pub fn point(&mut self, step: isize) {
+        let count = bands().len() as isize;
+        self.guess = (self.guess as isize + step).rem_euclid(count) as usize;
+    }
+
+    /// Answers the round: the colors drill with the side playing, the bands
+    /// drill with the band pointed at. A second answer changes nothing.
+    pub fn answer(&mut self) -> bool {
+        if let Some(right) = self.answered {
+            return right;
+        }
+        let right = match self.round {
+            Round::Colors { a, b, asked } => {
+                let picked = match self.playing {
+                    Listener::A => a,
+                    Listener::B => b,
+                };
+                picked == asked
+            }
+            Round::Bands { band, .. } => self.guess == band,
+        };
+        self.rounds += 1;
+        if right {
+            self.right += 1;
+            self.streak += 1;
+        } else {
+            self.streak = 0;
+        }
+        self.answered = Some(right);
+        right
+    }
+
+    /// Deals the next round and plays its A side.
+    pub fn next_round(&mut self, settings: &mut AudioSettings) {
+        settings.frequency_bands = self.restore.1;
+        self.round = Self::deal(self.drill, settings, &mut self.rng);
+        self.playing = Listener::A;
+        self.answered = None;
+        self.play(settings);
+    }
+
+    /// Puts back the mix and EQ the trainer started over.
+    pub fn finish(self, settings: &mut AudioSettings) {
+        settings.set_mix(self.restore.0);
+        settings.frequency_bands = self.restore.1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn a_colors_round_plays_each_side_and_scores_the_one_picked() {
+        let mut settings = AudioSettings {
+            frequency_bands: [0.6; MAX_BANDS],
+            ..AudioSettings::default()
+        };
+        settings.set_mix(SourceMix::solo(SoundStyle::Rain));
+        let mut trainer = Trainer::new(Drill::Colors, &mut settings, SmallRng::seed_from_u64(7));
+        let Round::Colors { a, b, asked } = trainer.round() else {
+            panic!("a colors drill deals colors");
+        };
+        assert_ne!(a, b);
+        assert!(asked == a || asked == b);
+        assert_eq!(settings.mix().solo_style(), Some(a));
+        trainer.switch(&mut settings);
+        assert_eq!(settings.mix().solo_style(), Some(b));
+
+        // Answering with the side that is not asked for is wrong, and the
+        // answer stands once given.
+        if asked == b {
+            trainer.switch(&mut settings);
+        }
+        assert!(!trainer.answer());
+        trainer.switch(&mut settings);
+        assert!(!trainer.answer());
+        assert_eq!(trainer.score(), (0, 1, 0));
+
+        trainer.next_round(&mut settings);
+        let Round::Colors { a, asked, .. } = trainer.round() else {
+            unreachable!();
+        };
+        if asked != a {
+            trainer.switch(&mut settings);
+        }
+        assert!(trainer.answer());
+        assert_eq!(trainer.score(), (1, 2, 1));
+
+        trainer.finish(&mut settings);
+        assert_eq!(settings.mix().solo_style(), Some(SoundStyle::Rain));
+        assert_eq!(settings.frequency_bands, [0.6; MAX_BANDS]);
+    }
+
+    #[test]
+    fn a_bands_round_moves_one_band_on_b_and_asks_which() {
+        // A band already at the top moves down instead.
+        let mut settings = AudioSettings {
+            frequency_bands: [1.0; MAX_BANDS],
+            ..AudioSettings::default()
+        };
+        let mut trainer = Trainer::new(
+            Drill::Bands(DEFAULT_TRAINER_DB),
+            &mut settings,
+            SmallRng::seed_from_u64(3),
+        );
+        let Round::Bands { band, db } = trainer.round() else {
+            panic!("a bands drill deals bands");
+        };
+        assert_eq!(db, -DEFAULT_TRAINER_DB);
+        assert_eq!(settings.frequency_bands, [1.0; MAX_BANDS]);
+        trainer.switch(&mut settings);
+        assert!((slider_to_db(settings.frequency_bands[band]) - (EQ_MAX_DB - 6.0)).abs() < 1e-3);
+        let moved = (0..MAX_BANDS)
+            .filter(|index| settings.frequency_bands[*index] != 1.0)
+            .count();
+        assert_eq!(moved, 1);
+
+        trainer.point(-1);
+        assert_eq!(trainer.guess(), bands().len() - 1);
+        while trainer.guess() != band {
+            trainer.point(1);
+        }
+        assert!(trainer.answer());
+        assert_eq!(trainer.answered(), Some(true));
+
+        trainer.next_round(&mut settings);
+        assert_eq!(trainer.playing(), Listener::A);
+        assert_eq!(trainer.answered(), None);
+        assert_eq!(settings.frequency_bands, [1.0; MAX_BANDS]);
+        trainer.finish(&mut settings);
+        assert_eq!(settings.frequency_bands, [1.0; MAX_BANDS]);
+    }
+}
@@ -1,42 +1,871 @@
+use std::collections::VecDeque;
 use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use crossterm::{
     cursor,
-    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+        KeyModifiers, KeyboardEnhancementFlags, MediaKeyCode, MouseButton, MouseEvent,
+        MouseEventKind, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
     execute, queue,
-    style::{Color, Print, ResetColor, SetForegroundColor},
+    style::{Color, ContentStyle, Print, PrintStyledContent, ResetColor, Stylize},
     terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use rand::SeedableRng;
+use rand::rngs::SmallRng;
 
-use crate::settings::{AudioSettings, FREQUENCY_BANDS, SourceMix, slider_to_db};
+use crate::audio::{
+    AudioMonitor, band_gain_db, band_rms_contribution_db, headroom_db, limiter_excess_db,
+    loop_crossfade_limits, stereo_correlation, tilt_edge_difference_db,
+};
+use crate::clock;
+use crate::command::{Command, CommandHistory, complete};
+use crate::exposure::Exposure;
+use crate::gallery;
+use crate::i18n::{text, text_with};
+use crate::night::ClockTime;
+use crate::notify;
+use crate::output::OutputHandle;
+use crate::preset::{import_preset, list_presets, presets_dir, validate_name};
+use crate::qr::{QUIET_ZONE, QrCode};
+use crate::record::{Recording, recordings_dir, timestamped_path};
+use crate::scene::{
+    LAYER_STEPS_HZ, Layer, LayerShape, MAX_LAYER_HZ, MIN_LAYER_HZ, Modulation, SWELL_STEPS_SECONDS,
+    list_scenes, load_scene, play_layers, playing_layers, save_layers, scenes_beside,
+};
+use crate::settings::{
+    AudioSettings, BandOverlay, CEILING_STEPS_HZ, DEFAULT_NOTCH_HZ, DriftSettings, EqSnapshot,
+    FREQUENCY_BANDS, HIGH_PASS_SLOPES_DB, HIGH_PASS_STEPS_HZ, HighPass, Listener, LoopSettings,
+    MAX_BAND_WIDTH, MAX_BANDS, MAX_BEAT_HZ, MAX_CARRIER_HZ, MAX_CEILING_HZ,
+    MAX_DRIFT_PERIOD_SECONDS, MAX_HIGH_PASS_HZ, MAX_NOTCH_HZ, MAX_NOTCH_OCTAVES, MAX_TILT_DB,
+    MIN_BEAT_HZ, MIN_CARRIER_HZ, MIN_CEILING_HZ, MIN_DRIFT_PERIOD_SECONDS, MIN_HIGH_PASS_HZ,
+    MIN_LOOP_CROSSFADE_SECONDS, MIN_NOTCH_HZ, MIN_NOTCH_OCTAVES, Notch, Palette,
+    SHUFFLE_MAX_SECONDS, SettingsGuard, SharedSettings, SoundStyle, SourceMix, TILT_BANDS,
+    ThemeSettings, ToneMode, ToneSettings, bands, format_hz, slider_to_db,
+};
+use crate::timer::{DEFAULT_FADE_OUT, SleepTimer, TimerAlarm, format_remaining};
+use crate::trainer::{Drill, Round, Trainer};
 
 const SLIDER_WIDTH: usize = 30;
+// The column of a slider's first cell: the marker, the 12-column name, and
+// the opening bracket come before it.
+const SLIDER_COLUMN: u16 = 18;
+// Below this the header, a few rows of a page, and the footer no longer
+// fit, and a slider's bar would be cut off.
+const MIN_COLUMNS: u16 = 50;
+const MIN_ROWS: u16 = 12;
+// Shorter terminals drop the level and limiter history before page rows.
+const HISTORY_MIN_ROWS: u16 = 20;
+const HISTORY_SECONDS: usize = 60;
+const SPARK_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const LEVEL_FLOOR_DB: f32 = -48.0;
+const REDUCTION_CEILING_DB: f32 = 6.0;
+// The limiter's gain comes back gradually, so the last sliver of its
+// recovery is not counted as limiting.
+const LIMITING_FROM_DB: f32 = 0.1;
+// The output meter's peak falls back like a PPM's, and the limiter light
+// stays on long enough to be seen after a single transient.
+const PEAK_FALL_DB_PER_SECOND: f32 = 20.0;
+const LIMIT_HOLD: Duration = Duration::from_secs(2);
+/// A visual alarm flashes this long, then stays lit until a key is pressed.
+const ALARM_FLASH: Duration = Duration::from_secs(10);
+const ALARM_FLASH_PERIOD_MS: u128 = 500;
+// Most terminals report key presses only, so a held B is recognized by its
+// auto-repeat. The first repeat can take up to about 660 ms to arrive, and
+// later ones come every 30 to 50 ms.
+const BYPASS_FIRST_HOLD: Duration = Duration::from_millis(700);
+const BYPASS_REPEAT_HOLD: Duration = Duration::from_millis(200);
+// Ctrl+Z takes back at most this many EQ changes.
+const UNDO_STEPS: usize = 100;
+
+/// Screens in Tab order. A new screen gets a variant here, a draw function,
+/// and a key handler; the header breadcrumb and Tab cycling pick it up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Page {
+    Main,
+    Mixer,
+    Eq,
+    Side,
+    Presets,
+    Scenes,
+    Help,
+}
+
+impl Page {
+    const ALL: [Self; 7] = [
+        Self::Main,
+        Self::Mixer,
+        Self::Eq,
+        Self::Side,
+        Self::Presets,
+        Self::Scenes,
+        Self::Help,
+    ];
+
+    fn title(self) -> &'static str {
+        text(match self {
+            Self::Main => "page.main",
+            Self::Mixer => "page.mixer",
+            Self::Eq => "page.eq",
+            Self::Side => "page.side",
+            Self::Presets => "page.presets",
+            Self::Scenes => "page.scenes",
+            Self::Help => "page.help",
+        })
+    }
+
+    fn index(self) -> usize {
+        Self::ALL.iter().position(|page| *page == self).unwrap_or(0)
+    }
+
+    fn next(self) -> Self {
+        Self::ALL[(self.index() + 1) % Self::ALL.len()]
+    }
+
+    fn previous(self) -> Self {
+        Self::ALL[(self.index() + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
+/// Mixer page rows: one fader per source, then the stereo width, crossfeed,
+/// the tone layer's controls, and the drift's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MixerRow {
+    Source(SoundStyle),
+    Width,
+    Crossfeed,
+    Tone,
+    Carrier,
+    Beat,
+    ToneLevel,
+    Drift,
+    DriftDepth,
+    DriftPeriod,
+}
+
+impl MixerRow {
+    const BELOW_SOURCES: [Self; 9] = [
+        Self::Width,
+        Self::Crossfeed,
+        Self::Tone,
+        Self::Carrier,
+        Self::Beat,
+        Self::ToneLevel,
+        Self::Drift,
+        Self::DriftDepth,
+        Self::DriftPeriod,
+    ];
+    const COUNT: usize = SoundStyle::ALL.len() + Self::BELOW_SOURCES.len();
+
+    fn at(index: usize) -> Self {
+        match SoundStyle::ALL.get(index) {
+            Some(style) => Self::Source(*style),
+            None => {
+                Self::BELOW_SOURCES
+                    [(index - SoundStyle::ALL.len()).min(Self::BELOW_SOURCES.len() - 1)]
+            }
+        }
+    }
+}
+
+/// Advanced EQ page rows: a tilt for each wide band, then the ceiling, the
+/// high-pass, the notch, the two loops' crossfades, sample shuffling, and
+/// a stereo width for each band.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EqRow {
+    Tilt(usize),
+    Ceiling,
+    HighPass,
+    Slope,
+    Notch,
+    NotchWidth,
+    RainFade,
+    RainShuffle,
+    SampleFade,
+    SampleShuffle,
+    Width(usize),
+}
+
+impl EqRow {
+    const BELOW_TILTS: [Self; 9] = [
+        Self::Ceiling,
+        Self::HighPass,
+        Self::Slope,
+        Self::Notch,
+        Self::NotchWidth,
+        Self::RainFade,
+        Self::RainShuffle,
+        Self::SampleFade,
+        Self::SampleShuffle,
+    ];
+
+    /// The rows there are with the band layout in use.
+    fn count() -> usize {
+        TILT_BANDS.len() + Self::BELOW_TILTS.len() + bands().len()
+    }
+
+    fn at(index: usize) -> Self {
+        let below = Self::BELOW_TILTS.len();
+        match index.checked_sub(TILT_BANDS.len()) {
+            None => Self::Tilt(index),
+            Some(offset) if offset < below => Self::BELOW_TILTS[offset],
+            Some(offset) => Self::Width((offset - below).min(bands().len().saturating_sub(1))),
+        }
+    }
+}
+
+/// Where each part of the screen goes at the terminal's size. The footer keeps
+/// to the bottom rows and a page gets the rest, scrolling its rows to keep
+/// the selection in view when they do not all fit. 80 by 24 is the layout
+/// the pages were designed at: the meter on row 19 and the prompt on row 23.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Layout {
+    columns: u16,
+    rows: u16,
+}
+
+impl Layout {
+    const STANDARD: Self = Self {
+        columns: 80,
+        rows: 24,
+    };
+
+    fn current() -> Self {
+        terminal::size().map_or(Self::STANDARD, |(columns, rows)| Self { columns, rows })
+    }
+
+    fn fits(self) -> bool {
+        self.columns >= MIN_COLUMNS && self.rows >= MIN_ROWS
+    }
+
+    /// The command line, or the last command's result.
+    fn prompt_row(self) -> u16 {
+        self.rows.saturating_sub(1)
+    }
+
+    /// The first of the level and limiter history's two rows, if they fit.
+    fn history_row(self) -> Option<u16> {
+        (self.rows >= HISTORY_MIN_ROWS).then(|| self.rows - 3)
+    }
+
+    fn timer_row(self) -> u16 {
+        self.history_row()
+            .unwrap_or(self.prompt_row())
+            .saturating_sub(1)
+    }
+
+    fn meter_row(self) -> u16 {
+        self.timer_row().saturating_sub(1)
+    }
+
+    /// The last row a page draws on, just above the output meter.
+    fn last_row(self) -> u16 {
+        self.meter_row().saturating_sub(1)
+    }
+
+    /// How many rows a list starting at `top` shows while leaving `below`
+    /// rows under it for a readout or its position.
+    fn rows_from(self, top: u16, below: u16) -> usize {
+        usize::from((self.last_row() + 1).saturating_sub(top + below)).max(1)
+    }
+
+    /// Rows for a page's sliders, which start on row `top` with the readout
+    /// under them.
+    fn slider_rows(self, top: u16) -> usize {
+        self.rows_from(top, 1)
+    }
+
+    /// Rows for the preset, scene, and device lists, which leave room for a
+    /// position line.
+    fn list_rows(self) -> usize {
+        self.rows_from(5, 2)
+    }
+
+    /// Help lines shown at once, above the line saying which they are.
+    fn help_rows(self) -> usize {
+        self.rows_from(2, 1).min(HELP_LINES.len())
+    }
+}
+
+/// The first item a list shows so that `selected` is in view, the way the
+/// preset list has always scrolled: the selection rides the bottom row once
+/// it is past the first screen.
+fn first_visible(selected: usize, visible: usize) -> usize {
+    (selected + 1).saturating_sub(visible)
+}
+
+/// The row a list starting at `top` draws its item `index` on, or None while
+/// scrolled out of view.
+fn visible_row(index: usize, selected: usize, visible: usize, top: u16) -> Option<u16> {
+    let offset = index.checked_sub(first_visible(selected, visible))?;
+    (offset < visible).then(|| top + offset as u16)
+}
+
+/// The item of a `count` long list starting at `top` under a click on `row`,
+/// scrolled as it was drawn.
+fn item_at(row: u16, top: u16, count: usize, selected: usize, visible: usize) -> Option<usize> {
+    let offset = usize::from(row.checked_sub(top)?);
+    let index = first_visible(selected, visible) + offset;
+    (offset < visible && index < count).then_some(index)
+}
+
+/// The parts of the screen a redraw compares with what it last wrote, so an
+/// unchanged part is not written again and nothing flickers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Region {
+    /// The header and the page, or the whole screen for the alarm, exam
+    /// mode, and a terminal too small to draw in.
+    Page,
+    Meter,
+    Timer,
+    History,
+    Prompt,
+}
+
+impl Region {
+    const COUNT: usize = 5;
+}
+
+/// Blanks `rows` and leaves the cursor at the first, so a region is drawn
+/// over whatever it showed before without clearing the whole screen.
+fn clear_rows(rows: std::ops::Range<u16>) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    for row in rows.clone() {
+        queue!(out, cursor::MoveTo(0, row), Clear(ClearType::CurrentLine))?;
+    }
+    queue!(out, cursor::MoveTo(0, rows.start))?;
+    Ok(out)
+}
+
+/// Text styles and bar glyphs for a palette. Every state the UI shows is
+/// also carried by something other than hue: the `>` marker and brackets
+/// for selection, the glyphs for slider fill, and the row label for the
+/// level and limiter sparklines.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Theme {
+    heading: ContentStyle,
+    /// The selected row and the current page tab.
+    selected: ContentStyle,
+    /// Readouts, warnings, and command results.
+    notice: ContentStyle,
+    text: ContentStyle,
+    dim: ContentStyle,
+    fill: ContentStyle,
+    empty: ContentStyle,
+    level: ContentStyle,
+    limiter: ContentStyle,
+    /// The lit phase of a visual alarm, filling the whole screen.
+    alert: ContentStyle,
+    fill_glyph: char,
+    empty_glyph: char,
+}
+
+fn darker(color: Color) -> Color {
+    match color {
+        Color::White => Color::Grey,
+        Color::Grey => Color::DarkGrey,
+        Color::Cyan => Color::DarkCyan,
+        Color::Yellow => Color::DarkYellow,
+        Color::Green => Color::DarkGreen,
+        Color::Red => Color::DarkRed,
+        Color::Blue => Color::DarkBlue,
+        Color::Magenta => Color::DarkMagenta,
+        other => other,
+    }
+}
+
+impl Theme {
+    fn new(palette: Palette) -> Self {
+        let plain = ContentStyle::new();
+        match palette {
+            Palette::Color => Self {
+                heading: plain.with(Color::Cyan),
+                selected: plain.with(Color::Yellow),
+                notice: plain.with(Color::Yellow),
+                text: plain.with(Color::White),
+                dim: plain.with(Color::DarkGrey),
+                fill: plain.with(Color::Green),
+                empty: plain.with(Color::DarkGrey),
+                level: plain.with(Color::Green),
+                limiter: plain.with(Color::Red),
+                alert: plain.with(Color::Yellow).reverse(),
+                fill_glyph: '#',
+                empty_glyph: '-',
+            },
+            Palette::Colorblind => Self {
+                heading: plain.with(Color::Cyan),
+                selected: plain.with(Color::Yellow).bold(),
+                notice: plain.with(Color::Yellow),
+                text: plain.with(Color::White),
+                dim: plain.with(Color::DarkGrey),
+                fill: plain.with(Color::Cyan).bold(),
+                empty: plain.with(Color::DarkGrey),
+                level: plain.with(Color::Cyan),
+                limiter: plain.with(Color::Yellow),
+                alert: plain.with(Color::Yellow).reverse().bold(),
+                fill_glyph: '█',
+                empty_glyph: '░',
+            },
+            Palette::Monochrome => Self {
+                heading: plain.bold(),
+                selected: plain.reverse(),
+                notice: plain.bold(),
+                text: plain,
+                dim: plain.dim(),
+                fill: plain.bold(),
+                empty: plain.dim(),
+                level: plain,
+                limiter: plain,
+                alert: plain.reverse(),
+                fill_glyph: '█',
+                empty_glyph: '░',
+            },
+        }
+    }
+
+    /// The same palette for a dark room: each color becomes its darker
+    /// shade, so the bars glow dark green instead of bright, and styles
+    /// without a color turn faint.
+    fn dimmed(&self) -> Self {
+        let dim = |style: ContentStyle| match style.foreground_color {
+            Some(color) => style.with(darker(color)),
+            None => style.dim(),
+        };
+        Self {
+            heading: dim(self.heading),
+            selected: dim(self.selected),
+            notice: dim(self.notice),
+            text: dim(self.text),
+            dim: dim(self.dim),
+            fill: dim(self.fill),
+            empty: dim(self.empty),
+            level: dim(self.level),
+            limiter: dim(self.limiter),
+            alert: dim(self.alert),
+            ..*self
+        }
+    }
+
+    /// A bar `width` cells wide with `filled` cells drawn as fill.
+    fn bar(&self, stdout: &mut impl Write, filled: usize, width: usize) -> Result<()> {
+        let filled = filled.min(width);
+        queue!(
+            stdout,
+            PrintStyledContent(self.fill.apply(self.fill_glyph.to_string().repeat(filled))),
+            PrintStyledContent(
+                self.empty
+                    .apply(self.empty_glyph.to_string().repeat(width - filled))
+            )
+        )?;
+        Ok(())
+    }
+}
+
+/// The output devices the D key lists, and the one under the cursor.
+struct DevicePicker {
+    names: Vec<String>,
+    selected: usize,
+}
+
+/// Scene editor columns, in Tab order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SceneField {
+    Source,
+    Level,
+    HighPass,
+    LowPass,
+    Swell,
+    Period,
+}
+
+impl SceneField {
+    const ALL: [Self; 6] = [
+        Self::Source,
+        Self::Level,
+        Self::HighPass,
+        Self::LowPass,
+        Self::Swell,
+        Self::Period,
+    ];
+
+    fn heading(self) -> &'static str {
+        text(match self {
+            Self::Source => "scene_editor.source",
+            Self::Level => "scene_editor.level",
+            Self::HighPass => "scene_editor.high_pass",
+            Self::LowPass => "scene_editor.low_pass",
+            Self::Swell => "scene_editor.swell",
+            Self::Period => "scene_editor.period",
+        })
+    }
+
+    fn hint(self) -> &'static str {
+        text(match self {
+            Self::Source => "scene_editor.source_hint",
+            Self::Level => "scene_editor.level_hint",
+            Self::HighPass => "scene_editor.high_pass_hint",
+            Self::LowPass => "scene_editor.low_pass_hint",
+            Self::Swell => "scene_editor.swell_hint",
+            Self::Period => "scene_editor.period_hint",
+        })
+    }
+
+    /// Column widths, wide enough for the longest heading or value.
+    fn width(self) -> usize {
+        match self {
+            Self::Source => 13,
+            _ => 10,
+        }
+    }
+}
+
+/// A scene open for editing over the Scenes page. Its layers play as they
+/// change; the mix and shaping they replaced come back if it is abandoned.
+struct SceneEditor {
+    name: String,
+    // A new scene's name is typed first, before any layer keys work.
+    naming: bool,
+    layers: Vec<Layer>,
+    row: usize,
+    field: usize,
+    changed: bool,
+    pending_discard: bool,
+    restore: (SourceMix, [LayerShape; SoundStyle::ALL.len()]),
+}
+
+/// The EQ that C swaps out, and its own undo history, which comes back with
+/// it.
+struct Compare {
+    playing: Listener,
+    other: EqSnapshot,
+    other_undo: Vec<EqSnapshot>,
+}
 
 pub struct InteractiveUi {
-    settings: Arc<Mutex<AudioSettings>>,
+    settings: Arc<SharedSettings>,
+    page: Page,
     selected: usize,
+    mixer_selected: usize,
+    eq_selected: usize,
+    // The Side EQ page: the mode on row 0, then the bands.
+    side_selected: usize,
+    // Levels to restore on unmute. Kept only in the UI so a muted source
+    // persists as silent and a restart never resurrects a stale level.
+    muted_levels: [Option<f32>; SoundStyle::ALL.len()],
+    solo_restore: Option<SourceMix>,
+    // Exam mode shows only a large volume bar and accepts only volume and
+    // quit keys, for shared screens and for lending the machine to a child.
+    exam_mode: bool,
+    // A kiosk shows the Main page for a shared space and takes no key but
+    // Ctrl+Q, so what plays can be seen there but not changed.
+    kiosk: bool,
+    // Without a loaded loop the Sample layer is silent, so S skips it and
+    // its mixer readout says how to load one.
+    sample_loaded: bool,
+    // The longest crossfade each loop takes, a third of its length.
+    loop_limits: LoopSettings,
+    // Fade length and alarm for timers set with the T key or the timer
+    // command.
+    fade_out: Duration,
+    timer_alarm: TimerAlarm,
+    // A visual timer that ran out: when it did and its length. Any key
+    // dismisses it. The notifier is swappable so tests stay off the desktop.
+    alarm: Option<(Instant, Duration)>,
+    drawn_alarm_lit: bool,
+    notifier: fn(&str, &str) -> Result<()>,
+    // While B is held: when the hold lapses unless another repeat arrives.
+    bypass_until: Option<Instant>,
+    // The EQ as of the last key, and the EQ before each change since, the
+    // latest last, for Ctrl+Z. Changes from a command or the control socket
+    // count as well as keys.
+    eq_seen: EqSnapshot,
+    undo: Vec<EqSnapshot>,
+    // The A/B comparison, from the first press of C.
+    compare: Option<Compare>,
+    // The recording Ctrl+R or --record started, and the folder Ctrl+R
+    // names new ones in.
+    recording: Option<Recording>,
+    recordings_dir: PathBuf,
+    // The `:` command line while it is open, and the result of the last
+    // command (or the completion candidates) until the next key.
+    prompt: Option<String>,
+    prompt_note: Option<String>,
+    // The digits typed after Enter on the main page, for an exact value of
+    // the selected slider.
+    value_entry: Option<String>,
+    // True from a click on a Main page bar until the button is let go, so
+    // dragging keeps moving that slider wherever the row under it is.
+    dragging: bool,
+    commands: CommandHistory,
+    // Saved presets as of the last visit to the Presets page or preset
+    // command; D asks for a second press before deleting the selection.
+    presets_dir: PathBuf,
+    presets: Vec<String>,
+    preset_selected: usize,
+    pending_delete: bool,
+    // The preset gallery's address from settings.toml, its listing while
+    // the Presets page shows it in place of the saved presets, and the
+    // request still out, if any.
+    gallery_url: Option<String>,
+    gallery: Option<Vec<gallery::Entry>>,
+    gallery_selected: usize,
+    gallery_request: Option<Receiver<gallery::Reply>>,
+    // Set while the note offers to trim a volume that keeps the limiter
+    // working; Y as the next key takes the offer.
+    pending_trim: bool,
+    // Scenes, from the `scenes/` folder beside the presets, as of the last
+    // visit to their page, and the scene being edited.
+    scenes: Vec<String>,
+    scene_selected: usize,
+    scene_editor: Option<SceneEditor>,
+    // The ear trainer from `:train`, over whatever page was open.
+    trainer: Option<Trainer>,
+    help_scroll: usize,
+    // The output keeper, and the device list D opens over the page.
+    output: Option<OutputHandle>,
+    device_picker: Option<DevicePicker>,
+    // The web remote's address, when --listen serves one, and whether U
+    // has its QR code up over the page.
+    remote_url: Option<String>,
+    showing_remote: bool,
     running: Arc<AtomicBool>,
+    monitor: Arc<AudioMonitor>,
+    drawn_resets: u32,
+    drawn_restarts: u32,
+    drawn_silences: u32,
+    drawn_refused_buffer: u32,
+    drawn_output_lost: bool,
+    // The noise dose, and whether the night's projection was over a full
+    // dose when last drawn.
+    exposure: Arc<Exposure>,
+    drawn_exposure_over: bool,
+    // The terminal's size as of the last draw or resize, and what each
+    // region of the screen was last drawn with.
+    layout: Layout,
+    painted: [Vec<u8>; Region::COUNT],
+    history: LevelHistory,
+    meter: OutputMeter,
+    theme: Theme,
+    // The `[theme]` table, and whether its dimming is in force now.
+    theme_settings: ThemeSettings,
+    dimmed: bool,
 }
 
 impl InteractiveUi {
-    pub fn new(settings: Arc<Mutex<AudioSettings>>, running: Arc<AtomicBool>) -> Self {
+    pub fn new(
+        settings: Arc<SharedSettings>,
+        running: Arc<AtomicBool>,
+        monitor: Arc<AudioMonitor>,
+    ) -> Self {
+        let eq_seen = EqSnapshot::of(
+            &settings
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        );
         Self {
             settings,
+            page: Page::Main,
             selected: 0,
+            mixer_selected: 0,
+            eq_selected: 0,
+            side_selected: 0,
+            muted_levels: [None; SoundStyle::ALL.len()],
+            solo_restore: None,
+            exam_mode: false,
+            kiosk: false,
+            sample_loaded: false,
+            loop_limits: loop_crossfade_limits(None).unwrap_or_default(),
+            fade_out: DEFAULT_FADE_OUT,
+            timer_alarm: TimerAlarm::Fade,
+            alarm: None,
+            drawn_alarm_lit: false,
+            notifier: notify::send,
+            bypass_until: None,
+            eq_seen,
+            undo: Vec::new(),
+            compare: None,
+            recording: None,
+            recordings_dir: recordings_dir(),
+            prompt: None,
+            prompt_note: None,
+            value_entry: None,
+            dragging: false,
+            commands: CommandHistory::default(),
+            presets_dir: presets_dir(),
+            presets: Vec::new(),
+            preset_selected: 0,
+            gallery_url: None,
+            gallery: None,
+            gallery_selected: 0,
+            gallery_request: None,
+            pending_delete: false,
+            pending_trim: false,
+            scenes: Vec::new(),
+            scene_selected: 0,
+            scene_editor: None,
+            trainer: None,
+            help_scroll: 0,
+            output: None,
+            device_picker: None,
+            remote_url: None,
+            showing_remote: false,
             running,
+            monitor,
+            drawn_resets: 0,
+            drawn_restarts: 0,
+            drawn_silences: 0,
+            drawn_refused_buffer: 0,
+            drawn_output_lost: false,
+            exposure: Arc::default(),
+            drawn_exposure_over: false,
+            layout: Layout::STANDARD,
+            painted: Default::default(),
+            history: LevelHistory::default(),
+            meter: OutputMeter::default(),
+            theme: Theme::new(Palette::default()),
+            theme_settings: ThemeSettings::default(),
+            dimmed: false,
         }
     }
 
+    /// Shows `note` on the command line until the first key, such as a
+    /// warning about settings.toml from before the screen was cleared.
+    pub fn with_note(mut self, note: String) -> Self {
+        self.prompt_note = Some(note);
+        self
+    }
+
+    pub fn with_theme(mut self, settings: ThemeSettings) -> Self {
+        self.theme = Theme::new(settings.palette);
+        self.theme_settings = settings;
+        self.dimmed = false;
+        self
+    }
+
+    /// Locks this UI to the distraction-free volume-only screen.
+    pub fn with_exam_mode(mut self) -> Self {
+        self.exam_mode = true;
+        self
+    }
+
+    /// Locks this UI to viewing: every key but Ctrl+Q is ignored.
+    pub fn with_kiosk(mut self) -> Self {
+        self.kiosk = true;
+        self
+    }
+
+    pub fn with_sample_loaded(mut self) -> Self {
+        self.sample_loaded = true;
+        self
+    }
+
+    pub fn with_exposure(mut self, exposure: Arc<Exposure>) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    pub fn with_output(mut self, output: OutputHandle) -> Self {
+        self.output = Some(output);
+        self
+    }
+
+    pub fn with_loop_limits(mut self, limits: LoopSettings) -> Self {
+        self.loop_limits = limits;
+        self
+    }
+
+    pub fn with_fade_out(mut self, fade_out: Duration) -> Self {
+        self.fade_out = fade_out;
+        self
+    }
+
+    pub fn with_timer_alarm(mut self, alarm: TimerAlarm) -> Self {
+        self.timer_alarm = alarm;
+        self
+    }
+
+    pub fn with_recording(mut self, recording: Option<Recording>) -> Self {
+        self.recording = recording;
+        self
+    }
+
+    pub fn with_remote_url(mut self, url: Option<String>) -> Self {
+        self.remote_url = url;
+        self
+    }
+
+    pub fn with_gallery_url(mut self, url: Option<String>) -> Self {
+        self.gallery_url = url;
+        self
+    }
+
+    /// The recording still running when the UI closed, for the caller to
+    /// stop once the output has.
+    pub fn take_recording(&mut self) -> Option<Recording> {
+        self.recording.take()
+    }
+
+    pub fn with_command_history(mut self, commands: CommandHistory) -> Self {
+        self.commands = commands;
+        self
+    }
+
+    pub fn command_history(&self) -> &CommandHistory {
+        &self.commands
+    }
+
     pub fn run(&mut self) -> Result<()> {
         let _terminal = TerminalSession::enter()?;
+        self.check_clipping();
         self.draw()?;
 
         while self.running.load(Ordering::Relaxed) {
+            let now = clock::now();
+            if self.check_timer(now) {
+                break;
+            }
+            let bypass_lapsed = self.check_bypass(now);
+            let restyled = self.check_dimming(ClockTime::now());
+            let gallery_answered = self.check_gallery();
+            let peak = self.monitor.take_peak();
+            let reduction = self.monitor.take_reduction();
+            self.meter.record(peak, reduction, self.monitor.rms(), now);
+            let second_closed = self.history.record(peak, reduction, now);
             if !event::poll(Duration::from_millis(100))? {
+                // Redraw only when the audio thread has something new to show,
+                // a held bypass ends, or a flashing alarm changes phase. The
+                // output meter alone moves every tick, so only its row does.
+                if second_closed
+                    || bypass_lapsed
+                    || restyled
+                    || gallery_answered
+                    || self.monitor.dsp_resets() != self.drawn_resets
+                    || self.monitor.engine_restarts() != self.drawn_restarts
+                    || self.monitor.silence_restarts() != self.drawn_silences
+                    || self.monitor.refused_buffer() != self.drawn_refused_buffer
+                    || self.monitor.output_lost() != self.drawn_output_lost
+                    || self.exposure.over() != self.drawn_exposure_over
+                    || self
+                        .alarm_lit(clock::now())
+                        .is_some_and(|lit| lit != self.drawn_alarm_lit)
+                {
+                    self.draw()?;
+                } else if self.alarm.is_none() && !self.exam_mode && self.layout.fits() {
+                    let mut meter =
+                        clear_rows(self.layout.meter_row()..self.layout.meter_row() + 1)?;
+                    self.draw_meter(&mut meter, now)?;
+                    let mut frame = Vec::new();
+                    self.paint(&mut frame, Region::Meter, meter);
+                    write_frame(&frame)?;
+                }
                 continue;
             }
 
@@ -47,314 +876,5826 @@ impl InteractiveUi {
                     }
                     self.draw()?;
                 }
-                Event::Resize(_, _) => self.draw()?,
+                // Windows and a few terminals do report releases.
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('b' | 'B'),
+                    ..
+                }) => {
+                    self.release_bypass();
+                    self.draw()?;
+                }
+                Event::Mouse(mouse) => {
+                    self.handle_mouse(mouse);
+                    self.draw()?;
+                }
+                Event::Resize(columns, rows) => {
+                    self.resize(Layout { columns, rows });
+                    self.draw()?;
+                }
                 _ => {}
             }
         }
+        // The trainer's rounds are not the sound to save.
+        if let Some(trainer) = self.trainer.take() {
+            trainer.finish(&mut self.lock_settings());
+        }
         Ok(())
     }
 
-    fn draw(&self) -> Result<()> {
+    /// Dims or restores the palette when the hour or a sleep timer calls
+    /// for it; true when it changed.
+    fn check_dimming(&mut self, time: ClockTime) -> bool {
+        let dim = self.theme_settings.dims(time, self.sleep_timer().is_some());
+        if dim == self.dimmed {
+            return false;
+        }
+        self.dimmed = dim;
+        let theme = Theme::new(self.theme_settings.palette);
+        self.theme = if dim { theme.dimmed() } else { theme };
+        true
+    }
+
+    /// Handles an expired timer: true means quit (a fading timer); a visual
+    /// timer is cleared and raises the alarm instead.
+    fn check_timer(&mut self, now: Instant) -> bool {
+        let Some(timer) = self.sleep_timer().filter(|timer| timer.expired(now)) else {
+            return false;
+        };
+        if timer.alarm() == TimerAlarm::Fade {
+            return true;
+        }
+        self.lock_settings().sleep_timer = None;
+        self.alarm = Some((now, timer.length()));
+        if let Err(error) = (self.notifier)(text("alarm.title"), &alarm_message(timer.length())) {
+            self.prompt_note = Some(text_with(
+                "alarm.notify_failed",
+                &[("error", &format!("{error:#}"))],
+            ));
+        }
+        false
+    }
+
+    /// Ends a held bypass whose key stopped repeating; true if it did.
+    fn check_bypass(&mut self, now: Instant) -> bool {
+        if self.bypass_until.is_some_and(|until| now >= until) {
+            self.release_bypass();
+            return true;
+        }
+        false
+    }
+
+    fn hold_bypass(&mut self, now: Instant) {
+        let hold = if self.bypass_until.is_some() {
+            BYPASS_REPEAT_HOLD
+        } else {
+            BYPASS_FIRST_HOLD
+        };
+        self.bypass_until = Some(now + hold);
+        self.lock_settings().bypass = true;
+    }
+
+    fn release_bypass(&mut self) {
+        if self.bypass_until.take().is_some() {
+            self.lock_settings().bypass = false;
+        }
+    }
+
+    /// Whether a raised alarm is in its lit phase, or None with no alarm.
+    fn alarm_lit(&self, now: Instant) -> Option<bool> {
+        let (raised, _) = self.alarm?;
+        let elapsed = now.saturating_duration_since(raised);
+        Some(elapsed >= ALARM_FLASH || (elapsed.as_millis() / ALARM_FLASH_PERIOD_MS) % 2 == 0)
+    }
+
+    /// Takes a new terminal size. Its content reflows on a resize, so every
+    /// region is drawn afresh.
+    fn resize(&mut self, layout: Layout) {
+        self.layout = layout;
+        self.painted = Default::default();
+        self.help_scroll = self.help_scroll.min(HELP_LINES.len() - layout.help_rows());
+    }
+
+    /// Draws each region of the screen into memory and writes only those
+    /// that differ from what is already shown, in one write.
+    fn draw(&mut self) -> Result<()> {
         let settings = *self
             .settings
             .lock()
             .unwrap_or_else(|poisoned| poisoned.into_inner());
-        let mut stdout = io::stdout().lock();
+        let mut frame = Vec::new();
+        let layout = Layout::current();
+        if layout != self.layout || self.painted.iter().all(Vec::is_empty) {
+            self.resize(layout);
+            queue!(frame, Clear(ClearType::All))?;
+        }
 
-        execute!(stdout, Clear(ClearType::All), cursor::MoveTo(0, 0))?;
-        queue!(
-            stdout,
-            SetForegroundColor(Color::Cyan),
-            Print("Whitenoise\r\n"),
-            ResetColor,
-            Print(format!(
-                "Source: {} (S to switch)\r\n",
-                settings.mix().describe()
-            )),
-            Print(format!(
-                "Listening contour: {} (N to toggle)\r\n",
-                if settings.listening_contour {
-                    "on"
-                } else {
-                    "off"
-                }
-            )),
-            Print("Controls: Up/Down select, Left/Right adjust, R reset EQ, Q quit\r\n\r\n")
-        )?;
+        let alarm = self.alarm.zip(self.alarm_lit(clock::now()));
+        let full_screen =
+            alarm.is_some() || self.exam_mode || self.showing_remote || !layout.fits();
+        let page_end = if full_screen {
+            layout.rows
+        } else {
+            layout.last_row() + 1
+        };
+        let mut page = clear_rows(0..page_end)?;
+        if let Some(((_, length), lit)) = alarm {
+            draw_alarm(&mut page, &self.theme, &alarm_message(length), lit, layout)?;
+            self.drawn_alarm_lit = lit;
+        } else if self.exam_mode {
+            draw_exam(&mut page, &self.theme, settings.volume, layout)?;
+        } else if let Some(url) = self.remote_url.as_deref().filter(|_| self.showing_remote) {
+            draw_remote(&mut page, &self.theme, url, layout)?;
+        } else if !layout.fits() {
+            draw_too_small(&mut page, &self.theme, layout)?;
+        } else {
+            self.draw_header(&mut page, settings)?;
+            match self.page {
+                _ if self.device_picker.is_some() => self.draw_devices(&mut page)?,
+                _ if self.scene_editor.is_some() => self.draw_scene_editor(&mut page)?,
+                _ if self.trainer.is_some() => self.draw_trainer(&mut page)?,
+                Page::Main => self.draw_main(&mut page, settings)?,
+                Page::Mixer => self.draw_mixer(&mut page, settings)?,
+                Page::Eq => self.draw_eq(&mut page, settings)?,
+                Page::Side => self.draw_side(&mut page, settings)?,
+                Page::Presets => self.draw_presets(&mut page)?,
+                Page::Scenes => self.draw_scenes(&mut page)?,
+                Page::Help => draw_help(&mut page, &self.theme, self.help_scroll, layout)?,
+            }
+        }
+        self.paint(&mut frame, Region::Page, page);
+        if full_screen {
+            // The page covered the footer, which is drawn afresh after.
+            for region in &mut self.painted[1..] {
+                region.clear();
+            }
+            return write_frame(&frame);
+        }
 
-        draw_slider(
-            &mut stdout,
-            "Volume",
-            settings.volume,
-            5,
-            self.selected == 0,
-            &format!("{:>3.0}%", settings.volume * 100.0),
+        let row = layout.timer_row();
+        let mut timer = clear_rows(row..row + 1)?;
+        queue!(
+            timer,
+            cursor::MoveTo(2, row),
+            PrintStyledContent(
+                self.theme
+                    .heading
+                    .apply(timer_line(settings.sleep_timer, clock::now()))
+            )
         )?;
+        self.paint(&mut frame, Region::Timer, timer);
+        let row = layout.meter_row();
+        let mut meter = clear_rows(row..row + 1)?;
+        self.draw_meter(&mut meter, clock::now())?;
+        self.paint(&mut frame, Region::Meter, meter);
+        if let Some(row) = layout.history_row() {
+            let mut history = clear_rows(row..row + 2)?;
+            self.draw_history(&mut history, row)?;
+            self.paint(&mut frame, Region::History, history);
+        }
+        self.drawn_resets = self.monitor.dsp_resets();
+        self.drawn_restarts = self.monitor.engine_restarts();
+        self.drawn_silences = self.monitor.silence_restarts();
+        self.drawn_refused_buffer = self.monitor.refused_buffer();
+        self.drawn_output_lost = self.monitor.output_lost();
+        self.drawn_exposure_over = self.exposure.over();
+        let row = layout.prompt_row();
+        let mut prompt = clear_rows(row..row + 1)?;
+        self.draw_prompt(&mut prompt)?;
+        self.paint(&mut frame, Region::Prompt, prompt);
+        write_frame(&frame)
+    }
 
-        for (index, band) in FREQUENCY_BANDS.iter().enumerate() {
-            draw_slider(
-                &mut stdout,
-                band.name,
-                settings.frequency_bands[index],
-                6 + index as u16,
-                self.selected == index + 1,
-                &format!("{:+5.1} dB", slider_to_db(settings.frequency_bands[index])),
-            )?;
+    /// Adds `drawn` to the frame unless the region already shows it.
+    fn paint(&mut self, frame: &mut Vec<u8>, region: Region, drawn: Vec<u8>) {
+        let painted = &mut self.painted[region as usize];
+        if *painted != drawn {
+            frame.extend_from_slice(&drawn);
+            *painted = drawn;
         }
+    }
 
+    /// Above the timer on every page: the live output level after the
+    /// limiter, as a bar to the RMS with a mark at the falling peak, and the
+    /// limiter's gain reduction while it is turning the output down.
+    fn draw_meter(&self, stdout: &mut impl Write, now: Instant) -> Result<()> {
+        let theme = &self.theme;
+        let meter = &self.meter;
+        let (filled, peak) = meter_cells(meter.rms_db, meter.peak_db, SLIDER_WIDTH);
+        let readout = if meter.peak_db <= LEVEL_FLOOR_DB {
+            text("footer.silent").to_owned()
+        } else {
+            text_with(
+                "footer.meter",
+                &[
+                    ("peak", &format!("{:.1}", meter.peak_db)),
+                    ("rms", &format!("{:.1}", meter.rms_db)),
+                ],
+            )
+        };
         queue!(
             stdout,
-            cursor::MoveTo(2, 15),
-            SetForegroundColor(Color::DarkGrey),
-            Print("EQ range: -12 dB to +12 dB; center position is neutral."),
-            cursor::MoveTo(2, 16),
-            Print("Bands: ")
+            cursor::MoveTo(2, self.layout.meter_row()),
+            PrintStyledContent(theme.dim.apply(format!("{:<8}", text("footer.output")))),
+            PrintStyledContent(
+                theme
+                    .level
+                    .apply(theme.fill_glyph.to_string().repeat(filled))
+            )
         )?;
-        for (index, band) in FREQUENCY_BANDS.iter().enumerate() {
-            if index == 4 {
-                queue!(stdout, cursor::MoveTo(9, 17))?;
+        for cell in filled..SLIDER_WIDTH {
+            if Some(cell) == peak {
+                queue!(stdout, PrintStyledContent(theme.level.apply('|')))?;
+            } else {
+                queue!(
+                    stdout,
+                    PrintStyledContent(theme.empty.apply(theme.empty_glyph))
+                )?;
             }
+        }
+        queue!(
+            stdout,
+            PrintStyledContent(theme.dim.apply(format!(" {readout}")))
+        )?;
+        if let Some(reduction) = meter.limiting(now) {
             queue!(
                 stdout,
-                Print(format!(
-                    "{} {:.0}-{:.0} Hz  ",
-                    band.name, band.min_freq, band.max_freq
-                ))
+                Print("  "),
+                PrintStyledContent(theme.limiter.apply(text_with(
+                    "footer.limiting",
+                    &[("db", &format!("{reduction:.1}"))]
+                )))
             )?;
         }
-        queue!(stdout, ResetColor)?;
-        stdout.flush()?;
         Ok(())
     }
 
-    fn handle_key(&mut self, key: KeyEvent) -> bool {
-        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
-            return true;
-        }
+    /// Footer on every page tall enough for it, from `row`: one column per
+    /// second over the last minute.
+    fn draw_history(&self, stdout: &mut impl Write, row: u16) -> Result<()> {
+        let (level, reduction) = self.history.sparklines();
+        let theme = &self.theme;
+        queue!(
+            stdout,
+            cursor::MoveTo(2, row),
+            PrintStyledContent(theme.dim.apply(format!("{:<8}", text("footer.level")))),
+            PrintStyledContent(theme.level.apply(format!("{level:<HISTORY_SECONDS$}"))),
+            PrintStyledContent(theme.dim.apply(format!(
+                " {}",
+                match self.history.loudest() {
+                    peak if peak > 0.0 =>
+                        text_with("footer.peak", &[("db", &format!("{:+.1}", level_db(peak)))]),
+                    _ => text("footer.silent").to_owned(),
+                }
+            ))),
+            cursor::MoveTo(2, row + 1),
+            PrintStyledContent(theme.dim.apply(format!("{:<8}", text("footer.limiter")))),
+            PrintStyledContent(
+                theme
+                    .limiter
+                    .apply(format!("{reduction:<HISTORY_SECONDS$}"))
+            ),
+            PrintStyledContent(theme.dim.apply(format!(
+                " {}",
+                text_with(
+                    "footer.reduction",
+                    &[("db", &format!("{:.1}", self.history.deepest_reduction()))]
+                )
+            )))
+        )?;
+        Ok(())
+    }
 
-        match key.code {
-            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
-            KeyCode::Down => {
-                self.selected = (self.selected + 1).min(FREQUENCY_BANDS.len());
-            }
-            KeyCode::Left => self.adjust_selected(-0.05),
-            KeyCode::Right => self.adjust_selected(0.05),
-            KeyCode::Char('n' | 'N') => {
-                let mut settings = self.lock_settings();
-                settings.listening_contour = !settings.listening_contour;
+    /// The bottom row on every page: the open command line, or the last
+    /// result.
+    fn draw_prompt(&self, stdout: &mut impl Write) -> Result<()> {
+        let width = usize::from(self.layout.columns.saturating_sub(3));
+        let (style, text) = match (&self.prompt, &self.value_entry, &self.prompt_note) {
+            (Some(line), _, _) => (self.theme.text, format!(":{line}_")),
+            (None, Some(digits), _) => (
+                self.theme.text,
+                text_with(
+                    "main.entry",
+                    &[("control", &self.selected_label()), ("value", digits)],
+                ),
+            ),
+            (None, None, Some(note)) => (self.theme.notice, note.clone()),
+            // Engine warnings wait for the command line to be free.
+            (None, None, None) if self.drawn_restarts > 0 => (
+                self.theme.notice,
+                text_with("footer.engine_restarts", &[("count", &self.drawn_restarts)]),
+            ),
+            (None, None, None) if self.drawn_silences > 0 => (
+                self.theme.notice,
+                text_with(
+                    "footer.silence_restarts",
+                    &[("count", &self.drawn_silences)],
+                ),
+            ),
+            (None, None, None) if self.drawn_resets > 0 => (
+                self.theme.notice,
+                text_with("footer.dsp_resets", &[("count", &self.drawn_resets)]),
+            ),
+            (None, None, None) if self.drawn_refused_buffer > 0 => (
+                self.theme.notice,
+                text_with(
+                    "footer.refused_buffer",
+                    &[("frames", &self.drawn_refused_buffer)],
+                ),
+            ),
+            (None, None, None) => return Ok(()),
+        };
+        // Keep the end of a long command line, where the typing happens.
+        let skip = text.chars().count().saturating_sub(width);
+        let text: String = if self.prompt.is_some() {
+            text.chars().skip(skip).collect()
+        } else {
+            text.chars().take(width).collect()
+        };
+        queue!(
+            stdout,
+            cursor::MoveTo(2, self.layout.prompt_row()),
+            PrintStyledContent(style.apply(text))
+        )?;
+        Ok(())
+    }
+
+    /// Row 0 on every page: a breadcrumb, then every page with the current one
+    /// highlighted so Tab's destination is always visible.
+    fn draw_header(&self, stdout: &mut impl Write, settings: AudioSettings) -> Result<()> {
+        let theme = &self.theme;
+        queue!(
+            stdout,
+            PrintStyledContent(theme.heading.apply(text_with(
+                "page.breadcrumb",
+                &[("page", &self.page.title())]
+            ))),
+            Print("    ")
+        )?;
+        for (index, page) in Page::ALL.into_iter().enumerate() {
+            if index > 0 {
+                queue!(stdout, PrintStyledContent(theme.dim.apply(" | ")))?;
             }
-            KeyCode::Char('s' | 'S') => {
-                let mut settings = self.lock_settings();
-                // From a custom mix, S solos the source after the loudest one.
-                let next = settings.mix().dominant().next();
-                settings.set_mix(SourceMix::solo(next));
+            if page == self.page {
+                queue!(
+                    stdout,
+                    PrintStyledContent(theme.selected.apply(format!("[{}]", page.title())))
+                )?;
+            } else {
+                queue!(stdout, PrintStyledContent(theme.dim.apply(page.title())))?;
             }
-            KeyCode::Char('r' | 'R') => {
-                self.lock_settings().frequency_bands = [0.5; FREQUENCY_BANDS.len()];
+        }
+        for (active, key) in [
+            (settings.paused, "page.paused"),
+            (settings.muted, "page.muted"),
+            (settings.bypass, "page.bypass"),
+            (settings.night_ceiling_db.is_some(), "page.night"),
+            (settings.duck_db.is_some(), "page.ducked"),
+            (settings.call_duck_db.is_some(), "page.on_call"),
+            (self.monitor.output_lost(), "page.reconnecting"),
+            (self.kiosk, "page.kiosk"),
+        ] {
+            if active {
+                queue!(
+                    stdout,
+                    Print("    "),
+                    PrintStyledContent(theme.notice.apply(text(key)))
+                )?;
             }
-            KeyCode::Char('q' | 'Q') | KeyCode::Esc => return true,
-            _ => {}
         }
-        false
-    }
+        if let Some(gain) = settings.ambient_gain_db {
+            queue!(
+                stdout,
+                Print("    "),
+                PrintStyledContent(theme.notice.apply(text_with(
+                    "page.ambient",
+                    &[("gain", &format!("{gain:+.1}"))]
+                )))
+            )?;
+        }
+        if self.recording.is_some() {
+            queue!(
+                stdout,
+                Print("    "),
+                PrintStyledContent(theme.notice.apply(text("page.recording")))
+            )?;
+        }
+        if let Some(compare) = &self.compare {
+            queue!(
+                stdout,
+                Print("    "),
+                PrintStyledContent(theme.notice.apply(text_with(
+                    "page.compare",
+                    &[("side", &compare.playing.label())]
+                )))
+            )?;
+        }
+        if !settings.listeners.is_unset() {
+            queue!(
+                stdout,
+                Print("    "),
+                PrintStyledContent(theme.notice.apply(text_with(
+                    "page.listener",
+                    &[("listener", &settings.listeners.active.label())]
+                )))
+            )?;
+        }
+        if self.exposure.over() {
+            queue!(
+                stdout,
+                Print("    "),
+                PrintStyledContent(theme.notice.apply(text_with(
+                    "page.exposure",
+                    &[(
+                        "percent",
+                        &format!("{:.0}", self.exposure.projected() * 100.0)
+                    )]
+                )))
+            )?;
+        }
+        queue!(stdout, Print("\r\n"))?;
+        Ok(())
+    }
+
+    fn draw_main(&self, stdout: &mut impl Write, settings: AudioSettings) -> Result<()> {
+        queue!(
+            stdout,
+            Print(text_with(
+                "main.source",
+                &[("source", &settings.mix().describe())]
+            )),
+            Print("\r\n"),
+            Print(text_with(
+                "main.contour",
+                &[(
+                    "state",
+                    &settings.contour_phon().map_or_else(
+                        || text("main.off").to_owned(),
+                        |phon| text_with("main.on_phon", &[("phon", &format!("{phon:.0}"))])
+                    )
+                )]
+            )),
+            Print("\r\n"),
+            Print(text(if self.kiosk {
+                "main.kiosk_controls"
+            } else {
+                "main.controls"
+            })),
+            Print("\r\n\r\n")
+        )?;
+
+        let visible = self.layout.slider_rows(5);
+        let row = |index| visible_row(index, self.selected, visible, 5);
+        let mut volume = format!("{:>3.0}%", settings.volume * 100.0);
+        if settings.volume > 0.0 {
+            volume.push_str(&format!(
+                " {:+5.1} dB",
+                20.0 * settings.volume.log10() + headroom_db(settings)
+            ));
+        }
+        // The estimate follows the meter, so it is left out during silence.
+        let rms = self.monitor.rms();
+        if let Some(spl) = settings
+            .spl
+            .estimate_db(20.0 * rms.log10())
+            .filter(|_| rms > 0.0)
+        {
+            volume.push_str(&format!(
+                "  {}",
+                text_with("main.spl", &[("spl", &format!("{spl:.0}"))])
+            ));
+            let dose = self.exposure.dose();
+            if dose >= 0.01 {
+                volume.push_str(&format!(
+                    ", {}",
+                    text_with("main.dose", &[("percent", &format!("{:.0}", dose * 100.0))])
+                ));
+            }
+        }
+        draw_slider(
+            stdout,
+            &self.theme,
+            text("main.volume"),
+            settings.volume,
+            row(0),
+            self.selected == 0,
+            &volume,
+        )?;
+
+        let overlay = settings.band_overlay;
+        for (index, band) in bands().iter().enumerate() {
+            let mut label = format!("{:+5.1} dB", slider_to_db(settings.frequency_bands[index]));
+            if overlay.solo == Some(index) {
+                label.push_str(&format!("  {}", text("main.band_solo")));
+            } else if overlay.silences(index) {
+                label.push_str(&format!("  {}", text("main.band_muted")));
+            }
+            draw_slider(
+                stdout,
+                &self.theme,
+                band.label(),
+                settings.frequency_bands[index],
+                row(index + 1),
+                self.selected == index + 1,
+                &label,
+            )?;
+        }
+
+        draw_scroll_marks(
+            stdout,
+            &self.theme,
+            self.selected,
+            1 + bands().len(),
+            visible,
+            5,
+        )?;
+
+        // Below the sliders: the readout, then the EQ range and the band
+        // edges, four to a line, for as many lines as fit above the meter.
+        let readout_row = 5 + visible.min(1 + bands().len()) as u16;
+        queue!(
+            stdout,
+            cursor::MoveTo(4, readout_row),
+            PrintStyledContent(
+                self.theme
+                    .notice
+                    .apply(main_readout(settings, self.selected))
+            )
+        )?;
+        let last_row = self.layout.last_row();
+        if readout_row + 2 > last_row {
+            return Ok(());
+        }
+        queue!(
+            stdout,
+            cursor::MoveTo(2, readout_row + 1),
+            PrintStyledContent(self.theme.dim.apply(text("main.eq_range"))),
+            cursor::MoveTo(2, readout_row + 2),
+            PrintStyledContent(self.theme.dim.apply(format!("{} ", text("main.bands"))))
+        )?;
+        let indent = 3 + text("main.bands").chars().count() as u16;
+        for (index, band) in bands().iter().enumerate() {
+            let row = readout_row + 2 + (index / 4) as u16;
+            if row > last_row {
+                break;
+            }
+            if index > 0 && index % 4 == 0 {
+                queue!(stdout, cursor::MoveTo(indent, row))?;
+            }
+            queue!(
+                stdout,
+                PrintStyledContent(self.theme.dim.apply(text_with(
+                    "main.band_range",
+                    &[
+                        ("band", &band.label()),
+                        ("min", &format!("{:.0}", band.min_freq)),
+                        ("max", &format!("{:.0}", band.max_freq)),
+                    ]
+                ))),
+                Print("  ")
+            )?;
+        }
+        Ok(())
+    }
+
+    fn draw_mixer(&self, stdout: &mut impl Write, settings: AudioSettings) -> Result<()> {
+        let mix = settings.mix();
+        queue!(
+            stdout,
+            Print(text_with("mixer.source", &[("source", &mix.describe())])),
+            Print("\r\n"),
+            Print(text_with(
+                "mixer.master",
+                &[("percent", &format!("{:.0}", settings.volume * 100.0))]
+            )),
+            Print("\r\n"),
+            Print(text("mixer.controls")),
+            Print("\r\n\r\n")
+        )?;
+        let visible = self.layout.slider_rows(5);
+        let row = |index| visible_row(index, self.mixer_selected, visible, 5);
+        let below_sources = |offset| row(SoundStyle::ALL.len() + offset);
+        let selected = MixerRow::at(self.mixer_selected);
+
+        for (index, style) in SoundStyle::ALL.into_iter().enumerate() {
+            let level = mix.level(style);
+            let mut label = format!("{:>3.0}%", level * 100.0);
+            if self.muted_levels[index].is_some() {
+                label.push_str(&format!("  {}", text("mixer.muted")));
+            } else if self.solo_restore.is_some() && level > 0.0 {
+                label.push_str(&format!("  {}", text("mixer.solo")));
+            }
+            draw_slider(
+                stdout,
+                &self.theme,
+                style.label(),
+                level,
+                row(index),
+                self.mixer_selected == index,
+                &label,
+            )?;
+        }
+
+        draw_slider(
+            stdout,
+            &self.theme,
+            text("mixer.width"),
+            settings.stereo_width,
+            below_sources(0),
+            selected == MixerRow::Width,
+            &format!("{:>3.0}%", settings.stereo_width * 100.0),
+        )?;
+        draw_choice(
+            stdout,
+            &self.theme,
+            text("mixer.crossfeed"),
+            text(if settings.crossfeed {
+                "main.on"
+            } else {
+                "main.off"
+            }),
+            below_sources(1),
+            selected == MixerRow::Crossfeed,
+        )?;
+
+        let tone = settings.tone;
+        draw_choice(
+            stdout,
+            &self.theme,
+            text("mixer.tone"),
+            tone.mode.label(),
+            below_sources(2),
+            selected == MixerRow::Tone,
+        )?;
+        draw_slider(
+            stdout,
+            &self.theme,
+            text("mixer.carrier"),
+            (tone.carrier_hz - MIN_CARRIER_HZ) / (MAX_CARRIER_HZ - MIN_CARRIER_HZ),
+            below_sources(3),
+            selected == MixerRow::Carrier,
+            &format!("{:>4.0} Hz", tone.carrier_hz),
+        )?;
+        draw_slider(
+            stdout,
+            &self.theme,
+            text("mixer.beat"),
+            (tone.beat_hz - MIN_BEAT_HZ) / (MAX_BEAT_HZ - MIN_BEAT_HZ),
+            below_sources(4),
+            selected == MixerRow::Beat,
+            &format!("{:>4} Hz", format_hz(tone.beat_hz)),
+        )?;
+        draw_slider(
+            stdout,
+            &self.theme,
+            text("mixer.tone_level"),
+            tone.level,
+            below_sources(5),
+            selected == MixerRow::ToneLevel,
+            &format!("{:>3.0}%", tone.level * 100.0),
+        )?;
+
+        let drift = settings.drift;
+        draw_choice(
+            stdout,
+            &self.theme,
+            text("mixer.drift"),
+            text(if drift.enabled { "main.on" } else { "main.off" }),
+            below_sources(6),
+            selected == MixerRow::Drift,
+        )?;
+        draw_slider(
+            stdout,
+            &self.theme,
+            text("mixer.drift_depth"),
+            drift.depth,
+            below_sources(7),
+            selected == MixerRow::DriftDepth,
+            &format!("{:>3.0}%", drift.depth * 100.0),
+        )?;
+        draw_slider(
+            stdout,
+            &self.theme,
+            text("mixer.drift_period"),
+            (drift.period_seconds / MIN_DRIFT_PERIOD_SECONDS).ln()
+                / (MAX_DRIFT_PERIOD_SECONDS / MIN_DRIFT_PERIOD_SECONDS).ln(),
+            below_sources(8),
+            selected == MixerRow::DriftPeriod,
+            &format!("{:>7}", format_period(drift.period_seconds)),
+        )?;
+
+        let readout = match selected {
+            MixerRow::Source(SoundStyle::Sample) if !self.sample_loaded => {
+                text("readout.sample_missing").to_owned()
+            }
+            MixerRow::Source(style) => mixer_readout(style, mix.level(style)),
+            MixerRow::Width => width_readout(settings.stereo_width),
+            MixerRow::Crossfeed if settings.crossfeed => text("readout.crossfeed_on").to_owned(),
+            MixerRow::Crossfeed => text("readout.crossfeed_off").to_owned(),
+            MixerRow::ToneLevel => tone_level_readout(tone),
+            MixerRow::Tone | MixerRow::Carrier | MixerRow::Beat => tone_readout(tone),
+            MixerRow::Drift | MixerRow::DriftDepth | MixerRow::DriftPeriod => drift_readout(drift),
+        };
+        draw_scroll_marks(
+            stdout,
+            &self.theme,
+            self.mixer_selected,
+            MixerRow::COUNT,
+            visible,
+            5,
+        )?;
+        self.draw_readout(
+            stdout,
+            5 + visible.min(MixerRow::COUNT) as u16,
+            readout,
+            "mixer.note",
+        )
+    }
+
+    fn draw_eq(&self, stdout: &mut impl Write, settings: AudioSettings) -> Result<()> {
+        queue!(
+            stdout,
+            Print(text("eq.heading")),
+            Print("\r\n"),
+            Print(text("eq.controls")),
+            Print("\r\n\r\n")
+        )?;
+        let visible = self.layout.slider_rows(4);
+        let row = |index| visible_row(index, self.eq_selected, visible, 4);
+        let below_tilts = |offset| row(TILT_BANDS.len() + offset);
+        let selected = EqRow::at(self.eq_selected);
+        for (tilt, band) in TILT_BANDS.into_iter().enumerate() {
+            let value = settings.band_tilts[tilt];
+            draw_slider(
+                stdout,
+                &self.theme,
+                &text_with("eq.tilt", &[("band", &FREQUENCY_BANDS[band].label())]),
+                (value + MAX_TILT_DB) / (2.0 * MAX_TILT_DB),
+                row(tilt),
+                selected == EqRow::Tilt(tilt),
+                &format!("{value:+5.1} dB"),
+            )?;
+        }
+        // Log position, so each third-octave step moves the bar equally;
+        // off draws full, like a slider pushed past its top.
+        let (position, value) = match settings.ceiling_hz {
+            Some(hz) => (
+                (hz / MIN_CEILING_HZ).ln() / (MAX_CEILING_HZ / MIN_CEILING_HZ).ln(),
+                format!("{hz:>5.0} Hz"),
+            ),
+            None => (1.0, text("main.off").to_owned()),
+        };
+        draw_slider(
+            stdout,
+            &self.theme,
+            text("eq.ceiling"),
+            position,
+            below_tilts(0),
+            selected == EqRow::Ceiling,
+            &value,
+        )?;
+        // Off draws empty, the mirror of the ceiling.
+        let high_pass = settings.high_pass;
+        let (position, value) = match high_pass.hz {
+            Some(hz) => (
+                (hz / MIN_HIGH_PASS_HZ).ln() / (MAX_HIGH_PASS_HZ / MIN_HIGH_PASS_HZ).ln(),
+                format!("{:>5} Hz", format_hz(hz)),
+            ),
+            None => (0.0, text("main.off").to_owned()),
+        };
+        draw_slider(
+            stdout,
+            &self.theme,
+            text("eq.high_pass"),
+            position,
+            below_tilts(1),
+            selected == EqRow::HighPass,
+            &value,
+        )?;
+        let slopes = HIGH_PASS_SLOPES_DB;
+        draw_slider(
+            stdout,
+            &self.theme,
+            text("eq.slope"),
+            high_pass.slope_db as f32 / slopes[slopes.len() - 1] as f32,
+            below_tilts(2),
+            selected == EqRow::Slope,
+            &text_with("eq.slope_value", &[("db", &high_pass.slope_db)]),
+        )?;
+        // Log position like the other corners; off draws empty.
+        let notch = settings.notch;
+        let (position, value) = match notch.hz {
+            Some(hz) => (
+                (hz / MIN_NOTCH_HZ).ln() / (MAX_NOTCH_HZ / MIN_NOTCH_HZ).ln(),
+                format!("{hz:>5.0} Hz"),
+            ),
+            None => (0.0, text("main.off").to_owned()),
+        };
+        draw_slider(
+            stdout,
+            &self.theme,
+            text("eq.notch"),
+            position,
+            below_tilts(3),
+            selected == EqRow::Notch,
+            &value,
+        )?;
+        draw_slider(
+            stdout,
+            &self.theme,
+            text("eq.notch_width"),
+            (notch.width_octaves - MIN_NOTCH_OCTAVES) / (MAX_NOTCH_OCTAVES - MIN_NOTCH_OCTAVES),
+            below_tilts(4),
+            selected == EqRow::NotchWidth,
+            &text_with(
+                "eq.notch_width_value",
+                &[("octaves", &format!("{:.1}", notch.width_octaves))],
+            ),
+        )?;
+        let limits = self.loop_limits;
+        for (offset, eq_row, label, seconds, limit) in [
+            (
+                5,
+                EqRow::RainFade,
+                "eq.rain_fade",
+                settings.loops.rain_crossfade_seconds,
+                limits.rain_crossfade_seconds,
+            ),
+            (
+                7,
+                EqRow::SampleFade,
+                "eq.sample_fade",
+                settings.loops.sample_crossfade_seconds,
+                limits.sample_crossfade_seconds,
+            ),
+        ] {
+            draw_slider(
+                stdout,
+                &self.theme,
+                text(label),
+                (seconds - MIN_LOOP_CROSSFADE_SECONDS) / (limit - MIN_LOOP_CROSSFADE_SECONDS),
+                below_tilts(offset),
+                selected == eq_row,
+                &format!("{seconds:>5.1} s"),
+            )?;
+        }
+        for (offset, eq_row, label, shuffle) in [
+            (
+                6,
+                EqRow::RainShuffle,
+                "eq.rain_shuffle",
+                settings.loops.rain_shuffle,
+            ),
+            (
+                8,
+                EqRow::SampleShuffle,
+                "eq.sample_shuffle",
+                settings.loops.sample_shuffle,
+            ),
+        ] {
+            draw_choice(
+                stdout,
+                &self.theme,
+                text(label),
+                text(if shuffle { "main.on" } else { "main.off" }),
+                below_tilts(offset),
+                selected == eq_row,
+            )?;
+        }
+        for (band, info) in bands().iter().enumerate() {
+            let factor = settings.band_widths[band];
+            draw_slider(
+                stdout,
+                &self.theme,
+                &text_with("eq.width", &[("band", &info.label())]),
+                factor / MAX_BAND_WIDTH,
+                below_tilts(EqRow::BELOW_TILTS.len() + band),
+                selected == EqRow::Width(band),
+                &format!("{:>4.0}%", factor * 100.0),
+            )?;
+        }
+        let readout = match selected {
+            EqRow::Tilt(tilt) => tilt_readout(settings, tilt),
+            EqRow::Ceiling => ceiling_readout(settings.ceiling_hz),
+            EqRow::HighPass | EqRow::Slope => high_pass_readout(high_pass),
+            EqRow::Notch | EqRow::NotchWidth => notch_readout(notch),
+            EqRow::RainFade => loop_fade_readout(
+                SoundStyle::Rain,
+                settings.loops.rain_crossfade_seconds,
+                limits.rain_crossfade_seconds,
+            ),
+            EqRow::RainShuffle => shuffle_readout(
+                SoundStyle::Rain,
+                settings.loops.rain_shuffle,
+                settings.loops.rain_crossfade_seconds,
+                limits.rain_crossfade_seconds,
+            ),
+            EqRow::SampleFade if !self.sample_loaded => text("readout.sample_missing").to_owned(),
+            EqRow::SampleFade => loop_fade_readout(
+                SoundStyle::Sample,
+                settings.loops.sample_crossfade_seconds,
+                limits.sample_crossfade_seconds,
+            ),
+            EqRow::SampleShuffle if !self.sample_loaded => {
+                text("readout.sample_missing").to_owned()
+            }
+            EqRow::SampleShuffle => shuffle_readout(
+                SoundStyle::Sample,
+                settings.loops.sample_shuffle,
+                settings.loops.sample_crossfade_seconds,
+                limits.sample_crossfade_seconds,
+            ),
+            EqRow::Width(band) => band_width_readout(settings, band),
+        };
+        draw_scroll_marks(
+            stdout,
+            &self.theme,
+            self.eq_selected,
+            EqRow::count(),
+            visible,
+            4,
+        )?;
+        self.draw_readout(
+            stdout,
+            4 + visible.min(EqRow::count()) as u16,
+            readout,
+            "eq.note",
+        )
+    }
+
+    fn draw_side(&self, stdout: &mut impl Write, settings: AudioSettings) -> Result<()> {
+        queue!(
+            stdout,
+            Print(text("side.heading")),
+            Print("\r\n"),
+            Print(text("side.controls")),
+            Print("\r\n\r\n")
+        )?;
+        let visible = self.layout.slider_rows(4);
+        let row = |index| visible_row(index, self.side_selected, visible, 4);
+        draw_choice(
+            stdout,
+            &self.theme,
+            text("side.mode"),
+            text(if settings.mid_side {
+                "main.on"
+            } else {
+                "main.off"
+            }),
+            row(0),
+            self.side_selected == 0,
+        )?;
+        for (index, band) in bands().iter().enumerate() {
+            let value = settings.side_bands[index];
+            draw_slider(
+                stdout,
+                &self.theme,
+                band.label(),
+                value,
+                row(index + 1),
+                self.side_selected == index + 1,
+                &format!("{:+5.1} dB", slider_to_db(value)),
+            )?;
+        }
+        draw_scroll_marks(
+            stdout,
+            &self.theme,
+            self.side_selected,
+            1 + bands().len(),
+            visible,
+            4,
+        )?;
+        self.draw_readout(
+            stdout,
+            4 + visible.min(1 + bands().len()) as u16,
+            side_readout(settings, self.side_selected),
+            "side.note",
+        )
+    }
+
+    /// The selected row's readout on `row`, and the page's note under it when
+    /// it fits above the meter.
+    fn draw_readout(
+        &self,
+        stdout: &mut impl Write,
+        row: u16,
+        readout: String,
+        note: &'static str,
+    ) -> Result<()> {
+        queue!(
+            stdout,
+            cursor::MoveTo(4, row),
+            PrintStyledContent(self.theme.notice.apply(readout))
+        )?;
+        if row < self.layout.last_row() {
+            queue!(
+                stdout,
+                cursor::MoveTo(2, row + 1),
+                PrintStyledContent(self.theme.dim.apply(text(note)))
+            )?;
+        }
+        Ok(())
+    }
+
+    fn draw_presets(&self, stdout: &mut impl Write) -> Result<()> {
+        if let Some(entries) = &self.gallery {
+            return self.draw_gallery(stdout, entries);
+        }
+        queue!(
+            stdout,
+            Print(text_with(
+                "presets.heading",
+                &[("dir", &self.presets_dir.display())]
+            )),
+            Print("\r\n"),
+            Print(text("presets.save_hint")),
+            Print("\r\n"),
+            Print(text("presets.controls")),
+            Print("\r\n\r\n")
+        )?;
+        if self.presets.is_empty() {
+            queue!(
+                stdout,
+                PrintStyledContent(self.theme.dim.apply(format!("  {}", text("presets.empty"))))
+            )?;
+            return Ok(());
+        }
+        self.draw_names(stdout, &self.presets, self.preset_selected)
+    }
+
+    fn draw_gallery(&self, stdout: &mut impl Write, entries: &[gallery::Entry]) -> Result<()> {
+        queue!(
+            stdout,
+            Print(text_with(
+                "gallery.heading",
+                &[("url", &self.gallery_url.as_deref().unwrap_or_default())]
+            )),
+            Print("\r\n"),
+            Print(text("gallery.hint")),
+            Print("\r\n"),
+            Print(text("gallery.controls")),
+            Print("\r\n\r\n")
+        )?;
+        if entries.is_empty() {
+            queue!(
+                stdout,
+                PrintStyledContent(self.theme.dim.apply(format!("  {}", text("gallery.empty"))))
+            )?;
+            return Ok(());
+        }
+        let lines: Vec<String> = entries.iter().map(gallery::Entry::line).collect();
+        self.draw_names(stdout, &lines, self.gallery_selected)
+    }
+
+    /// The Presets or Scenes page's list from row 5, scrolled to keep the
+    /// selection in view.
+    fn draw_names(&self, stdout: &mut impl Write, names: &[String], selected: usize) -> Result<()> {
+        let rows = self.layout.list_rows();
+        for (row, (index, name)) in names
+            .iter()
+            .enumerate()
+            .skip(first_visible(selected, rows))
+            .take(rows)
+            .enumerate()
+        {
+            let (style, marker) = if index == selected {
+                (self.theme.selected, ">")
+            } else {
+                (self.theme.text, " ")
+            };
+            queue!(
+                stdout,
+                cursor::MoveTo(2, 5 + row as u16),
+                PrintStyledContent(style.apply(format!("{marker} {name}")))
+            )?;
+        }
+        if names.len() > rows {
+            queue!(
+                stdout,
+                cursor::MoveTo(2, 6 + rows as u16),
+                PrintStyledContent(self.theme.dim.apply(text_with(
+                    "presets.position",
+                    &[("index", &(selected + 1)), ("count", &names.len())]
+                )))
+            )?;
+        }
+        Ok(())
+    }
+
+    fn draw_scenes(&self, stdout: &mut impl Write) -> Result<()> {
+        queue!(
+            stdout,
+            Print(text_with(
+                "scenes.heading",
+                &[("dir", &self.scenes_dir().display())]
+            )),
+            Print("\r\n"),
+            Print(text("scenes.hint")),
+            Print("\r\n"),
+            Print(text("scenes.controls")),
+            Print("\r\n\r\n")
+        )?;
+        if self.scenes.is_empty() {
+            queue!(
+                stdout,
+                PrintStyledContent(self.theme.dim.apply(format!("  {}", text("scenes.empty"))))
+            )?;
+            return Ok(());
+        }
+        self.draw_names(stdout, &self.scenes, self.scene_selected)
+    }
+
+    /// The ear trainer in place of the page: the question, the two sides
+    /// with the one playing marked, the bands to answer with, and the
+    /// result and score.
+    fn draw_trainer(&self, stdout: &mut impl Write) -> Result<()> {
+        let Some(trainer) = &self.trainer else {
+            return Ok(());
+        };
+        let (heading, controls) = match trainer.round() {
+            Round::Colors { asked, .. } => (
+                text_with("trainer.colors_heading", &[("color", &asked.label())]),
+                text("trainer.colors_controls"),
+            ),
+            Round::Bands { db, .. } => (
+                text_with(
+                    "trainer.bands_heading",
+                    &[("db", &format!("{:.0}", db.abs()))],
+                ),
+                text("trainer.bands_controls"),
+            ),
+        };
+        queue!(
+            stdout,
+            Print(heading),
+            Print("\r\n"),
+            Print(controls),
+            cursor::MoveTo(2, 5)
+        )?;
+        for side in [Listener::A, Listener::B] {
+            let style = if side == trainer.playing() {
+                self.theme.selected
+            } else {
+                self.theme.dim
+            };
+            queue!(
+                stdout,
+                PrintStyledContent(
+                    style.apply(text_with("trainer.side", &[("side", &side.label())]))
+                ),
+                Print("   ")
+            )?;
+        }
+
+        let mut row = 7;
+        if let Drill::Bands(_) = trainer.drill() {
+            let visible = self.layout.rows_from(7, 3);
+            for (index, band) in bands().iter().enumerate() {
+                let Some(screen_row) = visible_row(index, trainer.guess(), visible, 7) else {
+                    continue;
+                };
+                let (style, marker) = if index == trainer.guess() {
+                    (self.theme.selected, ">")
+                } else {
+                    (self.theme.text, " ")
+                };
+                queue!(
+                    stdout,
+                    cursor::MoveTo(2, screen_row),
+                    PrintStyledContent(style.apply(format!("{marker} {}", band.label())))
+                )?;
+            }
+            row += visible.min(bands().len()) as u16 + 1;
+        }
+
+        if let Some(right) = trainer.answered() {
+            let answer = match trainer.round() {
+                Round::Colors { a, b, .. } => text_with(
+                    "trainer.colors_answer",
+                    &[("a", &a.label()), ("b", &b.label())],
+                ),
+                Round::Bands { band, db } => text_with(
+                    "trainer.bands_answer",
+                    &[
+                        ("band", &bands()[band].label()),
+                        ("db", &format!("{db:+.0}")),
+                    ],
+                ),
+            };
+            let (style, verdict) = if right {
+                (self.theme.heading, text("trainer.right"))
+            } else {
+                (self.theme.notice, text("trainer.wrong"))
+            };
+            queue!(
+                stdout,
+                cursor::MoveTo(2, row),
+                PrintStyledContent(style.apply(format!("{verdict} {answer}")))
+            )?;
+        }
+        let (right, rounds, streak) = trainer.score();
+        queue!(
+            stdout,
+            cursor::MoveTo(2, row + 1),
+            PrintStyledContent(self.theme.dim.apply(text_with(
+                "trainer.score",
+                &[("right", &right), ("rounds", &rounds), ("streak", &streak)]
+            )))
+        )?;
+        Ok(())
+    }
+
+    /// The scene editor in place of the Scenes page: a name field for a new
+    /// scene, then a row per layer with the selected cell in brackets.
+    fn draw_scene_editor(&self, stdout: &mut impl Write) -> Result<()> {
+        let Some(editor) = &self.scene_editor else {
+            return Ok(());
+        };
+        if editor.naming {
+            queue!(
+                stdout,
+                Print(text_with(
+                    "scene_editor.new_heading",
+                    &[("dir", &self.scenes_dir().display())]
+                )),
+                Print("\r\n"),
+                Print(text("scene_editor.name_controls")),
+                Print("\r\n\r\n"),
+                PrintStyledContent(self.theme.selected.apply(text_with(
+                    "scene_editor.name",
+                    &[("name", &format!("{}_", editor.name))]
+                )))
+            )?;
+            return Ok(());
+        }
+
+        queue!(
+            stdout,
+            Print(text_with("scene_editor.heading", &[("name", &editor.name)])),
+            Print("\r\n"),
+            Print(text("scene_editor.controls")),
+            Print("\r\n\r\n"),
+            cursor::MoveTo(2, 4)
+        )?;
+        let mut heading = String::from("  ");
+        for field in SceneField::ALL {
+            heading.push_str(&format!(
+                " {:<width$} ",
+                field.heading(),
+                width = field.width()
+            ));
+        }
+        queue!(stdout, PrintStyledContent(self.theme.dim.apply(heading)))?;
+        let visible = self.layout.list_rows();
+        for (row, layer) in editor.layers.iter().enumerate() {
+            let Some(screen_row) = visible_row(row, editor.row, visible, 5) else {
+                continue;
+            };
+            let (style, marker) = if row == editor.row {
+                (self.theme.selected, ">")
+            } else {
+                (self.theme.text, " ")
+            };
+            let mut line = format!("{marker} ");
+            for (index, field) in SceneField::ALL.into_iter().enumerate() {
+                let value = layer_cell(layer, field);
+                let width = field.width();
+                if row == editor.row && index == editor.field {
+                    line.push_str(&format!("[{value:<width$}]"));
+                } else {
+                    line.push_str(&format!(" {value:<width$} "));
+                }
+            }
+            queue!(
+                stdout,
+                cursor::MoveTo(2, screen_row),
+                PrintStyledContent(style.apply(line))
+            )?;
+        }
+        let below = 6 + visible.min(SoundStyle::ALL.len()) as u16;
+        if editor.layers.is_empty() {
+            queue!(
+                stdout,
+                cursor::MoveTo(2, 5),
+                PrintStyledContent(
+                    self.theme
+                        .dim
+                        .apply(format!("  {}", text("scene_editor.no_layers")))
+                )
+            )?;
+        }
+        queue!(
+            stdout,
+            cursor::MoveTo(2, below),
+            PrintStyledContent(
+                self.theme
+                    .notice
+                    .apply(SceneField::ALL[editor.field].hint())
+            )
+        )?;
+        Ok(())
+    }
+
+    /// The device list in place of the page, the playing device marked.
+    fn draw_devices(&self, stdout: &mut impl Write) -> Result<()> {
+        let Some(picker) = &self.device_picker else {
+            return Ok(());
+        };
+        let playing = self
+            .output
+            .as_ref()
+            .map(OutputHandle::device_name)
+            .unwrap_or_default();
+        queue!(
+            stdout,
+            Print(text_with("devices.heading", &[("device", &playing)])),
+            Print("\r\n"),
+            Print(text("devices.controls")),
+            Print("\r\n\r\n")
+        )?;
+        let rows = self.layout.list_rows();
+        for (row, (index, name)) in picker
+            .names
+            .iter()
+            .enumerate()
+            .skip(first_visible(picker.selected, rows))
+            .take(rows)
+            .enumerate()
+        {
+            let (style, marker) = if index == picker.selected {
+                (self.theme.selected, ">")
+            } else {
+                (self.theme.text, " ")
+            };
+            let suffix = if *name == playing {
+                format!(" {}", text("devices.playing"))
+            } else {
+                String::new()
+            };
+            queue!(
+                stdout,
+                cursor::MoveTo(2, 4 + row as u16),
+                PrintStyledContent(style.apply(format!("{marker} {name}{suffix}")))
+            )?;
+        }
+        Ok(())
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let quit = self.dispatch_key(key);
+        self.note_eq_change();
+        quit
+    }
+
+    fn dispatch_key(&mut self, key: KeyEvent) -> bool {
+        // Not even Ctrl+C leaves a kiosk, and the volume keys do nothing.
+        // Any key may still clear an alarm, which changes nothing.
+        if self.kiosk {
+            self.alarm = None;
+            return key.modifiers.contains(KeyModifiers::CONTROL)
+                && matches!(key.code, KeyCode::Char('q' | 'Q'));
+        }
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return true;
+        }
+        // The key that dismisses an alarm does nothing else.
+        if self.alarm.take().is_some() {
+            return false;
+        }
+        // The keyboard's volume keys, where the terminal passes them on,
+        // work the same on every page and over the prompt.
+        if let KeyCode::Media(media) = key.code {
+            match media {
+                MediaKeyCode::RaiseVolume => self.adjust_volume(0.05),
+                MediaKeyCode::LowerVolume => self.adjust_volume(-0.05),
+                MediaKeyCode::MuteVolume if !self.exam_mode => {
+                    let mut settings = self.lock_settings();
+                    settings.muted = !settings.muted;
+                }
+                _ => {}
+            }
+            return false;
+        }
+        if self.exam_mode {
+            return self.handle_exam_key(key);
+        }
+        if self.prompt.is_some() {
+            return self.handle_prompt_key(key);
+        }
+        // The key that closes the remote's QR code does nothing else.
+        if self.showing_remote {
+            self.showing_remote = false;
+            return false;
+        }
+        let trimming = std::mem::take(&mut self.pending_trim);
+        self.prompt_note = None;
+        if trimming && matches!(key.code, KeyCode::Char('y' | 'Y')) {
+            self.run_command(Command::Trim);
+            return false;
+        }
+        if self.value_entry.is_some() {
+            self.handle_value_entry_key(key);
+            return false;
+        }
+        if self.device_picker.is_some() {
+            self.handle_device_key(key);
+            return false;
+        }
+        if self.trainer.is_some() {
+            self.handle_trainer_key(key);
+            return false;
+        }
+        if self.scene_editor.is_some() {
+            self.handle_scene_editor_key(key);
+            return false;
+        }
+        if key.modifiers.contains(KeyModifiers::CONTROL)
+            && matches!(key.code, KeyCode::Char('z' | 'Z'))
+        {
+            self.undo_eq();
+            return false;
+        }
+        if key.modifiers.contains(KeyModifiers::CONTROL)
+            && matches!(key.code, KeyCode::Char('r' | 'R'))
+        {
+            self.toggle_recording();
+            return false;
+        }
+
+        // Some terminals report Shift+Tab as BackTab, others as Tab with SHIFT.
+        let shifted = key.modifiers.contains(KeyModifiers::SHIFT);
+        match (self.page, key.code) {
+            (_, KeyCode::BackTab) => self.show_page(self.page.previous()),
+            (_, KeyCode::Tab) if shifted => self.show_page(self.page.previous()),
+            (_, KeyCode::Tab) => self.show_page(self.page.next()),
+            (Page::Main, _) => return self.handle_main_key(key),
+            (Page::Mixer, _) => return self.handle_mixer_key(key),
+            (Page::Eq, _) => return self.handle_eq_key(key),
+            (Page::Side, _) => return self.handle_side_key(key),
+            (Page::Presets, _) => return self.handle_presets_key(key),
+            (Page::Scenes, _) => return self.handle_scenes_key(key),
+            (Page::Help, KeyCode::Up) => self.help_scroll = self.help_scroll.saturating_sub(1),
+            (Page::Help, KeyCode::Down) => {
+                self.help_scroll =
+                    (self.help_scroll + 1).min(HELP_LINES.len() - self.layout.help_rows());
+            }
+            (Page::Help, _) => return self.handle_shared_key(key),
+        }
+        false
+    }
+
+    /// A click on a slider row selects it, and on the Main page a click or
+    /// drag along the bar sets the value where the pointer is. The wheel
+    /// steps the selection as Left and Right do, or scrolls a list.
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        if self.kiosk || self.prompt.is_some() || self.value_entry.is_some() {
+            return;
+        }
+        let wheel = match mouse.kind {
+            MouseEventKind::ScrollUp => Some(true),
+            MouseEventKind::ScrollDown => Some(false),
+            _ => None,
+        };
+        if let Some(up) = wheel {
+            let adjusts = self.device_picker.is_none()
+                && (self.scene_editor.is_some()
+                    || matches!(self.page, Page::Main | Page::Mixer | Page::Eq | Page::Side));
+            let code = match (adjusts, up) {
+                (true, true) => KeyCode::Right,
+                (true, false) => KeyCode::Left,
+                (false, true) => KeyCode::Up,
+                (false, false) => KeyCode::Down,
+            };
+            self.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+            return;
+        }
+
+        let position = slider_position(mouse.column);
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {}
+            MouseEventKind::Drag(MouseButton::Left) if self.dragging => {
+                if let Some(position) = position {
+                    self.set_selected(position);
+                }
+                return;
+            }
+            // A drag is one change to undo, however far it went.
+            MouseEventKind::Up(_) => {
+                self.dragging = false;
+                self.note_eq_change();
+                return;
+            }
+            _ => return,
+        }
+        // Like a key, a click first dismisses an alarm.
+        if self.alarm.take().is_some()
+            || self.exam_mode
+            || self.device_picker.is_some()
+            || self.scene_editor.is_some()
+        {
+            return;
+        }
+        self.prompt_note = None;
+        let layout = self.layout;
+        let row = mouse.row;
+        match self.page {
+            Page::Main => {
+                let count = 1 + bands().len();
+                let visible = layout.slider_rows(5);
+                if let Some(index) = item_at(row, 5, count, self.selected, visible) {
+                    self.selected = index;
+                    if let Some(position) = position {
+                        self.set_selected(position);
+                        self.dragging = true;
+                    }
+                }
+            }
+            Page::Mixer => {
+                let visible = layout.slider_rows(5);
+                if let Some(index) = item_at(row, 5, MixerRow::COUNT, self.mixer_selected, visible)
+                {
+                    self.mixer_selected = index;
+                }
+            }
+            Page::Eq => {
+                let visible = layout.slider_rows(4);
+                if let Some(index) = item_at(row, 4, EqRow::count(), self.eq_selected, visible) {
+                    self.eq_selected = index;
+                }
+            }
+            Page::Side => {
+                let visible = layout.slider_rows(4);
+                if let Some(index) = item_at(row, 4, 1 + bands().len(), self.side_selected, visible)
+                {
+                    self.side_selected = index;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn show_page(&mut self, page: Page) {
+        self.page = page;
+        match page {
+            Page::Presets => self.refresh_presets(),
+            Page::Scenes => self.refresh_scenes(),
+            _ => {}
+        }
+    }
+
+    fn refresh_presets(&mut self) {
+        match list_presets(&self.presets_dir) {
+            Ok(presets) => self.presets = presets,
+            Err(error) => self.prompt_note = Some(format!("{error:#}")),
+        }
+        self.preset_selected = self
+            .preset_selected
+            .min(self.presets.len().saturating_sub(1));
+        self.pending_delete = false;
+    }
+
+    /// Asks the gallery for its listing in the background.
+    fn open_gallery(&mut self) {
+        match gallery::configured(self.gallery_url.as_deref()) {
+            Ok(url) => {
+                self.gallery_request = Some(gallery::request_list(url.to_owned()));
+                self.prompt_note = Some(text("gallery.fetching").to_owned());
+            }
+            Err(error) => self.prompt_note = Some(format!("{error:#}")),
+        }
+    }
+
+    /// Takes the answer to a gallery request once it is in: a listing
+    /// replaces the saved presets on their page, and a downloaded preset is
+    /// saved under the name the listing shows and selected among them.
+    fn check_gallery(&mut self) -> bool {
+        let reply = match self.gallery_request.as_ref().map(Receiver::try_recv) {
+            Some(Ok(reply)) => reply,
+            Some(Err(TryRecvError::Disconnected)) => {
+                self.gallery_request = None;
+                return false;
+            }
+            Some(Err(TryRecvError::Empty)) | None => return false,
+        };
+        self.gallery_request = None;
+        self.prompt_note = None;
+        match reply {
+            gallery::Reply::List(Ok(entries)) => {
+                self.gallery = Some(entries);
+                self.gallery_selected = 0;
+            }
+            gallery::Reply::Preset(Ok(content)) => {
+                let Some(name) = self
+                    .gallery
+                    .as_ref()
+                    .and_then(|entries| entries.get(self.gallery_selected))
+                    .map(|entry| entry.name.clone())
+                else {
+                    return true;
+                };
+                if self
+                    .presets
+                    .iter()
+                    .any(|saved| saved.eq_ignore_ascii_case(&name))
+                {
+                    self.prompt_note = Some(text_with("gallery.exists", &[("name", &name)]));
+                    return true;
+                }
+                match import_preset(&self.presets_dir, &content, Some(&name), false) {
+                    Ok(import) => {
+                        self.gallery = None;
+                        self.refresh_presets();
+                        self.preset_selected = self
+                            .presets
+                            .iter()
+                            .position(|saved| *saved == import.name)
+                            .unwrap_or(self.preset_selected);
+                        self.prompt_note =
+                            Some(text_with("gallery.saved", &[("name", &import.name)]));
+                    }
+                    Err(error) => self.prompt_note = Some(format!("{error:#}")),
+                }
+            }
+            gallery::Reply::List(Err(error)) | gallery::Reply::Preset(Err(error)) => {
+                self.prompt_note = Some(format!("{error:#}"));
+            }
+        }
+        true
+    }
+
+    fn scenes_dir(&self) -> PathBuf {
+        scenes_beside(&self.presets_dir)
+    }
+
+    fn refresh_scenes(&mut self) {
+        match list_scenes(&self.scenes_dir()) {
+            Ok(scenes) => self.scenes = scenes,
+            Err(error) => self.prompt_note = Some(format!("{error:#}")),
+        }
+        self.scene_selected = self.scene_selected.min(self.scenes.len().saturating_sub(1));
+    }
+
+    fn handle_exam_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Up | KeyCode::Right => self.adjust_volume(0.05),
+            KeyCode::Down | KeyCode::Left => self.adjust_volume(-0.05),
+            KeyCode::Char('q' | 'Q') | KeyCode::Esc => return true,
+            _ => {}
+        }
+        false
+    }
+
+    fn handle_prompt_key(&mut self, key: KeyEvent) -> bool {
+        let Some(line) = self.prompt.as_mut() else {
+            return false;
+        };
+        match key.code {
+            KeyCode::Enter => {
+                let line = std::mem::take(line);
+                self.prompt = None;
+                self.commands.push(&line);
+                if !line.trim().is_empty() {
+                    return self.execute(&line);
+                }
+            }
+            KeyCode::Esc => self.prompt = None,
+            KeyCode::Backspace => {
+                line.pop();
+            }
+            KeyCode::Tab => {
+                let (completed, candidates) = complete(line, &self.presets_dir);
+                *line = completed;
+                self.prompt_note = (!candidates.is_empty()).then(|| candidates.join("  "));
+            }
+            KeyCode::Up => {
+                if let Some(entry) = self.commands.older() {
+                    *line = entry.to_owned();
+                }
+            }
+            KeyCode::Down => *line = self.commands.newer().unwrap_or_default().to_owned(),
+            KeyCode::Char(character) => line.push(character),
+            _ => {}
+        }
+        false
+    }
+
+    fn execute(&mut self, line: &str) -> bool {
+        match Command::parse(line) {
+            Ok(Command::Quit) => return true,
+            Ok(command) => self.run_command(command),
+            Err(error) => self.prompt_note = Some(error),
+        }
+        false
+    }
+
+    /// Applies a command and shows its result. Replacing the mix forgets
+    /// mute and solo state, as the S key does.
+    fn run_command(&mut self, command: Command) {
+        if matches!(
+            command,
+            Command::Style(_)
+                | Command::Mix(_)
+                | Command::LoadPreset(_)
+                | Command::LoadScene(_)
+                | Command::LoadShareCode(_)
+        ) {
+            self.muted_levels = [None; SoundStyle::ALL.len()];
+            self.solo_restore = None;
+        }
+        if let Command::Train(drill) = command {
+            self.open_trainer(drill);
+            return;
+        }
+        let changes_presets = matches!(command, Command::SavePreset(_) | Command::DeletePreset(_));
+        let loads_sound = matches!(
+            command,
+            Command::LoadPreset(_) | Command::LoadScene(_) | Command::LoadShareCode(_)
+        );
+        let command = match command {
+            Command::Timer(Some(length)) => Command::TimerWithAlarm(length, self.timer_alarm),
+            Command::SplReading(measured_db) => {
+                let rms = self.monitor.rms();
+                if rms <= 0.0 {
+                    self.prompt_note = Some(text("main.spl_silent").to_owned());
+                    return;
+                }
+                let spl = self
+                    .lock_settings()
+                    .spl
+                    .from_reading(measured_db, 20.0 * rms.log10());
+                Command::SplOffset(spl.offset_db)
+            }
+            command => command,
+        };
+        let note = command.apply(
+            &mut self.lock_settings(),
+            self.fade_out,
+            clock::now(),
+            &self.presets_dir,
+        );
+        if changes_presets {
+            self.refresh_presets();
+        }
+        self.prompt_note = Some(note);
+        if loads_sound {
+            self.check_clipping();
+        }
+    }
+
+    /// Offers to trim the volume when the EQ, the listening contour, and
+    /// the volume would keep the limiter working, which is otherwise only
+    /// heard as distortion. The warning joins any note already showing.
+    fn check_clipping(&mut self) {
+        if self.kiosk || self.exam_mode {
+            return;
+        }
+        let Some(excess) = limiter_excess_db(*self.lock_settings()) else {
+            return;
+        };
+        let warning = text_with("main.clipping", &[("db", &format!("{excess:.1}"))]);
+        self.prompt_note = Some(match self.prompt_note.take() {
+            Some(note) => format!("{note}; {warning}"),
+            None => warning,
+        });
+        self.pending_trim = true;
+    }
+
+    fn adjust_volume(&self, amount: f32) {
+        let mut settings = self.lock_settings();
+        settings.volume = (settings.volume + amount).clamp(0.0, 1.0);
+    }
+
+    fn handle_main_key(&mut self, key: KeyEvent) -> bool {
+        let shifted = key.modifiers.contains(KeyModifiers::SHIFT);
+        let drawing = key.modifiers.contains(KeyModifiers::ALT);
+        match key.code {
+            KeyCode::Up if drawing => self.draw_across(false),
+            KeyCode::Down if drawing => self.draw_across(true),
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => {
+                self.selected = (self.selected + 1).min(bands().len());
+            }
+            KeyCode::Left if shifted => self.adjust_selected(-0.01),
+            KeyCode::Right if shifted => self.adjust_selected(0.01),
+            KeyCode::Left => self.adjust_selected(-0.05),
+            KeyCode::Right => self.adjust_selected(0.05),
+            KeyCode::PageUp => self.adjust_selected(0.25),
+            KeyCode::PageDown => self.adjust_selected(-0.25),
+            KeyCode::Char(digit @ '1'..='9') => {
+                let tenths = digit.to_digit(10).unwrap_or_default();
+                self.set_selected(tenths as f32 / 10.0);
+            }
+            KeyCode::Enter => self.value_entry = Some(String::new()),
+            KeyCode::Char('s' | 'S') => {
+                // From a custom mix, S solos the source after the loudest one.
+                let mut next = self.lock_settings().mix().dominant().next();
+                if next == SoundStyle::Sample && !self.sample_loaded {
+                    next = next.next();
+                }
+                self.replace_mix(SourceMix::solo(next));
+            }
+            // With a band selected, M and O mute and solo it; on the volume
+            // row M still mutes the output.
+            KeyCode::Char('m' | 'M') if self.selected > 0 => {
+                self.lock_settings()
+                    .band_overlay
+                    .toggle_mute(self.selected - 1);
+            }
+            KeyCode::Char('o' | 'O') if self.selected > 0 => {
+                self.lock_settings()
+                    .band_overlay
+                    .toggle_solo(self.selected - 1);
+            }
+            _ => return self.handle_shared_key(key),
+        }
+        false
+    }
+
+    fn handle_mixer_key(&mut self, key: KeyEvent) -> bool {
+        match (key.code, MixerRow::at(self.mixer_selected)) {
+            (KeyCode::Up, _) => self.mixer_selected = self.mixer_selected.saturating_sub(1),
+            (KeyCode::Down, _) => {
+                self.mixer_selected = (self.mixer_selected + 1).min(MixerRow::COUNT - 1);
+            }
+            (KeyCode::Left, row) => self.adjust_mixer_row(row, -1.0),
+            (KeyCode::Right, row) => self.adjust_mixer_row(row, 1.0),
+            (KeyCode::Char('m' | 'M'), MixerRow::Source(style)) => self.toggle_mute(style),
+            (KeyCode::Char('s' | 'S'), MixerRow::Source(style)) => self.toggle_solo(style),
+            // On this page M means a source, so it never mutes the output.
+            (KeyCode::Char('m' | 'M'), _) => {}
+            _ => return self.handle_shared_key(key),
+        }
+        false
+    }
+
+    /// One step left (-1) or right (+1): 5% for levels, width, and drift
+    /// depth, crossfeed or drift toggled, the next tone mode, 10 Hz of
+    /// carrier, 0.5 Hz of beat, or the next drift period.
+    fn adjust_mixer_row(&mut self, row: MixerRow, direction: f32) {
+        if let MixerRow::Source(style) = row {
+            self.adjust_level(style, 0.05 * direction);
+            return;
+        }
+        let mut settings = self.lock_settings();
+        let tone = &mut settings.tone;
+        match row {
+            MixerRow::Source(_) => {}
+            MixerRow::Width => {
+                settings.stereo_width = (settings.stereo_width + 0.05 * direction).clamp(0.0, 1.0);
+            }
+            MixerRow::Crossfeed => settings.crossfeed = !settings.crossfeed,
+            MixerRow::Tone if direction > 0.0 => tone.mode = tone.mode.next(),
+            MixerRow::Tone => tone.mode = tone.mode.previous(),
+            MixerRow::Carrier => {
+                tone.carrier_hz =
+                    (tone.carrier_hz + 10.0 * direction).clamp(MIN_CARRIER_HZ, MAX_CARRIER_HZ);
+            }
+            MixerRow::Beat => {
+                tone.beat_hz = (tone.beat_hz + 0.5 * direction).clamp(MIN_BEAT_HZ, MAX_BEAT_HZ);
+            }
+            MixerRow::ToneLevel => tone.level = (tone.level + 0.05 * direction).clamp(0.0, 1.0),
+            MixerRow::Drift => settings.drift.enabled = !settings.drift.enabled,
+            MixerRow::DriftDepth => {
+                settings.drift.depth = (settings.drift.depth + 0.05 * direction).clamp(0.0, 1.0);
+            }
+            MixerRow::DriftPeriod => {
+                settings.drift.period_seconds =
+                    step_drift_period(settings.drift.period_seconds, direction);
+            }
+        }
+    }
+
+    fn handle_eq_key(&mut self, key: KeyEvent) -> bool {
+        let step = match key.code {
+            KeyCode::Up => {
+                self.eq_selected = self.eq_selected.saturating_sub(1);
+                return false;
+            }
+            KeyCode::Down => {
+                self.eq_selected = (self.eq_selected + 1).min(EqRow::count() - 1);
+                return false;
+            }
+            KeyCode::Left => -0.5,
+            KeyCode::Right => 0.5,
+            _ => return self.handle_shared_key(key),
+        };
+        let mut settings = self.lock_settings();
+        match EqRow::at(self.eq_selected) {
+            EqRow::Tilt(tilt) => {
+                let tilt = &mut settings.band_tilts[tilt];
+                *tilt = (*tilt + step).clamp(-MAX_TILT_DB, MAX_TILT_DB);
+            }
+            EqRow::Ceiling => settings.ceiling_hz = step_ceiling(settings.ceiling_hz, step > 0.0),
+            EqRow::HighPass => {
+                settings.high_pass.hz = step_high_pass(settings.high_pass.hz, step > 0.0);
+            }
+            EqRow::Slope => {
+                let slopes = HIGH_PASS_SLOPES_DB;
+                settings.high_pass.slope_db = if step > 0.0 {
+                    slopes[slopes.len() - 1]
+                } else {
+                    slopes[0]
+                };
+            }
+            EqRow::Notch => settings.notch.hz = step_notch(settings.notch.hz, step > 0.0),
+            EqRow::NotchWidth => {
+                let octaves = &mut settings.notch.width_octaves;
+                *octaves = (*octaves + step / 5.0).clamp(MIN_NOTCH_OCTAVES, MAX_NOTCH_OCTAVES);
+            }
+            EqRow::RainFade => {
+                let seconds = &mut settings.loops.rain_crossfade_seconds;
+                *seconds = step_loop_fade(*seconds, step, self.loop_limits.rain_crossfade_seconds);
+            }
+            EqRow::SampleFade => {
+                let seconds = &mut settings.loops.sample_crossfade_seconds;
+                *seconds =
+                    step_loop_fade(*seconds, step, self.loop_limits.sample_crossfade_seconds);
+            }
+            EqRow::RainShuffle => settings.loops.rain_shuffle = !settings.loops.rain_shuffle,
+            EqRow::SampleShuffle => {
+                settings.loops.sample_shuffle = !settings.loops.sample_shuffle;
+            }
+            EqRow::Width(band) => {
+                let width = &mut settings.band_widths[band];
+                *width = ((*width + step / 5.0) * 10.0).round() / 10.0;
+                *width = width.clamp(0.0, MAX_BAND_WIDTH);
+            }
+        }
+        false
+    }
+
+    fn handle_side_key(&mut self, key: KeyEvent) -> bool {
+        let step = match key.code {
+            KeyCode::Up => {
+                self.side_selected = self.side_selected.saturating_sub(1);
+                return false;
+            }
+            KeyCode::Down => {
+                self.side_selected = (self.side_selected + 1).min(bands().len());
+                return false;
+            }
+            KeyCode::Left if key.modifiers.contains(KeyModifiers::SHIFT) => -0.01,
+            KeyCode::Right if key.modifiers.contains(KeyModifiers::SHIFT) => 0.01,
+            KeyCode::Left => -0.05,
+            KeyCode::Right => 0.05,
+            KeyCode::Enter if self.side_selected == 0 => 0.0,
+            _ => return self.handle_shared_key(key),
+        };
+        let mut settings = self.lock_settings();
+        match self.side_selected {
+            0 => settings.mid_side = !settings.mid_side,
+            band => {
+                let value = &mut settings.side_bands[band - 1];
+                *value = (*value + step).clamp(0.0, 1.0);
+            }
+        }
+        false
+    }
+
+    fn handle_presets_key(&mut self, key: KeyEvent) -> bool {
+        let confirming = std::mem::take(&mut self.pending_delete);
+        if self.gallery.is_some() {
+            return self.handle_gallery_key(key);
+        }
+        let selected = self.presets.get(self.preset_selected).cloned();
+        match (key.code, selected) {
+            (KeyCode::Up, _) => self.preset_selected = self.preset_selected.saturating_sub(1),
+            (KeyCode::Down, _) => {
+                self.preset_selected =
+                    (self.preset_selected + 1).min(self.presets.len().saturating_sub(1));
+            }
+            (KeyCode::Enter, Some(name)) => self.run_command(Command::LoadPreset(name)),
+            (KeyCode::Char('d' | 'D'), Some(name)) if confirming => {
+                self.run_command(Command::DeletePreset(name));
+            }
+            (KeyCode::Char('d' | 'D'), Some(name)) => {
+                self.pending_delete = true;
+                self.prompt_note = Some(text_with("presets.confirm_delete", &[("name", &name)]));
+            }
+            (KeyCode::Char('g' | 'G'), _) if self.gallery_request.is_none() => {
+                self.open_gallery();
+            }
+            _ => return self.handle_shared_key(key),
+        }
+        false
+    }
+
+    fn handle_gallery_key(&mut self, key: KeyEvent) -> bool {
+        let entries = self.gallery.as_deref().unwrap_or_default();
+        let selected = entries
+            .get(self.gallery_selected)
+            .map(|entry| entry.id.clone());
+        let count = entries.len();
+        match (key.code, selected, self.gallery_url.clone()) {
+            (KeyCode::Up, ..) => self.gallery_selected = self.gallery_selected.saturating_sub(1),
+            (KeyCode::Down, ..) => {
+                self.gallery_selected = (self.gallery_selected + 1).min(count.saturating_sub(1));
+            }
+            (KeyCode::Enter, Some(id), Some(url)) if self.gallery_request.is_none() => {
+                self.gallery_request = Some(gallery::request_preset(url, id));
+                self.prompt_note = Some(text("gallery.downloading").to_owned());
+            }
+            (KeyCode::Char('g' | 'G') | KeyCode::Esc, ..) => self.gallery = None,
+            _ => return self.handle_shared_key(key),
+        }
+        false
+    }
+
+    fn handle_scenes_key(&mut self, key: KeyEvent) -> bool {
+        let selected = self.scenes.get(self.scene_selected).cloned();
+        match (key.code, selected) {
+            (KeyCode::Up, _) => self.scene_selected = self.scene_selected.saturating_sub(1),
+            (KeyCode::Down, _) => {
+                self.scene_selected =
+                    (self.scene_selected + 1).min(self.scenes.len().saturating_sub(1));
+            }
+            (KeyCode::Enter, Some(name)) => self.run_command(Command::LoadScene(name)),
+            (KeyCode::Char('e' | 'E'), Some(name)) => match load_scene(&self.scenes_dir(), &name) {
+                Ok(scene) => self.open_scene_editor(name, scene.editable_layers(), false),
+                Err(error) => self.prompt_note = Some(format!("{error:#}")),
+            },
+            (KeyCode::Char('a' | 'A'), _) => {
+                let layers = playing_layers(&self.lock_settings());
+                self.open_scene_editor(String::new(), layers, true);
+            }
+            _ => return self.handle_shared_key(key),
+        }
+        false
+    }
+
+    /// Opens the editor on `layers`, playing them at once unless a new
+    /// scene's name comes first.
+    fn open_scene_editor(&mut self, name: String, layers: Vec<Layer>, naming: bool) {
+        let mut settings = self.lock_settings();
+        let restore = (settings.mix(), settings.layers);
+        if !naming {
+            play_layers(&layers, &mut settings);
+        }
+        drop(settings);
+        self.muted_levels = [None; SoundStyle::ALL.len()];
+        self.solo_restore = None;
+        self.scene_editor = Some(SceneEditor {
+            name,
+            naming,
+            layers,
+            row: 0,
+            field: 0,
+            changed: false,
+            pending_discard: false,
+            restore,
+        });
+    }
+
+    fn open_trainer(&mut self, drill: Drill) {
+        if let Some(trainer) = self.trainer.take() {
+            trainer.finish(&mut self.lock_settings());
+        }
+        let rng = SmallRng::from_rng(&mut rand::rng());
+        let trainer = Trainer::new(drill, &mut self.lock_settings(), rng);
+        self.trainer = Some(trainer);
+    }
+
+    fn handle_trainer_key(&mut self, key: KeyEvent) {
+        let Some(trainer) = &mut self.trainer else {
+            return;
+        };
+        let mut settings = self
+            .settings
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match key.code {
+            KeyCode::Char(' ') => trainer.switch(&mut settings),
+            KeyCode::Left | KeyCode::Up => trainer.point(-1),
+            KeyCode::Right | KeyCode::Down => trainer.point(1),
+            KeyCode::Enter if trainer.answered().is_some() => trainer.next_round(&mut settings),
+            KeyCode::Enter => {
+                trainer.answer();
+            }
+            KeyCode::Char('q' | 'Q') | KeyCode::Esc => {
+                let (right, rounds, _) = trainer.score();
+                if let Some(trainer) = self.trainer.take() {
+                    trainer.finish(&mut settings);
+                }
+                drop(settings);
+                self.prompt_note = Some(text_with(
+                    "trainer.finished",
+                    &[("right", &right), ("rounds", &rounds)],
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_scene_editor_key(&mut self, key: KeyEvent) {
+        let Some(editor) = &mut self.scene_editor else {
+            return;
+        };
+        if editor.naming {
+            match key.code {
+                KeyCode::Char(character) => editor.name.push(character),
+                KeyCode::Backspace => {
+                    editor.name.pop();
+                }
+                KeyCode::Enter => self.name_scene(),
+                KeyCode::Esc => self.scene_editor = None,
+                _ => {}
+            }
+            return;
+        }
+
+        let discarding = std::mem::take(&mut editor.pending_discard);
+        let sources: Vec<SoundStyle> = editor.layers.iter().map(|layer| layer.source).collect();
+        let fields = SceneField::ALL.len();
+        let shifted = key.modifiers.contains(KeyModifiers::SHIFT);
+        let mut replay = false;
+        let mut note = None;
+        let mut restore = None;
+        match key.code {
+            KeyCode::Up => editor.row = editor.row.saturating_sub(1),
+            KeyCode::Down => {
+                editor.row = (editor.row + 1).min(editor.layers.len().saturating_sub(1));
+            }
+            KeyCode::BackTab => editor.field = (editor.field + fields - 1) % fields,
+            KeyCode::Tab if shifted => editor.field = (editor.field + fields - 1) % fields,
+            KeyCode::Tab => editor.field = (editor.field + 1) % fields,
+            KeyCode::Left | KeyCode::Right => {
+                if let Some(layer) = editor.layers.get_mut(editor.row) {
+                    let up = key.code == KeyCode::Right;
+                    *layer = step_layer(*layer, SceneField::ALL[editor.field], up, &sources);
+                    replay = true;
+                }
+            }
+            KeyCode::Char('a' | 'A') => {
+                match SoundStyle::ALL
+                    .into_iter()
+                    .find(|style| !sources.contains(style))
+                {
+                    Some(source) => {
+                        editor.layers.push(Layer::new(source, 0.5));
+                        editor.row = editor.layers.len() - 1;
+                        replay = true;
+                    }
+                    None => note = Some(text("scene_editor.full").to_owned()),
+                }
+            }
+            KeyCode::Char('x' | 'X') | KeyCode::Delete if editor.row < editor.layers.len() => {
+                editor.layers.remove(editor.row);
+                editor.row = editor.row.min(editor.layers.len().saturating_sub(1));
+                replay = true;
+            }
+            KeyCode::Char('w' | 'W') => return self.save_scene(),
+            KeyCode::Char('q' | 'Q') | KeyCode::Esc if editor.changed && !discarding => {
+                editor.pending_discard = true;
+                note = Some(text_with(
+                    "scene_editor.confirm_discard",
+                    &[("name", &editor.name)],
+                ));
+            }
+            KeyCode::Char('q' | 'Q') | KeyCode::Esc => restore = Some(editor.restore),
+            _ => {}
+        }
+        let layers = replay.then(|| {
+            editor.changed = true;
+            editor.layers.clone()
+        });
+        if note.is_some() {
+            self.prompt_note = note;
+        }
+        let mut settings = self.lock_settings();
+        if let Some(layers) = layers {
+            play_layers(&layers, &mut settings);
+        }
+        if let Some((mix, shapes)) = restore {
+            settings.set_mix(mix);
+            settings.layers = shapes;
+            drop(settings);
+            self.scene_editor = None;
+        }
+    }
+
+    /// Takes the typed name for a new scene, unless it is invalid or taken.
+    fn name_scene(&mut self) {
+        let Some(editor) = &mut self.scene_editor else {
+            return;
+        };
+        let name = match validate_name(&editor.name) {
+            Ok(name) => name.to_owned(),
+            Err(error) => {
+                self.prompt_note = Some(format!("{error:#}"));
+                return;
+            }
+        };
+        if list_scenes(&scenes_beside(&self.presets_dir)).is_ok_and(|scenes| scenes.contains(&name))
+        {
+            self.prompt_note = Some(text_with("scene_editor.exists", &[("name", &name)]));
+            return;
+        }
+        editor.name = name;
+        editor.naming = false;
+        editor.changed = true;
+        let layers = editor.layers.clone();
+        play_layers(&layers, &mut self.lock_settings());
+    }
+
+    /// Writes the edited layers to the scene's file, keeping the rest of it,
+    /// and leaves them playing.
+    fn save_scene(&mut self) {
+        let Some(editor) = &self.scene_editor else {
+            return;
+        };
+        if editor.layers.is_empty() {
+            self.prompt_note = Some(text("scene_editor.no_layers").to_owned());
+            return;
+        }
+        let settings = *self.lock_settings();
+        let name = editor.name.clone();
+        match save_layers(&self.scenes_dir(), &name, &editor.layers, &settings) {
+            Ok(()) => {
+                self.scene_editor = None;
+                self.refresh_scenes();
+                self.scene_selected = self
+                    .scenes
+                    .iter()
+                    .position(|scene| *scene == name)
+                    .unwrap_or(self.scene_selected);
+                self.prompt_note = Some(text_with("scene_editor.saved", &[("name", &name)]));
+            }
+            Err(error) => self.prompt_note = Some(format!("{error:#}")),
+        }
+    }
+
+    fn handle_device_key(&mut self, key: KeyEvent) {
+        let Some(picker) = &mut self.device_picker else {
+            return;
+        };
+        match key.code {
+            KeyCode::Up => picker.selected = picker.selected.saturating_sub(1),
+            KeyCode::Down => {
+                picker.selected = (picker.selected + 1).min(picker.names.len().saturating_sub(1));
+            }
+            KeyCode::Enter => {
+                let name = picker.names[picker.selected].clone();
+                self.device_picker = None;
+                if let Some(output) = &self.output {
+                    self.prompt_note = Some(match output.switch_to(&name) {
+                        Ok(note) => note,
+                        Err(error) => format!("{error:#}"),
+                    });
+                }
+            }
+            KeyCode::Char('d' | 'D' | 'q' | 'Q') | KeyCode::Esc => self.device_picker = None,
+            _ => {}
+        }
+    }
+
+    /// Lists the host's output devices, starting on the one playing.
+    fn open_device_picker(&mut self) {
+        let Some(output) = &self.output else {
+            self.prompt_note = Some(text("devices.unavailable").to_owned());
+            return;
+        };
+        match output.devices() {
+            Ok(names) if names.is_empty() => {
+                self.prompt_note = Some(text("devices.none").to_owned());
+            }
+            Ok(names) => {
+                let playing = output.device_name();
+                let selected = names.iter().position(|name| *name == playing).unwrap_or(0);
+                self.device_picker = Some(DevicePicker { names, selected });
+            }
+            Err(error) => self.prompt_note = Some(format!("{error:#}")),
+        }
+    }
+
+    /// Keys that behave the same on every page.
+    fn handle_shared_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char('d' | 'D') => self.open_device_picker(),
+            KeyCode::Char('u' | 'U') => {
+                if self.remote_url.is_some() {
+                    self.showing_remote = true;
+                } else {
+                    self.prompt_note = Some(text("remote.off").to_owned());
+                }
+            }
+            KeyCode::Char('n' | 'N') => {
+                let mut settings = self.lock_settings();
+                settings.listening_contour = !settings.listening_contour;
+            }
+            KeyCode::Char('r' | 'R') => {
+                let mut settings = self.lock_settings();
+                settings.frequency_bands = [0.5; MAX_BANDS];
+                settings.band_tilts = [0.0; TILT_BANDS.len()];
+                settings.band_widths = [1.0; MAX_BANDS];
+                settings.side_bands = [0.5; MAX_BANDS];
+                settings.band_overlay = BandOverlay::default();
+            }
+            KeyCode::Char('t' | 'T') => {
+                let mut settings = self.lock_settings();
+                settings.sleep_timer =
+                    SleepTimer::next_preset(settings.sleep_timer, self.fade_out, clock::now())
+                        .map(|timer| timer.with_alarm(self.timer_alarm));
+            }
+            KeyCode::Char(' ') => {
+                let mut settings = self.lock_settings();
+                settings.paused = !settings.paused;
+            }
+            KeyCode::Char('m' | 'M') => {
+                let mut settings = self.lock_settings();
+                settings.muted = !settings.muted;
+            }
+            KeyCode::Char('b' | 'B') => self.hold_bypass(clock::now()),
+            KeyCode::Char('l' | 'L') => {
+                let mut settings = self.lock_settings();
+                let other = settings.listeners.active.other();
+                settings.switch_listener(other);
+                let eq = EqSnapshot::of(&settings);
+                drop(settings);
+                // The other listener's EQ is theirs, not a change to undo.
+                self.eq_seen = eq;
+                self.undo.clear();
+            }
+            KeyCode::Char('c' | 'C') => self.compare_eq(),
+            KeyCode::Char(':') => self.open_prompt(""),
+            KeyCode::Char('p' | 'P') => self.open_prompt("preset save "),
+            KeyCode::Char('q' | 'Q') | KeyCode::Esc => return true,
+            _ => {}
+        }
+        false
+    }
+
+    /// Records a change to the EQ since the last key as a step Ctrl+Z can
+    /// take back.
+    fn note_eq_change(&mut self) {
+        let eq = EqSnapshot::of(&self.lock_settings());
+        if eq == self.eq_seen {
+            return;
+        }
+        if self.undo.len() == UNDO_STEPS {
+            self.undo.remove(0);
+        }
+        self.undo.push(std::mem::replace(&mut self.eq_seen, eq));
+    }
+
+    fn undo_eq(&mut self) {
+        self.note_eq_change();
+        let Some(eq) = self.undo.pop() else {
+            self.prompt_note = Some(text("undo.nothing").to_owned());
+            return;
+        };
+        eq.apply(&mut self.lock_settings());
+        self.eq_seen = eq;
+        self.prompt_note = Some(text_with("undo.done", &[("steps", &self.undo.len())]));
+    }
+
+    /// Stops the running recording, or starts one in the recordings folder.
+    fn toggle_recording(&mut self) {
+        let note = match self.recording.take() {
+            Some(recording) => match recording.stop() {
+                Ok(recorded) => recorded.summary(),
+                Err(error) => format!("{error:#}"),
+            },
+            None => {
+                let path = timestamped_path(&self.recordings_dir);
+                match Recording::start(&path, Arc::clone(&self.monitor)) {
+                    Ok(recording) => {
+                        self.recording = Some(recording);
+                        text_with("record.started", &[("path", &path.display())])
+                    }
+                    Err(error) => format!("{error:#}"),
+                }
+            }
+        };
+        self.prompt_note = Some(note);
+    }
+
+    /// Keeps the playing EQ as this side of the comparison and plays the
+    /// other. The first press keeps it as A and plays a copy as B, so
+    /// nothing changes until B is adjusted.
+    fn compare_eq(&mut self) {
+        self.note_eq_change();
+        let current = self.eq_seen;
+        let undo = std::mem::take(&mut self.undo);
+        let compare = self.compare.take().unwrap_or(Compare {
+            playing: Listener::A,
+            other: current,
+            other_undo: Vec::new(),
+        });
+        compare.other.apply(&mut self.lock_settings());
+        self.eq_seen = compare.other;
+        self.undo = compare.other_undo;
+        self.compare = Some(Compare {
+            playing: compare.playing.other(),
+            other: current,
+            other_undo: undo,
+        });
+    }
+
+    fn open_prompt(&mut self, line: &str) {
+        self.prompt = Some(line.to_owned());
+        self.commands.stop_browsing();
+    }
+
+    /// Moving a fader commits it: the source is no longer muted, and a
+    /// pending solo becomes the new mix.
+    fn adjust_level(&mut self, style: SoundStyle, amount: f32) {
+        let index = style_index(style);
+        let mut settings = self.lock_settings();
+        let mut mix = settings.mix();
+        let current = self.muted_levels[index].unwrap_or(mix.level(style));
+        mix.set_level(style, (current + amount).clamp(0.0, 1.0));
+        settings.set_mix(mix);
+        drop(settings);
+        self.muted_levels[index] = None;
+        self.solo_restore = None;
+    }
+
+    fn toggle_mute(&mut self, style: SoundStyle) {
+        let index = style_index(style);
+        let mut mix = self.lock_settings().mix();
+        match self.muted_levels[index].take() {
+            Some(level) => mix.set_level(style, level),
+            None => {
+                self.muted_levels[index] = Some(mix.level(style));
+                mix.set_level(style, 0.0);
+            }
+        }
+        self.lock_settings().set_mix(mix);
+    }
+
+    /// Solo keeps the selected source at its level and silences the rest;
+    /// pressing S again restores the mix from before the solo.
+    fn toggle_solo(&mut self, style: SoundStyle) {
+        if let Some(previous) = self.solo_restore.take() {
+            self.lock_settings().set_mix(previous);
+            return;
+        }
+
+        let index = style_index(style);
+        let mut settings = self.lock_settings();
+        let previous = settings.mix();
+        let level = match previous.level(style) {
+            level if level > 0.0 => level,
+            _ => self.muted_levels[index]
+                .filter(|level| *level > 0.0)
+                .unwrap_or(1.0),
+        };
+        let mut solo = SourceMix::solo(style);
+        solo.set_level(style, level);
+        settings.set_mix(solo);
+        drop(settings);
+        self.muted_levels = [None; SoundStyle::ALL.len()];
+        self.solo_restore = Some(previous);
+    }
+
+    /// Replaces the whole mix, dropping mute and solo bookkeeping that
+    /// referred to the old one.
+    fn replace_mix(&mut self, mix: SourceMix) {
+        self.lock_settings().set_mix(mix);
+        self.muted_levels = [None; SoundStyle::ALL.len()];
+        self.solo_restore = None;
+    }
+
+    /// Up to three digits, a percentage of the slider's travel: for a band
+    /// 50 is 0 dB. Enter sets it, and Esc or an empty entry leaves it.
+    fn handle_value_entry_key(&mut self, key: KeyEvent) {
+        let Some(digits) = self.value_entry.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Char(digit @ '0'..='9') if digits.len() < 3 => digits.push(digit),
+            KeyCode::Backspace => {
+                digits.pop();
+            }
+            KeyCode::Esc => self.value_entry = None,
+            KeyCode::Enter => {
+                let digits = self.value_entry.take().unwrap_or_default();
+                match digits.parse::<u16>() {
+                    Ok(percent) if percent <= 100 => self.set_selected(f32::from(percent) / 100.0),
+                    Ok(_) => self.prompt_note = Some(text("main.entry_range").to_owned()),
+                    Err(_) => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn selected_label(&self) -> String {
+        match self.selected {
+            0 => text("main.volume").to_owned(),
+            band => bands()[band - 1].label().to_owned(),
+        }
+    }
+
+    fn adjust_selected(&self, amount: f32) {
+        let mut settings = self.lock_settings();
+        let value = self.selected_slider(&mut settings);
+        *value = (*value + amount).clamp(0.0, 1.0);
+    }
+
+    /// Alt+Up/Down: onto the next band up or down, painting it at the level
+    /// of the band left behind, so a held Alt paints a shelf across the
+    /// bands and Alt+Left/Right on the way bends it into a curve. The
+    /// volume is never painted; from its row Alt+Down only moves.
+    fn draw_across(&mut self, down: bool) {
+        let from = self.selected;
+        let to = match (from, down) {
+            (0, true) => 1,
+            (0, false) => return,
+            (band, true) => (band + 1).min(bands().len()),
+            (band, false) => (band - 1).max(1),
+        };
+        if from > 0 && to != from {
+            let mut settings = self.lock_settings();
+            settings.frequency_bands[to - 1] = settings.frequency_bands[from - 1];
+        }
+        self.selected = to;
+    }
+
+    fn set_selected(&self, value: f32) {
+        let mut settings = self.lock_settings();
+        *self.selected_slider(&mut settings) = value.clamp(0.0, 1.0);
+    }
+
+    /// The volume on row 0, then the EQ bands.
+    fn selected_slider<'a>(&self, settings: &'a mut AudioSettings) -> &'a mut f32 {
+        match self.selected {
+            0 => &mut settings.volume,
+            band => &mut settings.frequency_bands[band - 1],
+        }
+    }
+
+    fn sleep_timer(&self) -> Option<SleepTimer> {
+        self.lock_settings().sleep_timer
+    }
+
+    fn lock_settings(&self) -> SettingsGuard<'_> {
+        self.settings
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// The whole exam-mode screen: a percentage and a bar as wide as the
+/// terminal, three rows tall, vertically centered.
+fn draw_exam(stdout: &mut impl Write, theme: &Theme, volume: f32, layout: Layout) -> Result<()> {
+    let Layout { columns, rows } = layout;
+    let width = usize::from(columns.saturating_sub(4)).max(10);
+    let filled = (volume.clamp(0.0, 1.0) * width as f32).round() as usize;
+    let top = (rows / 2).saturating_sub(3);
+
+    let label = text_with(
+        "exam.volume",
+        &[("percent", &format!("{:.0}", volume * 100.0))],
+    );
+    let label_column = (usize::from(columns).saturating_sub(label.chars().count()) / 2) as u16;
+    queue!(
+        stdout,
+        cursor::MoveTo(label_column, top),
+        PrintStyledContent(theme.text.apply(label))
+    )?;
+    for row in 0..3 {
+        queue!(stdout, cursor::MoveTo(2, top + 2 + row))?;
+        theme.bar(stdout, filled, width)?;
+    }
+    queue!(
+        stdout,
+        cursor::MoveTo(2, top + 6),
+        PrintStyledContent(theme.dim.apply(text("exam.controls")))
+    )?;
+    Ok(())
+}
+
+/// The whole screen while a visual alarm is up: filled with the alert style in
+/// its lit phase, plain text in the dark one, the message centered.
+fn draw_alarm(
+    stdout: &mut impl Write,
+    theme: &Theme,
+    message: &str,
+    lit: bool,
+    layout: Layout,
+) -> Result<()> {
+    let Layout { columns, rows } = layout;
+    let style = if lit { theme.alert } else { theme.heading };
+    let lines = [text("alarm.title"), message, "", text("alarm.dismiss")];
+    let top = (rows / 2).saturating_sub(2);
+    for row in 0..rows {
+        let line = row
+            .checked_sub(top)
+            .and_then(|index| lines.get(usize::from(index)))
+            .copied()
+            .unwrap_or("");
+        let padding = usize::from(columns).saturating_sub(line.chars().count());
+        let left = padding / 2;
+        let padded = format!("{}{line}{}", " ".repeat(left), " ".repeat(padding - left));
+        queue!(
+            stdout,
+            cursor::MoveTo(0, row),
+            PrintStyledContent(style.apply(padded))
+        )?;
+    }
+    Ok(())
+}
+
+/// The whole screen while U is held up for a phone: the web remote's
+/// address and its QR code, dark on light whatever the theme, since a
+/// camera wants the contrast a printed code has.
+fn draw_remote(stdout: &mut impl Write, theme: &Theme, url: &str, layout: Layout) -> Result<()> {
+    let Layout { columns, rows } = layout;
+    queue!(
+        stdout,
+        cursor::MoveTo(2, 0),
+        PrintStyledContent(
+            theme
+                .heading
+                .apply(text_with("remote.heading", &[("url", &url)]))
+        ),
+        cursor::MoveTo(2, 1),
+        PrintStyledContent(theme.dim.apply(text("remote.controls")))
+    )?;
+    // A phone cannot reach this machine's loopback address.
+    let loopback = url
+        .trim_start_matches("http://")
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .parse::<SocketAddr>()
+        .is_ok_and(|address| address.ip().is_loopback());
+    if loopback {
+        queue!(
+            stdout,
+            cursor::MoveTo(2, 2),
+            PrintStyledContent(theme.notice.apply(text("remote.loopback")))
+        )?;
+    }
+    let Some(code) = QrCode::encode(url.as_bytes()) else {
+        return Ok(());
+    };
+    let lines = code.half_block_rows();
+    let width = code.size() + 2 * QUIET_ZONE;
+    let top = 4;
+    if usize::from(columns) < width || usize::from(rows) < top + lines.len() {
+        let message = text_with(
+            "remote.too_small",
+            &[("columns", &width), ("rows", &(top + lines.len()))],
+        );
+        queue!(
+            stdout,
+            cursor::MoveTo(2, top as u16),
+            PrintStyledContent(theme.notice.apply(message))
+        )?;
+        return Ok(());
+    }
+    let column = ((usize::from(columns) - width) / 2) as u16;
+    for (row, line) in lines.into_iter().enumerate() {
+        queue!(
+            stdout,
+            cursor::MoveTo(column, (top + row) as u16),
+            PrintStyledContent(line.black().on_white())
+        )?;
+    }
+    Ok(())
+}
+
+/// The whole screen while the terminal is too small for the pages: the size
+/// they need, centered. Keys still work, so Q quits.
+fn draw_too_small(stdout: &mut impl Write, theme: &Theme, layout: Layout) -> Result<()> {
+    let message = text_with(
+        "page.too_small",
+        &[
+            ("min_columns", &MIN_COLUMNS),
+            ("min_rows", &MIN_ROWS),
+            ("columns", &layout.columns),
+            ("rows", &layout.rows),
+        ],
+    );
+    let column = usize::from(layout.columns).saturating_sub(message.chars().count()) / 2;
+    queue!(
+        stdout,
+        cursor::MoveTo(column as u16, layout.rows / 2),
+        PrintStyledContent(theme.notice.apply(message))
+    )?;
+    Ok(())
+}
+
+/// Writes a frame in one synchronized update, so terminals that support it
+/// show the regions it redraws at once rather than line by line.
+fn write_frame(frame: &[u8]) -> Result<()> {
+    if frame.is_empty() {
+        return Ok(());
+    }
+    let mut stdout = io::stdout().lock();
+    queue!(stdout, terminal::BeginSynchronizedUpdate)?;
+    stdout.write_all(frame)?;
+    queue!(stdout, ResetColor, terminal::EndSynchronizedUpdate)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// What a visual timer says when it runs out, on screen and in the desktop
+/// notification.
+pub fn alarm_message(length: Duration) -> String {
+    text_with("alarm.message", &[("length", &format_remaining(length))])
+}
+
+/// Per-second output peaks and limiter gain reductions from the audio
+/// monitor, kept for one minute.
+#[derive(Debug, Default)]
+struct LevelHistory {
+    // Each second's peak and deepest reduction in dB, oldest first.
+    seconds: VecDeque<(f32, f32)>,
+    current: (f32, f32),
+    current_started: Option<Instant>,
+}
+
+impl LevelHistory {
+    /// Folds a peak and a reduction into the current second. Returns true
+    /// when a second closes, which is when the footer has a new column to
+    /// draw.
+    fn record(&mut self, peak: f32, reduction: f32, now: Instant) -> bool {
+        self.current = (self.current.0.max(peak), self.current.1.max(reduction));
+        let started = *self.current_started.get_or_insert(now);
+        if now.duration_since(started) < Duration::from_secs(1) {
+            return false;
+        }
+
+        if self.seconds.len() == HISTORY_SECONDS {
+            self.seconds.pop_front();
+        }
+        self.seconds.push_back(std::mem::take(&mut self.current));
+        self.current_started = Some(now);
+        true
+    }
+
+    fn loudest(&self) -> f32 {
+        self.seconds
+            .iter()
+            .fold(0.0, |loudest, (peak, _)| loudest.max(*peak))
+    }
+
+    fn deepest_reduction(&self) -> f32 {
+        self.seconds
+            .iter()
+            .fold(0.0, |deepest, (_, reduction)| deepest.max(*reduction))
+    }
+
+    /// Output level (after the limiter) and limiter gain reduction, one
+    /// character per second. Silent seconds and seconds without reduction
+    /// are blank so activity stands out.
+    fn sparklines(&self) -> (String, String) {
+        let level = self
+            .seconds
+            .iter()
+            .map(|(peak, _)| spark(level_db(*peak) - LEVEL_FLOOR_DB, -LEVEL_FLOOR_DB))
+            .collect();
+        let reduction = self
+            .seconds
+            .iter()
+            .map(|(_, reduction)| match *reduction {
+                reduction if reduction >= LIMITING_FROM_DB => {
+                    spark(reduction, REDUCTION_CEILING_DB)
+                }
+                _ => ' ',
+            })
+            .collect();
+        (level, reduction)
+    }
+}
+
+/// The live output meter's state, in dBFS after the limiter.
+#[derive(Debug)]
+struct OutputMeter {
+    peak_db: f32,
+    rms_db: f32,
+    updated: Option<Instant>,
+    // The deepest gain reduction since the limiter light came on, and when
+    // it goes off.
+    reduction_db: f32,
+    limit_until: Option<Instant>,
+}
+
+impl Default for OutputMeter {
+    fn default() -> Self {
+        Self {
+            peak_db: LEVEL_FLOOR_DB,
+            rms_db: LEVEL_FLOOR_DB,
+            updated: None,
+            reduction_db: 0.0,
+            limit_until: None,
+        }
+    }
+}
+
+impl OutputMeter {
+    /// Takes the output peak and the limiter's gain reduction since the
+    /// last call, and the current RMS. A louder peak shows at once; a
+    /// quieter one lets the mark fall.
+    fn record(&mut self, peak: f32, reduction_db: f32, rms: f32, now: Instant) {
+        let fallen = self.updated.map_or(LEVEL_FLOOR_DB, |then| {
+            self.peak_db
+                - PEAK_FALL_DB_PER_SECOND * now.saturating_duration_since(then).as_secs_f32()
+        });
+        let peak_db = if peak > 0.0 {
+            level_db(peak)
+        } else {
+            LEVEL_FLOOR_DB
+        };
+        self.peak_db = peak_db.max(fallen).max(LEVEL_FLOOR_DB);
+        self.rms_db = (20.0 * rms.max(1e-6).log10()).max(LEVEL_FLOOR_DB);
+        self.updated = Some(now);
+        if reduction_db >= LIMITING_FROM_DB {
+            if self.limiting(now).is_none() {
+                self.reduction_db = 0.0;
+            }
+            self.reduction_db = self.reduction_db.max(reduction_db);
+            self.limit_until = Some(now + LIMIT_HOLD);
+        }
+    }
+
+    /// The deepest gain reduction while the limiter light is on.
+    fn limiting(&self, now: Instant) -> Option<f32> {
+        self.limit_until
+            .is_some_and(|until| now < until)
+            .then_some(self.reduction_db)
+    }
+}
+
+/// Cells of a `width`-cell meter filled to `rms_db`, and the cell holding
+/// the peak mark when it falls past the fill. The scale runs from
+/// `LEVEL_FLOOR_DB` to 0 dBFS.
+fn meter_cells(rms_db: f32, peak_db: f32, width: usize) -> (usize, Option<usize>) {
+    let cells = |db: f32| {
+        ((db - LEVEL_FLOOR_DB) / -LEVEL_FLOOR_DB * width as f32)
+            .round()
+            .clamp(0.0, width as f32) as usize
+    };
+    let filled = cells(rms_db);
+    let peak = cells(peak_db);
+    (filled, (peak > filled).then(|| peak.min(width) - 1))
+}
+
+/// Output level in dBFS for a peak after the limiter.
+fn level_db(peak: f32) -> f32 {
+    20.0 * peak.max(1e-6).log10()
+}
+
+fn spark(value: f32, full_scale: f32) -> char {
+    if value <= 0.0 || !value.is_finite() {
+        return ' ';
+    }
+    let step = (value / full_scale * SPARK_LEVELS.len() as f32).ceil() as usize;
+    SPARK_LEVELS[step.clamp(1, SPARK_LEVELS.len()) - 1]
+}
+
+/// Plays until Ctrl+C with one status line, rewritten in place, instead of
+/// the full-screen UI. Meant for tmux panes and other small terminals, and
+/// used automatically where the full-screen UI cannot draw.
+pub fn run_status_line(
+    settings: &SharedSettings,
+    running: &AtomicBool,
+    monitor: &AudioMonitor,
+    exposure: &Exposure,
+) -> Result<()> {
+    let mut stdout = io::stdout();
+    // Blanks over a shorter line instead of an erase sequence, so the line
+    // also stays clean on terminals with no escape support at all.
+    let mut previous_width: usize = 0;
+    while running.load(Ordering::Relaxed) {
+        std::thread::sleep(Duration::from_millis(250));
+        let current = *settings
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let now = clock::now();
+        if let Some(timer) = current.sleep_timer.filter(|timer| timer.expired(now)) {
+            if timer.alarm() == TimerAlarm::Fade {
+                break;
+            }
+            // A visual timer leaves its message above the line and plays on.
+            settings
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .sleep_timer = None;
+            let message = alarm_message(timer.length());
+            queue!(
+                stdout,
+                Print("\r"),
+                Print(format!("{message:<previous_width$}")),
+                Print("\r\n")
+            )?;
+            previous_width = 0;
+            if let Err(error) = notify::send(text("alarm.title"), &message) {
+                queue!(
+                    stdout,
+                    Print(text_with(
+                        "alarm.notify_failed",
+                        &[("error", &format!("{error:#}"))]
+                    )),
+                    Print("\r\n")
+                )?;
+            }
+            continue;
+        }
+        let mut line = status_line(current, monitor.take_peak(), now);
+        let resets = monitor.dsp_resets();
+        if resets > 0 {
+            line.push_str(&text_with("status.dsp_resets", &[("count", &resets)]));
+        }
+        let restarts = monitor.engine_restarts();
+        if restarts > 0 {
+            line.push_str(&text_with(
+                "status.engine_restarts",
+                &[("count", &restarts)],
+            ));
+        }
+        let silences = monitor.silence_restarts();
+        if silences > 0 {
+            line.push_str(&text_with(
+                "status.silence_restarts",
+                &[("count", &silences)],
+            ));
+        }
+        let refused = monitor.refused_buffer();
+        if refused > 0 {
+            line.push_str(&text_with("status.refused_buffer", &[("frames", &refused)]));
+        }
+        if monitor.output_lost() {
+            line.push_str(text("status.reconnecting"));
+        }
+        if exposure.over() {
+            line.push_str(&text_with(
+                "status.exposure",
+                &[("percent", &format!("{:.0}", exposure.projected() * 100.0))],
+            ));
+        }
+        let width = line.chars().count();
+        queue!(
+            stdout,
+            Print("\r"),
+            Print(line),
+            Print(" ".repeat(previous_width.saturating_sub(width)))
+        )?;
+        previous_width = width;
+        stdout.flush()?;
+    }
+    queue!(stdout, Print("\r\n"))?;
+    stdout.flush()?;
+    Ok(())
+}
+
+fn status_line(settings: AudioSettings, peak: f32, now: Instant) -> String {
+    const METER_WIDTH: usize = 10;
+    let meter = if peak > 0.0 {
+        let db = level_db(peak);
+        let filled = (((db - LEVEL_FLOOR_DB) / -LEVEL_FLOOR_DB).clamp(0.0, 1.0)
+            * METER_WIDTH as f32)
+            .round() as usize;
+        format!(
+            "[{}{}] {db:+.0} dBFS",
+            "#".repeat(filled),
+            "-".repeat(METER_WIDTH - filled)
+        )
+    } else {
+        format!("[{}] {}", "-".repeat(METER_WIDTH), text("status.silent"))
+    };
+    let timer = match settings.sleep_timer {
+        Some(timer) => text_with(
+            if timer.alarm() == TimerAlarm::Visual {
+                "status.alarm"
+            } else if timer.fading(now) {
+                "status.fading"
+            } else {
+                "status.sleep"
+            },
+            &[("time", &format_remaining(timer.remaining(now)))],
+        ),
+        None => String::new(),
+    };
+    text_with(
+        "status.line",
+        &[
+            ("source", &settings.mix().describe()),
+            ("percent", &format!("{:.0}", settings.volume * 100.0)),
+            (
+                "paused",
+                &match (settings.paused, settings.muted) {
+                    (true, _) => text("status.paused"),
+                    (false, true) => text("status.muted"),
+                    (false, false) => "",
+                },
+            ),
+            ("timer", &timer),
+            ("meter", &meter),
+        ],
+    )
+}
+
+fn timer_line(timer: Option<SleepTimer>, now: Instant) -> String {
+    let Some(timer) = timer else {
+        return text("footer.timer_off").to_owned();
+    };
+    let remaining = format_remaining(timer.remaining(now));
+    if timer.alarm() == TimerAlarm::Visual {
+        text_with("footer.timer_visual", &[("time", &remaining)])
+    } else if timer.fading(now) {
+        text_with("footer.timer_fading", &[("time", &remaining)])
+    } else {
+        text_with(
+            "footer.timer_running",
+            &[
+                ("time", &remaining),
+                ("fade", &format_remaining(timer.fade())),
+            ],
+        )
+    }
+}
+
+/// The expanded line under the main page's sliders: the selected control's
+/// exact value and, for a band, its range and effect on the output level.
+fn main_readout(settings: AudioSettings, selected: usize) -> String {
+    if selected == 0 {
+        if settings.volume <= 0.0 {
+            return text("readout.volume_muted").to_owned();
+        }
+        let headroom = headroom_db(settings);
+        return text_with(
+            if headroom < 0.0 {
+                "readout.volume_headroom"
+            } else {
+                "readout.volume"
+            },
+            &[
+                ("percent", &format!("{:.0}", settings.volume * 100.0)),
+                ("db", &format!("{:+.1}", 20.0 * settings.volume.log10())),
+                ("headroom", &format!("{headroom:+.1}")),
+            ],
+        );
+    }
+
+    let index = selected - 1;
+    let band = &bands()[index];
+    let value = settings.frequency_bands[index];
+    let corrected = settings.room_correction_db[index] != 0.0;
+    let contour = match (settings.listening_contour, corrected) {
+        (false, false) => None,
+        (true, false) => Some("readout.band_contour"),
+        (false, true) => Some("readout.band_correction"),
+        (true, true) => Some("readout.band_contour_correction"),
+    }
+    .map(|key| {
+        text_with(
+            key,
+            &[("db", &format!("{:+.1}", band_gain_db(settings, index)))],
+        )
+    })
+    .unwrap_or_default();
+    text_with(
+        "readout.band",
+        &[
+            ("band", &band.label()),
+            ("min", &format!("{:.0}", band.min_freq)),
+            ("max", &format!("{:.0}", band.max_freq)),
+            ("percent", &format!("{:.0}", value * 100.0)),
+            ("db", &format!("{:+.1}", slider_to_db(value))),
+            ("contour", &contour),
+            (
+                "rms",
+                &format!("{:+.1}", band_rms_contribution_db(settings, index)),
+            ),
+            (
+                "mid",
+                &if settings.mid_side {
+                    text("readout.band_mid")
+                } else {
+                    ""
+                },
+            ),
+        ],
+    )
+}
+
+/// The mode on row 0, or a side slider against the band slider it
+/// pairs with.
+fn side_readout(settings: AudioSettings, selected: usize) -> String {
+    let Some(index) = selected.checked_sub(1) else {
+        return text(if settings.mid_side {
+            "readout.mid_side_on"
+        } else {
+            "readout.mid_side_off"
+        })
+        .to_owned();
+    };
+    text_with(
+        if settings.mid_side {
+            "readout.side_band"
+        } else {
+            "readout.side_band_waiting"
+        },
+        &[
+            ("band", &bands()[index].label()),
+            (
+                "side",
+                &format!("{:+.1}", slider_to_db(settings.side_bands[index])),
+            ),
+            (
+                "mid",
+                &format!("{:+.1}", slider_to_db(settings.frequency_bands[index])),
+            ),
+        ],
+    )
+}
+
+/// The next third-octave step above or below `ceiling`. Raising it past
+/// the top step turns it off; lowering it from off starts at the top.
+fn step_ceiling(ceiling: Option<f32>, up: bool) -> Option<f32> {
+    match (ceiling, up) {
+        (None, true) => None,
+        (None, false) => Some(MAX_CEILING_HZ),
+        (Some(hz), true) => CEILING_STEPS_HZ.into_iter().find(|step| *step > hz),
+        (Some(hz), false) => Some(
+            CEILING_STEPS_HZ
+                .into_iter()
+                .rev()
+                .find(|step| *step < hz)
+                .unwrap_or(MIN_CEILING_HZ),
+        ),
+    }
+}
+
+fn ceiling_readout(ceiling: Option<f32>) -> String {
+    match ceiling {
+        Some(hz) => text_with(
+            "readout.ceiling",
+            &[
+                ("hz", &format!("{hz:.0}")),
+                ("octave", &format!("{:.0}", 2.0 * hz)),
+            ],
+        ),
+        None => text("readout.ceiling_off").to_owned(),
+    }
+}
+
+/// The next step above or below the high-pass corner. Lowering it past the
+/// bottom step turns it off; raising it from off starts at the bottom.
+fn step_high_pass(high_pass: Option<f32>, up: bool) -> Option<f32> {
+    match (high_pass, up) {
+        (None, true) => Some(MIN_HIGH_PASS_HZ),
+        (None, false) => None,
+        (Some(hz), true) => Some(
+            HIGH_PASS_STEPS_HZ
+                .into_iter()
+                .find(|step| *step > hz)
+                .unwrap_or(MAX_HIGH_PASS_HZ),
+        ),
+        (Some(hz), false) => HIGH_PASS_STEPS_HZ.into_iter().rev().find(|step| *step < hz),
+    }
+}
+
+fn high_pass_readout(high_pass: HighPass) -> String {
+    match high_pass.hz {
+        Some(hz) => text_with(
+            "readout.high_pass",
+            &[
+                ("hz", &format_hz(hz)),
+                ("slope", &high_pass.slope_db.to_string()),
+                ("octave", &format_hz(hz / 2.0)),
+            ],
+        ),
+        None => text("readout.high_pass_off").to_owned(),
+    }
+}
+
+/// The next semitone above or below the notch center, fine enough to match
+/// a tinnitus pitch by ear. The steps are a scale through 1 kHz rounded to
+/// whole hertz, so its octaves land on round numbers and a typed center
+/// moves onto it. Lowering it past the bottom turns it off; raising it from
+/// off starts at `DEFAULT_NOTCH_HZ`.
+fn step_notch(notch: Option<f32>, up: bool) -> Option<f32> {
+    let Some(hz) = notch else {
+        return up.then_some(DEFAULT_NOTCH_HZ);
+    };
+    // The slack covers the rounding, which is worth up to 0.04 semitone
+    // at the bottom.
+    let semitones = 12.0 * (hz / 1_000.0).log2();
+    let next = if up {
+        (semitones + 0.1).floor() + 1.0
+    } else {
+        (semitones - 0.1).ceil() - 1.0
+    };
+    let hz = (1_000.0 * 2.0_f32.powf(next / 12.0)).round();
+    if up {
+        Some(hz.min(MAX_NOTCH_HZ))
+    } else {
+        Some(hz).filter(|hz| *hz >= MIN_NOTCH_HZ)
+    }
+}
+
+fn notch_readout(notch: Notch) -> String {
+    match notch.hz {
+        Some(hz) => {
+            let (low, high) = notch.edges_hz(hz);
+            text_with(
+                "readout.notch",
+                &[
+                    ("hz", &format!("{hz:.0}")),
+                    ("octaves", &format!("{:.1}", notch.width_octaves)),
+                    ("low", &format!("{low:.0}")),
+                    ("high", &format!("{high:.0}")),
+                ],
+            )
+        }
+        None => text("readout.notch_off").to_owned(),
+    }
+}
+
+/// Half a second at a time, no longer than the loop allows; a loop too short
+/// for the minimum still gets it, and the player shortens it.
+fn step_loop_fade(seconds: f32, step: f32, limit: f32) -> f32 {
+    (seconds + step).min(limit).max(MIN_LOOP_CROSSFADE_SECONDS)
+}
+
+fn loop_fade_readout(source: SoundStyle, seconds: f32, limit: f32) -> String {
+    text_with(
+        "readout.loop_fade",
+        &[
+            ("source", &source.label()),
+            ("seconds", &format!("{seconds:.1}")),
+            ("length", &format!("{:.1}", limit * 3.0)),
+            ("max", &format!("{limit:.1}")),
+        ],
+    )
+}
+
+/// A shuffle row's line. `limit` is a third of the loop's length, as for
+/// the fades, and `seconds` its fade.
+fn shuffle_readout(source: SoundStyle, shuffle: bool, seconds: f32, limit: f32) -> String {
+    let length = limit * 3.0;
+    let key = if length >= SHUFFLE_MAX_SECONDS {
+        "readout.shuffle_long"
+    } else if shuffle {
+        "readout.shuffle_on"
+    } else {
+        "readout.shuffle_off"
+    };
+    text_with(
+        key,
+        &[
+            ("source", &source.label()),
+            ("length", &format!("{length:.1}")),
+            ("seconds", &format!("{seconds:.1}")),
+            ("max", &format!("{SHUFFLE_MAX_SECONDS:.0}")),
+        ],
+    )
+}
+
+/// The advanced EQ page's line: how far apart the tilted band's edges end
+/// up, since the shelves do not reach the full tilt inside the band.
+/// What the band's width factor makes of the stereo width.
+fn band_width_readout(settings: AudioSettings, band: usize) -> String {
+    let factor = settings.band_widths[band];
+    text_with(
+        if factor == 1.0 {
+            "readout.band_width_follow"
+        } else {
+            "readout.band_width"
+        },
+        &[
+            ("band", &bands()[band].label()),
+            ("percent", &format!("{:.0}", factor * 100.0)),
+            (
+                "width",
+                &format!("{:.0}", settings.band_width(band) * 100.0),
+            ),
+        ],
+    )
+}
+
+fn tilt_readout(settings: AudioSettings, tilt: usize) -> String {
+    let band = &FREQUENCY_BANDS[TILT_BANDS[tilt]];
+    let value = settings.band_tilts[tilt];
+    let center = format!("{:.0}", band.center_frequency());
+    if value == 0.0 {
+        return text_with(
+            "readout.tilt_flat",
+            &[("band", &band.label()), ("center", &center)],
+        );
+    }
+    text_with(
+        "readout.tilt",
+        &[
+            ("band", &band.label()),
+            ("db", &format!("{value:+.1}")),
+            ("min", &format!("{:.0}", band.min_freq)),
+            ("max", &format!("{:.0}", band.max_freq)),
+            (
+                "edges",
+                &format!("{:+.1}", tilt_edge_difference_db(tilt, value)),
+            ),
+            ("center", &center),
+        ],
+    )
+}
+
+fn mixer_readout(style: SoundStyle, level: f32) -> String {
+    if level <= 0.0 {
+        return text_with("readout.source_silent", &[("source", &style.label())]);
+    }
+    text_with(
+        "readout.source",
+        &[
+            ("source", &style.label()),
+            ("percent", &format!("{:.0}", level * 100.0)),
+            ("db", &format!("{:+.1}", 10.0 * level.log10())),
+        ],
+    )
+}
+
+fn width_readout(width: f32) -> String {
+    let name = match width {
+        width if width <= 0.0 => "readout.width_mono",
+        width if width < 0.35 => "readout.width_narrow",
+        width if width < 0.75 => "readout.width_normal",
+        _ => "readout.width_wide",
+    };
+    text_with(
+        "readout.width",
+        &[
+            ("percent", &format!("{:.0}", width * 100.0)),
+            ("name", &text(name)),
+            ("correlation", &format!("{:.2}", stereo_correlation(width))),
+        ],
+    )
+}
+
+fn tone_readout(tone: ToneSettings) -> String {
+    let beat = format_hz(tone.beat_hz);
+    match tone.mode {
+        ToneMode::Off => text("readout.tone_off").to_owned(),
+        ToneMode::Binaural => text_with(
+            "readout.binaural",
+            &[
+                ("left", &format_hz(tone.carrier_hz - tone.beat_hz / 2.0)),
+                ("right", &format_hz(tone.carrier_hz + tone.beat_hz / 2.0)),
+                ("beat", &beat),
+            ],
+        ),
+        ToneMode::Isochronic => text_with(
+            "readout.isochronic",
+            &[
+                ("carrier", &format!("{:.0}", tone.carrier_hz)),
+                ("beat", &beat),
+            ],
+        ),
+    }
+}
+
+/// The drift periods Left and Right step through, in seconds.
+const DRIFT_PERIODS: [f32; 12] = [
+    10.0, 20.0, 30.0, 45.0, 60.0, 90.0, 120.0, 180.0, 240.0, 300.0, 420.0, 600.0,
+];
+
+/// The next of `DRIFT_PERIODS` past `seconds` in `direction`, or the end
+/// of the list.
+fn step_drift_period(seconds: f32, direction: f32) -> f32 {
+    let next = if direction > 0.0 {
+        DRIFT_PERIODS.iter().find(|period| **period > seconds)
+    } else {
+        DRIFT_PERIODS.iter().rev().find(|period| **period < seconds)
+    };
+    next.copied().unwrap_or(seconds)
+}
+
+/// `45 s`, `2 min`, or `1.5 min`.
+fn format_period(seconds: f32) -> String {
+    if seconds < 60.0 {
+        format!("{seconds:.0} s")
+    } else {
+        format!("{} min", format_hz(seconds / 60.0))
+    }
+}
+
+fn drift_readout(drift: DriftSettings) -> String {
+    if !drift.enabled {
+        return text("readout.drift_off").to_owned();
+    }
+    text_with(
+        "readout.drift",
+        &[
+            ("percent", &format!("{:.0}", drift.depth * 100.0)),
+            ("period", &format_period(drift.period_seconds)),
+        ],
+    )
+}
+
+fn tone_level_readout(tone: ToneSettings) -> String {
+    if tone.level <= 0.0 {
+        return text("readout.tone_level_silent").to_owned();
+    }
+    text_with(
+        "readout.tone_level",
+        &[
+            ("percent", &format!("{:.0}", tone.level * 100.0)),
+            ("db", &format!("{:+.1}", 10.0 * tone.level.log10())),
+        ],
+    )
+}
+
+/// Key legends, which stay as printed on the keyboard, and message keys for
+/// what they do. A row without keys is a section heading; an empty row is
+/// a gap.
+const HELP_LINES: &[(&str, &str)] = &[
+    ("Tab / Shift+Tab", "help.next_page"),
+    ("N", "help.contour"),
+    ("R", "help.reset"),
+    ("T", "help.timer"),
+    ("B (hold)", "help.bypass"),
+    ("L", "help.listener"),
+    ("C", "help.compare"),
+    ("Ctrl+Z", "help.undo"),
+    ("Ctrl+R", "help.record"),
+    ("Space", "help.pause"),
+    ("M", "help.mute"),
+    ("Volume keys", "help.volume_keys"),
+    (":", "help.command"),
+    ("P", "help.save_preset"),
+    ("D", "help.devices"),
+    ("U", "help.remote"),
+    ("Q / Esc", "help.quit"),
+    ("", ""),
+    ("", "help.main_page"),
+    ("Up / Down", "help.main_select"),
+    ("Left / Right", "help.main_adjust"),
+    ("Shift+Left/Right", "help.main_fine"),
+    ("Alt+Up / Down", "help.main_draw"),
+    ("PgUp / PgDn", "help.main_jump"),
+    ("1 - 9", "help.main_digits"),
+    ("Enter", "help.main_entry"),
+    ("S", "help.main_solo"),
+    ("M", "help.main_band_mute"),
+    ("O", "help.main_band_solo"),
+    ("", ""),
+    ("", "help.mixer_page"),
+    ("Up / Down", "help.mixer_select"),
+    ("Left / Right", "help.mixer_adjust"),
+    ("M", "help.mixer_mute"),
+    ("S", "help.mixer_solo"),
+    ("", ""),
+    ("", "help.eq_page"),
+    ("Up / Down", "help.eq_select"),
+    ("Left / Right", "help.eq_adjust"),
+    ("", ""),
+    ("", "help.side_page"),
+    ("Up / Down", "help.side_select"),
+    ("Left / Right", "help.side_adjust"),
+    ("Enter", "help.side_toggle"),
+    ("", ""),
+    ("", "help.presets_page"),
+    ("Enter", "help.presets_load"),
+    ("D", "help.presets_delete"),
+    ("G", "help.presets_gallery"),
+    ("", ""),
+    ("", "help.scenes_page"),
+    ("Enter", "help.scenes_load"),
+    ("E", "help.scenes_edit"),
+    ("A", "help.scenes_new"),
+    ("", ""),
+    ("", "help.scene_editor"),
+    ("Up / Down", "help.scene_editor_select"),
+    ("Tab / Shift+Tab", "help.scene_editor_column"),
+    ("Left / Right", "help.scene_editor_adjust"),
+    ("A / X", "help.scene_editor_layers"),
+    ("W", "help.scene_editor_save"),
+    ("Esc", "help.scene_editor_close"),
+    ("", ""),
+    ("", "help.trainer"),
+    ("Space", "help.trainer_switch"),
+    ("Left / Right", "help.trainer_point"),
+    ("Enter", "help.trainer_answer"),
+    ("Esc", "help.trainer_close"),
+];
+
+fn draw_help(stdout: &mut impl Write, theme: &Theme, scroll: usize, layout: Layout) -> Result<()> {
+    let rows = layout.help_rows();
+    let visible = HELP_LINES.iter().skip(scroll).take(rows);
+    for (index, (keys, message)) in visible.enumerate() {
+        queue!(stdout, cursor::MoveTo(2, 2 + index as u16))?;
+        match (*keys, *message) {
+            (_, "") => {}
+            ("", heading) => queue!(
+                stdout,
+                PrintStyledContent(theme.heading.apply(text(heading)))
+            )?,
+            (keys, action) => queue!(
+                stdout,
+                PrintStyledContent(theme.text.apply(format!("{keys:<18}"))),
+                PrintStyledContent(theme.dim.apply(text(action)))
+            )?,
+        }
+    }
+    queue!(
+        stdout,
+        cursor::MoveTo(2, 2 + rows as u16),
+        PrintStyledContent(theme.dim.apply(text_with(
+            "help.scroll",
+            &[
+                ("first", &(scroll + 1)),
+                ("last", &(scroll + rows)),
+                ("count", &HELP_LINES.len()),
+            ]
+        )))
+    )?;
+    Ok(())
+}
+
+/// One cell of a scene editor row.
+fn layer_cell(layer: &Layer, field: SceneField) -> String {
+    let corner = |hz: Option<f32>| match hz {
+        Some(hz) => text_with("scene_editor.hz", &[("hz", &format_hz(hz))]),
+        None => text("scene_editor.off").to_owned(),
+    };
+    match (field, layer.modulation) {
+        (SceneField::Source, _) => layer.source.label().to_owned(),
+        (SceneField::Level, _) => format!("{:.0}%", layer.level * 100.0),
+        (SceneField::HighPass, _) => corner(layer.high_pass_hz),
+        (SceneField::LowPass, _) => corner(layer.low_pass_hz),
+        (SceneField::Swell, Some(swell)) if swell.depth > 0.0 => {
+            format!("{:.0}%", swell.depth * 100.0)
+        }
+        (SceneField::Period, Some(swell)) => text_with(
+            "scene_editor.seconds",
+            &[("seconds", &format_hz(swell.period_seconds))],
+        ),
+        (SceneField::Swell, _) => text("scene_editor.off").to_owned(),
+        (SceneField::Period, None) => "-".to_owned(),
+    }
+}
+
+/// `layer` with one field stepped. Sources step to the next one no other
+/// layer uses; levels and swell depths by 5% and 10%; filter corners by
+/// third-octaves, turning off past their outer end; periods through
+/// `SWELL_STEPS_SECONDS`, once the layer swells.
+fn step_layer(layer: Layer, field: SceneField, up: bool, taken: &[SoundStyle]) -> Layer {
+    let step_index = |index: usize, offset: usize| {
+        let count = SoundStyle::ALL.len();
+        if up {
+            (index + offset) % count
+        } else {
+            (index + count - offset) % count
+        }
+    };
+    match field {
+        SceneField::Source => Layer {
+            source: (1..SoundStyle::ALL.len())
+                .map(|offset| SoundStyle::ALL[step_index(style_index(layer.source), offset)])
+                .find(|style| !taken.contains(style))
+                .unwrap_or(layer.source),
+            ..layer
+        },
+        SceneField::Level => Layer {
+            level: ((layer.level * 20.0).round() + if up { 1.0 } else { -1.0 }).clamp(0.0, 20.0)
+                / 20.0,
+            ..layer
+        },
+        SceneField::HighPass => Layer {
+            high_pass_hz: match (layer.high_pass_hz, up) {
+                (None, true) => Some(MIN_LAYER_HZ),
+                (None, false) => None,
+                (Some(hz), true) => Some(
+                    LAYER_STEPS_HZ
+                        .into_iter()
+                        .find(|step| *step > hz)
+                        .unwrap_or(MAX_LAYER_HZ),
+                ),
+                (Some(hz), false) => LAYER_STEPS_HZ.into_iter().rev().find(|step| *step < hz),
+            },
+            ..layer
+        },
+        SceneField::LowPass => Layer {
+            low_pass_hz: match (layer.low_pass_hz, up) {
+                (None, true) => None,
+                (None, false) => Some(MAX_LAYER_HZ),
+                (Some(hz), true) => LAYER_STEPS_HZ.into_iter().find(|step| *step > hz),
+                (Some(hz), false) => Some(
+                    LAYER_STEPS_HZ
+                        .into_iter()
+                        .rev()
+                        .find(|step| *step < hz)
+                        .unwrap_or(MIN_LAYER_HZ),
+                ),
+            },
+            ..layer
+        },
+        SceneField::Swell => {
+            let swell = layer.modulation.unwrap_or_default();
+            let depth = ((swell.depth * 10.0).round() + if up { 1.0 } else { -1.0 })
+                .clamp(0.0, 10.0)
+                / 10.0;
+            Layer {
+                modulation: (depth > 0.0).then_some(Modulation { depth, ..swell }),
+                ..layer
+            }
+        }
+        SceneField::Period => Layer {
+            modulation: layer.modulation.map(|swell| Modulation {
+                period_seconds: if up {
+                    SWELL_STEPS_SECONDS
+                        .into_iter()
+                        .find(|step| *step > swell.period_seconds)
+                        .unwrap_or(swell.period_seconds)
+                } else {
+                    SWELL_STEPS_SECONDS
+                        .into_iter()
+                        .rev()
+                        .find(|step| *step < swell.period_seconds)
+                        .unwrap_or(swell.period_seconds)
+                },
+                ..swell
+            }),
+            ..layer
+        },
+    }
+}
+
+fn style_index(style: SoundStyle) -> usize {
+    SoundStyle::ALL
+        .iter()
+        .position(|candidate| *candidate == style)
+        .unwrap_or(0)
+}
+
+/// Arrows beside the first and last rows a list shows while it has more above
+/// or below them.
+fn draw_scroll_marks(
+    stdout: &mut impl Write,
+    theme: &Theme,
+    selected: usize,
+    count: usize,
+    visible: usize,
+    top: u16,
+) -> Result<()> {
+    let first = first_visible(selected, visible);
+    if first > 0 {
+        queue!(
+            stdout,
+            cursor::MoveTo(0, top),
+            PrintStyledContent(theme.dim.apply('↑'))
+        )?;
+    }
+    if first + visible < count {
+        queue!(
+            stdout,
+            cursor::MoveTo(0, top + visible as u16 - 1),
+            PrintStyledContent(theme.dim.apply('↓'))
+        )?;
+    }
+    Ok(())
+}
+
+/// A row with a named choice in place of a slider bar; a row scrolled out of
+/// view is None and draws nothing.
+fn draw_choice(
+    stdout: &mut impl Write,
+    theme: &Theme,
+    name: &str,
+    value: &str,
+    row: Option<u16>,
+    selected: bool,
+) -> Result<()> {
+    let Some(row) = row else {
+        return Ok(());
+    };
+    let (marker, style) = if selected {
+        ('>', theme.selected)
+    } else {
+        (' ', theme.text)
+    };
+    queue!(
+        stdout,
+        cursor::MoveTo(2, row),
+        PrintStyledContent(style.apply(format!("{marker} {name:<12} < {value} >")))
+    )?;
+    Ok(())
+}
+
+/// Where a click in `column` falls along a slider, from 0 at the first cell
+/// to 1 at the last. The brackets count as the ends; anything else is None.
+fn slider_position(column: u16) -> Option<f32> {
+    let last = SLIDER_COLUMN + SLIDER_WIDTH as u16 - 1;
+    (SLIDER_COLUMN - 1..=last + 1).contains(&column).then(|| {
+        (f32::from(column.saturating_sub(SLIDER_COLUMN)) / f32::from(last - SLIDER_COLUMN)).min(1.0)
+    })
+}
+
+/// A named bar with its value; a row scrolled out of view is None and draws
+/// nothing.
+fn draw_slider(
+    stdout: &mut impl Write,
+    theme: &Theme,
+    name: &str,
+    value: f32,
+    row: Option<u16>,
+    selected: bool,
+    value_label: &str,
+) -> Result<()> {
+    let Some(row) = row else {
+        return Ok(());
+    };
+    let filled = (value.clamp(0.0, 1.0) * SLIDER_WIDTH as f32).round() as usize;
+    let (marker, style) = if selected {
+        ('>', theme.selected)
+    } else {
+        (' ', theme.text)
+    };
+    queue!(
+        stdout,
+        cursor::MoveTo(2, row),
+        PrintStyledContent(style.apply(format!("{marker} {name:<12} [")))
+    )?;
+    theme.bar(stdout, filled, SLIDER_WIDTH)?;
+    queue!(
+        stdout,
+        PrintStyledContent(theme.text.apply(format!("] {value_label}")))
+    )?;
+    Ok(())
+}
+
+struct TerminalSession {
+    enhanced_keys: bool,
+}
+
+impl TerminalSession {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        if let Err(error) = execute!(io::stdout(), EnterAlternateScreen, cursor::Hide) {
+            let _ = terminal::disable_raw_mode();
+            return Err(error.into());
+        }
+        // Terminals that speak the kitty keyboard protocol send the volume
+        // keys only once escape codes are disambiguated; the others never
+        // send them. Releases stay off, so the bypass hold is unchanged.
+        let enhanced_keys = terminal::supports_keyboard_enhancement().unwrap_or(false)
+            && execute!(
+                io::stdout(),
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+            )
+            .is_ok();
+        // Clicks and the wheel reach the sliders. Most terminals still
+        // select text with Shift held.
+        let _ = execute!(io::stdout(), EnableMouseCapture);
+        // A line longer than a narrow terminal is cut off rather than
+        // wrapped onto the row below, which belongs to something else.
+        let _ = execute!(io::stdout(), terminal::DisableLineWrap);
+        Ok(Self { enhanced_keys })
+    }
+}
+
+impl Drop for TerminalSession {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), terminal::EnableLineWrap, DisableMouseCapture);
+        if self.enhanced_keys {
+            let _ = execute!(io::stdout(), PopKeyboardEnhancementFlags);
+        }
+        let _ = terminal::disable_raw_mode();
+        let _ = execute!(io::stdout(), cursor::Show, LeaveAlternateScreen);
+    }
+}
+
+// Key handling and slider adjustment are tested directly; the rendering and
+// raw-terminal paths (draw, draw_slider, TerminalSession, run) are exempt as
+// terminal-bound, per the CLAUDE.md decision log.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{Listener, SoundStyle};
+
+    fn ui() -> InteractiveUi {
+        let mut ui = InteractiveUi::new(
+            Arc::new(SharedSettings::new(AudioSettings::default())),
+            Arc::new(AtomicBool::new(true)),
+            Arc::new(AudioMonitor::default()),
+        );
+        ui.presets_dir = scratch_presets("none");
+        ui.notifier = |_, _| Ok(());
+        ui
+    }
+
+    fn scratch_presets(label: &str) -> PathBuf {
+        let mut path = std::env::temp_dir();
+        path.push(format!("whitenoise-ui-test-{}-{label}", std::process::id()));
+        path.push("presets");
+        path
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn settings(ui: &InteractiveUi) -> AudioSettings {
+        *ui.settings.lock().unwrap()
+    }
+
+    #[test]
+    fn visual_timers_raise_an_alarm_and_keep_playing() {
+        static NOTIFIED: AtomicBool = AtomicBool::new(false);
+        let mut ui = ui().with_timer_alarm(TimerAlarm::Visual);
+        ui.notifier = |title, body| {
+            assert_eq!(title, "Timer done");
+            assert_eq!(body, "The 15:00 timer is up.");
+            NOTIFIED.store(true, Ordering::Relaxed);
+            Ok(())
+        };
+        let volume = settings(&ui).volume;
+
+        ui.handle_key(key(KeyCode::Char('t')));
+        let timer = settings(&ui).sleep_timer.unwrap();
+        assert_eq!(timer.alarm(), TimerAlarm::Visual);
+        let start = Instant::now();
+        assert!(!ui.check_timer(start));
+        assert!(ui.alarm.is_none());
+
+        let expiry = start + timer.length() + Duration::from_secs(1);
+        assert!(!ui.check_timer(expiry), "a visual timer must not quit");
+        assert!(NOTIFIED.load(Ordering::Relaxed));
+        assert!(settings(&ui).sleep_timer.is_none());
+        assert_eq!(settings(&ui).volume, volume);
+        assert_eq!(ui.alarm_lit(expiry), Some(true));
+        assert_eq!(
+            ui.alarm_lit(expiry + Duration::from_millis(600)),
+            Some(false)
+        );
+        assert_eq!(ui.alarm_lit(expiry + ALARM_FLASH), Some(true));
+
+        // The dismissing key is consumed: Q here does not quit.
+        assert!(!ui.handle_key(key(KeyCode::Char('q'))));
+        assert!(ui.alarm.is_none());
+        assert_eq!(ui.alarm_lit(expiry), None);
+
+        ui.run_command(Command::Timer(Some(Duration::from_secs(60))));
+        assert_eq!(
+            settings(&ui).sleep_timer.unwrap().alarm(),
+            TimerAlarm::Visual
+        );
+    }
+
+    #[test]
+    fn selection_clamps_at_both_ends() {
+        let mut ui = ui();
+        ui.handle_key(key(KeyCode::Up));
+        assert_eq!(ui.selected, 0);
+
+        for _ in 0..bands().len() + 5 {
+            ui.handle_key(key(KeyCode::Down));
+        }
+        assert_eq!(ui.selected, bands().len());
+    }
+
+    #[test]
+    fn left_right_adjust_volume_in_steps_and_clamp() {
+        let mut ui = ui();
+        ui.handle_key(key(KeyCode::Right));
+        assert!((settings(&ui).volume - 0.05).abs() < 1e-6);
+
+        for _ in 0..40 {
+            ui.handle_key(key(KeyCode::Right));
+        }
+        assert_eq!(settings(&ui).volume, 1.0);
+
+        for _ in 0..40 {
+            ui.handle_key(key(KeyCode::Left));
+        }
+        assert_eq!(settings(&ui).volume, 0.0);
+    }
+
+    #[test]
+    fn alt_arrows_paint_a_curve_across_the_bands() {
+        let mut ui = ui();
+        let alt = |code| KeyEvent::new(code, KeyModifiers::ALT);
+        let volume = settings(&ui).volume;
+        // From the volume row Alt+Up goes nowhere and Alt+Down only moves.
+        ui.handle_key(alt(KeyCode::Up));
+        assert_eq!(ui.selected, 0);
+        ui.handle_key(alt(KeyCode::Down));
+        assert_eq!(ui.selected, 1);
+        assert_eq!(settings(&ui).frequency_bands, [0.5; MAX_BANDS]);
+
+        // Raise the first band, carry it two bands down, then bend the line
+        // down over the next two.
+        ui.handle_key(key(KeyCode::Char('8')));
+        ui.handle_key(alt(KeyCode::Down));
+        ui.handle_key(alt(KeyCode::Down));
+        for _ in 0..2 {
+            ui.handle_key(alt(KeyCode::Down));
+            ui.handle_key(alt(KeyCode::Left));
+            ui.handle_key(alt(KeyCode::Left));
+        }
+        assert_eq!(ui.selected, 5);
+        let drawn = settings(&ui).frequency_bands;
+        for (band, expected) in [0.8, 0.8, 0.8, 0.7, 0.6, 0.5].iter().enumerate() {
+            assert!(
+                (drawn[band] - expected).abs() < 1e-6,
+                "band {band}: {drawn:?}"
+            );
+        }
+
+        // Going back up repaints on the way, and the top band stops the pen.
+        for _ in 0..bands().len() {
+            ui.handle_key(alt(KeyCode::Up));
+        }
+        assert_eq!(ui.selected, 1);
+        assert!(
+            settings(&ui).frequency_bands[..5]
+                .iter()
+                .all(|value| (value - 0.6).abs() < 1e-6)
+        );
+        assert_eq!(settings(&ui).volume, volume);
+        // Each step is one undo.
+        ui.handle_key(KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL));
+        assert!((settings(&ui).frequency_bands[0] - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn fine_steps_jumps_and_digits_move_the_selected_slider() {
+        let mut ui = ui();
+        ui.handle_key(KeyEvent::new(KeyCode::Right, KeyModifiers::SHIFT));
+        ui.handle_key(KeyEvent::new(KeyCode::Right, KeyModifiers::SHIFT));
+        assert!((settings(&ui).volume - 0.02).abs() < 1e-6);
+        ui.handle_key(key(KeyCode::PageUp));
+        assert!((settings(&ui).volume - 0.27).abs() < 1e-6);
+        ui.handle_key(key(KeyCode::Char('3')));
+        assert!((settings(&ui).volume - 0.3).abs() < 1e-6);
+        ui.handle_key(key(KeyCode::PageDown));
+        ui.handle_key(key(KeyCode::PageDown));
+        assert_eq!(settings(&ui).volume, 0.0);
+
+        ui.handle_key(key(KeyCode::Down));
+        ui.handle_key(key(KeyCode::Char('9')));
+        assert!((settings(&ui).frequency_bands[0] - 0.9).abs() < 1e-6);
+        assert_eq!(settings(&ui).volume, 0.0);
+    }
+
+    #[test]
+    fn clicks_and_drags_set_a_main_page_slider_and_the_wheel_steps_it() {
+        let mut ui = ui();
+        let mouse = |kind, column, row| MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        };
+        let last = SLIDER_COLUMN + SLIDER_WIDTH as u16 - 1;
+        let down = MouseEventKind::Down(MouseButton::Left);
+        let drag = MouseEventKind::Drag(MouseButton::Left);
+
+        // The first band's row, on its last cell.
+        ui.handle_mouse(mouse(down, last, 6));
+        assert_eq!(ui.selected, 1);
+        assert_eq!(settings(&ui).frequency_bands[0], 1.0);
+        // Dragging keeps to that slider even as the pointer drifts a row.
+        ui.handle_mouse(mouse(drag, SLIDER_COLUMN - 1, 7));
+        assert_eq!(settings(&ui).frequency_bands[0], 0.0);
+        assert_eq!(settings(&ui).frequency_bands[1], 0.5);
+        ui.handle_mouse(mouse(MouseEventKind::Up(MouseButton::Left), 0, 0));
+        ui.handle_mouse(mouse(drag, last, 7));
+        assert_eq!(settings(&ui).frequency_bands[0], 0.0);
+
+        // A click on a name selects without moving the value.
+        ui.handle_mouse(mouse(down, 4, 5));
+        assert_eq!(ui.selected, 0);
+        assert_eq!(settings(&ui).volume, 0.0);
+        ui.handle_mouse(mouse(MouseEventKind::ScrollUp, 0, 0));
+        ui.handle_mouse(mouse(MouseEventKind::ScrollUp, 0, 0));
+        assert!((settings(&ui).volume - 0.1).abs() < 1e-6);
+        ui.handle_mouse(mouse(MouseEventKind::ScrollDown, 0, 0));
+        assert!((settings(&ui).volume - 0.05).abs() < 1e-6);
+
+        // Other pages select their rows; the help page scrolls.
+        ui.show_page(Page::Mixer);
+        ui.handle_mouse(mouse(down, SLIDER_COLUMN, 5 + SoundStyle::ALL.len() as u16));
+        assert_eq!(MixerRow::at(ui.mixer_selected), MixerRow::Width);
+        ui.show_page(Page::Help);
+        ui.handle_mouse(mouse(MouseEventKind::ScrollDown, 0, 0));
+        assert_eq!(ui.help_scroll, 1);
+
+        // A kiosk takes no clicks.
+        let mut kiosk = ui.with_kiosk();
+        kiosk.show_page(Page::Main);
+        kiosk.handle_mouse(mouse(down, last, 5));
+        kiosk.handle_mouse(mouse(MouseEventKind::ScrollUp, 0, 0));
+        assert!((settings(&kiosk).volume - 0.05).abs() < 1e-6);
+    }
+
+    #[test]
+    fn enter_takes_an_exact_percentage_for_the_selected_slider() {
+        let mut ui = ui();
+        for code in [KeyCode::Enter, KeyCode::Char('3'), KeyCode::Char('7')] {
+            ui.handle_key(key(code));
+        }
+        assert_eq!(ui.value_entry.as_deref(), Some("37"));
+        // Typing is not a shortcut: S and M do nothing until Enter.
+        ui.handle_key(key(KeyCode::Char('m')));
+        assert!(!settings(&ui).muted);
+        assert_eq!(settings(&ui).volume, 0.0);
+        ui.handle_key(key(KeyCode::Enter));
+        assert!((settings(&ui).volume - 0.37).abs() < 1e-6);
+        assert_eq!(ui.value_entry, None);
+
+        for code in [KeyCode::Enter, KeyCode::Char('5'), KeyCode::Esc] {
+            ui.handle_key(key(code));
+        }
+        assert!((settings(&ui).volume - 0.37).abs() < 1e-6);
+
+        for code in [
+            KeyCode::Enter,
+            KeyCode::Char('2'),
+            KeyCode::Char('0'),
+            KeyCode::Char('0'),
+            KeyCode::Char('0'),
+            KeyCode::Enter,
+        ] {
+            ui.handle_key(key(code));
+        }
+        assert!((settings(&ui).volume - 0.37).abs() < 1e-6);
+        assert_eq!(ui.prompt_note.as_deref(), Some(text("main.entry_range")));
+
+        ui.handle_key(key(KeyCode::Down));
+        for code in [KeyCode::Enter, KeyCode::Char('5'), KeyCode::Backspace] {
+            ui.handle_key(key(code));
+        }
+        for code in [KeyCode::Char('1'), KeyCode::Char('0'), KeyCode::Enter] {
+            ui.handle_key(key(code));
+        }
+        assert!((settings(&ui).frequency_bands[0] - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn adjusting_a_band_only_touches_that_band() {
+        let mut ui = ui();
+        ui.handle_key(key(KeyCode::Down));
+        ui.handle_key(key(KeyCode::Right));
+
+        let current = settings(&ui);
+        assert!((current.frequency_bands[0] - 0.55).abs() < 1e-6);
+        assert!(
+            current.frequency_bands[1..]
+                .iter()
+                .all(|value| *value == 0.5)
+        );
+        assert_eq!(current.volume, 0.0);
+    }
+
+    #[test]
+    fn s_cycles_the_sound_style() {
+        let mut ui = ui();
+        ui.handle_key(key(KeyCode::Char('s')));
+        assert_eq!(settings(&ui).sound_style, SoundStyle::Pink);
+        ui.handle_key(key(KeyCode::Char('S')));
+        assert_eq!(settings(&ui).sound_style, SoundStyle::Brown);
+    }
+
+    #[test]
+    fn s_from_a_custom_mix_solos_the_source_after_the_dominant_one() {
+        let mut ui = ui();
+        {
+            let mut locked = ui.settings.lock().unwrap();
+            locked.set_mix(SourceMix {
+                white: 0.1,
+                pink: 0.0,
+                brown: 0.0,
+                blue: 0.0,
+                violet: 0.7,
+                rain: 0.2,
+                ocean: 0.0,
+                wind: 0.0,
+                fire: 0.0,
+                fan: 0.0,
+                sample: 0.0,
+            });
+        }
+        ui.handle_key(key(KeyCode::Char('s')));
+
+        let current = settings(&ui);
+        assert_eq!(current.mix(), SourceMix::solo(SoundStyle::Rain));
+        assert_eq!(current.sound_style, SoundStyle::Rain);
+    }
+
+    #[test]
+    fn n_toggles_the_listening_contour() {
+        let mut ui = ui();
+        ui.handle_key(key(KeyCode::Char('n')));
+        assert!(settings(&ui).listening_contour);
+        ui.handle_key(key(KeyCode::Char('N')));
+        assert!(!settings(&ui).listening_contour);
+    }
+
+    #[test]
+    fn r_resets_every_band_but_not_the_volume() {
+        let mut ui = ui();
+        {
+            let mut locked = ui.settings.lock().unwrap();
+            locked.frequency_bands = [0.9; MAX_BANDS];
+            locked.volume = 0.7;
+        }
+        ui.handle_key(key(KeyCode::Char('r')));
+
+        let current = settings(&ui);
+        assert_eq!(current.frequency_bands, [0.5; MAX_BANDS]);
+        assert_eq!(current.volume, 0.7);
+    }
+
+    #[test]
+    fn l_swaps_volume_and_eq_between_two_listeners_and_leaves_the_sources() {
+        let mut ui = ui();
+        {
+            let mut locked = ui.settings.lock().unwrap();
+            locked.volume = 0.3;
+            locked.frequency_bands[7] = 0.2;
+            locked.set_mix(SourceMix::solo(SoundStyle::Rain));
+        }
+        // The first swap starts B from A's sound, so nothing jumps.
+        ui.handle_key(key(KeyCode::Char('l')));
+        let current = settings(&ui);
+        assert_eq!(current.listeners.active, Listener::B);
+        assert_eq!((current.volume, current.frequency_bands[7]), (0.3, 0.2));
+
+        {
+            let mut locked = ui.settings.lock().unwrap();
+            locked.volume = 0.5;
+            locked.frequency_bands[7] = 0.8;
+        }
+        ui.handle_key(key(KeyCode::Char('L')));
+        let current = settings(&ui);
+        assert_eq!(current.listeners.active, Listener::A);
+        assert_eq!((current.volume, current.frequency_bands[7]), (0.3, 0.2));
+        ui.handle_key(key(KeyCode::Char('l')));
+        let current = settings(&ui);
+        assert_eq!((current.volume, current.frequency_bands[7]), (0.5, 0.8));
+        assert_eq!(current.mix(), SourceMix::solo(SoundStyle::Rain));
+
+        let mut screen = Vec::new();
+        ui.draw_header(&mut screen, current).unwrap();
+        let screen = String::from_utf8(screen).unwrap();
+        assert!(screen.contains(&text_with("page.listener", &[("listener", &"B")])));
+    }
+
+    #[test]
+    fn ctrl_r_starts_and_stops_a_recording_in_the_recordings_folder() {
+        let dir = std::env::temp_dir().join(format!("whitenoise-ui-record-{}", std::process::id()));
+        let mut ui = ui();
+        ui.recordings_dir = dir.clone();
+        let record = KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL);
+        ui.handle_key(record);
+        let path = ui.recording.as_ref().unwrap().path().to_path_buf();
+        assert!(path.starts_with(&dir) && path.exists());
+        assert_eq!(
+            ui.prompt_note,
+            Some(text_with("record.started", &[("path", &path.display())]))
+        );
+        // Ctrl+R records; R alone still resets the EQ.
+        assert_eq!(
+            settings(&ui).frequency_bands,
+            AudioSettings::default().frequency_bands
+        );
+        let mut screen = Vec::new();
+        ui.draw_header(&mut screen, settings(&ui)).unwrap();
+        assert!(
+            String::from_utf8(screen)
+                .unwrap()
+                .contains(text("page.recording"))
+        );
+
+        // No engine feeds this monitor, so the second press finds nothing
+        // recorded and keeps no file.
+        ui.handle_key(record);
+        assert!(ui.recording.is_none());
+        assert!(
+            ui.prompt_note
+                .as_deref()
+                .unwrap()
+                .contains("nothing played")
+        );
+        assert!(!path.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn c_compares_two_eqs_and_ctrl_z_undoes_each_ones_changes() {
+        let mut ui = ui();
+        let band = |ui: &InteractiveUi| settings(ui).frequency_bands[0];
+        let undo = KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL);
+        ui.handle_key(key(KeyCode::Down));
+        ui.handle_key(key(KeyCode::Right));
+        ui.handle_key(key(KeyCode::Right));
+        assert!((band(&ui) - 0.6).abs() < 1e-6);
+
+        // The first press plays a copy as B, so nothing moves yet.
+        ui.handle_key(key(KeyCode::Char('c')));
+        assert!((band(&ui) - 0.6).abs() < 1e-6);
+        ui.handle_key(key(KeyCode::Left));
+        ui.settings.lock().unwrap().ceiling_hz = Some(8_000.0);
+        ui.handle_key(key(KeyCode::Char('C')));
+        assert!((band(&ui) - 0.6).abs() < 1e-6);
+        assert_eq!(settings(&ui).ceiling_hz, None);
+        ui.handle_key(key(KeyCode::Char('c')));
+        assert!((band(&ui) - 0.55).abs() < 1e-6);
+        assert_eq!(settings(&ui).ceiling_hz, Some(8_000.0));
+
+        // B's changes come off first, the ceiling set from outside the keys
+        // among them, then nothing is left to undo.
+        ui.handle_key(undo);
+        assert_eq!(settings(&ui).ceiling_hz, None);
+        ui.handle_key(undo);
+        assert!((band(&ui) - 0.6).abs() < 1e-6);
+        ui.handle_key(undo);
+        assert_eq!(ui.prompt_note.as_deref(), Some(text("undo.nothing")));
+        // A kept its own two steps.
+        ui.handle_key(key(KeyCode::Char('c')));
+        ui.handle_key(undo);
+        ui.handle_key(undo);
+        assert!((band(&ui) - 0.5).abs() < 1e-6);
+
+        let mut screen = Vec::new();
+        ui.draw_header(&mut screen, settings(&ui)).unwrap();
+        let screen = String::from_utf8(screen).unwrap();
+        assert!(screen.contains(&text_with("page.compare", &[("side", &"A")])));
+    }
+
+    #[test]
+    fn the_ear_trainer_takes_the_keys_and_puts_the_sound_back() {
+        let mut ui = ui();
+        let before = settings(&ui);
+        ui.execute("train bands 6");
+        assert!(ui.trainer.is_some());
+        // Space plays B, which moves one band; it moves back on A.
+        ui.handle_key(key(KeyCode::Char(' ')));
+        let moved = (0..bands().len())
+            .filter(|band| settings(&ui).frequency_bands[*band] != before.frequency_bands[*band])
+            .count();
+        assert_eq!(moved, 1);
+        ui.handle_key(key(KeyCode::Char(' ')));
+        assert_eq!(settings(&ui).frequency_bands, before.frequency_bands);
+
+        let mut screen = Vec::new();
+        ui.draw_trainer(&mut screen).unwrap();
+        let screen = String::from_utf8(screen).unwrap();
+        assert!(screen.contains(&text_with("trainer.bands_heading", &[("db", &"6")])));
+        assert!(screen.contains(&text_with(
+            "trainer.score",
+            &[("right", &0), ("rounds", &0), ("streak", &0)]
+        )));
+
+        ui.handle_key(key(KeyCode::Enter));
+        assert_eq!(ui.trainer.as_ref().unwrap().score().1, 1);
+        ui.handle_key(key(KeyCode::Enter));
+        assert_eq!(ui.trainer.as_ref().unwrap().answered(), None);
+        // Q ends the trainer rather than quitting.
+        assert!(!ui.handle_key(key(KeyCode::Char('q'))));
+        assert!(ui.trainer.is_none());
+        assert_eq!(settings(&ui).frequency_bands, before.frequency_bands);
+        assert_eq!(settings(&ui).mix(), before.mix());
+        assert!(ui.prompt_note.as_deref().unwrap().contains("of 1 right"));
+    }
+
+    #[test]
+    fn a_sound_that_lives_in_the_limiter_is_offered_a_trim() {
+        let mut ui = ui();
+        {
+            let mut settings = ui.settings.lock().unwrap();
+            settings.volume = 0.2;
+            settings.frequency_bands = [1.0; MAX_BANDS];
+        }
+        ui.check_clipping();
+        assert!(ui.prompt_note.is_none() && !ui.pending_trim);
+
+        ui.settings.lock().unwrap().volume = 1.0;
+        ui = ui.with_note("a settings warning".to_owned());
+        ui.check_clipping();
+        let note = ui.prompt_note.clone().unwrap();
+        assert!(note.starts_with("a settings warning; "), "{note}");
+        assert!(ui.pending_trim);
+        // Another key declines, and the offer is gone.
+        ui.handle_key(key(KeyCode::Down));
+        assert!(!ui.pending_trim);
+        assert_eq!(settings(&ui).volume, 1.0);
+
+        ui.check_clipping();
+        ui.handle_key(key(KeyCode::Char('y')));
+        assert!(settings(&ui).volume < 0.4);
+        assert!(limiter_excess_db(settings(&ui)).is_none());
+        assert!(
+            ui.prompt_note
+                .as_deref()
+                .unwrap()
+                .contains("under the limiter")
+        );
+    }
+
+    fn mixer(mix: SourceMix) -> InteractiveUi {
+        let mut ui = ui();
+        ui.settings.lock().unwrap().set_mix(mix);
+        ui.handle_key(key(KeyCode::Tab));
+        assert_eq!(ui.page, Page::Mixer);
+        ui
+    }
+
+    fn rain_and_brown() -> SourceMix {
+        SourceMix {
+            white: 0.0,
+            pink: 0.0,
+            brown: 0.4,
+            blue: 0.0,
+            violet: 0.0,
+            rain: 0.6,
+            ocean: 0.0,
+            wind: 0.0,
+            fire: 0.0,
+            fan: 0.0,
+            sample: 0.0,
+        }
+    }
+
+    #[test]
+    fn the_layout_keeps_the_footer_at_the_bottom_and_scrolls_short_pages() {
+        let standard = Layout::STANDARD;
+        assert_eq!(
+            (
+                standard.last_row(),
+                standard.meter_row(),
+                standard.timer_row(),
+                standard.history_row(),
+                standard.prompt_row()
+            ),
+            (18, 19, 20, Some(21), 23)
+        );
+        assert_eq!(standard.list_rows(), 12);
+        assert_eq!(standard.slider_rows(5), 13);
+
+        // A short terminal gives up the history, then scrolls the sliders.
+        let short = Layout {
+            columns: 80,
+            rows: 14,
+        };
+        assert!(short.fits());
+        assert_eq!((short.history_row(), short.prompt_row()), (None, 13));
+        assert_eq!(short.slider_rows(5), 5);
+        assert_eq!(visible_row(0, 0, 4, 5), Some(5));
+        assert_eq!(visible_row(4, 0, 4, 5), None);
+        assert_eq!(visible_row(0, 6, 4, 5), None);
+        assert_eq!(visible_row(6, 6, 4, 5), Some(8));
+        assert!(
+            !Layout {
+                columns: 80,
+                rows: 8
+            }
+            .fits()
+        );
+
+        // Clicks land on the slider drawn under them.
+        let mut ui = ui();
+        ui.resize(short);
+        ui.selected = 6;
+        let down = MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column: 4,
+            row: 5,
+            modifiers: KeyModifiers::NONE,
+        };
+        ui.handle_mouse(down);
+        assert_eq!(ui.selected, 2);
+        ui.handle_mouse(MouseEvent { row: 9, ..down });
+        assert_eq!(ui.selected, 4);
+        // The readout's row is not a slider.
+        ui.handle_mouse(MouseEvent { row: 10, ..down });
+        assert_eq!(ui.selected, 4);
+        // Help scrolls no further than its last screen at this height.
+        ui.show_page(Page::Help);
+        for _ in 0..HELP_LINES.len() {
+            ui.handle_key(key(KeyCode::Down));
+        }
+        assert_eq!(ui.help_scroll, HELP_LINES.len() - short.help_rows());
+        ui.resize(Layout::STANDARD);
+        assert_eq!(
+            ui.help_scroll,
+            HELP_LINES.len() - Layout::STANDARD.help_rows()
+        );
+    }
+
+    #[test]
+    fn every_page_draws_from_the_string_catalog() {
+        // In debug builds text() asserts that each key has an English string.
+        let mut ui = ui();
+        let mut current = AudioSettings {
+            volume: 0.4,
+            listening_contour: true,
+            ..AudioSettings::default()
+        };
+        current.tone.mode = ToneMode::Binaural;
+        let mut screen = Vec::new();
+        ui.draw_header(&mut screen, current).unwrap();
+        ui.draw_header(
+            &mut screen,
+            AudioSettings {
+                paused: true,
+                bypass: true,
+                ..current
+            },
+        )
+        .unwrap();
+        for selected in 0..=bands().len() {
+            ui.selected = selected;
+            ui.draw_main(&mut screen, current).unwrap();
+        }
+        for selected in 0..MixerRow::COUNT {
+            ui.mixer_selected = selected;
+            ui.draw_mixer(&mut screen, current).unwrap();
+        }
+        for tilt in [0.0, -2.5] {
+            current.band_tilts = [tilt; TILT_BANDS.len()];
+            for selected in 0..EqRow::count() {
+                ui.eq_selected = selected;
+                ui.draw_eq(&mut screen, current).unwrap();
+            }
+        }
+        for mid_side in [false, true] {
+            current.mid_side = mid_side;
+            for selected in 0..=bands().len() {
+                ui.side_selected = selected;
+                ui.draw_side(&mut screen, current).unwrap();
+            }
+        }
+        current.band_widths[0] = 0.0;
+        ui.eq_selected = TILT_BANDS.len() + EqRow::BELOW_TILTS.len();
+        ui.draw_eq(&mut screen, current).unwrap();
+        current.ceiling_hz = Some(8_000.0);
+        current.high_pass.hz = Some(31.5);
+        ui.eq_selected = 0;
+        ui.draw_eq(&mut screen, current).unwrap();
+        ui.draw_presets(&mut screen).unwrap();
+        ui.presets = vec!["focus".to_owned(); ui.layout.list_rows() + 1];
+        ui.draw_presets(&mut screen).unwrap();
+        ui.draw_scenes(&mut screen).unwrap();
+        ui.scenes = vec!["attic rain".to_owned()];
+        ui.draw_scenes(&mut screen).unwrap();
+        ui.open_scene_editor(String::new(), Vec::new(), true);
+        ui.draw_scene_editor(&mut screen).unwrap();
+        let swell = Layer {
+            low_pass_hz: Some(6300.0),
+            modulation: Some(Modulation::default()),
+            ..Layer::new(SoundStyle::Rain, 0.6)
+        };
+        ui.open_scene_editor("attic rain".to_owned(), vec![swell], false);
+        for field in 0..SceneField::ALL.len() {
+            ui.scene_editor.as_mut().unwrap().field = field;
+            ui.draw_scene_editor(&mut screen).unwrap();
+        }
+        ui.scene_editor.as_mut().unwrap().layers.clear();
+        ui.draw_scene_editor(&mut screen).unwrap();
+        ui.scene_editor = None;
+        let layout = ui.layout;
+        for scroll in 0..=HELP_LINES.len() - layout.help_rows() {
+            draw_help(&mut screen, &ui.theme, scroll, layout).unwrap();
+        }
+        draw_exam(&mut screen, &ui.theme, 0.4, layout).unwrap();
+        draw_too_small(
+            &mut screen,
+            &ui.theme,
+            Layout {
+                columns: 40,
+                rows: 10,
+            },
+        )
+        .unwrap();
+        ui.draw_history(&mut screen, 21).unwrap();
+
+        let screen = String::from_utf8_lossy(&screen);
+        for expected in [
+            "Whitenoise > Main",
+            "Listening contour: on",
+            "Tone level",
+            "Mid tilt",
+            " 8000 Hz",
+            " 31.5 Hz",
+            "24 dB/oct",
+            "Sub Bass width 0% of the stereo width",
+            "Air side +0.0 dB, mid +0.0 dB",
+            "Presets page",
+            "1 of 13",
+            "Scenes in",
+            "Editing scene attic rain",
+            "[6300 Hz",
+            " 30 s ",
+            "Limiter",
+            "at least 50 by 12",
+        ] {
+            assert!(screen.contains(expected), "missing {expected}");
+        }
+        assert!(!screen.contains('{'), "unfilled placeholder");
+    }
+
+    #[test]
+    fn accessible_palettes_mark_state_without_hue() {
+        for palette in [Palette::Colorblind, Palette::Monochrome] {
+            let theme = Theme::new(palette);
+            assert_ne!(theme.fill_glyph, theme.empty_glyph);
+            assert_ne!(theme.fill.attributes, theme.empty.attributes, "{palette:?}");
+            assert_ne!(
+                theme.selected.attributes, theme.text.attributes,
+                "{palette:?}"
+            );
+            // Red and green are the hues most often confused.
+            for style in [theme.fill, theme.level, theme.limiter, theme.selected] {
+                assert!(!matches!(
+                    style.foreground_color,
+                    Some(Color::Red | Color::DarkRed | Color::Green | Color::DarkGreen)
+                ));
+            }
+
+            let mut row = Vec::new();
+            draw_slider(&mut row, &theme, "Volume", 0.5, Some(5), true, "50%").unwrap();
+            let row = String::from_utf8_lossy(&row);
+            assert_eq!(row.matches(theme.fill_glyph).count(), SLIDER_WIDTH / 2);
+            assert_eq!(row.matches(theme.empty_glyph).count(), SLIDER_WIDTH / 2);
+        }
+
+        // Monochrome sets no color at all, only intensity and reverse video.
+        let mono = Theme::new(Palette::Monochrome);
+        let mut screen = Vec::new();
+        draw_help(&mut screen, &mono, 0, Layout::STANDARD).unwrap();
+        draw_exam(&mut screen, &mono, 0.4, Layout::STANDARD).unwrap();
+        let screen = String::from_utf8_lossy(&screen);
+        assert!(!screen.contains("\x1b[38;"), "foreground color set");
+
+        // Dimmed, no palette keeps a bright color, and the bars still
+        // differ from their empty cells.
+        for palette in [Palette::Color, Palette::Colorblind, Palette::Monochrome] {
+            let theme = Theme::new(palette).dimmed();
+            assert_ne!(theme, Theme::new(palette));
+            assert_ne!(theme.fill, theme.empty, "{palette:?}");
+            for style in [theme.fill, theme.level, theme.text, theme.heading] {
+                assert!(!matches!(
+                    style.foreground_color,
+                    Some(Color::Green | Color::White | Color::Cyan)
+                ));
+            }
+        }
+
+        // The original palette draws exactly as before.
+        assert_eq!(ui().theme, Theme::new(Palette::Color));
+        assert_eq!(Theme::new(Palette::Color).fill_glyph, '#');
+    }
+
+    #[test]
+    fn eq_page_tilts_the_wide_bands_in_half_decibel_steps() {
+        let mut ui = ui();
+        ui.page = Page::Eq;
+        ui.handle_key(key(KeyCode::Down));
+        assert_eq!(ui.eq_selected, TILT_BANDS.len() - 1);
+        ui.handle_key(key(KeyCode::Left));
+        assert_eq!(settings(&ui).band_tilts, [0.0, -0.5]);
+        for _ in 0..30 {
+            ui.handle_key(key(KeyCode::Right));
+        }
+        assert_eq!(settings(&ui).band_tilts, [0.0, MAX_TILT_DB]);
+
+        ui.handle_key(key(KeyCode::Char('r')));
+        assert_eq!(settings(&ui).band_tilts, [0.0; TILT_BANDS.len()]);
+    }
+
+    #[test]
+    fn width_rows_narrow_or_widen_each_band_in_ten_percent_steps() {
+        let mut ui = ui();
+        ui.page = Page::Eq;
+        for _ in 0..EqRow::count() {
+            ui.handle_key(key(KeyCode::Down));
+        }
+        let last = bands().len() - 1;
+        assert_eq!(EqRow::at(ui.eq_selected), EqRow::Width(last));
+        ui.handle_key(key(KeyCode::Right));
+        assert_eq!(settings(&ui).band_widths[last], 1.1);
+        for _ in 0..20 {
+            ui.handle_key(key(KeyCode::Right));
+        }
+        assert_eq!(settings(&ui).band_widths[last], MAX_BAND_WIDTH);
+
+        ui.eq_selected = TILT_BANDS.len() + EqRow::BELOW_TILTS.len();
+        assert_eq!(EqRow::at(ui.eq_selected), EqRow::Width(0));
+        for _ in 0..20 {
+            ui.handle_key(key(KeyCode::Left));
+        }
+        assert_eq!(settings(&ui).band_widths[0], 0.0);
+        let current = settings(&ui);
+        assert_eq!(
+            band_width_readout(current, 0),
+            "Sub Bass width 0% of the stereo width: plays 0% wide"
+        );
+        assert_eq!(
+            band_width_readout(current, 1),
+            "Bass width 100%: plays at the stereo width, 50% wide"
+        );
+
+        ui.handle_key(key(KeyCode::Char('r')));
+        assert_eq!(settings(&ui).band_widths, [1.0; MAX_BANDS]);
+    }
+
+    #[test]
+    fn side_page_turns_mid_side_on_and_shapes_the_side() {
+        let mut ui = ui();
+        ui.page = Page::Side;
+        assert_eq!(
+            side_readout(settings(&ui), 0),
+            "Mid/side off: the Main page's sliders shape both channels alike, and these wait"
+        );
+        ui.handle_key(key(KeyCode::Enter));
+        assert!(settings(&ui).mid_side);
+        ui.handle_key(key(KeyCode::Left));
+        assert!(!settings(&ui).mid_side);
+        ui.handle_key(key(KeyCode::Right));
+
+        let last = bands().len();
+        for _ in 0..last + 2 {
+            ui.handle_key(key(KeyCode::Down));
+        }
+        assert_eq!(ui.side_selected, last);
+        ui.handle_key(key(KeyCode::Right));
+        ui.handle_key(KeyEvent::new(KeyCode::Left, KeyModifiers::SHIFT));
+        assert!((settings(&ui).side_bands[last - 1] - 0.54).abs() < 1e-6);
+        assert_eq!(settings(&ui).frequency_bands, [0.5; MAX_BANDS]);
+        // Enter only toggles on the mode row.
+        ui.handle_key(key(KeyCode::Enter));
+        assert!(settings(&ui).mid_side);
+        assert_eq!(
+            side_readout(settings(&ui), last),
+            "Air side +1.0 dB, mid +0.0 dB"
+        );
+        assert!(main_readout(settings(&ui), last).ends_with("the Side EQ page the side"));
+
+        ui.handle_key(key(KeyCode::Char('r')));
+        assert_eq!(settings(&ui).side_bands, [0.5; MAX_BANDS]);
+    }
+
+    #[test]
+    fn ceiling_steps_by_third_octaves_and_turns_off_past_the_top() {
+        let mut ui = ui();
+        ui.page = Page::Eq;
+        for _ in 0..TILT_BANDS.len() {
+            ui.handle_key(key(KeyCode::Down));
+        }
+        assert_eq!(EqRow::at(ui.eq_selected), EqRow::Ceiling);
+        assert_eq!(settings(&ui).ceiling_hz, None);
+        ui.handle_key(key(KeyCode::Left));
+        assert_eq!(settings(&ui).ceiling_hz, Some(MAX_CEILING_HZ));
+        ui.handle_key(key(KeyCode::Left));
+        ui.handle_key(key(KeyCode::Left));
+        assert_eq!(settings(&ui).ceiling_hz, Some(12_500.0));
+        ui.handle_key(key(KeyCode::Right));
+        ui.handle_key(key(KeyCode::Right));
+        ui.handle_key(key(KeyCode::Right));
+        assert_eq!(settings(&ui).ceiling_hz, None);
+
+        // A typed value between steps moves to the neighboring step.
+        assert_eq!(step_ceiling(Some(7_000.0), true), Some(8_000.0));
+        assert_eq!(step_ceiling(Some(7_000.0), false), Some(6_300.0));
+        assert_eq!(
+            step_ceiling(Some(MIN_CEILING_HZ), false),
+            Some(MIN_CEILING_HZ)
+        );
+    }
+
+    #[test]
+    fn high_pass_steps_up_from_off_and_switches_slope() {
+        let mut ui = ui();
+        ui.page = Page::Eq;
+        for _ in 0..TILT_BANDS.len() + 2 {
+            ui.handle_key(key(KeyCode::Down));
+        }
+        assert_eq!(EqRow::at(ui.eq_selected), EqRow::Slope);
+        ui.handle_key(key(KeyCode::Left));
+        assert_eq!(settings(&ui).high_pass.slope_db, 12);
+
+        ui.handle_key(key(KeyCode::Up));
+        assert_eq!(EqRow::at(ui.eq_selected), EqRow::HighPass);
+        ui.handle_key(key(KeyCode::Left));
+        assert_eq!(settings(&ui).high_pass.hz, None);
+        for _ in 0..4 {
+            ui.handle_key(key(KeyCode::Right));
+        }
+        assert_eq!(settings(&ui).high_pass.hz, Some(40.0));
+        for _ in 0..20 {
+            ui.handle_key(key(KeyCode::Right));
+        }
+        assert_eq!(settings(&ui).high_pass.hz, Some(MAX_HIGH_PASS_HZ));
+        for _ in 0..20 {
+            ui.handle_key(key(KeyCode::Left));
+        }
+        assert_eq!(settings(&ui).high_pass.hz, None);
+    }
+
+    #[test]
+    fn notch_tunes_by_semitones_and_widens_by_tenths_of_an_octave() {
+        let mut ui = ui();
+        ui.page = Page::Eq;
+        for _ in 0..TILT_BANDS.len() + 3 {
+            ui.handle_key(key(KeyCode::Down));
+        }
+        assert_eq!(EqRow::at(ui.eq_selected), EqRow::Notch);
+        ui.handle_key(key(KeyCode::Left));
+        assert_eq!(settings(&ui).notch.hz, None);
+        ui.handle_key(key(KeyCode::Right));
+        assert_eq!(settings(&ui).notch.hz, Some(DEFAULT_NOTCH_HZ));
+        for _ in 0..12 {
+            ui.handle_key(key(KeyCode::Right));
+        }
+        assert_eq!(settings(&ui).notch.hz, Some(8_000.0));
+        assert_eq!(
+            notch_readout(settings(&ui).notch),
+            "Notch 8000 Hz, 1.0 octaves wide: silent at the center and 3 dB down at 5657 and 11314 Hz"
+        );
+
+        ui.handle_key(key(KeyCode::Down));
+        assert_eq!(EqRow::at(ui.eq_selected), EqRow::NotchWidth);
+        for _ in 0..5 {
+            ui.handle_key(key(KeyCode::Left));
+        }
+        assert!((settings(&ui).notch.width_octaves - 0.5).abs() < 1e-6);
+        for _ in 0..30 {
+            ui.handle_key(key(KeyCode::Left));
+        }
+        assert_eq!(settings(&ui).notch.width_octaves, MIN_NOTCH_OCTAVES);
+
+        assert_eq!(step_notch(Some(MAX_NOTCH_HZ), true), Some(MAX_NOTCH_HZ));
+        assert_eq!(step_notch(Some(MIN_NOTCH_HZ), false), None);
+        assert_eq!(step_notch(Some(6_500.0), false), Some(6_350.0));
+        assert_eq!(step_notch(None, false), None);
+    }
+
+    #[test]
+    fn loop_fades_step_by_half_seconds_up_to_a_third_of_the_loop() {
+        let mut ui = ui().with_loop_limits(LoopSettings {
+            rain_crossfade_seconds: 5.0,
+            sample_crossfade_seconds: 1.2,
+            ..LoopSettings::default()
+        });
+        ui.page = Page::Eq;
+        ui.eq_selected = TILT_BANDS.len() + EqRow::BELOW_TILTS.len() - 1;
+        ui.handle_key(key(KeyCode::Up));
+        assert_eq!(EqRow::at(ui.eq_selected), EqRow::SampleFade);
+        ui.handle_key(key(KeyCode::Right));
+        assert_eq!(settings(&ui).loops.sample_crossfade_seconds, 1.2);
+        for _ in 0..5 {
+            ui.handle_key(key(KeyCode::Left));
+        }
+        assert_eq!(
+            settings(&ui).loops.sample_crossfade_seconds,
+            MIN_LOOP_CROSSFADE_SECONDS
+        );
+
+        ui.handle_key(key(KeyCode::Up));
+        assert_eq!(EqRow::at(ui.eq_selected), EqRow::RainShuffle);
+        ui.handle_key(key(KeyCode::Right));
+        assert!(settings(&ui).loops.rain_shuffle && !settings(&ui).loops.sample_shuffle);
+        assert!(
+            shuffle_readout(SoundStyle::Rain, true, 2.0, 5.0)
+                .starts_with("Rain shuffle on: random stretches of the 15.0 s loop")
+        );
+        ui.handle_key(key(KeyCode::Up));
+        ui.handle_key(key(KeyCode::Right));
+        assert_eq!(settings(&ui).loops.rain_crossfade_seconds, 2.5);
+        for _ in 0..10 {
+            ui.handle_key(key(KeyCode::Right));
+        }
+        assert_eq!(settings(&ui).loops.rain_crossfade_seconds, 5.0);
+        assert_eq!(
+            loop_fade_readout(SoundStyle::Rain, 5.0, 5.0),
+            "Rain loop crossfade 5.0 s: the end of the 15.0 s loop fades into its start over 5.0 s; at most 5.0 s"
+        );
+        assert_eq!(step_high_pass(Some(35.0), false), Some(31.5));
+    }
+
+    #[test]
+    fn the_shuffle_row_toggles_and_says_when_the_loop_is_too_long() {
+        let short = LoopSettings {
+            sample_crossfade_seconds: 4.0,
+            ..LoopSettings::default()
+        };
+        let mut ui = ui().with_sample_loaded().with_loop_limits(short);
+        ui.page = Page::Eq;
+        ui.eq_selected = TILT_BANDS.len() + EqRow::BELOW_TILTS.len() - 1;
+        assert_eq!(EqRow::at(ui.eq_selected), EqRow::SampleShuffle);
+        ui.handle_key(key(KeyCode::Right));
+        assert!(settings(&ui).loops.sample_shuffle);
+        assert_eq!(
+            shuffle_readout(
+                SoundStyle::Sample,
+                settings(&ui).loops.sample_shuffle,
+                2.0,
+                short.sample_crossfade_seconds
+            ),
+            "Sample shuffle on: random stretches of the 12.0 s loop follow each other, each fading into the next over 2.0 s"
+        );
+        ui.handle_key(key(KeyCode::Left));
+        assert!(!settings(&ui).loops.sample_shuffle);
+        assert!(
+            shuffle_readout(
+                SoundStyle::Sample,
+                settings(&ui).loops.sample_shuffle,
+                2.0,
+                short.sample_crossfade_seconds
+            )
+            .starts_with("Sample shuffle off")
+        );
+
+        let long = LoopSettings {
+            sample_crossfade_seconds: 10.0,
+            ..LoopSettings::default()
+        };
+        assert!(
+            shuffle_readout(
+                SoundStyle::Sample,
+                false,
+                2.0,
+                long.sample_crossfade_seconds
+            )
+            .contains("the 30.0 s loop repeats too seldom")
+        );
+    }
+
+    #[test]
+    fn tab_and_shift_tab_cycle_every_page() {
+        let mut ui = ui();
+        for expected in [
+            Page::Mixer,
+            Page::Eq,
+            Page::Side,
+            Page::Presets,
+            Page::Scenes,
+            Page::Help,
+            Page::Main,
+        ] {
+            ui.handle_key(key(KeyCode::Tab));
+            assert_eq!(ui.page, expected);
+        }
+        for expected in [
+            Page::Help,
+            Page::Scenes,
+            Page::Presets,
+            Page::Side,
+            Page::Eq,
+            Page::Mixer,
+            Page::Main,
+        ] {
+            ui.handle_key(key(KeyCode::BackTab));
+            assert_eq!(ui.page, expected);
+        }
+        ui.handle_key(KeyEvent::new(KeyCode::Tab, KeyModifiers::SHIFT));
+        assert_eq!(ui.page, Page::Help);
+    }
+
+    #[test]
+    fn help_page_ignores_editing_keys_but_keeps_shared_ones() {
+        let mut ui = ui();
+        ui.page = Page::Help;
+        ui.handle_key(key(KeyCode::Right));
+        ui.handle_key(key(KeyCode::Char('s')));
+        assert_eq!(settings(&ui), AudioSettings::default());
+
+        ui.handle_key(key(KeyCode::Char('n')));
+        assert!(settings(&ui).listening_contour);
+
+        for _ in 0..HELP_LINES.len() {
+            ui.handle_key(key(KeyCode::Down));
+        }
+        assert_eq!(ui.help_scroll, HELP_LINES.len() - ui.layout.help_rows());
+        ui.handle_key(key(KeyCode::Up));
+        assert_eq!(ui.help_scroll, HELP_LINES.len() - ui.layout.help_rows() - 1);
+        assert!(ui.handle_key(key(KeyCode::Char('q'))));
+    }
+
+    #[test]
+    fn arrows_follow_the_current_page() {
+        let mut ui = mixer(SourceMix::solo(SoundStyle::White));
+        ui.handle_key(key(KeyCode::Down));
+        ui.handle_key(key(KeyCode::Left));
+        assert_eq!(ui.mixer_selected, 1);
+        assert_eq!(ui.selected, 0);
+        assert_eq!(settings(&ui).volume, 0.0);
+
+        ui.handle_key(key(KeyCode::BackTab));
+        assert_eq!(ui.page, Page::Main);
+        ui.handle_key(key(KeyCode::Right));
+        assert!((settings(&ui).volume - 0.05).abs() < 1e-6);
+    }
+
+    #[test]
+    fn mixer_faders_adjust_one_source_and_clamp() {
+        let mut ui = mixer(rain_and_brown());
+        for _ in 0..MixerRow::COUNT + 2 {
+            ui.handle_key(key(KeyCode::Down));
+        }
+        // The width, tone, and drift rows sit below the last source.
+        assert_eq!(MixerRow::at(ui.mixer_selected), MixerRow::DriftPeriod);
+        ui.mixer_selected = style_index(SoundStyle::Rain);
+
+        ui.handle_key(key(KeyCode::Right));
+        let mix = settings(&ui).mix();
+        assert!((mix.rain - 0.65).abs() < 1e-6);
+        assert!((mix.brown - 0.4).abs() < 1e-6);
+
+        for _ in 0..20 {
+            ui.handle_key(key(KeyCode::Right));
+        }
+        assert_eq!(settings(&ui).mix().rain, 1.0);
+    }
+
+    #[test]
+    fn width_row_adjusts_stereo_width_and_ignores_mute_and_solo() {
+        let mut ui = mixer(rain_and_brown());
+        ui.mixer_selected = SoundStyle::ALL.len();
+        ui.handle_key(key(KeyCode::Left));
+        assert!((settings(&ui).stereo_width - 0.45).abs() < 1e-6);
+        for _ in 0..20 {
+            ui.handle_key(key(KeyCode::Left));
+        }
+        assert_eq!(settings(&ui).stereo_width, 0.0);
+
+        ui.handle_key(key(KeyCode::Char('m')));
+        ui.handle_key(key(KeyCode::Char('s')));
+        assert_eq!(settings(&ui).mix(), rain_and_brown());
+        assert_eq!(ui.solo_restore, None);
+        assert!(!settings(&ui).muted);
+    }
+
+    #[test]
+    fn crossfeed_row_toggles_either_way() {
+        let mut ui = mixer(rain_and_brown());
+        ui.mixer_selected = SoundStyle::ALL.len() + 1;
+        assert_eq!(MixerRow::at(ui.mixer_selected), MixerRow::Crossfeed);
+        ui.handle_key(key(KeyCode::Right));
+        assert!(settings(&ui).crossfeed);
+        ui.handle_key(key(KeyCode::Left));
+        assert!(!settings(&ui).crossfeed);
+        assert_eq!(settings(&ui).mix(), rain_and_brown());
+    }
+
+    #[test]
+    fn tone_rows_step_the_mode_and_clamp_carrier_beat_and_level() {
+        let mut ui = mixer(rain_and_brown());
+        ui.mixer_selected = SoundStyle::ALL.len() + 2;
+        assert_eq!(MixerRow::at(ui.mixer_selected), MixerRow::Tone);
+        ui.handle_key(key(KeyCode::Right));
+        assert_eq!(settings(&ui).tone.mode, ToneMode::Binaural);
+        ui.handle_key(key(KeyCode::Left));
+        ui.handle_key(key(KeyCode::Left));
+        assert_eq!(settings(&ui).tone.mode, ToneMode::Isochronic);
+
+        ui.handle_key(key(KeyCode::Down));
+        ui.handle_key(key(KeyCode::Right));
+        assert_eq!(settings(&ui).tone.carrier_hz, 210.0);
+        for _ in 0..100 {
+            ui.handle_key(key(KeyCode::Right));
+        }
+        assert_eq!(settings(&ui).tone.carrier_hz, MAX_CARRIER_HZ);
+
+        ui.handle_key(key(KeyCode::Down));
+        ui.handle_key(key(KeyCode::Left));
+        assert_eq!(settings(&ui).tone.beat_hz, 3.5);
+        for _ in 0..20 {
+            ui.handle_key(key(KeyCode::Left));
+        }
+        assert_eq!(settings(&ui).tone.beat_hz, MIN_BEAT_HZ);
+
+        ui.handle_key(key(KeyCode::Down));
+        ui.handle_key(key(KeyCode::Left));
+        ui.handle_key(key(KeyCode::Left));
+        assert_eq!(settings(&ui).tone.level, 0.0);
+        assert_eq!(settings(&ui).mix(), rain_and_brown());
+    }
+
+    #[test]
+    fn drift_rows_toggle_it_and_step_depth_and_period() {
+        let mut ui = mixer(rain_and_brown());
+        ui.mixer_selected = SoundStyle::ALL.len() + 6;
+        assert_eq!(MixerRow::at(ui.mixer_selected), MixerRow::Drift);
+        ui.handle_key(key(KeyCode::Right));
+        assert!(settings(&ui).drift.enabled);
+        assert_eq!(
+            drift_readout(settings(&ui).drift),
+            "Drift 50%: the bands, the balance, and the loops' speed wander, turning about every 1 min"
+        );
 
-    fn adjust_selected(&self, amount: f32) {
-        let mut settings = self.lock_settings();
-        if self.selected == 0 {
-            settings.volume = (settings.volume + amount).clamp(0.0, 1.0);
-        } else {
-            let band = &mut settings.frequency_bands[self.selected - 1];
-            *band = (*band + amount).clamp(0.0, 1.0);
+        ui.handle_key(key(KeyCode::Down));
+        ui.handle_key(key(KeyCode::Left));
+        assert!((settings(&ui).drift.depth - 0.45).abs() < 1e-6);
+
+        ui.handle_key(key(KeyCode::Down));
+        ui.handle_key(key(KeyCode::Right));
+        assert_eq!(settings(&ui).drift.period_seconds, 90.0);
+        assert_eq!(format_period(90.0), "1.5 min");
+        for _ in 0..20 {
+            ui.handle_key(key(KeyCode::Left));
         }
+        assert_eq!(settings(&ui).drift.period_seconds, MIN_DRIFT_PERIOD_SECONDS);
+        assert_eq!(format_period(10.0), "10 s");
+        // A hand-edited period between the steps moves to the next one.
+        assert_eq!(step_drift_period(100.0, 1.0), 120.0);
+        assert_eq!(
+            step_drift_period(MAX_DRIFT_PERIOD_SECONDS, 1.0),
+            MAX_DRIFT_PERIOD_SECONDS
+        );
     }
 
-    fn lock_settings(&self) -> std::sync::MutexGuard<'_, AudioSettings> {
-        self.settings
-            .lock()
-            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    #[test]
+    fn mute_silences_a_source_and_restores_its_level() {
+        let mut ui = mixer(rain_and_brown());
+        ui.mixer_selected = style_index(SoundStyle::Rain);
+        ui.handle_key(key(KeyCode::Char('m')));
+        assert_eq!(settings(&ui).mix().rain, 0.0);
+        assert!((settings(&ui).mix().brown - 0.4).abs() < 1e-6);
+
+        ui.handle_key(key(KeyCode::Char('M')));
+        assert!((settings(&ui).mix().rain - 0.6).abs() < 1e-6);
+
+        // Moving a muted fader starts from the remembered level.
+        ui.handle_key(key(KeyCode::Char('m')));
+        ui.handle_key(key(KeyCode::Left));
+        assert!((settings(&ui).mix().rain - 0.55).abs() < 1e-6);
+        assert_eq!(ui.muted_levels, [None; SoundStyle::ALL.len()]);
     }
-}
 
-fn draw_slider(
-    stdout: &mut impl Write,
-    name: &str,
-    value: f32,
-    row: u16,
-    selected: bool,
-    value_label: &str,
-) -> Result<()> {
-    let filled = (value.clamp(0.0, 1.0) * SLIDER_WIDTH as f32).round() as usize;
-    queue!(stdout, cursor::MoveTo(2, row))?;
+    #[test]
+    fn solo_isolates_a_source_and_restores_the_mix() {
+        let mut ui = mixer(rain_and_brown());
+        ui.mixer_selected = style_index(SoundStyle::Brown);
+        ui.handle_key(key(KeyCode::Char('s')));
+        let solo = settings(&ui).mix();
+        assert!((solo.brown - 0.4).abs() < 1e-6);
+        assert_eq!(solo.rain, 0.0);
 
-    if selected {
-        queue!(
-            stdout,
-            SetForegroundColor(Color::Yellow),
-            Print(format!("> {:<12}", name))
-        )?;
-    } else {
-        queue!(
-            stdout,
-            SetForegroundColor(Color::White),
-            Print(format!("  {:<12}", name))
-        )?;
+        ui.handle_key(key(KeyCode::Char('s')));
+        assert_eq!(settings(&ui).mix(), rain_and_brown());
+
+        // Soloing a silent source brings it in at full level.
+        ui.mixer_selected = 0;
+        ui.handle_key(key(KeyCode::Char('s')));
+        assert_eq!(settings(&ui).mix(), SourceMix::solo(SoundStyle::White));
     }
 
-    queue!(stdout, Print(" ["), SetForegroundColor(Color::Green))?;
-    for _ in 0..filled {
-        queue!(stdout, Print("#"))?;
+    #[test]
+    fn moving_a_fader_commits_a_pending_solo() {
+        let mut ui = mixer(rain_and_brown());
+        ui.mixer_selected = style_index(SoundStyle::Rain);
+        ui.handle_key(key(KeyCode::Char('s')));
+        ui.handle_key(key(KeyCode::Right));
+        assert_eq!(ui.solo_restore, None);
+
+        ui.handle_key(key(KeyCode::Char('s')));
+        let mix = settings(&ui).mix();
+        assert!((mix.rain - 0.65).abs() < 1e-6);
+        assert_eq!(mix.brown, 0.0);
     }
-    queue!(stdout, SetForegroundColor(Color::DarkGrey))?;
-    for _ in filled..SLIDER_WIDTH {
-        queue!(stdout, Print("-"))?;
+
+    #[test]
+    fn readouts_show_exact_values() {
+        let mut current = AudioSettings {
+            volume: 0.5,
+            ..AudioSettings::default()
+        };
+        assert_eq!(main_readout(current, 0), "Volume 50% = -6.0 dB output gain");
+        current.headroom = true;
+        current.frequency_bands = [1.0; MAX_BANDS];
+        assert_eq!(
+            main_readout(current, 0),
+            "Volume 50% = -6.0 dB output gain, -12.0 dB more for the EQ boosts' headroom"
+        );
+        current.headroom = false;
+        current.frequency_bands = [0.5; MAX_BANDS];
+
+        current.frequency_bands[0] = 1.0;
+        let band = main_readout(current, 1);
+        assert!(band.starts_with("Sub Bass 20-60 Hz: 100% = +12.0 dB; output RMS"));
+
+        current.listening_contour = true;
+        assert!(main_readout(current, 1).contains("(+12.0 dB with contour)"));
+        current.listening_contour = false;
+        current.room_correction_db[2] = -4.5;
+        assert!(main_readout(current, 3).contains("(-4.5 dB with room correction)"));
+
+        assert_eq!(
+            mixer_readout(SoundStyle::Rain, 0.5),
+            "Rain: 50% power = -3.0 dB from full level"
+        );
+        assert_eq!(mixer_readout(SoundStyle::Pink, 0.0), "Pink Noise: silent");
+        assert_eq!(
+            width_readout(0.5),
+            "Stereo width 50%: normal, left/right correlation 0.71"
+        );
+        assert!(width_readout(0.0).contains("mono, left/right correlation 1.00"));
+        assert!(width_readout(1.0).contains("wide"));
+
+        let mut tone = ToneSettings {
+            mode: ToneMode::Binaural,
+            ..ToneSettings::default()
+        };
+        assert_eq!(
+            tone_readout(tone),
+            "Binaural: 198 Hz left, 202 Hz right, 4 Hz beat; needs headphones"
+        );
+        tone.mode = ToneMode::Isochronic;
+        tone.beat_hz = 7.5;
+        assert!(tone_readout(tone).starts_with("Isochronic: 200 Hz pulsed 7.5 times"));
+        assert_eq!(
+            tone_level_readout(tone),
+            "Tone level 10% power = -10.0 dB from a full-level source"
+        );
     }
-    queue!(
-        stdout,
-        SetForegroundColor(Color::White),
-        Print(format!("] {value_label}")),
-        ResetColor
-    )?;
-    Ok(())
-}
 
-struct TerminalSession;
+    #[test]
+    fn level_history_keeps_one_peak_per_second_for_a_minute() {
+        let start = Instant::now();
+        let mut history = LevelHistory::default();
+        assert!(!history.record(0.2, 0.0, start));
+        assert!(!history.record(0.5, 1.5, start + Duration::from_millis(500)));
+        assert!(history.record(0.1, 0.5, start + Duration::from_millis(1_000)));
+        assert_eq!(history.seconds, [(0.5, 1.5)]);
 
-impl TerminalSession {
-    fn enter() -> Result<Self> {
-        terminal::enable_raw_mode()?;
-        if let Err(error) = execute!(io::stdout(), EnterAlternateScreen, cursor::Hide) {
-            let _ = terminal::disable_raw_mode();
-            return Err(error.into());
+        for second in 2..100 {
+            history.record(0.3, 0.0, start + Duration::from_secs(second));
         }
-        Ok(Self)
+        assert_eq!(history.seconds.len(), HISTORY_SECONDS);
+        assert!(history.seconds.iter().all(|second| *second == (0.3, 0.0)));
+        assert_eq!(history.loudest(), 0.3);
+        assert_eq!(history.deepest_reduction(), 0.0);
     }
-}
 
-impl Drop for TerminalSession {
-    fn drop(&mut self) {
-        let _ = terminal::disable_raw_mode();
-        let _ = execute!(io::stdout(), cursor::Show, LeaveAlternateScreen);
+    #[test]
+    fn sparklines_show_level_and_only_real_limiting() {
+        let mut history = LevelHistory::default();
+        history
+            .seconds
+            .extend([(0.0, 0.0), (0.01, 0.0), (0.5, 0.05), (0.8, 0.0), (1.0, 6.0)]);
+        let (level, reduction) = history.sparklines();
+
+        let level: Vec<char> = level.chars().collect();
+        assert_eq!(level[0], ' ');
+        assert!(level[1] < level[2] && level[2] < level[3]);
+        assert_eq!(level[4], '█');
+        // Seconds the limiter left alone, or all but alone, stay blank.
+        assert_eq!(reduction, "    █");
+        assert_eq!(history.deepest_reduction(), 6.0);
     }
-}
 
-// Key handling and slider adjustment are tested directly; the rendering and
-// raw-terminal paths (draw, draw_slider, TerminalSession, run) are exempt as
-// terminal-bound, per the CLAUDE.md decision log.
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::settings::SoundStyle;
+    #[test]
+    fn output_meter_peak_falls_back_and_the_limiter_light_holds() {
+        let start = Instant::now();
+        let mut meter = OutputMeter::default();
+        meter.record(0.5, 0.0, 0.25, start);
+        assert!((meter.peak_db - level_db(0.5)).abs() < 1e-4);
+        assert!((meter.rms_db + 12.04).abs() < 0.01);
+        assert_eq!(meter.limiting(start), None);
 
-    fn ui() -> InteractiveUi {
-        InteractiveUi::new(
-            Arc::new(Mutex::new(AudioSettings::default())),
-            Arc::new(AtomicBool::new(true)),
-        )
+        // Quieter peaks let the mark fall at 20 dB per second.
+        meter.record(0.05, 0.0, 0.02, start + Duration::from_millis(100));
+        assert!((meter.peak_db - (level_db(0.5) - 2.0)).abs() < 1e-3);
+        meter.record(0.0, 0.0, 0.0, start + Duration::from_secs(10));
+        assert_eq!(meter.peak_db, LEVEL_FLOOR_DB);
+        assert_eq!(meter.rms_db, LEVEL_FLOOR_DB);
+
+        // The light shows the deepest reduction and outlasts the transient.
+        let limited = start + Duration::from_secs(20);
+        meter.record(0.89, 4.5, 0.6, limited);
+        meter.record(0.89, 1.0, 0.6, limited + Duration::from_millis(100));
+        assert_eq!(
+            meter.limiting(limited + Duration::from_millis(1_900)),
+            Some(4.5)
+        );
+        assert_eq!(
+            meter.limiting(limited + Duration::from_millis(100) + LIMIT_HOLD),
+            None
+        );
+        // Coming on again, it starts from the new reduction.
+        let again = limited + Duration::from_secs(10);
+        meter.record(0.89, 0.5, 0.6, again);
+        assert_eq!(meter.limiting(again), Some(0.5));
+
+        assert_eq!(meter_cells(LEVEL_FLOOR_DB, LEVEL_FLOOR_DB, 30), (0, None));
+        assert_eq!(meter_cells(-24.0, -6.0, 30), (15, Some(25)));
+        assert_eq!(meter_cells(-3.0, 0.0, 30), (28, Some(29)));
+        assert_eq!(meter_cells(0.0, 0.0, 30), (30, None));
     }
 
-    fn key(code: KeyCode) -> KeyEvent {
-        KeyEvent::new(code, KeyModifiers::NONE)
+    #[test]
+    fn status_line_shows_source_volume_and_meter() {
+        let current = AudioSettings {
+            volume: 0.2,
+            ..AudioSettings::default()
+        };
+        let now = Instant::now();
+        assert_eq!(
+            status_line(current, 0.0, now),
+            "White Noise | vol 20% | [----------] silent"
+        );
+        assert_eq!(
+            status_line(current, 0.5, now),
+            "White Noise | vol 20% | [#########-] -6 dBFS"
+        );
+        assert!(status_line(current, 1.0, now).ends_with("[##########] +0 dBFS"));
+
+        let timed = AudioSettings {
+            sleep_timer: Some(SleepTimer::new(
+                Duration::from_secs(45 * 60),
+                DEFAULT_FADE_OUT,
+                now,
+            )),
+            ..current
+        };
+        assert_eq!(
+            status_line(timed, 0.0, now),
+            "White Noise | vol 20% | sleep 45:00 | [----------] silent"
+        );
+        assert!(
+            status_line(timed, 0.0, now + Duration::from_secs(44 * 60 + 30))
+                .contains("| fading 0:30 |")
+        );
+
+        let paused = AudioSettings {
+            paused: true,
+            ..timed
+        };
+        assert_eq!(
+            status_line(paused, 0.0, now),
+            "White Noise | vol 20% | paused | sleep 45:00 | [----------] silent"
+        );
     }
 
-    fn settings(ui: &InteractiveUi) -> AudioSettings {
-        *ui.settings.lock().unwrap()
+    #[test]
+    fn t_steps_the_sleep_timer_on_any_page_and_shows_time_left() {
+        let mut ui = ui().with_fade_out(Duration::from_secs(120));
+        assert_eq!(
+            timer_line(None, Instant::now()),
+            "Sleep timer: off (T to set)"
+        );
+
+        ui.handle_key(key(KeyCode::Char('t')));
+        let timer = settings(&ui).sleep_timer.unwrap();
+        assert_eq!(timer.fade(), Duration::from_secs(120));
+        assert!(timer.remaining(Instant::now()) > Duration::from_secs(14 * 60));
+
+        ui.handle_key(key(KeyCode::Tab));
+        ui.handle_key(key(KeyCode::Char('T')));
+        let now = Instant::now();
+        let timer = settings(&ui).sleep_timer.unwrap();
+        assert!(timer.remaining(now) > Duration::from_secs(29 * 60));
+        assert_eq!(
+            timer_line(Some(timer), now),
+            "Sleep timer: stopping in 30:00, fading over the last 2:00 (T to change)"
+        );
+        assert_eq!(
+            timer_line(Some(timer), now + Duration::from_secs(29 * 60)),
+            "Sleep timer: fading out, stopping in 1:00"
+        );
+
+        // 45, 60, 90, and 120 minutes, then off.
+        for _ in 0..5 {
+            ui.handle_key(key(KeyCode::Char('t')));
+        }
+        assert_eq!(settings(&ui).sleep_timer, None);
     }
 
     #[test]
-    fn selection_clamps_at_both_ends() {
+    fn space_pauses_and_m_mutes_without_touching_the_volume() {
         let mut ui = ui();
-        ui.handle_key(key(KeyCode::Up));
-        assert_eq!(ui.selected, 0);
+        let volume = settings(&ui).volume;
+        ui.handle_key(key(KeyCode::Char(' ')));
+        assert!(settings(&ui).paused);
+        ui.handle_key(key(KeyCode::Char('m')));
+        assert!(settings(&ui).muted);
+        assert_eq!(
+            status_line(settings(&ui), 0.0, Instant::now()),
+            format!(
+                "White Noise | vol {:.0}% | paused | [----------] silent",
+                volume * 100.0
+            )
+        );
 
-        for _ in 0..FREQUENCY_BANDS.len() + 5 {
-            ui.handle_key(key(KeyCode::Down));
+        ui.handle_key(key(KeyCode::BackTab));
+        ui.handle_key(key(KeyCode::Char(' ')));
+        ui.handle_key(key(KeyCode::Char('M')));
+        assert!(!settings(&ui).paused && !settings(&ui).muted);
+        assert_eq!(settings(&ui).volume, volume);
+    }
+
+    #[test]
+    fn volume_keys_set_the_app_volume_on_any_page() {
+        let mut ui = ui();
+        let media = |code| key(KeyCode::Media(code));
+        for _ in 0..3 {
+            ui.handle_key(media(MediaKeyCode::RaiseVolume));
         }
-        assert_eq!(ui.selected, FREQUENCY_BANDS.len());
+        assert!((settings(&ui).volume - 0.15).abs() < 1e-6);
+        ui.handle_key(key(KeyCode::Tab));
+        ui.handle_key(key(KeyCode::Char(':')));
+        ui.handle_key(media(MediaKeyCode::LowerVolume));
+        assert!((settings(&ui).volume - 0.1).abs() < 1e-6);
+        assert_eq!(ui.prompt.as_deref(), Some(""));
+        ui.handle_key(media(MediaKeyCode::MuteVolume));
+        assert!(settings(&ui).muted);
+
+        // A hearing exam takes the volume keys but not the mute.
+        let mut exam = ui.with_exam_mode();
+        exam.handle_key(media(MediaKeyCode::MuteVolume));
+        exam.handle_key(media(MediaKeyCode::RaiseVolume));
+        assert!(settings(&exam).muted);
+        assert!((settings(&exam).volume - 0.15).abs() < 1e-6);
     }
 
     #[test]
-    fn left_right_adjust_volume_in_steps_and_clamp() {
+    fn m_and_o_mute_and_solo_the_selected_band() {
         let mut ui = ui();
+        ui.handle_key(key(KeyCode::Down));
+        ui.handle_key(key(KeyCode::Down));
         ui.handle_key(key(KeyCode::Right));
-        assert!((settings(&ui).volume - 0.05).abs() < 1e-6);
+        let bands = settings(&ui).frequency_bands;
+        ui.handle_key(key(KeyCode::Char('m')));
+        assert!(settings(&ui).band_overlay.muted[1]);
+        assert!(!settings(&ui).muted);
+        ui.handle_key(key(KeyCode::Down));
+        ui.handle_key(key(KeyCode::Char('O')));
+        assert_eq!(settings(&ui).band_overlay.solo, Some(2));
+        assert_eq!(settings(&ui).frequency_bands, bands);
 
-        for _ in 0..40 {
-            ui.handle_key(key(KeyCode::Right));
-        }
-        assert_eq!(settings(&ui).volume, 1.0);
+        let mut screen = Vec::new();
+        ui.draw_main(&mut screen, settings(&ui)).unwrap();
+        let screen = String::from_utf8_lossy(&screen);
+        assert_eq!(
+            screen
+                .matches(&format!("  {}", text("main.band_solo")))
+                .count(),
+            1
+        );
+        assert_eq!(screen.matches(text("main.band_muted")).count(), 7);
 
-        for _ in 0..40 {
-            ui.handle_key(key(KeyCode::Left));
+        ui.handle_key(key(KeyCode::Char('o')));
+        assert_eq!(settings(&ui).band_overlay.solo, None);
+        ui.handle_key(key(KeyCode::Char('r')));
+        assert_eq!(settings(&ui).band_overlay, BandOverlay::default());
+    }
+
+    #[test]
+    fn b_bypasses_while_it_keeps_repeating() {
+        let mut ui = ui();
+        let start = Instant::now();
+        ui.handle_key(key(KeyCode::Char('b')));
+        assert!(settings(&ui).bypass);
+        // Still inside the wait for the first auto-repeat.
+        assert!(!ui.check_bypass(start + Duration::from_millis(500)));
+        assert!(settings(&ui).bypass);
+
+        ui.handle_key(key(KeyCode::Tab));
+        ui.handle_key(key(KeyCode::Char('B')));
+        let repeat = Instant::now();
+        assert!(!ui.check_bypass(repeat + Duration::from_millis(150)));
+        assert!(ui.check_bypass(repeat + BYPASS_REPEAT_HOLD));
+        assert!(!settings(&ui).bypass);
+        assert!(!ui.check_bypass(repeat + Duration::from_secs(5)));
+
+        // With the prompt open, B is just a letter.
+        ui.handle_key(key(KeyCode::Char(':')));
+        ui.handle_key(key(KeyCode::Char('b')));
+        assert!(!settings(&ui).bypass);
+    }
+
+    fn type_line(ui: &mut InteractiveUi, text: &str) {
+        for character in text.chars() {
+            ui.handle_key(key(KeyCode::Char(character)));
         }
-        assert_eq!(settings(&ui).volume, 0.0);
     }
 
     #[test]
-    fn adjusting_a_band_only_touches_that_band() {
+    fn command_line_runs_completes_and_recalls_commands() {
         let mut ui = ui();
-        ui.handle_key(key(KeyCode::Down));
-        ui.handle_key(key(KeyCode::Right));
+        ui.handle_key(key(KeyCode::Char(':')));
+        type_line(&mut ui, "band a");
+        // Tab completes inside the prompt instead of switching pages.
+        ui.handle_key(key(KeyCode::Tab));
+        assert_eq!(ui.page, Page::Main);
+        assert_eq!(ui.prompt.as_deref(), Some("band air "));
+        type_line(&mut ui, "20");
+        assert!(!ui.handle_key(key(KeyCode::Enter)));
+        assert_eq!(ui.prompt, None);
+        assert_eq!(settings(&ui).frequency_bands[7], 0.2);
+        assert_eq!(ui.prompt_note.as_deref(), Some("Air 20% = -7.2 dB"));
 
-        let current = settings(&ui);
-        assert!((current.frequency_bands[0] - 0.55).abs() < 1e-6);
+        // Keys edit settings again once the prompt is closed, and the note clears.
+        ui.handle_key(key(KeyCode::Char('n')));
+        assert!(settings(&ui).listening_contour);
+        assert_eq!(ui.prompt_note, None);
+
+        ui.handle_key(key(KeyCode::Char(':')));
+        type_line(&mut ui, "timer soon");
+        ui.handle_key(key(KeyCode::Enter));
         assert!(
-            current.frequency_bands[1..]
-                .iter()
-                .all(|value| *value == 0.5)
+            ui.prompt_note
+                .as_deref()
+                .unwrap()
+                .contains("not a duration")
         );
-        assert_eq!(current.volume, 0.0);
+        assert_eq!(settings(&ui).sleep_timer, None);
+
+        ui.handle_key(key(KeyCode::Char(':')));
+        ui.handle_key(key(KeyCode::Up));
+        ui.handle_key(key(KeyCode::Up));
+        assert_eq!(ui.prompt.as_deref(), Some("band air 20"));
+        ui.handle_key(key(KeyCode::Down));
+        ui.handle_key(key(KeyCode::Down));
+        assert_eq!(ui.prompt.as_deref(), Some(""));
+        ui.handle_key(key(KeyCode::Esc));
+        assert_eq!(ui.prompt, None);
+        assert!(ui.running.load(Ordering::Relaxed));
+
+        ui.handle_key(key(KeyCode::Char(':')));
+        type_line(&mut ui, "quit");
+        assert!(ui.handle_key(key(KeyCode::Enter)));
     }
 
     #[test]
-    fn s_cycles_the_sound_style() {
+    fn presets_are_saved_with_p_and_switched_from_their_page() {
         let mut ui = ui();
-        ui.handle_key(key(KeyCode::Char('s')));
-        assert_eq!(settings(&ui).sound_style, SoundStyle::Pink);
-        ui.handle_key(key(KeyCode::Char('S')));
-        assert_eq!(settings(&ui).sound_style, SoundStyle::Brown);
+        ui.presets_dir = scratch_presets("switch");
+        ui.handle_key(key(KeyCode::Char('p')));
+        assert_eq!(ui.prompt.as_deref(), Some("preset save "));
+        type_line(&mut ui, "focus");
+        ui.handle_key(key(KeyCode::Enter));
+        assert_eq!(ui.prompt_note.as_deref(), Some("saved preset focus"));
+
+        ui.replace_mix(SourceMix::solo(SoundStyle::Rain));
+        ui.handle_key(key(KeyCode::Char(':')));
+        type_line(&mut ui, "preset save sleep");
+        ui.handle_key(key(KeyCode::Enter));
+
+        ui.show_page(Page::Presets);
+        assert_eq!(ui.page, Page::Presets);
+        assert_eq!(ui.presets, ["focus", "sleep"]);
+
+        // A muted source is forgotten once a preset replaces the mix.
+        ui.muted_levels[0] = Some(1.0);
+        ui.handle_key(key(KeyCode::Enter));
+        assert_eq!(settings(&ui).mix(), AudioSettings::default().mix());
+        assert_eq!(ui.muted_levels, [None; SoundStyle::ALL.len()]);
+
+        // D needs a second press; any other key in between cancels it.
+        ui.handle_key(key(KeyCode::Down));
+        ui.handle_key(key(KeyCode::Char('d')));
+        ui.handle_key(key(KeyCode::Down));
+        ui.handle_key(key(KeyCode::Char('d')));
+        assert_eq!(ui.presets, ["focus", "sleep"]);
+        ui.handle_key(key(KeyCode::Char('d')));
+        assert_eq!(ui.presets, ["focus"]);
+        assert_eq!(ui.preset_selected, 0);
+        assert_eq!(ui.prompt_note.as_deref(), Some("deleted preset sleep"));
+
+        std::fs::remove_dir_all(ui.presets_dir.parent().unwrap()).unwrap();
     }
 
     #[test]
-    fn s_from_a_custom_mix_solos_the_source_after_the_dominant_one() {
+    fn gallery_presets_are_browsed_and_downloaded_from_the_presets_page() {
         let mut ui = ui();
-        {
-            let mut locked = ui.settings.lock().unwrap();
-            locked.set_mix(SourceMix {
-                white: 0.1,
-                pink: 0.0,
-                brown: 0.7,
-                rain: 0.2,
-            });
-        }
-        ui.handle_key(key(KeyCode::Char('s')));
+        ui.presets_dir = scratch_presets("gallery");
+        ui.show_page(Page::Presets);
+        ui.handle_key(key(KeyCode::Char('g')));
+        assert!(
+            ui.prompt_note
+                .as_deref()
+                .unwrap()
+                .contains("no preset gallery")
+        );
+        assert!(ui.gallery_request.is_none());
 
-        let current = settings(&ui);
-        assert_eq!(current.mix(), SourceMix::solo(SoundStyle::Rain));
-        assert_eq!(current.sound_style, SoundStyle::Rain);
+        // The answers arrive as the background requests would send them.
+        let answer = |ui: &mut InteractiveUi, reply| {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            sender.send(reply).unwrap();
+            ui.gallery_request = Some(receiver);
+            assert!(ui.check_gallery());
+            assert!(ui.gallery_request.is_none());
+        };
+        let entries = vec![
+            gallery::Entry {
+                id: "b2".to_owned(),
+                name: "deep sleep".to_owned(),
+                description: "brown, low and slow".to_owned(),
+                downloads: 120,
+            },
+            gallery::Entry {
+                id: "a1".to_owned(),
+                name: "desk fan".to_owned(),
+                description: String::new(),
+                downloads: 3,
+            },
+        ];
+        ui.gallery_url = Some("https://presets.example.org/api".to_owned());
+        answer(&mut ui, gallery::Reply::List(Ok(entries.clone())));
+        let mut screen = Vec::new();
+        ui.draw_presets(&mut screen).unwrap();
+        let screen = String::from_utf8_lossy(&screen);
+        assert!(screen.contains("Preset gallery at https://presets.example.org/api"));
+        assert!(screen.contains("deep sleep: brown, low and slow (120 downloads)"));
+        ui.handle_key(key(KeyCode::Down));
+        ui.handle_key(key(KeyCode::Down));
+        assert_eq!(ui.gallery_selected, 1);
+        ui.handle_key(key(KeyCode::Up));
+
+        // A download is saved under the listing's name and selected.
+        answer(
+            &mut ui,
+            gallery::Reply::Preset(Ok("volume = 0.4\n".to_owned())),
+        );
+        assert!(ui.gallery.is_none());
+        assert_eq!(ui.presets, ["deep sleep"]);
+        assert_eq!(
+            ui.prompt_note.as_deref(),
+            Some("downloaded preset deep sleep from the gallery")
+        );
+
+        // A second copy is refused, and Esc goes back to the saved presets.
+        answer(&mut ui, gallery::Reply::List(Ok(entries)));
+        answer(
+            &mut ui,
+            gallery::Reply::Preset(Ok("volume = 0.9\n".to_owned())),
+        );
+        assert!(ui.prompt_note.as_deref().unwrap().contains("already saved"));
+        answer(
+            &mut ui,
+            gallery::Reply::List(Err(anyhow::anyhow!("failed to reach the gallery"))),
+        );
+        assert_eq!(
+            ui.prompt_note.as_deref(),
+            Some("failed to reach the gallery")
+        );
+        assert!(ui.gallery.is_some());
+        assert!(!ui.handle_key(key(KeyCode::Esc)));
+        assert!(ui.gallery.is_none());
+
+        std::fs::remove_dir_all(ui.presets_dir.parent().unwrap()).unwrap();
     }
 
     #[test]
-    fn n_toggles_the_listening_contour() {
+    fn scenes_are_made_edited_and_written_from_their_page() {
         let mut ui = ui();
-        ui.handle_key(key(KeyCode::Char('n')));
-        assert!(settings(&ui).listening_contour);
-        ui.handle_key(key(KeyCode::Char('N')));
-        assert!(!settings(&ui).listening_contour);
+        ui.presets_dir = scratch_presets("scenes");
+        let mut mix = SourceMix::solo(SoundStyle::Rain);
+        mix.set_level(SoundStyle::Brown, 0.5);
+        ui.replace_mix(mix);
+        ui.show_page(Page::Scenes);
+        assert!(ui.scenes.is_empty());
+
+        // A new scene starts from the playing sources once it has a name.
+        ui.handle_key(key(KeyCode::Char('a')));
+        type_line(&mut ui, "../x");
+        ui.handle_key(key(KeyCode::Enter));
+        assert!(ui.scene_editor.as_ref().unwrap().naming);
+        for _ in 0..4 {
+            ui.handle_key(key(KeyCode::Backspace));
+        }
+        type_line(&mut ui, "attic");
+        ui.handle_key(key(KeyCode::Enter));
+        let editor = ui.scene_editor.as_ref().unwrap();
+        assert!(!editor.naming);
+        assert_eq!(
+            editor.layers,
+            [
+                Layer::new(SoundStyle::Brown, 0.5),
+                Layer::new(SoundStyle::Rain, 1.0)
+            ]
+        );
+
+        // Edits play at once: the first row's level, then the second row's
+        // low-pass and swell.
+        ui.handle_key(key(KeyCode::Tab));
+        ui.handle_key(key(KeyCode::Left));
+        assert_eq!(settings(&ui).mix().level(SoundStyle::Brown), 0.45);
+        ui.handle_key(key(KeyCode::Down));
+        ui.handle_key(key(KeyCode::Tab));
+        ui.handle_key(key(KeyCode::Tab));
+        ui.handle_key(key(KeyCode::Left));
+        ui.handle_key(key(KeyCode::Left));
+        ui.handle_key(key(KeyCode::Tab));
+        ui.handle_key(key(KeyCode::Right));
+        let rain = settings(&ui).layers[style_index(SoundStyle::Rain)];
+        assert_eq!(rain.low_pass_hz, Some(16_000.0));
+        assert_eq!(rain.modulation.depth, 0.1);
+
+        // A adds the first unused source, its source steps past the taken
+        // ones, and X removes it.
+        ui.handle_key(key(KeyCode::Char('a')));
+        assert_eq!(ui.scene_editor.as_ref().unwrap().row, 2);
+        ui.handle_key(key(KeyCode::BackTab));
+        ui.handle_key(key(KeyCode::BackTab));
+        ui.handle_key(key(KeyCode::BackTab));
+        ui.handle_key(key(KeyCode::BackTab));
+        ui.handle_key(key(KeyCode::Right));
+        let added = ui.scene_editor.as_ref().unwrap().layers[2];
+        assert_eq!(added, Layer::new(SoundStyle::Pink, 0.5));
+        ui.handle_key(key(KeyCode::Right));
+        let added = ui.scene_editor.as_ref().unwrap().layers[2];
+        assert_eq!(added.source, SoundStyle::Blue);
+        ui.handle_key(key(KeyCode::Char('x')));
+        assert_eq!(settings(&ui).mix().level(SoundStyle::Blue), 0.0);
+
+        // W writes the file and lists the scene; loading it plays the same.
+        ui.handle_key(key(KeyCode::Char('w')));
+        assert!(ui.scene_editor.is_none());
+        assert_eq!(ui.prompt_note.as_deref(), Some("saved scene attic"));
+        assert_eq!(ui.scenes, ["attic"]);
+        let saved = load_scene(&ui.scenes_dir(), "attic").unwrap();
+        assert_eq!(saved.layers.len(), 2);
+        assert_eq!(saved.layers[1].low_pass_hz, Some(16_000.0));
+        let played = settings(&ui);
+        ui.replace_mix(SourceMix::default());
+        ui.handle_key(key(KeyCode::Enter));
+        assert_eq!(settings(&ui).mix(), played.mix());
+        assert_eq!(settings(&ui).layers, played.layers);
+
+        // Abandoning an edit asks once, then puts the sound back.
+        ui.handle_key(key(KeyCode::Char('e')));
+        ui.handle_key(key(KeyCode::Tab));
+        ui.handle_key(key(KeyCode::Right));
+        ui.handle_key(key(KeyCode::Esc));
+        assert!(ui.scene_editor.is_some());
+        ui.handle_key(key(KeyCode::Esc));
+        assert!(ui.scene_editor.is_none());
+        assert_eq!(settings(&ui).mix(), played.mix());
+
+        std::fs::remove_dir_all(ui.presets_dir.parent().unwrap()).unwrap();
     }
 
     #[test]
-    fn r_resets_every_band_but_not_the_volume() {
+    fn d_picks_an_output_device_and_switches_to_it() {
         let mut ui = ui();
+        ui.handle_key(key(KeyCode::Char('d')));
+        assert!(ui.device_picker.is_none());
+        assert_eq!(ui.prompt_note.as_deref(), Some(text("devices.unavailable")));
+
+        let mut ui = ui.with_output(OutputHandle::fake(&["Speakers", "USB Headphones"]));
+        ui.handle_key(key(KeyCode::Char('d')));
+        let picker = ui.device_picker.as_ref().unwrap();
+        assert_eq!(picker.names, ["Speakers", "USB Headphones"]);
+        assert_eq!(picker.selected, 0);
+        // The picker holds the keys, so Space does not pause.
+        ui.handle_key(key(KeyCode::Char(' ')));
+        assert!(!settings(&ui).paused);
+        ui.handle_key(key(KeyCode::Down));
+        ui.handle_key(key(KeyCode::Down));
+        ui.handle_key(key(KeyCode::Enter));
+        assert!(ui.device_picker.is_none());
+        assert_eq!(ui.prompt_note.as_deref(), Some("playing on USB Headphones"));
+        assert_eq!(ui.output.as_ref().unwrap().device_name(), "USB Headphones");
+
+        ui.handle_key(key(KeyCode::Char('d')));
+        assert_eq!(ui.device_picker.as_ref().unwrap().selected, 1);
+        ui.handle_key(key(KeyCode::Esc));
+        assert!(ui.device_picker.is_none());
+        assert!(ui.running.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn exam_mode_accepts_only_volume_and_quit() {
+        let mut ui = ui().with_exam_mode();
+        ui.handle_key(key(KeyCode::Up));
+        ui.handle_key(key(KeyCode::Right));
+        assert!((settings(&ui).volume - 0.1).abs() < 1e-6);
+        ui.handle_key(key(KeyCode::Down));
+        assert!((settings(&ui).volume - 0.05).abs() < 1e-6);
+
+        for code in [
+            KeyCode::Tab,
+            KeyCode::Char('s'),
+            KeyCode::Char('n'),
+            KeyCode::Char('m'),
+        ] {
+            assert!(!ui.handle_key(key(code)));
+        }
         {
             let mut locked = ui.settings.lock().unwrap();
-            locked.frequency_bands = [0.9; FREQUENCY_BANDS.len()];
-            locked.volume = 0.7;
+            locked.frequency_bands = [0.9; MAX_BANDS];
         }
         ui.handle_key(key(KeyCode::Char('r')));
-
+        assert_eq!(ui.page, Page::Main);
         let current = settings(&ui);
-        assert_eq!(current.frequency_bands, [0.5; FREQUENCY_BANDS.len()]);
-        assert_eq!(current.volume, 0.7);
+        assert_eq!(current.mix(), SourceMix::default());
+        assert!(!current.listening_contour);
+        assert_eq!(current.frequency_bands, [0.9; MAX_BANDS]);
+
+        assert!(ui.handle_key(key(KeyCode::Char('q'))));
+        assert!(ui.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)));
+    }
+
+    #[test]
+    fn the_interface_dims_overnight_and_under_a_sleep_timer() {
+        let time = |hour, minute| ClockTime::new(hour, minute).unwrap();
+        let mut ui = ui().with_theme(ThemeSettings {
+            dim_from: Some(time(22, 0)),
+            dim_with_timer: true,
+            ..ThemeSettings::default()
+        });
+        let day = Theme::new(Palette::Color);
+        assert!(!ui.check_dimming(time(21, 59)));
+        assert_eq!(ui.theme, day);
+        assert!(ui.check_dimming(time(22, 0)));
+        assert_eq!(ui.theme, day.dimmed());
+        assert!(!ui.check_dimming(time(3, 0)));
+        assert!(ui.check_dimming(time(7, 0)));
+        assert_eq!(ui.theme, day);
+
+        ui.handle_key(key(KeyCode::Char('t')));
+        assert!(ui.sleep_timer().is_some());
+        assert!(ui.check_dimming(time(12, 0)));
+        assert_eq!(ui.theme, day.dimmed());
+    }
+
+    #[test]
+    fn the_header_warns_when_the_night_would_pass_a_full_dose() {
+        let exposure = Arc::new(Exposure::default());
+        let ui = ui().with_exposure(Arc::clone(&exposure));
+        let header = |ui: &InteractiveUi| {
+            let mut screen = Vec::new();
+            ui.draw_header(&mut screen, settings(ui)).unwrap();
+            String::from_utf8(screen).unwrap()
+        };
+        exposure.set(0.4, 0.9);
+        assert!(!header(&ui).contains("dose"));
+        exposure.set(0.4, 1.25);
+        assert!(
+            header(&ui).contains(&text_with("page.exposure", &[("percent", &125)])),
+            "{}",
+            header(&ui)
+        );
+    }
+
+    #[test]
+    fn u_shows_the_remote_as_a_qr_code_until_a_key() {
+        let mut ui = ui();
+        ui.handle_key(key(KeyCode::Char('u')));
+        assert!(!ui.showing_remote);
+        assert_eq!(ui.prompt_note.as_deref(), Some(text("remote.off")));
+
+        let url = "http://192.168.1.23:8080/#kU3mQ9xTa2Lw";
+        let mut ui = ui.with_remote_url(Some(url.to_owned()));
+        ui.handle_key(key(KeyCode::Char('U')));
+        assert!(ui.showing_remote);
+        let mut screen = Vec::new();
+        draw_remote(&mut screen, &ui.theme, url, Layout::STANDARD).unwrap();
+        let screen = String::from_utf8(screen).unwrap();
+        assert!(screen.contains(url) && screen.contains('█'));
+        assert!(!screen.contains(text("remote.loopback")));
+
+        // The closing key does nothing else, even Q.
+        assert!(!ui.handle_key(key(KeyCode::Char('q'))));
+        assert!(!ui.showing_remote);
+
+        let mut screen = Vec::new();
+        let small = Layout {
+            columns: 30,
+            rows: 10,
+        };
+        draw_remote(
+            &mut screen,
+            &ui.theme,
+            "http://127.0.0.1:8080/#kU3mQ9xTa2Lw",
+            small,
+        )
+        .unwrap();
+        let screen = String::from_utf8(screen).unwrap();
+        assert!(screen.contains(text("remote.loopback")));
+        assert!(!screen.contains('█'));
+    }
+
+    #[test]
+    fn a_kiosk_ignores_every_key_but_ctrl_q() {
+        let mut ui = ui().with_kiosk();
+        let before = settings(&ui);
+        let control = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        for event in [
+            key(KeyCode::Tab),
+            key(KeyCode::Right),
+            key(KeyCode::Char(' ')),
+            key(KeyCode::Char('s')),
+            key(KeyCode::Char(':')),
+            key(KeyCode::Char('q')),
+            key(KeyCode::Esc),
+            key(KeyCode::Media(MediaKeyCode::RaiseVolume)),
+            key(KeyCode::Media(MediaKeyCode::MuteVolume)),
+            control('c'),
+        ] {
+            assert!(!ui.handle_key(event), "{event:?}");
+        }
+        assert_eq!(ui.page, Page::Main);
+        assert!(ui.prompt.is_none());
+        assert_eq!(settings(&ui), before);
+        assert!(ui.handle_key(control('q')));
+
+        let mut screen = Vec::new();
+        ui.draw_header(&mut screen, before).unwrap();
+        ui.draw_main(&mut screen, before).unwrap();
+        let screen = String::from_utf8(screen).unwrap();
+        assert!(screen.contains(text("page.kiosk")));
+        assert!(screen.contains(text("main.kiosk_controls")));
+        assert!(!screen.contains(text("main.controls")));
     }
 
     #[test]
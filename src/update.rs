@@ -0,0 +1,197 @@
+//! `whitenoise update --check`: asks the release feed whether a newer
+//! version is out, and with `--download` fetches this platform's build into
+//! a staging folder. Nothing is installed or replaced, and nothing runs
+//! unless the command is given, so a noise box never phones home by itself.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+
+/// The project's latest published release, as GitHub serves it.
+pub const RELEASE_FEED: &str =
+    "https://api.github.com/repos/clearcmos/whitenoise-cli/releases/latest";
+// A feed that stalls should fail the check, not hang a cron job.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct Release {
+    tag_name: String,
+    #[serde(default)]
+    html_url: String,
+    #[serde(default)]
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// `v1.2.3` or `1.2.3`, with any `-rc.1` or `+build` suffix dropped, as
+/// numbers that compare in release order. Missing parts are 0.
+fn parse_version(tag: &str) -> Option<[u64; 3]> {
+    let core = tag.trim().trim_start_matches('v');
+    let core = core.split(['-', '+']).next()?;
+    let mut parts = [0; 3];
+    for (index, part) in core.split('.').enumerate() {
+        *parts.get_mut(index)? = part.parse().ok()?;
+    }
+    Some(parts)
+}
+
+/// The asset built for this OS and architecture: its name holds both, with
+/// macOS also known as darwin or apple.
+fn pick_asset<'a>(assets: &'a [Asset], os: &str, arch: &str) -> Option<&'a Asset> {
+    let os_names: &[&str] = match os {
+        "macos" => &["macos", "darwin", "apple"],
+        other => &[other],
+    };
+    assets.iter().find(|asset| {
+        let name = asset.name.to_lowercase();
+        name.contains(arch) && os_names.iter().any(|os| name.contains(os))
+    })
+}
+
+/// Reports the latest release from `feed` against this build, and with
+/// `staging` downloads its asset for this platform there.
+pub fn run(feed: &str, staging: Option<&Path>) -> Result<()> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .build()
+        .into();
+    let release: Release = {
+        let mut response = agent
+            .get(feed)
+            .header("Accept", "application/vnd.github+json")
+            .call()
+            .with_context(|| format!("failed to reach the release feed {feed}"))?;
+        let body = response
+            .body_mut()
+            .read_to_string()
+            .context("failed to read the release feed")?;
+        serde_json::from_str(&body).context("the release feed's answer is not a release")?
+    };
+
+    let current = env!("CARGO_PKG_VERSION");
+    let Some(latest) = parse_version(&release.tag_name) else {
+        bail!(
+            "the latest release's tag '{}' is not a version",
+            release.tag_name
+        );
+    };
+    if parse_version(current).is_some_and(|current| current >= latest) {
+        println!("whitenoise {current} is up to date");
+        return Ok(());
+    }
+    println!(
+        "whitenoise {} is out (this is {current}): {}",
+        release.tag_name.trim_start_matches('v'),
+        release.html_url
+    );
+    let Some(staging) = staging else {
+        return Ok(());
+    };
+
+    let Some(asset) = pick_asset(
+        &release.assets,
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+    ) else {
+        let names: Vec<&str> = release
+            .assets
+            .iter()
+            .map(|asset| asset.name.as_str())
+            .collect();
+        bail!(
+            "the release has no build for {} {}; it has: {}",
+            std::env::consts::OS,
+            std::env::consts::ARCH,
+            if names.is_empty() {
+                "nothing".to_owned()
+            } else {
+                names.join(", ")
+            }
+        );
+    };
+    let path = download(&agent, asset, staging)?;
+    println!(
+        "Downloaded {} to {}; it is not installed, so check it and put it in place yourself",
+        asset.name,
+        path.display()
+    );
+    Ok(())
+}
+
+/// Writes beside the final name first, so an interrupted download never
+/// leaves a file that looks complete.
+fn download(agent: &ureq::Agent, asset: &Asset, staging: &Path) -> Result<PathBuf> {
+    // The name comes from the feed; only its last component is used.
+    let Some(file_name) = Path::new(&asset.name).file_name() else {
+        bail!("the release asset '{}' has no file name", asset.name);
+    };
+    fs::create_dir_all(staging)
+        .with_context(|| format!("failed to create {}", staging.display()))?;
+    let path = staging.join(file_name);
+    let partial = path.with_extension("part");
+    let mut response = agent
+        .get(&asset.browser_download_url)
+        .call()
+        .with_context(|| format!("failed to download {}", asset.browser_download_url))?;
+    let mut file = File::create(&partial)
+        .with_context(|| format!("failed to create {}", partial.display()))?;
+    io::copy(&mut response.body_mut().as_reader(), &mut file)
+        .with_context(|| format!("failed to download {}", asset.name))?;
+    file.sync_all()?;
+    fs::rename(&partial, &path).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_compare_in_release_order() {
+        assert_eq!(parse_version("v0.4.0"), Some([0, 4, 0]));
+        assert_eq!(parse_version("1.2"), Some([1, 2, 0]));
+        assert_eq!(parse_version("v1.0.0-rc.1"), Some([1, 0, 0]));
+        assert_eq!(parse_version("nightly"), None);
+        assert_eq!(parse_version("1.2.3.4"), None);
+        assert!(parse_version("v0.10.0") > parse_version("0.9.9"));
+        assert!(parse_version(env!("CARGO_PKG_VERSION")).is_some());
+    }
+
+    #[test]
+    fn the_feed_names_a_build_for_each_platform() {
+        let release: Release = serde_json::from_str(
+            r#"{
+                "tag_name": "v0.4.0",
+                "html_url": "https://github.com/clearcmos/whitenoise-cli/releases/tag/v0.4.0",
+                "draft": false,
+                "assets": [
+                    {"name": "whitenoise-x86_64-linux.tar.gz", "browser_download_url": "https://example.com/a"},
+                    {"name": "whitenoise-aarch64-apple-darwin.tar.gz", "browser_download_url": "https://example.com/b"},
+                    {"name": "SHA256SUMS", "browser_download_url": "https://example.com/c"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(release.tag_name, "v0.4.0");
+        let pick =
+            |os, arch| pick_asset(&release.assets, os, arch).map(|asset| asset.name.as_str());
+        assert_eq!(
+            pick("linux", "x86_64"),
+            Some("whitenoise-x86_64-linux.tar.gz")
+        );
+        assert_eq!(
+            pick("macos", "aarch64"),
+            Some("whitenoise-aarch64-apple-darwin.tar.gz")
+        );
+        assert_eq!(pick("windows", "x86_64"), None);
+    }
+}
@@ -0,0 +1,155 @@
+//! `--silence-watchdog`: a thread that notices the output staying silent
+//! while the settings say it should play, as a filter that blew up or a
+//! broken sample loop would leave it, and has the output keeper replace the
+//! engine, as it does after a panic, instead of leaving the rest of the
+//! night silent. `AudioMonitor::silence_restarts` counts the restarts for
+//! the interface, the status line, and the log.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::audio::AudioMonitor;
+use crate::clock;
+use crate::settings::{AudioSettings, SharedSettings, SoundStyle};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// How long the output has to stay silent before the engine is replaced:
+/// long past a fade or a source change, short enough to matter overnight.
+pub const SILENCE_LIMIT: Duration = Duration::from_secs(20);
+/// -140 dBFS. Any source at any volume the slider reaches plays well above
+/// it, and the engine's silence is exact zeros.
+const SILENT_RMS: f32 = 1e-7;
+
+/// How long the output has been silent while it should have played.
+#[derive(Debug, Default)]
+pub struct SilenceWatch {
+    silent_since: Option<Instant>,
+}
+
+impl SilenceWatch {
+    /// Takes one reading: whether the settings expect sound and the
+    /// output's RMS at `now`. True once the silence has lasted
+    /// `SILENCE_LIMIT`, which starts the count over. A non-finite RMS
+    /// counts as silent.
+    pub fn check(&mut self, expected: bool, rms: f32, now: Instant) -> bool {
+        if !expected || rms > SILENT_RMS {
+            self.silent_since = None;
+            return false;
+        }
+        let since = *self.silent_since.get_or_insert(now);
+        if now.duration_since(since) < SILENCE_LIMIT {
+            return false;
+        }
+        self.silent_since = None;
+        true
+    }
+}
+
+/// Whether `settings` should be heard at `now`: a volume above zero, not
+/// paused or muted, no sleep timer or schedule fade that has reached
+/// silence, and some source with a level whose scene layer is not timed
+/// out, the Sample source only with a sample loaded.
+pub fn expects_sound(settings: &AudioSettings, sample_loaded: bool, now: Instant) -> bool {
+    let mix = settings.mix();
+    let source = SoundStyle::ALL
+        .iter()
+        .zip(&settings.layers)
+        .any(|(style, layer)| {
+            mix.level(*style) > 0.0
+                && !layer.silenced
+                && (*style != SoundStyle::Sample || sample_loaded)
+        });
+    let faded = settings.sleep_timer.map_or(1.0, |timer| timer.gain(now))
+        * settings.schedule_fade.map_or(1.0, |fade| fade.gain(now));
+    source && settings.volume > 0.0 && !settings.paused && !settings.muted && faded > 0.0
+}
+
+/// Checks the output once a second until `running` clears, reporting a
+/// silence to `monitor` for the keeper to act on.
+pub fn watch(
+    settings: Arc<SharedSettings>,
+    monitor: Arc<AudioMonitor>,
+    running: Arc<AtomicBool>,
+    sample_loaded: bool,
+) {
+    std::thread::spawn(move || {
+        let mut watch = SilenceWatch::default();
+        while running.load(Ordering::Relaxed) {
+            std::thread::sleep(CHECK_INTERVAL);
+            let current = *settings
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            // A lost output is the keeper's to reopen, and its RMS is stale.
+            let expected =
+                !monitor.output_lost() && expects_sound(&current, sample_loaded, clock::now());
+            // Time spent suspended is not time spent silent.
+            if watch.check(expected, monitor.rms(), Instant::now()) {
+                monitor.report_silence();
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::SourceMix;
+    use crate::timer::SleepTimer;
+
+    #[test]
+    fn only_a_long_unexpected_silence_trips_the_watch() {
+        let start = Instant::now();
+        let at = |seconds: u64| start + Duration::from_secs(seconds);
+        let mut watch = SilenceWatch::default();
+        assert!(!watch.check(true, 0.0, at(0)));
+        assert!(!watch.check(true, 0.0, at(19)));
+        // Sound, or a silence the settings ask for, starts the count over.
+        assert!(!watch.check(true, 0.05, at(20)));
+        assert!(!watch.check(true, 0.0, at(21)));
+        assert!(!watch.check(false, 0.0, at(41)));
+        assert!(!watch.check(true, f32::NAN, at(42)));
+        assert!(watch.check(true, f32::NAN, at(62)));
+        // The count starts over after a restart.
+        assert!(!watch.check(true, 0.0, at(63)));
+    }
+
+    #[test]
+    fn sound_is_expected_only_when_something_should_play() {
+        let now = Instant::now();
+        let mut settings = AudioSettings {
+            volume: 0.3,
+            ..AudioSettings::default()
+        };
+        settings.set_mix(SourceMix::solo(SoundStyle::Pink));
+        assert!(expects_sound(&settings, false, now));
+        for quiet in [
+            AudioSettings {
+                paused: true,
+                ..settings
+            },
+            AudioSettings {
+                muted: true,
+                ..settings
+            },
+            AudioSettings {
+                volume: 0.0,
+                ..settings
+            },
+            AudioSettings {
+                sleep_timer: Some(SleepTimer::new(Duration::ZERO, Duration::ZERO, now)),
+                ..settings
+            },
+        ] {
+            assert!(!expects_sound(&quiet, false, now));
+        }
+
+        let mut timed_out = settings;
+        timed_out.layers[1].silenced = true;
+        assert!(!expects_sound(&timed_out, false, now));
+
+        settings.set_mix(SourceMix::solo(SoundStyle::Sample));
+        assert!(!expects_sound(&settings, false, now));
+        assert!(expects_sound(&settings, true, now));
+    }
+}
@@ -0,0 +1,590 @@
+//! Remote control over HTTP, built with the `web` feature: `--listen`
+//! serves a small page with sliders and a JSON API beside it. Changes go
+//! through `command.rs` under the settings lock, like the control socket's,
+//! so the interface, `ctl`, and a browser can all change the sound at once;
+//! each sees the others' changes the next time it reads the settings.
+//!
+//! A bare port is served on this machine only; reaching it from other
+//! machines takes `--listen-lan`. Every change must carry the session's
+//! token in an `X-Whitenoise-Token` header, and a request a browser sent
+//! from another site's page is refused. The token is `--listen-token`'s, or
+//! a new one each run, and the address the player prints holds it after
+//! `#`, where the page reads it.
+//!
+//! | Request | Body | Does |
+//! |---|---|---|
+//! | `GET /api/state` | | volume, pause, mix, bands, timer, presets |
+//! | `PUT /api/volume` | `{"value": 40}` | sets the volume in percent |
+//! | `PUT /api/bands/NAME` | `{"value": 50}` | sets a band slider; 50 is 0 dB |
+//! | `PUT /api/style` | `{"value": "rain"}` | plays one source |
+//! | `PUT /api/paused` | `{"value": true}` | pauses or resumes |
+//! | `PUT /api/preset` | `{"value": "Sleep"}` | loads a preset |
+//! | `POST /api/command` | `timer 45m` | runs any `:` prompt command |
+
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anyhow::{Result, anyhow, bail};
+use rand::RngExt;
+use rand::distr::Alphanumeric;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::clock;
+use crate::command::Command;
+use crate::preset::{apply_preset, list_presets, load_preset};
+use crate::settings::{SettingsGuard, SharedSettings, SoundStyle, bands, slider_to_db};
+use crate::timer::format_remaining;
+
+const PAGE: &str = include_str!("../assets/remote.html");
+// Request bodies are a few bytes; anything much longer is refused unread.
+const MAX_BODY: u64 = 4096;
+// The header a change carries the token in.
+const TOKEN_HEADER: &str = "X-Whitenoise-Token";
+// Long enough not to be guessed, and short enough that the address with
+// it still fits the QR code a version 3 symbol draws.
+const TOKEN_LENGTH: usize = 12;
+
+/// Where `--listen` serves: a bare port, or a full address.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Listen {
+    Port(u16),
+    Address(SocketAddr),
+}
+
+impl Listen {
+    /// A bare port is served on this machine only, or on every interface
+    /// with `lan`. An address other machines can reach needs `lan` too.
+    pub fn address(self, lan: bool) -> Result<SocketAddr> {
+        match self {
+            Self::Port(port) if lan => Ok(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port))),
+            Self::Port(port) => Ok(SocketAddr::from((Ipv4Addr::LOCALHOST, port))),
+            Self::Address(address) if lan || address.ip().is_loopback() => Ok(address),
+            Self::Address(address) => {
+                bail!("{address} can be reached from other machines; add --listen-lan to serve it")
+            }
+        }
+    }
+}
+
+impl FromStr for Listen {
+    type Err = String;
+
+    fn from_str(text: &str) -> std::result::Result<Self, String> {
+        if let Ok(port) = text.parse() {
+            return Ok(Self::Port(port));
+        }
+        text.parse().map(Self::Address).map_err(|_| {
+            format!("'{text}' is neither a port nor an address such as 127.0.0.1:8080")
+        })
+    }
+}
+
+/// A new token for a session that was not given one.
+pub fn new_token() -> String {
+    rand::rng()
+        .sample_iter(Alphanumeric)
+        .take(TOKEN_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+/// `--listen-token`'s value. It follows `#` in the printed address, so it
+/// keeps to characters a URL carries unchanged.
+pub fn parse_token(text: &str) -> std::result::Result<String, String> {
+    if text.len() < 8
+        || !text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~'))
+    {
+        return Err("a token is at least 8 letters, digits, '-', '.', '_', or '~'".to_owned());
+    }
+    Ok(text.to_owned())
+}
+
+/// What a running server needs from the session, as for the control socket.
+pub struct Session {
+    pub settings: Arc<SharedSettings>,
+    pub running: Arc<AtomicBool>,
+    pub fade_out: Duration,
+    pub presets: PathBuf,
+    pub token: String,
+}
+
+/// Stops the server when playback ends.
+pub struct WebServer {
+    server: Arc<Server>,
+    token: String,
+}
+
+impl WebServer {
+    pub fn address(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    /// The address to open the remote at, with the token after `#` so it
+    /// never reaches a server log. A server listening on every interface is
+    /// given this machine's LAN address, so a phone can reach it.
+    pub fn url(&self) -> Option<String> {
+        let mut address = self.address()?;
+        if address.ip().is_unspecified() {
+            if let Some(ip) = lan_ip() {
+                address.set_ip(ip);
+            }
+        }
+        Some(format!("http://{address}/#{}", self.token))
+    }
+}
+
+/// The address this machine would send from to reach the internet. A UDP
+/// `connect` only picks the route; nothing is sent.
+fn lan_ip() -> Option<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    // TEST-NET-1, reserved for documentation.
+    socket.connect("192.0.2.1:9").ok()?;
+    Some(socket.local_addr().ok()?.ip()).filter(|ip| !ip.is_unspecified())
+}
+
+impl Drop for WebServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+    }
+}
+
+/// Listens on `address` and answers requests on a background thread, one
+/// at a time.
+pub fn serve(address: SocketAddr, session: Session) -> Result<WebServer> {
+    let server = Arc::new(
+        Server::http(address).map_err(|error| anyhow!("failed to listen on {address}: {error}"))?,
+    );
+    let token = session.token.clone();
+    std::thread::spawn({
+        let server = Arc::clone(&server);
+        move || {
+            for mut request in server.incoming_requests() {
+                let mut body = String::new();
+                let reply = if let Some(refused) =
+                    refusal(request.method(), request.headers(), &session.token)
+                {
+                    refused
+                } else if request
+                    .as_reader()
+                    .take(MAX_BODY)
+                    .read_to_string(&mut body)
+                    .is_err()
+                {
+                    Reply::error(400, "the body is not UTF-8 text".to_owned())
+                } else {
+                    route(request.method(), request.url(), &body, &session)
+                };
+                let _ = request.respond(reply.into_response());
+            }
+        }
+    });
+    Ok(WebServer { server, token })
+}
+
+/// Why a request may not reach `route`, if it may not. A browser names the
+/// site whose page sent a request in `Origin`, so one naming any other than
+/// this server is refused; a change must also carry the token, which a
+/// page elsewhere cannot read even when it reaches this server by name.
+fn refusal(method: &Method, headers: &[Header], token: &str) -> Option<Reply> {
+    let header = |name: &'static str| {
+        headers
+            .iter()
+            .find(|header| header.field.equiv(name))
+            .map(|header| header.value.as_str())
+    };
+    if let Some(origin) = header("Origin") {
+        let own = header("Host").map(|host| format!("http://{host}"));
+        if own.as_deref() != Some(origin) {
+            return Some(Reply::error(
+                403,
+                format!("requests from {origin} are not accepted"),
+            ));
+        }
+    }
+    let changes = !matches!(method, Method::Get | Method::Head);
+    if changes && !header(TOKEN_HEADER).is_some_and(|sent| same(sent, token)) {
+        return Some(Reply::error(
+            401,
+            format!(
+                "changes need the remote's token in {TOKEN_HEADER}; open the address the player printed"
+            ),
+        ));
+    }
+    None
+}
+
+/// Compares without stopping at the first difference, so the time an answer
+/// takes does not tell how much of a guess was right.
+fn same(sent: &str, token: &str) -> bool {
+    sent.len() == token.len()
+        && sent
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+#[derive(Debug, PartialEq)]
+struct Reply {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Reply {
+    fn json(value: Value) -> Self {
+        Self {
+            status: 200,
+            content_type: "application/json",
+            body: value.to_string(),
+        }
+    }
+
+    fn error(status: u16, error: String) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: json!({ "error": error }).to_string(),
+        }
+    }
+
+    fn into_response(self) -> Response<std::io::Cursor<Vec<u8>>> {
+        let content_type = Header::from_bytes("Content-Type", self.content_type)
+            .expect("content types are valid header values");
+        Response::from_string(self.body)
+            .with_status_code(self.status)
+            .with_header(content_type)
+    }
+}
+
+#[derive(Deserialize)]
+struct SetValue {
+    value: Value,
+}
+
+/// Answers one request. Changes reply with the new state, so the page
+/// redraws from what was actually applied.
+fn route(method: &Method, url: &str, body: &str, session: &Session) -> Reply {
+    let path = url.split('?').next().unwrap_or_default();
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match (method, segments.as_slice()) {
+        (Method::Get, [""]) => Reply {
+            status: 200,
+            content_type: "text/html; charset=utf-8",
+            body: PAGE.to_owned(),
+        },
+        (Method::Get, ["api", "state"]) => Reply::json(state(session)),
+        (Method::Put, ["api", "paused"]) => match value(body) {
+            Ok(Value::Bool(paused)) => {
+                lock(&session.settings).paused = paused;
+                Reply::json(state(session))
+            }
+            Ok(_) => Reply::error(400, "paused takes true or false".to_owned()),
+            Err(error) => error,
+        },
+        (Method::Put, ["api", "volume"]) => set(session, body, |value| format!("volume {value}")),
+        (Method::Put, ["api", "bands", band]) => {
+            set(session, body, |value| format!("band {band} {value}"))
+        }
+        (Method::Put, ["api", "style"]) => set(session, body, |value| format!("style {value}")),
+        (Method::Put, ["api", "preset"]) => {
+            set(session, body, |value| format!("preset load {value}"))
+        }
+        (Method::Post, ["api", "command"]) => match run(session, body.trim()) {
+            Ok(message) => Reply::json(json!({ "message": message })),
+            Err(error) => Reply::error(400, error),
+        },
+        (
+            _,
+            [""]
+            | [
+                "api",
+                "state" | "paused" | "volume" | "bands" | "style" | "preset" | "command",
+                ..,
+            ],
+        ) => Reply::error(405, format!("{method} is not allowed on {path}")),
+        _ => Reply::error(404, format!("nothing at {path}")),
+    }
+}
+
+/// Reads `{"value": ...}`, the body of every PUT.
+fn value(body: &str) -> std::result::Result<Value, Reply> {
+    serde_json::from_str::<SetValue>(body)
+        .map(|set| set.value)
+        .map_err(|_| Reply::error(400, r#"expected a body like {"value": 40}"#.to_owned()))
+}
+
+/// Runs the command `line` makes of the body's value.
+fn set(session: &Session, body: &str, line: impl Fn(&str) -> String) -> Reply {
+    let value = match value(body) {
+        Ok(Value::String(text)) => text,
+        Ok(Value::Number(number)) => number.to_string(),
+        Ok(_) => return Reply::error(400, "value must be a string or a number".to_owned()),
+        Err(error) => return error,
+    };
+    match run(session, &line(&value)) {
+        Ok(_) => Reply::json(state(session)),
+        Err(error) => Reply::error(400, error),
+    }
+}
+
+/// Applies one `:` prompt command line. Loading a preset reports a missing
+/// one in its message rather than failing, so it is loaded here instead.
+fn run(session: &Session, line: &str) -> std::result::Result<String, String> {
+    match Command::parse(line)? {
+        Command::Quit => {
+            session.running.store(false, Ordering::Relaxed);
+            Ok("quitting".to_owned())
+        }
+        Command::LoadPreset(name) => {
+            let preset =
+                load_preset(&session.presets, &name).map_err(|error| format!("{error:#}"))?;
+            apply_preset(preset, &mut lock(&session.settings));
+            Ok(format!("loaded preset {}", name.trim()))
+        }
+        command => Ok(command.apply(
+            &mut lock(&session.settings),
+            session.fade_out,
+            clock::now(),
+            &session.presets,
+        )),
+    }
+}
+
+fn presets(session: &Session) -> Vec<String> {
+    list_presets(&session.presets).unwrap_or_default()
+}
+
+fn state(session: &Session) -> Value {
+    let settings = *lock(&session.settings);
+    let mix = settings.mix();
+    let bands: Vec<Value> = bands()
+        .iter()
+        .zip(settings.frequency_bands)
+        .map(|(band, value)| {
+            json!({
+                "name": band.name,
+                "label": band.label(),
+                "value": (value * 100.0).round(),
+                "db": (slider_to_db(value) * 10.0).round() / 10.0,
+            })
+        })
+        .collect();
+    json!({
+        "volume": (settings.volume * 100.0).round(),
+        "paused": settings.paused,
+        "muted": settings.muted,
+        "mix": mix.describe(),
+        "style": mix.solo_style().map(style_name),
+        "styles": SoundStyle::ALL.map(style_name),
+        "bands": bands,
+        "timer": settings
+            .sleep_timer
+            .map(|timer| format_remaining(timer.remaining(clock::now()))),
+        "presets": presets(session),
+    })
+}
+
+fn style_name(style: SoundStyle) -> Value {
+    serde_json::to_value(style).unwrap_or_default()
+}
+
+fn lock(settings: &SharedSettings) -> SettingsGuard<'_> {
+    settings
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{AudioSettings, SourceMix};
+    use std::io::Write;
+    use std::net::TcpStream;
+
+    fn session() -> Session {
+        Session {
+            settings: Arc::new(SharedSettings::new(AudioSettings {
+                volume: 0.2,
+                ..AudioSettings::default()
+            })),
+            running: Arc::new(AtomicBool::new(true)),
+            fade_out: Duration::from_secs(60),
+            presets: std::env::temp_dir().join("whitenoise-web-test-presets"),
+            token: "test-token".to_owned(),
+        }
+    }
+
+    fn body(reply: &Reply) -> Value {
+        serde_json::from_str(&reply.body).unwrap()
+    }
+
+    #[test]
+    fn puts_change_the_sound_and_reply_with_the_new_state() {
+        let session = session();
+        let reply = route(&Method::Put, "/api/volume", r#"{"value": 45}"#, &session);
+        assert_eq!(reply.status, 200);
+        assert_eq!(body(&reply)["volume"], 45.0);
+
+        let reply = route(
+            &Method::Put,
+            "/api/bands/air",
+            r#"{"value": "20"}"#,
+            &session,
+        );
+        assert_eq!(body(&reply)["bands"][7]["value"], 20.0);
+        route(&Method::Put, "/api/style", r#"{"value": "rain"}"#, &session);
+        let reply = route(&Method::Put, "/api/paused", r#"{"value": true}"#, &session);
+        let state = body(&reply);
+        assert_eq!(state["style"], "rain");
+        assert_eq!(state["paused"], true);
+        assert_eq!(state["styles"][0], "white");
+
+        let reply = route(&Method::Post, "/api/command", "timer 45m\n", &session);
+        assert_eq!(reply.status, 200, "{}", reply.body);
+        let settings = *session.settings.lock().unwrap();
+        assert_eq!(settings.volume, 0.45);
+        assert_eq!(settings.frequency_bands[7], 0.2);
+        assert_eq!(settings.mix(), SourceMix::solo(SoundStyle::Rain));
+        assert!(settings.sleep_timer.is_some());
+
+        route(&Method::Post, "/api/command", "quit", &session);
+        assert!(!session.running.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn bad_requests_are_refused_without_touching_the_settings() {
+        let session = session();
+        for (method, url, body, status) in [
+            (Method::Put, "/api/volume", "40", 400),
+            (Method::Put, "/api/volume", r#"{"value": 140}"#, 400),
+            (Method::Put, "/api/volume", r#"{"value": [40]}"#, 400),
+            (Method::Put, "/api/bands/treble", r#"{"value": 40}"#, 400),
+            (Method::Put, "/api/style", r#"{"value": "thunder"}"#, 400),
+            (Method::Put, "/api/paused", r#"{"value": "yes"}"#, 400),
+            (Method::Put, "/api/preset", r#"{"value": "Missing"}"#, 400),
+            (Method::Post, "/api/command", "launch", 400),
+            (Method::Delete, "/api/volume", "", 405),
+            (Method::Post, "/", "", 405),
+            (Method::Get, "/favicon.ico", "", 404),
+        ] {
+            let reply = route(&method, url, body, &session);
+            assert_eq!(reply.status, status, "{method} {url} {body}");
+            assert!(body_has_error(&reply), "{method} {url}");
+        }
+        assert_eq!(*session.settings.lock().unwrap(), {
+            AudioSettings {
+                volume: 0.2,
+                ..AudioSettings::default()
+            }
+        });
+    }
+
+    fn body_has_error(reply: &Reply) -> bool {
+        body(reply)["error"].is_string()
+    }
+
+    #[test]
+    fn the_page_and_the_api_are_served_over_http() {
+        let session = session();
+        let settings = Arc::clone(&session.settings);
+        let server = serve("127.0.0.1:0".parse().unwrap(), session).unwrap();
+        let address = server.address().unwrap();
+        let request = |text: &str| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(text.as_bytes()).unwrap();
+            let mut reply = String::new();
+            stream.read_to_string(&mut reply).unwrap();
+            reply
+        };
+
+        let page = request("GET / HTTP/1.0\r\n\r\n");
+        assert!(
+            page.starts_with("HTTP/1.") && page.contains(" 200 "),
+            "{page}"
+        );
+        assert!(page.contains("text/html") && page.contains("<input"));
+        let put = "PUT /api/volume HTTP/1.0\r\nContent-Length: 13\r\n";
+        let reply = request(&format!("{put}\r\n{{\"value\": 30}}"));
+        assert!(reply.contains(" 401 "), "{reply}");
+        let reply = request(&format!(
+            "{put}X-Whitenoise-Token: test-token\r\n\r\n{{\"value\": 30}}"
+        ));
+        assert!(reply.contains("\"volume\":30.0"), "{reply}");
+        assert_eq!(settings.lock().unwrap().volume, 0.3);
+        assert_eq!(
+            server.url().unwrap(),
+            format!("http://{address}/#test-token")
+        );
+        drop(server);
+    }
+
+    #[test]
+    fn changes_need_the_token_and_the_servers_own_origin() {
+        let headers = |pairs: &[(&str, &str)]| -> Vec<Header> {
+            pairs
+                .iter()
+                .map(|(field, value)| Header::from_bytes(*field, *value).unwrap())
+                .collect()
+        };
+        let refused = |method, pairs: &[(&str, &str)]| {
+            refusal(&method, &headers(pairs), "test-token").map(|reply| reply.status)
+        };
+        let host = ("Host", "192.168.1.23:8080");
+        let token = ("X-Whitenoise-Token", "test-token");
+
+        assert_eq!(refused(Method::Get, &[host]), None);
+        assert_eq!(refused(Method::Put, &[host]), Some(401));
+        assert_eq!(
+            refused(Method::Post, &[host, ("X-Whitenoise-Token", "test-tokem")]),
+            Some(401)
+        );
+        assert_eq!(refused(Method::Put, &[host, token]), None);
+        assert_eq!(
+            refused(
+                Method::Put,
+                &[host, token, ("Origin", "http://192.168.1.23:8080")]
+            ),
+            None
+        );
+        for origin in ["http://evil.example", "null", "https://192.168.1.23:8080"] {
+            assert_eq!(
+                refused(Method::Put, &[host, token, ("Origin", origin)]),
+                Some(403),
+                "{origin}"
+            );
+            assert_eq!(refused(Method::Get, &[host, ("Origin", origin)]), Some(403));
+        }
+    }
+
+    #[test]
+    fn a_bare_port_stays_on_this_machine_unless_the_lan_is_asked_for() {
+        let port: Listen = "8080".parse().unwrap();
+        assert_eq!(port.address(false).unwrap().to_string(), "127.0.0.1:8080");
+        assert_eq!(port.address(true).unwrap().to_string(), "0.0.0.0:8080");
+        let everywhere: Listen = "0.0.0.0:8080".parse().unwrap();
+        assert!(everywhere.address(false).is_err());
+        assert!(everywhere.address(true).is_ok());
+        let loopback: Listen = "[::1]:8080".parse().unwrap();
+        assert!(loopback.address(false).is_ok());
+        assert!("localhost:8080".parse::<Listen>().is_err());
+
+        assert!(parse_token("kitchen-radio").is_ok());
+        assert!(parse_token("short").is_err());
+        assert!(parse_token("has space in it").is_err());
+        assert!(parse_token("hash#in-it").is_err());
+        let token = new_token();
+        assert_eq!(token.len(), TOKEN_LENGTH);
+        assert!(parse_token(&token).is_ok());
+        assert_ne!(token, new_token());
+    }
+}
@@ -0,0 +1,146 @@
+//! Runs the whole program headless on its virtual output, drives it through
+//! the control socket, and checks what it played.
+
+#![cfg(unix)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output};
+use std::time::{Duration, Instant};
+
+const PROGRAM: &str = env!("CARGO_BIN_EXE_whitenoise");
+const WINDOW_SECONDS: f32 = 0.1;
+
+/// A settings folder of its own, removed afterwards.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("whitenoise-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+
+    /// The player, with this folder's settings, socket, and output file.
+    fn play(&self, arguments: &[&str]) -> Child {
+        Command::new(PROGRAM)
+            .args(self.common())
+            .arg("--virtual-output")
+            .arg(self.path("output.wav"))
+            .arg("--non-interactive")
+            .args(arguments)
+            .spawn()
+            .unwrap()
+    }
+
+    fn ctl(&self, command: &[&str]) -> Output {
+        Command::new(PROGRAM)
+            .args(self.common())
+            .arg("ctl")
+            .args(command)
+            .output()
+            .unwrap()
+    }
+
+    fn common(&self) -> Vec<PathBuf> {
+        vec![
+            "--config".into(),
+            self.path("settings.toml"),
+            "--socket".into(),
+            self.path("control.sock"),
+        ]
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn wait_for(what: &str, mut ready: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(20);
+    while !ready() {
+        assert!(Instant::now() < deadline, "timed out waiting for {what}");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// The left channel's RMS over each WINDOW_SECONDS of the file.
+fn window_rms(path: &Path) -> Vec<f32> {
+    let reader = hound::WavReader::open(path).unwrap();
+    let spec = reader.spec();
+    assert_eq!((spec.channels, spec.bits_per_sample), (2, 32));
+    let left: Vec<f32> = reader
+        .into_samples::<f32>()
+        .step_by(2)
+        .map(Result::unwrap)
+        .collect();
+    let window = (spec.sample_rate as f32 * WINDOW_SECONDS) as usize;
+    left.chunks_exact(window)
+        .map(|chunk| {
+            (chunk.iter().map(|sample| sample * sample).sum::<f32>() / window as f32).sqrt()
+        })
+        .collect()
+}
+
+#[test]
+fn commands_and_the_sleep_timer_reach_the_rendered_sound() {
+    let scratch = Scratch::new("virtual-output");
+    let mut player = scratch.play(&[
+        "--style",
+        "white",
+        "--volume",
+        "50",
+        "--seed",
+        "7",
+        "--fade-out",
+        "1s",
+    ]);
+    wait_for("the control socket", || {
+        scratch.path("control.sock").exists()
+    });
+
+    std::thread::sleep(Duration::from_millis(1_000));
+    let reply = scratch.ctl(&["volume", "25"]);
+    assert!(reply.status.success(), "{reply:?}");
+    assert_eq!(String::from_utf8_lossy(&reply.stdout).trim(), "volume 25%");
+    std::thread::sleep(Duration::from_millis(1_000));
+    let status = scratch.ctl(&["status"]);
+    assert!(
+        String::from_utf8_lossy(&status.stdout).contains("25%"),
+        "{status:?}"
+    );
+    // Two seconds, the last one fading out, and then the player stops.
+    assert!(scratch.ctl(&["timer", "2s"]).status.success());
+    let refused = scratch.ctl(&["volume", "loud"]);
+    assert!(!refused.status.success());
+
+    let mut exit = None;
+    wait_for("the timer to stop the player", || {
+        exit = player.try_wait().unwrap();
+        exit.is_some()
+    });
+    assert!(exit.unwrap().success());
+
+    let rms = window_rms(&scratch.path("output.wav"));
+    // About a second at 50%, a second at 25%, and two more to the end.
+    assert!(rms.len() >= 35, "{} windows", rms.len());
+    let loud = rms[5];
+    assert!(loud > 0.05, "{rms:?}");
+    let halved = rms
+        .iter()
+        .filter(|level| (0.45..0.55).contains(&(**level / loud)))
+        .count();
+    assert!(halved >= 8, "{rms:?}");
+    // The fade takes the sound down to silence before the file ends.
+    assert!(rms[rms.len() - 1] < loud * 0.05, "{rms:?}");
+
+    // The volume set through the socket was saved at exit.
+    let saved = std::fs::read_to_string(scratch.path("settings.toml")).unwrap();
+    assert!(saved.contains("volume = 0.25"), "{saved}");
+}